@@ -7,7 +7,11 @@
 //! Benchmark code of garbling / evaluating using Nigel's circuits.
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use fancy_garbling::{circuit::Circuit, classic::garble};
+use fancy_garbling::{
+    circuit::{self, Circuit, RandomCircuitConfig},
+    classic::garble,
+};
+use scuttlebutt::Block;
 use std::time::Duration;
 
 fn circuit(fname: &str) -> Circuit {
@@ -68,10 +72,22 @@ fn bench_eval_sha_256(c: &mut Criterion) {
     });
 }
 
+fn bench_garble_random(c: &mut Criterion) {
+    let config = RandomCircuitConfig {
+        ngates: 10_000,
+        noutputs: 16,
+        ..Default::default()
+    };
+    let circ = circuit::random(&config, Block::default()).unwrap();
+    c.bench_function("garble::random", move |bench| {
+        bench.iter(|| garble(&circ));
+    });
+}
+
 criterion_group! {
     name = parsing;
     config = Criterion::default().warm_up_time(Duration::from_millis(100));
-    targets = bench_garble_aes, bench_eval_aes, bench_garble_sha_1, bench_eval_sha_1, bench_garble_sha_256, bench_eval_sha_256
+    targets = bench_garble_aes, bench_eval_aes, bench_garble_sha_1, bench_eval_sha_1, bench_garble_sha_256, bench_eval_sha_256, bench_garble_random
 }
 
 criterion_main!(parsing);
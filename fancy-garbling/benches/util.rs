@@ -103,6 +103,40 @@ fn u128_from_bits(c: &mut Criterion, n: usize) {
     });
 }
 
+fn field_mul(c: &mut Criterion, p: u16, k: u8) {
+    c.bench_function(&format!("util::field_mul (k={})", k), move |b| {
+        let rng = &mut rand::thread_rng();
+        let x = rng.gen::<u16>() % (1 << k);
+        let y = rng.gen::<u16>() % (1 << k);
+        b.iter(|| {
+            let z = util::field_mul(x, y, p, k);
+            criterion::black_box(z);
+        });
+    });
+    c.bench_function(&format!("util::field_mul_ct (k={})", k), move |b| {
+        let rng = &mut rand::thread_rng();
+        let x = rng.gen::<u16>() % (1 << k);
+        let y = rng.gen::<u16>() % (1 << k);
+        b.iter(|| {
+            let z = util::field_mul_ct(x, y, p, k);
+            criterion::black_box(z);
+        });
+    });
+    c.bench_function(&format!("util::field_cmul_eq (k={})", k), move |b| {
+        let rng = &mut rand::thread_rng();
+        let mut xs = (0..32).map(|_| rng.gen::<u16>() % (1 << k)).collect::<Vec<_>>();
+        let y = rng.gen::<u16>() % (1 << k);
+        b.iter(|| {
+            util::field_cmul_eq(&mut xs, y, p, k);
+        });
+    });
+}
+
+fn bench_field_mul(c: &mut Criterion) {
+    field_mul(c, 0b10011, 4);
+    field_mul(c, 0b100011011, 8);
+}
+
 fn bench_base_q_add_eq(c: &mut Criterion) {
     base_q_add_eq(c, 2);
     base_q_add_eq(c, 3);
@@ -137,7 +171,7 @@ criterion_group! {
     config = Criterion::default().warm_up_time(Duration::from_millis(100));
     targets = bench_tweak, bench_tweak2, bench_output_tweak, bench_base_q_add_eq,
               bench_as_base_q_u128, bench_from_base_q, bench_u128_to_bits,
-              bench_u128_from_bits,
+              bench_u128_from_bits, bench_field_mul,
 }
 
 criterion_main!(util);
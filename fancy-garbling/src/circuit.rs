@@ -9,12 +9,31 @@
 
 use crate::{
     dummy::{Dummy, DummyVal},
-    errors::{CircuitBuilderError, DummyError, FancyError},
-    fancy::{BinaryBundle, CrtBundle, Fancy, FancyInput, HasModulus},
+    errors::{CircuitBuilderError, DummyError, FancyError, GateContext},
+    fancy::{BinaryBundle, CrtBundle, Fancy, FancyBranch, HasModulus},
     wire::Modulus
 };
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
 use itertools::Itertools;
-use std::collections::HashMap;
+
+#[cfg(feature = "std")]
+mod dot;
+mod equiv;
+mod optimize;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod random;
+#[cfg(feature = "std")]
+mod serialize;
+mod stats;
+pub use equiv::{Counterexample, EquivStrategy};
+pub use random::{random, GateWeights, RandomCircuitConfig};
+pub use stats::{CircuitStats, GateKind};
 
 /// The index and modulus of a gate in a circuit.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -24,8 +43,8 @@ pub struct CircuitRef {
     pub(crate) modulus: Modulus,
 }
 
-impl std::fmt::Display for CircuitRef {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for CircuitRef {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "[{} | {}]", self.ix, self.modulus)
     }
 }
@@ -47,6 +66,7 @@ pub struct Circuit {
     pub(crate) const_refs: Vec<CircuitRef>,
     pub(crate) output_refs: Vec<CircuitRef>,
     pub(crate) num_nonfree_gates: usize,
+    pub(crate) names: BTreeMap<usize, String>,
 }
 
 /// The most basic types of computation supported by fancy garbling.
@@ -63,7 +83,7 @@ pub(crate) enum Gate {
         id: usize,
     },
     Constant {
-        val: u16,
+        val: u64,
     },
     Add {
         xref: CircuitRef,
@@ -77,7 +97,7 @@ pub(crate) enum Gate {
     },
     Cmul {
         xref: CircuitRef,
-        c: u16,
+        c: u64,
         out: Option<usize>,
     },
     Mul {
@@ -94,8 +114,8 @@ pub(crate) enum Gate {
     },
 }
 
-impl std::fmt::Display for Gate {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Gate {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             Gate::GarblerInput { id } => write!(f, "GarblerInput {}", id),
             Gate::EvaluatorInput { id } => write!(f, "EvaluatorInput {}", id),
@@ -116,6 +136,67 @@ impl std::fmt::Display for Gate {
     }
 }
 
+/// Progress reporting and cooperative cancellation for `Circuit::eval_monitored`.
+///
+/// Every `interval` gates, and once all gates are done, the monitor calls its
+/// progress callback with the number of gates done and the total, then asks its
+/// cancellation check whether to abort.
+pub struct EvalMonitor<'a> {
+    interval: usize,
+    next: usize,
+    on_progress: Option<Box<dyn FnMut(usize, usize) + 'a>>,
+    is_cancelled: Option<Box<dyn FnMut() -> bool + 'a>>,
+}
+
+impl<'a> EvalMonitor<'a> {
+    /// Make a monitor which does nothing.
+    pub fn new() -> Self {
+        EvalMonitor {
+            interval: 0,
+            next: 0,
+            on_progress: None,
+            is_cancelled: None,
+        }
+    }
+
+    /// Make a monitor which is invoked every `interval` gates.
+    pub fn every(interval: usize) -> Self {
+        EvalMonitor {
+            interval,
+            next: interval,
+            ..Self::new()
+        }
+    }
+
+    /// Call `f(gates_done, gates_total)` whenever the monitor is invoked.
+    pub fn on_progress<P: FnMut(usize, usize) + 'a>(mut self, f: P) -> Self {
+        self.on_progress = Some(Box::new(f));
+        self
+    }
+
+    /// Abort evaluation with `FancyError::Cancelled` as soon as `f` returns `true`.
+    pub fn cancel_when<P: FnMut() -> bool + 'a>(mut self, f: P) -> Self {
+        self.is_cancelled = Some(Box::new(f));
+        self
+    }
+
+    fn tick(&mut self, done: usize, total: usize) -> Result<(), FancyError> {
+        if done != total && (self.interval == 0 || done < self.next) {
+            return Ok(());
+        }
+        self.next = done + self.interval;
+        if let Some(f) = self.on_progress.as_mut() {
+            f(done, total);
+        }
+        if let Some(f) = self.is_cancelled.as_mut() {
+            if f() {
+                return Err(FancyError::Cancelled { gates_done: done });
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Circuit {
     /// Make a new `Circuit` object.
     pub fn new(ngates: Option<usize>) -> Circuit {
@@ -128,9 +209,29 @@ impl Circuit {
             output_refs: Vec::new(),
             gate_moduli: Vec::new(),
             num_nonfree_gates: 0,
+            names: BTreeMap::new(),
         }
     }
 
+    /// The name given to the wire `r`, if any.
+    pub fn wire_name(&self, r: &CircuitRef) -> Option<&str> {
+        self.names.get(&r.ix).map(String::as_str)
+    }
+
+    // The error for reading wire `ix` before it is set, naming the wire if
+    // possible.
+    fn uninitialized(&self, ix: usize) -> FancyError {
+        match self.names.get(&ix) {
+            Some(name) => FancyError::UninitializedWire(name.clone()),
+            None => FancyError::UninitializedValue,
+        }
+    }
+
+    // The value of wire `r` in `cache`.
+    pub(crate) fn cached<'a, T>(&self, cache: &'a [Option<T>], r: &CircuitRef) -> Result<&'a T, FancyError> {
+        cache[r.ix].as_ref().ok_or_else(|| self.uninitialized(r.ix))
+    }
+
     /// Evaluate the circuit using fancy object `f`.
     pub fn eval<F: Fancy>(
         &self,
@@ -138,92 +239,129 @@ impl Circuit {
         garbler_inputs: &[F::Item],
         evaluator_inputs: &[F::Item],
     ) -> Result<Option<Vec<u16>>, F::Error> {
-        let mut cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
-        for (i, gate) in self.gates.iter().enumerate() {
-            let q = self.modulus(i);
-            let (zref_, val) = match *gate {
-                Gate::GarblerInput { id } => (None, garbler_inputs[id].clone()),
-                Gate::EvaluatorInput { id } => {
-                    assert!(
-                        id < evaluator_inputs.len(),
-                        "id={} ev_inps.len()={}",
-                        id,
-                        evaluator_inputs.len()
-                    );
-                    (None, evaluator_inputs[id].clone())
-                }
-                Gate::Constant { val } => (None, f.constant(val, &q)?),
-                Gate::Add { xref, yref, out } => (
-                    out,
-                    f.add(
-                        cache[xref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        cache[yref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                    )?,
-                ),
-                Gate::Sub { xref, yref, out } => (
-                    out,
-                    f.sub(
-                        cache[xref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        cache[yref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                    )?,
-                ),
-                Gate::Cmul { xref, c, out } => (
-                    out,
-                    f.cmul(
-                        cache[xref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        c,
-                    )?,
-                ),
-                Gate::Proj {
-                    xref, ref tt, out, ..
-                } => (
-                    out,
-                    f.proj(
-                        cache[xref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        &q,
-                        Some(tt.to_vec()),
-                    )?,
-                ),
-                Gate::Mul {
-                    xref, yref, out, ..
-                } => (
-                    out,
-                    f.mul(
-                        cache[xref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        cache[yref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                    )?,
-                ),
-            };
-            cache[zref_.unwrap_or(i)] = Some(val);
-        }
+        self.eval_monitored(f, garbler_inputs, evaluator_inputs, &mut EvalMonitor::new())
+    }
 
+    /// Evaluate the circuit using fancy object `f`, reporting progress to and
+    /// checking for cancellation with `monitor`.
+    ///
+    /// When used for streaming garbling, both parties should install monitors
+    /// with the same interval and cancel at the same gate, since a party which
+    /// keeps going will block waiting on its peer.
+    pub fn eval_monitored<F: Fancy>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        monitor: &mut EvalMonitor,
+    ) -> Result<Option<Vec<u16>>, F::Error> {
+        let cache = self.eval_gates(f, garbler_inputs, evaluator_inputs, monitor)?;
         let mut outputs = Vec::with_capacity(self.output_refs.len());
         for r in self.output_refs.iter() {
-            let r = cache[r.ix]
-                .as_ref()
-                .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?;
+            let r = self.cached(&cache, r)?;
             let out = f.output(r)?;
             outputs.push(out);
         }
         Ok(outputs.into_iter().collect())
     }
 
+    /// Evaluate the circuit using fancy object `f`, with outputs which may not
+    /// fit in a `u16`, as needed for `ZqBig` outputs.
+    pub fn eval_big<F: Fancy>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Option<Vec<u64>>, F::Error> {
+        let cache = self.eval_gates(f, garbler_inputs, evaluator_inputs, &mut EvalMonitor::new())?;
+        let mut outputs = Vec::with_capacity(self.output_refs.len());
+        for r in self.output_refs.iter() {
+            let r = self.cached(&cache, r)?;
+            outputs.push(f.output_big(r)?);
+        }
+        Ok(outputs.into_iter().collect())
+    }
+
+    /// Evaluate all gates of the circuit, returning the value of every wire.
+    fn eval_gates<F: Fancy>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        monitor: &mut EvalMonitor,
+    ) -> Result<Vec<Option<F::Item>>, F::Error> {
+        let ngates = self.gates.len();
+        let mut cache: Vec<Option<F::Item>> = vec![None; ngates];
+        for i in 0..ngates {
+            monitor.tick(i, ngates)?;
+            let (zref, val) = self.eval_gate(f, i, &cache, garbler_inputs, evaluator_inputs)?;
+            cache[zref] = Some(val);
+        }
+        monitor.tick(ngates, ngates)?;
+        Ok(cache)
+    }
+
+    /// Evaluate gate `i` on the wire values in `cache`, returning the index of
+    /// the wire it defines along with its value. Errors record the gate, see
+    /// `GateContext`.
+    pub(crate) fn eval_gate<F: Fancy>(
+        &self,
+        f: &mut F,
+        i: usize,
+        cache: &[Option<F::Item>],
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<(usize, F::Item), F::Error> {
+        self.apply_gate(f, i, cache, garbler_inputs, evaluator_inputs)
+            .map_err(|e| e.at_gate(i))
+    }
+
+    fn apply_gate<F: Fancy>(
+        &self,
+        f: &mut F,
+        i: usize,
+        cache: &[Option<F::Item>],
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<(usize, F::Item), F::Error> {
+        let q = self.modulus(i);
+        let (zref_, val) = match self.gates[i] {
+            Gate::GarblerInput { id } => (None, garbler_inputs[id].clone()),
+            Gate::EvaluatorInput { id } => {
+                assert!(
+                    id < evaluator_inputs.len(),
+                    "id={} ev_inps.len()={}",
+                    id,
+                    evaluator_inputs.len()
+                );
+                (None, evaluator_inputs[id].clone())
+            }
+            Gate::Constant { val } => (None, f.constant_big(val, &q)?),
+            Gate::Add { xref, yref, out } => (
+                out,
+                f.add(self.cached(cache, &xref)?, self.cached(cache, &yref)?)?,
+            ),
+            Gate::Sub { xref, yref, out } => (
+                out,
+                f.sub(self.cached(cache, &xref)?, self.cached(cache, &yref)?)?,
+            ),
+            Gate::Cmul { xref, c, out } => (out, f.cmul_big(self.cached(cache, &xref)?, c)?),
+            Gate::Proj {
+                xref, ref tt, out, ..
+            } => (
+                out,
+                f.proj(self.cached(cache, &xref)?, &q, Some(tt.to_vec()))?,
+            ),
+            Gate::Mul {
+                xref, yref, out, ..
+            } => (
+                out,
+                f.mul(self.cached(cache, &xref)?, self.cached(cache, &yref)?)?,
+            ),
+        };
+        Ok((zref_.unwrap_or(i), val))
+    }
+
     /// Evaluate the circuit in plaintext.
     pub fn eval_plain(
         &self,
@@ -256,24 +394,73 @@ impl Circuit {
         Ok(outputs.expect("dummy will always return Some(u16) output"))
     }
 
-    /// Print circuit info.
-    pub fn print_info(&self) -> Result<(), DummyError> {
-        let mut informer = crate::informer::Informer::new(Dummy::new());
+    /// Evaluate the circuit in plaintext, with values which may not fit in a
+    /// `u16`, as needed for `ZqBig` wires.
+    pub fn eval_plain_big(
+        &self,
+        garbler_inputs: &[u64],
+        evaluator_inputs: &[u64],
+    ) -> Result<Vec<u64>, DummyError> {
+        if garbler_inputs.len() != self.garbler_input_refs.len() {
+            return Err(DummyError::NotEnoughGarblerInputs);
+        }
+
+        if evaluator_inputs.len() != self.evaluator_input_refs.len() {
+            return Err(DummyError::NotEnoughEvaluatorInputs);
+        }
+
+        let gb = garbler_inputs
+            .iter()
+            .zip(self.garbler_input_refs.iter())
+            .map(|(x, r)| DummyVal::new_big(*x, r.modulus()))
+            .collect_vec();
+        let ev = evaluator_inputs
+            .iter()
+            .zip(self.evaluator_input_refs.iter())
+            .map(|(x, r)| DummyVal::new_big(*x, r.modulus()))
+            .collect_vec();
+
+        let outputs = self.eval_big(&mut Dummy::new(), &gb, &ev)?;
+        Ok(outputs.expect("dummy will always return Some(u64) output"))
+    }
+
+    /// Compute the informer statistics of the circuit, along with the names of
+    /// its named wires.
+    #[cfg(feature = "std")]
+    pub fn info(&self) -> Result<crate::informer::InformerStats, DummyError> {
+        use crate::fancy::FancyInput;
+
+        let zeros = vec![0; self.evaluator_input_refs.len()];
+        let dummy = Dummy::with_inputs(&[], &zeros);
+        let mut informer = crate::informer::Informer::new(dummy);
 
         // encode inputs as InformerVals
         let gb = self
             .garbler_input_refs
             .iter()
-            .map(|r| informer.receive(&r.modulus()))
-            .collect::<Result<Vec<DummyVal>, DummyError>>()?;
+            .map(|r| informer.encode(0, &r.modulus()))
+            .collect::<Result<Vec<_>, DummyError>>()?;
         let ev = self
             .evaluator_input_refs
             .iter()
             .map(|r| informer.receive(&r.modulus()))
-            .collect::<Result<Vec<DummyVal>, DummyError>>()?;
+            .collect::<Result<Vec<_>, DummyError>>()?;
 
         let _outputs = self.eval(&mut informer, &gb, &ev)?;
-        println!("{}", informer.stats());
+        let mut stats = informer.stats();
+        stats.set_wire_names(
+            self.names
+                .iter()
+                .map(|(ix, name)| (*ix, name.clone()))
+                .collect(),
+        );
+        Ok(stats)
+    }
+
+    /// Print circuit info.
+    #[cfg(feature = "std")]
+    pub fn print_info(&self) -> Result<(), DummyError> {
+        println!("{}", self.info()?);
         Ok(())
     }
 
@@ -316,20 +503,181 @@ impl Circuit {
     }
 }
 
+/// A computation written once against the `Fancy` interface.
+///
+/// The same definition can be run directly by any `Fancy` object, e.g. a
+/// streaming `Garbler` or `Evaluator`, or a `Dummy`, or turned into a `Circuit`
+/// for classic garbling with `Circuit::from_gadget`. Closures cannot be generic
+/// over `F`, hence the trait.
+pub trait FancyGadget {
+    /// Compute the output wires from the garbler's and evaluator's input wires.
+    fn run<F: Fancy>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error>;
+
+    /// Run the computation on `f` and output the results, mirroring
+    /// `Circuit::eval`.
+    fn eval<F: Fancy>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Option<Vec<u16>>, F::Error> {
+        let outputs = self.run(f, garbler_inputs, evaluator_inputs)?;
+        f.outputs(&outputs)
+    }
+}
+
+impl Circuit {
+    /// Build the circuit computing `gadget` on garbler inputs with moduli
+    /// `garbler_mods` and evaluator inputs with moduli `evaluator_mods`.
+    pub fn from_gadget<G: FancyGadget>(
+        gadget: &G,
+        garbler_mods: &[Modulus],
+        evaluator_mods: &[Modulus],
+    ) -> Result<Circuit, CircuitBuilderError> {
+        let mut b = CircuitBuilder::new();
+        let xs = b.garbler_inputs(garbler_mods);
+        let ys = b.evaluator_inputs(evaluator_mods);
+        gadget.eval(&mut b, &xs, &ys)?;
+        Ok(b.finish())
+    }
+}
+
+/// A region of gates with formal inputs and outputs, traced once and spliced
+/// into a `CircuitBuilder` any number of times with
+/// `CircuitBuilder::instantiate`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubCircuit {
+    // the formal inputs are the garbler inputs
+    circ: Circuit,
+}
+
+impl SubCircuit {
+    /// Trace `f` on formal inputs with moduli `input_mods`, taking the wires it
+    /// returns as the formal outputs.
+    pub fn new<F>(input_mods: &[Modulus], f: F) -> Result<SubCircuit, CircuitBuilderError>
+    where
+        F: FnOnce(
+            &mut CircuitBuilder,
+            &[CircuitRef],
+        ) -> Result<Vec<CircuitRef>, CircuitBuilderError>,
+    {
+        let mut b = CircuitBuilder::new();
+        let xs = b.garbler_inputs(input_mods);
+        let zs = f(&mut b, &xs)?;
+        b.circ.output_refs = zs;
+        Ok(SubCircuit { circ: b.finish() })
+    }
+
+    /// The moduli of the formal inputs.
+    pub fn input_moduli(&self) -> Vec<Modulus> {
+        (0..self.circ.num_garbler_inputs())
+            .map(|i| self.circ.garbler_input_mod(i))
+            .collect()
+    }
+
+    /// The number of formal outputs.
+    pub fn noutputs(&self) -> usize {
+        self.circ.noutputs()
+    }
+
+    /// The moduli of the formal outputs.
+    pub fn output_moduli(&self) -> Vec<Modulus> {
+        self.circ.output_refs.iter().map(|r| r.modulus()).collect()
+    }
+
+    /// Evaluate the gates with `f` on `inputs` in place of the formal inputs,
+    /// returning the values of the formal outputs.
+    pub(crate) fn apply<F: Fancy>(&self, f: &mut F, inputs: &[F::Item]) -> Result<Vec<F::Item>, F::Error> {
+        let mods = self.input_moduli();
+        if inputs.len() != mods.len() {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: inputs.len(),
+                needed: mods.len(),
+            }));
+        }
+        if let Some((x, q)) = inputs.iter().zip(mods.iter()).find(|(x, q)| x.modulus() != **q) {
+            return Err(F::Error::from(FancyError::InvalidArgMod {
+                got: x.modulus(),
+                needed: *q,
+            }));
+        }
+        let cache = self.circ.eval_gates(f, inputs, &[], &mut EvalMonitor::new())?;
+        self.circ
+            .output_refs
+            .iter()
+            .map(|r| Ok(self.circ.cached(&cache, r)?.clone()))
+            .collect()
+    }
+
+    /// Check that `branches` can be chosen between by a condition of modulus
+    /// `cond` and take `inputs`, see `FancyBranch::branch`, returning the
+    /// moduli of their outputs.
+    pub(crate) fn check_branches(
+        cond: &Modulus,
+        branches: &[SubCircuit],
+        inputs: &[Modulus],
+    ) -> Result<Vec<Modulus>, FancyError> {
+        let n = branches.len();
+        if n == 0 || cond.order() as usize != n {
+            return Err(FancyError::InvalidArg(format!(
+                "{} branches for a condition of modulus {}",
+                n, cond
+            )));
+        }
+        let (in_mods, out_mods) = (branches[0].input_moduli(), branches[0].output_moduli());
+        if branches
+            .iter()
+            .any(|b| b.input_moduli() != in_mods || b.output_moduli() != out_mods)
+        {
+            return Err(FancyError::InvalidArg(
+                "branches take or return wires of different moduli".to_string(),
+            ));
+        }
+        if inputs != &in_mods[..] {
+            return Err(FancyError::InvalidArg(
+                "inputs do not match the moduli the branches take".to_string(),
+            ));
+        }
+        Ok(out_mods)
+    }
+}
+
 /// CircuitBuilder is used to build circuits.
 pub struct CircuitBuilder {
     next_ref_ix: usize,
     next_garbler_input_id: usize,
     next_evaluator_input_id: usize,
-    const_map: HashMap<(u16, Modulus), CircuitRef>,
+    const_map: BTreeMap<(u64, Modulus), CircuitRef>,
+    // gates already built, when common subexpression elimination is enabled
+    gate_map: Option<BTreeMap<GateKey, CircuitRef>>,
     circ: Circuit,
 }
 
+/// The operation and operands of a gate, by which structurally identical gates
+/// are found. Operands of commutative gates are ordered.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum GateKey {
+    Add(usize, usize),
+    Sub(usize, usize),
+    Cmul(usize, u64),
+    Mul(usize, usize),
+    Proj(usize, Modulus, Vec<u16>),
+}
+
 impl Fancy for CircuitBuilder {
     type Item = CircuitRef;
     type Error = CircuitBuilderError;
 
     fn constant(&mut self, val: u16, modulus: &Modulus) -> Result<CircuitRef, Self::Error> {
+        self.constant_big(val as u64, modulus)
+    }
+
+    fn constant_big(&mut self, val: u64, modulus: &Modulus) -> Result<CircuitRef, Self::Error> {
         match self.const_map.get(&(val, *modulus)) {
             Some(&r) => Ok(r),
             None => {
@@ -344,37 +692,54 @@ impl Fancy for CircuitBuilder {
 
     fn add(&mut self, xref: &CircuitRef, yref: &CircuitRef) -> Result<CircuitRef, Self::Error> {
         if xref.modulus() != yref.modulus() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::UnequalModuli {
+                x: xref.modulus(),
+                y: yref.modulus(),
+            }));
         }
-        let gate = Gate::Add {
-            xref: *xref,
-            yref: *yref,
-            out: None,
-        };
-        Ok(self.gate(gate, &xref.modulus()))
+        let key = GateKey::Add(xref.ix.min(yref.ix), xref.ix.max(yref.ix));
+        self.hash_consed(key, |b| {
+            let gate = Gate::Add {
+                xref: *xref,
+                yref: *yref,
+                out: None,
+            };
+            Ok(b.gate(gate, &xref.modulus()))
+        })
     }
 
     fn sub(&mut self, xref: &CircuitRef, yref: &CircuitRef) -> Result<CircuitRef, Self::Error> {
         if xref.modulus() != yref.modulus() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::UnequalModuli {
+                x: xref.modulus(),
+                y: yref.modulus(),
+            }));
         }
-        let gate = Gate::Sub {
-            xref: *xref,
-            yref: *yref,
-            out: None,
-        };
-        Ok(self.gate(gate, &xref.modulus()))
+        self.hash_consed(GateKey::Sub(xref.ix, yref.ix), |b| {
+            let gate = Gate::Sub {
+                xref: *xref,
+                yref: *yref,
+                out: None,
+            };
+            Ok(b.gate(gate, &xref.modulus()))
+        })
     }
 
     fn cmul(&mut self, xref: &CircuitRef, c: u16) -> Result<CircuitRef, Self::Error> {
-        Ok(self.gate(
-            Gate::Cmul {
-                xref: *xref,
-                c,
-                out: None,
-            },
-            &xref.modulus(),
-        ))
+        self.cmul_big(xref, c as u64)
+    }
+
+    fn cmul_big(&mut self, xref: &CircuitRef, c: u64) -> Result<CircuitRef, Self::Error> {
+        self.hash_consed(GateKey::Cmul(xref.ix, c), |b| {
+            Ok(b.gate(
+                Gate::Cmul {
+                    xref: *xref,
+                    c,
+                    out: None,
+                },
+                &xref.modulus(),
+            ))
+        })
     }
 
     fn proj(
@@ -384,16 +749,19 @@ impl Fancy for CircuitBuilder {
         tt: Option<Vec<u16>>,
     ) -> Result<CircuitRef, Self::Error> {
         let tt = tt.ok_or_else(|| Self::Error::from(FancyError::NoTruthTable))?;
-        if tt.len() < xref.modulus().size() as usize  || !tt.iter().all(|&x| x < output_modulus.size()) {
+        if tt.len() < xref.modulus().order() as usize  || !tt.iter().all(|&x| (x as u32) < output_modulus.order()) {
             return Err(Self::Error::from(FancyError::InvalidTruthTable));
         }
-        let gate = Gate::Proj {
-            xref: *xref,
-            tt: tt.to_vec(),
-            id: self.get_next_ciphertext_id(),
-            out: None,
-        };
-        Ok(self.gate(gate, output_modulus))
+        let key = GateKey::Proj(xref.ix, *output_modulus, tt.clone());
+        self.hash_consed(key, |b| {
+            let gate = Gate::Proj {
+                xref: *xref,
+                tt,
+                id: b.get_next_ciphertext_id(),
+                out: None,
+            };
+            Ok(b.gate(gate, output_modulus))
+        })
     }
 
     fn mul(&mut self, xref: &CircuitRef, yref: &CircuitRef) -> Result<CircuitRef, Self::Error> {
@@ -403,18 +771,14 @@ impl Fancy for CircuitBuilder {
                     return self.mul(yref, xref);
                 }
         
-                let gate = Gate::Mul {
-                    xref: *xref,
-                    yref: *yref,
-                    id: self.get_next_ciphertext_id(),
-                    out: None,
-                };
-
-                Ok(self.gate(gate, &xref.modulus()))
+                self.mul_gate(xref, yref)
+            },
+            (xq, yq) if xq.is_field() => {
+                if xq != yq {
+                    return Err(Self::Error::from(FancyError::UnequalModuli { x: xq, y: yq }));
+                }
+                self.mul_gate(xref, yref)
             },
-            // (Modulus::GF4 { p: xpoly }, Modulus::GF4 { p: ypoly }) => {
-            //     // TODO
-            // },
             _ => {
                 Err(Self::Error::from(FancyError::InvalidArg(String::from("Not supported for combining a field and ring element."))))
             },
@@ -425,6 +789,50 @@ impl Fancy for CircuitBuilder {
         self.circ.output_refs.push(*xref);
         Ok(None)
     }
+
+    fn output_big(&mut self, xref: &CircuitRef) -> Result<Option<u64>, Self::Error> {
+        self.circ.output_refs.push(*xref);
+        Ok(None)
+    }
+}
+
+impl FancyBranch for CircuitBuilder {
+    /// Every branch is spliced into the circuit and the outputs are selected
+    /// by multiplexing on `cond`, so garbling the circuit costs as much as
+    /// taking all branches, plus a projection and multiplication per output
+    /// and branch. A `Circuit` has no branch gate, so neither `classic::garble`
+    /// nor serialized circuits stack branches; only a streaming `Garbler`
+    /// calling `FancyBranch::branch` directly does.
+    fn branch(
+        &mut self,
+        cond: &CircuitRef,
+        branches: &[SubCircuit],
+        inputs: &[CircuitRef],
+    ) -> Result<Vec<CircuitRef>, CircuitBuilderError> {
+        let in_mods = inputs.iter().map(|x| x.modulus()).collect_vec();
+        let out_mods = SubCircuit::check_branches(&cond.modulus(), branches, &in_mods)?;
+        let n = branches.len();
+        let outs = branches
+            .iter()
+            .map(|b| self.instantiate(b, inputs))
+            .collect::<Result<Vec<_>, _>>()?;
+        // z = out_0 + sum_i [cond = i] * (out_i - out_0)
+        let mut zs = outs[0].clone();
+        for (i, out) in outs.iter().enumerate().skip(1) {
+            let tt = (0..n).map(|c| (c == i) as u16).collect_vec();
+            for ((z, x), (y, q)) in zs
+                .iter_mut()
+                .zip(outs[0].iter())
+                .zip(out.iter().zip(out_mods.iter()))
+            {
+                let d = self.sub(y, x)?;
+                let c = self.proj(cond, q, Some(tt.clone()))?;
+                let d = self.mul(&d, &c)?;
+                *z = self.add(z, &d)?;
+            }
+        }
+        Ok(zs)
+    }
 }
 
 impl CircuitBuilder {
@@ -434,11 +842,62 @@ impl CircuitBuilder {
             next_ref_ix: 0,
             next_garbler_input_id: 0,
             next_evaluator_input_id: 0,
-            const_map: HashMap::new(),
+            const_map: BTreeMap::new(),
+            gate_map: None,
             circ: Circuit::new(None),
         }
     }
 
+    /// Make a new `CircuitBuilder` which eliminates common subexpressions.
+    ///
+    /// Building an addition, subtraction, scalar multiplication,
+    /// multiplication or projection identical to an earlier one, on the same
+    /// operands, returns the earlier `CircuitRef` instead of adding a gate.
+    pub fn with_cse() -> Self {
+        CircuitBuilder {
+            gate_map: Some(BTreeMap::new()),
+            ..Self::new()
+        }
+    }
+
+    // Look `key` up when eliminating common subexpressions, building the gate
+    // with `build` if it is new.
+    fn hash_consed<B>(&mut self, key: GateKey, build: B) -> Result<CircuitRef, CircuitBuilderError>
+    where
+        B: FnOnce(&mut Self) -> Result<CircuitRef, CircuitBuilderError>,
+    {
+        if let Some(r) = self.gate_map.as_ref().and_then(|m| m.get(&key)) {
+            return Ok(*r);
+        }
+        let r = build(self)?;
+        if let Some(m) = self.gate_map.as_mut() {
+            m.insert(key, r);
+        }
+        Ok(r)
+    }
+
+    fn mul_gate(
+        &mut self,
+        xref: &CircuitRef,
+        yref: &CircuitRef,
+    ) -> Result<CircuitRef, CircuitBuilderError> {
+        // operands are only interchangeable when their moduli agree
+        let key = if xref.modulus() == yref.modulus() {
+            GateKey::Mul(xref.ix.min(yref.ix), xref.ix.max(yref.ix))
+        } else {
+            GateKey::Mul(xref.ix, yref.ix)
+        };
+        self.hash_consed(key, |b| {
+            let gate = Gate::Mul {
+                xref: *xref,
+                yref: *yref,
+                id: b.get_next_ciphertext_id(),
+                out: None,
+            };
+            Ok(b.gate(gate, &xref.modulus()))
+        })
+    }
+
     /// Finish circuit building, outputting the resulting circuit.
     pub fn finish(self) -> Circuit {
         self.circ
@@ -475,6 +934,17 @@ impl CircuitBuilder {
         CircuitRef { ix, modulus: *modulus }
     }
 
+    /// Splice the gates of `sub` into the circuit, with `inputs` in place of its
+    /// formal inputs, returning the wires of its formal outputs.
+    pub fn instantiate(
+        &mut self,
+        sub: &SubCircuit,
+        inputs: &[CircuitRef],
+    ) -> Result<Vec<CircuitRef>, CircuitBuilderError> {
+        // replaying the gates through `self` remaps their indices
+        sub.apply(self, inputs)
+    }
+
     /// Get CircuitRef for a garbler input wire.
     pub fn garbler_input(&mut self, modulus: &Modulus) -> CircuitRef {
         let id = self.get_next_garbler_input_id();
@@ -491,6 +961,26 @@ impl CircuitBuilder {
         r
     }
 
+    /// Get CircuitRef for a garbler input wire named `name`.
+    pub fn named_garbler_input(&mut self, name: &str, modulus: &Modulus) -> CircuitRef {
+        let r = self.garbler_input(modulus);
+        self.set_name(&r, name);
+        r
+    }
+
+    /// Get CircuitRef for an evaluator input wire named `name`.
+    pub fn named_evaluator_input(&mut self, name: &str, modulus: &Modulus) -> CircuitRef {
+        let r = self.evaluator_input(modulus);
+        self.set_name(&r, name);
+        r
+    }
+
+    /// Name the wire `r`, replacing any previous name. Names are kept when
+    /// the circuit is serialized, and used in errors about the wire.
+    pub fn set_name(&mut self, r: &CircuitRef, name: &str) {
+        self.circ.names.insert(r.ix, name.to_string());
+    }
+
     /// Get a vec of CircuitRefs for garbler inputs.
     pub fn garbler_inputs(&mut self, mods: &[Modulus]) -> Vec<CircuitRef> {
         mods.iter().map(|q| self.garbler_input(q)).collect()
@@ -529,6 +1019,121 @@ mod plaintext {
     use itertools::Itertools;
     use rand::{thread_rng, seq::SliceRandom, Rng};
 
+    #[test] // named wires
+    fn names() {
+        let q = Modulus::Zq { q: 5 };
+        let mut b = CircuitBuilder::new();
+        let x = b.named_garbler_input("x", &q);
+        let y = b.evaluator_input(&q);
+        let w = b.cmul(&y, 2).unwrap();
+        b.set_name(&w, "w");
+        let z = b.add(&x, &w).unwrap();
+        b.output(&z).unwrap();
+        let mut c = b.finish();
+        assert_eq!(c.wire_name(&x), Some("x"));
+        assert_eq!(c.wire_name(&y), None);
+        assert_eq!(c.eval_plain(&[1], &[3]).unwrap(), vec![2]);
+        let stats = c.info().unwrap();
+        assert_eq!(
+            stats.wire_names(),
+            &[(x.ix, "x".to_string()), (w.ix, "w".to_string())]
+        );
+        assert!(stats.to_string().contains("named wires"));
+
+        let mut opt = c.clone();
+        opt.optimize().unwrap();
+        assert_eq!(opt.wire_name(&opt.garbler_input_refs[0]), Some("x"));
+        assert!(opt.names.values().any(|name| name == "w"));
+
+        // reading `w` before it is computed names it in the error, along with
+        // the gate which read it
+        c.gates.swap(w.ix, z.ix);
+        match c.eval_plain(&[1], &[3]) {
+            Err(DummyError::FancyError(e)) => {
+                assert_eq!(e.gate(), Some(w.ix));
+                match e.root_cause() {
+                    FancyError::UninitializedWire(name) => assert_eq!(name, "w"),
+                    e => panic!("unexpected error {}", e),
+                }
+            }
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test] // sub-circuits
+    fn instantiate() {
+        let q = Modulus::Zq { q: 11 };
+        // one round: (x, y) -> (x * y + 3, x - y)
+        let round = SubCircuit::new(&[q, q], |b, xs| {
+            let three = b.constant(3, &q)?;
+            let z = b.mul(&xs[0], &xs[1])?;
+            let z = b.add(&z, &three)?;
+            let w = b.sub(&xs[0], &xs[1])?;
+            Ok(vec![z, w])
+        })
+        .unwrap();
+        assert_eq!(round.input_moduli(), vec![q, q]);
+        assert_eq!(round.noutputs(), 2);
+
+        let mut b = CircuitBuilder::new();
+        let mut xs = vec![b.garbler_input(&q), b.evaluator_input(&q)];
+        for _ in 0..4 {
+            xs = b.instantiate(&round, &xs).unwrap();
+        }
+        b.outputs(&xs).unwrap();
+        assert!(b.instantiate(&round, &xs[..1]).is_err());
+        let z = b.constant(0, &Modulus::Zq { q: 5 }).unwrap();
+        assert!(b.instantiate(&round, &[xs[0], z]).is_err());
+        let c = b.finish();
+        assert_eq!(c.num_nonfree_gates, 4);
+
+        for x in 0..11 {
+            for y in 0..11 {
+                let (mut u, mut v) = (x, y);
+                for _ in 0..4 {
+                    let (u_, v_) = ((u * v + 3) % 11, (11 + u - v) % 11);
+                    u = u_;
+                    v = v_;
+                }
+                assert_eq!(c.eval_plain(&[x], &[y]).unwrap(), vec![u, v]);
+            }
+        }
+    }
+
+    #[test] // common subexpression elimination
+    fn cse() {
+        let q = Modulus::Zq { q: 7 };
+        let tt = (0..7).map(|x| x * x % 7).collect_vec();
+        let build = |mut b: CircuitBuilder| {
+            let x = b.garbler_input(&q);
+            let y = b.evaluator_input(&q);
+            for _ in 0..3 {
+                let s = b.proj(&x, &q, Some(tt.clone())).unwrap();
+                let t = b.mul(&y, &x).unwrap();
+                let u = b.add(&s, &t).unwrap();
+                let u = b.cmul(&u, 3).unwrap();
+                let u = b.sub(&u, &y).unwrap();
+                b.output(&u).unwrap();
+            }
+            let t = b.mul(&x, &y).unwrap();
+            b.output(&t).unwrap();
+            b.finish()
+        };
+        let plain = build(CircuitBuilder::new());
+        let consed = build(CircuitBuilder::with_cse());
+        assert_eq!(plain.num_nonfree_gates, 7);
+        assert_eq!(consed.num_nonfree_gates, 2);
+        assert_eq!(consed.gates.len(), 2 + 5);
+        for x in 0..7 {
+            for y in 0..7 {
+                assert_eq!(
+                    consed.eval_plain(&[x], &[y]).unwrap(),
+                    plain.eval_plain(&[x], &[y]).unwrap()
+                );
+            }
+        }
+    }
+
     #[test] // and_gate_fan_n
     fn and_gate_fan_n() {
         let mut rng = thread_rng();
@@ -683,6 +1288,86 @@ mod plaintext {
             assert_eq!(z[0], (x ^ c) as u16);
         }
     }
+
+    #[test]
+    fn eval_monitored() {
+        let mut b = CircuitBuilder::new();
+        let q = Modulus::Zq { q: 2 };
+        let inps = b.evaluator_inputs(&[q; 10]);
+        let z = b.and_many(&inps).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let ngates = c.gates.len();
+        let inps = vec![DummyVal::new(1, q); 10];
+
+        let mut progress = Vec::new();
+        let mut monitor = EvalMonitor::every(4).on_progress(|done, total| progress.push((done, total)));
+        let out = c.eval_monitored(&mut Dummy::new(), &[], &inps, &mut monitor).unwrap();
+        assert_eq!(out, Some(vec![1]));
+        drop(monitor);
+        let mut expected = (4..ngates).step_by(4).map(|i| (i, ngates)).collect_vec();
+        expected.push((ngates, ngates));
+        assert_eq!(progress, expected);
+
+        let mut checks = 0;
+        let mut monitor = EvalMonitor::every(4).cancel_when(|| {
+            checks += 1;
+            checks == 2
+        });
+        match c.eval_monitored(&mut Dummy::new(), &[], &inps, &mut monitor) {
+            Err(DummyError::FancyError(FancyError::Cancelled { gates_done })) => {
+                assert_eq!(gates_done, 8)
+            }
+            r => panic!("expected cancellation, got {:?}", r),
+        }
+    }
+
+    // x * y + c, then squared with a projection
+    struct MulAddSquare {
+        c: u16,
+    }
+
+    impl FancyGadget for MulAddSquare {
+        fn run<F: Fancy>(
+            &self,
+            f: &mut F,
+            garbler_inputs: &[F::Item],
+            evaluator_inputs: &[F::Item],
+        ) -> Result<Vec<F::Item>, F::Error> {
+            let x = &garbler_inputs[0];
+            let y = &evaluator_inputs[0];
+            let q = x.modulus();
+            let xy = f.mul(x, y)?;
+            let c = f.constant(self.c, &q)?;
+            let z = f.add(&xy, &c)?;
+            let tt = (0..q.size()).map(|i| i * i % q.size()).collect();
+            let zz = f.proj(&z, &q, Some(tt))?;
+            Ok(vec![z, zz])
+        }
+    }
+
+    #[test]
+    fn from_gadget() {
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let q = rng.gen_modulus();
+            let m = Modulus::Zq { q };
+            let gadget = MulAddSquare { c: rng.gen_u16() % q };
+            let c = Circuit::from_gadget(&gadget, &[m], &[m]).unwrap();
+            assert_eq!(c.noutputs(), 2);
+            assert_eq!(c.num_nonfree_gates, 2);
+
+            let x = rng.gen_u16() % q;
+            let y = rng.gen_u16() % q;
+            let z = ((x as u32 * y as u32 + gadget.c as u32) % q as u32) as u16;
+            let expected = vec![z, ((z as u32 * z as u32) % q as u32) as u16];
+            assert_eq!(c.eval_plain(&[x], &[y]).unwrap(), expected);
+            let direct = gadget
+                .eval(&mut Dummy::new(), &[DummyVal::new(x, m)], &[DummyVal::new(y, m)])
+                .unwrap();
+            assert_eq!(direct, Some(expected));
+        }
+    }
 }
 
 #[cfg(test)]
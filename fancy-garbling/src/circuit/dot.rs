@@ -0,0 +1,101 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Export of circuits as Graphviz DOT graphs, for debugging small gadgets.
+
+use super::{Circuit, Gate};
+use std::io::{Result, Write};
+
+// Longest projection truth table which is shown in full.
+const MAX_TT_LEN: usize = 16;
+
+impl Circuit {
+    /// Write the circuit to `w` as a Graphviz DOT graph.
+    ///
+    /// Every wire is a node labelled with its name if it has one, the gate
+    /// defining it, and its modulus, with an edge from each of the gate's
+    /// operands. Inputs are drawn as ellipses and outputs are marked by
+    /// separate `output` nodes. Render it with, e.g.,
+    /// `dot -Tsvg circuit.dot -o circuit.svg`.
+    pub fn to_dot<W: Write>(&self, mut w: W) -> Result<()> {
+        writeln!(w, "digraph circuit {{")?;
+        writeln!(w, "  node [shape=box];")?;
+        for (i, gate) in self.gates.iter().enumerate() {
+            let ix = gate.out().unwrap_or(i);
+            let q = self.modulus(i);
+            let (label, operands) = match *gate {
+                Gate::GarblerInput { id } => (format!("garbler input {}", id), vec![]),
+                Gate::EvaluatorInput { id } => (format!("evaluator input {}", id), vec![]),
+                Gate::Constant { val } => (format!("constant {}", val), vec![]),
+                Gate::Add { xref, yref, .. } => ("add".to_string(), vec![xref, yref]),
+                Gate::Sub { xref, yref, .. } => ("sub".to_string(), vec![xref, yref]),
+                Gate::Cmul { xref, c, .. } => (format!("cmul {}", c), vec![xref]),
+                Gate::Mul { xref, yref, .. } => ("mul".to_string(), vec![xref, yref]),
+                Gate::Proj { xref, ref tt, .. } if tt.len() <= MAX_TT_LEN => {
+                    (format!("proj {:?}", tt), vec![xref])
+                }
+                Gate::Proj { xref, .. } => ("proj".to_string(), vec![xref]),
+            };
+            let shape = match gate {
+                Gate::GarblerInput { .. } | Gate::EvaluatorInput { .. } => " shape=ellipse",
+                _ => "",
+            };
+            let label = match self.names.get(&ix) {
+                Some(name) => format!("{}\\n{}", name.escape_default(), label),
+                None => label,
+            };
+            writeln!(w, "  w{} [label=\"{}\\n{}\"{}];", ix, label, q, shape)?;
+            for x in operands {
+                writeln!(w, "  w{} -> w{};", x.ix, ix)?;
+            }
+        }
+        for (i, r) in self.output_refs.iter().enumerate() {
+            writeln!(w, "  out{} [label=\"output {}\" shape=plaintext];", i, i)?;
+            writeln!(w, "  w{} -> out{};", r.ix, i)?;
+        }
+        writeln!(w, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        circuit::{Circuit, CircuitBuilder},
+        fancy::Fancy,
+        Modulus,
+    };
+
+    #[test]
+    fn small_gadget() {
+        let q = Modulus::X4_X_1;
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = b.evaluator_input(&q);
+        let z = b.add(&x, &y).unwrap();
+        let z = b.proj(&z, &q, Some((0..16).rev().collect())).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+
+        let mut dot = Vec::new();
+        c.to_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph circuit {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("w0 [label=\"garbler input 0\\nGF4(p=19)\" shape=ellipse];"));
+        assert!(dot.contains("w0 -> w2;\n  w1 -> w2;"));
+        assert!(dot.contains("w3 [label=\"proj [15, 14, 13,"));
+        assert!(dot.contains("w3 -> out0;"));
+    }
+
+    #[test]
+    fn parsed_circuit() {
+        let c = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        let mut dot = Vec::new();
+        c.to_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert_eq!(dot.matches("-> out").count(), c.output_refs.len());
+    }
+}
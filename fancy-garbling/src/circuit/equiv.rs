@@ -0,0 +1,243 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Checking that two circuits compute the same function, by evaluating both in
+//! plaintext on the same inputs.
+
+use super::Circuit;
+use crate::{
+    errors::{DummyError, FancyError},
+    wire::Modulus,
+};
+use alloc::vec::Vec;
+use rand::{Rng, SeedableRng};
+use scuttlebutt::{AesRng, Block};
+
+/// How `Circuit::equiv` chooses the inputs to compare circuits on.
+#[derive(Clone, Copy, Debug)]
+pub enum EquivStrategy {
+    /// Compare on every possible input.
+    Exhaustive,
+    /// Compare on `trials` uniformly random inputs, drawn from an RNG seeded
+    /// with `seed`.
+    Random {
+        /// Number of inputs to compare on.
+        trials: usize,
+        /// Seed for the inputs.
+        seed: Block,
+    },
+    /// Compare exhaustively if there are at most `max_inputs` possible inputs,
+    /// and randomly otherwise.
+    Auto {
+        /// Largest number of inputs to compare exhaustively.
+        max_inputs: u128,
+        /// Number of inputs to compare on otherwise.
+        trials: usize,
+        /// Seed for the inputs.
+        seed: Block,
+    },
+}
+
+/// Inputs on which two circuits give different outputs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Counterexample {
+    /// The garbler inputs.
+    pub garbler_inputs: Vec<u64>,
+    /// The evaluator inputs.
+    pub evaluator_inputs: Vec<u64>,
+}
+
+impl Circuit {
+    /// Check whether `self` and `other` compute the same function, returning
+    /// inputs on which they differ if any are found.
+    ///
+    /// Only an exhaustive comparison shows that the circuits are equivalent;
+    /// random comparison only gives confidence that they are. Returns an error
+    /// if the circuits take different inputs or give different numbers of
+    /// outputs.
+    pub fn equiv(
+        &self,
+        other: &Circuit,
+        strategy: EquivStrategy,
+    ) -> Result<Option<Counterexample>, DummyError> {
+        let moduli = |c: &Circuit| {
+            let gb = c.garbler_input_refs.iter().map(|r| r.modulus);
+            let ev = c.evaluator_input_refs.iter().map(|r| r.modulus);
+            gb.chain(ev).collect::<Vec<_>>()
+        };
+        let mods = moduli(self);
+        let other_mods = moduli(other);
+        if self.num_garbler_inputs() != other.num_garbler_inputs() || mods.len() != other_mods.len()
+        {
+            return Err(DummyError::FancyError(FancyError::InvalidArgNum {
+                got: other_mods.len(),
+                needed: mods.len(),
+            }));
+        }
+        if let Some((q, p)) = mods.iter().zip(other_mods.iter()).find(|(q, p)| q != p) {
+            return Err(DummyError::FancyError(FancyError::InvalidArgMod {
+                got: *p,
+                needed: *q,
+            }));
+        }
+        if self.output_refs.len() != other.output_refs.len() {
+            return Err(DummyError::FancyError(FancyError::InvalidArgNum {
+                got: other.output_refs.len(),
+                needed: self.output_refs.len(),
+            }));
+        }
+
+        let ngb = self.num_garbler_inputs();
+        let differs = |xs: &[u64]| -> Result<Option<Counterexample>, DummyError> {
+            let (gb, ev) = xs.split_at(ngb);
+            if self.eval_plain_big(gb, ev)? == other.eval_plain_big(gb, ev)? {
+                Ok(None)
+            } else {
+                Ok(Some(Counterexample {
+                    garbler_inputs: gb.to_vec(),
+                    evaluator_inputs: ev.to_vec(),
+                }))
+            }
+        };
+
+        let ninputs = mods
+            .iter()
+            .try_fold(1u128, |n, q| n.checked_mul(q.order() as u128));
+        let (trials, seed) = match strategy {
+            EquivStrategy::Exhaustive => return exhaustive(&mods, differs),
+            EquivStrategy::Auto { max_inputs, .. } if ninputs.is_some_and(|n| n <= max_inputs) => {
+                return exhaustive(&mods, differs)
+            }
+            EquivStrategy::Random { trials, seed } | EquivStrategy::Auto { trials, seed, .. } => {
+                (trials, seed)
+            }
+        };
+        let mut rng = AesRng::from_seed(seed);
+        for _ in 0..trials {
+            let xs = mods
+                .iter()
+                .map(|q| rng.gen::<u64>() % order(q))
+                .collect::<Vec<_>>();
+            if let Some(cex) = differs(&xs)? {
+                return Ok(Some(cex));
+            }
+        }
+        Ok(None)
+    }
+}
+
+fn order(q: &Modulus) -> u64 {
+    match q {
+        Modulus::ZqBig { q } => *q,
+        q => q.order() as u64,
+    }
+}
+
+// Call `differs` on every input for wires with moduli `mods`, in lexicographic
+// order, until it finds a counterexample.
+fn exhaustive<D>(mods: &[Modulus], mut differs: D) -> Result<Option<Counterexample>, DummyError>
+where
+    D: FnMut(&[u64]) -> Result<Option<Counterexample>, DummyError>,
+{
+    let mut xs = vec![0; mods.len()];
+    loop {
+        if let Some(cex) = differs(&xs)? {
+            return Ok(Some(cex));
+        }
+        // increment `xs` as a mixed-radix number, least significant digit last
+        let mut i = xs.len();
+        loop {
+            if i == 0 {
+                return Ok(None);
+            }
+            i -= 1;
+            xs[i] += 1;
+            if xs[i] < order(&mods[i]) {
+                break;
+            }
+            xs[i] = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::CircuitBuilder, fancy::Fancy};
+
+    // x^2 + c over GF(2^4), squaring with a projection or a multiplication
+    fn square(c: u16, proj: bool) -> Circuit {
+        let q = Modulus::X4_X_1;
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = if proj {
+            let tt = (0..16)
+                .map(|x| crate::util::field_mul(x, x, 0b10011, 4))
+                .collect();
+            b.proj(&x, &q, Some(tt)).unwrap()
+        } else {
+            b.mul(&x, &x).unwrap()
+        };
+        let c = b.constant(c, &q).unwrap();
+        let z = b.add(&y, &c).unwrap();
+        b.output(&z).unwrap();
+        b.finish()
+    }
+
+    // parity of `n` bits, or of all but the first if `skip_first`
+    fn parity(n: usize, skip_first: bool) -> Circuit {
+        let q = Modulus::Zq { q: 2 };
+        let mut b = CircuitBuilder::new();
+        let xs = b.evaluator_inputs(&vec![q; n]);
+        let mut z = b.constant(0, &q).unwrap();
+        for x in xs.iter().skip(skip_first as usize).rev() {
+            z = b.add(&z, x).unwrap();
+        }
+        b.output(&z).unwrap();
+        b.finish()
+    }
+
+    #[test]
+    fn exhaustive() {
+        let (c, d) = (square(3, true), square(3, false));
+        assert_eq!(c.equiv(&d, EquivStrategy::Exhaustive).unwrap(), None);
+        let cex = c
+            .equiv(&square(2, false), EquivStrategy::Exhaustive)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cex.garbler_inputs, vec![0]);
+        assert!(cex.evaluator_inputs.is_empty());
+    }
+
+    #[test]
+    fn random() {
+        let strategy = EquivStrategy::Auto {
+            max_inputs: 1 << 16,
+            trials: 64,
+            seed: Block::default(),
+        };
+        let c = parity(100, false);
+        assert_eq!(c.equiv(&parity(100, false), strategy).unwrap(), None);
+        let d = parity(100, true);
+        let cex = c.equiv(&d, strategy).unwrap().unwrap();
+        assert_eq!(cex.evaluator_inputs[0], 1);
+        assert_ne!(
+            c.eval_plain_big(&[], &cex.evaluator_inputs).unwrap(),
+            d.eval_plain_big(&[], &cex.evaluator_inputs).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_different_interfaces() {
+        let c = parity(4, false);
+        assert!(c
+            .equiv(&parity(5, false), EquivStrategy::Exhaustive)
+            .is_err());
+        assert!(c
+            .equiv(&square(0, true), EquivStrategy::Exhaustive)
+            .is_err());
+    }
+}
@@ -0,0 +1,393 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Optimization pass over circuits: constant folding, removal of identity
+//! gates, and dead-gate elimination.
+
+use super::{Circuit, CircuitBuilder, CircuitRef, Gate};
+use crate::{
+    dummy::{Dummy, DummyVal},
+    errors::{CircuitBuilderError, DummyError, FancyError},
+    fancy::{Fancy, HasModulus},
+    wire::Modulus,
+};
+use alloc::{string::ToString, vec::Vec};
+
+// The value of a wire after folding: a known constant, or a wire of the
+// optimized circuit.
+#[derive(Clone, Copy)]
+enum Val {
+    Const(u64, Modulus),
+    Wire(CircuitRef),
+}
+
+impl Circuit {
+    /// Optimize the circuit in place, returning how many nonfree gates were
+    /// removed.
+    ///
+    /// Gates whose operands are all constant are folded into constants, adding
+    /// or subtracting zero and multiplying by zero or one are simplified away,
+    /// and multiplications by a constant of the same modulus become free
+    /// scalar multiplications. Finally, gates which no output depends on are
+    /// removed. Inputs are always kept, so the circuit takes the same inputs
+    /// and computes the same outputs.
+    pub fn optimize(&mut self) -> Result<usize, CircuitBuilderError> {
+        // parsed circuits do not set `num_nonfree_gates`, so count them
+        let before = self.nonfree_gates();
+        let mut c = self.fold()?;
+        c.eliminate_dead_gates();
+        *self = c;
+        Ok(before - self.num_nonfree_gates)
+    }
+
+    fn nonfree_gates(&self) -> usize {
+        self.gates
+            .iter()
+            .filter(|g| matches!(g, Gate::Mul { .. } | Gate::Proj { .. }))
+            .count()
+    }
+
+    // Rebuild the circuit, folding constants and identity gates as it goes.
+    fn fold(&self) -> Result<Circuit, CircuitBuilderError> {
+        let mut b = CircuitBuilder::new();
+        let mut d = Dummy::new();
+        let nwires = self
+            .gates
+            .iter()
+            .enumerate()
+            .map(|(i, g)| g.out().unwrap_or(i) + 1)
+            .max()
+            .unwrap_or(0);
+        let mut vals: Vec<Option<Val>> = vec![None; nwires];
+        let mut garbler_inputs = Vec::new();
+        let mut evaluator_inputs = Vec::new();
+
+        for (i, gate) in self.gates.iter().enumerate() {
+            let q = self.gate_moduli[i];
+            let val = |r: &CircuitRef| vals[r.ix].ok_or_else(|| self.uninitialized(r.ix));
+            let v = match *gate {
+                Gate::GarblerInput { id } => {
+                    let r = b.gate(Gate::GarblerInput { id }, &q);
+                    garbler_inputs.push((id, r));
+                    Val::Wire(r)
+                }
+                Gate::EvaluatorInput { id } => {
+                    let r = b.gate(Gate::EvaluatorInput { id }, &q);
+                    evaluator_inputs.push((id, r));
+                    Val::Wire(r)
+                }
+                Gate::Constant { val } => Val::Const(val % q.order() as u64, q),
+                Gate::Add { xref, yref, .. } => match (val(&xref)?, val(&yref)?) {
+                    (Val::Const(x, q), Val::Const(y, _)) => {
+                        let z = d
+                            .add(&DummyVal::new_big(x, q), &DummyVal::new_big(y, q))
+                            .map_err(plain)?;
+                        Val::Const(z.val_big(), q)
+                    }
+                    (x, Val::Const(0, _)) | (Val::Const(0, _), x) => x,
+                    (x, y) => {
+                        let (x, y) = (wire(&mut b, x)?, wire(&mut b, y)?);
+                        Val::Wire(b.add(&x, &y)?)
+                    }
+                },
+                Gate::Sub { xref, yref, .. } => match (val(&xref)?, val(&yref)?) {
+                    (Val::Const(x, q), Val::Const(y, _)) => {
+                        let z = d
+                            .sub(&DummyVal::new_big(x, q), &DummyVal::new_big(y, q))
+                            .map_err(plain)?;
+                        Val::Const(z.val_big(), q)
+                    }
+                    (x, Val::Const(0, _)) => x,
+                    (x, y) => {
+                        let (x, y) = (wire(&mut b, x)?, wire(&mut b, y)?);
+                        Val::Wire(b.sub(&x, &y)?)
+                    }
+                },
+                Gate::Cmul { xref, c, .. } => cmul(&mut b, val(&xref)?, c)?,
+                Gate::Mul { xref, yref, .. } => match (val(&xref)?, val(&yref)?) {
+                    (Val::Const(x, qx), Val::Const(y, qy)) => {
+                        let (x, y) = (DummyVal::new_big(x, qx), DummyVal::new_big(y, qy));
+                        let z = if qx.order() < qy.order() {
+                            d.mul(&y, &x)
+                        } else {
+                            d.mul(&x, &y)
+                        };
+                        let z = z.map_err(plain)?;
+                        Val::Const(z.val_big(), q)
+                    }
+                    (x, Val::Const(c, qc)) | (Val::Const(c, qc), x) if modulus(&x) == qc => {
+                        cmul(&mut b, x, c)?
+                    }
+                    (x, y) => {
+                        let (x, y) = (wire(&mut b, x)?, wire(&mut b, y)?);
+                        Val::Wire(b.mul(&x, &y)?)
+                    }
+                },
+                Gate::Proj { xref, ref tt, .. } => match val(&xref)? {
+                    Val::Const(x, _) => Val::Const(tt[x as usize] as u64, q),
+                    x => {
+                        let x = wire(&mut b, x)?;
+                        Val::Wire(b.proj(&x, &q, Some(tt.clone()))?)
+                    }
+                },
+            };
+            vals[gate.out().unwrap_or(i)] = Some(v);
+        }
+
+        for r in self.output_refs.iter() {
+            let v = vals[r.ix].ok_or_else(|| self.uninitialized(r.ix))?;
+            let z = wire(&mut b, v)?;
+            if let Some(name) = self.names.get(&r.ix) {
+                b.circ.names.entry(z.ix).or_insert_with(|| name.clone());
+            }
+            b.circ.output_refs.push(z);
+        }
+        // names of wires which were folded into constants are dropped
+        for (ix, name) in self.names.iter() {
+            if let Some(Val::Wire(r)) = vals.get(*ix).copied().flatten() {
+                b.circ.names.entry(r.ix).or_insert_with(|| name.clone());
+            }
+        }
+        garbler_inputs.sort_by_key(|&(id, _)| id);
+        evaluator_inputs.sort_by_key(|&(id, _)| id);
+        b.circ.garbler_input_refs = garbler_inputs.into_iter().map(|(_, r)| r).collect();
+        b.circ.evaluator_input_refs = evaluator_inputs.into_iter().map(|(_, r)| r).collect();
+        Ok(b.finish())
+    }
+
+    // Remove the gates which no output depends on, keeping inputs.
+    fn eliminate_dead_gates(&mut self) {
+        let n = self.gates.len();
+        let mut live = vec![false; n];
+        for r in self.output_refs.iter() {
+            live[r.ix] = true;
+        }
+        for i in (0..n).rev() {
+            match self.gates[i] {
+                Gate::GarblerInput { .. } | Gate::EvaluatorInput { .. } => live[i] = true,
+                _ if !live[i] => (),
+                Gate::Constant { .. } => (),
+                Gate::Add { xref, yref, .. }
+                | Gate::Sub { xref, yref, .. }
+                | Gate::Mul { xref, yref, .. } => {
+                    live[xref.ix] = true;
+                    live[yref.ix] = true;
+                }
+                Gate::Cmul { xref, .. } | Gate::Proj { xref, .. } => live[xref.ix] = true,
+            }
+        }
+
+        // new index of every live gate
+        let mut ixs = vec![0; n];
+        let mut next = 0;
+        for i in 0..n {
+            ixs[i] = next;
+            next += live[i] as usize;
+        }
+        let remap = |r: &mut CircuitRef| r.ix = ixs[r.ix];
+
+        let gates = core::mem::take(&mut self.gates);
+        let moduli = core::mem::take(&mut self.gate_moduli);
+        self.num_nonfree_gates = 0;
+        for ((mut gate, q), _) in gates
+            .into_iter()
+            .zip(moduli)
+            .zip(live.iter())
+            .filter(|(_, &l)| l)
+        {
+            match gate {
+                Gate::Add {
+                    ref mut xref,
+                    ref mut yref,
+                    ..
+                }
+                | Gate::Sub {
+                    ref mut xref,
+                    ref mut yref,
+                    ..
+                } => {
+                    remap(xref);
+                    remap(yref);
+                }
+                Gate::Cmul { ref mut xref, .. } => remap(xref),
+                Gate::Mul {
+                    ref mut xref,
+                    ref mut yref,
+                    ref mut id,
+                    ..
+                } => {
+                    remap(xref);
+                    remap(yref);
+                    *id = self.num_nonfree_gates;
+                    self.num_nonfree_gates += 1;
+                }
+                Gate::Proj {
+                    ref mut xref,
+                    ref mut id,
+                    ..
+                } => {
+                    remap(xref);
+                    *id = self.num_nonfree_gates;
+                    self.num_nonfree_gates += 1;
+                }
+                _ => (),
+            }
+            self.gates.push(gate);
+            self.gate_moduli.push(q);
+        }
+        self.const_refs.retain(|r| live[r.ix]);
+        for r in self
+            .garbler_input_refs
+            .iter_mut()
+            .chain(self.evaluator_input_refs.iter_mut())
+            .chain(self.const_refs.iter_mut())
+            .chain(self.output_refs.iter_mut())
+        {
+            remap(r);
+        }
+        self.names = core::mem::take(&mut self.names)
+            .into_iter()
+            .filter(|(ix, _)| live[*ix])
+            .map(|(ix, name)| (ixs[ix], name))
+            .collect();
+    }
+}
+
+impl Gate {
+    // The wire written by the gate, if it is not the gate index.
+    pub(super) fn out(&self) -> Option<usize> {
+        match *self {
+            Gate::Add { out, .. }
+            | Gate::Sub { out, .. }
+            | Gate::Cmul { out, .. }
+            | Gate::Mul { out, .. }
+            | Gate::Proj { out, .. } => out,
+            _ => None,
+        }
+    }
+}
+
+fn modulus(v: &Val) -> Modulus {
+    match v {
+        Val::Const(_, q) => *q,
+        Val::Wire(r) => r.modulus(),
+    }
+}
+
+// A wire carrying `v`, adding a constant gate if needed.
+fn wire(b: &mut CircuitBuilder, v: Val) -> Result<CircuitRef, CircuitBuilderError> {
+    match v {
+        Val::Const(c, q) => b.constant_big(c, &q),
+        Val::Wire(r) => Ok(r),
+    }
+}
+
+fn cmul(b: &mut CircuitBuilder, x: Val, c: u64) -> Result<Val, CircuitBuilderError> {
+    let q = modulus(&x);
+    Ok(match (x, c % q.order() as u64) {
+        (Val::Const(x, _), c) => {
+            let z = Dummy::new()
+                .cmul_big(&DummyVal::new_big(x, q), c)
+                .map_err(plain)?;
+            Val::Const(z.val_big(), q)
+        }
+        (_, 0) => Val::Const(0, q),
+        (x, 1) => x,
+        (Val::Wire(r), c) => Val::Wire(b.cmul_big(&r, c)?),
+    })
+}
+
+// Errors folding constants are those of the gate itself.
+fn plain(e: DummyError) -> CircuitBuilderError {
+    match e {
+        DummyError::FancyError(e) => CircuitBuilderError::from(e),
+        e => CircuitBuilderError::from(FancyError::InvalidArg(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{random, RandomCircuitConfig},
+        util::RngExt,
+    };
+    use rand::thread_rng;
+
+    // check that `c` and its optimization agree on random inputs
+    fn check_equivalent(c: &Circuit) -> Circuit {
+        let mut rng = thread_rng();
+        let mut opt = c.clone();
+        let removed = opt.optimize().unwrap();
+        assert_eq!(c.nonfree_gates() - opt.nonfree_gates(), removed);
+        assert_eq!(opt.nonfree_gates(), opt.num_nonfree_gates);
+        assert!(opt.gates.len() <= c.gates.len());
+        for _ in 0..16 {
+            let gb = (0..c.num_garbler_inputs())
+                .map(|i| rng.gen_u16() % c.garbler_input_mod(i).size())
+                .collect::<Vec<_>>();
+            let ev = (0..c.num_evaluator_inputs())
+                .map(|i| rng.gen_u16() % c.evaluator_input_mod(i).size())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                opt.eval_plain(&gb, &ev).unwrap(),
+                c.eval_plain(&gb, &ev).unwrap()
+            );
+        }
+        opt
+    }
+
+    #[test]
+    fn random_circuits() {
+        let mut rng = thread_rng();
+        for _ in 0..32 {
+            let config = RandomCircuitConfig {
+                ngates: 256,
+                ..Default::default()
+            };
+            check_equivalent(&random(&config, rng.gen_block()).unwrap());
+        }
+    }
+
+    #[test]
+    fn folds_and_eliminates() {
+        let q = Modulus::Zq { q: 17 };
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = b.evaluator_input(&q);
+        let zero = b.constant(0, &q).unwrap();
+        let three = b.constant(3, &q).unwrap();
+        let five = b.constant(5, &q).unwrap();
+        // (3 + 5) * x, which is free once folded
+        let eight = b.add(&three, &five).unwrap();
+        let z = b.mul(&eight, &x).unwrap();
+        // ((z + 0) * 1) - 0
+        let z = b.add(&z, &zero).unwrap();
+        let z = b.cmul(&z, 1).unwrap();
+        let z = b.sub(&z, &zero).unwrap();
+        b.output(&z).unwrap();
+        // a projection of a constant, and one which is never output
+        let w = b.proj(&five, &q, Some((0..17).rev().collect())).unwrap();
+        b.output(&w).unwrap();
+        let _ = b.mul(&x, &y).unwrap();
+        let c = b.finish();
+
+        let opt = check_equivalent(&c);
+        assert_eq!(c.num_nonfree_gates, 3);
+        assert_eq!(opt.num_nonfree_gates, 0);
+        // the inputs, the scalar multiplication, and the folded constant
+        assert_eq!(opt.gates.len(), 4);
+        assert_eq!(opt.eval_plain(&[2], &[0]).unwrap(), vec![16, 11]);
+    }
+
+    #[test]
+    fn parsed_circuit() {
+        let c = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        let opt = check_equivalent(&c);
+        assert_eq!(opt.num_nonfree_gates, c.nonfree_gates());
+        assert_eq!(opt.output_refs.len(), c.output_refs.len());
+    }
+}
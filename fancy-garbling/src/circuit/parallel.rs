@@ -0,0 +1,137 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Parallel evaluation of circuits, one topological level at a time.
+
+use super::{Circuit, Gate};
+use crate::fancy::Fancy;
+use rayon::prelude::*;
+
+impl Circuit {
+    /// Group the gates of the circuit into levels, such that every gate only
+    /// depends on wires defined in earlier levels. Inputs and constants are in
+    /// the first level.
+    pub fn levels(&self) -> Vec<Vec<usize>> {
+        let mut depth = vec![0; self.gates.len()];
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+        for (i, gate) in self.gates.iter().enumerate() {
+            let d = match *gate {
+                Gate::GarblerInput { .. } | Gate::EvaluatorInput { .. } | Gate::Constant { .. } => {
+                    0
+                }
+                Gate::Add { xref, yref, .. }
+                | Gate::Sub { xref, yref, .. }
+                | Gate::Mul { xref, yref, .. } => 1 + depth[xref.ix].max(depth[yref.ix]),
+                Gate::Cmul { xref, .. } | Gate::Proj { xref, .. } => 1 + depth[xref.ix],
+            };
+            depth[gate.out().unwrap_or(i)] = d;
+            if levels.len() <= d {
+                levels.resize_with(d + 1, Vec::new);
+            }
+            levels[d].push(i);
+        }
+        levels
+    }
+
+    /// Evaluate the circuit using fancy object `f`, evaluating the gates of
+    /// each level in parallel.
+    ///
+    /// Every worker thread evaluates gates on its own clone of `f`, so this is
+    /// only meaningful for `Fancy` objects whose results do not depend on
+    /// internal state, such as `Dummy`.
+    pub fn eval_parallel<F>(
+        &self,
+        f: &F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Option<Vec<u16>>, F::Error>
+    where
+        F: Fancy + Clone + Send + Sync,
+        F::Item: Send + Sync,
+        F::Error: Send,
+    {
+        let mut cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        for level in self.levels() {
+            let vals = level
+                .par_iter()
+                .map_init(
+                    || f.clone(),
+                    |f, &i| self.eval_gate(f, i, &cache, garbler_inputs, evaluator_inputs),
+                )
+                .collect::<Result<Vec<_>, F::Error>>()?;
+            for (zref, val) in vals {
+                cache[zref] = Some(val);
+            }
+        }
+        let mut f = f.clone();
+        let mut outputs = Vec::with_capacity(self.output_refs.len());
+        for r in self.output_refs.iter() {
+            let r = self.cached(&cache, r)?;
+            outputs.push(f.output(r)?);
+        }
+        Ok(outputs.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        circuit::{random, Circuit, CircuitBuilder, RandomCircuitConfig},
+        dummy::{Dummy, DummyVal},
+        fancy::Fancy,
+        util::RngExt,
+        Modulus,
+    };
+    use rand::{thread_rng, Rng};
+    use scuttlebutt::Block;
+
+    fn check_parallel(c: &Circuit) {
+        let mut rng = thread_rng();
+        let gb = (0..c.num_garbler_inputs())
+            .map(|i| rng.gen_u16() % c.garbler_input_mod(i).size())
+            .collect::<Vec<_>>();
+        let ev = (0..c.num_evaluator_inputs())
+            .map(|i| rng.gen_u16() % c.evaluator_input_mod(i).size())
+            .collect::<Vec<_>>();
+        let xs = (0..gb.len())
+            .map(|i| DummyVal::new(gb[i], c.garbler_input_mod(i)))
+            .collect::<Vec<_>>();
+        let ys = (0..ev.len())
+            .map(|i| DummyVal::new(ev[i], c.evaluator_input_mod(i)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            c.eval_parallel(&Dummy::new(), &xs, &ys).unwrap().unwrap(),
+            c.eval_plain(&gb, &ev).unwrap()
+        );
+    }
+
+    #[test]
+    fn levels() {
+        let q = Modulus::Zq { q: 5 };
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = b.evaluator_input(&q);
+        let z = b.add(&x, &y).unwrap();
+        let z = b.mul(&z, &x).unwrap();
+        let w = b.cmul(&y, 2).unwrap();
+        b.outputs(&[z, w]).unwrap();
+        let c = b.finish();
+        assert_eq!(c.levels(), vec![vec![0, 1], vec![2, 4], vec![3]]);
+    }
+
+    #[test]
+    fn random_circuits() {
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            check_parallel(&random(&RandomCircuitConfig::default(), rng.gen::<Block>()).unwrap());
+        }
+    }
+
+    #[test]
+    fn parsed_circuit() {
+        check_parallel(&Circuit::parse("circuits/AES-non-expanded.txt").unwrap());
+    }
+}
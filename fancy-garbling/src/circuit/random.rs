@@ -0,0 +1,281 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Seeded generator of random circuits, for property tests, fuzzing, and
+//! benchmarks.
+
+use super::{Circuit, CircuitBuilder, CircuitRef};
+use crate::{
+    errors::{CircuitBuilderError, FancyError},
+    fancy::{Fancy, HasModulus},
+    wire::Modulus,
+};
+use alloc::{string::ToString, vec::Vec};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use scuttlebutt::{AesRng, Block};
+
+/// Relative frequencies of the gate types in a random circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GateWeights {
+    /// Weight of constant gates.
+    pub constant: u32,
+    /// Weight of addition gates.
+    pub add: u32,
+    /// Weight of subtraction gates.
+    pub sub: u32,
+    /// Weight of constant multiplication gates.
+    pub cmul: u32,
+    /// Weight of multiplication gates.
+    pub mul: u32,
+    /// Weight of projection gates, whose output modulus is drawn from the
+    /// configured moduli.
+    pub proj: u32,
+}
+
+impl Default for GateWeights {
+    fn default() -> Self {
+        GateWeights {
+            constant: 1,
+            add: 4,
+            sub: 2,
+            cmul: 2,
+            mul: 2,
+            proj: 2,
+        }
+    }
+}
+
+/// Shape of the circuits produced by `random`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RandomCircuitConfig {
+    /// Moduli to draw wires from. Inputs are assigned them round-robin.
+    pub moduli: Vec<Modulus>,
+    /// Number of garbler inputs.
+    pub ngarbler_inputs: usize,
+    /// Number of evaluator inputs.
+    pub nevaluator_inputs: usize,
+    /// Number of gates after the inputs.
+    pub ngates: usize,
+    /// Number of outputs.
+    pub noutputs: usize,
+    /// Maximum number of nonfree gates on any path. When set, operands of
+    /// nonfree gates are biased towards the deepest eligible wires, so that
+    /// the bound is reached given enough nonfree gates.
+    pub depth: Option<usize>,
+    /// Frequencies of the gate types.
+    pub weights: GateWeights,
+}
+
+impl Default for RandomCircuitConfig {
+    fn default() -> Self {
+        RandomCircuitConfig {
+            moduli: vec![
+                Modulus::Zq { q: 2 },
+                Modulus::Zq { q: 3 },
+                Modulus::Zq { q: 17 },
+                Modulus::X4_X_1,
+                Modulus::GF8_MODULI[0],
+            ],
+            ngarbler_inputs: 4,
+            nevaluator_inputs: 4,
+            ngates: 64,
+            noutputs: 4,
+            depth: None,
+            weights: GateWeights::default(),
+        }
+    }
+}
+
+enum Kind {
+    Constant,
+    Add,
+    Sub,
+    Cmul,
+    Mul,
+    Proj,
+}
+
+/// Generate a random valid circuit shaped by `config`. The same `config` and
+/// `seed` always yield the same circuit.
+pub fn random(config: &RandomCircuitConfig, seed: Block) -> Result<Circuit, CircuitBuilderError> {
+    if config.moduli.is_empty() || config.ngarbler_inputs + config.nevaluator_inputs == 0 {
+        return Err(CircuitBuilderError::from(FancyError::InvalidArg(
+            "random circuits need at least one modulus and one input".to_string(),
+        )));
+    }
+    let w = &config.weights;
+    let kinds = [
+        (Kind::Constant, w.constant),
+        (Kind::Add, w.add),
+        (Kind::Sub, w.sub),
+        (Kind::Cmul, w.cmul),
+        (Kind::Mul, w.mul),
+        (Kind::Proj, w.proj),
+    ];
+    if kinds.iter().all(|(_, w)| *w == 0) {
+        return Err(CircuitBuilderError::from(FancyError::InvalidArg(
+            "all gate weights are zero".to_string(),
+        )));
+    }
+    let max_depth = config.depth.unwrap_or(usize::MAX);
+
+    let mut rng = AesRng::from_seed(seed);
+    let mut b = CircuitBuilder::new();
+    // every wire alongside its nonfree depth
+    let mut wires: Vec<(CircuitRef, usize)> = Vec::new();
+    let mut qs = config.moduli.iter().cycle();
+    for _ in 0..config.ngarbler_inputs {
+        wires.push((b.garbler_input(qs.next().unwrap()), 0));
+    }
+    for _ in 0..config.nevaluator_inputs {
+        wires.push((b.evaluator_input(qs.next().unwrap()), 0));
+    }
+
+    for _ in 0..config.ngates {
+        let (x, dx) = *wires.choose(&mut rng).unwrap();
+        let q = x.modulus();
+        let size = q.size();
+        let kind = &kinds.choose_weighted(&mut rng, |(_, w)| *w).unwrap().0;
+        let y = *wires
+            .iter()
+            .filter(|(y, _)| y.modulus() == q)
+            .collect::<Vec<_>>()
+            .choose(&mut rng)
+            .unwrap();
+        let z = match kind {
+            Kind::Constant => {
+                let q = *config.moduli.choose(&mut rng).unwrap();
+                (b.constant(rng.gen::<u16>() % q.size(), &q)?, 0)
+            }
+            Kind::Add => (b.add(&x, &y.0)?, dx.max(y.1)),
+            Kind::Sub => (b.sub(&x, &y.0)?, dx.max(y.1)),
+            Kind::Cmul => (b.cmul(&x, rng.gen::<u16>() % size)?, dx),
+            Kind::Mul | Kind::Proj => {
+                let eligible = wires
+                    .iter()
+                    .filter(|(w, d)| w.modulus() == q && *d < max_depth)
+                    .collect::<Vec<_>>();
+                if eligible.is_empty() {
+                    (b.add(&x, &y.0)?, dx.max(y.1))
+                } else {
+                    let deepest = eligible.iter().map(|(_, d)| *d).max().unwrap();
+                    let pick = |rng: &mut AesRng| -> (CircuitRef, usize) {
+                        if config.depth.is_some() && rng.gen() {
+                            **eligible.iter().rev().find(|(_, d)| *d == deepest).unwrap()
+                        } else {
+                            **eligible.choose(rng).unwrap()
+                        }
+                    };
+                    let (x, dx) = pick(&mut rng);
+                    match kind {
+                        Kind::Mul => {
+                            let (y, dy) = pick(&mut rng);
+                            (b.mul(&x, &y)?, dx.max(dy) + 1)
+                        }
+                        _ => {
+                            let out = *config.moduli.choose(&mut rng).unwrap();
+                            let tt = (0..size)
+                                .map(|_| rng.gen::<u16>() % out.size())
+                                .collect();
+                            (b.proj(&x, &out, Some(tt))?, dx + 1)
+                        }
+                    }
+                }
+            }
+        };
+        wires.push(z);
+    }
+
+    // the deepest wire is always an output, the rest are drawn at random
+    let deepest = *wires.iter().rev().max_by_key(|(_, d)| *d).unwrap();
+    let mut outputs = vec![deepest.0];
+    while outputs.len() < config.noutputs {
+        outputs.push(wires.choose(&mut rng).unwrap().0);
+    }
+    outputs.truncate(config.noutputs);
+    b.outputs(&outputs)?;
+    Ok(b.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::Gate, classic::garble, util::RngExt};
+    use rand::thread_rng;
+
+    // nonfree depth of every gate of `c`
+    fn depths(c: &Circuit) -> Vec<usize> {
+        let mut ds: Vec<usize> = Vec::with_capacity(c.gates.len());
+        for g in c.gates.iter() {
+            let d = match *g {
+                Gate::Add { xref, yref, .. } | Gate::Sub { xref, yref, .. } => {
+                    ds[xref.ix].max(ds[yref.ix])
+                }
+                Gate::Cmul { xref, .. } => ds[xref.ix],
+                Gate::Mul { xref, yref, .. } => ds[xref.ix].max(ds[yref.ix]) + 1,
+                Gate::Proj { xref, .. } => ds[xref.ix] + 1,
+                _ => 0,
+            };
+            ds.push(d);
+        }
+        ds
+    }
+
+    #[test]
+    fn reproducible() {
+        let config = RandomCircuitConfig::default();
+        let seed = thread_rng().gen::<Block>();
+        let c1 = random(&config, seed).unwrap();
+        let c2 = random(&config, seed).unwrap();
+        assert_eq!(c1.gates, c2.gates);
+        assert_eq!(c1.output_refs, c2.output_refs);
+        let c3 = random(&config, thread_rng().gen::<Block>()).unwrap();
+        assert_ne!(c1.gates, c3.gates);
+    }
+
+    #[test]
+    fn depth_target() {
+        let mut rng = thread_rng();
+        for depth in 1..6 {
+            let config = RandomCircuitConfig {
+                ngates: 256,
+                depth: Some(depth),
+                ..Default::default()
+            };
+            let c = random(&config, rng.gen::<Block>()).unwrap();
+            let ds = depths(&c);
+            assert_eq!(ds.iter().max(), Some(&depth));
+            assert_eq!(ds[c.output_refs[0].ix], depth);
+        }
+    }
+
+    #[test]
+    fn garbles_correctly() {
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let c = random(&RandomCircuitConfig::default(), rng.gen::<Block>()).unwrap();
+            let gb = (0..c.num_garbler_inputs())
+                .map(|i| rng.gen_u16() % c.garbler_input_mod(i).size())
+                .collect::<Vec<_>>();
+            let ev = (0..c.num_evaluator_inputs())
+                .map(|i| rng.gen_u16() % c.evaluator_input_mod(i).size())
+                .collect::<Vec<_>>();
+            let (en, gc) = garble(&c).unwrap();
+            let xs = en.encode_garbler_inputs(&gb);
+            let ys = en.encode_evaluator_inputs(&ev);
+            assert_eq!(gc.eval(&c, &xs, &ys).unwrap(), c.eval_plain(&gb, &ev).unwrap());
+        }
+    }
+
+    #[test]
+    fn rejects_empty_config() {
+        let config = RandomCircuitConfig {
+            moduli: vec![],
+            ..Default::default()
+        };
+        assert!(random(&config, Block::default()).is_err());
+    }
+}
@@ -0,0 +1,523 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Compact, versioned binary encoding of circuits.
+//!
+//! An encoding is the magic bytes `FGCIRC`, a version byte, and a sequence of
+//! sections, each a varint tag, a varint byte length, and its payload. Readers
+//! skip sections with unknown tags, so later versions can add sections without
+//! breaking older readers. Integers are LEB128 varints, moduli are stored once
+//! and referred to by index, and so are repeated projection truth tables.
+
+use super::{Circuit, CircuitRef, Gate};
+use crate::wire::Modulus;
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind, Read, Result, Write},
+};
+
+const MAGIC: &[u8; 6] = b"FGCIRC";
+const VERSION: u8 = 1;
+
+const SECTION_MODULI: u64 = 1;
+const SECTION_TRUTH_TABLES: u64 = 2;
+const SECTION_GATES: u64 = 3;
+const SECTION_REFS: u64 = 4;
+// Optional, and only written for circuits with named wires.
+const SECTION_NAMES: u64 = 5;
+
+const GATE_GARBLER_INPUT: u8 = 0;
+const GATE_EVALUATOR_INPUT: u8 = 1;
+const GATE_CONSTANT: u8 = 2;
+const GATE_ADD: u8 = 3;
+const GATE_SUB: u8 = 4;
+const GATE_CMUL: u8 = 5;
+const GATE_MUL: u8 = 6;
+const GATE_PROJ: u8 = 7;
+
+impl Circuit {
+    /// Write the circuit to `writer` in a compact binary format, which `load`
+    /// reads back.
+    pub fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        let mut enc = Encoder::default();
+        for q in self.gate_moduli.iter() {
+            enc.modulus(q);
+        }
+        let mut gates = Vec::new();
+        for (gate, q) in self.gates.iter().zip(self.gate_moduli.iter()) {
+            let q = enc.modulus(q);
+            enc.gate(&mut gates, gate, q);
+        }
+        let mut refs = Vec::new();
+        for rs in [
+            &self.garbler_input_refs,
+            &self.evaluator_input_refs,
+            &self.const_refs,
+            &self.output_refs,
+        ]
+        .iter()
+        {
+            write_varint(&mut refs, rs.len() as u64);
+            for r in rs.iter() {
+                enc.circuit_ref(&mut refs, r);
+            }
+        }
+        write_varint(&mut refs, self.num_nonfree_gates as u64);
+
+        let mut moduli = Vec::new();
+        write_varint(&mut moduli, enc.moduli.len() as u64);
+        for q in enc.moduli.iter() {
+            q.write_bytes(&mut moduli);
+        }
+        let mut tts = Vec::new();
+        write_varint(&mut tts, enc.tts.len() as u64);
+        for tt in enc.tts.iter() {
+            write_varint(&mut tts, tt.len() as u64);
+            for &x in tt.iter() {
+                write_varint(&mut tts, x as u64);
+            }
+        }
+
+        let mut sections = vec![
+            (SECTION_MODULI, moduli),
+            (SECTION_TRUTH_TABLES, tts),
+            (SECTION_GATES, gates),
+            (SECTION_REFS, refs),
+        ];
+        if !self.names.is_empty() {
+            let mut names = Vec::new();
+            write_varint(&mut names, self.names.len() as u64);
+            for (ix, name) in self.names.iter() {
+                write_varint(&mut names, *ix as u64);
+                write_varint(&mut names, name.len() as u64);
+                names.extend_from_slice(name.as_bytes());
+            }
+            sections.push((SECTION_NAMES, names));
+        }
+
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        for (tag, section) in sections.iter() {
+            write_varint(&mut out, *tag);
+            write_varint(&mut out, section.len() as u64);
+            out.extend_from_slice(section);
+        }
+        writer.write_all(&out)
+    }
+
+    /// Read a circuit written by `save`.
+    ///
+    /// Returns an error of kind `InvalidData` if the encoding is malformed or
+    /// of a later version.
+    pub fn load<R: Read>(mut reader: R) -> Result<Circuit> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut r = Reader(&bytes);
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("not a circuit encoding"));
+        }
+        let version = r.byte()?;
+        if version > VERSION {
+            return Err(invalid(&format!(
+                "unsupported circuit encoding version {}",
+                version
+            )));
+        }
+        let mut sections = HashMap::new();
+        while !r.0.is_empty() {
+            let tag = r.varint()?;
+            let len = r.len()?;
+            sections.insert(tag, r.take(len)?);
+        }
+        let mut section = |tag| {
+            sections
+                .remove(&tag)
+                .map(Reader)
+                .ok_or_else(|| invalid(&format!("missing section {}", tag)))
+        };
+
+        let mut r = section(SECTION_MODULI)?;
+        let moduli = (0..r.len()?)
+            .map(|_| {
+                let (q, rest) = Modulus::read_bytes(r.0).map_err(|e| invalid(&e.to_string()))?;
+                r.0 = rest;
+                Ok(q)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut r = section(SECTION_TRUTH_TABLES)?;
+        let tts = (0..r.len()?)
+            .map(|_| (0..r.len()?).map(|_| r.u16()).collect::<Result<Vec<_>>>())
+            .collect::<Result<Vec<_>>>()?;
+
+        let dec = Decoder { moduli, tts };
+        let mut c = Circuit::new(None);
+        let mut r = section(SECTION_GATES)?;
+        while !r.0.is_empty() {
+            let (gate, q) = dec.gate(&mut r, c.gates.len())?;
+            c.gates.push(gate);
+            c.gate_moduli.push(q);
+        }
+        let mut r = section(SECTION_REFS)?;
+        for rs in [
+            &mut c.garbler_input_refs,
+            &mut c.evaluator_input_refs,
+            &mut c.const_refs,
+            &mut c.output_refs,
+        ]
+        .iter_mut()
+        {
+            for _ in 0..r.len()? {
+                rs.push(dec.circuit_ref(&mut r, c.gates.len())?);
+            }
+        }
+        c.num_nonfree_gates = r.len()?;
+        if let Some(names) = sections.remove(&SECTION_NAMES) {
+            let mut r = Reader(names);
+            for _ in 0..r.len()? {
+                let ix = r.len()?;
+                let len = r.len()?;
+                let name = std::str::from_utf8(r.take(len)?)
+                    .map_err(|_| invalid("wire name is not valid UTF-8"))?;
+                c.names.insert(ix, name.to_string());
+            }
+        }
+        Ok(c)
+    }
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        out.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+fn write_option(out: &mut Vec<u8>, x: Option<usize>) {
+    write_varint(out, x.map_or(0, |x| x as u64 + 1));
+}
+
+// Assigns indices to moduli and truth tables as they are first seen.
+#[derive(Default)]
+struct Encoder {
+    moduli: Vec<Modulus>,
+    modulus_ixs: HashMap<Modulus, u64>,
+    tts: Vec<Vec<u16>>,
+    tt_ixs: HashMap<Vec<u16>, u64>,
+}
+
+impl Encoder {
+    fn modulus(&mut self, q: &Modulus) -> u64 {
+        let moduli = &mut self.moduli;
+        *self.modulus_ixs.entry(*q).or_insert_with(|| {
+            moduli.push(*q);
+            moduli.len() as u64 - 1
+        })
+    }
+
+    fn truth_table(&mut self, tt: &[u16]) -> u64 {
+        if let Some(&ix) = self.tt_ixs.get(tt) {
+            return ix;
+        }
+        self.tts.push(tt.to_vec());
+        let ix = self.tts.len() as u64 - 1;
+        self.tt_ixs.insert(tt.to_vec(), ix);
+        ix
+    }
+
+    fn circuit_ref(&mut self, out: &mut Vec<u8>, r: &CircuitRef) {
+        write_varint(out, r.ix as u64);
+        write_varint(out, self.modulus(&r.modulus));
+    }
+
+    fn gate(&mut self, out: &mut Vec<u8>, gate: &Gate, q: u64) {
+        match gate {
+            Gate::GarblerInput { id } => {
+                out.push(GATE_GARBLER_INPUT);
+                write_varint(out, *id as u64);
+            }
+            Gate::EvaluatorInput { id } => {
+                out.push(GATE_EVALUATOR_INPUT);
+                write_varint(out, *id as u64);
+            }
+            Gate::Constant { val } => {
+                out.push(GATE_CONSTANT);
+                write_varint(out, *val);
+            }
+            Gate::Add { xref, yref, out: o } => {
+                out.push(GATE_ADD);
+                self.circuit_ref(out, xref);
+                self.circuit_ref(out, yref);
+                write_option(out, *o);
+            }
+            Gate::Sub { xref, yref, out: o } => {
+                out.push(GATE_SUB);
+                self.circuit_ref(out, xref);
+                self.circuit_ref(out, yref);
+                write_option(out, *o);
+            }
+            Gate::Cmul { xref, c, out: o } => {
+                out.push(GATE_CMUL);
+                self.circuit_ref(out, xref);
+                write_varint(out, *c);
+                write_option(out, *o);
+            }
+            Gate::Mul {
+                xref,
+                yref,
+                id,
+                out: o,
+            } => {
+                out.push(GATE_MUL);
+                self.circuit_ref(out, xref);
+                self.circuit_ref(out, yref);
+                write_varint(out, *id as u64);
+                write_option(out, *o);
+            }
+            Gate::Proj {
+                xref,
+                tt,
+                id,
+                out: o,
+            } => {
+                out.push(GATE_PROJ);
+                self.circuit_ref(out, xref);
+                let tt = self.truth_table(tt);
+                write_varint(out, tt);
+                write_varint(out, *id as u64);
+                write_option(out, *o);
+            }
+        }
+        write_varint(out, q);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.0.len() < n {
+            return Err(invalid("unexpected end of circuit encoding"));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut x = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            x |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(x);
+            }
+        }
+        Err(invalid("varint overflow"))
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        let x = self.varint()?;
+        if x > usize::MAX as u64 {
+            return Err(invalid("length overflow"));
+        }
+        Ok(x as usize)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let x = self.varint()?;
+        if x > u16::MAX as u64 {
+            return Err(invalid("truth table entry overflow"));
+        }
+        Ok(x as u16)
+    }
+
+    fn option(&mut self) -> Result<Option<usize>> {
+        Ok(self.len()?.checked_sub(1))
+    }
+}
+
+// Resolves the indices of an encoding into moduli and truth tables.
+struct Decoder {
+    moduli: Vec<Modulus>,
+    tts: Vec<Vec<u16>>,
+}
+
+impl Decoder {
+    fn modulus(&self, r: &mut Reader) -> Result<Modulus> {
+        let ix = r.len()?;
+        self.moduli
+            .get(ix)
+            .copied()
+            .ok_or_else(|| invalid("modulus index out of range"))
+    }
+
+    // A reference to one of the first `ngates` gates, which are the ones decoded
+    // so far, so that evaluating the circuit never indexes past its gates.
+    fn circuit_ref(&self, r: &mut Reader, ngates: usize) -> Result<CircuitRef> {
+        let ix = r.len()?;
+        if ix >= ngates {
+            return Err(invalid("gate reference out of range"));
+        }
+        let modulus = self.modulus(r)?;
+        Ok(CircuitRef { ix, modulus })
+    }
+
+    fn gate(&self, r: &mut Reader, ngates: usize) -> Result<(Gate, Modulus)> {
+        let gate = match r.byte()? {
+            GATE_GARBLER_INPUT => Gate::GarblerInput { id: r.len()? },
+            GATE_EVALUATOR_INPUT => Gate::EvaluatorInput { id: r.len()? },
+            GATE_CONSTANT => Gate::Constant { val: r.varint()? },
+            GATE_ADD => Gate::Add {
+                xref: self.circuit_ref(r, ngates)?,
+                yref: self.circuit_ref(r, ngates)?,
+                out: r.option()?,
+            },
+            GATE_SUB => Gate::Sub {
+                xref: self.circuit_ref(r, ngates)?,
+                yref: self.circuit_ref(r, ngates)?,
+                out: r.option()?,
+            },
+            GATE_CMUL => Gate::Cmul {
+                xref: self.circuit_ref(r, ngates)?,
+                c: r.varint()?,
+                out: r.option()?,
+            },
+            GATE_MUL => Gate::Mul {
+                xref: self.circuit_ref(r, ngates)?,
+                yref: self.circuit_ref(r, ngates)?,
+                id: r.len()?,
+                out: r.option()?,
+            },
+            GATE_PROJ => Gate::Proj {
+                xref: self.circuit_ref(r, ngates)?,
+                tt: self
+                    .tts
+                    .get(r.len()?)
+                    .cloned()
+                    .ok_or_else(|| invalid("truth table index out of range"))?,
+                id: r.len()?,
+                out: r.option()?,
+            },
+            tag => return Err(invalid(&format!("unknown gate tag {}", tag))),
+        };
+        Ok((gate, self.modulus(r)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{random, CircuitBuilder, RandomCircuitConfig};
+    use crate::fancy::Fancy;
+    use scuttlebutt::Block;
+
+    #[test]
+    fn round_trip() {
+        for seed in 0..16u128 {
+            let config = RandomCircuitConfig {
+                ngates: 512,
+                ..Default::default()
+            };
+            let c = random(&config, Block::from(seed)).unwrap();
+            let mut bytes = Vec::new();
+            c.save(&mut bytes).unwrap();
+            assert_eq!(Circuit::load(&bytes[..]).unwrap(), c);
+        }
+    }
+
+    #[test]
+    fn keeps_names() {
+        let q = Modulus::Zq { q: 7 };
+        let mut b = CircuitBuilder::new();
+        let x = b.named_garbler_input("key[3]", &q);
+        let y = b.named_evaluator_input("msg[0]", &q);
+        let z = b.mul(&x, &y).unwrap();
+        b.set_name(&z, "ctxt[0]");
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let mut bytes = Vec::new();
+        c.save(&mut bytes).unwrap();
+        let d = Circuit::load(&bytes[..]).unwrap();
+        assert_eq!(d, c);
+        assert_eq!(d.wire_name(&x), Some("key[3]"));
+        assert_eq!(d.wire_name(&z), Some("ctxt[0]"));
+    }
+
+    #[test]
+    fn dedups_truth_tables() {
+        let q = Modulus::Zq { q: 17 };
+        let tt = (0..17).rev().collect::<Vec<u16>>();
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = b.proj(&x, &q, Some(tt.clone())).unwrap();
+        b.output(&y).unwrap();
+        let mut bytes = Vec::new();
+        b.finish().save(&mut bytes).unwrap();
+        let single = bytes.len();
+
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        for _ in 0..100 {
+            let y = b.proj(&x, &q, Some(tt.clone())).unwrap();
+            b.output(&y).unwrap();
+        }
+        let mut bytes = Vec::new();
+        b.finish().save(&mut bytes).unwrap();
+        // each extra projection costs a handful of bytes, not a truth table
+        assert!(bytes.len() < single + 100 * 10);
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let c = random(&RandomCircuitConfig::default(), Block::default()).unwrap();
+        let mut bytes = Vec::new();
+        c.save(&mut bytes).unwrap();
+        assert!(Circuit::load(&bytes[..bytes.len() - 1]).is_err());
+        let mut later = bytes.clone();
+        later[MAGIC.len()] = VERSION + 1;
+        assert!(Circuit::load(&later[..]).is_err());
+        // unknown sections are skipped
+        let mut extended = bytes.clone();
+        extended.extend_from_slice(&[100, 3, 1, 2, 3]);
+        assert_eq!(Circuit::load(&extended[..]).unwrap(), c);
+        assert!(Circuit::load(&b"FGCIR"[..]).is_err());
+    }
+
+    #[test]
+    fn rejects_dangling_refs() {
+        let q = Modulus::Zq { q: 5 };
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let z = b.add(&x, &x).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let load = |c: &Circuit| {
+            let mut bytes = Vec::new();
+            c.save(&mut bytes).unwrap();
+            Circuit::load(&bytes[..])
+        };
+        assert_eq!(load(&c).unwrap(), c);
+
+        // a gate may only refer to the gates before it
+        let mut forward = c.clone();
+        if let Gate::Add { ref mut yref, .. } = forward.gates[1] {
+            yref.ix = 1;
+        }
+        assert!(load(&forward).is_err());
+        let mut outside = c.clone();
+        outside.output_refs[0].ix = 100;
+        assert!(load(&outside).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Statistics about the gates of a circuit and the size of its garbling.
+
+use super::{Circuit, Gate};
+use crate::{fancy::HasModulus, wire::Modulus};
+use alloc::{collections::BTreeMap, vec::Vec};
+
+/// The kind of a gate in a circuit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GateKind {
+    /// Garbler input.
+    GarblerInput,
+    /// Evaluator input.
+    EvaluatorInput,
+    /// Constant.
+    Constant,
+    /// Addition.
+    Add,
+    /// Subtraction.
+    Sub,
+    /// Scalar multiplication.
+    Cmul,
+    /// Multiplication.
+    Mul,
+    /// Projection.
+    Proj,
+}
+
+/// Statistics about a circuit, as returned by `Circuit::stats`.
+#[derive(Clone, Debug)]
+pub struct CircuitStats {
+    counts: BTreeMap<(GateKind, Modulus), usize>,
+    ciphertexts: Vec<usize>,
+    output_ciphertexts: usize,
+    depth: usize,
+    nonfree_depth: usize,
+}
+
+impl CircuitStats {
+    /// Number of gates of each kind and output modulus.
+    pub fn counts(&self) -> &BTreeMap<(GateKind, Modulus), usize> {
+        &self.counts
+    }
+
+    /// Number of gates of kind `kind`.
+    pub fn num_gates(&self, kind: GateKind) -> usize {
+        self.counts
+            .iter()
+            .filter(|((k, _), _)| *k == kind)
+            .map(|(_, n)| n)
+            .sum()
+    }
+
+    /// Number of gates with output modulus `q`.
+    pub fn num_gates_with_modulus(&self, q: &Modulus) -> usize {
+        self.counts
+            .iter()
+            .filter(|((_, m), _)| m == q)
+            .map(|(_, n)| n)
+            .sum()
+    }
+
+    /// Number of ciphertexts each gate contributes to the garbled circuit,
+    /// indexed by gate. Constants count one for the wire-label sent for them.
+    pub fn gate_ciphertexts(&self) -> &[usize] {
+        &self.ciphertexts
+    }
+
+    /// Number of ciphertexts for all gates.
+    pub fn num_ciphertexts(&self) -> usize {
+        self.ciphertexts.iter().sum()
+    }
+
+    /// Number of ciphertexts needed to decode the outputs.
+    pub fn num_output_ciphertexts(&self) -> usize {
+        self.output_ciphertexts
+    }
+
+    /// Length of the longest path from an input or constant to any gate.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Largest number of multiplications and projections on any path.
+    pub fn nonfree_depth(&self) -> usize {
+        self.nonfree_depth
+    }
+
+    /// Estimated size in bytes of the garbled circuit, including output
+    /// decoding information but not the encoded inputs.
+    pub fn garbled_size(&self) -> usize {
+        (self.num_ciphertexts() + self.num_output_ciphertexts()) * 16
+    }
+}
+
+impl Circuit {
+    /// Compute statistics about the circuit.
+    pub fn stats(&self) -> CircuitStats {
+        let n = self.gates.len();
+        let mut counts = BTreeMap::new();
+        let mut ciphertexts = Vec::with_capacity(n);
+        // depth and nonfree depth of every wire
+        let mut depths = vec![(0, 0); n];
+        let max = |(a, b): (usize, usize), (c, d): (usize, usize)| (a.max(c), b.max(d));
+        for (i, gate) in self.gates.iter().enumerate() {
+            // the kind of gate, its ciphertexts, and the depths of its operands
+            let (kind, cs, operands) = match *gate {
+                Gate::GarblerInput { .. } => (GateKind::GarblerInput, 0, None),
+                Gate::EvaluatorInput { .. } => (GateKind::EvaluatorInput, 0, None),
+                Gate::Constant { .. } => (GateKind::Constant, 1, None),
+                Gate::Add { xref, yref, .. } => (
+                    GateKind::Add,
+                    0,
+                    Some(max(depths[xref.ix], depths[yref.ix])),
+                ),
+                Gate::Sub { xref, yref, .. } => (
+                    GateKind::Sub,
+                    0,
+                    Some(max(depths[xref.ix], depths[yref.ix])),
+                ),
+                Gate::Cmul { xref, .. } => (GateKind::Cmul, 0, Some(depths[xref.ix])),
+                Gate::Mul { xref, yref, .. } => {
+                    let (qx, qy) = (xref.modulus(), yref.modulus());
+                    // an extra ciphertext is needed for unequal moduli
+                    let cs = qx.order() + qy.order() - 2 + (qx != qy) as u32;
+                    let ds = max(depths[xref.ix], depths[yref.ix]);
+                    (GateKind::Mul, cs as usize, Some(ds))
+                }
+                Gate::Proj { xref, .. } => {
+                    let cs = xref.modulus().order() - 1;
+                    (GateKind::Proj, cs as usize, Some(depths[xref.ix]))
+                }
+            };
+            if let Some((d, nd)) = operands {
+                let nonfree = matches!(kind, GateKind::Mul | GateKind::Proj);
+                depths[gate.out().unwrap_or(i)] = (d + 1, nd + nonfree as usize);
+            }
+            *counts.entry((kind, self.modulus(i))).or_insert(0) += 1;
+            ciphertexts.push(cs);
+        }
+        let output_ciphertexts = self
+            .output_refs
+            .iter()
+            .map(|r| match r.modulus() {
+                // outputs of `ZqBig` wires are decoded from a single block
+                Modulus::ZqBig { .. } => 1,
+                q => q.order() as usize,
+            })
+            .sum();
+        let (depth, nonfree_depth) = depths.into_iter().fold((0, 0), max);
+        CircuitStats {
+            counts,
+            ciphertexts,
+            output_ciphertexts,
+            depth,
+            nonfree_depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GateKind;
+    use crate::{
+        circuit::{Circuit, CircuitBuilder},
+        classic::garble,
+        fancy::Fancy,
+        Modulus,
+    };
+
+    #[test]
+    fn small_circuit() {
+        let (p, q) = (Modulus::Zq { q: 3 }, Modulus::Zq { q: 5 });
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = b.evaluator_input(&p);
+        let c = b.constant(1, &q).unwrap();
+        let z = b.mul(&x, &y).unwrap();
+        let z = b.add(&z, &c).unwrap();
+        let z = b.proj(&z, &p, Some(vec![0, 1, 2, 0, 1])).unwrap();
+        let w = b.cmul(&y, 2).unwrap();
+        b.outputs(&[z, w]).unwrap();
+        let circ = b.finish();
+
+        let stats = circ.stats();
+        assert_eq!(stats.num_gates(GateKind::Mul), 1);
+        assert_eq!(stats.num_gates(GateKind::Proj), 1);
+        assert_eq!(stats.num_gates(GateKind::Constant), 1);
+        assert_eq!(stats.counts()[&(GateKind::Add, q)], 1);
+        assert_eq!(stats.num_gates_with_modulus(&p), 3);
+        assert_eq!(stats.num_gates_with_modulus(&q), 4);
+        assert_eq!(stats.gate_ciphertexts(), &[0, 0, 1, 7, 0, 4, 0]);
+        assert_eq!(stats.num_output_ciphertexts(), 6);
+        assert_eq!(stats.depth(), 3);
+        assert_eq!(stats.nonfree_depth(), 2);
+        assert_eq!(stats.garbled_size(), 18 * 16);
+    }
+
+    #[test]
+    fn matches_garbling() {
+        let c = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        let stats = c.stats();
+        assert_eq!(stats.num_gates(GateKind::Mul), 6800);
+        assert!(stats.depth() >= stats.nonfree_depth());
+        let (_, gc) = garble(&c).unwrap();
+        assert_eq!(
+            stats.num_ciphertexts() + stats.num_output_ciphertexts(),
+            gc.size()
+        );
+    }
+}
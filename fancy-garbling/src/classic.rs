@@ -8,29 +8,53 @@
 //! circuit without streaming.
 
 use crate::{
-    circuit::Circuit,
-    errors::{EvaluatorError, GarblerError},
-    fancy::HasModulus,
-    garble::{Evaluator, Garbler},
+    circuit::{Circuit, CircuitRef, Gate},
+    errors::{EvaluatorError, FancyError, GarblerError, GateContext},
+    fancy::{Fancy, HasModulus},
+    garble::{Evaluator, GarbleMode, Garbler},
+    role::{self, HasRole},
     wire::{Wire,Modulus},
 };
 use itertools::Itertools;
+use rand::{Rng, SeedableRng};
 use scuttlebutt::{AbstractChannel, AesRng, Block, Channel};
-use std::{collections::HashMap, convert::TryInto, rc::Rc};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    io::{Error, ErrorKind, Read, Result as IoResult, Write},
+    ops::Range,
+    rc::Rc,
+};
+
+const GARBLED_CIRCUIT_MAGIC: &[u8; 6] = b"FGGARB";
+const ENCODER_MAGIC: &[u8; 6] = b"FGENCD";
+const ENCODING_VERSION: u8 = 1;
 
 /// Static evaluator for a circuit, created by the `garble` function.
 ///
 /// Uses `Evaluator` under the hood to actually implement the evaluation.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 pub struct GarbledCircuit {
     blocks: Vec<Block>,
+    mode: GarbleMode,
 }
 
 impl GarbledCircuit {
     /// Create a new object from a vector of garbled gates and constant wires.
     pub fn new(blocks: Vec<Block>) -> Self {
-        GarbledCircuit { blocks }
+        Self::with_mode(blocks, GarbleMode::Standard)
+    }
+
+    /// Create a new object from a vector of gates and constant wires garbled in
+    /// `mode`.
+    pub fn with_mode(blocks: Vec<Block>, mode: GarbleMode) -> Self {
+        GarbledCircuit { blocks, mode }
+    }
+
+    /// The mode the circuit was garbled in.
+    pub fn mode(&self) -> GarbleMode {
+        self.mode
     }
 
     /// The number of garbled rows and constant wires in the garbled circuit.
@@ -46,22 +70,115 @@ impl GarbledCircuit {
         evaluator_inputs: &[Wire],
     ) -> Result<Vec<u16>, EvaluatorError> {
         let channel = Channel::new(GarbledReader::new(&self.blocks), GarbledWriter::new(None));
-        let mut evaluator = Evaluator::new(channel);
+        let mut evaluator = Evaluator::with_mode(channel, self.mode).without_mode_announcement();
         let outputs = c.eval(&mut evaluator, garbler_inputs, evaluator_inputs)?;
         Ok(outputs.expect("evaluator outputs always are Some(u16)"))
     }
+
+    /// Evaluate the garbled circuit, with outputs which may not fit in a `u16`,
+    /// as needed for `ZqBig` outputs.
+    pub fn eval_big(
+        &self,
+        c: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<Vec<u64>, EvaluatorError> {
+        let channel = Channel::new(GarbledReader::new(&self.blocks), GarbledWriter::new(None));
+        let mut evaluator = Evaluator::with_mode(channel, self.mode).without_mode_announcement();
+        let outputs = c.eval_big(&mut evaluator, garbler_inputs, evaluator_inputs)?;
+        Ok(outputs.expect("evaluator outputs always are Some(u64)"))
+    }
+
+    /// Re-encrypt this garbling of `c` under fresh deltas and wire labels
+    /// derived from `seed`, giving an instance unlinkable to `self`, so that
+    /// the same function can be offered to many evaluators.
+    ///
+    /// Every row is encrypted under a hash of its input labels, so moving the
+    /// tables to fresh labels takes a hash per row however it is done. This
+    /// computes each row under the new labels directly rather than decrypting
+    /// the old one first; to rerandomize the same circuit repeatedly, a
+    /// `GarbleSession` flattens it only once. The same `seed` always yields the
+    /// same instance.
+    pub fn rerandomize(
+        &self,
+        c: &Circuit,
+        seed: Block,
+    ) -> Result<(Encoder, GarbledCircuit), GarblerError> {
+        let (en, gc) = GarbleSession::with_seed(c, self.mode, seed)?.regarble()?;
+        if gc.size() != self.size() {
+            return Err(GarblerError::FancyError(FancyError::InvalidArg(format!(
+                "circuit garbles to {} blocks but this garbled circuit has {}",
+                gc.size(),
+                self.size()
+            ))));
+        }
+        Ok((en, gc))
+    }
+
+    /// Write the garbled circuit to `writer`, so that it can be evaluated
+    /// elsewhere after `load`ing it.
+    pub fn save<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        let mut out = header(GARBLED_CIRCUIT_MAGIC);
+        out.push(self.mode.to_byte());
+        out.extend_from_slice(&(self.blocks.len() as u64).to_le_bytes());
+        for block in self.blocks.iter() {
+            out.extend_from_slice(block.as_ref());
+        }
+        writer.write_all(&out)
+    }
+
+    /// Read a garbled circuit written by `save`.
+    pub fn load<R: Read>(reader: R) -> IoResult<GarbledCircuit> {
+        let bytes = read_with_header(reader, GARBLED_CIRCUIT_MAGIC)?;
+        let mode = match bytes.first().and_then(|b| GarbleMode::from_byte(*b)) {
+            Some(mode) => mode,
+            None => return Err(invalid("unknown garbling mode")),
+        };
+        let (n, rest) = read_u64(&bytes[1..])?;
+        if rest.len() as u64 != n.saturating_mul(16) {
+            return Err(invalid("garbled circuit has the wrong length"));
+        }
+        let blocks = rest
+            .chunks(16)
+            .map(|b| {
+                let b: [u8; 16] = b.try_into().unwrap();
+                Block::from(b)
+            })
+            .collect();
+        Ok(GarbledCircuit { blocks, mode })
+    }
+}
+
+impl HasRole for GarbledCircuit {
+    type Role = role::Evaluator;
 }
 
 /// Garble a circuit without streaming.
 pub fn garble(c: &Circuit) -> Result<(Encoder, GarbledCircuit), GarblerError> {
+    garble_with_mode(c, GarbleMode::Standard)
+}
+
+/// Garble a circuit without streaming in `mode`. The garbled circuit records
+/// the mode, so that it is evaluated accordingly.
+pub fn garble_with_mode(
+    c: &Circuit,
+    mode: GarbleMode,
+) -> Result<(Encoder, GarbledCircuit), GarblerError> {
+    garble_with_rng(c, AesRng::new(), mode)
+}
+
+pub(crate) fn garble_with_rng(
+    c: &Circuit,
+    rng: AesRng,
+    mode: GarbleMode,
+) -> Result<(Encoder, GarbledCircuit), GarblerError> {
     let channel = Channel::new(
         GarbledReader::new(&[]),
         GarbledWriter::new(Some(c.num_nonfree_gates)),
     );
     let channel_ = channel.clone();
 
-    let rng = AesRng::new();
-    let mut garbler = Garbler::new(channel_, rng);
+    let mut garbler = Garbler::with_mode(channel_, rng, mode).without_mode_announcement();
 
     // get input wires, ignoring encoded values
     let gb_inps = (0..c.num_garbler_inputs())
@@ -80,20 +197,304 @@ pub fn garble(c: &Circuit) -> Result<(Encoder, GarbledCircuit), GarblerError> {
         })
         .collect_vec();
 
-    c.eval(&mut garbler, &gb_inps, &ev_inps)?;
+    // `eval_big` so that `ZqBig` outputs are garbled as well
+    c.eval_big(&mut garbler, &gb_inps, &ev_inps)?;
 
     let en = Encoder::new(gb_inps, ev_inps, garbler.get_deltas());         // Encoder has all zero wire labels for inputs + all deltas 
 
-    let gc = GarbledCircuit::new(                                          // This retrieves all blocks from the garbled circuit
+    let gc = GarbledCircuit::with_mode(                                    // This retrieves all blocks from the garbled circuit
         Rc::try_unwrap(channel.writer())
             .unwrap()
             .into_inner()
             .blocks,
+        mode,
     );
 
     Ok((en, gc))
 }
 
+/// Garbles the same circuit again and again, each time with fresh deltas and
+/// wire labels.
+///
+/// The circuit is flattened once, with the wires every gate reads resolved and
+/// checked and the truth tables of all projections in one buffer, so that each
+/// `regarble` only draws new randomness and hashes, e.g. for serving a fresh
+/// garbling of an AES oracle to every query.
+pub struct GarbleSession<'a> {
+    circuit: &'a Circuit,
+    mode: GarbleMode,
+    layout: Layout,
+    size: usize,
+    rng: AesRng,
+}
+
+impl<'a> GarbleSession<'a> {
+    /// Make a new session garbling `c` in `mode`.
+    pub fn new(c: &'a Circuit, mode: GarbleMode) -> Result<Self, GarblerError> {
+        Self::with_rng(c, mode, AesRng::new())
+    }
+
+    /// Make a new session garbling `c` in `mode`, drawing the randomness of
+    /// every garbling from `seed`. Sessions with the same seed produce the
+    /// same sequence of garbled circuits.
+    pub fn with_seed(c: &'a Circuit, mode: GarbleMode, seed: Block) -> Result<Self, GarblerError> {
+        Self::with_rng(c, mode, AesRng::from_seed(seed))
+    }
+
+    fn with_rng(c: &'a Circuit, mode: GarbleMode, rng: AesRng) -> Result<Self, GarblerError> {
+        let size = crate::estimate::estimate_circuit(c, mode)?.num_ciphertexts();
+        Ok(GarbleSession {
+            circuit: c,
+            mode,
+            layout: Layout::new(c)?,
+            size,
+            rng,
+        })
+    }
+
+    /// The circuit garbled by this session.
+    pub fn circuit(&self) -> &'a Circuit {
+        self.circuit
+    }
+
+    /// The number of blocks of every garbled circuit of this session.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Garble the circuit with fresh deltas and wire labels, unlinkable to
+    /// every other garbling.
+    pub fn regarble(&mut self) -> Result<(Encoder, GarbledCircuit), GarblerError> {
+        let rng = AesRng::from_seed(self.rng.gen());
+        self.layout.garble(rng, self.mode, GarbledWriter::with_capacity(self.size))
+    }
+}
+
+// A gate of a `Layout`, reading the wires at the given indices.
+enum Step {
+    GarblerInput(usize),
+    EvaluatorInput(usize),
+    Constant(u64, Modulus),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Cmul(usize, u64),
+    Mul(usize, usize),
+    // the truth table is this range of `Layout::tables`
+    Proj(usize, Modulus, Range<usize>),
+}
+
+// A circuit flattened for `GarbleSession`.
+struct Layout {
+    garbler_input_moduli: Vec<Modulus>,
+    evaluator_input_moduli: Vec<Modulus>,
+    // every gate, with the index of the wire it sets
+    steps: Vec<(Step, usize)>,
+    tables: Vec<u16>,
+    outputs: Vec<usize>,
+    nwires: usize,
+}
+
+impl Layout {
+    fn new(c: &Circuit) -> Result<Self, FancyError> {
+        // which wires are set so far, so that reading a wire before it is set
+        // fails here rather than in every garbling
+        let mut set: Vec<Option<()>> = vec![None; c.gates.len()];
+        let mut steps = Vec::with_capacity(c.gates.len());
+        let mut tables = Vec::new();
+        for (i, gate) in c.gates.iter().enumerate() {
+            let wire = |r: &CircuitRef| c.cached(&set, r).map(|_| r.ix).map_err(|e| e.at_gate(i));
+            let (step, out) = match *gate {
+                Gate::GarblerInput { id } => (Step::GarblerInput(id), None),
+                Gate::EvaluatorInput { id } => (Step::EvaluatorInput(id), None),
+                Gate::Constant { val } => (Step::Constant(val, c.modulus(i)), None),
+                Gate::Add { xref, yref, out } => (Step::Add(wire(&xref)?, wire(&yref)?), out),
+                Gate::Sub { xref, yref, out } => (Step::Sub(wire(&xref)?, wire(&yref)?), out),
+                Gate::Cmul { xref, c: k, out } => (Step::Cmul(wire(&xref)?, k), out),
+                Gate::Mul { xref, yref, out, .. } => (Step::Mul(wire(&xref)?, wire(&yref)?), out),
+                Gate::Proj { xref, ref tt, out, .. } => {
+                    let start = tables.len();
+                    tables.extend_from_slice(tt);
+                    (Step::Proj(wire(&xref)?, c.modulus(i), start..tables.len()), out)
+                }
+            };
+            let z = out.unwrap_or(i);
+            set[z] = Some(());
+            steps.push((step, z));
+        }
+        let outputs = c
+            .output_refs
+            .iter()
+            .map(|r| c.cached(&set, r).map(|_| r.ix))
+            .collect::<Result<_, _>>()?;
+        Ok(Layout {
+            garbler_input_moduli: (0..c.num_garbler_inputs())
+                .map(|i| c.garbler_input_mod(i))
+                .collect(),
+            evaluator_input_moduli: (0..c.num_evaluator_inputs())
+                .map(|i| c.evaluator_input_mod(i))
+                .collect(),
+            steps,
+            tables,
+            outputs,
+            nwires: c.gates.len(),
+        })
+    }
+
+    // Garble the circuit as `garble_with_rng` does, collecting the garbled
+    // circuit in `writer`.
+    fn garble(
+        &self,
+        rng: AesRng,
+        mode: GarbleMode,
+        writer: GarbledWriter,
+    ) -> Result<(Encoder, GarbledCircuit), GarblerError> {
+        let channel = Channel::new(GarbledReader::new(&[]), writer);
+        let mut garbler = Garbler::with_mode(channel.clone(), rng, mode).without_mode_announcement();
+
+        let gb_inps = self
+            .garbler_input_moduli
+            .iter()
+            .map(|q| garbler.encode_wire(0, q).0)
+            .collect_vec();
+        let ev_inps = self
+            .evaluator_input_moduli
+            .iter()
+            .map(|q| garbler.encode_wire(0, q).0)
+            .collect_vec();
+
+        let mut wires: Vec<Option<Wire>> = vec![None; self.nwires];
+        for (i, (step, z)) in self.steps.iter().enumerate() {
+            let w = |ix: usize| wires[ix].as_ref().expect("checked by `Layout::new`");
+            let val = match *step {
+                Step::GarblerInput(id) => Ok(gb_inps[id].clone()),
+                Step::EvaluatorInput(id) => Ok(ev_inps[id].clone()),
+                Step::Constant(val, q) => garbler.constant_big(val, &q),
+                Step::Add(x, y) => garbler.add(w(x), w(y)),
+                Step::Sub(x, y) => garbler.sub(w(x), w(y)),
+                Step::Cmul(x, k) => garbler.cmul_big(w(x), k),
+                Step::Mul(x, y) => garbler.mul(w(x), w(y)),
+                Step::Proj(x, q, ref table) => {
+                    garbler.proj_table(w(x), &q, &self.tables[table.clone()])
+                }
+            };
+            wires[*z] = Some(val.map_err(|e| e.at_gate(i))?);
+        }
+        for &r in self.outputs.iter() {
+            garbler.output_big(wires[r].as_ref().expect("checked by `Layout::new`"))?;
+        }
+
+        let en = Encoder::new(gb_inps, ev_inps, garbler.get_deltas());
+        let gc = GarbledCircuit::with_mode(
+            Rc::try_unwrap(channel.writer())
+                .unwrap()
+                .into_inner()
+                .blocks,
+            mode,
+        );
+        Ok((en, gc))
+    }
+}
+
+/// Garble a circuit without streaming in `mode` like `garble_with_mode`, but
+/// garble the gates of each topological level in parallel.
+///
+/// Every gate keeps its gate number and its ciphertexts their place in circuit
+/// order, so the result is evaluated with `GarbledCircuit::eval` as usual.
+#[cfg(feature = "parallel")]
+pub fn garble_parallel(
+    c: &Circuit,
+    mode: GarbleMode,
+) -> Result<(Encoder, GarbledCircuit), GarblerError> {
+    use rayon::prelude::*;
+
+    let mut rng = AesRng::new();
+    let channel = Channel::new(
+        GarbledReader::new(&[]),
+        GarbledWriter::new(Some(c.num_nonfree_gates)),
+    );
+    let mut garbler = Garbler::with_mode(channel.clone(), AesRng::from_seed(rng.gen()), mode)
+        .without_mode_announcement();
+
+    let gb_inps = (0..c.num_garbler_inputs())
+        .map(|i| garbler.encode_wire(0, &c.garbler_input_mod(i)).0)
+        .collect_vec();
+    let ev_inps = (0..c.num_evaluator_inputs())
+        .map(|i| garbler.encode_wire(0, &c.evaluator_input_mod(i)).0)
+        .collect_vec();
+
+    // every worker garbles with the same deltas
+    let deltas = c
+        .gate_moduli
+        .iter()
+        .unique()
+        .map(|q| (*q, garbler.delta(q)))
+        .collect::<HashMap<_, _>>();
+
+    // number the non-free gates in circuit order, as `Garbler` does
+    let mut gate_nums = vec![0; c.gates.len()];
+    let mut n = 0;
+    for (i, gate) in c.gates.iter().enumerate() {
+        if let Gate::Mul { .. } | Gate::Proj { .. } = gate {
+            gate_nums[i] = n;
+            n += 1;
+        }
+    }
+    let seeds = (0..c.gates.len()).map(|_| rng.gen::<Block>()).collect_vec();
+
+    let mut cache: Vec<Option<Wire>> = vec![None; c.gates.len()];
+    let mut garbled: Vec<(Vec<Block>, Vec<u8>)> = vec![(Vec::new(), Vec::new()); c.gates.len()];
+    for level in c.levels() {
+        let results = level
+            .par_iter()
+            .map_init(
+                || {
+                    let channel = Channel::new(GarbledReader::new(&[]), GarbledWriter::new(None));
+                    let garbler = Garbler::with_mode(channel.clone(), AesRng::new(), mode)
+                        .without_mode_announcement()
+                        .with_deltas(deltas.clone());
+                    (channel, garbler)
+                },
+                |(channel, garbler), &i| {
+                    garbler.seek(gate_nums[i], AesRng::from_seed(seeds[i]));
+                    let (zref, val) = c.eval_gate(garbler, i, &cache, &gb_inps, &ev_inps)?;
+                    let blocks = channel.clone().writer().borrow_mut().blocks.drain(..).collect();
+                    Ok((i, zref, val, (blocks, garbler.take_halves())))
+                },
+            )
+            .collect::<Result<Vec<_>, GarblerError>>()?;
+        for (i, zref, val, gate) in results {
+            cache[zref] = Some(val);
+            garbled[i] = gate;
+        }
+    }
+
+    // lay the gates out as the sequential garbler does
+    for (blocks, halves) in garbled {
+        if halves.is_empty() {
+            for block in blocks.iter() {
+                garbler.write_block(block)?;
+            }
+        } else {
+            let mut bytes = blocks.iter().flat_map(|b| u128::from(*b).to_le_bytes()).collect_vec();
+            bytes.extend(halves);
+            garbler.write_halves(&bytes)?;
+        }
+    }
+    for r in c.output_refs.iter() {
+        garbler.output_big(c.cached(&cache, r)?)?;
+    }
+
+    let en = Encoder::new(gb_inps, ev_inps, garbler.get_deltas());
+    let gc = GarbledCircuit::with_mode(
+        Rc::try_unwrap(channel.writer())
+            .unwrap()
+            .into_inner()
+            .blocks,
+        mode,
+    );
+    Ok((en, gc))
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Encoder
 
@@ -162,6 +563,112 @@ impl Encoder {
             .map(|(id, &x)| self.encode_evaluator_input(x, id))
             .collect()
     }
+
+    /// Encode a slice of garbler inputs, which may not fit in a `u16`, into
+    /// their associated wire-labels.
+    pub fn encode_garbler_inputs_big(&self, inputs: &[u64]) -> Vec<Wire> {
+        debug_assert_eq!(inputs.len(), self.garbler_inputs.len());
+        self.garbler_inputs
+            .iter()
+            .zip(inputs)
+            .map(|(X, &x)| X.plus(&self.deltas[&X.modulus()].cmul_big(x)))
+            .collect()
+    }
+
+    /// Encode a slice of evaluator inputs, which may not fit in a `u16`, into
+    /// their associated wire-labels.
+    pub fn encode_evaluator_inputs_big(&self, inputs: &[u64]) -> Vec<Wire> {
+        debug_assert_eq!(inputs.len(), self.evaluator_inputs.len());
+        self.evaluator_inputs
+            .iter()
+            .zip(inputs)
+            .map(|(X, &x)| X.plus(&self.deltas[&X.modulus()].cmul_big(x)))
+            .collect()
+    }
+
+    /// Write the encoder to `writer`. The encoder holds the garbler's deltas,
+    /// so it must be kept as secret as the garbler's own state.
+    pub fn save<W: Write>(&self, mut writer: W) -> IoResult<()> {
+        let mut out = header(ENCODER_MAGIC);
+        // sort the deltas so that the encoding is deterministic
+        let deltas = self.deltas.values().sorted_by_key(|w| w.to_bytes()).cloned().collect_vec();
+        for wires in [&self.garbler_inputs, &self.evaluator_inputs, &deltas].iter() {
+            out.extend_from_slice(&(wires.len() as u64).to_le_bytes());
+            for w in wires.iter() {
+                w.write_bytes(&mut out);
+            }
+        }
+        writer.write_all(&out)
+    }
+
+    /// Read an encoder written by `save`.
+    pub fn load<R: Read>(reader: R) -> IoResult<Encoder> {
+        let bytes = read_with_header(reader, ENCODER_MAGIC)?;
+        let mut rest = &bytes[..];
+        let mut wires = Vec::with_capacity(3);
+        for _ in 0..3 {
+            let (n, mut tail) = read_u64(rest)?;
+            // each wire takes at least 18 bytes, which bounds the allocation
+            if n > (tail.len() / 18) as u64 {
+                return Err(invalid("encoder has the wrong length"));
+            }
+            let mut ws = Vec::with_capacity(n as usize);
+            for _ in 0..n {
+                let (w, t) = Wire::read_bytes(tail).map_err(|e| invalid(&e.to_string()))?;
+                ws.push(w);
+                tail = t;
+            }
+            wires.push(ws);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(invalid("encoder has the wrong length"));
+        }
+        let deltas = wires.pop().unwrap().into_iter().map(|w| (w.modulus(), w)).collect();
+        let evaluator_inputs = wires.pop().unwrap();
+        let garbler_inputs = wires.pop().unwrap();
+        Ok(Encoder::new(garbler_inputs, evaluator_inputs, deltas))
+    }
+}
+
+// The encoder holds the garbler's deltas.
+impl HasRole for Encoder {
+    type Role = role::Garbler;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Encoding of garbled circuits and encoders
+
+fn invalid(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+fn header(magic: &[u8]) -> Vec<u8> {
+    let mut out = magic.to_vec();
+    out.push(ENCODING_VERSION);
+    out
+}
+
+// Read all of `reader`, checking and removing the header.
+fn read_with_header<R: Read>(mut reader: R, magic: &[u8]) -> IoResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if !bytes.starts_with(magic) {
+        return Err(invalid("unexpected magic bytes"));
+    }
+    match bytes.get(magic.len()) {
+        Some(&ENCODING_VERSION) => Ok(bytes.split_off(magic.len() + 1)),
+        Some(v) => Err(invalid(&format!("unsupported encoding version {}", v))),
+        None => Err(invalid("unexpected end of encoding")),
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> IoResult<(u64, &[u8])> {
+    if bytes.len() < 8 {
+        return Err(invalid("unexpected end of encoding"));
+    }
+    let (n, rest) = bytes.split_at(8);
+    Ok((u64::from_le_bytes(n.try_into().unwrap()), rest))
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -213,6 +720,11 @@ impl GarbledWriter {
         };
         Self { blocks }
     }
+
+    /// Make a new `GarbledWriter` with room for exactly `nblocks` blocks.
+    fn with_capacity(nblocks: usize) -> Self {
+        Self { blocks: Vec::with_capacity(nblocks) }
+    }
 }
 
 impl std::io::Write for GarbledWriter {
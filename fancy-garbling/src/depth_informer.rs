@@ -7,10 +7,11 @@
 //! Fancy object to compute the multiplicative depth of a computation.
 
 use crate::{
-    errors::FancyError,
+    errors::{FancyError, GateContext},
     fancy::{Fancy, FancyInput, FancyReveal, HasModulus}, Modulus,
 };
-use std::cmp::max;
+use alloc::vec::Vec;
+use core::cmp::max;
 
 /// Carries the depth of the computation.
 #[derive(Clone, Debug)]
@@ -40,8 +41,17 @@ impl From<FancyError> for DepthError {
     }
 }
 
-impl std::fmt::Display for DepthError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl GateContext for DepthError {
+    fn at_gate(self, index: usize) -> Self {
+        match self {
+            DepthError::Underlying(e) => DepthError::Underlying(e.at_gate(index)),
+            e => e,
+        }
+    }
+}
+
+impl core::fmt::Display for DepthError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             Self::ProjUnsupported => writeln!(f, "Projection unsupported"),
             Self::Underlying(e) => writeln!(f, "Fancy error: {}", e),
@@ -61,8 +71,8 @@ pub struct DepthInformer {
     mul_depth: usize,
 }
 
-impl std::fmt::Display for DepthInformer {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for DepthInformer {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         writeln!(f, "computation info:")?;
         writeln!(f, "  inputs:             {:16}", self.ninputs)?;
         writeln!(f, "  constants:          {:16}", self.nconstants)?;
@@ -176,6 +186,18 @@ impl Fancy for DepthInformer {
         self.mul_depth = max(self.mul_depth, x.depth);
         Ok(None)
     }
+
+    fn constant_big(&mut self, _val: u64, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        self.constant(0, q)
+    }
+
+    fn cmul_big(&mut self, x: &Self::Item, _y: u64) -> Result<Self::Item, Self::Error> {
+        self.cmul(x, 0)
+    }
+
+    fn output_big(&mut self, x: &Self::Item) -> Result<Option<u64>, Self::Error> {
+        self.output(x).map(|_| None)
+    }
 }
 
 impl FancyReveal for DepthInformer {
@@ -8,20 +8,134 @@
 //!
 //! Useful for evaluating the circuits produced by `Fancy` without actually
 //! creating any circuits.
+//!
+//! A `Dummy` created with `Dummy::traced` also records the operations it
+//! performs, with their operands and results, to find where an implementation
+//! diverges from its reference:
+//!
+//! ```
+//! use fancy_garbling::{dummy::Dummy, Fancy, FancyInput, Modulus};
+//!
+//! let q = Modulus::Zq { q: 7 };
+//! let mut d = Dummy::traced().trace_names(&["round 2"]);
+//! let mut x = d.encode(3, &q).unwrap();
+//! for round in 1..=3 {
+//!     d.set_name(&format!("round {}", round));
+//!     let y = d.mul(&x, &x).unwrap();
+//!     x = d.add(&y, &x).unwrap();
+//! }
+//! assert_eq!(d.trace().len(), 2);
+//! d.dump_trace(&mut std::io::stderr()).unwrap();
+//! ```
+//!
+//! A `Dummy` created with `Dummy::with_inputs` holds the inputs of both
+//! parties, so that code written for a two-party protocol, which `receive`s
+//! the inputs of the other party, runs unchanged in the clear.
+//!
+//! A `Dummy` made `checked` panics on values out of range for their modulus,
+//! e.g. 16 on a `GF4` wire, and on truth tables with more entries than their
+//! input modulus has elements. Such values are computed with silently here,
+//! but garble to labels which decode to something else.
 
 
 use crate::{
+    circuit::SubCircuit,
     errors::{DummyError, FancyError},
-    fancy::{Fancy, FancyInput, FancyReveal, HasModulus}, Modulus, util,
+    fancy::{Fancy, FancyBranch, FancyInput, FancyReveal, HasModulus}, Modulus, util,
+    role::Party,
 };
+use alloc::{
+    boxed::Box,
+    collections::VecDeque,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::ops::Range;
+
+/// Simple struct that performs the fancy computation over `u16`, or `u64` for
+/// `ZqBig` moduli.
+#[derive(Clone, Debug)]
+pub struct Dummy {
+    trace: Option<Box<Trace>>,
+    party: Party,
+    garbler_inputs: VecDeque<u16>,
+    evaluator_inputs: VecDeque<u16>,
+    checked: bool,
+}
+
+impl Default for Dummy {
+    fn default() -> Self {
+        Dummy {
+            trace: None,
+            party: Party::Garbler,
+            garbler_inputs: VecDeque::new(),
+            evaluator_inputs: VecDeque::new(),
+            checked: false,
+        }
+    }
+}
+
+// The operations recorded by a traced `Dummy`.
+#[derive(Clone, Debug, Default)]
+struct Trace {
+    entries: Vec<TraceEntry>,
+    // the number of operations so far, recorded or not
+    count: usize,
+    name: Option<String>,
+    names: Option<Vec<String>>,
+    range: Option<Range<usize>>,
+}
+
+/// An operation recorded by a traced `Dummy`.
+#[derive(Clone, Debug)]
+pub struct TraceEntry {
+    /// The number of operations before this one, recorded or not.
+    pub index: usize,
+    /// The name set with `Dummy::set_name` when the operation ran.
+    pub name: Option<String>,
+    /// The `Fancy` or `FancyInput` method, e.g. `"mul"`, or `"input"` for
+    /// encoded values.
+    pub op: &'static str,
+    /// The wire operands.
+    pub operands: Vec<DummyVal>,
+    /// The constant operand of `constant` and `cmul`.
+    pub constant: Option<u64>,
+    /// The resulting wire, or the output wire of `output`.
+    pub result: DummyVal,
+}
 
-/// Simple struct that performs the fancy computation over `u16`.
-pub struct Dummy {}
+impl core::fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:8}", self.index)?;
+        if let Some(name) = self.name.as_ref() {
+            write!(f, " [{}]", name)?;
+        }
+        write!(f, " {}(", self.op)?;
+        for (i, x) in self.operands.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", x.val)?;
+        }
+        if let Some(c) = self.constant {
+            if !self.operands.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "#{}", c)?;
+        }
+        let q = self.operands.first().map_or(self.result.modulus, |x| x.modulus);
+        write!(f, ") : {} = {}", q, self.result.val)?;
+        if q != self.result.modulus {
+            write!(f, " : {}", self.result.modulus)?;
+        }
+        Ok(())
+    }
+}
 
-/// Wrapper around `u16`.
+/// Wrapper around `u16`, or `u64` for `ZqBig` moduli.
 #[derive(Clone, Debug)]
 pub struct DummyVal {
-    val: u16,
+    val: u64,
     modulus: Modulus,
 }
 
@@ -34,11 +148,21 @@ impl HasModulus for DummyVal {
 impl DummyVal {
     /// Create a new DummyVal.
     pub fn new(val: u16, modulus: Modulus) -> Self {
-        Self { val, modulus: modulus }
+        Self { val: val as u64, modulus }
+    }
+
+    /// Create a new DummyVal whose value may not fit in a `u16`.
+    pub fn new_big(val: u64, modulus: Modulus) -> Self {
+        Self { val, modulus }
     }
 
     /// Extract the value.
     pub fn val(&self) -> u16 {
+        self.val as u16
+    }
+
+    /// Extract the value, which may not fit in a `u16`.
+    pub fn val_big(&self) -> u64 {
         self.val
     }
 }
@@ -46,7 +170,154 @@ impl DummyVal {
 impl Dummy {
     /// Create a new Dummy.
     pub fn new() -> Dummy {
-        Dummy {}
+        Dummy::default()
+    }
+
+    /// Create a new Dummy recording every operation, see `trace`.
+    pub fn traced() -> Dummy {
+        Dummy {
+            trace: Some(Box::new(Trace::default())),
+            ..Dummy::default()
+        }
+    }
+
+    /// Create a new Dummy playing the garbler, whose `receive_many` takes the
+    /// next of `evaluator_vals`. With `playing(Party::Evaluator)` it takes the
+    /// next of `garbler_vals` instead. `encode_many` encodes the values it is
+    /// given in either case.
+    pub fn with_inputs(garbler_vals: &[u16], evaluator_vals: &[u16]) -> Dummy {
+        Dummy {
+            garbler_inputs: garbler_vals.iter().cloned().collect(),
+            evaluator_inputs: evaluator_vals.iter().cloned().collect(),
+            ..Dummy::default()
+        }
+    }
+
+    /// Play `party`, receiving the inputs of the other party. A Dummy plays
+    /// the garbler by default.
+    pub fn playing(mut self, party: Party) -> Dummy {
+        self.party = party;
+        self
+    }
+
+    /// Panic on values out of range for their modulus, whether encoded,
+    /// constant or operands of a gate, and on truth tables of `proj` which
+    /// do not have exactly one entry per element of the input modulus.
+    pub fn checked(mut self) -> Dummy {
+        self.checked = true;
+        self
+    }
+
+    /// The party this Dummy plays.
+    pub fn party(&self) -> Party {
+        self.party
+    }
+
+    /// The number of inputs of `party` not received yet.
+    pub fn remaining_inputs(&self, party: Party) -> usize {
+        match party {
+            Party::Garbler => self.garbler_inputs.len(),
+            Party::Evaluator => self.evaluator_inputs.len(),
+        }
+    }
+
+    /// Only record the operations run under one of `names`, as set by
+    /// `set_name`. Does nothing if the Dummy is not traced.
+    pub fn trace_names(mut self, names: &[&str]) -> Dummy {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.names = Some(names.iter().map(|name| name.to_string()).collect());
+        }
+        self
+    }
+
+    /// Only record the operations whose index is in `range`. Does nothing if
+    /// the Dummy is not traced.
+    pub fn trace_range(mut self, range: Range<usize>) -> Dummy {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.range = Some(range);
+        }
+        self
+    }
+
+    /// Name the operations that follow, e.g. after the wires or the round
+    /// they compute, until the next call.
+    pub fn set_name(&mut self, name: &str) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.name = Some(name.to_string());
+        }
+    }
+
+    /// Stop naming the operations that follow.
+    pub fn clear_name(&mut self) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.name = None;
+        }
+    }
+
+    /// The recorded operations, in order, or nothing if the Dummy is not
+    /// traced.
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.trace.as_ref().map_or(&[], |trace| &trace.entries)
+    }
+
+    /// Write the recorded operations to `writer`, one per line.
+    #[cfg(feature = "std")]
+    pub fn dump_trace<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        for entry in self.trace() {
+            writeln!(writer, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    // Assert that the operands of `op` are in range, if checked.
+    fn check(&self, op: &str, xs: &[&DummyVal]) {
+        if !self.checked {
+            return;
+        }
+        for x in xs.iter() {
+            assert!(
+                in_range(x.val, &x.modulus),
+                "Dummy {}: value {} is out of range for {}",
+                op,
+                x.val,
+                x.modulus
+            );
+        }
+    }
+
+    // Record an operation if traced, and if the filters let it through.
+    fn record(
+        &mut self,
+        op: &'static str,
+        operands: &[&DummyVal],
+        constant: Option<u64>,
+        result: &DummyVal,
+    ) {
+        let trace = match self.trace.as_mut() {
+            Some(trace) => trace,
+            None => return,
+        };
+        let index = trace.count;
+        trace.count += 1;
+        if let Some(range) = trace.range.as_ref() {
+            if !range.contains(&index) {
+                return;
+            }
+        }
+        if let Some(names) = trace.names.as_ref() {
+            match trace.name.as_ref() {
+                Some(name) if names.contains(name) => {}
+                _ => return,
+            }
+        }
+        trace.entries.push(TraceEntry {
+            index,
+            name: trace.name.clone(),
+            op,
+            operands: operands.iter().map(|&x| x.clone()).collect(),
+            constant,
+            result: result.clone(),
+        });
     }
 }
 
@@ -56,7 +327,10 @@ impl FancyInput for Dummy {
 
     /// Encode a single dummy value.
     fn encode(&mut self, value: u16, modulus: &Modulus) -> Result<DummyVal, DummyError> {
-        Ok(DummyVal::new(value, *modulus))
+        let x = DummyVal::new(value, *modulus);
+        self.check("input", &[&x]);
+        self.record("input", &[], None, &x);
+        Ok(x)
     }
 
     /// Encode a slice of inputs and a slice of moduli as DummyVals.
@@ -64,16 +338,27 @@ impl FancyInput for Dummy {
         if xs.len() != moduli.len() {
             return Err(DummyError::EncodingError);
         }
-        Ok(xs
-            .iter()
+        xs.iter()
             .zip(moduli.iter())
-            .map(|(x, q)| DummyVal::new(*x, *q))
-            .collect())
+            .map(|(x, q)| self.encode(*x, q))
+            .collect()
     }
 
-    fn receive_many(&mut self, _moduli: &[Modulus]) -> Result<Vec<DummyVal>, DummyError> {
-        // Receive is undefined for Dummy which is a single party "protocol"
-        Err(DummyError::EncodingError)
+    /// Receive the next inputs of the other party, as given to
+    /// `Dummy::with_inputs`.
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<DummyVal>, DummyError> {
+        let (inputs, error) = match self.party.other() {
+            Party::Garbler => (&mut self.garbler_inputs, DummyError::NotEnoughGarblerInputs),
+            Party::Evaluator => (&mut self.evaluator_inputs, DummyError::NotEnoughEvaluatorInputs),
+        };
+        if inputs.len() < moduli.len() {
+            return Err(error);
+        }
+        if inputs.iter().zip(moduli.iter()).any(|(&x, q)| !in_range(x as u64, q)) {
+            return Err(DummyError::EncodingError);
+        }
+        let xs = inputs.drain(..moduli.len()).collect::<Vec<_>>();
+        self.encode_many(&xs, moduli)
     }
 }
 
@@ -82,81 +367,97 @@ impl Fancy for Dummy {
     type Error = DummyError;
 
     fn constant(&mut self, val: u16, modulus: &Modulus) -> Result<DummyVal, Self::Error> {
-        Ok(DummyVal::new(val, *modulus))
+        let z = DummyVal::new(val, *modulus);
+        self.check("constant", &[&z]);
+        self.record("constant", &[], Some(val as u64), &z);
+        Ok(z)
     }
 
     fn add(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.check("add", &[x, y]);
         if x.modulus() != y.modulus() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::UnequalModuli {
+                x: x.modulus(),
+                y: y.modulus(),
+            }));
         }
+        let (a, b) = (x.val(), y.val());
         let result = match x.modulus {
-            Modulus::Zq { q } => (x.val + y.val) % q,
-            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} => x.val ^ y.val,
+            Modulus::Zq { q } => ((a as u32 + b as u32) % q as u32) as u64,
+            Modulus::ZqBig { q } => (x.val + y.val) % q,
+            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} | Modulus::GF16 {..} => (a ^ b) as u64,
+            Modulus::GFpk { p, k, .. } => util::gfpk_add(a, b, p, k) as u64,
         };
 
-        Ok(DummyVal {
+        let z = DummyVal {
             val: result,
             modulus: x.modulus,
-        })
+        };
+        self.record("add", &[x, y], None, &z);
+        Ok(z)
     }
 
     fn sub(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.check("sub", &[x, y]);
         if x.modulus() != y.modulus() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::UnequalModuli {
+                x: x.modulus(),
+                y: y.modulus(),
+            }));
         }
 
+        let (a, b) = (x.val(), y.val());
         let result = match x.modulus {
-            Modulus::Zq { q } => (q + x.val - y.val) % q,
-            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} =>
+            Modulus::Zq { q } => ((q as u32 + a as u32 - b as u32) % q as u32) as u64,
+            Modulus::ZqBig { q } => (q + x.val - y.val) % q,
+            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} | Modulus::GF16 {..} =>
                 // In F2 subtraction is the same as addition !
-                x.val ^ y.val,
+                (a ^ b) as u64,
+            Modulus::GFpk { p, k, .. } => util::gfpk_add(a, util::gfpk_neg(b, p, k), p, k) as u64,
         };
 
-        Ok(DummyVal {
+        let z = DummyVal {
             val: result,
             modulus: x.modulus,
-        })
+        };
+        self.record("sub", &[x, y], None, &z);
+        Ok(z)
     }
 
     fn cmul(&mut self, x: &DummyVal, c: u16) -> Result<DummyVal, Self::Error> {
-
-        let result = match x.modulus() {
-            Modulus::Zq {q} => (x.val * c) % q,
-            Modulus::GF4 { p } => {
-                util::field_mul(x.val, c, p as u16, 4) as u16
-            },
-            Modulus::GF8 { p } => {
-                util::field_mul(x.val, c, p, 8) as u16
-            },
-            Modulus::GFk { k, p } => {
-                util::field_mul(x.val, c, p, k) as u16
-            }
-        };
-
-        Ok(DummyVal {
-            val: result,
-            modulus: x.modulus,
-        })
+        self.cmul_big(x, c as u64)
     }
 
     fn mul(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.check("mul", &[x, y]);
+        let (a, b) = (x.val(), y.val());
         let result = match x.modulus() {
-            Modulus::Zq { q } => (x.val * y.val) % q,
+            Modulus::Zq { q } => a as u32 * b as u32 % q as u32,
+            Modulus::ZqBig { .. } => {
+                return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                    "Multiplication of {} is not supported",
+                    x.modulus
+                ))))
+            }
             Modulus::GF4 { p } => {
-                util::field_mul(x.val, y.val  , p as u16, 4) as u16
+                util::field_mul(a, b, p as u16, 4) as u32
             },
             Modulus::GF8 { p } => {
-                util::field_mul(x.val, y.val, p, 8) as u16
+                util::field_mul(a, b, p, 8) as u32
             },
             Modulus::GFk { k, p } => {
-                util::field_mul(x.val, y.val, p, k) as u16
+                util::field_mul(a, b, p, k) as u32
             },
+            Modulus::GF16 { p } => util::field_mul(a, b, p, 16) as u32,
+            Modulus::GFpk { p, k, poly } => util::gfpk_mul(a, b, p, k, poly) as u32,
         };
 
-        Ok(DummyVal {
-            val: result,
+        let z = DummyVal {
+            val: result as u64,
             modulus: x.modulus,
-        })
+        };
+        self.record("mul", &[x, y], None, &z);
+        Ok(z)
     }
 
     fn proj(
@@ -167,23 +468,263 @@ impl Fancy for Dummy {
     ) -> Result<DummyVal, Self::Error> {
         let tt = tt.ok_or_else(|| Self::Error::from(FancyError::NoTruthTable))?;
         let xmodulus = x.modulus();
-
-        if tt.len() < xmodulus.size() as usize || !tt.iter().all(|&x| x < modulus.size()) {
+        self.check("proj", &[x]);
+        assert!(
+            !self.checked || tt.len() == xmodulus.order() as usize,
+            "Dummy proj: truth table of {} entries for {}",
+            tt.len(),
+            xmodulus
+        );
+
+        if tt.len() < xmodulus.order() as usize || !tt.iter().all(|&x| (x as u32) < modulus.order()) {
             return Err(Self::Error::from(FancyError::InvalidTruthTable));
         }
        
         let val = tt[x.val as usize];
-        Ok(DummyVal { val, modulus: *modulus })
+        let z = DummyVal::new(val, *modulus);
+        self.record("proj", &[x], None, &z);
+        Ok(z)
     }
 
     fn output(&mut self, x: &DummyVal) -> Result<Option<u16>, Self::Error> {
+        if let Modulus::ZqBig { .. } = x.modulus {
+            return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                "Outputs of {} need output_big",
+                x.modulus
+            ))));
+        }
+        self.check("output", &[x]);
+        self.record("output", &[], None, x);
+        Ok(Some(x.val()))
+    }
+
+    fn constant_big(&mut self, val: u64, modulus: &Modulus) -> Result<DummyVal, Self::Error> {
+        let z = DummyVal::new_big(val % modulus.order() as u64, *modulus);
+        self.record("constant", &[], Some(val), &z);
+        Ok(z)
+    }
+
+    fn cmul_big(&mut self, x: &DummyVal, c: u64) -> Result<DummyVal, Self::Error> {
+        self.check("cmul", &[x]);
+        let c = c % x.modulus.order() as u64;
+        let result = match x.modulus() {
+            Modulus::Zq { q } => x.val * c % q as u64,
+            Modulus::ZqBig { q } => x.val * c % q,
+            Modulus::GF4 { p } => util::field_mul(x.val(), c as u16, p as u16, 4) as u64,
+            Modulus::GF8 { p } => util::field_mul(x.val(), c as u16, p, 8) as u64,
+            Modulus::GFk { k, p } => util::field_mul(x.val(), c as u16, p, k) as u64,
+            Modulus::GF16 { p } => util::field_mul(x.val(), c as u16, p, 16) as u64,
+            Modulus::GFpk { p, k, poly } => util::gfpk_mul(x.val(), c as u16, p, k, poly) as u64,
+        };
+
+        let z = DummyVal {
+            val: result,
+            modulus: x.modulus,
+        };
+        self.record("cmul", &[x], Some(c), &z);
+        Ok(z)
+    }
+
+    fn output_big(&mut self, x: &DummyVal) -> Result<Option<u64>, Self::Error> {
+        self.check("output", &[x]);
+        self.record("output", &[], None, x);
         Ok(Some(x.val))
     }
 }
 
+impl FancyBranch for Dummy {
+    fn branch(
+        &mut self,
+        cond: &DummyVal,
+        branches: &[SubCircuit],
+        inputs: &[DummyVal],
+    ) -> Result<Vec<DummyVal>, Self::Error> {
+        let in_mods = inputs.iter().map(|x| x.modulus()).collect::<Vec<_>>();
+        SubCircuit::check_branches(&cond.modulus(), branches, &in_mods)?;
+        branches[cond.val() as usize].apply(self, inputs)
+    }
+}
+
 impl FancyReveal for Dummy {
     fn reveal(&mut self, x: &DummyVal) -> Result<u16, DummyError> {
-        Ok(x.val)
+        self.check("reveal", &[x]);
+        Ok(x.val())
+    }
+}
+
+// Whether `val` is an element of `modulus`.
+fn in_range(val: u64, modulus: &Modulus) -> bool {
+    match modulus {
+        Modulus::ZqBig { q } => val < *q,
+        q => val < q.order() as u64,
+    }
+}
+
+#[cfg(test)]
+mod trace {
+    use super::*;
+
+    // x -> x * x + 1, `n` times, each named after its round
+    fn rounds(d: &mut Dummy, n: usize) -> u16 {
+        let q = Modulus::Zq { q: 11 };
+        let one = d.constant(1, &q).unwrap();
+        let mut x = d.encode(2, &q).unwrap();
+        for round in 1..=n {
+            d.set_name(&format!("round {}", round));
+            let y = d.mul(&x, &x).unwrap();
+            x = d.add(&y, &one).unwrap();
+        }
+        d.clear_name();
+        d.output(&x).unwrap().unwrap()
+    }
+
+    #[test]
+    fn records() {
+        let mut d = Dummy::traced();
+        assert_eq!(rounds(&mut d, 3), 6);
+        let ops = d.trace().iter().map(|e| e.op).collect::<Vec<_>>();
+        assert_eq!(ops, ["constant", "input", "mul", "add", "mul", "add", "mul", "add", "output"]);
+        let e = &d.trace()[4];
+        assert_eq!((e.index, e.name.as_deref()), (4, Some("round 2")));
+        assert_eq!(e.operands.iter().map(|x| x.val()).collect::<Vec<_>>(), [5, 5]);
+        assert_eq!(e.result.val(), 3);
+
+        let mut dump = Vec::new();
+        d.dump_trace(&mut dump).unwrap();
+        let dump = String::from_utf8(dump).unwrap();
+        let lines = dump.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 9);
+        assert_eq!(lines[0], "       0 constant(#1) : Zq(q=11) = 1");
+        assert_eq!(lines[4], "       4 [round 2] mul(5, 5) : Zq(q=11) = 3");
+
+        let mut d = Dummy::new();
+        rounds(&mut d, 3);
+        assert!(d.trace().is_empty());
+    }
+
+    #[test]
+    fn filters() {
+        let mut d = Dummy::traced().trace_names(&["round 2", "round 12"]);
+        rounds(&mut d, 12);
+        let indices = d.trace().iter().map(|e| e.index).collect::<Vec<_>>();
+        assert_eq!(indices, [4, 5, 24, 25]);
+
+        let mut d = Dummy::traced().trace_range(20..23);
+        rounds(&mut d, 12);
+        let names = d.trace().iter().map(|e| e.name.clone().unwrap()).collect::<Vec<_>>();
+        assert_eq!(names, ["round 10", "round 10", "round 11"]);
+
+        let mut d = Dummy::traced().trace_names(&["round 3"]).trace_range(0..7);
+        rounds(&mut d, 3);
+        assert_eq!(d.trace().len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod inputs {
+    use super::*;
+
+    // shared by both parties, as with the twopac garbler and evaluator
+    fn inner_product<F>(f: &mut F, party: Party, input: &[u16]) -> Result<u16, <F as Fancy>::Error>
+    where
+        F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Error>,
+    {
+        let qs = vec![Modulus::Zq { q: 11 }; input.len()];
+        let (xs, ys) = match party {
+            Party::Garbler => {
+                let xs = f.encode_many(input, &qs)?;
+                (xs, f.receive_many(&qs)?)
+            }
+            Party::Evaluator => {
+                let xs = f.receive_many(&qs)?;
+                (xs, f.encode_many(input, &qs)?)
+            }
+        };
+        let zs = xs
+            .iter()
+            .zip(ys.iter())
+            .map(|(x, y)| f.mul(x, y))
+            .collect::<Result<Vec<_>, _>>()?;
+        let z = f.add_many(&zs)?;
+        Ok(f.output(&z)?.unwrap())
+    }
+
+    #[test]
+    fn both_parties() {
+        let (a, b) = ([1, 2, 3], [4, 5, 6]);
+        let mut d = Dummy::with_inputs(&[], &b);
+        assert_eq!(d.party(), Party::Garbler);
+        assert_eq!(inner_product(&mut d, Party::Garbler, &a).unwrap(), 32 % 11);
+        assert_eq!(d.remaining_inputs(Party::Evaluator), 0);
+
+        let mut d = Dummy::with_inputs(&a, &b).playing(Party::Evaluator);
+        assert_eq!(inner_product(&mut d, Party::Evaluator, &b).unwrap(), 32 % 11);
+        assert_eq!(d.remaining_inputs(Party::Garbler), 0);
+        assert_eq!(d.remaining_inputs(Party::Evaluator), 3);
+    }
+
+    #[test]
+    fn errors() {
+        let q = Modulus::Zq { q: 3 };
+        let mut d = Dummy::new();
+        assert!(d.receive_many(&[]).unwrap().is_empty());
+        match d.receive(&q) {
+            Err(DummyError::NotEnoughEvaluatorInputs) => {}
+            r => panic!("unexpected result {:?}", r),
+        }
+        let mut d = Dummy::with_inputs(&[1], &[]).playing(Party::Evaluator);
+        assert!(d.receive_many(&[q, q]).is_err());
+        assert_eq!(d.remaining_inputs(Party::Garbler), 1);
+        let mut d = Dummy::with_inputs(&[], &[2, 3]);
+        assert_eq!(d.receive(&q).unwrap().val(), 2);
+        assert!(d.receive(&q).is_err());
+    }
+}
+
+#[cfg(test)]
+mod checked {
+    use super::*;
+
+    #[test]
+    fn values_in_range() {
+        let (g, q) = (Modulus::X4_X_1, Modulus::Zq { q: 5 });
+        let mut d = Dummy::new().checked();
+        let x = d.encode(15, &g).unwrap();
+        let y = d.mul(&x, &x).unwrap();
+        let z = d.proj(&y, &q, Some((0..16).map(|i| i % 5).collect())).unwrap();
+        let big = d.constant_big(1 << 20, &Modulus::ZqBig { q: 1 << 21 }).unwrap();
+        d.output_big(&big).unwrap();
+        assert_eq!(d.output(&z).unwrap(), Some(d.reveal(&y).unwrap() % 5));
+
+        // unchecked, out of range values go through
+        let mut d = Dummy::new();
+        let x = d.encode(16, &g).unwrap();
+        d.add(&x, &x).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "value 16 is out of range for GF4")]
+    fn input_out_of_range() {
+        let mut d = Dummy::new().checked();
+        d.encode(16, &Modulus::X4_X_1).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Dummy add: value 7 is out of range for Zq(q=7)")]
+    fn operand_out_of_range() {
+        let q = Modulus::Zq { q: 7 };
+        let mut d = Dummy::new().checked();
+        let x = d.encode(3, &q).unwrap();
+        d.add(&x, &DummyVal::new(7, q)).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "truth table of 4 entries for Zq(q=3)")]
+    fn partial_truth_table() {
+        let q = Modulus::Zq { q: 3 };
+        let mut d = Dummy::new().checked();
+        let x = d.encode(1, &q).unwrap();
+        d.proj(&x, &q, Some(vec![0, 1, 2, 0])).unwrap();
     }
 }
 
@@ -191,7 +732,7 @@ impl FancyReveal for Dummy {
 mod bundle {
     use super::*;
     use crate::{
-        fancy::{BinaryGadgets, Bundle, BundleGadgets, CrtGadgets},
+        fancy::{BinaryBundle, BinaryGadgets, Bundle, BundleGadgets, CrtGadgets},
         util::{self, RngExt},
     };
     use itertools::Itertools;
@@ -276,6 +817,38 @@ mod bundle {
         }
     }
 
+    #[test]
+    fn test_karatsuba_multiplication() {
+        let mut rng = thread_rng();
+        for &nbits in &[8, 17, 33, 64, 100, 160] {
+            for _ in 0..16 {
+                let x = rng.gen_u128();
+                let y = rng.gen_u128();
+                let mut d = Dummy::new();
+                let x = d.bin_encode(x, nbits.min(128)).unwrap();
+                let y = d.bin_encode(y, nbits.min(128)).unwrap();
+                // widen past 128 bits with random high bits
+                let mut xs = x.wires().to_vec();
+                let mut ys = y.wires().to_vec();
+                for _ in 128..nbits {
+                    xs.push(DummyVal::new(rng.gen_u16() % 2, Modulus::Zq { q: 2 }));
+                    ys.push(DummyVal::new(rng.gen_u16() % 2, Modulus::Zq { q: 2 }));
+                }
+                let x = BinaryBundle::new(xs);
+                let y = BinaryBundle::new(ys);
+                let z = d.bin_mul_karatsuba(&x, &y).unwrap();
+                let should_be = d.bin_mul(&x, &y).unwrap();
+                assert_eq!(z.size(), 2 * nbits);
+                assert_eq!(
+                    d.output_bundle(&z).unwrap(),
+                    d.output_bundle(&should_be).unwrap(),
+                    "nbits={}",
+                    nbits
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_shift_extend() {
         let mut rng = thread_rng();
@@ -337,6 +910,117 @@ mod bundle {
         }
     }
 
+    #[test]
+    fn binary_mod() {
+        let mut rng = thread_rng();
+        for _ in 0..NITERS {
+            let nbits = 1 + rng.gen_usize() % 127;
+            let x = rng.gen_u128() & ((1 << nbits) - 1);
+            let mbits = 1 + rng.gen_usize() % 128;
+            let m = (rng.gen_u128() >> (128 - mbits)).max(1);
+            let mut d = Dummy::new();
+            let out;
+            {
+                let x = d.bin_encode(x, nbits).unwrap();
+                let z = d.bin_mod(&x, m).unwrap();
+                assert_eq!(z.size(), 128 - m.leading_zeros() as usize);
+                out = d.bin_output(&z).unwrap().unwrap();
+            }
+            assert_eq!(out, x % m, "{} % {}", x, m);
+        }
+        let mut d = Dummy::new();
+        let x = d.bin_encode(5, 8).unwrap();
+        assert!(d.bin_mod(&x, 0).is_err());
+        let x = d.bin_encode(5, 128).unwrap();
+        assert!(d.bin_mod(&x, 3).is_err());
+    }
+
+    #[test]
+    fn binary_mod_secret() {
+        let mut rng = thread_rng();
+        for _ in 0..NITERS {
+            let nbits = 64;
+            let q = 1 << nbits;
+            let x = rng.gen_u128() % q;
+            let y = rng.gen_u128() % (1 << (rng.gen_usize() % nbits));
+            let mut d = Dummy::new();
+            let out;
+            {
+                let x = d.bin_encode(x, nbits).unwrap();
+                let y = d.bin_encode(y, nbits).unwrap();
+                let z = d.bin_mod_secret(&x, &y).unwrap();
+                out = d.bin_output(&z).unwrap().unwrap();
+            }
+            assert_eq!(out, x.checked_rem(y).unwrap_or(x));
+        }
+    }
+
+    #[test]
+    fn popcount() {
+        let mut rng = thread_rng();
+        for nbits in 0..140 {
+            let x = rng.gen_u128();
+            let mut d = Dummy::new();
+            let mut xs = d.bin_encode(x, nbits.min(128)).unwrap().wires().to_vec();
+            for _ in 128..nbits {
+                xs.push(DummyVal::new(1, Modulus::Zq { q: 2 }));
+            }
+            let should_be = xs.iter().filter(|w| w.val() == 1).count() as u128;
+            let z = d.bin_popcount(&BinaryBundle::new(xs)).unwrap();
+            assert_eq!(z.size(), 128 - (nbits as u128).leading_zeros().min(127) as usize);
+            assert_eq!(d.bin_output(&z).unwrap().unwrap(), should_be);
+        }
+        for _ in 0..64 {
+            let (x, y) = (rng.gen_u128(), rng.gen_u128());
+            let mut d = Dummy::new();
+            let x_ = d.bin_encode(x, 128).unwrap();
+            let y_ = d.bin_encode(y, 128).unwrap();
+            let z = d.bin_hamming_distance(&x_, &y_).unwrap();
+            assert_eq!(d.bin_output(&z).unwrap().unwrap(), (x ^ y).count_ones() as u128);
+        }
+    }
+
+    #[test]
+    fn pow() {
+        let mut rng = thread_rng();
+        let q = util::modulus_with_width(32);
+        let modpow = |x: u128, e: u128, m: u128| {
+            (0..128).rev().fold(1, |acc, i| {
+                let acc = acc * acc % m;
+                if (e >> i) & 1 == 1 {
+                    acc * x % m
+                } else {
+                    acc
+                }
+            })
+        };
+        for _ in 0..64 {
+            let nbits = 1 + rng.gen_usize() % 32;
+            let ebits = rng.gen_usize() % 10;
+            let x = rng.gen_u128() % (1 << nbits);
+            let e = rng.gen_u128() % (1 << ebits);
+            let mut d = Dummy::new();
+            let e_ = d.bin_encode(e, ebits).unwrap();
+
+            let x_ = d.bin_encode(x, nbits).unwrap();
+            let z = d.bin_pow(&x_, &e_).unwrap();
+            assert_eq!(d.bin_output(&z).unwrap().unwrap(), modpow(x, e, 1 << nbits));
+
+            let x = rng.gen_u128() % q;
+            let x_ = d.crt_encode(x, q).unwrap();
+            let z = d.crt_pow(&x_, &e_).unwrap();
+            assert_eq!(d.crt_output(&z).unwrap().unwrap(), modpow(x, e, q));
+        }
+        let mut d = Dummy::new();
+        let zero = d.bin_encode(0, 8).unwrap();
+        let e = d.bin_encode(0, 4).unwrap();
+        let z = d.bin_pow(&zero, &e).unwrap();
+        assert_eq!(d.bin_output(&z).unwrap(), Some(1));
+        let zero = d.crt_encode(0, q).unwrap();
+        let z = d.crt_pow(&zero, &e).unwrap();
+        assert_eq!(d.crt_output(&z).unwrap(), Some(1));
+    }
+
     #[test]
     fn max() {
         let mut rng = thread_rng();
@@ -359,6 +1043,40 @@ mod bundle {
         }
     }
 
+    #[test]
+    fn argmax() {
+        let mut rng = thread_rng();
+        let q = util::modulus_with_width(10);
+        for n in 2..12 {
+            for _ in 0..16 {
+                // small values, so that ties occur
+                let inps = (0..n).map(|_| rng.gen_u128() % 8).collect_vec();
+                let max = *inps.iter().max().unwrap();
+                let min = *inps.iter().min().unwrap();
+                let imax = inps.iter().position(|x| *x == max).unwrap() as u128;
+                let imin = inps.iter().position(|x| *x == min).unwrap() as u128;
+                let mut d = Dummy::new();
+
+                let xs = inps.iter().map(|x| d.crt_encode(*x, q).unwrap()).collect_vec();
+                let (z, i) = d.crt_argmax(&xs, "100%").unwrap();
+                assert_eq!(d.crt_output(&z).unwrap().unwrap(), max);
+                assert_eq!(d.bin_output(&i).unwrap().unwrap(), imax);
+                let (z, i) = d.crt_argmin(&xs, "100%").unwrap();
+                assert_eq!(d.crt_output(&z).unwrap().unwrap(), min);
+                assert_eq!(d.bin_output(&i).unwrap().unwrap(), imin);
+
+                let xs = inps.iter().map(|x| d.bin_encode(*x, 4).unwrap()).collect_vec();
+                let (z, i) = d.bin_argmax(&xs).unwrap();
+                assert_eq!(d.bin_output(&z).unwrap().unwrap(), max);
+                assert_eq!(d.bin_output(&i).unwrap().unwrap(), imax);
+                assert_eq!(i.size(), (n as f64).log2().ceil() as usize);
+                let (z, i) = d.bin_argmin(&xs).unwrap();
+                assert_eq!(d.bin_output(&z).unwrap().unwrap(), min);
+                assert_eq!(d.bin_output(&i).unwrap().unwrap(), imin);
+            }
+        }
+    }
+
     #[test]
     fn twos_complement() {
         let mut rng = thread_rng();
@@ -427,6 +1145,83 @@ mod bundle {
         }
     }
 
+    #[test]
+    fn signed_arithmetic() {
+        let mut rng = thread_rng();
+        let nbits = 16;
+        let enc = |d: &mut Dummy, x: i16| d.bin_encode(x as u16 as u128, nbits).unwrap();
+        let out = |d: &mut Dummy, z: &BinaryBundle<DummyVal>| {
+            d.bin_output(z).unwrap().unwrap() as u16 as i16
+        };
+        let mut cases = vec![
+            (i16::MIN, -1),
+            (i16::MIN, 1),
+            (i16::MAX, -1),
+            (-7, 2),
+            (7, -2),
+            (-7, 0),
+            (0, 0),
+            (i16::MIN, i16::MIN),
+        ];
+        for _ in 0..NITERS {
+            cases.push((rng.gen_u16() as i16, rng.gen_u16() as i16));
+            cases.push((rng.gen_u16() as i8 as i16, rng.gen_u16() as i8 as i16));
+        }
+        for (a, b) in cases.into_iter() {
+            let mut d = Dummy::new();
+            let x = enc(&mut d, a);
+            let y = enc(&mut d, b);
+
+            let lt = d.bin_signed_lt(&x, &y).unwrap();
+            assert_eq!(d.output(&lt).unwrap().unwrap() == 1, a < b, "{} < {}", a, b);
+            let geq = d.bin_signed_geq(&x, &y).unwrap();
+            assert_eq!(d.output(&geq).unwrap().unwrap() == 1, a >= b, "{} >= {}", a, b);
+
+            let n = (b as u16 % 20) as usize;
+            let z = d.bin_signed_shr(&x, n).unwrap();
+            assert_eq!(out(&mut d, &z), a >> n.min(15), "{} >> {}", a, n);
+
+            let (z, overflow) = d.bin_signed_mul(&x, &y).unwrap();
+            let (should_be, should_overflow) = a.overflowing_mul(b);
+            assert_eq!(out(&mut d, &z), should_be, "{} * {}", a, b);
+            assert_eq!(d.output(&overflow).unwrap().unwrap() == 1, should_overflow);
+
+            let z = d.bin_signed_div(&x, &y).unwrap();
+            let should_be = if b == 0 { 0 } else { a.wrapping_div(b) };
+            assert_eq!(out(&mut d, &z), should_be, "{} / {}", a, b);
+        }
+    }
+
+    #[test]
+    fn crt_to_binary() {
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let q = rng.gen_usable_composite_modulus();
+            let nbits = 128 - q.leading_zeros() as usize;
+            let x = rng.gen_u128() % q;
+            let mut d = Dummy::new();
+            let y = d.crt_encode(x, q).unwrap();
+            let z = d.crt_to_bin(&y, nbits).unwrap();
+            assert_eq!(d.bin_output(&z).unwrap().unwrap(), x, "q={}", q);
+            let z = d.crt_to_bin(&y, 8).unwrap();
+            assert_eq!(d.bin_output(&z).unwrap().unwrap(), x % 256, "q={}", q);
+        }
+    }
+
+    #[test]
+    fn binary_to_crt() {
+        let mut rng = thread_rng();
+        let nbits = 64;
+        for _ in 0..NITERS {
+            let q = rng.gen_usable_composite_modulus();
+            let x = rng.gen_u128() % (1 << nbits);
+            let mut d = Dummy::new();
+            let y = d.bin_encode(x, nbits).unwrap();
+            let z = d.bin_to_crt(&y, q).unwrap();
+            assert_eq!(d.crt_output(&z).unwrap().unwrap(), x % q, "q={}", q);
+        }
+    }
+
     #[test]
     fn binary_lt() {
         let mut rng = thread_rng();
@@ -564,6 +1359,43 @@ mod bundle {
         }
     }
 
+    #[test]
+    fn binary_mux() {
+        let mut rng = thread_rng();
+        let nbits = 5;
+        for n in [1, 7, 32].iter() {
+            let vals = (0..*n).map(|_| rng.gen_u128() % 256).collect_vec();
+            for i in 0..1 << nbits {
+                let v = rng.gen_u128() % 256;
+                let mut d = Dummy::new();
+                let array = vals
+                    .iter()
+                    .map(|x| d.bin_encode(*x, 8).unwrap())
+                    .collect_vec();
+                let index = d.bin_encode(i, nbits).unwrap();
+                let z = d.bin_mux(&index, &array).unwrap();
+                let should_be = vals.get(i as usize).cloned().unwrap_or(0);
+                assert_eq!(d.bin_output(&z).unwrap().unwrap(), should_be);
+
+                let value = d.bin_encode(v, 8).unwrap();
+                let array = d.bin_mux_write(&index, &array, &value).unwrap();
+                let outs = d.bin_outputs(&array).unwrap().unwrap();
+                let mut should_be = vals.clone();
+                if (i as usize) < should_be.len() {
+                    should_be[i as usize] = v;
+                }
+                assert_eq!(outs, should_be);
+            }
+        }
+        let mut d = Dummy::new();
+        let index = d.bin_encode(0, nbits).unwrap();
+        assert!(d.bin_mux(&index, &[]).is_err());
+        let array = vec![d.bin_encode(0, 8).unwrap(), d.bin_encode(0, 4).unwrap()];
+        assert!(d.bin_mux(&index, &array).is_err());
+        let array = vec![d.bin_encode(0, 8).unwrap(); 33];
+        assert!(d.bin_mux(&index, &array).is_err());
+    }
+
     #[test]
     fn binary_eq() {
         let mut rng = thread_rng();
@@ -732,16 +1564,14 @@ mod pmr_tests {
     }
 
     #[test]
-    #[ignore]
     fn crt_div() {
         let mut rng = rand::thread_rng();
-        for _ in 0..8 {
-            let qs = rng.gen_usable_factors();
-            let n = qs.len();
-            let q = crate::util::product(&qs);
-            let q_ = crate::util::product(&qs[..n - 1]);
+        for _ in 0..16 {
+            let q = crate::util::modulus_with_width(8 + rng.gen_u32() % 33);
+            let qs = crate::util::factor(q);
+            let q_ = crate::util::product(&qs[..qs.len() - 1]);
             let pt_x = rng.gen_u128() % q_;
-            let pt_y = rng.gen_u128() % q_;
+            let pt_y = 1 + rng.gen_u128() % (q_ - 1);
 
             let mut f = Dummy::new();
             let crt_x = f.crt_encode(pt_x, q).unwrap();
@@ -751,7 +1581,16 @@ mod pmr_tests {
 
             let should_be = pt_x / pt_y;
             assert_eq!(res, should_be, "q={}, x={}, y={}", q, pt_x, pt_y);
+
+            // dividing by zero sets every bit of the quotient
+            let zero = f.crt_encode(0, q).unwrap();
+            let z = f.crt_div(&crt_x, &zero).unwrap();
+            let l = 128 - q_.leading_zeros();
+            assert_eq!(f.crt_output(&z).unwrap().unwrap(), (1 << l) - 1);
         }
+        let mut f = Dummy::new();
+        let x = f.crt_encode(1, 7).unwrap();
+        assert!(f.crt_div(&x, &x).is_err());
     }
 }
 
@@ -5,18 +5,36 @@
 // See LICENSE for licensing information.
 
 //! Errors that may be output by this library.
-
-use scuttlebutt::Block;
-use std::fmt::{self, Display, Formatter};
-
+//!
+//! Errors carry what is known where they occur: `FancyError::UnequalModuli`
+//! the moduli which clashed, `FancyError::AtGate` the index of the circuit gate
+//! being evaluated, and errors reading or writing a channel the underlying
+//! `std::io::Error`, which `std::error::Error::source` returns. Twopac errors
+//! tell failures of the channel from a misbehaving peer with
+//! `TwopacError::kind`.
+
+#[cfg(feature = "std")]
+use crate::garble::GarbleMode;
 use crate::Modulus;
+use alloc::{boxed::Box, string::String};
+use core::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+#[cfg(feature = "std")]
+use scuttlebutt::Block;
 
 /// Errors that may occur when using the `Fancy` trait. These errors are
 /// API-usage errors, such as trying to add two `Items` with different moduli.
 #[derive(Debug)]
 pub enum FancyError {
-    /// Unequal moduli.
-    UnequalModuli,
+    /// Unequal moduli of the operands.
+    UnequalModuli {
+        /// The modulus of the first operand.
+        x: Modulus,
+        /// The modulus of the second operand.
+        y: Modulus,
+    },
     /// Invalid argument.
     InvalidArg(String),
     /// Invalid number of arguments.
@@ -41,8 +59,61 @@ pub enum FancyError {
     InvalidTruthTable,
     /// Uninitialized value encountered.
     UninitializedValue,
+    /// Uninitialized value encountered on a named wire.
+    UninitializedWire(String),
     /// Unequal value for k in GF(2^k)
     UnequalK,
+    /// The computation was cancelled by the user.
+    Cancelled {
+        /// Number of gates processed before cancelling.
+        gates_done: usize,
+    },
+    /// An error occurred evaluating a gate of a `Circuit`.
+    AtGate {
+        /// The index of the gate.
+        index: usize,
+        /// The error of the gate.
+        error: Box<FancyError>,
+    },
+}
+
+impl FancyError {
+    /// The error for bundles whose moduli `xs` and `ys` differ: the first
+    /// unequal pair, or the lengths if one is a prefix of the other.
+    pub fn unequal_bundles(xs: &[Modulus], ys: &[Modulus]) -> FancyError {
+        match xs.iter().zip(ys).find(|(x, y)| x != y) {
+            Some((x, y)) => FancyError::UnequalModuli { x: *x, y: *y },
+            None => FancyError::InvalidArgNum {
+                got: ys.len(),
+                needed: xs.len(),
+            },
+        }
+    }
+
+    /// The error without the gates it occurred at.
+    pub fn root_cause(&self) -> &FancyError {
+        match self {
+            FancyError::AtGate { error, .. } => error.root_cause(),
+            e => e,
+        }
+    }
+
+    /// The gate at which the error occurred, if known.
+    pub fn gate(&self) -> Option<usize> {
+        match self {
+            FancyError::AtGate { index, .. } => Some(*index),
+            _ => None,
+        }
+    }
+}
+
+/// Errors which can record the gate of a `Circuit` at which they occurred.
+///
+/// Errors wrapping a `FancyError` wrap it in `FancyError::AtGate`, and other
+/// errors are unchanged.
+pub trait GateContext {
+    /// This error, as occurring at gate `index`.
+    fn at_gate(self, index: usize) -> Self;
 }
 
 /// Errors from the dummy fancy object.
@@ -59,6 +130,7 @@ pub enum DummyError {
 }
 
 /// Errors from the evaluator.
+#[cfg(feature = "std")]
 #[derive(Debug)]
 pub enum EvaluatorError {
     /// Not enough garbler inputs provided.
@@ -67,6 +139,22 @@ pub enum EvaluatorError {
     NotEnoughEvaluatorInputs,
     /// Decoding failed.
     DecodingFailed,
+    /// The garbled circuit exceeds one of the evaluator's resource limits.
+    ResourceLimitExceeded {
+        /// The resource whose limit was exceeded.
+        resource: &'static str,
+        /// The configured limit.
+        limit: usize,
+    },
+    /// The garbler garbles in another mode than the evaluator evaluates in.
+    ModeMismatch {
+        /// The mode of the evaluator.
+        expected: GarbleMode,
+        /// The mode the garbler announced, if it is a known one.
+        got: Option<GarbleMode>,
+    },
+    /// Reading from or writing to the channel failed.
+    IoError(std::io::Error),
     /// A communication error has occurred.
     CommunicationError(String),
     /// A fancy error has occurred.
@@ -74,8 +162,11 @@ pub enum EvaluatorError {
 }
 
 /// Errors from the garbler.
+#[cfg(feature = "std")]
 #[derive(Debug)]
 pub enum GarblerError {
+    /// Reading from or writing to the channel failed.
+    IoError(std::io::Error),
     /// An error occurred while processing a message.
     CommunicationError(String),
     /// Asymmetric moduli error.
@@ -105,12 +196,12 @@ pub enum CircuitBuilderError {
 impl Display for FancyError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            FancyError::UnequalModuli => "unequal moduli".fmt(f),
+            FancyError::UnequalModuli { x, y } => write!(f, "unequal moduli {} and {}", x, y),
             FancyError::InvalidArg(s) => write!(f, "invalid argument: {}", s),
             FancyError::InvalidArgNum { got, needed } => write!(
                 f,
                 "invalid number of arguments: needed {} but got {}",
-                got, needed
+                needed, got
             ),
             FancyError::InvalidArgMod { got, needed } => write!(
                 f,
@@ -123,9 +214,36 @@ impl Display for FancyError {
             FancyError::UninitializedValue => {
                 "uninitialized value in circuit. is the circuit topologically sorted?".fmt(f)
             },
+            FancyError::UninitializedWire(name) => write!(
+                f,
+                "uninitialized value for wire \"{}\". is the circuit topologically sorted?",
+                name
+            ),
             FancyError::UnequalK => {
                 "unequal k values for GF(2^k)".fmt(f)
             }
+            FancyError::Cancelled { gates_done } => {
+                write!(f, "computation cancelled after {} gates", gates_done)
+            }
+            FancyError::AtGate { index, error } => write!(f, "gate {}: {}", index, error),
+        }
+    }
+}
+
+impl Error for FancyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FancyError::AtGate { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl GateContext for FancyError {
+    fn at_gate(self, index: usize) -> Self {
+        FancyError::AtGate {
+            index,
+            error: Box::new(self),
         }
     }
 }
@@ -144,6 +262,24 @@ impl Display for DummyError {
     }
 }
 
+impl Error for DummyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DummyError::FancyError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl GateContext for DummyError {
+    fn at_gate(self, index: usize) -> Self {
+        match self {
+            DummyError::FancyError(e) => DummyError::FancyError(e.at_gate(index)),
+            e => e,
+        }
+    }
+}
+
 impl From<FancyError> for DummyError {
     fn from(e: FancyError) -> DummyError {
         DummyError::FancyError(e)
@@ -153,30 +289,65 @@ impl From<FancyError> for DummyError {
 ////////////////////////////////////////////////////////////////////////////////
 // Evaluator error
 
+#[cfg(feature = "std")]
 impl Display for EvaluatorError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             EvaluatorError::NotEnoughGarblerInputs => "not enough garbler inputs".fmt(f),
             EvaluatorError::NotEnoughEvaluatorInputs => "not enough evaluator inputs".fmt(f),
             EvaluatorError::DecodingFailed => write!(f, "decodiing failed"),
+            EvaluatorError::ResourceLimitExceeded { resource, limit } => {
+                write!(f, "resource limit exceeded: at most {} {} allowed", limit, resource)
+            }
+            EvaluatorError::ModeMismatch { expected, got: Some(got) } => {
+                write!(f, "garbling mode mismatch: expected {:?}, got {:?}", expected, got)
+            }
+            EvaluatorError::ModeMismatch { expected, got: None } => {
+                write!(f, "garbling mode mismatch: expected {:?}, got an unknown mode", expected)
+            }
+            EvaluatorError::IoError(e) => write!(f, "io error: {}", e),
             EvaluatorError::CommunicationError(s) => write!(f, "communication error: {}", s),
             EvaluatorError::FancyError(e) => write!(f, "fancy error: {}", e),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl Error for EvaluatorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EvaluatorError::IoError(e) => Some(e),
+            EvaluatorError::FancyError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl GateContext for EvaluatorError {
+    fn at_gate(self, index: usize) -> Self {
+        match self {
+            EvaluatorError::FancyError(e) => EvaluatorError::FancyError(e.at_gate(index)),
+            e => e,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl From<FancyError> for EvaluatorError {
     fn from(e: FancyError) -> Self {
         EvaluatorError::FancyError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for EvaluatorError {
     fn from(e: std::io::Error) -> Self {
-        EvaluatorError::CommunicationError(e.to_string())
+        EvaluatorError::IoError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::sync::mpsc::RecvError> for EvaluatorError {
     fn from(e: std::sync::mpsc::RecvError) -> Self {
         EvaluatorError::CommunicationError(e.to_string())
@@ -186,9 +357,11 @@ impl From<std::sync::mpsc::RecvError> for EvaluatorError {
 ////////////////////////////////////////////////////////////////////////////////
 // Garbler error
 
+#[cfg(feature = "std")]
 impl Display for GarblerError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
+            GarblerError::IoError(e) => write!(f, "io error: {}", e),
             GarblerError::CommunicationError(s) => write!(f, "{}", s),
             GarblerError::AsymmetricHalfGateModuliMax8(q) => write!(
                 f,
@@ -210,18 +383,42 @@ impl Display for GarblerError {
     }
 }
 
+#[cfg(feature = "std")]
+impl Error for GarblerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GarblerError::IoError(e) => Some(e),
+            GarblerError::FancyError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl GateContext for GarblerError {
+    fn at_gate(self, index: usize) -> Self {
+        match self {
+            GarblerError::FancyError(e) => GarblerError::FancyError(e.at_gate(index)),
+            e => e,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl From<FancyError> for GarblerError {
     fn from(e: FancyError) -> Self {
         GarblerError::FancyError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for GarblerError {
     fn from(e: std::io::Error) -> Self {
-        GarblerError::CommunicationError(e.to_string())
+        GarblerError::IoError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::sync::mpsc::SendError<Vec<Block>>> for GarblerError {
     fn from(e: std::sync::mpsc::SendError<Vec<Block>>) -> Self {
         GarblerError::CommunicationError(e.to_string())
@@ -244,6 +441,24 @@ impl Display for CircuitBuilderError {
     }
 }
 
+impl Error for CircuitBuilderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CircuitBuilderError::FancyError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl GateContext for CircuitBuilderError {
+    fn at_gate(self, index: usize) -> Self {
+        match self {
+            CircuitBuilderError::FancyError(e) => CircuitBuilderError::FancyError(e.at_gate(index)),
+            e => e,
+        }
+    }
+}
+
 impl From<FancyError> for CircuitBuilderError {
     fn from(e: FancyError) -> Self {
         CircuitBuilderError::FancyError(e)
@@ -251,6 +466,7 @@ impl From<FancyError> for CircuitBuilderError {
 }
 
 /// Errors emitted by the circuit parser.
+#[cfg(feature = "std")]
 #[derive(Debug)]
 pub enum CircuitParserError {
     /// An I/O error occurred.
@@ -265,6 +481,7 @@ pub enum CircuitParserError {
     ParseGateError(String),
 }
 
+#[cfg(feature = "std")]
 impl Display for CircuitParserError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
@@ -277,28 +494,75 @@ impl Display for CircuitParserError {
     }
 }
 
+#[cfg(feature = "std")]
+impl Error for CircuitParserError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CircuitParserError::IoError(e) => Some(e),
+            CircuitParserError::RegexError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl From<std::io::Error> for CircuitParserError {
     fn from(e: std::io::Error) -> CircuitParserError {
         CircuitParserError::IoError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<regex::Error> for CircuitParserError {
     fn from(e: regex::Error) -> CircuitParserError {
         CircuitParserError::RegexError(e)
     }
 }
 
-impl From<std::num::ParseIntError> for CircuitParserError {
-    fn from(_: std::num::ParseIntError) -> CircuitParserError {
+#[cfg(feature = "std")]
+impl From<core::num::ParseIntError> for CircuitParserError {
+    fn from(_: core::num::ParseIntError) -> CircuitParserError {
         CircuitParserError::ParseIntError
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+// wire serialization errors
+
+/// Errors emitted when decoding serialized wire-labels.
+#[derive(Debug, PartialEq, Eq)]
+pub enum WireDecodingError {
+    /// The encoding was produced by an unsupported format version.
+    UnsupportedVersion(u8),
+    /// The modulus tag is unknown.
+    UnknownModulus(u8),
+    /// The modulus parameters are not valid.
+    InvalidModulus,
+    /// The input ended early or has trailing bytes.
+    InvalidLength,
+}
+
+impl Display for WireDecodingError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            WireDecodingError::UnsupportedVersion(v) => {
+                write!(f, "unsupported wire encoding version {}", v)
+            }
+            WireDecodingError::UnknownModulus(t) => write!(f, "unknown modulus tag {}", t),
+            WireDecodingError::InvalidModulus => "invalid modulus parameters".fmt(f),
+            WireDecodingError::InvalidLength => "invalid wire encoding length".fmt(f),
+        }
+    }
+}
+
+impl Error for WireDecodingError {}
+
 ////////////////////////////////////////////////////////////////////////////////
 // 2PC errors
 
-/// Errors produced by `twopac`.
+/// Errors produced by `twopac`. See `kind` for whether the channel failed or
+/// the other party misbehaved.
+#[cfg(feature = "std")]
 #[derive(Debug)]
 pub enum TwopacError {
     /// An I/O error has occurred.
@@ -311,60 +575,195 @@ pub enum TwopacError {
     EvaluatorError(EvaluatorError),
     /// Processing the garbled circuit produced an error.
     FancyError(FancyError),
+    /// The other party deviated from the protocol.
+    CheatingDetected(String),
+}
+
+/// The broad cause of a `TwopacError`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TwopacErrorKind {
+    /// The channel to the other party failed. The protocol may be run again.
+    Transport,
+    /// The other party sent messages the protocol does not allow, whether by
+    /// cheating or by running something else.
+    Protocol,
+    /// The computation or its inputs are invalid, with either party.
+    Usage,
+}
+
+#[cfg(feature = "std")]
+impl TwopacError {
+    /// Whether the channel failed, the other party violated the protocol, or
+    /// the computation is invalid.
+    pub fn kind(&self) -> TwopacErrorKind {
+        match self {
+            TwopacError::IoError(_) => TwopacErrorKind::Transport,
+            TwopacError::OtError(e) => match e {
+                ocelot::Error::IoError(_) => TwopacErrorKind::Transport,
+                ocelot::Error::CoinTossError(scuttlebutt::cointoss::Error::IoError(_)) => {
+                    TwopacErrorKind::Transport
+                }
+                ocelot::Error::InvalidInputLength => TwopacErrorKind::Usage,
+                _ => TwopacErrorKind::Protocol,
+            },
+            TwopacError::GarblerError(e) => match e {
+                GarblerError::IoError(_) | GarblerError::CommunicationError(_) => {
+                    TwopacErrorKind::Transport
+                }
+                _ => TwopacErrorKind::Usage,
+            },
+            TwopacError::EvaluatorError(e) => match e {
+                EvaluatorError::IoError(_) | EvaluatorError::CommunicationError(_) => {
+                    TwopacErrorKind::Transport
+                }
+                EvaluatorError::DecodingFailed | EvaluatorError::ResourceLimitExceeded { .. } => {
+                    TwopacErrorKind::Protocol
+                }
+                _ => TwopacErrorKind::Usage,
+            },
+            TwopacError::FancyError(_) => TwopacErrorKind::Usage,
+            TwopacError::CheatingDetected(_) => TwopacErrorKind::Protocol,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for TwopacError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TwopacError::IoError(e) => Some(e),
+            TwopacError::GarblerError(e) => Some(e),
+            TwopacError::EvaluatorError(e) => Some(e),
+            TwopacError::FancyError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
-impl std::error::Error for TwopacError {}
+#[cfg(feature = "std")]
+impl GateContext for TwopacError {
+    fn at_gate(self, index: usize) -> Self {
+        match self {
+            TwopacError::GarblerError(e) => TwopacError::GarblerError(e.at_gate(index)),
+            TwopacError::EvaluatorError(e) => TwopacError::EvaluatorError(e.at_gate(index)),
+            TwopacError::FancyError(e) => TwopacError::FancyError(e.at_gate(index)),
+            e => e,
+        }
+    }
+}
 
+#[cfg(feature = "std")]
 impl From<ocelot::Error> for TwopacError {
     fn from(e: ocelot::Error) -> TwopacError {
         TwopacError::OtError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for TwopacError {
     fn from(e: std::io::Error) -> TwopacError {
         TwopacError::IoError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<EvaluatorError> for TwopacError {
     fn from(e: EvaluatorError) -> TwopacError {
         TwopacError::EvaluatorError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<GarblerError> for TwopacError {
     fn from(e: GarblerError) -> TwopacError {
         TwopacError::GarblerError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<FancyError> for TwopacError {
     fn from(e: FancyError) -> TwopacError {
         TwopacError::FancyError(e)
     }
 }
 
-impl std::fmt::Display for TwopacError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+#[cfg(feature = "std")]
+impl core::fmt::Display for TwopacError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             TwopacError::IoError(e) => write!(f, "IO error: {}", e),
             TwopacError::OtError(e) => write!(f, "oblivious transfer error: {}", e),
             TwopacError::EvaluatorError(e) => write!(f, "evaluator error: {}", e),
             TwopacError::GarblerError(e) => write!(f, "garbler error: {}", e),
             TwopacError::FancyError(e) => write!(f, "fancy error: {}", e),
+            TwopacError::CheatingDetected(s) => write!(f, "cheating detected: {}", s),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<TwopacError> for GarblerError {
     fn from(e: TwopacError) -> GarblerError {
         GarblerError::CommunicationError(e.to_string())
     }
 }
 
+#[cfg(feature = "std")]
 impl From<TwopacError> for EvaluatorError {
     fn from(e: TwopacError) -> EvaluatorError {
         EvaluatorError::CommunicationError(e.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context() {
+        let (q3, q5) = (Modulus::Zq { q: 3 }, Modulus::Zq { q: 5 });
+        match FancyError::unequal_bundles(&[q3, q3], &[q3, q5]) {
+            FancyError::UnequalModuli { x, y } => assert_eq!((x, y), (q3, q5)),
+            e => panic!("unexpected error {}", e),
+        }
+        match FancyError::unequal_bundles(&[q3, q3], &[q3]) {
+            FancyError::InvalidArgNum { got, needed } => assert_eq!((got, needed), (1, 2)),
+            e => panic!("unexpected error {}", e),
+        }
+
+        let e = DummyError::from(FancyError::UnequalModuli { x: q3, y: q5 }).at_gate(7);
+        assert_eq!(e.to_string(), "fancy error: gate 7: unequal moduli Zq(q=3) and Zq(q=5)");
+        let source = e.source().unwrap().downcast_ref::<FancyError>().unwrap();
+        assert_eq!(source.gate(), Some(7));
+        assert!(source.source().is_some());
+        match source.root_cause() {
+            FancyError::UnequalModuli { .. } => {}
+            e => panic!("unexpected error {}", e),
+        }
+        let e = DummyError::NotEnoughGarblerInputs.at_gate(7);
+        assert!(e.source().is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn twopac_kinds() {
+        let io = || std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "closed");
+        let e = TwopacError::from(GarblerError::from(io()));
+        assert_eq!(e.kind(), TwopacErrorKind::Transport);
+        let source = e.source().unwrap().source().unwrap();
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+        assert_eq!(TwopacError::from(io()).kind(), TwopacErrorKind::Transport);
+        assert_eq!(
+            TwopacError::from(ocelot::Error::IoError(io())).kind(),
+            TwopacErrorKind::Transport
+        );
+        let e = TwopacError::CheatingDetected("bad decoding".to_string());
+        assert_eq!(e.kind(), TwopacErrorKind::Protocol);
+        let e = TwopacError::from(EvaluatorError::DecodingFailed);
+        assert_eq!(e.kind(), TwopacErrorKind::Protocol);
+        let e = TwopacError::from(FancyError::NoTruthTable).at_gate(3);
+        assert_eq!(e.kind(), TwopacErrorKind::Usage);
+        assert_eq!(e.to_string(), "fancy error: gate 3: truth table required");
+    }
+}
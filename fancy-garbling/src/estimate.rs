@@ -0,0 +1,396 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Compute the exact size of a garbling without garbling.
+//!
+//! `Estimator` implements `Fancy` over moduli alone and counts the bytes the
+//! streaming `Garbler` would write for every operation in a given
+//! `GarbleMode`, including the packing of three-halves AND gates. Use
+//! `estimate` for a fancy computation and `estimate_circuit` for a `Circuit`.
+
+use crate::{
+    circuit::Circuit,
+    errors::FancyError,
+    fancy::{Fancy, FancyInput, FancyReveal, HasModulus},
+    garble::{three_halves, GarbleMode},
+    wire::Modulus,
+};
+
+/// The size of a garbling, as computed by an `Estimator`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Estimate {
+    garbler_inputs: usize,
+    evaluator_inputs: usize,
+    constant_bytes: usize,
+    gate_bytes: usize,
+    output_bytes: usize,
+}
+
+impl Estimate {
+    /// Number of garbler inputs.
+    pub fn num_garbler_inputs(&self) -> usize {
+        self.garbler_inputs
+    }
+
+    /// Number of evaluator inputs.
+    pub fn num_evaluator_inputs(&self) -> usize {
+        self.evaluator_inputs
+    }
+
+    /// Bytes of the wire-labels of all inputs, one block each. Protocols which
+    /// send evaluator inputs by oblivious transfer send more than that.
+    pub fn input_bytes(&self) -> usize {
+        (self.garbler_inputs + self.evaluator_inputs) * 16
+    }
+
+    /// Bytes of the wire-labels sent for constants.
+    pub fn constant_bytes(&self) -> usize {
+        self.constant_bytes
+    }
+
+    /// Bytes of garbled gates, including the padding of three-halves gates to
+    /// whole blocks.
+    pub fn gate_bytes(&self) -> usize {
+        self.gate_bytes
+    }
+
+    /// Bytes needed to decode the outputs.
+    pub fn output_bytes(&self) -> usize {
+        self.output_bytes
+    }
+
+    /// Bytes of the garbled circuit: constants, gates and outputs, but not the
+    /// inputs. This is `16 * GarbledCircuit::size()` for a garbled `Circuit`.
+    pub fn garbled_size(&self) -> usize {
+        self.constant_bytes + self.gate_bytes + self.output_bytes
+    }
+
+    /// Number of blocks of the garbled circuit.
+    pub fn num_ciphertexts(&self) -> usize {
+        self.garbled_size() / 16
+    }
+
+    /// Bytes sent by the garbler in total, inputs included.
+    pub fn total_bytes(&self) -> usize {
+        self.input_bytes() + self.garbled_size()
+    }
+}
+
+/// Implements `Fancy`, counting the bytes garbling would send without doing
+/// any cryptography.
+#[derive(Clone, Debug)]
+pub struct Estimator {
+    mode: GarbleMode,
+    estimate: Estimate,
+    halves: usize, // bytes of three-halves gates not filling a block yet
+}
+
+/// A wire of an `Estimator`, which only knows its modulus.
+#[derive(Clone, Debug)]
+pub struct EstimatorVal {
+    modulus: Modulus,
+}
+
+impl HasModulus for EstimatorVal {
+    fn modulus(&self) -> Modulus {
+        self.modulus
+    }
+}
+
+impl Estimator {
+    /// Make a new `Estimator` for garbling in `mode`.
+    pub fn new(mode: GarbleMode) -> Self {
+        Estimator {
+            mode,
+            estimate: Estimate::default(),
+            halves: 0,
+        }
+    }
+
+    /// The size of the garbling so far, as if the garbler was flushed.
+    pub fn estimate(&self) -> Estimate {
+        let mut estimate = self.estimate.clone();
+        estimate.gate_bytes += self.padding();
+        estimate
+    }
+
+    fn padding(&self) -> usize {
+        if self.halves == 0 {
+            0
+        } else {
+            16 - self.halves
+        }
+    }
+
+    // Like the garbler, pad three-halves gates before writing any block.
+    fn align(&mut self) {
+        self.estimate.gate_bytes += self.padding();
+        self.halves = 0;
+    }
+
+    fn gate_blocks(&mut self, n: usize) {
+        self.align();
+        self.estimate.gate_bytes += 16 * n;
+    }
+
+    fn val(modulus: &Modulus) -> EstimatorVal {
+        EstimatorVal { modulus: *modulus }
+    }
+}
+
+impl FancyInput for Estimator {
+    type Item = EstimatorVal;
+    type Error = FancyError;
+
+    fn encode_many(
+        &mut self,
+        _values: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<EstimatorVal>, FancyError> {
+        self.align();
+        self.estimate.garbler_inputs += moduli.len();
+        Ok(moduli.iter().map(Self::val).collect())
+    }
+
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<EstimatorVal>, FancyError> {
+        self.align();
+        self.estimate.evaluator_inputs += moduli.len();
+        Ok(moduli.iter().map(Self::val).collect())
+    }
+}
+
+impl Fancy for Estimator {
+    type Item = EstimatorVal;
+    type Error = FancyError;
+
+    fn constant(&mut self, _x: u16, q: &Modulus) -> Result<EstimatorVal, FancyError> {
+        self.constant_big(0, q)
+    }
+
+    fn constant_big(&mut self, _x: u64, q: &Modulus) -> Result<EstimatorVal, FancyError> {
+        self.align();
+        self.estimate.constant_bytes += 16;
+        Ok(Self::val(q))
+    }
+
+    fn add(&mut self, x: &EstimatorVal, y: &EstimatorVal) -> Result<EstimatorVal, FancyError> {
+        if x.modulus != y.modulus {
+            return Err(FancyError::UnequalModuli {
+                x: x.modulus,
+                y: y.modulus,
+            });
+        }
+        Ok(x.clone())
+    }
+
+    fn sub(&mut self, x: &EstimatorVal, y: &EstimatorVal) -> Result<EstimatorVal, FancyError> {
+        self.add(x, y)
+    }
+
+    fn cmul(&mut self, x: &EstimatorVal, _c: u16) -> Result<EstimatorVal, FancyError> {
+        Ok(x.clone())
+    }
+
+    fn cmul_big(&mut self, x: &EstimatorVal, _c: u64) -> Result<EstimatorVal, FancyError> {
+        Ok(x.clone())
+    }
+
+    fn mul(&mut self, x: &EstimatorVal, y: &EstimatorVal) -> Result<EstimatorVal, FancyError> {
+        match (x.modulus, y.modulus) {
+            (Modulus::Zq { q }, Modulus::Zq { q: qb }) => {
+                if q < qb {
+                    return self.mul(y, x);
+                }
+                let q = q as usize;
+                let qb = qb as usize;
+                if self.mode == GarbleMode::PrivacyFree {
+                    self.gate_blocks(qb - 1);
+                } else if self.mode == GarbleMode::ThreeHalves && q == 2 {
+                    self.estimate.gate_bytes += three_halves::GATE_BYTES;
+                    self.halves = (self.halves + three_halves::GATE_BYTES) % 16;
+                } else if q != qb && qb > 8 {
+                    return Err(FancyError::InvalidArg(format!(
+                        "Multiplication of unequal moduli needs the smaller one to be at most 8, got {}",
+                        qb
+                    )));
+                } else {
+                    // an extra ciphertext is needed for unequal moduli
+                    self.gate_blocks(q + qb - 2 + (q != qb) as usize);
+                }
+                Ok(x.clone())
+            }
+            (q, qb) if q.is_field() && q == qb && self.mode != GarbleMode::PrivacyFree => {
+                self.gate_blocks(2 * (q.order() as usize - 1));
+                Ok(x.clone())
+            }
+            (q, qb) => Err(FancyError::InvalidArg(format!(
+                "Multiplication of {:?} and {:?} is not supported",
+                q, qb
+            ))),
+        }
+    }
+
+    fn proj(
+        &mut self,
+        x: &EstimatorVal,
+        q: &Modulus,
+        tt: Option<Vec<u16>>,
+    ) -> Result<EstimatorVal, FancyError> {
+        tt.ok_or(FancyError::NoTruthTable)?;
+        let p = x.modulus;
+        let supported = match (p, *q) {
+            (Modulus::ZqBig { .. }, _) | (_, Modulus::ZqBig { .. }) => false,
+            (Modulus::Zq { .. }, Modulus::Zq { .. }) => true,
+            _ => self.mode != GarbleMode::PrivacyFree,
+        };
+        if !supported {
+            return Err(FancyError::InvalidArg(format!(
+                "Projection of {} into {} is not supported",
+                p, q
+            )));
+        }
+        self.gate_blocks(p.order() as usize - 1);
+        Ok(Self::val(q))
+    }
+
+    fn output(&mut self, x: &EstimatorVal) -> Result<Option<u16>, FancyError> {
+        if let Modulus::ZqBig { .. } = x.modulus {
+            return Err(FancyError::InvalidArg(format!(
+                "Outputs of {} need output_big",
+                x.modulus
+            )));
+        }
+        self.align();
+        self.estimate.output_bytes += 16 * x.modulus.order() as usize;
+        Ok(None)
+    }
+
+    fn output_big(&mut self, x: &EstimatorVal) -> Result<Option<u64>, FancyError> {
+        if let Modulus::ZqBig { .. } = x.modulus {
+            // outputs of `ZqBig` wires are decoded from a single block
+            self.align();
+            self.estimate.output_bytes += 16;
+            return Ok(None);
+        }
+        Ok(self.output(x)?.map(u64::from))
+    }
+}
+
+impl FancyReveal for Estimator {
+    fn reveal(&mut self, x: &EstimatorVal) -> Result<u16, FancyError> {
+        self.output(x)?;
+        Ok(0)
+    }
+}
+
+/// Compute the size of garbling the fancy computation `f` in `mode`.
+pub fn estimate<F>(mode: GarbleMode, f: F) -> Result<Estimate, FancyError>
+where
+    F: FnOnce(&mut Estimator) -> Result<(), FancyError>,
+{
+    let mut estimator = Estimator::new(mode);
+    f(&mut estimator)?;
+    Ok(estimator.estimate())
+}
+
+/// Compute the size of garbling `c` in `mode`, as done by
+/// `classic::garble_with_mode` or by streaming it with `Circuit::eval_big`.
+pub fn estimate_circuit(c: &Circuit, mode: GarbleMode) -> Result<Estimate, FancyError> {
+    estimate(mode, |f| {
+        let gb_inps = (0..c.num_garbler_inputs())
+            .map(|i| c.garbler_input_mod(i))
+            .collect::<Vec<_>>();
+        let ev_inps = (0..c.num_evaluator_inputs())
+            .map(|i| c.evaluator_input_mod(i))
+            .collect::<Vec<_>>();
+        let gb_inps = f.encode_many(&vec![0; gb_inps.len()], &gb_inps)?;
+        let ev_inps = f.receive_many(&ev_inps)?;
+        c.eval_big(f, &gb_inps, &ev_inps)?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::CircuitBuilder, classic::garble_with_mode, util::RngExt};
+    use rand::thread_rng;
+
+    #[test]
+    fn matches_garbling() {
+        let aes = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        let mut rng = thread_rng();
+        let mut b = CircuitBuilder::new();
+        let q = Modulus::Zq { q: rng.gen_prime() };
+        let f = Modulus::GF8 { p: 0x11b };
+        let x = b.garbler_input(&q);
+        let y = b.evaluator_input(&Modulus::Zq { q: 2 });
+        let u = b.garbler_input(&f);
+        let v = b.evaluator_input(&f);
+        let c = b.constant(1, &q).unwrap();
+        let z = b.mul(&x, &y).unwrap();
+        let z = b.add(&z, &c).unwrap();
+        let w = b.mul(&u, &v).unwrap();
+        let tt = (0..256).map(|i| i % 3).collect();
+        let w = b.proj(&w, &Modulus::Zq { q: 3 }, Some(tt)).unwrap();
+        let a = b.and(&y, &y).unwrap();
+        let a = b.and(&a, &y).unwrap();
+        b.outputs(&[z, w, a]).unwrap();
+        let mixed = b.finish();
+
+        for &mode in &[GarbleMode::Standard, GarbleMode::ThreeHalves] {
+            for c in &[&aes, &mixed] {
+                let estimate = estimate_circuit(c, mode).unwrap();
+                let (_, gc) = garble_with_mode(c, mode).unwrap();
+                assert_eq!(estimate.num_ciphertexts(), gc.size());
+                assert_eq!(estimate.garbled_size(), 16 * gc.size());
+                assert_eq!(estimate.num_garbler_inputs(), c.num_garbler_inputs());
+            }
+        }
+        let estimate = estimate_circuit(&aes, GarbleMode::PrivacyFree).unwrap();
+        let (_, gc) = garble_with_mode(&aes, GarbleMode::PrivacyFree).unwrap();
+        assert_eq!(estimate.num_ciphertexts(), gc.size());
+    }
+
+    #[test]
+    fn field_gates() {
+        // a GF(2^4) multiplication and a projection out of GF(2^8)
+        let estimate = estimate(GarbleMode::Standard, |f| {
+            let g4 = Modulus::GF4 { p: 0b10011 };
+            let g8 = Modulus::GF8 { p: 0x11b };
+            let xs = f.receive_many(&[g4, g4, g8])?;
+            let z = f.mul(&xs[0], &xs[1])?;
+            let w = f.proj(&xs[2], &g4, Some(vec![0; 256]))?;
+            f.output(&z)?;
+            f.output(&w)?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(estimate.num_evaluator_inputs(), 3);
+        assert_eq!(estimate.gate_bytes(), 16 * (2 * 15 + 255));
+        assert_eq!(estimate.output_bytes(), 16 * (16 + 16));
+        assert_eq!(estimate.total_bytes(), 16 * (3 + 2 * 15 + 255 + 32));
+    }
+
+    #[test]
+    fn three_halves_padding() {
+        let mode = GarbleMode::ThreeHalves;
+        let ands = |n: usize| {
+            estimate(mode, move |f| {
+                let x = f.receive(&Modulus::Zq { q: 2 })?;
+                for _ in 0..n {
+                    f.and(&x, &x)?;
+                }
+                Ok(())
+            })
+            .unwrap()
+            .gate_bytes()
+        };
+        assert_eq!(ands(1), 32);
+        assert_eq!(ands(16), 27 * 16);
+        assert_eq!(ands(17), 27 * 16 + 32);
+    }
+}
@@ -9,20 +9,37 @@
 //! An implementer must be able to create inputs, constants, do modular arithmetic, and
 //! create projections.
 
-use crate::errors::FancyError;
+use crate::errors::{FancyError, GateContext};
+use alloc::vec::Vec;
 use itertools::Itertools;
 
 mod binary;
+mod branch;
 mod bundle;
 mod crt;
+mod embed;
+mod gf;
 mod input;
+mod matrix;
+mod permutation;
+mod piecewise;
 mod pmr;
 mod reveal;
-pub use binary::{BinaryBundle, BinaryGadgets};
+mod state_matrix;
+mod zq;
+pub use binary::{BinaryBundle, BinaryGadgets, KARATSUBA_THRESHOLD};
+pub use branch::FancyBranch;
 pub use bundle::{Bundle, BundleGadgets};
 pub use crt::{CrtBundle, CrtGadgets};
+pub use embed::EmbeddingGadgets;
+pub use gf::GfGadgets;
 pub use input::FancyInput;
+pub use matrix::{MatrixGadgets, MatrixHints};
+pub use permutation::{waksman_control_bits, waksman_size, PermutationGadgets};
+pub use piecewise::{PiecewiseGadgets, PiecewiseLinear};
 pub use reveal::FancyReveal;
+pub use state_matrix::StateMatrix;
+pub use zq::ZqGadgets;
 pub use crate::wire::Modulus;
 /// An object that has some modulus. Basic object of `Fancy` computations.
 pub trait HasModulus {
@@ -36,7 +53,10 @@ pub trait Fancy {
     type Item: Clone + HasModulus;
 
     /// Errors which may be thrown by the users of Fancy.
-    type Error: std::fmt::Debug + std::fmt::Display + std::convert::From<FancyError>;
+    type Error: core::fmt::Debug
+        + core::fmt::Display
+        + core::convert::From<FancyError>
+        + GateContext;
 
     /// Create a constant `x` with modulus `q`.
     fn constant(&mut self, x: u16, q: &Modulus) -> Result<Self::Item, Self::Error>;
@@ -67,9 +87,61 @@ pub trait Fancy {
     /// output, but they need to be involved in the process, so they can return `None`.
     fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error>;
 
+    /// Create a constant `x` with modulus `q`, where `x` may not fit in a `u16`.
+    ///
+    /// This is the only way to create constants for `ZqBig` moduli. By default
+    /// it rejects those, and otherwise calls `constant` with `x` reduced modulo
+    /// the order of `q`.
+    fn constant_big(&mut self, x: u64, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        let x = reduce_small(x, q)?;
+        self.constant(x, q)
+    }
+
+    /// Multiply `x` times the constant `c`, where `c` may not fit in a `u16`.
+    ///
+    /// By default it rejects `ZqBig` wires, and otherwise calls `cmul` with `c`
+    /// reduced modulo the order of the modulus of `x`.
+    fn cmul_big(&mut self, x: &Self::Item, c: u64) -> Result<Self::Item, Self::Error> {
+        let c = reduce_small(c, &x.modulus())?;
+        self.cmul(x, c)
+    }
+
+    /// Process this wire as output, where the result may not fit in a `u16`.
+    ///
+    /// By default it rejects `ZqBig` wires, and otherwise calls `output`.
+    fn output_big(&mut self, x: &Self::Item) -> Result<Option<u64>, Self::Error> {
+        if let q @ Modulus::ZqBig { .. } = x.modulus() {
+            return Err(Self::Error::from(big_unsupported(&q)));
+        }
+        Ok(self.output(x)?.map(u64::from))
+    }
+
     ////////////////////////////////////////////////////////////////////////////////
     // Functions built on top of basic fancy operations.
 
+    /// Project each `xs[i]` according to the truth table `tts[i]` into
+    /// modulus `q`.
+    ///
+    /// The projections are independent, so implementors may compute them in
+    /// parallel. By default they are computed one after the other with `proj`.
+    fn proj_many(
+        &mut self,
+        xs: &[Self::Item],
+        q: &Modulus,
+        tts: &[Vec<u16>],
+    ) -> Result<Vec<Self::Item>, Self::Error> {
+        if tts.len() != xs.len() {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: tts.len(),
+                needed: xs.len(),
+            }));
+        }
+        xs.iter()
+            .zip(tts)
+            .map(|(x, tt)| self.proj(x, q, Some(tt.clone())))
+            .collect()
+    }
+
     /// Sum up a slice of wires.
     fn add_many(&mut self, args: &[Self::Item]) -> Result<Self::Item, Self::Error> {
         if args.len() < 2 {
@@ -274,4 +346,26 @@ pub trait Fancy {
         }
         Ok(zs.into_iter().collect())
     }
+
+    /// Output a slice of wires whose values may not fit in a `u16`.
+    fn outputs_big(&mut self, xs: &[Self::Item]) -> Result<Option<Vec<u64>>, Self::Error> {
+        let mut zs = Vec::with_capacity(xs.len());
+        for x in xs.iter() {
+            zs.push(self.output_big(x)?);
+        }
+        Ok(zs.into_iter().collect())
+    }
+}
+
+/// Reduce `x` modulo the order of `q` for the `u16` methods of `Fancy`, failing
+/// for `ZqBig` moduli.
+fn reduce_small<E: From<FancyError>>(x: u64, q: &Modulus) -> Result<u16, E> {
+    match q {
+        Modulus::ZqBig { .. } => Err(E::from(big_unsupported(q))),
+        _ => Ok((x % q.order() as u64) as u16),
+    }
+}
+
+fn big_unsupported(q: &Modulus) -> FancyError {
+    FancyError::InvalidArg(format!("{} is not supported by this Fancy object", q))
 }
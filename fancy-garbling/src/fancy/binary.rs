@@ -8,14 +8,16 @@ use crate::{
     errors::FancyError,
     fancy::{
         bundle::{Bundle, BundleGadgets},
+        crt::CrtBundle,
         Fancy,
         HasModulus,
     },
     util,
     Modulus,
 };
+use alloc::{collections::VecDeque, string::ToString, vec::Vec};
+use core::ops::{Deref, DerefMut};
 use itertools::Itertools;
-use std::ops::{Deref, DerefMut};
 
 /// Bundle which is explicitly binary representation.
 #[derive(Clone)]
@@ -125,7 +127,10 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<(BinaryBundle<Self::Item>, Self::Item), Self::Error> {
         if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &xs.moduli(),
+                &ys.moduli(),
+            )));
         }
         let xwires = xs.wires();
         let ywires = ys.wires();
@@ -147,7 +152,10 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
         if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &xs.moduli(),
+                &ys.moduli(),
+            )));
         }
         let xwires = xs.wires();
         let ywires = ys.wires();
@@ -178,7 +186,10 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
         if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &xs.moduli(),
+                &ys.moduli(),
+            )));
         }
 
         let xwires = xs.wires();
@@ -210,7 +221,10 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
         if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &xs.moduli(),
+                &ys.moduli(),
+            )));
         }
 
         let xwires = xs.wires();
@@ -240,6 +254,28 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         Ok(sum)
     }
 
+    /// Full multiplier using Karatsuba's method, with the same output as
+    /// `bin_mul`.
+    ///
+    /// Operands are split in halves recursively, replacing four half-width
+    /// products with three and a few additions, until they are at most
+    /// `KARATSUBA_THRESHOLD` bits wide and multiplied with `bin_mul`. This
+    /// saves AND gates from 32-bit operands on: about half at 128 bits, and
+    /// two thirds at 512 bits.
+    fn bin_mul_karatsuba(
+        &mut self,
+        xs: &BinaryBundle<Self::Item>,
+        ys: &BinaryBundle<Self::Item>,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        if xs.moduli() != ys.moduli() {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &xs.moduli(),
+                &ys.moduli(),
+            )));
+        }
+        karatsuba(self, xs.wires(), ys.wires()).map(BinaryBundle::new)
+    }
+
     /// Divider
     fn bin_div(
         &mut self,
@@ -247,20 +283,84 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
         if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &xs.moduli(),
+                &ys.moduli(),
+            )));
+        }
+        restoring_division(self, xs, ys).map(|(qs, _)| qs)
+    }
+
+    /// Reduce `x` modulo the public `m`, using Barrett reduction.
+    ///
+    /// The result is as wide as `m`. Rather than dividing bit by bit, the
+    /// quotient is estimated from the top bits of `x` and the precomputed
+    /// `2^n / m`, where `x` is `n` bits wide, which is off by at most two. This
+    /// costs two constant multiplications and three subtractions in total,
+    /// instead of a subtraction per bit of `x`. `x` must be less than 128 bits
+    /// wide.
+    fn bin_mod(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        m: u128,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        if m == 0 {
+            return Err(Self::Error::from(FancyError::InvalidArg(
+                "cannot reduce modulo 0".to_string(),
+            )));
+        }
+        let n = x.size();
+        let k = (128 - m.leading_zeros()) as usize;
+        let zero = self.constant(0, &Modulus::Zq { q: 2 })?;
+        let resize = |ws: &[Self::Item], n: usize| {
+            let mut ws = ws.to_vec();
+            ws.resize(n, zero.clone());
+            BinaryBundle::new(ws)
+        };
+        if n < k {
+            // x < 2^(k-1) <= m
+            return Ok(resize(x.wires(), k));
         }
-        let ys_neg = self.bin_twos_complement(ys)?;
-        let mut acc = self.bin_constant_bundle(0, xs.size())?;
-        let mut qs = BinaryBundle::new(Vec::new());
-        for x in xs.iter().rev() {
-            acc.pop();
-            acc.insert(0, x.clone());
-            let (res, cout) = self.bin_addition(&acc, &ys_neg)?;
-            acc = self.multiplex(&cout, &acc, &res).map(BinaryBundle)?;
-            qs.push(cout);
+        if n > 127 {
+            return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                "bin_mod needs an input narrower than 128 bits, got {}",
+                n
+            ))));
         }
-        qs.reverse(); // Switch back to little-endian
-        Ok(qs)
+        // the estimate floor(floor(x / 2^(k-1)) mu / 2^(n-k+1)) is at most two
+        // less than floor(x / m), and less than 2^(n-k+2)
+        let mu = (1 << n) / m;
+        let width = 2 * (n - k) + 3;
+        let q1 = resize(&x.wires()[k - 1..], width);
+        let q2 = self.bin_cmul(&q1, mu, width)?;
+        // so the remainder is less than 3m < 2^(k+2) and we only need its low
+        // k+2 bits
+        let width = k + 2;
+        let q3 = resize(&q2.wires()[n - k + 1..], width);
+        let q3m = self.bin_cmul(&q3, m, width)?;
+        let (mut r, _) = self.bin_subtraction(&resize(x.wires(), width), &q3m)?;
+        let m_ = self.bin_constant_bundle(m, width)?;
+        for _ in 0..2 {
+            let (res, geq) = self.bin_subtraction(&r, &m_)?;
+            r = self.multiplex(&geq, &r, &res).map(BinaryBundle)?;
+        }
+        Ok(resize(&r.wires()[..k], k))
+    }
+
+    /// Reduce `x` modulo `y`, the remainder of `bin_div`. As with `bin_div`
+    /// dividing by zero, `x` modulo zero is `x`.
+    fn bin_mod_secret(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        y: &BinaryBundle<Self::Item>,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        if x.moduli() != y.moduli() {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &x.moduli(),
+                &y.moduli(),
+            )));
+        }
+        restoring_division(self, x, y).map(|(_, r)| r)
     }
 
     /// Compute the twos complement of the input bundle (which must be base 2).
@@ -332,6 +432,105 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
             })
     }
 
+    /// Exponentiate `x` by the secret `e` using square and multiply, wrapping
+    /// around to the width of `x` as `u128::wrapping_pow` does. `e` may be of
+    /// any width, and `0^0` is `1`.
+    fn bin_pow(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        e: &BinaryBundle<Self::Item>,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let mut acc: Option<BinaryBundle<Self::Item>> = None;
+        let mut base = x.clone();
+        for (i, b) in e.iter().enumerate() {
+            // base if b = 1, else 1
+            let notb = self.negate(b)?;
+            let factor = base
+                .wires()
+                .iter()
+                .enumerate()
+                .map(|(j, w)| {
+                    let z = self.and(w, b)?;
+                    if j == 0 {
+                        self.xor(&z, &notb)
+                    } else {
+                        Ok(z)
+                    }
+                })
+                .collect::<Result<Vec<Self::Item>, Self::Error>>()
+                .map(BinaryBundle::new)?;
+            acc = Some(match acc {
+                None => factor,
+                Some(acc) => self.bin_multiplication_lower_half(&acc, &factor)?,
+            });
+            if i + 1 < e.size() {
+                base = self.bin_multiplication_lower_half(&base, &base)?;
+            }
+        }
+        match acc {
+            Some(acc) => Ok(acc),
+            None => self.bin_constant_bundle(1, x.size()),
+        }
+    }
+
+    /// Count the bits of `x` which are set, in a bundle just wide enough for
+    /// `x.size()`.
+    ///
+    /// Each column of equally significant bits is compressed with carry-save
+    /// full adders, taken in the order they are produced so that they form a
+    /// tree, until it holds a single bit. This costs about one AND gate per bit
+    /// of `x`.
+    fn bin_popcount(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let nbits = index_bits(x.size() + 1);
+        let mut columns = vec![VecDeque::new(); nbits + 1];
+        columns[0].extend(x.wires().iter().cloned());
+        let mut zs = Vec::with_capacity(nbits);
+        for i in 0..nbits {
+            while columns[i].len() > 1 {
+                let a = columns[i].pop_front().unwrap();
+                let b = columns[i].pop_front().unwrap();
+                let c = if columns[i].len() > 1 {
+                    columns[i].pop_front()
+                } else {
+                    None
+                };
+                let (z, carry) = self.adder(&a, &b, c.as_ref())?;
+                columns[i].push_back(z);
+                columns[i + 1].push_back(carry);
+            }
+            zs.push(match columns[i].pop_front() {
+                Some(z) => z,
+                None => self.constant(0, &Modulus::Zq { q: 2 })?,
+            });
+        }
+        Ok(BinaryBundle::new(zs))
+    }
+
+    /// Count the bits in which `x` and `y` differ, see `bin_popcount`.
+    fn bin_hamming_distance(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        y: &BinaryBundle<Self::Item>,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        if x.moduli() != y.moduli() {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &x.moduli(),
+                &y.moduli(),
+            )));
+        }
+        let diff = x
+            .wires()
+            .iter()
+            .zip(y.wires().iter())
+            .map(|(a, b)| self.xor(a, b))
+            .collect::<Result<Vec<Self::Item>, Self::Error>>()
+            .map(BinaryBundle::new)?;
+        self.bin_popcount(&diff)
+    }
+
     /// Compute the absolute value of a binary bundle.
     fn bin_abs(
         &mut self,
@@ -352,17 +551,13 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         // requiring special care to remove the y != 0, which is what follows.
         let (_, lhs) = self.bin_subtraction(x, y)?;
 
-        // Now we build a clause equal to (y == 0 || x >= y), which we can OR with
+        // Now we build a clause equal to (y == 0 && x >= y), which we can OR with
         // lhs to remove the y==0 aspect.
         // check if y==0
         let y_contains_1 = self.or_many(y.wires())?;
-        let y_eq_0 = self.negate(&y_contains_1)?;
-
-        // if x != 0, then x >= y, ... assuming x is not negative
-        let x_contains_1 = self.or_many(x.wires())?;
 
-        // y == 0 && x >= y
-        let rhs = self.and(&y_eq_0, &x_contains_1)?;
+        // every x is >= 0, so this is just y == 0
+        let rhs = self.negate(&y_contains_1)?;
 
         // (y != 0 && x >= y) || (y == 0 && x >= y)
         // => x >= y && (y != 0 || y == 0)\
@@ -382,6 +577,110 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         self.negate(&z)
     }
 
+    /// Returns 1 if `x < y`, interpreting both as two's complement integers.
+    fn bin_signed_lt(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        y: &BinaryBundle<Self::Item>,
+    ) -> Result<Self::Item, Self::Error> {
+        // flipping the sign bits maps signed order onto unsigned order
+        let x = self.bin_flip_sign(x)?;
+        let y = self.bin_flip_sign(y)?;
+        self.bin_lt(&x, &y)
+    }
+
+    /// Returns 1 if `x >= y`, interpreting both as two's complement integers.
+    fn bin_signed_geq(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        y: &BinaryBundle<Self::Item>,
+    ) -> Result<Self::Item, Self::Error> {
+        let z = self.bin_signed_lt(x, y)?;
+        self.negate(&z)
+    }
+
+    /// Arithmetic right shift of the two's complement integer `x` by `n` bits,
+    /// filling with its sign bit. This is free, and rounds towards negative
+    /// infinity like `>>` on Rust's signed integers.
+    fn bin_signed_shr(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        n: usize,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let ws = x.wires();
+        let sign = ws.last().unwrap();
+        Ok(BinaryBundle::new(
+            (0..ws.len())
+                .map(|i| ws.get(i + n).unwrap_or(sign).clone())
+                .collect(),
+        ))
+    }
+
+    /// Multiply the two's complement integers `x` and `y`. Returns the product
+    /// wrapped to the width of the inputs, as `wrapping_mul` on Rust's signed
+    /// integers, and a wire which is 1 if it overflowed.
+    ///
+    /// Computing the overflow requires a product of twice the width, which
+    /// costs about four times as much as `bin_multiplication_lower_half`.
+    fn bin_signed_mul(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        y: &BinaryBundle<Self::Item>,
+    ) -> Result<(BinaryBundle<Self::Item>, Self::Item), Self::Error> {
+        if x.moduli() != y.moduli() {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &x.moduli(),
+                &y.moduli(),
+            )));
+        }
+        let n = x.size();
+        let xe = bin_sign_extend(x, 2 * n);
+        let ye = bin_sign_extend(y, 2 * n);
+        let p = self.bin_multiplication_lower_half(&xe, &ye)?;
+        let ws = p.wires();
+        // the product fits iff its high half repeats the sign of the low half
+        let diffs = ws[n..]
+            .iter()
+            .map(|w| self.xor(w, &ws[n - 1]))
+            .collect::<Result<Vec<Self::Item>, Self::Error>>()?;
+        let overflow = self.or_many(&diffs)?;
+        Ok((BinaryBundle::new(ws[..n].to_vec()), overflow))
+    }
+
+    /// Divide the two's complement integers `x` and `y`, rounding towards zero.
+    ///
+    /// As `wrapping_div` on Rust's signed integers, the most negative value
+    /// divided by `-1` wraps around to itself. Dividing by zero yields zero.
+    fn bin_signed_div(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        y: &BinaryBundle<Self::Item>,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        if x.moduli() != y.moduli() {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &x.moduli(),
+                &y.moduli(),
+            )));
+        }
+        let xabs = self.bin_abs(x)?;
+        let yabs = self.bin_abs(y)?;
+        let q = self.bin_div(&xabs, &yabs)?;
+        let neg = self.xor(x.wires().last().unwrap(), y.wires().last().unwrap())?;
+        let negated = self.bin_twos_complement(&q)?;
+        self.multiplex(&neg, &q, &negated).map(BinaryBundle)
+    }
+
+    /// Flip the most significant bit of `x`.
+    fn bin_flip_sign(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let mut ws = x.wires().to_vec();
+        let sign = ws.pop().unwrap();
+        ws.push(self.negate(&sign)?);
+        Ok(BinaryBundle::new(ws))
+    }
+
     /// Compute the maximum bundle in `xs`.
     fn bin_max(
         &mut self,
@@ -411,6 +710,53 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
         })
     }
 
+    /// Compute the maximum bundle in `xs` and the index of its first
+    /// occurrence, as a binary bundle of `ceil(log2(xs.len()))` bits.
+    ///
+    /// On top of the comparisons of `bin_max`, tracking the index costs an AND
+    /// gate per bit of the index and element of `xs`.
+    fn bin_argmax(
+        &mut self,
+        xs: &[BinaryBundle<Self::Item>],
+    ) -> Result<(BinaryBundle<Self::Item>, BinaryBundle<Self::Item>), Self::Error> {
+        if xs.len() < 2 {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: 2,
+            }));
+        }
+        let mut max = xs[0].clone();
+        let mut index = self.bin_constant_bundle(0, index_bits(xs.len()))?;
+        for (i, y) in xs.iter().enumerate().skip(1) {
+            let pos = self.bin_lt(&max, y)?;
+            max = self.multiplex(&pos, &max, y).map(BinaryBundle)?;
+            index = select_index(self, &pos, &index, i)?;
+        }
+        Ok((max, index))
+    }
+
+    /// Compute the minimum bundle in `xs` and the index of its first
+    /// occurrence, see `bin_argmax`.
+    fn bin_argmin(
+        &mut self,
+        xs: &[BinaryBundle<Self::Item>],
+    ) -> Result<(BinaryBundle<Self::Item>, BinaryBundle<Self::Item>), Self::Error> {
+        if xs.len() < 2 {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: 2,
+            }));
+        }
+        let mut min = xs[0].clone();
+        let mut index = self.bin_constant_bundle(0, index_bits(xs.len()))?;
+        for (i, y) in xs.iter().enumerate().skip(1) {
+            let pos = self.bin_lt(y, &min)?;
+            min = self.multiplex(&pos, &min, y).map(BinaryBundle)?;
+            index = select_index(self, &pos, &index, i)?;
+        }
+        Ok((min, index))
+    }
+
     /// Demux a binary bundle into a unary vector.
     fn bin_demux(&mut self, x: &BinaryBundle<Self::Item>) -> Result<Vec<Self::Item>, Self::Error> {
         let wires = x.wires();
@@ -441,4 +787,295 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
 
         Ok(outs)
     }
+
+    /// Convert the CRT bundle `x` to binary, returning the `nbits` least
+    /// significant bits of its value modulo `Q = x.composite_modulus()`.
+    ///
+    /// The value is `sum x_i M_i mod Q` for constants `M_i`. Each term is
+    /// projected to binary from its residue, and the terms are added with a
+    /// reduction modulo `Q` after each addition. `Q` must be less than `2^127`.
+    fn crt_to_bin(
+        &mut self,
+        x: &CrtBundle<Self::Item>,
+        nbits: usize,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let q = x.composite_modulus();
+        if q >= 1 << 127 {
+            return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                "composite modulus {} is too large to convert to binary",
+                q
+            ))));
+        }
+        // wide enough for the sum of two values modulo q
+        let width = 129 - (q - 1).leading_zeros() as usize;
+        let two = Modulus::Zq { q: 2 };
+        let q_bits = self.bin_constant_bundle(q, width)?;
+
+        let mut acc: Option<BinaryBundle<Self::Item>> = None;
+        for w in x.wires() {
+            let p = w.modulus().size();
+            let qp = q / p as u128;
+            let m = qp * util::inv((qp % p as u128) as i128, p as i128) as u128 % q;
+            let mut vals = Vec::with_capacity(p as usize);
+            let mut v = 0;
+            for _ in 0..p {
+                vals.push(v);
+                v = (v + m) % q;
+            }
+            let bits = (0..width)
+                .map(|i| {
+                    let tt = vals.iter().map(|v| ((v >> i) & 1) as u16).collect_vec();
+                    if tt.iter().all(|&b| b == 0) {
+                        self.constant(0, &two)
+                    } else {
+                        self.proj(w, &two, Some(tt))
+                    }
+                })
+                .collect::<Result<Vec<Self::Item>, Self::Error>>()
+                .map(BinaryBundle::new)?;
+            acc = Some(match acc {
+                None => bits,
+                Some(acc) => {
+                    let s = self.bin_addition_no_carry(&acc, &bits)?;
+                    let (t, geq) = self.bin_subtraction(&s, &q_bits)?;
+                    self.multiplex(&geq, &s, &t).map(BinaryBundle::from)?
+                }
+            });
+        }
+
+        let mut ws = match acc {
+            Some(acc) => acc.wires().to_vec(),
+            None => return self.bin_constant_bundle(0, nbits),
+        };
+        ws.truncate(nbits);
+        while ws.len() < nbits {
+            ws.push(self.constant(0, &two)?);
+        }
+        Ok(BinaryBundle::new(ws))
+    }
+
+    /// Convert the binary bundle `x` to a CRT bundle with composite modulus
+    /// `q`, which holds its value modulo `q`.
+    ///
+    /// Each residue is a sum of the bits of `x` weighted by powers of two, so
+    /// this costs one ciphertext per bit and prime.
+    fn bin_to_crt(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        q: u128,
+    ) -> Result<CrtBundle<Self::Item>, Self::Error> {
+        util::factor(q)
+            .into_iter()
+            .map(|p| {
+                let modulus = Modulus::Zq { q: p };
+                let mut c = 1 % p;
+                let mut terms = Vec::with_capacity(x.size());
+                for b in x.wires() {
+                    if c != 0 {
+                        terms.push(self.proj(b, &modulus, Some(vec![0, c]))?);
+                    }
+                    c = (2 * c as u32 % p as u32) as u16;
+                }
+                match terms.len() {
+                    0 => self.constant(0, &modulus),
+                    1 => Ok(terms.pop().unwrap()),
+                    _ => self.add_many(&terms),
+                }
+            })
+            .collect::<Result<Vec<Self::Item>, Self::Error>>()
+            .map(CrtBundle::new)
+    }
+
+    /// Read `array[index]` for a secret `index`, by a linear scan over the
+    /// array using `bin_demux`. Indices past the end of `array` read zero.
+    ///
+    /// This costs one AND gate per bit of the array on top of the demux, and
+    /// supports indices of up to 8 bits, like `bin_demux`.
+    fn bin_mux(
+        &mut self,
+        index: &BinaryBundle<Self::Item>,
+        array: &[BinaryBundle<Self::Item>],
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let sel = self.bin_array_select(index, array)?;
+        (0..array[0].size())
+            .map(|j| {
+                let mut terms = sel
+                    .iter()
+                    .zip(array.iter())
+                    .map(|(s, x)| self.and(s, &x.wires()[j]))
+                    .collect::<Result<Vec<Self::Item>, Self::Error>>()?;
+                match terms.len() {
+                    1 => Ok(terms.pop().unwrap()),
+                    _ => self.add_many(&terms),
+                }
+            })
+            .collect::<Result<Vec<Self::Item>, Self::Error>>()
+            .map(BinaryBundle::new)
+    }
+
+    /// Write `value` to `array[index]` for a secret `index`, returning the new
+    /// array. Every element is rewritten, so the result does not reveal which
+    /// one changed. Indices past the end of `array` leave it unchanged.
+    fn bin_mux_write(
+        &mut self,
+        index: &BinaryBundle<Self::Item>,
+        array: &[BinaryBundle<Self::Item>],
+        value: &BinaryBundle<Self::Item>,
+    ) -> Result<Vec<BinaryBundle<Self::Item>>, Self::Error> {
+        let sel = self.bin_array_select(index, array)?;
+        if value.moduli() != array[0].moduli() {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &value.moduli(),
+                &array[0].moduli(),
+            )));
+        }
+        sel.iter()
+            .zip(array.iter())
+            .map(|(s, x)| self.multiplex(s, x, value).map(BinaryBundle))
+            .collect()
+    }
+
+    /// Demux `index` into a selector bit for each element of `array`, checking
+    /// that `array` is nonempty, addressable by `index`, and its elements are
+    /// of the same size.
+    fn bin_array_select(
+        &mut self,
+        index: &BinaryBundle<Self::Item>,
+        array: &[BinaryBundle<Self::Item>],
+    ) -> Result<Vec<Self::Item>, Self::Error> {
+        if array.is_empty() {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: 0,
+                needed: 1,
+            }));
+        }
+        if let Some(x) = array.iter().find(|x| x.moduli() != array[0].moduli()) {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &array[0].moduli(),
+                &x.moduli(),
+            )));
+        }
+        let mut sel = self.bin_demux(index)?;
+        if sel.len() < array.len() {
+            return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                "a {}-bit index cannot address an array of length {}",
+                index.size(),
+                array.len()
+            ))));
+        }
+        sel.truncate(array.len());
+        Ok(sel)
+    }
+}
+
+/// Width in bits up to which `bin_mul_karatsuba` multiplies operands with the
+/// schoolbook `bin_mul`.
+pub const KARATSUBA_THRESHOLD: usize = 16;
+
+// The full product of the equal-width `x` and `y`, see `bin_mul_karatsuba`.
+fn karatsuba<F: BinaryGadgets + ?Sized>(
+    f: &mut F,
+    x: &[F::Item],
+    y: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    let n = x.len();
+    if n <= KARATSUBA_THRESHOLD {
+        let z = f.bin_mul(&BinaryBundle::new(x.to_vec()), &BinaryBundle::new(y.to_vec()))?;
+        return Ok(z.wires().to_vec());
+    }
+    let zero = f.constant(0, &Modulus::Zq { q: 2 })?;
+    let pad = |ws: &[F::Item], n: usize| {
+        let mut ws = ws.to_vec();
+        ws.resize(n, zero.clone());
+        BinaryBundle::new(ws)
+    };
+    // x = x0 + 2^m x1, with x1 at least as wide as x0
+    let m = n / 2;
+    let h = n - m;
+    let (x0, x1) = x.split_at(m);
+    let (y0, y1) = y.split_at(m);
+    let z0 = karatsuba(f, x0, y0)?;
+    let z2 = karatsuba(f, x1, y1)?;
+
+    let (sx, cx) = f.bin_addition(&pad(x0, h), &pad(x1, h))?;
+    let (sy, cy) = f.bin_addition(&pad(y0, h), &pad(y1, h))?;
+    let mut sx = sx.wires().to_vec();
+    let mut sy = sy.wires().to_vec();
+    sx.push(cx);
+    sy.push(cy);
+    // (x0 + x1)(y0 + y1) - z0 - z2 = x0 y1 + x1 y0, which is less than
+    // 2^(m + h + 1) and so fits below the top m + 2h bits of the product
+    let z1 = pad(&karatsuba(f, &sx, &sy)?, 2 * h + 2);
+    let (z1, _) = f.bin_subtraction(&z1, &pad(&z0, 2 * h + 2))?;
+    let (z1, _) = f.bin_subtraction(&z1, &pad(&z2, 2 * h + 2))?;
+    let mut z1 = z1.wires().to_vec();
+    z1.truncate(m + 2 * h);
+
+    // z0 and 2^(2m) z2 do not overlap, so only z1 needs adding
+    let mut z = z0;
+    z.extend(z2);
+    let hi = f.bin_addition_no_carry(&BinaryBundle::new(z.split_off(m)), &pad(&z1, m + 2 * h))?;
+    z.extend(hi.wires().iter().cloned());
+    Ok(z)
+}
+
+// The quotient and remainder of the equal-width `xs` and `ys`, computed bit by
+// bit from the top.
+fn restoring_division<F: BinaryGadgets + ?Sized>(
+    f: &mut F,
+    xs: &BinaryBundle<F::Item>,
+    ys: &BinaryBundle<F::Item>,
+) -> Result<(BinaryBundle<F::Item>, BinaryBundle<F::Item>), F::Error> {
+    let ys_neg = f.bin_twos_complement(ys)?;
+    let mut acc = f.bin_constant_bundle(0, xs.size())?;
+    let mut qs = BinaryBundle::new(Vec::new());
+    for x in xs.iter().rev() {
+        acc.pop();
+        acc.insert(0, x.clone());
+        let (res, cout) = f.bin_addition(&acc, &ys_neg)?;
+        acc = f.multiplex(&cout, &acc, &res).map(BinaryBundle)?;
+        qs.push(cout);
+    }
+    qs.reverse(); // Switch back to little-endian
+    Ok((qs, acc))
+}
+
+// Sign-extend the two's complement integer `x` to `n` bits.
+fn bin_sign_extend<W: Clone + HasModulus>(x: &BinaryBundle<W>, n: usize) -> BinaryBundle<W> {
+    let mut ws = x.wires().to_vec();
+    let sign = ws.last().unwrap().clone();
+    ws.resize(n, sign);
+    BinaryBundle::new(ws)
+}
+
+// The number of bits of an index into `n` elements.
+pub(super) fn index_bits(n: usize) -> usize {
+    let mut nbits = 1;
+    while 1 << nbits < n {
+        nbits += 1;
+    }
+    nbits
+}
+
+// If `b = 0` return `index`, else the constant `i`, with one AND gate per bit.
+pub(super) fn select_index<F: Fancy + ?Sized>(
+    f: &mut F,
+    b: &F::Item,
+    index: &BinaryBundle<F::Item>,
+    i: usize,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    let notb = f.negate(b)?;
+    index
+        .wires()
+        .iter()
+        .enumerate()
+        .map(|(j, w)| {
+            if (i >> j) & 1 == 1 {
+                f.or(w, b)
+            } else {
+                f.and(w, &notb)
+            }
+        })
+        .collect::<Result<Vec<F::Item>, F::Error>>()
+        .map(BinaryBundle::new)
 }
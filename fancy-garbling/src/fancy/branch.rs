@@ -0,0 +1,35 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Module containing `FancyBranch`, for evaluating one of several
+//! sub-circuits chosen by a wire.
+
+use super::Fancy;
+use crate::circuit::SubCircuit;
+use alloc::vec::Vec;
+
+/// Fancy objects which can evaluate one of several sub-circuits, chosen by the
+/// value of a wire which need not be known to anyone.
+///
+/// A `CircuitBuilder` splices every branch into the circuit and multiplexes
+/// their outputs, while a streaming `Garbler` and `Evaluator` stack the
+/// garbled branches, so that only the material of the longest branch is sent,
+/// plus tables to select the taken one.
+///
+/// Stacking is only available when streaming: a built `Circuit` keeps no record
+/// of its branches, so garbling it with `classic::garble`, or from a serialized
+/// circuit, pays for every branch.
+pub trait FancyBranch: Fancy {
+    /// Evaluate the branch of `branches` chosen by `cond` on `inputs`,
+    /// returning its outputs. The modulus of `cond` must have one element per
+    /// branch, and the branches must take and return wires of the same moduli.
+    fn branch(
+        &mut self,
+        cond: &Self::Item,
+        branches: &[SubCircuit],
+        inputs: &[Self::Item],
+    ) -> Result<Vec<Self::Item>, Self::Error>;
+}
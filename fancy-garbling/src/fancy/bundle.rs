@@ -9,8 +9,9 @@ use crate::{
     fancy::{Fancy, HasModulus},
     Modulus,
 };
+use alloc::vec::Vec;
 use itertools::Itertools;
-use std::ops::Index;
+use core::ops::Index;
 
 /// A collection of wires, useful for the garbled gadgets defined by `BundleGadgets`.
 #[derive(Clone)]
@@ -84,7 +85,7 @@ impl<W: Clone + HasModulus> Bundle<W> {
     }
 
     /// Access the underlying iterator
-    pub fn iter(&self) -> std::slice::Iter<W> {
+    pub fn iter(&self) -> core::slice::Iter<'_, W> {
         self.0.iter()
     }
 
@@ -220,8 +221,11 @@ pub trait BundleGadgets: Fancy {
                 needed: 2,
             }));
         }
-        if !xs.iter().all(|x| x.moduli() == xs[0].moduli()) {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+        if let Some(x) = xs.iter().find(|x| x.moduli() != xs[0].moduli()) {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &xs[0].moduli(),
+                &x.moduli(),
+            )));
         }
 
         let mut digit_carry = None;
@@ -300,8 +304,11 @@ pub trait BundleGadgets: Fancy {
                 needed: 2,
             }));
         }
-        if !xs.iter().all(|x| x.moduli() == xs[0].moduli()) {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+        if let Some(x) = xs.iter().find(|x| x.moduli() != xs[0].moduli()) {
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &xs[0].moduli(),
+                &x.moduli(),
+            )));
         }
 
         let mut opt_carry = None;
@@ -419,7 +426,10 @@ pub trait BundleGadgets: Fancy {
         y: &Bundle<Self::Item>,
     ) -> Result<Self::Item, Self::Error> {
         if x.moduli() != y.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &x.moduli(),
+                &y.moduli(),
+            )));
         }
         let wlen = x.wires().len() as u16;
         let zs = x
@@ -9,12 +9,16 @@
 use super::{Fancy, HasModulus};
 use crate::{
     errors::FancyError,
-    fancy::bundle::{Bundle, BundleGadgets},
+    fancy::{
+        binary::{self, BinaryBundle},
+        bundle::{Bundle, BundleGadgets},
+    },
     util,
     Modulus
 };
+use alloc::{string::ToString, vec::Vec};
+use core::ops::Deref;
 use itertools::Itertools;
-use std::ops::Deref;
 
 /// Bundle which is explicitly CRT-representation.
 #[derive(Clone)]
@@ -152,6 +156,43 @@ pub trait CrtGadgets: Fancy + BundleGadgets {
             .map(CrtBundle::new)
     }
 
+    /// Exponentiate `x` by the secret `e`, given by its bits, using square and
+    /// multiply. As with `u128::pow`, `0^0` is `1`.
+    ///
+    /// Squaring is a projection on each wire, so this costs a multiplication
+    /// of bundles per bit of `e`.
+    fn crt_pow(
+        &mut self,
+        x: &CrtBundle<Self::Item>,
+        e: &BinaryBundle<Self::Item>,
+    ) -> Result<CrtBundle<Self::Item>, Self::Error> {
+        let q = x.composite_modulus();
+        let one = self.crt_constant_bundle(1, q)?;
+        let x_minus_one = self.crt_sub(x, &one)?;
+        let mut acc: Option<CrtBundle<Self::Item>> = None;
+        let mut base = x.clone();
+        let mut base_minus_one = x_minus_one;
+        for (i, b) in e.iter().enumerate() {
+            // base if b = 1, else 1
+            let factor = base_minus_one
+                .wires()
+                .iter()
+                .map(|w| self.mul(w, b))
+                .collect::<Result<Vec<Self::Item>, Self::Error>>()
+                .map(CrtBundle::new)?;
+            let factor = self.crt_add(&one, &factor)?;
+            acc = Some(match acc {
+                None => factor,
+                Some(acc) => self.crt_mul(&acc, &factor)?,
+            });
+            if i + 1 < e.size() {
+                base = self.crt_cexp(&base, 2)?;
+                base_minus_one = self.crt_sub(&base, &one)?;
+            }
+        }
+        Ok(acc.unwrap_or(one))
+    }
+
     /// Compute the remainder with respect to modulus `p`.
     fn crt_rem(
         &mut self,
@@ -329,6 +370,58 @@ pub trait CrtGadgets: Fancy + BundleGadgets {
         })
     }
 
+    /// Compute the maximum bundle in `xs` and the index of its first
+    /// occurrence, as a binary bundle of `ceil(log2(xs.len()))` bits.
+    fn crt_argmax(
+        &mut self,
+        xs: &[CrtBundle<Self::Item>],
+        accuracy: &str,
+    ) -> Result<(CrtBundle<Self::Item>, BinaryBundle<Self::Item>), Self::Error> {
+        if xs.len() < 2 {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: 2,
+            }));
+        }
+        let nbits = binary::index_bits(xs.len());
+        let mut max = xs[0].clone();
+        let mut index = self
+            .constant_bundle(&vec![0; nbits], &vec![Modulus::Zq { q: 2 }; nbits])
+            .map(BinaryBundle::from)?;
+        for (i, y) in xs.iter().enumerate().skip(1) {
+            let pos = self.crt_lt(&max, y, accuracy)?;
+            max = crt_select(self, &pos, &max, y)?;
+            index = binary::select_index(self, &pos, &index, i)?;
+        }
+        Ok((max, index))
+    }
+
+    /// Compute the minimum bundle in `xs` and the index of its first
+    /// occurrence, see `crt_argmax`.
+    fn crt_argmin(
+        &mut self,
+        xs: &[CrtBundle<Self::Item>],
+        accuracy: &str,
+    ) -> Result<(CrtBundle<Self::Item>, BinaryBundle<Self::Item>), Self::Error> {
+        if xs.len() < 2 {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: 2,
+            }));
+        }
+        let nbits = binary::index_bits(xs.len());
+        let mut min = xs[0].clone();
+        let mut index = self
+            .constant_bundle(&vec![0; nbits], &vec![Modulus::Zq { q: 2 }; nbits])
+            .map(BinaryBundle::from)?;
+        for (i, y) in xs.iter().enumerate().skip(1) {
+            let pos = self.crt_lt(y, &min, accuracy)?;
+            min = crt_select(self, &pos, &min, y)?;
+            index = binary::select_index(self, &pos, &index, i)?;
+        }
+        Ok((min, index))
+    }
+
     /// Convert the xs bundle to PMR representation. Useful for extracting out of CRT.
     fn crt_to_pmr(
         &mut self,
@@ -422,21 +515,33 @@ pub trait CrtGadgets: Fancy + BundleGadgets {
         self.negate(&z)
     }
 
-    /// Generic, and expensive, CRT-based addition for two ciphertexts. Uses PMR
-    /// comparison repeatedly. Requires an extra unused prime in both inputs.
+    /// Divide `x` by `y`, rounding down, by long division with PMR comparisons.
+    ///
+    /// The last modulus of the bundles must be unused: `x` and `y` must be less
+    /// than the product `Q'` of the other moduli, which is what lets `pmr_geq`
+    /// compare them. The quotient is computed one bit at a time, from the
+    /// highest bit of `Q'` down, with two PMR comparisons per bit. Dividing by
+    /// zero yields `2^l - 1`, where `l` is the bit length of `Q'`.
     fn crt_div(
         &mut self,
         x: &CrtBundle<Self::Item>,
         y: &CrtBundle<Self::Item>,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
         if x.moduli() != y.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+            return Err(Self::Error::from(FancyError::unequal_bundles(
+                &x.moduli(),
+                &y.moduli(),
+            )));
+        }
+        let nprimes = x.moduli().len();
+        if nprimes < 2 {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: nprimes,
+                needed: 2,
+            }));
         }
 
         let q = x.composite_modulus();
-
-        // Compute l based on the assumption that the last prime is unused.
-        let nprimes = x.moduli().len();
         let qs_ = &x.moduli()[..nprimes - 1];
         let q_ = util::product(&qs_.iter().map(|q| q.size()).collect_vec());
         let l = 128 - q_.leading_zeros();
@@ -444,39 +549,55 @@ pub trait CrtGadgets: Fancy + BundleGadgets {
         let mut quotient = self.crt_constant_bundle(0, q)?;
         let mut a = x.clone();
 
-        let one = self.crt_constant_bundle(1, q)?;
         for i in 0..l {
-            let b = 2u128.pow(l - i - 1);
-            let mut pb = q_ / b;
-            if q_ % b == 0 {
-                pb -= 1;
-            }
+            let b = 1u128 << (l - i - 1);
+            // the largest y with y b < Q', so that y b does not wrap around
+            let pb = (q_ - 1) / b;
 
             let tmp = self.crt_cmul(&y, b)?;
             let c1 = self.pmr_geq(&a, &tmp)?;
-
             let pb_crt = self.crt_constant_bundle(pb, q)?;
             let c2 = self.pmr_geq(&pb_crt, &y)?;
-
             let c = self.and(&c1, &c2)?;
 
-            let c_ws = one
-                .iter()
-                .map(|w| self.mul(w, &c))
-                .collect::<Result<Vec<_>, _>>()?;
-            let c_crt = CrtBundle::new(c_ws);
-
-            let b_if = self.crt_cmul(&c_crt, b)?;
-            quotient = self.crt_add(&quotient, &b_if)?;
-
-            let tmp_if = self.crt_mul(&c_crt, &tmp)?;
-            a = self.crt_sub(&a, &tmp_if)?;
+            // if c = 1 then add b to the quotient and subtract y b from a
+            let mut b_if = Vec::with_capacity(nprimes);
+            let mut tmp_if = Vec::with_capacity(nprimes);
+            for (w, p) in tmp.wires().iter().zip(x.moduli()) {
+                let p_ = p.size();
+                b_if.push(self.proj(&c, &p, Some(vec![0, (b % p_ as u128) as u16]))?);
+                let c_ = self.proj(&c, &p, Some(vec![0, 1]))?;
+                tmp_if.push(self.mul(w, &c_)?);
+            }
+            quotient = self.crt_add(&quotient, &CrtBundle::new(b_if))?;
+            a = self.crt_sub(&a, &CrtBundle::new(tmp_if))?;
         }
 
         Ok(quotient)
     }
 }
 
+// If `b = 0` return `x`, else `y`. Unlike `Fancy::mux`, this keeps the moduli
+// of `x` and `y`.
+fn crt_select<F: Fancy + ?Sized>(
+    f: &mut F,
+    b: &F::Item,
+    x: &CrtBundle<F::Item>,
+    y: &CrtBundle<F::Item>,
+) -> Result<CrtBundle<F::Item>, F::Error> {
+    let notb = f.negate(b)?;
+    x.wires()
+        .iter()
+        .zip(y.wires().iter())
+        .map(|(x, y)| {
+            let xp = f.mul(x, &notb)?;
+            let yp = f.mul(y, b)?;
+            f.add(&xp, &yp)
+        })
+        .collect::<Result<Vec<F::Item>, F::Error>>()
+        .map(CrtBundle::new)
+}
+
 /// Compute the `ms` needed for the number of CRT primes in `x`, with accuracy
 /// `accuracy`.
 ///
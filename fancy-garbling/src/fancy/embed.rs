@@ -0,0 +1,145 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Module containing `EmbeddingGadgets`, which convert wires between GF(2^4)
+//! and GF(2^8).
+
+use super::{Fancy, HasModulus};
+use crate::{errors::FancyError, util, Modulus};
+use alloc::vec::Vec;
+
+impl<F: Fancy> EmbeddingGadgets for F {}
+
+/// Extension trait for `Fancy` converting wires between GF(2^4) and GF(2^8),
+/// e.g. to combine PHOTON states over GF(2^4) with AES-style components.
+///
+/// An embedding of GF(2^4) into GF(2^8) is fixed by the image `root` of `x`,
+/// which must be one of the four roots of the GF(2^4) modulus listed by
+/// `util::gf4_embedding_roots`. Each conversion is a single projection: 15
+/// ciphertexts into GF(2^8) and 255 back.
+pub trait EmbeddingGadgets: Fancy {
+    /// Embed the GF(2^4) wire `x` into GF(2^8) modulo `q`, sending `x` to `root`.
+    fn gf4_to_gf8(
+        &mut self,
+        x: &Self::Item,
+        q: &Modulus,
+        root: u16,
+    ) -> Result<Self::Item, Self::Error> {
+        let tt = embedding(&x.modulus(), q, root)?;
+        self.proj(x, q, Some(tt))
+    }
+
+    /// Map the GF(2^8) wire `x` back into GF(2^4) modulo `q`, inverting
+    /// `gf4_to_gf8` with the same `root`.
+    ///
+    /// Elements outside the image of the embedding are mapped to `0`.
+    fn gf8_to_gf4(
+        &mut self,
+        x: &Self::Item,
+        q: &Modulus,
+        root: u16,
+    ) -> Result<Self::Item, Self::Error> {
+        let mut tt = vec![0; 256];
+        for (a, y) in embedding::<Self::Error>(q, &x.modulus(), root)?
+            .into_iter()
+            .enumerate()
+        {
+            tt[y as usize] = a as u16;
+        }
+        self.proj(x, q, Some(tt))
+    }
+}
+
+// The embedding of GF(2^4) modulo `q4` into GF(2^8) modulo `q8` sending `x`
+// to `root`, checking that it is one.
+fn embedding<E: From<FancyError>>(q4: &Modulus, q8: &Modulus, root: u16) -> Result<Vec<u16>, E> {
+    let (p4, p8) = match (*q4, *q8) {
+        (Modulus::GF4 { p: p4 }, Modulus::GF8 { p: p8 }) => (p4, p8),
+        _ => {
+            return Err(E::from(FancyError::InvalidArg(format!(
+                "expected GF(2^4) and GF(2^8) moduli, got {} and {}",
+                q4, q8
+            ))))
+        }
+    };
+    if !util::is_irreducible(p4 as u16, 4) || !util::gf4_embedding_roots(p4, p8).contains(&root) {
+        return Err(E::from(FancyError::InvalidArg(format!(
+            "{:#x} does not define an embedding of {} into {}",
+            root, q4, q8
+        ))));
+    }
+    Ok(util::gf4_embedding(p8, root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+    };
+
+    #[test]
+    fn homomorphism() {
+        let mut d = Dummy::new();
+        for &q4 in &[Modulus::X4_X_1, Modulus::X4_X3_X2_X_1] {
+            for &q8 in Modulus::GF8_MODULI.iter().take(4) {
+                let roots = util::gf4_embedding_roots(q4.value() as u8, q8.value());
+                assert_eq!(roots.len(), 4);
+                for &root in roots.iter() {
+                    let embed = |d: &mut Dummy, a: u16| {
+                        d.gf4_to_gf8(&DummyVal::new(a, q4), &q8, root)
+                            .unwrap()
+                            .val()
+                    };
+                    for a in 0..16 {
+                        let y = embed(&mut d, a);
+                        let z = d.gf8_to_gf4(&DummyVal::new(y, q8), &q4, root).unwrap();
+                        assert_eq!(z.val(), a);
+                        for b in 0..16 {
+                            let ab = util::field_mul(a, b, q4.value(), 4);
+                            let yb = util::field_mul(y, embed(&mut d, b), q8.value(), 8);
+                            assert_eq!(embed(&mut d, ab), yb);
+                            assert_eq!(embed(&mut d, a ^ b), y ^ embed(&mut d, b));
+                        }
+                    }
+                }
+            }
+        }
+        let q8 = Modulus::GF8_MODULI[0];
+        let x = DummyVal::new(1, Modulus::X4_X_1);
+        assert!(d.gf4_to_gf8(&x, &q8, 1).is_err());
+        let x = DummyVal::new(1, Modulus::X4_X2_1);
+        assert!(d.gf4_to_gf8(&x, &q8, 1).is_err());
+    }
+
+    #[test]
+    fn garbled_round_trip() {
+        let q4 = Modulus::X4_X_1;
+        let q8 = Modulus::GF8_MODULI[0];
+        let root = util::gf4_embedding_roots(q4.value() as u8, q8.value())[0];
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q4);
+        let y = b.evaluator_input(&q8);
+        let x8 = b.gf4_to_gf8(&x, &q8, root).unwrap();
+        let z = b.add(&x8, &y).unwrap();
+        b.output(&z).unwrap();
+        let w = b.gf8_to_gf4(&x8, &q4, root).unwrap();
+        b.output(&w).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let table = util::gf4_embedding(q8.value(), root);
+        for x in 0..16 {
+            for &y in &[0, 1, 0x53, 0xff] {
+                let X = en.encode_garbler_inputs(&[x]);
+                let Y = en.encode_evaluator_inputs(&[y]);
+                let out = gc.eval(&circ, &X, &Y).unwrap();
+                assert_eq!(out, vec![table[x as usize] ^ y, x]);
+            }
+        }
+    }
+}
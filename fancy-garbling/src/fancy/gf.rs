@@ -0,0 +1,285 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Module containing `GfGadgets`, which are field operations on single
+//! `GF(2^k)` wires and conversions to and from their bits.
+
+use super::{BinaryBundle, Fancy, HasModulus};
+use crate::{errors::FancyError, util, Modulus};
+use alloc::vec::Vec;
+
+impl<F: Fancy> GfGadgets for F {}
+
+/// Extension trait for `Fancy` providing field inversion and division on
+/// `GF(2^4)` and `GF(2^8)` wires, as well as `GF(2^k)` wires for `k <= 8`.
+///
+/// Inversion is a single projection costing `2^k - 1` ciphertexts. As with
+/// `x^(2^k - 2)`, `0` is mapped to `0`, which is the convention of the AES
+/// S-box.
+///
+/// Equality and zero tests give mod-2 wires, so that field elements can drive
+/// Boolean logic, e.g. comparing an authentication tag.
+///
+/// Elements convert to and from their `k` coefficients as mod-2 wires, least
+/// significant first, so that bit-oriented layers can be computed with free
+/// XORs. Splitting costs `k` projections of `2^k - 1` ciphertexts, and joining
+/// `k` projections of a single ciphertext.
+pub trait GfGadgets: Fancy {
+    /// Compute the multiplicative inverse of `x`, mapping `0` to `0`.
+    fn gf_inv(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let q = x.modulus();
+        let tables = gf_tables::<Self::Error>(&q)?;
+        let tt = (0..q.size()).map(|a| tables.inv(a).unwrap_or(0)).collect();
+        self.proj(x, &q, Some(tt))
+    }
+
+    /// Compute `x / y`, where dividing by `0` yields `0`.
+    fn gf_div(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let yinv = self.gf_inv(y)?;
+        self.mul(x, &yinv)
+    }
+
+    /// Return 1 if `x` is `0`, as a mod-2 wire, with a single projection.
+    fn gf_is_zero(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let q = x.modulus();
+        gf_degree::<Self::Error>(&q)?;
+        let tt = (0..q.size()).map(|a| (a == 0) as u16).collect();
+        self.proj(x, &Modulus::Zq { q: 2 }, Some(tt))
+    }
+
+    /// Return 1 if `x == y`, as a mod-2 wire. The difference is free, so this
+    /// costs the same as `gf_is_zero`.
+    fn gf_eq(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let z = self.sub(x, y)?;
+        self.gf_is_zero(&z)
+    }
+
+    /// Split `x` into its `k` bits, least significant first.
+    fn gf_to_bits(&mut self, x: &Self::Item) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let q = x.modulus();
+        let k = gf_degree::<Self::Error>(&q)?;
+        let bits = (0..k)
+            .map(|i| {
+                let tt = (0..q.size()).map(|a| (a >> i) & 1).collect();
+                self.proj(x, &Modulus::Zq { q: 2 }, Some(tt))
+            })
+            .collect::<Result<Vec<Self::Item>, Self::Error>>()?;
+        Ok(BinaryBundle::new(bits))
+    }
+
+    /// Join the bits `bits`, least significant first, into an element modulo
+    /// `q`, inverting `gf_to_bits`.
+    fn bits_to_gf(
+        &mut self,
+        bits: &BinaryBundle<Self::Item>,
+        q: &Modulus,
+    ) -> Result<Self::Item, Self::Error> {
+        let k = gf_degree::<Self::Error>(q)?;
+        if bits.size() != k as usize {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: bits.size(),
+                needed: k as usize,
+            }));
+        }
+        let terms = bits
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                if b.modulus() != (Modulus::Zq { q: 2 }) {
+                    return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                        "expected a mod-2 wire, got modulus {}",
+                        b.modulus()
+                    ))));
+                }
+                self.proj(b, q, Some(vec![0, 1 << i]))
+            })
+            .collect::<Result<Vec<Self::Item>, Self::Error>>()?;
+        self.add_many(&terms)
+    }
+}
+
+// The degree `k` of `q`, checking that it is `GF(2^k)` with `k <= 8`.
+fn gf_degree<E: From<FancyError>>(q: &Modulus) -> Result<u8, E> {
+    match *q {
+        Modulus::GF4 { .. } => Ok(4),
+        Modulus::GF8 { .. } => Ok(8),
+        Modulus::GFk { k, .. } if k <= 8 => Ok(k),
+        _ => Err(E::from(FancyError::InvalidArg(format!(
+            "expected a GF(2^k) wire with k <= 8, got modulus {}",
+            q
+        )))),
+    }
+}
+
+// The log/antilog tables of the field of `q`, checking that it is one.
+fn gf_tables<E: From<FancyError>>(q: &Modulus) -> Result<&'static util::FieldTables, E> {
+    let k = gf_degree(q)?;
+    util::field_tables(q.value(), k).ok_or_else(|| {
+        E::from(FancyError::InvalidArg(format!(
+            "{} is not a field, its polynomial is reducible",
+            q
+        )))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        BinaryGadgets,
+    };
+
+    #[test]
+    fn inverse() {
+        let mut d = Dummy::new();
+        let moduli = [
+            Modulus::X4_X_1,
+            Modulus::X4_X3_X2_X_1,
+            Modulus::GF8 { p: 0x11b },
+            Modulus::GF8_MODULI[7],
+            Modulus::GFk { k: 3, p: 0b1011 },
+        ];
+        for q in moduli.iter() {
+            for x in 0..q.size() {
+                let y = d.gf_inv(&DummyVal::new(x, *q)).unwrap().val();
+                let k = q.bit_length() as u8;
+                if x == 0 {
+                    assert_eq!(y, 0);
+                } else {
+                    assert_eq!(util::field_mul(x, y, q.value(), k), 1, "{}^-1 in {}", x, q);
+                }
+            }
+        }
+        assert!(d.gf_inv(&DummyVal::new(3, Modulus::X4_X2_1)).is_err());
+        assert!(d.gf_inv(&DummyVal::new(3, Modulus::Zq { q: 7 })).is_err());
+    }
+
+    #[test]
+    fn aes_sbox_inverse() {
+        let q = Modulus::GF8 { p: 0x11b };
+        let mut d = Dummy::new();
+        for x in 0..256 {
+            let b = d.gf_inv(&DummyVal::new(x, q)).unwrap().val();
+            let rotl = |n: u32| ((b << n) | (b >> (8 - n))) & 0xff;
+            assert_eq!(
+                crate::primitives::utils::AES_SBOX[x as usize],
+                b ^ rotl(1) ^ rotl(2) ^ rotl(3) ^ rotl(4) ^ 0x63
+            );
+        }
+    }
+
+    #[test]
+    fn garbled_div() {
+        let q = Modulus::X4_X_1;
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = b.evaluator_input(&q);
+        let z = b.gf_div(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        for x in 0..16 {
+            for y in 0..16 {
+                let X = en.encode_garbler_inputs(&[x]);
+                let Y = en.encode_evaluator_inputs(&[y]);
+                let z = gc.eval(&circ, &X, &Y).unwrap()[0];
+                if y == 0 {
+                    assert_eq!(z, 0);
+                } else {
+                    assert_eq!(util::field_mul(z, y, q.value(), 4), x);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn equality() {
+        let mut d = Dummy::new();
+        for q in [Modulus::X4_X_1, Modulus::GF8 { p: 0x11b }].iter() {
+            for x in 0..q.size() {
+                let x_ = DummyVal::new(x, *q);
+                assert_eq!(d.gf_is_zero(&x_).unwrap().val(), (x == 0) as u16);
+                for y in (0..q.size()).step_by(7) {
+                    let y_ = DummyVal::new(y, *q);
+                    let z = d.gf_eq(&x_, &y_).unwrap();
+                    assert_eq!(z.modulus(), Modulus::Zq { q: 2 });
+                    assert_eq!(z.val(), (x == y) as u16);
+                }
+            }
+        }
+        assert!(d.gf_is_zero(&DummyVal::new(3, Modulus::Zq { q: 7 })).is_err());
+    }
+
+    #[test]
+    fn garbled_tag_check() {
+        // compare a tag of two GF(2^4) elements in full
+        let q = Modulus::X4_X_1;
+        let mut b = CircuitBuilder::new();
+        let xs = (0..2).map(|_| b.garbler_input(&q)).collect::<Vec<_>>();
+        let ys = (0..2).map(|_| b.evaluator_input(&q)).collect::<Vec<_>>();
+        let e0 = b.gf_eq(&xs[0], &ys[0]).unwrap();
+        let e1 = b.gf_eq(&xs[1], &ys[1]).unwrap();
+        let ok = b.and(&e0, &e1).unwrap();
+        b.output(&ok).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        for &(x, y) in &[([3, 9], [3, 9]), ([3, 9], [3, 8]), ([0, 0], [0, 0]), ([1, 0], [0, 0])] {
+            let X = en.encode_garbler_inputs(&x);
+            let Y = en.encode_evaluator_inputs(&y);
+            let out = gc.eval(&circ, &X, &Y).unwrap();
+            assert_eq!(out, vec![(x == y) as u16]);
+        }
+    }
+
+    #[test]
+    fn bits_round_trip() {
+        let mut d = Dummy::new();
+        let moduli = [
+            Modulus::X4_X_1,
+            Modulus::GF8 { p: 0x11b },
+            Modulus::GFk { k: 5, p: 0b100101 },
+        ];
+        for q in moduli.iter() {
+            for x in 0..q.size() {
+                let bits = d.gf_to_bits(&DummyVal::new(x, *q)).unwrap();
+                assert_eq!(d.bin_output(&bits).unwrap(), Some(x as u128));
+                assert_eq!(d.bits_to_gf(&bits, q).unwrap().val(), x);
+            }
+        }
+        let bits = d.gf_to_bits(&DummyVal::new(3, Modulus::X4_X_1)).unwrap();
+        assert!(d.bits_to_gf(&bits, &Modulus::GF8 { p: 0x11b }).is_err());
+        assert!(d.gf_to_bits(&DummyVal::new(3, Modulus::Zq { q: 16 })).is_err());
+    }
+
+    #[test]
+    fn garbled_mixed_logic() {
+        // a bit-oriented map followed by a field multiplication, as in ciphers
+        // with a bitsliced S-box and an MDS layer over GF(2^4)
+        let q = Modulus::X4_X_1;
+        let f = |x: u16| {
+            let y = x ^ (((x >> 1) & (x >> 2) & 1) << 3);
+            util::field_mul(y, 0x9, q.value(), 4)
+        };
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let bits = b.gf_to_bits(&x).unwrap();
+        let t = b.and(&bits[1], &bits[2]).unwrap();
+        let mut ws = bits.wires().to_vec();
+        ws[3] = b.xor(&ws[3], &t).unwrap();
+        let y = b.bits_to_gf(&BinaryBundle::new(ws), &q).unwrap();
+        let z = b.cmul(&y, 0x9).unwrap();
+        b.output(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        for x in 0..16 {
+            let X = en.encode_garbler_inputs(&[x]);
+            assert_eq!(gc.eval(&circ, &X, &[]).unwrap(), vec![f(x)]);
+        }
+    }
+}
@@ -7,6 +7,7 @@
 use super::*;
 use crate::wire::Modulus;
 use crate::util;
+use alloc::{string::ToString, vec::Vec};
 use itertools::Itertools;
 
 /// Convenience functions for encoding input to Fancy objects.
@@ -225,4 +226,27 @@ pub trait FancyInput {
             .collect_vec();
         Ok(buns)
     }
+
+    /// Encode a matrix of values, all with modulus `modulus`.
+    fn encode_matrix(
+        &mut self,
+        values: &StateMatrix<u16>,
+        modulus: &Modulus,
+    ) -> Result<StateMatrix<Self::Item>, Self::Error> {
+        let qs = vec![*modulus; values.cells().len()];
+        let ws = self.encode_many(values.cells(), &qs)?;
+        StateMatrix::from_rows(values.nrows(), values.ncols(), ws).map_err(Self::Error::from)
+    }
+
+    /// Receive an `nrows`-by-`ncols` matrix of values, all with modulus
+    /// `modulus`.
+    fn receive_matrix(
+        &mut self,
+        nrows: usize,
+        ncols: usize,
+        modulus: &Modulus,
+    ) -> Result<StateMatrix<Self::Item>, Self::Error> {
+        let ws = self.receive_many(&vec![*modulus; nrows * ncols])?;
+        StateMatrix::from_rows(nrows, ncols, ws).map_err(Self::Error::from)
+    }
 }
@@ -0,0 +1,313 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Module containing `MatrixGadgets`, which multiply matrices of secret CRT or
+//! binary bundles.
+
+use super::{BinaryBundle, BinaryGadgets, CrtBundle, CrtGadgets, Fancy};
+use crate::errors::FancyError;
+use alloc::vec::Vec;
+
+/// How the matrix gadgets order their work. The result does not depend on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatrixHints {
+    /// Evaluate the product in tiles of `block` rows, columns and inner
+    /// indices, so that the bundles a tile touches stay close together.
+    pub block: usize,
+    /// Sum each entry with a balanced tree of additions instead of a chain.
+    ///
+    /// Additions of binary bundles are not free, and a balanced tree lowers the
+    /// depth of the circuit from linear to logarithmic in the inner dimension
+    /// for the same number of gates. That leaves more independent gates per
+    /// level for `classic::garble_parallel` to spread across threads, at the
+    /// cost of keeping more partial sums alive.
+    pub balanced: bool,
+}
+
+impl Default for MatrixHints {
+    fn default() -> Self {
+        MatrixHints {
+            block: 16,
+            balanced: false,
+        }
+    }
+}
+
+impl<F: Fancy> MatrixGadgets for F {}
+
+/// Extension trait for `Fancy` multiplying matrices and vectors of secret
+/// bundles, e.g. for the dense layers of a neural network.
+///
+/// Matrices are given as slices of rows. CRT products are exact modulo the
+/// composite modulus. Binary products wrap around to the width of the bundles,
+/// as integers of that width do.
+pub trait MatrixGadgets: Fancy + CrtGadgets + BinaryGadgets {
+    /// Multiply the matrix `m` with the vector `xs` of CRT bundles.
+    fn crt_matrix_vec_mul(
+        &mut self,
+        m: &[Vec<CrtBundle<Self::Item>>],
+        xs: &[CrtBundle<Self::Item>],
+        hints: MatrixHints,
+    ) -> Result<Vec<CrtBundle<Self::Item>>, Self::Error> {
+        let col = xs.iter().map(|x| vec![x.clone()]).collect::<Vec<_>>();
+        let z = self.crt_matrix_mul(m, &col, hints)?;
+        Ok(z.into_iter().map(|mut row| row.pop().unwrap()).collect())
+    }
+
+    /// Multiply the matrices `a` and `b` of CRT bundles.
+    fn crt_matrix_mul(
+        &mut self,
+        a: &[Vec<CrtBundle<Self::Item>>],
+        b: &[Vec<CrtBundle<Self::Item>>],
+        hints: MatrixHints,
+    ) -> Result<Vec<Vec<CrtBundle<Self::Item>>>, Self::Error> {
+        matrix_mul(
+            self,
+            a,
+            b,
+            hints,
+            |f: &mut Self, x, y| f.crt_mul(x, y),
+            |f: &mut Self, x, y| f.crt_add(x, y),
+        )
+    }
+
+    /// Multiply the matrix `m` with the vector `xs` of binary bundles.
+    fn bin_matrix_vec_mul(
+        &mut self,
+        m: &[Vec<BinaryBundle<Self::Item>>],
+        xs: &[BinaryBundle<Self::Item>],
+        hints: MatrixHints,
+    ) -> Result<Vec<BinaryBundle<Self::Item>>, Self::Error> {
+        let col = xs.iter().map(|x| vec![x.clone()]).collect::<Vec<_>>();
+        let z = self.bin_matrix_mul(m, &col, hints)?;
+        Ok(z.into_iter().map(|mut row| row.pop().unwrap()).collect())
+    }
+
+    /// Multiply the matrices `a` and `b` of binary bundles.
+    fn bin_matrix_mul(
+        &mut self,
+        a: &[Vec<BinaryBundle<Self::Item>>],
+        b: &[Vec<BinaryBundle<Self::Item>>],
+        hints: MatrixHints,
+    ) -> Result<Vec<Vec<BinaryBundle<Self::Item>>>, Self::Error> {
+        matrix_mul(
+            self,
+            a,
+            b,
+            hints,
+            |f: &mut Self, x, y| f.bin_multiplication_lower_half(x, y),
+            |f: &mut Self, x, y| f.bin_addition_no_carry(x, y),
+        )
+    }
+}
+
+// Multiply the `n`-by-`k` matrix `a` with the `k`-by-`m` matrix `b`.
+fn matrix_mul<F, B, M, A>(
+    f: &mut F,
+    a: &[Vec<B>],
+    b: &[Vec<B>],
+    hints: MatrixHints,
+    mut mul: M,
+    mut add: A,
+) -> Result<Vec<Vec<B>>, F::Error>
+where
+    F: Fancy + ?Sized,
+    B: Clone,
+    M: FnMut(&mut F, &B, &B) -> Result<B, F::Error>,
+    A: FnMut(&mut F, &B, &B) -> Result<B, F::Error>,
+{
+    let (n, k) = (a.len(), b.len());
+    let m = b.first().map_or(0, |row| row.len());
+    if k == 0 || m == 0 {
+        return Err(F::Error::from(FancyError::InvalidArgNum { got: 0, needed: 1 }));
+    }
+    for row in a.iter() {
+        if row.len() != k {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: row.len(),
+                needed: k,
+            }));
+        }
+    }
+    for row in b.iter() {
+        if row.len() != m {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: row.len(),
+                needed: m,
+            }));
+        }
+    }
+    let block = hints.block.max(1);
+
+    // the partial sums of each entry, one per block of inner indices
+    let mut partials: Vec<Vec<Vec<B>>> = vec![vec![Vec::new(); m]; n];
+    for i0 in (0..n).step_by(block) {
+        for j0 in (0..m).step_by(block) {
+            for l0 in (0..k).step_by(block) {
+                for i in i0..n.min(i0 + block) {
+                    for j in j0..m.min(j0 + block) {
+                        let terms = (l0..k.min(l0 + block))
+                            .map(|l| mul(f, &a[i][l], &b[l][j]))
+                            .collect::<Result<Vec<B>, F::Error>>()?;
+                        let sum = sum(f, terms, hints.balanced, &mut add)?;
+                        partials[i][j].push(sum);
+                    }
+                }
+            }
+        }
+    }
+    partials
+        .into_iter()
+        .map(|row| {
+            row.into_iter()
+                .map(|terms| sum(f, terms, hints.balanced, &mut add))
+                .collect()
+        })
+        .collect()
+}
+
+// Sum the nonempty `terms` in a chain, or a balanced tree.
+fn sum<F, B, A>(f: &mut F, mut terms: Vec<B>, balanced: bool, add: &mut A) -> Result<B, F::Error>
+where
+    F: Fancy + ?Sized,
+    B: Clone,
+    A: FnMut(&mut F, &B, &B) -> Result<B, F::Error>,
+{
+    if balanced {
+        while terms.len() > 1 {
+            let mut next = Vec::with_capacity((terms.len() + 1) / 2);
+            for pair in terms.chunks(2) {
+                next.push(match pair {
+                    [x, y] => add(f, x, y)?,
+                    [x] => x.clone(),
+                    _ => unreachable!(),
+                });
+            }
+            terms = next;
+        }
+        Ok(terms.pop().unwrap())
+    } else {
+        let mut terms = terms.into_iter();
+        let first = terms.next().unwrap();
+        terms.try_fold(first, |acc, x| add(f, &acc, &x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util::{self, RngExt},
+        BundleGadgets,
+        CrtGadgets,
+        FancyInput,
+    };
+    use rand::thread_rng;
+
+    fn plain_mul(a: &[Vec<u128>], b: &[Vec<u128>], q: u128) -> Vec<Vec<u128>> {
+        a.iter()
+            .map(|row| {
+                (0..b[0].len())
+                    .map(|j| row.iter().zip(b.iter()).fold(0, |s, (x, r)| (s + x * r[j]) % q))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn random_matrix(n: usize, m: usize, bound: u128) -> Vec<Vec<u128>> {
+        let mut rng = thread_rng();
+        (0..n)
+            .map(|_| (0..m).map(|_| rng.gen_u128() % bound).collect())
+            .collect()
+    }
+
+    #[test]
+    fn crt_matrix() {
+        let q = util::modulus_with_width(32);
+        let hints = [
+            MatrixHints::default(),
+            MatrixHints {
+                block: 2,
+                balanced: true,
+            },
+            MatrixHints {
+                block: 3,
+                balanced: false,
+            },
+        ];
+        for &(n, k, m) in &[(1, 1, 1), (3, 5, 2), (7, 4, 6)] {
+            let a = random_matrix(n, k, 1 << 16);
+            let b = random_matrix(k, m, 1 << 16);
+            let should_be = plain_mul(&a, &b, q);
+            for h in hints.iter() {
+                let mut d = Dummy::new();
+                let enc = |d: &mut Dummy, m: &[Vec<u128>]| {
+                    m.iter()
+                        .map(|row| row.iter().map(|x| d.crt_encode(*x, q).unwrap()).collect())
+                        .collect::<Vec<Vec<_>>>()
+                };
+                let a_ = enc(&mut d, &a);
+                let b_ = enc(&mut d, &b);
+                let z = d.crt_matrix_mul(&a_, &b_, *h).unwrap();
+                let z = z
+                    .iter()
+                    .map(|row| d.crt_outputs(row).unwrap().unwrap())
+                    .collect::<Vec<_>>();
+                assert_eq!(z, should_be);
+
+                let xs = b_.iter().map(|row| row[0].clone()).collect::<Vec<_>>();
+                let z = d.crt_matrix_vec_mul(&a_, &xs, *h).unwrap();
+                let z = d.crt_outputs(&z).unwrap().unwrap();
+                assert_eq!(z, should_be.iter().map(|row| row[0]).collect::<Vec<_>>());
+            }
+        }
+        let mut d = Dummy::new();
+        let x = d.crt_encode(1, q).unwrap();
+        let bad = vec![vec![x.clone(), x.clone()], vec![x.clone()]];
+        assert!(d.crt_matrix_mul(&bad, &bad, MatrixHints::default()).is_err());
+        assert!(d.crt_matrix_vec_mul(&[], &[], MatrixHints::default()).is_err());
+    }
+
+    #[test]
+    fn garbled_bin_matrix() {
+        let nbits = 8;
+        let (n, k, m) = (3, 4, 2);
+        let a = random_matrix(n, k, 1 << nbits);
+        let b = random_matrix(k, m, 1 << nbits);
+        let should_be = plain_mul(&a, &b, 1 << nbits);
+        for &balanced in &[false, true] {
+            let hints = MatrixHints { block: 2, balanced };
+            let mut c = CircuitBuilder::new();
+            let a_ = (0..n)
+                .map(|_| (0..k).map(|_| c.bin_garbler_input(nbits)).collect())
+                .collect::<Vec<Vec<_>>>();
+            let b_ = (0..k)
+                .map(|_| (0..m).map(|_| c.bin_evaluator_input(nbits)).collect())
+                .collect::<Vec<Vec<_>>>();
+            let z = c.bin_matrix_mul(&a_, &b_, hints).unwrap();
+            for row in z.iter() {
+                for x in row.iter() {
+                    c.output_bundle(x).unwrap();
+                }
+            }
+            let circ = c.finish();
+            let (en, gc) = garble(&circ).unwrap();
+            let bits = |m: &[Vec<u128>]| {
+                m.iter()
+                    .flatten()
+                    .flat_map(|x| util::u128_to_bits(*x, nbits))
+                    .collect::<Vec<u16>>()
+            };
+            let X = en.encode_garbler_inputs(&bits(&a));
+            let Y = en.encode_evaluator_inputs(&bits(&b));
+            let out = gc.eval(&circ, &X, &Y).unwrap();
+            assert_eq!(out, bits(&should_be));
+        }
+    }
+}
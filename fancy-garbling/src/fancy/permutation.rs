@@ -0,0 +1,337 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Module containing `PermutationGadgets`, which apply a secret permutation to
+//! bundles with a Waksman network, and `waksman_control_bits`, which routes a
+//! permutation through it.
+
+use super::{Bundle, Fancy, HasModulus};
+use crate::{errors::FancyError, Modulus};
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+/// The number of switches, and so of control bits, in the Waksman network on
+/// `n` inputs. This is `n log2(n) - n + 1` when `n` is a power of two.
+pub fn waksman_size(n: usize) -> usize {
+    if n <= 1 {
+        return 0;
+    }
+    let (n1, n2) = (n / 2, n - n / 2);
+    n1 + waksman_size(n1) + waksman_size(n2) + output_switches(n)
+}
+
+/// Compute the control bits making the Waksman network on `perm.len()` inputs
+/// output the `perm[j]`th input as its `j`th output.
+///
+/// This is run in the clear by the party choosing the permutation, which then
+/// inputs the bits as mod-2 wires.
+pub fn waksman_control_bits(perm: &[usize]) -> Result<Vec<u16>, FancyError> {
+    let n = perm.len();
+    let mut inv = vec![n; n];
+    for (j, &x) in perm.iter().enumerate() {
+        if x >= n || inv[x] != n {
+            return Err(FancyError::InvalidArg(format!(
+                "{:?} is not a permutation",
+                perm
+            )));
+        }
+        inv[x] = j;
+    }
+    let mut bits = Vec::with_capacity(waksman_size(n));
+    route(perm, &inv, &mut bits);
+    Ok(bits)
+}
+
+// The number of switches in the output layer of the network on `n` inputs.
+// When `n` is even its last two outputs come straight from the subnetworks.
+fn output_switches(n: usize) -> usize {
+    if n % 2 == 0 {
+        n / 2 - 1
+    } else {
+        n / 2
+    }
+}
+
+// Push the control bits for `perm`, whose inverse is `inv`, onto `bits`, in
+// the order `permute` consumes them: the input layer, the top subnetwork, the
+// bottom subnetwork, and the output layer.
+//
+// Each input is sent to the top (0) or bottom (1) subnetwork. The two inputs of
+// an input switch must go to different ones, as must the inputs bound for the
+// two outputs of an output switch, which chains the inputs into paths and even
+// cycles that can be colored alternately.
+fn route(perm: &[usize], inv: &[usize], bits: &mut Vec<u16>) {
+    let n = perm.len();
+    if n <= 1 {
+        return;
+    }
+    let n1 = n / 2;
+    let in_partner = |x: usize| if x < 2 * n1 { Some(x ^ 1) } else { None };
+    let out_partner = |j: usize| if j < 2 * n1 { Some(j ^ 1) } else { None };
+
+    let mut color: Vec<Option<u16>> = vec![None; n];
+    // color `x` with `c`, unless it already is, and walk along alternating
+    // constraints from it, starting with an input constraint if `via_input`
+    let mut walk = |mut x: usize, mut c: u16, mut via_input: bool| {
+        if color[x].is_some() {
+            return;
+        }
+        loop {
+            if let Some(c_) = color[x] {
+                debug_assert_eq!(c, c_);
+                break;
+            }
+            color[x] = Some(c);
+            let next = if via_input {
+                in_partner(x)
+            } else {
+                out_partner(inv[x]).map(|j| perm[j])
+            };
+            match next {
+                Some(y) => {
+                    x = y;
+                    c ^= 1;
+                    via_input = !via_input;
+                }
+                None => break,
+            }
+        }
+    };
+    // the last output always comes from the bottom subnetwork, and when `n` is
+    // even the one before it from the top, and when `n` is odd the last input
+    // always goes to the bottom
+    walk(perm[n - 1], 1, true);
+    if n % 2 == 0 {
+        walk(perm[n - 2], 0, true);
+    } else {
+        walk(n - 1, 1, false);
+    }
+    for x in 0..n {
+        walk(x, 0, true);
+    }
+    let color = color.into_iter().map(Option::unwrap).collect::<Vec<u16>>();
+
+    for i in 0..n1 {
+        bits.push(color[2 * i]);
+    }
+    // an input `x` enters its subnetwork at position `x / 2`
+    let sub_perm = |c: u16, len: usize| {
+        (0..len)
+            .map(|i| {
+                let j = (2 * i..n.min(2 * i + 2))
+                    .find(|&j| color[perm[j]] == c)
+                    .unwrap();
+                perm[j] / 2
+            })
+            .collect::<Vec<usize>>()
+    };
+    for &(c, len) in &[(0, n1), (1, n - n1)] {
+        let p = sub_perm(c, len);
+        let mut inv = vec![0; len];
+        for (j, &x) in p.iter().enumerate() {
+            inv[x] = j;
+        }
+        route(&p, &inv, bits);
+    }
+    for i in 0..output_switches(n) {
+        bits.push(color[perm[2 * i]]);
+    }
+}
+
+impl<F: Fancy> PermutationGadgets for F {}
+
+/// Extension trait for `Fancy` applying a permutation to a vector of bundles,
+/// chosen by secret control bits, e.g. for shuffles and sort-free PSI.
+///
+/// The Waksman network on `n` inputs has `waksman_size(n)` switches, each of
+/// which conditionally swaps two bundles at the cost of a multiplication by a
+/// bit for each wire. The bundles must have equal moduli, and be mod-2 or
+/// `Zq` wires.
+pub trait PermutationGadgets: Fancy {
+    /// Output the `perm[j]`th bundle of `xs` as the `j`th, where `controls` are
+    /// the mod-2 wires of the bits `waksman_control_bits(perm)`.
+    fn permute<B>(&mut self, xs: &[B], controls: &[Self::Item]) -> Result<Vec<B>, Self::Error>
+    where
+        B: Deref<Target = Bundle<Self::Item>> + From<Bundle<Self::Item>>,
+    {
+        let n = xs.len();
+        if controls.len() != waksman_size(n) {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: controls.len(),
+                needed: waksman_size(n),
+            }));
+        }
+        if let Some(x) = xs.first() {
+            if let Some(y) = xs.iter().find(|y| y.moduli() != x.moduli()) {
+                return Err(Self::Error::from(FancyError::unequal_bundles(
+                    &x.moduli(),
+                    &y.moduli(),
+                )));
+            }
+        }
+        let xs = xs.iter().map(|x| x.wires().to_vec()).collect::<Vec<_>>();
+        let zs = waksman(self, xs, controls)?;
+        Ok(zs.into_iter().map(|z| B::from(Bundle::new(z))).collect())
+    }
+}
+
+// Apply the network to the bundles `xs`, see `route`.
+fn waksman<F: Fancy + ?Sized>(
+    f: &mut F,
+    mut xs: Vec<Vec<F::Item>>,
+    controls: &[F::Item],
+) -> Result<Vec<Vec<F::Item>>, F::Error> {
+    let n = xs.len();
+    if n <= 1 {
+        return Ok(xs);
+    }
+    let (n1, n2) = (n / 2, n - n / 2);
+    let (input_controls, rest) = controls.split_at(n1);
+    let (top_controls, rest) = rest.split_at(waksman_size(n1));
+    let (bottom_controls, output_controls) = rest.split_at(waksman_size(n2));
+
+    let last = if n % 2 == 1 { xs.pop() } else { None };
+    let mut top = Vec::with_capacity(n1);
+    let mut bottom = Vec::with_capacity(n2);
+    let mut xs = xs.into_iter();
+    for c in input_controls.iter() {
+        let (a, b) = switch(f, c, xs.next().unwrap(), xs.next().unwrap())?;
+        top.push(a);
+        bottom.push(b);
+    }
+    bottom.extend(last);
+
+    let top = waksman(f, top, top_controls)?;
+    let bottom = waksman(f, bottom, bottom_controls)?;
+
+    let mut zs = Vec::with_capacity(n);
+    let mut top = top.into_iter();
+    let mut bottom = bottom.into_iter();
+    for c in output_controls.iter() {
+        let (a, b) = switch(f, c, top.next().unwrap(), bottom.next().unwrap())?;
+        zs.push(a);
+        zs.push(b);
+    }
+    zs.extend(top);
+    zs.extend(bottom);
+    Ok(zs)
+}
+
+// If `c = 1` swap the bundles `xs` and `ys`, with a multiplication per wire.
+fn switch<F: Fancy + ?Sized>(
+    f: &mut F,
+    c: &F::Item,
+    xs: Vec<F::Item>,
+    ys: Vec<F::Item>,
+) -> Result<(Vec<F::Item>, Vec<F::Item>), F::Error> {
+    if c.modulus() != (Modulus::Zq { q: 2 }) {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: c.modulus(),
+            needed: Modulus::Zq { q: 2 },
+        }));
+    }
+    let mut zs = Vec::with_capacity(xs.len());
+    let mut ws = Vec::with_capacity(ys.len());
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        let diff = f.sub(y, x)?;
+        let d = f.mul(&diff, c)?;
+        zs.push(f.add(x, &d)?);
+        ws.push(f.sub(y, &d)?);
+    }
+    Ok((zs, ws))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util::{self, RngExt},
+        BinaryBundle,
+        BundleGadgets,
+        CrtBundle,
+        CrtGadgets,
+        FancyInput,
+    };
+    use rand::{seq::SliceRandom, thread_rng};
+
+    #[test]
+    fn sizes() {
+        assert_eq!(waksman_size(1), 0);
+        assert_eq!(waksman_size(2), 1);
+        assert_eq!(waksman_size(3), 3);
+        assert_eq!(waksman_size(4), 5);
+        assert_eq!(waksman_size(8), 17);
+        assert_eq!(waksman_size(1 << 10), (1 << 10) * 10 - (1 << 10) + 1);
+    }
+
+    #[test]
+    fn dummy() {
+        let mut rng = thread_rng();
+        for n in 0..40 {
+            for _ in 0..8 {
+                let mut perm = (0..n).collect::<Vec<usize>>();
+                perm.shuffle(&mut rng);
+                let bits = waksman_control_bits(&perm).unwrap();
+                assert_eq!(bits.len(), waksman_size(n));
+
+                let q = util::modulus_with_width(16);
+                let xs = (0..n).map(|_| rng.gen_u128() % q).collect::<Vec<_>>();
+                let mut d = Dummy::new();
+                let xs_ = xs
+                    .iter()
+                    .map(|x| d.crt_encode(*x, q).unwrap())
+                    .collect::<Vec<CrtBundle<_>>>();
+                let cs = d.encode_many(&bits, &vec![Modulus::Zq { q: 2 }; bits.len()]).unwrap();
+                let zs = d.permute(&xs_, &cs).unwrap();
+                let zs = d.crt_outputs(&zs).unwrap().unwrap();
+                let should_be = perm.iter().map(|&x| xs[x]).collect::<Vec<_>>();
+                assert_eq!(zs, should_be, "{:?}", perm);
+            }
+        }
+        assert!(waksman_control_bits(&[0, 0]).is_err());
+        assert!(waksman_control_bits(&[2, 0]).is_err());
+        let mut d = Dummy::new();
+        let x = d.bin_encode(1, 4).unwrap();
+        assert!(d.permute(&[x.clone(), x], &[]).is_err());
+    }
+
+    #[test]
+    fn garbled() {
+        let mut rng = thread_rng();
+        let (n, nbits) = (11, 8);
+        let mut b = CircuitBuilder::new();
+        let xs = (0..n)
+            .map(|_| b.bin_evaluator_input(nbits))
+            .collect::<Vec<BinaryBundle<_>>>();
+        let cs = (0..waksman_size(n))
+            .map(|_| b.garbler_input(&Modulus::Zq { q: 2 }))
+            .collect::<Vec<_>>();
+        let zs = b.permute(&xs, &cs).unwrap();
+        for z in zs.iter() {
+            b.output_bundle(z).unwrap();
+        }
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        for _ in 0..8 {
+            let mut perm = (0..n).collect::<Vec<usize>>();
+            perm.shuffle(&mut rng);
+            let xs = (0..n).map(|_| rng.gen_u128() % (1 << nbits)).collect::<Vec<_>>();
+            let bits = |xs: &[u128]| {
+                xs.iter()
+                    .flat_map(|x| util::u128_to_bits(*x, nbits))
+                    .collect::<Vec<u16>>()
+            };
+            let X = en.encode_garbler_inputs(&waksman_control_bits(&perm).unwrap());
+            let Y = en.encode_evaluator_inputs(&bits(&xs));
+            let out = gc.eval(&circ, &X, &Y).unwrap();
+            let should_be = perm.iter().map(|&x| xs[x]).collect::<Vec<_>>();
+            assert_eq!(out, bits(&should_be));
+        }
+    }
+}
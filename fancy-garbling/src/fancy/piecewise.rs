@@ -0,0 +1,272 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Module containing `PiecewiseGadgets`, which evaluate public piecewise-linear
+//! functions on CRT bundles, and `PiecewiseLinear`, which describes them.
+
+use super::{CrtBundle, CrtGadgets, Fancy};
+use crate::errors::FancyError;
+use alloc::{string::ToString, vec::Vec};
+use core::cmp::Ordering;
+
+/// A function of signed integers which is linear between public breakpoints.
+///
+/// The `i`th piece `(a, b)` maps `x` to `a * x + b` for `x` from breakpoint
+/// `i - 1`, inclusive, to breakpoint `i`, exclusive. There is one more piece
+/// than there are breakpoints, the first covering everything below the first
+/// breakpoint, and the last everything from the last breakpoint on.
+///
+/// As CRT bundles hold integers, slopes and intercepts are integers too. To
+/// approximate a function of reals, encode `x` as the fixed-point `x * s`, and
+/// take the output to be `f(x) * s^2`, as for the product of two fixed-point
+/// numbers of scale `s`. This is what `from_fn` and the helpers built on it do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PiecewiseLinear {
+    breakpoints: Vec<i128>,
+    pieces: Vec<(i128, i128)>,
+}
+
+impl PiecewiseLinear {
+    /// Create a function from its increasing `breakpoints` and its `pieces`,
+    /// of which there must be one more than there are breakpoints.
+    pub fn new(breakpoints: Vec<i128>, pieces: Vec<(i128, i128)>) -> Result<Self, FancyError> {
+        if pieces.len() != breakpoints.len() + 1 {
+            return Err(FancyError::InvalidArgNum {
+                got: pieces.len(),
+                needed: breakpoints.len() + 1,
+            });
+        }
+        if breakpoints.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(FancyError::InvalidArg(
+                "breakpoints must be strictly increasing".to_string(),
+            ));
+        }
+        Ok(PiecewiseLinear {
+            breakpoints,
+            pieces,
+        })
+    }
+
+    /// Approximate `f` on `[lo, hi]` by `n` chords, for inputs and outputs of
+    /// fixed-point scale `scale` and `scale^2`. Outside of `[lo, hi]` the
+    /// approximation is constant.
+    ///
+    /// Slopes are rounded to multiples of `1 / scale`, so each chord is off by
+    /// at most `(hi - lo) / (2 n scale)` at its right end, on top of the error
+    /// of the chord itself.
+    pub fn from_fn<F: Fn(f64) -> f64>(
+        f: F,
+        lo: f64,
+        hi: f64,
+        n: usize,
+        scale: i128,
+    ) -> Result<Self, FancyError> {
+        if n == 0 || scale <= 0 || lo.partial_cmp(&hi) != Some(Ordering::Less) {
+            return Err(FancyError::InvalidArg(format!(
+                "cannot approximate on [{}, {}] by {} chords at scale {}",
+                lo, hi, n, scale
+            )));
+        }
+        let s = scale as f64;
+        let breakpoints = (0..=n)
+            .map(|i| ((lo + (hi - lo) * i as f64 / n as f64) * s).round() as i128)
+            .collect::<Vec<i128>>();
+        let mut pieces = Vec::with_capacity(n + 2);
+        pieces.push((0, (f(lo) * s * s).round() as i128));
+        for w in breakpoints.windows(2) {
+            let (x0, x1) = (w[0] as f64 / s, w[1] as f64 / s);
+            let slope = (f(x1) - f(x0)) / (x1 - x0);
+            let a = (slope * s).round() as i128;
+            let b = (f(x0) * s * s).round() as i128 - a * w[0];
+            pieces.push((a, b));
+        }
+        pieces.push((0, (f(hi) * s * s).round() as i128));
+        PiecewiseLinear::new(breakpoints, pieces)
+    }
+
+    /// Approximate the logistic sigmoid `1 / (1 + e^-x)` on `[-8, 8]` by `n`
+    /// chords, see `from_fn`.
+    pub fn sigmoid(n: usize, scale: i128) -> Result<Self, FancyError> {
+        PiecewiseLinear::from_fn(|x| 1.0 / (1.0 + (-x).exp()), -8.0, 8.0, n, scale)
+    }
+
+    /// Approximate `tanh` on `[-4, 4]` by `n` chords, see `from_fn`.
+    pub fn tanh(n: usize, scale: i128) -> Result<Self, FancyError> {
+        PiecewiseLinear::from_fn(f64::tanh, -4.0, 4.0, n, scale)
+    }
+
+    /// Approximate `e^x` on `[lo, hi]` by `n` chords, see `from_fn`. The
+    /// output must fit the bundles, so `hi` is best kept small.
+    pub fn exp(lo: f64, hi: f64, n: usize, scale: i128) -> Result<Self, FancyError> {
+        PiecewiseLinear::from_fn(f64::exp, lo, hi, n, scale)
+    }
+
+    /// The breakpoints.
+    pub fn breakpoints(&self) -> &[i128] {
+        &self.breakpoints
+    }
+
+    /// The slope and intercept of each piece.
+    pub fn pieces(&self) -> &[(i128, i128)] {
+        &self.pieces
+    }
+
+    /// Evaluate the function in the clear.
+    pub fn eval(&self, x: i128) -> i128 {
+        let i = self.breakpoints.iter().take_while(|&&t| t <= x).count();
+        let (a, b) = self.pieces[i];
+        a * x + b
+    }
+}
+
+impl<F: Fancy> PiecewiseGadgets for F {}
+
+/// Extension trait for `Fancy` evaluating `PiecewiseLinear` functions on CRT
+/// bundles, e.g. the activation functions of a neural network.
+///
+/// Bundles are signed as for `crt_sign`: values from `Q/2` up are negative. The
+/// input must be within `Q/2` of every breakpoint, and the output must fit in
+/// the bundle. Each breakpoint costs a `crt_sign`, with the given accuracy, and
+/// a multiplication by a bit for every modulus.
+pub trait PiecewiseGadgets: Fancy + CrtGadgets {
+    /// Evaluate `f` on `x`.
+    fn crt_piecewise_linear(
+        &mut self,
+        x: &CrtBundle<Self::Item>,
+        f: &PiecewiseLinear,
+        accuracy: &str,
+    ) -> Result<CrtBundle<Self::Item>, Self::Error> {
+        let q = x.composite_modulus();
+        let affine = |this: &mut Self, a: i128, b: i128| {
+            let ax = this.crt_cmul(x, to_zq(a, q))?;
+            let b = this.crt_constant_bundle(to_zq(b, q), q)?;
+            this.crt_add(&ax, &b)
+        };
+        let (a0, b0) = f.pieces[0];
+        let mut z = affine(self, a0, b0)?;
+        for (t, w) in f.breakpoints.iter().zip(f.pieces.windows(2)) {
+            // from t on, switch from the previous piece to the next one
+            let t_ = self.crt_constant_bundle(to_zq(*t, q), q)?;
+            let d = self.crt_sub(x, &t_)?;
+            let below = self.crt_sign(&d, accuracy)?;
+            let above = self.negate(&below)?;
+            let diff = affine(self, w[1].0 - w[0].0, w[1].1 - w[0].1)?;
+            let diff = diff
+                .wires()
+                .iter()
+                .map(|w| self.mul(w, &above))
+                .collect::<Result<Vec<Self::Item>, Self::Error>>()
+                .map(CrtBundle::new)?;
+            z = self.crt_add(&z, &diff)?;
+        }
+        Ok(z)
+    }
+}
+
+// The residue of `c` modulo `q`.
+fn to_zq(c: i128, q: u128) -> u128 {
+    let r = c.unsigned_abs() % q;
+    if c < 0 && r > 0 {
+        q - r
+    } else {
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util::{self, RngExt},
+        BundleGadgets,
+        FancyInput,
+    };
+    use rand::thread_rng;
+
+    fn from_zq(x: u128, q: u128) -> i128 {
+        if x >= q / 2 {
+            -((q - x) as i128)
+        } else {
+            x as i128
+        }
+    }
+
+    #[test]
+    fn dummy() {
+        let mut rng = thread_rng();
+        let q = util::modulus_with_nprimes(10);
+        let f = PiecewiseLinear::new(
+            vec![-1000, 0, 50, 51, 7000],
+            vec![(0, -3), (2, 5), (-7, 1), (0, 0), (1, -100), (3, 11)],
+        )
+        .unwrap();
+        let mut xs = vec![-1001, -1000, -1, 0, 49, 50, 51, 6999, 7000];
+        xs.extend((0..64).map(|_| (rng.gen_u128() % 40_000) as i128 - 20_000));
+        for &x in xs.iter() {
+            let mut d = Dummy::new();
+            let x_ = d.crt_encode(to_zq(x, q), q).unwrap();
+            let z = d.crt_piecewise_linear(&x_, &f, "100%").unwrap();
+            let z = from_zq(d.crt_output(&z).unwrap().unwrap(), q);
+            assert_eq!(z, f.eval(x), "f({})", x);
+        }
+        assert!(PiecewiseLinear::new(vec![1, 1], vec![(0, 0); 3]).is_err());
+        assert!(PiecewiseLinear::new(vec![1, 2], vec![(0, 0); 2]).is_err());
+        assert!(PiecewiseLinear::from_fn(|x| x, f64::NAN, 1.0, 4, 16).is_err());
+        assert!(PiecewiseLinear::from_fn(|x| x, 1.0, 1.0, 4, 16).is_err());
+    }
+
+    #[test]
+    fn approximations() {
+        let scale = 1 << 10;
+        let s = scale as f64;
+        let fs: [(PiecewiseLinear, fn(f64) -> f64, f64); 3] = [
+            (
+                PiecewiseLinear::sigmoid(32, scale).unwrap(),
+                |x| 1.0 / (1.0 + (-x).exp()),
+                0.01,
+            ),
+            (PiecewiseLinear::tanh(32, scale).unwrap(), f64::tanh, 0.01),
+            (
+                PiecewiseLinear::exp(-4.0, 2.0, 64, scale).unwrap(),
+                f64::exp,
+                0.02,
+            ),
+        ];
+        for (pwl, f, tolerance) in fs.iter() {
+            for i in -6000..6000 {
+                let x = i as f64 / 1000.0;
+                let y = pwl.eval((x * s).round() as i128) as f64 / (s * s);
+                let lo = pwl.breakpoints()[0] as f64 / s;
+                let hi = *pwl.breakpoints().last().unwrap() as f64 / s;
+                let expected = f(x.max(lo).min(hi));
+                assert!((y - expected).abs() < *tolerance, "{} vs {} at {}", y, expected, x);
+            }
+        }
+    }
+
+    #[test]
+    fn garbled_sigmoid() {
+        let q = util::modulus_with_nprimes(8);
+        let scale = 1 << 8;
+        let f = PiecewiseLinear::sigmoid(4, scale).unwrap();
+        let mut b = CircuitBuilder::new();
+        let x = b.crt_garbler_input(q);
+        let z = b.crt_piecewise_linear(&x, &f, "100%").unwrap();
+        b.output_bundle(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let ps = util::factor(q);
+        for &x in &[-3000, -1024, -5, 0, 77, 1024, 1500, 4000] {
+            let X = en.encode_garbler_inputs(&util::crt(to_zq(x, q), &ps));
+            let out = gc.eval(&circ, &X, &[]).unwrap();
+            let z = from_zq(util::crt_inv(&out, &ps), q);
+            assert_eq!(z, f.eval(x), "f({})", x);
+        }
+    }
+}
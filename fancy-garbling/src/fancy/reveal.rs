@@ -6,6 +6,7 @@
 
 use super::*;
 use crate::util;
+use alloc::vec::Vec;
 
 /// Trait to describe Fancy objects which can reveal outputs to both parties. For many
 /// simple Fancy objects in this library such as Dummy, this is simply output. For Garbler
@@ -0,0 +1,169 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Module containing `StateMatrix`, a matrix of cells such as the state of
+//! PHOTON, LED, or AES.
+
+use crate::errors::FancyError;
+use alloc::vec::Vec;
+
+/// A matrix of cells, which may be wires or plain values, stored row-major.
+///
+/// Ciphers disagree about how a vector of cells fills their state: PHOTON
+/// and LED fill it by rows and AES by columns. `from_rows` and `from_columns`
+/// make that choice explicit, and everything else is indexed by row `i` and
+/// column `j` or listed row by row, except `col` and `to_column_major`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateMatrix<W> {
+    nrows: usize,
+    ncols: usize,
+    cells: Vec<W>,
+}
+
+impl<W: Clone> StateMatrix<W> {
+    /// Create an `nrows`-by-`ncols` matrix from its cells listed row by row.
+    pub fn from_rows(nrows: usize, ncols: usize, cells: Vec<W>) -> Result<Self, FancyError> {
+        if cells.len() != nrows * ncols {
+            return Err(FancyError::InvalidArgNum {
+                got: cells.len(),
+                needed: nrows * ncols,
+            });
+        }
+        Ok(StateMatrix {
+            nrows,
+            ncols,
+            cells,
+        })
+    }
+
+    /// Create an `nrows`-by-`ncols` matrix from its cells listed column by
+    /// column.
+    pub fn from_columns(nrows: usize, ncols: usize, cells: Vec<W>) -> Result<Self, FancyError> {
+        if cells.len() != nrows * ncols {
+            return Err(FancyError::InvalidArgNum {
+                got: cells.len(),
+                needed: nrows * ncols,
+            });
+        }
+        let cells = (0..nrows * ncols)
+            .map(|k| cells[(k % ncols) * nrows + k / ncols].clone())
+            .collect();
+        Ok(StateMatrix {
+            nrows,
+            ncols,
+            cells,
+        })
+    }
+
+    /// The number of rows.
+    pub fn nrows(&self) -> usize {
+        self.nrows
+    }
+
+    /// The number of columns.
+    pub fn ncols(&self) -> usize {
+        self.ncols
+    }
+
+    /// The cell in row `i` and column `j`.
+    pub fn get(&self, i: usize, j: usize) -> &W {
+        assert!(i < self.nrows && j < self.ncols);
+        &self.cells[i * self.ncols + j]
+    }
+
+    /// Replace the cell in row `i` and column `j`.
+    pub fn set(&mut self, i: usize, j: usize, x: W) {
+        assert!(i < self.nrows && j < self.ncols);
+        self.cells[i * self.ncols + j] = x;
+    }
+
+    /// Row `i`.
+    pub fn row(&self, i: usize) -> &[W] {
+        assert!(i < self.nrows);
+        &self.cells[i * self.ncols..][..self.ncols]
+    }
+
+    /// Column `j`, from the top.
+    pub fn col(&self, j: usize) -> Vec<W> {
+        assert!(j < self.ncols);
+        self.cells.iter().skip(j).step_by(self.ncols).cloned().collect()
+    }
+
+    /// Replace row `i` with the `ncols` cells `xs`.
+    pub fn set_row(&mut self, i: usize, xs: &[W]) {
+        assert!(i < self.nrows && xs.len() == self.ncols);
+        self.cells[i * self.ncols..][..self.ncols].clone_from_slice(xs);
+    }
+
+    /// Replace column `j` with the `nrows` cells `xs`, from the top.
+    pub fn set_col(&mut self, j: usize, xs: &[W]) {
+        assert!(j < self.ncols && xs.len() == self.nrows);
+        for (i, x) in xs.iter().enumerate() {
+            self.cells[i * self.ncols + j] = x.clone();
+        }
+    }
+
+    /// The cells, row by row.
+    pub fn cells(&self) -> &[W] {
+        &self.cells
+    }
+
+    /// The cells, column by column.
+    pub fn to_column_major(&self) -> Vec<W> {
+        (0..self.cells.len())
+            .map(|k| self.cells[(k % self.nrows) * self.ncols + k / self.nrows].clone())
+            .collect()
+    }
+
+    /// Take the cells, row by row.
+    pub fn into_cells(self) -> Vec<W> {
+        self.cells
+    }
+
+    /// Apply `f` to every cell.
+    pub fn map<U, G: FnMut(&W) -> U>(&self, f: G) -> StateMatrix<U> {
+        StateMatrix {
+            nrows: self.nrows,
+            ncols: self.ncols,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders() {
+        // 0 1 2
+        // 3 4 5
+        let m = StateMatrix::from_rows(2, 3, (0..6).collect()).unwrap();
+        assert_eq!(*m.get(1, 0), 3);
+        assert_eq!(m.to_column_major(), vec![0, 3, 1, 4, 2, 5]);
+        let n = StateMatrix::from_columns(2, 3, m.to_column_major()).unwrap();
+        assert_eq!(m, n);
+        let mut n = n.map(|x| x * 2);
+        n.set(0, 2, 7);
+        assert_eq!(n.into_cells(), vec![0, 2, 7, 6, 8, 10]);
+        assert!(StateMatrix::from_rows(2, 2, vec![0; 3]).is_err());
+        assert_eq!(m.row(1), &[3, 4, 5]);
+        assert_eq!(m.col(2), vec![2, 5]);
+        assert!(StateMatrix::from_columns(2, 2, vec![0; 5]).is_err());
+    }
+
+    #[test]
+    fn slices() {
+        let mut m = StateMatrix::from_rows(3, 2, vec![0; 6]).unwrap();
+        m.set_row(1, &[1, 2]);
+        m.set_col(0, &[7, 8, 9]);
+        assert_eq!(m.cells(), &[7, 0, 8, 2, 9, 0]);
+        let cols = (0..2).flat_map(|j| m.col(j)).collect::<Vec<_>>();
+        assert_eq!(cols, m.to_column_major());
+        let rows = (0..3).flat_map(|i| m.row(i).to_vec()).collect::<Vec<_>>();
+        assert_eq!(rows, m.into_cells());
+    }
+}
@@ -0,0 +1,125 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Module containing `ZqGadgets`, which are arithmetic gadgets for single `Zq` wires.
+
+use super::{Fancy, HasModulus};
+use crate::{errors::FancyError, util, Modulus};
+
+impl<F: Fancy> ZqGadgets for F {}
+
+/// Extension trait for `Fancy` providing modular arithmetic on `Zq` wires.
+///
+/// Unary functions of a single wire are computed with one projection, which
+/// costs `q - 1` ciphertexts. That is less than a single half-gate
+/// multiplication, so it beats square-and-multiply for every exponent.
+pub trait ZqGadgets: Fancy {
+    /// Compute `x^e mod q` for a public exponent `e`.
+    ///
+    /// By convention `0^0 = 1`.
+    fn zq_pow_const(&mut self, x: &Self::Item, e: u32) -> Result<Self::Item, Self::Error> {
+        let q = zq_modulus(x)?;
+        if e == 1 {
+            return Ok(x.clone());
+        }
+        let tt = (0..q).map(|i| util::pow_mod(i, e, q)).collect();
+        self.proj(x, &Modulus::Zq { q }, Some(tt))
+    }
+
+    /// Compute the multiplicative inverse of `x` modulo a prime `q`, mapping `0` to
+    /// `0`.
+    fn zq_inverse(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let q = zq_modulus(x)?;
+        if !util::is_prime(q) {
+            return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                "zq_inverse requires a prime modulus, got {}",
+                q
+            ))));
+        }
+        // Fermat: x^(q-2) = x^-1 for x != 0, and 0^(q-2) = 0 for q > 2
+        let tt = (0..q)
+            .map(|i| if i == 0 { 0 } else { util::pow_mod(i, q as u32 - 2, q) })
+            .collect();
+        self.proj(x, &Modulus::Zq { q }, Some(tt))
+    }
+
+    /// Compute `x / y mod q` for a prime `q`, where dividing by `0` yields `0`.
+    fn zq_div(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let yinv = self.zq_inverse(y)?;
+        self.mul(x, &yinv)
+    }
+}
+
+fn zq_modulus<W: HasModulus, E: From<FancyError>>(x: &W) -> Result<u16, E> {
+    match x.modulus() {
+        Modulus::Zq { q } => Ok(q),
+        m => Err(E::from(FancyError::InvalidArg(format!(
+            "expected a Zq wire, got modulus {}",
+            m
+        )))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+    };
+
+    #[test]
+    fn pow_const() {
+        let mut d = Dummy::new();
+        for &q in &[2, 3, 7, 17, 100, 257] {
+            for e in 0..10 {
+                for x in 0..q {
+                    let z = d.zq_pow_const(&DummyVal::new(x, Modulus::Zq { q }), e).unwrap();
+                    let expected = (0..e).fold(1 % q as u32, |acc, _| acc * x as u32 % q as u32);
+                    assert_eq!(z.val() as u32, expected, "{}^{} mod {}", x, e, q);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        let mut d = Dummy::new();
+        for &q in &[3, 5, 7, 11, 13, 101, 257] {
+            for x in 0..q {
+                let y = d.zq_inverse(&DummyVal::new(x, Modulus::Zq { q })).unwrap();
+                if x == 0 {
+                    assert_eq!(y.val(), 0);
+                } else {
+                    assert_eq!(x as u32 * y.val() as u32 % q as u32, 1);
+                }
+            }
+        }
+        assert!(d.zq_inverse(&DummyVal::new(3, Modulus::Zq { q: 15 })).is_err());
+        assert!(d.zq_inverse(&DummyVal::new(3, Modulus::X4_X_1)).is_err());
+    }
+
+    #[test]
+    fn garbled_div() {
+        let q = 13;
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&Modulus::Zq { q });
+        let y = b.evaluator_input(&Modulus::Zq { q });
+        let z = b.zq_div(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        for x in 0..q {
+            for y in 1..q {
+                let X = en.encode_garbler_inputs(&[x]);
+                let Y = en.encode_evaluator_inputs(&[y]);
+                let z = gc.eval(&circ, &X, &Y).unwrap()[0];
+                assert_eq!(z as u32 * y as u32 % q as u32, x as u32);
+            }
+        }
+    }
+}
@@ -8,8 +8,57 @@
 
 mod evaluator;
 mod garbler;
+#[cfg(any(test, feature = "debug_invariants"))]
+mod invariants;
+mod stack;
+pub(crate) mod three_halves;
+
+pub use crate::garble::{
+    evaluator::{Evaluator, EvaluatorLimits},
+    garbler::Garbler,
+};
+#[cfg(any(test, feature = "debug_invariants"))]
+pub use crate::garble::invariants::{CheckedWire, InvariantChecker};
+
+/// How a `Garbler` garbles gates, which its `Evaluator` must agree on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub enum GarbleMode {
+    /// Standard garbling, which hides every wire value from the evaluator.
+    #[default]
+    Standard,
+    /// Privacy-free garbling, for when the evaluator knows all the inputs, as
+    /// in garbled-circuit zero-knowledge proofs. The evaluator learns the value
+    /// of every wire from the color of its label, in exchange for
+    /// multiplications costing `qb - 1` ciphertexts instead of `q + qb - 2`.
+    /// Only `Zq` wires can be multiplied or projected in this mode.
+    PrivacyFree,
+    /// Standard garbling, except that multiplications of two `Zq { q: 2 }`
+    /// wires, i.e. AND gates, use the three-halves scheme of Rosulek and Roy:
+    /// three half ciphertexts and three control bytes, 27 bytes instead of 32.
+    /// Consecutive AND gates are packed into whole blocks.
+    ThreeHalves,
+}
+
+impl GarbleMode {
+    // The byte identifying the mode in garbled streams and saved circuits.
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            GarbleMode::Standard => 0,
+            GarbleMode::PrivacyFree => 1,
+            GarbleMode::ThreeHalves => 2,
+        }
+    }
 
-pub use crate::garble::{evaluator::Evaluator, garbler::Garbler};
+    pub(crate) fn from_byte(b: u8) -> Option<GarbleMode> {
+        match b {
+            0 => Some(GarbleMode::Standard),
+            1 => Some(GarbleMode::PrivacyFree),
+            2 => Some(GarbleMode::ThreeHalves),
+            _ => None,
+        }
+    }
+}
 
 ////////////////////////////////////////////////////////////////////////////////
 // tests
@@ -17,14 +66,14 @@ pub use crate::garble::{evaluator::Evaluator, garbler::Garbler};
 #[cfg(test)]
 mod nonstreaming {
     use crate::{
-        circuit::{Circuit, CircuitBuilder},
-        classic::garble,
-        fancy::{Bundle, BundleGadgets, Fancy},
+        circuit::{Circuit, CircuitBuilder, SubCircuit},
+        classic::{garble, garble_with_mode, garble_with_rng, Encoder, GarbleSession, GarbledCircuit},
+        fancy::{Bundle, BundleGadgets, Fancy, FancyBranch},
         util::{self, RngExt},
-        Modulus,
+        GarbleMode, Modulus,
     };
     use itertools::Itertools;
-    use rand::{thread_rng, SeedableRng};
+    use rand::{thread_rng, Rng, SeedableRng};
     use scuttlebutt::{AesRng, Block};
 
     // helper
@@ -283,23 +332,408 @@ mod nonstreaming {
             assert_eq!(Y[0], (x + c) % q, "garbled");
         }
     }
+
+    #[test] // linear circuit over a modulus larger than u16
+    fn zq_big() {
+        let mut rng = thread_rng();
+        let q = 4294967291; // largest prime below 2^32
+        let m = Modulus::ZqBig { q };
+        let c = rng.gen::<u64>() % q;
+        let k = rng.gen::<u64>() % q;
+
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&m);
+        let y = b.evaluator_input(&m);
+        let z = b.cmul_big(&x, c).unwrap();
+        let z = b.sub(&z, &y).unwrap();
+        let kref = b.constant_big(k, &m).unwrap();
+        let z = b.add(&z, &kref).unwrap();
+        b.output_big(&z).unwrap();
+        let circ = b.finish();
+
+        let (en, ev) = garble(&circ).unwrap();
+        for _ in 0..64 {
+            let x = rng.gen::<u64>() % q;
+            let y = rng.gen::<u64>() % q;
+            let should_be = ((x as u128 * c as u128 + (q - y) as u128 + k as u128)
+                % q as u128) as u64;
+
+            let outputs = circ.eval_plain_big(&[x], &[y]).unwrap();
+            assert_eq!(outputs[0], should_be, "plaintext");
+
+            let X = en.encode_garbler_inputs_big(&[x]);
+            let Y = en.encode_evaluator_inputs_big(&[y]);
+            let outputs = ev.eval_big(&circ, &X, &Y).unwrap();
+            assert_eq!(outputs[0], should_be, "garbled");
+        }
+    }
+
+    #[test] // rerandomize
+    fn rerandomize() {
+        let mut rng = thread_rng();
+        let q = rng.gen_prime();
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&Modulus::Zq { q });
+        let y = b.evaluator_input(&Modulus::Zq { q });
+        let z = b.mul(&x, &y).unwrap();
+        let w = b.mod_change(&z, 2).unwrap();
+        b.outputs(&[z, w]).unwrap();
+        let circ = b.finish();
+
+        let (en, gc) = garble(&circ).unwrap();
+        let seed = rng.gen::<Block>();
+        let (en1, gc1) = gc.rerandomize(&circ, seed).unwrap();
+        let (en2, gc2) = gc.rerandomize(&circ, seed).unwrap();
+        let (_, gc3) = gc.rerandomize(&circ, rng.gen::<Block>()).unwrap();
+        assert_eq!(gc1, gc2);
+        assert_ne!(gc, gc1);
+        assert_ne!(gc1, gc3);
+        assert_eq!(gc1.mode(), gc.mode());
+
+        for _ in 0..16 {
+            let x = rng.gen_u16() % q;
+            let y = rng.gen_u16() % q;
+            let X = en1.encode_garbler_inputs(&[x]);
+            let Y = en1.encode_evaluator_inputs(&[y]);
+            assert_eq!(X, en2.encode_garbler_inputs(&[x]));
+            let outputs = gc1.eval(&circ, &X, &Y).unwrap();
+            assert_eq!(outputs, circ.eval_plain(&[x], &[y]).unwrap());
+
+            // the labels are fresh: the old ones do not evaluate the new tables
+            let X0 = en.encode_garbler_inputs(&[x]);
+            let Y0 = en.encode_evaluator_inputs(&[y]);
+            assert_ne!(X, X0);
+            assert_ne!(Y, Y0);
+            assert!(gc1.eval(&circ, &X0, &Y0).is_err());
+        }
+
+        // a different circuit does not rerandomize this garbled circuit
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&Modulus::Zq { q });
+        b.output(&x).unwrap();
+        assert!(gc.rerandomize(&b.finish(), seed).is_err());
+    }
+
+    #[test] // regarbling a circuit in a session
+    fn garble_session() {
+        let mut rng = thread_rng();
+        let circ = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        for &mode in &[GarbleMode::Standard, GarbleMode::ThreeHalves] {
+            let mut session = GarbleSession::new(&circ, mode).unwrap();
+            let (en1, gc1) = session.regarble().unwrap();
+            let (en2, gc2) = session.regarble().unwrap();
+            assert_eq!(gc1.size(), session.size());
+            assert_eq!(gc2.size(), session.size());
+            assert_ne!(gc1, gc2);
+
+            let x = (0..128).map(|_| rng.gen_u16() % 2).collect_vec();
+            let y = (0..128).map(|_| rng.gen_u16() % 2).collect_vec();
+            let should_be = circ.eval_plain(&x, &y).unwrap();
+            assert_ne!(en1.encode_garbler_inputs(&x), en2.encode_garbler_inputs(&x));
+            for (en, gc) in [(en1, gc1), (en2, gc2)].iter() {
+                let X = en.encode_garbler_inputs(&x);
+                let Y = en.encode_evaluator_inputs(&y);
+                assert_eq!(gc.eval(&circ, &X, &Y).unwrap(), should_be);
+            }
+        }
+
+        // sessions with the same seed garble alike
+        let seed = rng.gen::<Block>();
+        let mut s1 = GarbleSession::with_seed(&circ, GarbleMode::Standard, seed).unwrap();
+        let mut s2 = GarbleSession::with_seed(&circ, GarbleMode::Standard, seed).unwrap();
+        assert_eq!(s1.regarble().unwrap().1, s2.regarble().unwrap().1);
+    }
+
+    #[test] // a session garbles exactly as `garble` does
+    fn garble_session_layout() {
+        let mut rng = thread_rng();
+        let q = Modulus::Zq { q: rng.gen_prime() };
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = b.evaluator_input(&q);
+        let c = b.constant(1, &q).unwrap();
+        let z = b.mul(&x, &y).unwrap();
+        let z = b.sub(&z, &c).unwrap();
+        let z = b.cmul(&z, 3).unwrap();
+        let w = b.mod_change(&z, 2).unwrap();
+        let v = b.mod_change(&w, 3).unwrap();
+        let f = b.garbler_input(&Modulus::X4_X_1);
+        let g = b.evaluator_input(&Modulus::X4_X_1);
+        let h = b.add(&f, &g).unwrap();
+        b.outputs(&[z, w, v, h]).unwrap();
+        let small = b.finish();
+        let aes = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+
+        let cases = [
+            (&small, GarbleMode::Standard),
+            (&aes, GarbleMode::Standard),
+            (&aes, GarbleMode::ThreeHalves),
+            (&aes, GarbleMode::PrivacyFree),
+        ];
+        for &(circ, mode) in cases.iter() {
+            let seed = rng.gen::<Block>();
+            let mut session = GarbleSession::with_seed(circ, mode, seed).unwrap();
+            let (en, gc) = session.regarble().unwrap();
+            let rng = AesRng::from_seed(AesRng::from_seed(seed).gen());
+            let (en_, gc_) = garble_with_rng(circ, rng, mode).unwrap();
+            assert_eq!(gc, gc_);
+            let xs = vec![0; circ.num_garbler_inputs()];
+            assert_eq!(en.encode_garbler_inputs(&xs), en_.encode_garbler_inputs(&xs));
+        }
+    }
+
+    #[test] // conditional branches
+    fn branch() {
+        let mut rng = thread_rng();
+        let q = Modulus::Zq { q: 5 };
+        let add = SubCircuit::new(&[q, q], |b, xs| Ok(vec![b.add(&xs[0], &xs[1])?])).unwrap();
+        let mul = SubCircuit::new(&[q, q], |b, xs| Ok(vec![b.mul(&xs[0], &xs[1])?])).unwrap();
+        let neg = SubCircuit::new(&[q, q], |b, xs| Ok(vec![b.cmul(&xs[1], 4)?])).unwrap();
+
+        let mut b = CircuitBuilder::new();
+        let cond = b.evaluator_input(&Modulus::Zq { q: 3 });
+        let xs = b.garbler_inputs(&[q, q]);
+        let zs = b.branch(&cond, &[add.clone(), mul.clone(), neg], &xs).unwrap();
+        b.outputs(&zs).unwrap();
+        assert!(b.branch(&cond, &[add, mul], &xs).is_err());
+        let circ = b.finish();
+
+        let (en, gc) = garble(&circ).unwrap();
+        for _ in 0..16 {
+            let c = rng.gen_u16() % 3;
+            let xs = [rng.gen_u16() % 5, rng.gen_u16() % 5];
+            let should_be = match c {
+                0 => (xs[0] + xs[1]) % 5,
+                1 => xs[0] * xs[1] % 5,
+                _ => (5 - xs[1]) % 5,
+            };
+            assert_eq!(circ.eval_plain(&xs, &[c]).unwrap(), vec![should_be]);
+            let X = en.encode_garbler_inputs(&xs);
+            let Y = en.encode_evaluator_inputs(&[c]);
+            assert_eq!(gc.eval(&circ, &X, &Y).unwrap(), vec![should_be]);
+        }
+    }
+
+    #[test] // garble offline, then evaluate from saved bytes
+    fn offline() {
+        let mut rng = thread_rng();
+        let (q, p) = (Modulus::Zq { q: 7 }, Modulus::X4_X_1);
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(&q);
+        let y = b.evaluator_input(&q);
+        let z = b.mul(&x, &y).unwrap();
+        let u = b.garbler_input(&p);
+        let v = b.evaluator_input(&p);
+        let w = b.mul(&u, &v).unwrap();
+        b.outputs(&[z, w]).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+
+        let (mut circ_bytes, mut gc_bytes, mut en_bytes) = (Vec::new(), Vec::new(), Vec::new());
+        circ.save(&mut circ_bytes).unwrap();
+        gc.save(&mut gc_bytes).unwrap();
+        en.save(&mut en_bytes).unwrap();
+        let circ = Circuit::load(&circ_bytes[..]).unwrap();
+        let gc2 = GarbledCircuit::load(&gc_bytes[..]).unwrap();
+        let en2 = Encoder::load(&en_bytes[..]).unwrap();
+        assert_eq!(gc2, gc);
+
+        for _ in 0..16 {
+            let xs = [rng.gen_u16() % 7, rng.gen_u16() % 16];
+            let ys = [rng.gen_u16() % 7, rng.gen_u16() % 16];
+            let X = en2.encode_garbler_inputs(&xs);
+            let Y = en2.encode_evaluator_inputs(&ys);
+            assert_eq!(X, en.encode_garbler_inputs(&xs));
+            assert_eq!(Y, en.encode_evaluator_inputs(&ys));
+            let outputs = gc2.eval(&circ, &X, &Y).unwrap();
+            assert_eq!(outputs, circ.eval_plain(&xs, &ys).unwrap());
+        }
+
+        assert!(GarbledCircuit::load(&gc_bytes[..gc_bytes.len() - 1]).is_err());
+        assert!(GarbledCircuit::load(&en_bytes[..]).is_err());
+        assert!(Encoder::load(&en_bytes[..en_bytes.len() - 1]).is_err());
+        assert!(Encoder::load(&gc_bytes[..]).is_err());
+    }
+
+    #[test] // privacy-free garbling
+    fn privacy_free() {
+        let mut rng = thread_rng();
+        let (q, r, p) = (Modulus::Zq { q: 7 }, Modulus::Zq { q: 3 }, Modulus::Zq { q: 5 });
+        let mut b = CircuitBuilder::new();
+        let xs = b.garbler_inputs(&[q, r]);
+        let ys = b.evaluator_inputs(&[q, Modulus::Zq { q: 2 }, Modulus::Zq { q: 2 }]);
+        let c = b.constant(3, &q).unwrap();
+        let x = b.add(&xs[0], &c).unwrap();
+        let z1 = b.mul(&x, &ys[0]).unwrap();
+        let z2 = b.mul(&z1, &xs[1]).unwrap();
+        let z3 = b.proj(&z2, &p, Some(vec![0, 4, 3, 2, 1, 0, 4])).unwrap();
+        let z4 = b.and(&ys[1], &ys[2]).unwrap();
+        b.outputs(&[z1, z2, z3, z4]).unwrap();
+        let circ = b.finish();
+
+        let (en, gc) = garble_with_mode(&circ, GarbleMode::PrivacyFree).unwrap();
+        assert_eq!(gc.mode(), GarbleMode::PrivacyFree);
+        // constant, 6 + 2 + 6 + 1 ciphertexts for the gates, 7 + 7 + 5 + 2 for outputs
+        assert_eq!(gc.size(), 1 + 15 + 21);
+        assert!(gc.size() < garble(&circ).unwrap().1.size());
+
+        for _ in 0..16 {
+            let xs = [rng.gen_u16() % 7, rng.gen_u16() % 3];
+            let ys = [rng.gen_u16() % 7, rng.gen_u16() % 2, rng.gen_u16() % 2];
+            let X = en.encode_garbler_inputs(&xs);
+            let Y = en.encode_evaluator_inputs(&ys);
+            // the colors of the labels are the values
+            assert_eq!(X.iter().map(|w| w.color()).collect_vec(), xs);
+            assert_eq!(Y.iter().map(|w| w.color()).collect_vec(), ys);
+            let outputs = gc.eval(&circ, &X, &Y).unwrap();
+            assert_eq!(outputs, circ.eval_plain(&xs, &ys).unwrap());
+        }
+
+        let mut bytes = Vec::new();
+        gc.save(&mut bytes).unwrap();
+        assert_eq!(GarbledCircuit::load(&bytes[..]).unwrap(), gc);
+    }
+
+    #[test] // privacy-free garbling halves the ciphertexts of AES
+    fn privacy_free_aes() {
+        let mut rng = thread_rng();
+        let circ = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        let (en, gc) = garble_with_mode(&circ, GarbleMode::PrivacyFree).unwrap();
+        let (_, gc_standard) = garble(&circ).unwrap();
+        // 6800 ANDs, each half as large, and the same constant and outputs
+        assert_eq!(gc_standard.size() - gc.size(), 6800);
+
+        let xs = (0..128).map(|_| rng.gen_u16() % 2).collect_vec();
+        let ys = (0..128).map(|_| rng.gen_u16() % 2).collect_vec();
+        let X = en.encode_garbler_inputs(&xs);
+        let Y = en.encode_evaluator_inputs(&ys);
+        let outputs = gc.eval(&circ, &X, &Y).unwrap();
+        assert_eq!(outputs, circ.eval_plain(&xs, &ys).unwrap());
+    }
+
+    #[test] // three-halves garbling interleaved with other gates
+    fn three_halves() {
+        let mut rng = thread_rng();
+        let (two, three) = (Modulus::Zq { q: 2 }, Modulus::Zq { q: 3 });
+        let mut b = CircuitBuilder::new();
+        let xs = b.garbler_inputs(&[two, two, three]);
+        let ys = b.evaluator_inputs(&[two, two, three]);
+        let z1 = b.and(&xs[0], &ys[0]).unwrap();
+        let z2 = b.and(&z1, &xs[1]).unwrap();
+        let z3 = b.and(&z2, &ys[1]).unwrap();
+        let z4 = b.mul(&xs[2], &ys[2]).unwrap();
+        let z5 = b.proj(&z4, &two, Some(vec![1, 0, 1])).unwrap();
+        let z6 = b.and(&z5, &z3).unwrap();
+        let z7 = b.and(&z6, &z6).unwrap();
+        let z8 = b.xor(&z7, &xs[0]).unwrap();
+        let z9 = b.and(&z8, &ys[0]).unwrap();
+        b.outputs(&[z1, z2, z3, z4, z5, z6, z7, z9]).unwrap();
+        let circ = b.finish();
+
+        let (en, gc) = garble_with_mode(&circ, GarbleMode::ThreeHalves).unwrap();
+        assert_eq!(gc.mode(), GarbleMode::ThreeHalves);
+        // two runs of 3 ANDs in 81 bytes each, the mod 3 mul and proj, and
+        // 7 * 2 + 3 output blocks
+        assert_eq!(gc.size(), 6 + 4 + 2 + 6 + 17);
+
+        for _ in 0..64 {
+            let xs = [rng.gen_u16() % 2, rng.gen_u16() % 2, rng.gen_u16() % 3];
+            let ys = [rng.gen_u16() % 2, rng.gen_u16() % 2, rng.gen_u16() % 3];
+            let X = en.encode_garbler_inputs(&xs);
+            let Y = en.encode_evaluator_inputs(&ys);
+            let outputs = gc.eval(&circ, &X, &Y).unwrap();
+            assert_eq!(outputs, circ.eval_plain(&xs, &ys).unwrap());
+        }
+
+        let mut bytes = Vec::new();
+        gc.save(&mut bytes).unwrap();
+        assert_eq!(GarbledCircuit::load(&bytes[..]).unwrap(), gc);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test] // parallel garbling lays out ciphertexts like sequential garbling
+    fn garble_parallel() {
+        use crate::{
+            circuit::{random, RandomCircuitConfig},
+            classic::garble_parallel,
+        };
+        let mut rng = thread_rng();
+        let mut circs = (0..8)
+            .map(|_| random(&RandomCircuitConfig::default(), rng.gen::<Block>()).unwrap())
+            .collect_vec();
+        circs.push(Circuit::parse("circuits/AES-non-expanded.txt").unwrap());
+        for circ in circs.iter() {
+            for &mode in [GarbleMode::Standard, GarbleMode::ThreeHalves].iter() {
+                let (en, gc) = garble_parallel(circ, mode).unwrap();
+                assert_eq!(gc.size(), garble_with_mode(circ, mode).unwrap().1.size());
+                let xs = (0..circ.num_garbler_inputs())
+                    .map(|i| rng.gen_u16() % circ.garbler_input_mod(i).size())
+                    .collect_vec();
+                let ys = (0..circ.num_evaluator_inputs())
+                    .map(|i| rng.gen_u16() % circ.evaluator_input_mod(i).size())
+                    .collect_vec();
+                let X = en.encode_garbler_inputs(&xs);
+                let Y = en.encode_evaluator_inputs(&ys);
+                let outputs = gc.eval(circ, &X, &Y).unwrap();
+                assert_eq!(outputs, circ.eval_plain(&xs, &ys).unwrap());
+            }
+        }
+    }
+
+    #[test] // three-halves garbling of AES
+    fn three_halves_aes() {
+        let mut rng = thread_rng();
+        let circ = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        let (en, gc) = garble_with_mode(&circ, GarbleMode::ThreeHalves).unwrap();
+        let (_, gc_standard) = garble(&circ).unwrap();
+        // 6800 ANDs of 27 instead of 32 bytes, up to the padding of runs of ANDs
+        assert!(16 * gc.size() <= 16 * gc_standard.size() - 6800 * 5 + 16 * 6800 / 3);
+        assert!(16 * gc.size() >= 16 * gc_standard.size() - 6800 * 5);
+
+        for _ in 0..4 {
+            let xs = (0..128).map(|_| rng.gen_u16() % 2).collect_vec();
+            let ys = (0..128).map(|_| rng.gen_u16() % 2).collect_vec();
+            let X = en.encode_garbler_inputs(&xs);
+            let Y = en.encode_evaluator_inputs(&ys);
+            let outputs = gc.eval(&circ, &X, &Y).unwrap();
+            assert_eq!(outputs, circ.eval_plain(&xs, &ys).unwrap());
+        }
+    }
 }
 
 #[cfg(test)]
 mod streaming {
+    use super::stack;
     use crate::{
+        circuit::SubCircuit,
         dummy::{Dummy, DummyVal},
+        errors::EvaluatorError,
+        hash::{AesMmo, FixedKeyAes, GateHash, Sha3},
         util::RngExt,
         Evaluator,
+        EvaluatorLimits,
         Fancy,
+        FancyBranch,
         FancyInput,
+        GarbleMode,
         Garbler,
+        HasModulus,
         Wire,
         Modulus,
     };
     use itertools::Itertools;
     use rand::thread_rng;
-    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+    use scuttlebutt::{
+        unix_channel_pair,
+        AbstractChannel,
+        AesRng,
+        Block,
+        Channel,
+        PipelinedChannel,
+        SymChannel,
+        UnixChannel,
+    };
+    use std::os::unix::net::UnixStream;
 
     // helper - checks that Streaming evaluation of a fancy function equals Dummy
     // evaluation of the same function
@@ -322,11 +756,14 @@ mod streaming {
         let should_be = f_du(&mut dummy, &dinps).unwrap();
 
         let (sender, receiver) = unix_channel_pair();
+        // a failure is reproduced by garbling with the same seed
+        let seed = rng.gen_block();
+        let inputs = &inputs;
 
         crossbeam::scope(|s| {
             s.spawn(move |_| {
-                let mut gb = Garbler::new(sender, rng);
-                let (gb_inp, ev_inp) = gb.encode_many_wires(&inputs, &input_mods).unwrap();
+                let mut gb = Garbler::new_seeded(sender, seed);
+                let (gb_inp, ev_inp) = gb.encode_many_wires(inputs, input_mods).unwrap();
                 for w in ev_inp.iter() {
                     gb.send_wire(w).unwrap();
                 }
@@ -340,11 +777,58 @@ mod streaming {
                 .collect_vec();
             let result = f_ev(&mut ev, &ev_inp).unwrap();
 
-            assert_eq!(result, should_be)
+            assert_eq!(result, should_be, "garbler seed {:?}, inputs {:?}", seed, inputs)
         })
         .unwrap();
     }
 
+    #[test]
+    fn seeded_transcripts() {
+        fn garble(seed: Block, buffer: usize) -> [u8; 32] {
+            let channel = Channel::new(std::io::empty(), std::io::sink());
+            let mut gb = Garbler::new_seeded(channel, seed)
+                .with_buffer(buffer)
+                .with_transcript();
+            let (q, b) = (Modulus::Zq { q: 5 }, Modulus::Zq { q: 2 });
+            let (xs, ys) = gb.encode_many_wires(&[3, 1, 1], &[q, q, b]).unwrap();
+            gb.send_wires(&ys).unwrap();
+            let z = gb.mul(&xs[0], &xs[1]).unwrap();
+            let z = gb.proj(&z, &b, Some(vec![0, 1, 1, 0, 1])).unwrap();
+            let z = gb.and(&z, &xs[2]).unwrap();
+            let c = gb.constant(1, &q).unwrap();
+            gb.output(&z).unwrap();
+            gb.output(&c).unwrap();
+            gb.flush().unwrap();
+            gb.transcript_hash().unwrap()
+        }
+
+        let mut rng = thread_rng();
+        let seed = rng.gen_block();
+        let hash = garble(seed, 0);
+        assert_eq!(garble(seed, 0), hash);
+        assert_eq!(garble(seed, 48), hash);
+        assert_ne!(garble(rng.gen_block(), 0), hash);
+
+        let channel = Channel::new(std::io::empty(), std::io::sink());
+        assert!(Garbler::new_seeded(channel, seed).transcript_hash().is_none());
+    }
+
+    #[test]
+    fn encode_with_delta() {
+        let mut rng = thread_rng();
+        let (sender, _) = unix_channel_pair();
+        let mut gb = Garbler::new(sender, AesRng::new());
+        for _ in 0..16 {
+            let q = Modulus::Zq { q: rng.gen_modulus() };
+            let x = rng.gen_u16() % q.size();
+            let seed = rng.gen_block();
+            let (zero, enc) = gb.encode_with_delta(seed, x, &q);
+            assert_eq!(enc, zero.plus(&gb.delta(&q).cmul(x)));
+            let (zero_, _) = gb.encode_with_delta(seed, 0, &q);
+            assert_eq!(zero, zero_);
+        }
+    }
+
     #[test]
     fn addition() {
         fn fancy_addition<F: Fancy>(b: &mut F, xs: &[F::Item]) -> Option<u16> {
@@ -440,6 +924,411 @@ mod streaming {
             );
         }
     }
+
+    fn fancy_mul_proj<F: Fancy>(b: &mut F, xs: &[F::Item]) -> Result<Option<u16>, F::Error> {
+        let q = xs[0].modulus();
+        let tab = (0..q.size()).map(|i| (i + 1) % q.size()).collect_vec();
+        let y = b.mul(&xs[0], &xs[1])?;
+        let z = b.proj(&y, &q, Some(tab))?;
+        b.output(&z)
+    }
+
+    // helper - evaluates `fancy_mul_proj` on an evaluator with the given limits
+    fn limited_test(q: u16, limits: EvaluatorLimits) -> Result<Option<u16>, EvaluatorError> {
+        let mut rng = AesRng::new();
+        let q = Modulus::Zq { q };
+        let inputs = [rng.gen_u16() % q.size(), rng.gen_u16() % q.size()];
+        let (sender, receiver) = unix_channel_pair();
+
+        crossbeam::scope(|s| {
+            let handle = s.spawn(move |_| {
+                let mut gb = Garbler::new(sender, rng);
+                let (gb_inp, ev_inp) = gb.encode_many_wires(&inputs, &[q, q]).unwrap();
+                for w in ev_inp.iter() {
+                    gb.send_wire(w).unwrap();
+                }
+                fancy_mul_proj(&mut gb, &gb_inp).unwrap();
+            });
+
+            let mut ev = Evaluator::with_limits(receiver, limits);
+            let ev_inp = (0..2).map(|_| ev.read_wire(&q).unwrap()).collect_vec();
+            let result = fancy_mul_proj(&mut ev, &ev_inp);
+            // keep the channel open until the garbler is done writing
+            handle.join().unwrap();
+            result
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn output_stream() {
+        let q = Modulus::Zq { q: 17 };
+        let nchunks = 4;
+        let mut rng = AesRng::new();
+        let chunks = (0..nchunks)
+            .map(|_| (0..3).map(|_| rng.gen_u16() % q.size()).collect_vec())
+            .collect_vec();
+        let chunks_ = chunks.clone();
+        let (sender, receiver) = unix_channel_pair();
+
+        crossbeam::scope(|s| {
+            s.spawn(move |_| {
+                let mut channel = sender.clone();
+                let mut gb = Garbler::new(sender, rng);
+                let mut acc = None;
+                for chunk in chunks_.iter() {
+                    let (gb_inp, ev_inp) = gb
+                        .encode_many_wires(chunk, &vec![q; chunk.len()])
+                        .unwrap();
+                    for w in ev_inp.iter() {
+                        gb.send_wire(w).unwrap();
+                    }
+                    let z = match acc {
+                        None => gb.add_many(&gb_inp).unwrap(),
+                        Some(a) => gb.add_many(&[&[a], &gb_inp[..]].concat()).unwrap(),
+                    };
+                    gb.output_stream(std::slice::from_ref(&z)).unwrap();
+                    acc = Some(z);
+                    // wait for the evaluator to acknowledge the intermediate output
+                    assert_eq!(channel.read_u16().unwrap(), 1);
+                }
+            });
+
+            let mut channel = receiver.clone();
+            let mut ev = Evaluator::new(receiver);
+            let mut acc = None;
+            let mut outputs = Vec::new();
+            for chunk in chunks.iter() {
+                let ev_inp = chunk.iter().map(|_| ev.read_wire(&q).unwrap()).collect_vec();
+                let z = match acc {
+                    None => ev.add_many(&ev_inp).unwrap(),
+                    Some(a) => ev.add_many(&[&[a], &ev_inp[..]].concat()).unwrap(),
+                };
+                ev.output_stream(std::slice::from_ref(&z), |i, val| outputs.push((i, val)))
+                    .unwrap();
+                acc = Some(z);
+                channel.write_u16(1).unwrap();
+                channel.flush().unwrap();
+            }
+
+            let mut sum = 0;
+            for (i, chunk) in chunks.iter().enumerate() {
+                sum = chunk.iter().fold(sum, |s, x| (s + x) % q.size());
+                assert_eq!(outputs[i], (i, sum));
+            }
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn mode_mismatch() {
+        let q = Modulus::Zq { q: 2 };
+        let modes = [GarbleMode::Standard, GarbleMode::PrivacyFree, GarbleMode::ThreeHalves];
+        for &gb_mode in modes.iter() {
+            for &ev_mode in modes.iter() {
+                let (sender, receiver) = unix_channel_pair();
+                let handle = std::thread::spawn(move || {
+                    let mut gb = Garbler::with_mode(sender, AesRng::new(), gb_mode);
+                    let (x, y) = gb.encode_wire(1, &q);
+                    gb.send_wire(&y).unwrap();
+                    let z = gb.mul(&x, &x).unwrap();
+                    gb.output(&z).unwrap();
+                    gb.flush().unwrap();
+                });
+                let mut ev = Evaluator::with_mode(receiver, ev_mode);
+                match ev.read_wire(&q) {
+                    Ok(x) if gb_mode == ev_mode => {
+                        let z = ev.mul(&x, &x).unwrap();
+                        assert_eq!(ev.output(&z).unwrap(), Some(1));
+                    }
+                    Err(EvaluatorError::ModeMismatch { expected, got }) => {
+                        assert_ne!(gb_mode, ev_mode);
+                        assert_eq!((expected, got), (ev_mode, Some(gb_mode)));
+                    }
+                    r => panic!("unexpected {:?} for modes {:?}, {:?}", r, gb_mode, ev_mode),
+                }
+                // a failing evaluator hangs up on a garbler still writing
+                let _ = handle.join();
+            }
+        }
+    }
+
+    #[test]
+    fn buffered() {
+        // ciphertexts of all kinds, including three-halves gates which do not
+        // fill whole blocks, sent through the buffer and a pipelined channel
+        let q = Modulus::Zq { q: 2 };
+        let n = 64;
+        let mut rng = AesRng::new();
+        let inputs = (0..n).map(|_| rng.gen_u16() % 2).collect_vec();
+        let f = |b: &mut Dummy, xs: &[DummyVal]| {
+            let mut zs = Vec::new();
+            for pair in xs.chunks(2) {
+                let z = b.and(&pair[0], &pair[1]).unwrap();
+                let z = b.xor(&z, &xs[0]).unwrap();
+                zs.push(b.negate(&z).unwrap());
+            }
+            zs.iter().map(|z| b.output(z).unwrap().unwrap()).collect_vec()
+        };
+        let mut dummy = Dummy::new();
+        let dinps = dummy.encode_many(&inputs, &vec![q; n]).unwrap();
+        let should_be = f(&mut dummy, &dinps);
+
+        for &buffer_size in &[0, 16, 100, 1 << 12] {
+            let (a, b) = UnixStream::pair().unwrap();
+            let inputs = inputs.clone();
+
+            crossbeam::scope(|s| {
+                s.spawn(move |_| {
+                    let channel =
+                        PipelinedChannel::with_capacity(a.try_clone().unwrap(), a, 64, 2);
+                    let mut gb =
+                        Garbler::with_mode(channel, AesRng::new(), GarbleMode::ThreeHalves)
+                            .with_buffer(buffer_size);
+                    let (gb_inp, ev_inp) = gb.encode_many_wires(&inputs, &vec![q; n]).unwrap();
+                    for w in ev_inp.iter() {
+                        gb.send_wire(w).unwrap();
+                    }
+                    for pair in gb_inp.chunks(2) {
+                        let z = gb.and(&pair[0], &pair[1]).unwrap();
+                        let z = gb.xor(&z, &gb_inp[0]).unwrap();
+                        let z = gb.negate(&z).unwrap();
+                        gb.output(&z).unwrap();
+                    }
+                    gb.flush().unwrap();
+                });
+
+                let mut ev = Evaluator::with_mode(SymChannel::new(b), GarbleMode::ThreeHalves);
+                let ev_inp = (0..n).map(|_| ev.read_wire(&q).unwrap()).collect_vec();
+                let mut results = Vec::new();
+                for pair in ev_inp.chunks(2) {
+                    let z = ev.and(&pair[0], &pair[1]).unwrap();
+                    let z = ev.xor(&z, &ev_inp[0]).unwrap();
+                    let z = ev.negate(&z).unwrap();
+                    results.push(ev.output(&z).unwrap().unwrap());
+                }
+                assert_eq!(results, should_be);
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn gate_hash() {
+        fn f<F: Fancy>(b: &mut F, xs: &[F::Item]) -> Vec<Option<u16>> {
+            let y = b.mul(&xs[0], &xs[1]).unwrap();
+            let z = b.mod_change(&y, 3).unwrap();
+            let w = b.and(&xs[2], &xs[3]).unwrap();
+            vec![b.output(&y).unwrap(), b.output(&z).unwrap(), b.output(&w).unwrap()]
+        }
+        fn run<H: GateHash + Copy + Send>(hasher: H, mode: GarbleMode) {
+            let mods = [5, 5, 2, 2].iter().map(|&q| Modulus::Zq { q }).collect_vec();
+            let mut rng = AesRng::new();
+            let inputs = mods.iter().map(|q| rng.gen_u16() % q.size()).collect_vec();
+            let mut dummy = Dummy::new();
+            let dinps = dummy.encode_many(&inputs, &mods).unwrap();
+            let should_be = f(&mut dummy, &dinps);
+
+            let (sender, receiver) = unix_channel_pair();
+            crossbeam::scope(|s| {
+                let inputs = &inputs;
+                let mods = &mods;
+                s.spawn(move |_| {
+                    let mut gb = Garbler::with_mode(sender, rng, mode).with_hash(hasher);
+                    let (gb_inp, ev_inp) = gb.encode_many_wires(inputs, mods).unwrap();
+                    for w in ev_inp.iter() {
+                        gb.send_wire(w).unwrap();
+                    }
+                    f(&mut gb, &gb_inp);
+                });
+                let mut ev = Evaluator::with_mode(receiver, mode).with_hash(hasher);
+                let ev_inp = mods.iter().map(|q| ev.read_wire(q).unwrap()).collect_vec();
+                assert_eq!(f(&mut ev, &ev_inp), should_be);
+            })
+            .unwrap();
+        }
+        for &mode in &[GarbleMode::Standard, GarbleMode::ThreeHalves] {
+            run(FixedKeyAes, mode);
+            run(AesMmo, mode);
+            run(Sha3, mode);
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test] // projections garbled on worker threads stream like sequential ones
+    fn proj_many_parallel() {
+        use crate::primitives::{PhotonGadgets, PhotonParams};
+        fn f<F: Fancy>(b: &mut F, xs: &[F::Item]) -> Vec<Option<u16>> {
+            let q = Modulus::Zq { q: 7 };
+            let tts = (0..xs.len() as u16)
+                .map(|i| (0..16).map(|x| (x * i + 1) % 7).collect())
+                .collect_vec();
+            let ys = b.proj_many(xs, &q, &tts).unwrap();
+            let zs = b.photon_custom(xs, &PhotonParams::P100).unwrap();
+            ys.iter().chain(zs.iter()).map(|y| b.output(y).unwrap()).collect()
+        }
+        let mods = vec![Modulus::X4_X_1; 25];
+        for &mode in &[GarbleMode::Standard, GarbleMode::ThreeHalves] {
+            let mut rng = AesRng::new();
+            let inputs = mods.iter().map(|q| rng.gen_u16() % q.size()).collect_vec();
+            let mut dummy = Dummy::new();
+            let dinps = dummy.encode_many(&inputs, &mods).unwrap();
+            let should_be = f(&mut dummy, &dinps);
+
+            let (sender, receiver) = unix_channel_pair();
+            crossbeam::scope(|s| {
+                let inputs = &inputs;
+                let mods = &mods;
+                s.spawn(move |_| {
+                    let mut gb = Garbler::with_mode(sender, rng, mode);
+                    let (gb_inp, ev_inp) = gb.encode_many_wires(inputs, mods).unwrap();
+                    for w in ev_inp.iter() {
+                        gb.send_wire(w).unwrap();
+                    }
+                    f(&mut gb, &gb_inp);
+                });
+                let mut ev = Evaluator::with_mode(receiver, mode);
+                let ev_inp = mods.iter().map(|q| ev.read_wire(q).unwrap()).collect_vec();
+                assert_eq!(f(&mut ev, &ev_inp), should_be);
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let q = 5;
+        // mul reads 2q-2 ciphertexts, proj q-1, and output q
+        let nbytes = 16 * (2 * q + q - 1 + q - 2) as usize;
+        assert!(limited_test(q, EvaluatorLimits::unlimited()).is_ok());
+        assert!(limited_test(q, EvaluatorLimits::unlimited().max_ciphertext_bytes(nbytes)).is_ok());
+
+        let exceeded = |r: Result<Option<u16>, EvaluatorError>| match r {
+            Err(EvaluatorError::ResourceLimitExceeded { resource, .. }) => resource,
+            r => panic!("expected a resource limit error, got {:?}", r),
+        };
+        let limits = EvaluatorLimits::unlimited();
+        assert_eq!(exceeded(limited_test(q, limits.max_gates(1))), "gates");
+        assert_eq!(exceeded(limited_test(q, limits.max_outputs(0))), "outputs");
+        assert_eq!(
+            exceeded(limited_test(q, limits.max_table_size(q as usize))),
+            "gate table size"
+        );
+        assert_eq!(
+            exceeded(limited_test(q, limits.max_ciphertext_bytes(nbytes - 1))),
+            "ciphertext bytes"
+        );
+    }
+
+    // helper - garbles `branches` on the evaluator's condition `c` and the
+    // garbler's inputs `xs`, returning the evaluator's outputs and the number
+    // of bytes sent for the branch
+    fn stacked_test(mode: GarbleMode, branches: &[SubCircuit], c: u16, xs: &[u16]) -> (Vec<u16>, usize) {
+        let in_mods = branches[0].input_moduli();
+        let cond_mod = Modulus::Zq { q: branches.len() as u16 };
+        let channel = Channel::new(std::io::empty(), Vec::new());
+        let mut gb = Garbler::with_mode(channel.clone(), AesRng::new(), mode);
+        let (gb_inp, ev_inp) = gb.encode_many_wires(xs, &in_mods).unwrap();
+        let (cond, ev_cond) = gb.encode_wire(c, &cond_mod);
+        gb.send_wires(&[&ev_inp[..], &[ev_cond]].concat()).unwrap();
+        gb.flush().unwrap();
+        let before = channel.clone().writer().borrow().len();
+        let zs = gb.branch(&cond, branches, &gb_inp).unwrap();
+        gb.flush().unwrap();
+        let nbytes = channel.clone().writer().borrow().len() - before;
+        for z in zs.iter() {
+            gb.output(z).unwrap();
+        }
+        gb.flush().unwrap();
+
+        let bytes = channel.writer().take();
+        let mut ev = Evaluator::with_mode(Channel::new(std::io::Cursor::new(bytes), std::io::sink()), mode);
+        let ev_inp = ev.read_wires(&[&in_mods[..], &[cond_mod]].concat()).unwrap();
+        let (cond, ev_inp) = ev_inp.split_last().unwrap();
+        let zs = ev.branch(cond, branches, ev_inp).unwrap();
+        let outputs = zs.iter().map(|z| ev.output(z).unwrap().unwrap()).collect_vec();
+        (outputs, nbytes)
+    }
+
+    #[test] // stacked branches cost as much as the longest
+    fn stacked_branch() {
+        let mut rng = thread_rng();
+        let q = Modulus::Zq { q: 5 };
+        let add = SubCircuit::new(&[q, q], |b, xs| Ok(vec![b.add(&xs[0], &xs[1])?])).unwrap();
+        let mul = SubCircuit::new(&[q, q], |b, xs| Ok(vec![b.mul(&xs[0], &xs[1])?])).unwrap();
+        let neg = SubCircuit::new(&[q, q], |b, xs| Ok(vec![b.cmul(&xs[1], 4)?])).unwrap();
+        let arith = [add, mul, neg];
+        let b = Modulus::Zq { q: 2 };
+        let and = SubCircuit::new(&[b; 4], |f, xs| {
+            let y = f.and(&xs[0], &xs[1])?;
+            let z = f.and(&xs[2], &xs[3])?;
+            Ok(vec![f.and(&y, &z)?, f.xor(&y, &z)?])
+        })
+        .unwrap();
+        let or = SubCircuit::new(&[b; 4], |f, xs| {
+            let y = f.or(&xs[0], &xs[1])?;
+            Ok(vec![f.or(&y, &xs[2])?, xs[3]])
+        })
+        .unwrap();
+        let binary = [and, or];
+
+        for &mode in [GarbleMode::Standard, GarbleMode::ThreeHalves].iter() {
+            for c in 0..3 {
+                let xs = [rng.gen_u16() % 5, rng.gen_u16() % 5];
+                let should_be = match c {
+                    0 => (xs[0] + xs[1]) % 5,
+                    1 => xs[0] * xs[1] % 5,
+                    _ => (5 - xs[1]) % 5,
+                };
+                let (zs, _) = stacked_test(mode, &arith, c, &xs);
+                assert_eq!(zs, vec![should_be], "mode {:?}, branch {}", mode, c);
+            }
+            for c in 0..2 {
+                let xs = (0..4).map(|_| rng.gen_u16() % 2).collect_vec();
+                let (y, z) = if c == 0 {
+                    (xs[0] & xs[1], xs[2] & xs[3])
+                } else {
+                    (xs[0] | xs[1], xs[2])
+                };
+                let should_be = if c == 0 { vec![y & z, y ^ z] } else { vec![y | z, xs[3]] };
+                let (zs, nbytes) = stacked_test(mode, &binary, c, &xs);
+                assert_eq!(zs, should_be, "mode {:?}, branch {}", mode, c);
+
+                // the seed table, demultiplexers, longest material and
+                // multiplexers
+                let longest = binary
+                    .iter()
+                    .map(|br| stack::garble(br, rng.gen_block(), mode, &FixedKeyAes).unwrap().material.len())
+                    .max()
+                    .unwrap();
+                let nblocks = 2 * 2 + 2 * 4 * (2 * 2) + longest + 2 * 2 * (2 * 2);
+                assert_eq!(nbytes, 16 * nblocks);
+            }
+        }
+    }
+
+    #[test]
+    fn stacked_branch_errors() {
+        let q = Modulus::Zq { q: 5 };
+        let id = SubCircuit::new(&[q], |_, xs| Ok(vec![xs[0]])).unwrap();
+        let channel = Channel::new(std::io::empty(), std::io::sink());
+        let mut gb = Garbler::with_mode(channel, AesRng::new(), GarbleMode::PrivacyFree);
+        let x = gb.encode_wire(0, &q).0;
+        let cond = gb.encode_wire(0, &Modulus::Zq { q: 2 }).0;
+        assert!(gb.branch(&cond, &[id.clone(), id.clone()], std::slice::from_ref(&x)).is_err());
+
+        let channel = Channel::new(std::io::empty(), std::io::sink());
+        let mut gb = Garbler::new(channel, AesRng::new());
+        let x = gb.encode_wire(0, &q).0;
+        let cond = gb.encode_wire(0, &Modulus::Zq { q: 2 }).0;
+        assert!(gb.branch(&cond, std::slice::from_ref(&id), std::slice::from_ref(&x)).is_err());
+        assert!(gb.branch(&cond, &[id.clone(), id.clone()], &[x.clone(), x.clone()]).is_err());
+        let gf = Modulus::X4_X_1;
+        let y = gb.encode_wire(0, &gf).0;
+        let gf_id = SubCircuit::new(&[gf], |_, xs| Ok(vec![xs[0]])).unwrap();
+        assert!(gb.branch(&cond, &[gf_id.clone(), gf_id], &[y]).is_err());
+        assert!(gb.branch(&cond, &[id.clone(), id], &[x]).is_ok());
+    }
 }
 
 #[cfg(test)]
@@ -620,6 +1509,19 @@ mod GF4_nonstreaming {
         });
     }
 
+    #[test] // half_gate
+    fn half_gate() {
+        garble_test_helper(|q| {
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let y = b.evaluator_input(q);
+            let z = b.mul(&x, &y).unwrap();
+            let z = b.mul(&z, &x).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+
     #[test] // proj_cycle
     fn proj_cycle() {
         garble_test_helper(|q| {
@@ -819,6 +1721,26 @@ mod GF4_streaming {
         }
     }
 
+    #[test]
+    fn multiplication() {
+        fn fancy_multiplication<F: Fancy>(b: &mut F, xs: &[F::Item]) -> Option<u16> {
+            let z = b.mul(&xs[0], &xs[1]).unwrap();
+            let z = b.mul(&z, &xs[0]).unwrap();
+            b.output(&z).unwrap()
+        }
+
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let q = Modulus::GF4_MODULI.choose(&mut rng).unwrap();
+            streaming_test_GF4(
+                fancy_multiplication,
+                fancy_multiplication,
+                fancy_multiplication,
+                &[*q, *q],
+            );
+        }
+    }
+
     #[test]
     fn proj() {
         fn fancy_projection<F: Fancy>(b: &mut F, xs: &[F::Item], q: &Modulus) -> Option<u16> {
@@ -997,6 +1919,19 @@ mod GF8_nonstreaming {
         });
     }
 
+    #[test] // half_gate
+    fn half_gate() {
+        garble_test_helper(|q| {
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let y = b.evaluator_input(q);
+            let z = b.mul(&x, &y).unwrap();
+            let z = b.mul(&z, &x).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+
     #[test] // proj_cycle
     fn proj_cycle() {
         garble_test_helper(|q| {
@@ -1195,6 +2130,26 @@ mod GF8_streaming {
         }
     }
 
+    #[test]
+    fn multiplication() {
+        fn fancy_multiplication<F: Fancy>(b: &mut F, xs: &[F::Item]) -> Option<u16> {
+            let z = b.mul(&xs[0], &xs[1]).unwrap();
+            let z = b.mul(&z, &xs[0]).unwrap();
+            b.output(&z).unwrap()
+        }
+
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let q = Modulus::GF8_MODULI.choose(&mut rng).unwrap();
+            streaming_test_GF8(
+                fancy_multiplication,
+                fancy_multiplication,
+                fancy_multiplication,
+                &[*q, *q],
+            );
+        }
+    }
+
     #[test]
     fn proj() {
         fn fancy_projection<F: Fancy>(b: &mut F, xs: &[F::Item], q: &Modulus) -> Option<u16> {
@@ -1283,4 +2238,144 @@ mod GF8_streaming {
             );
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod GFpk_nonstreaming {
+    use crate::{
+        circuit::{Circuit, CircuitBuilder},
+        classic::garble,
+        fancy::Fancy,
+        util::RngExt,
+        Modulus,
+    };
+    use rand::{thread_rng, seq::SliceRandom};
+
+    // irreducible polynomials over GF(3) and GF(5)
+    const MODULI: [Modulus; 6] = [
+        Modulus::GFpk { p: 3, k: 2, poly: 5 },
+        Modulus::GFpk { p: 3, k: 3, poly: 7 },
+        Modulus::GFpk { p: 3, k: 4, poly: 11 },
+        Modulus::GFpk { p: 5, k: 2, poly: 2 },
+        Modulus::GFpk { p: 5, k: 3, poly: 6 },
+        Modulus::GFpk { p: 7, k: 2, poly: 4 },
+    ];
+
+    // helper
+    fn garble_test_helper<F>(f: F)
+        where
+            F: Fn(&Modulus) -> Circuit,
+    {
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let p = MODULI.choose(&mut rng).unwrap();
+            let c = &f(p);
+            let (en, ev) = garble(c).unwrap();
+            for _ in 0..16 {
+                let mut inps = Vec::new();
+                for _ in 0..c.num_evaluator_inputs() {
+                    inps.push(rng.gen_u16() % p.size());
+                }
+                // Run the garbled circuit evaluator.
+                let xs = &en.encode_evaluator_inputs(&inps);
+                let decoded = &ev.eval(c, &[], xs).unwrap();
+
+                // Run the dummy evaluator.
+                let should_be = c.eval_plain(&[], &inps).unwrap();
+                assert_eq!(decoded[0], should_be[0]);
+            }
+        }
+    }
+
+    #[test] // add
+    fn add_GFpk() {
+        garble_test_helper(|q| {
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let y = b.evaluator_input(q);
+            let z = b.add(&x, &y).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+
+    #[test] // sub
+    fn sub() {
+        garble_test_helper(|q| {
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let y = b.evaluator_input(q);
+            let z = b.sub(&x, &y).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+
+    #[test] // cmul
+    fn cmul() {
+        garble_test_helper(|q| {
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let z = b.cmul(&x, q.size() - 1).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+
+    #[test] // half_gate
+    fn half_gate() {
+        garble_test_helper(|q| {
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let y = b.evaluator_input(q);
+            let z = b.mul(&x, &y).unwrap();
+            let z = b.mul(&z, &x).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+
+    #[test] // proj_cycle
+    fn proj_cycle() {
+        garble_test_helper(|q| {
+            let mut tab = Vec::new();
+            for i in 0..q.size() {
+                tab.push((i + 1) % q.size());
+            }
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let z = b.proj(&x, q, Some(tab)).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+
+    #[test] // proj_rand
+    fn proj_rand() {
+        garble_test_helper(|q| {
+            let mut rng = thread_rng();
+            let out = *MODULI.choose(&mut rng).unwrap();
+            let mut tab = Vec::new();
+            for _ in 0..q.size() {
+                tab.push(rng.gen_u16() % out.size());
+            }
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let z = b.proj(&x, &out, Some(tab)).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+
+    #[test] // constants
+    fn constants() {
+        garble_test_helper(|q| {
+            let mut rng = thread_rng();
+            let mut b = CircuitBuilder::new();
+            let x = b.evaluator_input(q);
+            let y = b.constant(rng.gen_u16() % q.size(), q).unwrap();
+            let z = b.sub(&x, &y).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        });
+    }
+}
@@ -5,33 +5,228 @@
 // See LICENSE for licensing information.
 
 use crate::{
+    circuit::SubCircuit,
     errors::{EvaluatorError, FancyError},
-    fancy::{Fancy, FancyReveal, HasModulus},
+    fancy::{Fancy, FancyBranch, FancyReveal, HasModulus},
+    garble::{stack, three_halves as th, GarbleMode},
+    hash::{FixedKeyAes, GateHash},
+    role::{self, HasRole},
     util::{output_tweak, tweak, tweak2},
     wire::{Wire, Modulus},
 };
-use scuttlebutt::AbstractChannel;
+use scuttlebutt::{AbstractChannel, Block};
+
+/// Resource limits enforced by an `Evaluator` while it consumes a garbled circuit.
+///
+/// Useful when the garbled circuit comes from an untrusted garbler, e.g., when
+/// the evaluator does not know the function being computed. A limit of `None`
+/// means the resource is unbounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvaluatorLimits {
+    /// Maximum number of non-free gates (multiplications and projections).
+    pub max_gates: Option<usize>,
+    /// Maximum total number of ciphertext bytes read for gates and outputs.
+    pub max_ciphertext_bytes: Option<usize>,
+    /// Maximum number of ciphertexts in a single gate table.
+    pub max_table_size: Option<usize>,
+    /// Maximum number of outputs.
+    pub max_outputs: Option<usize>,
+}
+
+impl EvaluatorLimits {
+    /// Limits which never trigger.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of non-free gates.
+    pub fn max_gates(mut self, n: usize) -> Self {
+        self.max_gates = Some(n);
+        self
+    }
+
+    /// Set the maximum total number of ciphertext bytes.
+    pub fn max_ciphertext_bytes(mut self, n: usize) -> Self {
+        self.max_ciphertext_bytes = Some(n);
+        self
+    }
+
+    /// Set the maximum number of ciphertexts in a single gate table.
+    pub fn max_table_size(mut self, n: usize) -> Self {
+        self.max_table_size = Some(n);
+        self
+    }
+
+    /// Set the maximum number of outputs.
+    pub fn max_outputs(mut self, n: usize) -> Self {
+        self.max_outputs = Some(n);
+        self
+    }
+}
 
 /// Streaming evaluator using a callback to receive ciphertexts as needed.
 ///
 /// Evaluates a garbled circuit on the fly, using messages containing ciphertexts and
 /// wires. Parallelizable.
-pub struct Evaluator<C> {
+pub struct Evaluator<C, H = FixedKeyAes> {
     channel: C,
     current_gate: usize,
     current_output: usize,
+    limits: EvaluatorLimits,
+    ciphertext_bytes: usize,
+    mode: GarbleMode,
+    check_mode: bool, // whether the garbler's mode is still to be read before the first block
+    halves: Vec<u8>, // bytes of the last block read for three-halves gates
+    hasher: H,
 }
 
 impl<C: AbstractChannel> Evaluator<C> {
     /// Create a new `Evaluator`.
     pub fn new(channel: C) -> Self {
+        Self::with_limits(channel, EvaluatorLimits::unlimited())
+    }
+
+    /// Create a new `Evaluator` which refuses to process more than `limits` allows.
+    pub fn with_limits(channel: C, limits: EvaluatorLimits) -> Self {
+        Self::with_limits_and_mode(channel, limits, GarbleMode::Standard)
+    }
+
+    /// Create a new `Evaluator` for a circuit garbled in `mode`. Reading from a
+    /// garbler of another mode fails with `EvaluatorError::ModeMismatch`.
+    pub fn with_mode(channel: C, mode: GarbleMode) -> Self {
+        Self::with_limits_and_mode(channel, EvaluatorLimits::unlimited(), mode)
+    }
+
+    /// Create a new `Evaluator` for a circuit garbled in `mode`, which refuses
+    /// to process more than `limits` allows.
+    pub fn with_limits_and_mode(channel: C, limits: EvaluatorLimits, mode: GarbleMode) -> Self {
         Evaluator {
             channel,
             current_gate: 0,
             current_output: 0,
+            limits,
+            ciphertext_bytes: 0,
+            mode,
+            check_mode: true,
+            halves: Vec::new(),
+            hasher: FixedKeyAes,
+        }
+    }
+}
+
+impl<C: AbstractChannel, H: GateHash> Evaluator<C, H> {
+    /// Evaluate gates with `hasher` instead of fixed-key AES. The garbler must
+    /// use the same hash.
+    pub fn with_hash<H2: GateHash>(self, hasher: H2) -> Evaluator<C, H2> {
+        Evaluator {
+            channel: self.channel,
+            current_gate: self.current_gate,
+            current_output: self.current_output,
+            limits: self.limits,
+            ciphertext_bytes: self.ciphertext_bytes,
+            mode: self.mode,
+            check_mode: self.check_mode,
+            halves: self.halves,
+            hasher,
         }
     }
 
+    /// The resource limits of this evaluator.
+    pub fn limits(&self) -> &EvaluatorLimits {
+        &self.limits
+    }
+
+    /// The garbling mode of this evaluator.
+    pub fn mode(&self) -> GarbleMode {
+        self.mode
+    }
+
+    /// Do not read the garbler's mode, for garbled circuits which record it
+    /// themselves.
+    pub(crate) fn without_mode_announcement(mut self) -> Self {
+        self.check_mode = false;
+        self
+    }
+
+    /// Read the mode the garbler announced before its first block, see
+    /// `Garbler::with_mode`, and check that it is ours.
+    fn read_mode(&mut self) -> Result<(), EvaluatorError> {
+        if self.check_mode {
+            self.check_mode = false;
+            let block = u128::from(self.channel.read_block()?);
+            if block != self.mode.to_byte() as u128 {
+                return Err(EvaluatorError::ModeMismatch {
+                    expected: self.mode,
+                    got: GarbleMode::from_byte(block as u8).filter(|_| block < 256),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a gate with `nblocks` ciphertexts fits within the limits, and
+    /// account for it. Must be called before reading the table from the channel.
+    fn check_gate(&mut self, nblocks: usize) -> Result<(), EvaluatorError> {
+        self.check_gate_bytes(16 * nblocks)
+    }
+
+    /// Like `check_gate`, for a gate of `nbytes` bytes. Its table size counts
+    /// the blocks it spans.
+    fn check_gate_bytes(&mut self, nbytes: usize) -> Result<(), EvaluatorError> {
+        if let Some(max) = self.limits.max_gates {
+            if self.current_gate >= max {
+                return Err(EvaluatorError::ResourceLimitExceeded {
+                    resource: "gates",
+                    limit: max,
+                });
+            }
+        }
+        if let Some(max) = self.limits.max_table_size {
+            if (nbytes + 15) / 16 > max {
+                return Err(EvaluatorError::ResourceLimitExceeded {
+                    resource: "gate table size",
+                    limit: max,
+                });
+            }
+        }
+        self.check_ciphertext_bytes(nbytes)
+    }
+
+    /// Check that reading `nblocks` more ciphertexts fits within the limits, and
+    /// account for them.
+    fn check_ciphertexts(&mut self, nblocks: usize) -> Result<(), EvaluatorError> {
+        self.check_ciphertext_bytes(16 * nblocks)
+    }
+
+    /// Check that reading `nbytes` more ciphertext bytes fits within the
+    /// limits, and account for them.
+    fn check_ciphertext_bytes(&mut self, nbytes: usize) -> Result<(), EvaluatorError> {
+        let total = self.ciphertext_bytes + nbytes;
+        if let Some(max) = self.limits.max_ciphertext_bytes {
+            if total > max {
+                return Err(EvaluatorError::ResourceLimitExceeded {
+                    resource: "ciphertext bytes",
+                    limit: max,
+                });
+            }
+        }
+        self.ciphertext_bytes = total;
+        Ok(())
+    }
+
+    /// Check that one more output fits within the limits.
+    fn check_output(&self) -> Result<(), EvaluatorError> {
+        if let Some(max) = self.limits.max_outputs {
+            if self.current_output >= max {
+                return Err(EvaluatorError::ResourceLimitExceeded {
+                    resource: "outputs",
+                    limit: max,
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// The current non-free gate index of the garbling computation.
     fn current_gate(&mut self) -> usize {
         let current = self.current_gate;
@@ -46,14 +241,140 @@ impl<C: AbstractChannel> Evaluator<C> {
         current
     }
 
+    /// Privacy-free multiplication of two `Zq` wires with `q >= qb`, see
+    /// `Garbler::mul_privacy_free`.
+    fn mul_privacy_free(&mut self, A: &Wire, B: &Wire) -> Result<Wire, EvaluatorError> {
+        let modA = A.modulus();
+        let ngates = B.modulus().size() as usize - 1;
+        self.check_gate(ngates)?;
+        let gate = self.read_blocks(ngates)?;
+        let g = tweak2(self.current_gate() as u64, 0);
+        let b = B.color();
+        if b == 0 {
+            Ok(B.hashback_with_color(&self.hasher, g, &modA, 0))
+        } else {
+            let ct = Wire::from_block(gate[b as usize - 1], &modA);
+            Ok(B.hashback_with(&self.hasher, g, &modA).plus_mov(&ct).plus_mov(&A.cmul(b)))
+        }
+    }
+
+    /// Privacy-free projection of a `Zq` wire into `Zq`, see
+    /// `Garbler::proj_privacy_free`.
+    fn proj_privacy_free(&mut self, x: &Wire, modulus: &Modulus, tt: &[u16]) -> Result<Wire, EvaluatorError> {
+        let ngates = x.modulus().size() as usize - 1;
+        self.check_gate(ngates)?;
+        let gate = self.read_blocks(ngates)?;
+        let t = tweak(self.current_gate());
+        match x.color() {
+            0 => Ok(x.hashback_with_color(&self.hasher, t, modulus, tt[0])),
+            a => Ok(Wire::from_block(gate[a as usize - 1] ^ x.hash_with(&self.hasher, t), modulus)),
+        }
+    }
+
+    /// Three-halves evaluation of an AND gate, see `Garbler::and_three_halves`.
+    fn and_three_halves(&mut self, A: &Wire, B: &Wire) -> Result<Wire, EvaluatorError> {
+        self.check_gate_bytes(th::GATE_BYTES)?;
+        let gate = self.read_halves(th::GATE_BYTES)?;
+        let gate_num = self.current_gate() as u64;
+        let (i, j) = (A.color(), B.color());
+
+        let (a, ma) = th::hash_half(A.hash_with(&self.hasher, tweak2(gate_num, 0)));
+        let (b, mb) = th::hash_half(B.hash_with(&self.hasher, tweak2(gate_num, 1)));
+        let (k, _) = th::hash_half(A.plus(B).hash_with(&self.hasher, tweak2(gate_num, 2)));
+
+        let mut G = [0u64; 3];
+        for (g, chunk) in G.iter_mut().zip(gate.chunks(8)) {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(chunk);
+            *g = u64::from_le_bytes(bytes);
+        }
+        let control = match (i, j) {
+            (0, 0) => gate[24],
+            (0, 1) => gate[25],
+            (1, 0) => gate[26],
+            _ => gate[24] ^ gate[25] ^ gate[26],
+        };
+        let (p, q) = th::unpack(control ^ ma ^ mb);
+
+        let mut C = th::combine(a, b, k);
+        let n = th::select(i, j, G);
+        let (pa, qb) = (th::apply(p, th::split(A.as_block())), th::apply(q, th::split(B.as_block())));
+        for h in 0..2 {
+            C[h] ^= n[h] ^ pa[h] ^ qb[h];
+        }
+        Ok(Wire::from_block(th::join(C), &A.modulus()))
+    }
+
+    /// Read the next `n` bytes of three-halves gates, reading whole blocks as
+    /// needed.
+    fn read_halves(&mut self, n: usize) -> Result<Vec<u8>, EvaluatorError> {
+        self.read_mode()?;
+        while self.halves.len() < n {
+            let block = self.channel.read_block()?;
+            self.halves.extend_from_slice(&u128::from(block).to_le_bytes());
+        }
+        Ok(self.halves.drain(..n).collect())
+    }
+
+    /// Skip the padding after buffered three-halves gates, see
+    /// `Garbler::align`.
+    pub fn align(&mut self) {
+        self.halves.clear();
+    }
+
+    /// Read a block after skipping the padding of three-halves gates.
+    fn read_block(&mut self) -> Result<Block, EvaluatorError> {
+        self.read_mode()?;
+        self.align();
+        Ok(self.channel.read_block()?)
+    }
+
+    /// Read `n` blocks after skipping the padding of three-halves gates.
+    fn read_blocks(&mut self, n: usize) -> Result<Vec<Block>, EvaluatorError> {
+        self.read_mode()?;
+        self.align();
+        Ok(self.channel.read_blocks(n)?)
+    }
+
     /// Read a Wire from the reader.
     pub fn read_wire(&mut self, modulus: &Modulus) -> Result<Wire, EvaluatorError> {
-        let block = self.channel.read_block()?;
+        let block = self.read_block()?;
         Ok(Wire::from_block(block, modulus))
     }
+
+    /// Read wires of `moduli` sent together by `Garbler::send_wires`.
+    pub fn read_wires(&mut self, moduli: &[Modulus]) -> Result<Vec<Wire>, EvaluatorError> {
+        let blocks = self.read_blocks(moduli.len())?;
+        Ok(blocks
+            .into_iter()
+            .zip(moduli.iter())
+            .map(|(block, q)| Wire::from_block(block, q))
+            .collect())
+    }
+
+    /// Decode a batch of outputs as soon as the garbler has sent them, calling
+    /// `callback` with the index and value of each output in turn.
+    ///
+    /// Output indices count every output of the computation so far, so results
+    /// of successive calls can be told apart. Pairs with `Garbler::output_stream`.
+    pub fn output_stream<F>(&mut self, xs: &[Wire], mut callback: F) -> Result<(), EvaluatorError>
+    where
+        F: FnMut(usize, u16),
+    {
+        for x in xs.iter() {
+            let i = self.current_output;
+            let val = self.output(x)?.expect("Evaluator always outputs Some(u16)");
+            callback(i, val);
+        }
+        Ok(())
+    }
 }
 
-impl<C: AbstractChannel> FancyReveal for Evaluator<C> {
+impl<C, H> HasRole for Evaluator<C, H> {
+    type Role = role::Evaluator;
+}
+
+impl<C: AbstractChannel, H: GateHash> FancyReveal for Evaluator<C, H> {
     fn reveal(&mut self, x: &Wire) -> Result<u16, EvaluatorError> {
         let val = self.output(x)?.expect("Evaluator always outputs Some(u16)");
         self.channel.write_u16(val)?;
@@ -62,7 +383,79 @@ impl<C: AbstractChannel> FancyReveal for Evaluator<C> {
     }
 }
 
-impl<C: AbstractChannel> Fancy for Evaluator<C> {
+impl<C: AbstractChannel, H: GateHash> FancyBranch for Evaluator<C, H> {
+    /// Evaluate the stacked branches, see `garble::stack`. Regarbling and
+    /// evaluating the branches is not counted against the limits, only the
+    /// tables and the stacked material read.
+    fn branch(
+        &mut self,
+        cond: &Wire,
+        branches: &[SubCircuit],
+        inputs: &[Wire],
+    ) -> Result<Vec<Wire>, EvaluatorError> {
+        let out_mods = stack::check(self.mode, cond, branches, inputs)?;
+        let n = branches.len();
+
+        self.check_gate(n * n)?;
+        let table = self.read_blocks(n * n)?;
+        let g = self.current_gate() as u64;
+        let row = &table[cond.color() as usize * n..][..n];
+        let seeds = row
+            .iter()
+            .enumerate()
+            .map(|(j, ct)| *ct ^ cond.hash_with(&self.hasher, tweak2(g, j as u64)))
+            .collect::<Vec<_>>();
+
+        let mut demuxed = Vec::with_capacity(n);
+        for _ in 0..n {
+            let mut xs = Vec::with_capacity(inputs.len());
+            for x in inputs.iter() {
+                let q = x.modulus();
+                let nrows = n * q.size() as usize;
+                self.check_gate(nrows)?;
+                let table = self.read_blocks(nrows)?;
+                let g = self.current_gate() as u64;
+                let ct = table[(cond.color() * q.size() + x.color()) as usize];
+                let key = cond.hash_with(&self.hasher, tweak2(g, 0)) ^ x.hash_with(&self.hasher, tweak2(g, 1));
+                xs.push(Wire::from_block(ct ^ key, &q));
+            }
+            demuxed.push(xs);
+        }
+
+        let regarbled = branches
+            .iter()
+            .zip(seeds.iter())
+            .map(|(b, s)| stack::garble(b, *s, self.mode, &self.hasher).map(|gb| gb.material))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(stack::evaluator_error)?;
+        let len = regarbled.iter().map(|m| m.len()).max().unwrap_or(0);
+        self.check_ciphertexts(len)?;
+        let mut rest = self.read_blocks(len)?;
+        for m in regarbled.iter() {
+            stack::xor_into(&mut rest, m);
+        }
+
+        let mut ys = out_mods.iter().map(Wire::zero).collect::<Vec<_>>();
+        for ((b, m), xs) in branches.iter().zip(regarbled.iter()).zip(demuxed.iter()) {
+            let mut material = rest.clone();
+            stack::xor_into(&mut material, m);
+            let zs = stack::eval(b, &material, self.mode, &self.hasher, xs)?;
+            for (y, Z) in ys.iter_mut().zip(zs.iter()) {
+                let q = Z.modulus();
+                let nrows = n * q.size() as usize;
+                self.check_gate(nrows)?;
+                let table = self.read_blocks(nrows)?;
+                let g = self.current_gate() as u64;
+                let ct = table[(cond.color() * q.size() + Z.color()) as usize];
+                let key = cond.hash_with(&self.hasher, tweak2(g, 0)) ^ Z.hash_with(&self.hasher, tweak2(g, 1));
+                y.plus_eq(&Wire::from_block(ct ^ key, &q));
+            }
+        }
+        Ok(ys)
+    }
+}
+
+impl<C: AbstractChannel, H: GateHash> Fancy for Evaluator<C, H> {
     type Item = Wire;
     type Error = EvaluatorError;
 
@@ -72,14 +465,20 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
 
     fn add(&mut self, x: &Wire, y: &Wire) -> Result<Wire, EvaluatorError> {
         if x.modulus() != y.modulus() {
-            return Err(EvaluatorError::FancyError(FancyError::UnequalModuli));
+            return Err(EvaluatorError::FancyError(FancyError::UnequalModuli {
+                x: x.modulus(),
+                y: y.modulus(),
+            }));
         }
         Ok(x.plus(y))
     }
 
     fn sub(&mut self, x: &Wire, y: &Wire) -> Result<Wire, EvaluatorError> {
         if x.modulus() != y.modulus() {
-            return Err(EvaluatorError::FancyError(FancyError::UnequalModuli));
+            return Err(EvaluatorError::FancyError(FancyError::UnequalModuli {
+                x: x.modulus(),
+                y: y.modulus(),
+            }));
         }
         Ok(x.minus(y))
     }
@@ -88,19 +487,34 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
         Ok(x.cmul(c))
     }
 
+    fn constant_big(&mut self, _: u64, modulus: &Modulus) -> Result<Wire, EvaluatorError> {
+        self.read_wire(modulus)
+    }
+
+    fn cmul_big(&mut self, x: &Wire, c: u64) -> Result<Wire, EvaluatorError> {
+        Ok(x.cmul_big(c))
+    }
+
     fn mul(&mut self, A: &Wire, B: &Wire) -> Result<Wire, EvaluatorError> {
         match (A.modulus(), B.modulus()) {
             (Modulus::Zq { q }, Modulus::Zq { q: qb }) => {
                 if q < qb {
                     return self.mul(B, A);
                 }
+                if self.mode == GarbleMode::PrivacyFree {
+                    return self.mul_privacy_free(A, B);
+                }
+                if self.mode == GarbleMode::ThreeHalves && q == 2 {
+                    return self.and_three_halves(A, B);
+                }
                 let qM = A.modulus();
                 let unequal = q != qb;
                 let ngates = q as usize + qb as usize - 2 + unequal as usize;
+                self.check_gate(ngates)?;
                 let mut gate = Vec::with_capacity(ngates);
                 {
                     for _ in 0..ngates {
-                        let block = self.channel.read_block()?;
+                        let block = self.read_block()?;
                         gate.push(block);
                     }
                 }
@@ -109,25 +523,25 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
         
                 // garbler's half gate
                 let L = if A.color() == 0 {
-                    A.hashback(g, &qM)
+                    A.hashback_with(&self.hasher, g, &qM)
                 } else {
                     let ct_left = gate[A.color() as usize - 1];
-                    Wire::from_block(ct_left ^ A.hash(g), &qM)
+                    Wire::from_block(ct_left ^ A.hash_with(&self.hasher, g), &qM)
                 };
         
                 // evaluator's half gate
                 let R = if B.color() == 0 {
-                    B.hashback(g, &qM)
+                    B.hashback_with(&self.hasher, g, &qM)
                 } else {
                     let ct_right = gate[(q + B.color()) as usize - 2];
-                    Wire::from_block(ct_right ^ B.hash(g), &qM)
+                    Wire::from_block(ct_right ^ B.hash_with(&self.hasher, g), &qM)
                 };
         
                 // hack for unequal mods
                 let new_b_color = if unequal {
                     let minitable = *gate.last().unwrap();
                     let ct = u128::from(minitable) >> (B.color() * 16);
-                    let pt = u128::from(B.hash(tweak2(gate_num as u64, 1))) ^ ct;
+                    let pt = u128::from(B.hash_with(&self.hasher, tweak2(gate_num as u64, 1))) ^ ct;
                     pt as u16
                 } else {
                     B.color()
@@ -136,6 +550,32 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
                 let res = L.plus_mov(&R.plus_mov(&A.cmul(new_b_color)));
                 Ok(res)
             }
+            (q, qb) if q.is_field() && q == qb && self.mode != GarbleMode::PrivacyFree => {
+                let n = q.order() as usize;
+                let ngates = 2 * (n - 1);
+                self.check_gate(ngates)?;
+                let mut gate = Vec::with_capacity(ngates);
+                for _ in 0..ngates {
+                    gate.push(self.read_block()?);
+                }
+                let g = tweak2(self.current_gate() as u64, 0);
+
+                // garbler's half gate
+                let L = if A.color() == 0 {
+                    A.hashback_with(&self.hasher, g, &q)
+                } else {
+                    Wire::from_block(gate[A.color() as usize - 1] ^ A.hash_with(&self.hasher, g), &q)
+                };
+
+                // evaluator's half gate
+                let R = if B.color() == 0 {
+                    B.hashback_with(&self.hasher, g, &q)
+                } else {
+                    Wire::from_block(gate[n - 1 + B.color() as usize - 1] ^ B.hash_with(&self.hasher, g), &q)
+                };
+
+                Ok(L.plus_mov(&R.plus_mov(&A.cmul(B.color()))))
+            }
             _ => {
                 Err(EvaluatorError::FancyError(FancyError::InvalidArg(String::from("Not supported for combining a field and ring element."))))
             }
@@ -143,33 +583,59 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
         
     }
 
-    fn proj(&mut self, x: &Wire, modulus: &Modulus, _: Option<Vec<u16>>) -> Result<Wire, EvaluatorError> {
-        let q = x.modulus().size();
-        let ngates = (q - 1) as usize;
+    fn proj(&mut self, x: &Wire, modulus: &Modulus, tt: Option<Vec<u16>>) -> Result<Wire, EvaluatorError> {
+        if let Some(q) = [x.modulus(), *modulus].iter().find(|q| matches!(q, Modulus::ZqBig { .. })) {
+            return Err(EvaluatorError::FancyError(FancyError::InvalidArg(format!(
+                "Projection of {} is not supported",
+                q
+            ))));
+        }
+        if self.mode == GarbleMode::PrivacyFree {
+            // the evaluator needs the truth table to label the output of the
+            // row which is not sent
+            let tt = tt.ok_or(EvaluatorError::FancyError(FancyError::NoTruthTable))?;
+            return match (x.modulus(), *modulus) {
+                (Modulus::Zq { .. }, Modulus::Zq { .. }) => self.proj_privacy_free(x, modulus, &tt),
+                (q, r) => Err(EvaluatorError::FancyError(FancyError::InvalidArg(format!(
+                    "Privacy-free projection of {} into {} is not supported",
+                    q, r
+                )))),
+            };
+        }
+        let ngates = x.modulus().order() as usize - 1;
+        self.check_gate(ngates)?;
         let mut gate = Vec::with_capacity(ngates);
         for _ in 0..ngates {
-            let block = self.channel.read_block()?;
+            let block = self.read_block()?;
             gate.push(block);
         }
         let t = tweak(self.current_gate());
         if x.color() == 0 {
-            Ok(x.hashback(t, modulus))
+            Ok(x.hashback_with(&self.hasher, t, modulus))
         } else {
             let ct = gate[x.color() as usize - 1];
-            Ok(Wire::from_block(ct ^ x.hash(t), modulus))
+            Ok(Wire::from_block(ct ^ x.hash_with(&self.hasher, t), modulus))
         }
     }
 
     fn output(&mut self, x: &Wire) -> Result<Option<u16>, EvaluatorError> {
         let modulus = x.modulus();
+        if let Modulus::ZqBig { .. } = modulus {
+            return Err(EvaluatorError::FancyError(FancyError::InvalidArg(format!(
+                "Outputs of {} need output_big",
+                modulus
+            ))));
+        }
+        self.check_output()?;
+        self.check_ciphertexts(modulus.order() as usize)?;
         let i = self.current_output();
         let mut decoded = None;
 
         // Receive the output ciphertext from the garbler
-        let ct = self.channel.read_blocks(modulus.size() as usize)?;
+        let ct = self.read_blocks(modulus.order() as usize)?;
         // Attempt to brute force x using the output ciphertext
-        for k in 0..modulus.size() {
-            let hashed_wire = x.hash(output_tweak(i, k));
+        for k in (0..modulus.order()).map(|k| k as u16) {
+            let hashed_wire = x.hash_with(&self.hasher, output_tweak(i, k));
             if hashed_wire == ct[k as usize] {
                 decoded = Some(k);
                 break;
@@ -182,4 +648,21 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
             Err(EvaluatorError::DecodingFailed)
         }
     }
+
+    fn output_big(&mut self, x: &Wire) -> Result<Option<u64>, EvaluatorError> {
+        let q = match x.modulus() {
+            Modulus::ZqBig { q } => q,
+            _ => return Ok(self.output(x)?.map(u64::from)),
+        };
+        self.check_output()?;
+        self.check_ciphertexts(1)?;
+        self.current_output();
+        // The garbler sends the color of the zero wire-label, see
+        // `Garbler::output_big`.
+        let zero_color = u128::from(self.read_block()?) as u64;
+        if zero_color >= q {
+            return Err(EvaluatorError::DecodingFailed);
+        }
+        Ok(Some((x.color_big() + q - zero_color) % q))
+    }
 }
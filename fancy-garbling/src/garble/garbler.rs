@@ -5,35 +5,164 @@
 // See LICENSE for licensing information.
 
 use crate::{
+    circuit::SubCircuit,
     errors::{FancyError, GarblerError},
-    fancy::{BinaryBundle, CrtBundle, Fancy, FancyReveal, HasModulus},
-    util::{output_tweak, tweak, tweak2, RngExt},
+    fancy::{BinaryBundle, CrtBundle, Fancy, FancyBranch, FancyReveal, HasModulus},
+    garble::{stack, three_halves as th, GarbleMode},
+    hash::{FixedKeyAes, GateHash},
+    role::{self, HasRole},
+    util::{self, output_tweak, tweak, tweak2, RngExt},
     wire::{Wire, Modulus},
 };
-use rand::{CryptoRng, RngCore};
-use scuttlebutt::{AbstractChannel, Block};
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 
 /// Streams garbled circuit ciphertexts through a callback.
-pub struct Garbler<C, RNG> {
+pub struct Garbler<C, RNG, H = FixedKeyAes> {
     channel: C,
     deltas: HashMap<Modulus, Wire>, // map from modulus to associated delta wire-label.
     current_output: usize,
     current_gate: usize,
     rng: RNG,
+    mode: GarbleMode,
+    announce_mode: bool, // whether the mode is still to be written before the first block
+    halves: Vec<u8>, // three-halves gate bytes not yet written as a whole block
+    buffer: Vec<u8>, // ciphertexts not yet written to the channel
+    buffer_size: usize, // write the buffer once it holds this many bytes
+    hasher: H,
+    transcript: Option<Sha256>, // hash of the bytes written to the channel
 }
 
 impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
     /// Create a new garbler.
     pub fn new(channel: C, rng: RNG) -> Self {
+        Self::with_mode(channel, rng, GarbleMode::Standard)
+    }
+
+    /// Create a new garbler which garbles in `mode`. The evaluator must use the
+    /// same mode: the garbler announces it in a block before everything else it
+    /// writes, and an `Evaluator` of another mode fails with
+    /// `EvaluatorError::ModeMismatch` on reading it.
+    pub fn with_mode(channel: C, rng: RNG, mode: GarbleMode) -> Self {
         Garbler {
             channel,
             deltas: HashMap::new(),
             current_gate: 0,
             current_output: 0,
             rng,
+            mode,
+            announce_mode: true,
+            halves: Vec::new(),
+            buffer: Vec::new(),
+            buffer_size: 0,
+            hasher: FixedKeyAes,
+            transcript: None,
         }
     }
+}
+
+impl<C: AbstractChannel> Garbler<C, AesRng> {
+    /// Create a new garbler drawing its deltas and labels from an `AesRng`
+    /// seeded with `seed`.
+    ///
+    /// Two such garblers with the same seed, running the same computation,
+    /// write the same bytes, so a failing run can be reproduced exactly and
+    /// its transcript compared against a stored one, see `with_transcript`.
+    /// The seed must stay secret from the evaluator, and a seed must never be
+    /// used for more than one garbling.
+    pub fn new_seeded(channel: C, seed: Block) -> Self {
+        Self::new(channel, AesRng::from_seed(seed))
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + RngCore, H: GateHash> Garbler<C, RNG, H> {
+    /// Garble gates with `hasher` instead of fixed-key AES. The evaluator must
+    /// use the same hash.
+    pub fn with_hash<H2: GateHash>(self, hasher: H2) -> Garbler<C, RNG, H2> {
+        Garbler {
+            channel: self.channel,
+            deltas: self.deltas,
+            current_gate: self.current_gate,
+            current_output: self.current_output,
+            rng: self.rng,
+            mode: self.mode,
+            announce_mode: self.announce_mode,
+            halves: self.halves,
+            buffer: self.buffer,
+            buffer_size: self.buffer_size,
+            hasher,
+            transcript: self.transcript,
+        }
+    }
+
+    /// Keep a running SHA-256 hash of every byte written to the channel, which
+    /// `transcript_hash` returns. Bytes are hashed as they reach the channel,
+    /// so buffering does not change the hash.
+    pub fn with_transcript(mut self) -> Self {
+        self.transcript = Some(Sha256::new());
+        self
+    }
+
+    /// The hash of the bytes written to the channel so far, not counting
+    /// those still buffered, if the garbler was created `with_transcript`.
+    /// Call `flush` first to include everything garbled so far.
+    pub fn transcript_hash(&self) -> Option<[u8; 32]> {
+        self.transcript.as_ref().map(|hash| {
+            let mut h = [0u8; 32];
+            h.copy_from_slice(&hash.clone().result());
+            h
+        })
+    }
+
+    /// Collect ciphertexts into a buffer and write them to the channel in one
+    /// piece once it holds `nbytes` bytes, instead of writing each ciphertext
+    /// as it is produced. `0` turns buffering off, which is the default.
+    ///
+    /// Buffered ciphertexts are written by `flush`, which `reveal` and
+    /// `output_stream` call. Call it after garbling, and before writing to the
+    /// channel directly. Together with a `scuttlebutt::PipelinedChannel` the
+    /// buffer is sent by a background thread while garbling continues.
+    pub fn with_buffer(mut self, nbytes: usize) -> Self {
+        self.buffer_size = nbytes;
+        self.buffer = Vec::with_capacity(nbytes);
+        self
+    }
+
+    /// The garbling mode of this garbler.
+    pub fn mode(&self) -> GarbleMode {
+        self.mode
+    }
+
+    /// Do not announce the mode, for garbled circuits which record it
+    /// themselves.
+    pub(crate) fn without_mode_announcement(mut self) -> Self {
+        self.announce_mode = false;
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Use `deltas` instead of sampling new ones, so that gates garbled by
+    /// several garblers fit together.
+    pub(crate) fn with_deltas(mut self, deltas: HashMap<Modulus, Wire>) -> Self {
+        self.deltas = deltas;
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Garble the next gate as non-free gate number `gate`, drawing its
+    /// randomness from `rng`. Used to garble gates out of order.
+    pub(crate) fn seek(&mut self, gate: usize, rng: RNG) {
+        self.current_gate = gate;
+        self.rng = rng;
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Take the bytes of three-halves gates which do not fill a block yet.
+    pub(crate) fn take_halves(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.halves)
+    }
 
     #[cfg(feature = "serde1")]
     /// Load pre-chosen deltas from a file
@@ -54,6 +183,12 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
 
     /// Create a delta if it has not been created yet for this modulus, otherwise just
     /// return the existing one.
+    ///
+    /// The delta is the global offset between the labels of consecutive values
+    /// of every wire with this modulus, so it is exposed for protocols which
+    /// need correlated labels, e.g. for committed inputs. Anyone holding the
+    /// delta together with any one label of a wire can compute every label of
+    /// that wire, so it must never reach the evaluator.
     pub fn delta(&mut self, modulus: &Modulus) -> Wire {
         if let Some(delta) = self.deltas.get(modulus) {
             return delta.clone();
@@ -63,6 +198,299 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
         w
     }
 
+    /// Turn a random label into a zero label. In privacy-free mode zero labels
+    /// have color `0`, so that the color of a label is the value it encodes.
+    fn zero_label(&mut self, w: Wire) -> Wire {
+        if self.mode != GarbleMode::PrivacyFree {
+            return w;
+        }
+        let delta = self.delta(&w.modulus());
+        match w.modulus() {
+            Modulus::ZqBig { .. } => w.minus(&delta.cmul_big(w.color_big())),
+            _ => w.minus(&delta.cmul(w.color())),
+        }
+    }
+
+    /// Privacy-free multiplication of two `Zq` wires with `q >= qb`.
+    ///
+    /// The evaluator knows the value `b` of `B`, so only its half-gate is
+    /// needed: for each `b > 0` the garbler sends `C - H(B + bDb) - bA`, which
+    /// the evaluator turns into `C + abD` by adding its hash of `B` and `bA`.
+    /// The output zero label `C` is the hash of the zero label of `B`, so the
+    /// gate costs `qb - 1` ciphertexts and needs no extra ciphertext for
+    /// unequal moduli.
+    fn mul_privacy_free(&mut self, A: &Wire, B: &Wire) -> Result<Wire, GarblerError> {
+        let modA = A.modulus();
+        let qb = B.modulus().size();
+        let g = tweak2(self.current_gate() as u64, 0);
+        let Db = self.delta(&B.modulus());
+
+        let C = B.hashback_with_color(&self.hasher, g, &modA, 0);
+        let mut B_ = B.clone();
+        let mut A_ = A.clone();
+        for _ in 1..qb {
+            B_.plus_eq(&Db);
+            // A_ = bA
+            let ct = C.minus(&B_.hashback_with(&self.hasher, g, &modA)).minus_mov(&A_);
+            self.write_block(&ct.as_block())?;
+            A_.plus_eq(A);
+        }
+        Ok(C)
+    }
+
+    /// Privacy-free projection of a `Zq` wire into `Zq`.
+    ///
+    /// Rows are indexed by the value of `A`, which is its color, and the row
+    /// for `0` is the hash of the zero label of `A`. Unlike the standard
+    /// projection this does not save any ciphertexts, but the output zero label
+    /// has color `0`.
+    fn proj_privacy_free(&mut self, A: &Wire, mod_out: &Modulus, tt: &[u16]) -> Result<Wire, GarblerError> {
+        let mod_in = A.modulus();
+        let g = tweak(self.current_gate());
+        let Din = self.delta(&mod_in);
+        let Dout = self.delta(mod_out);
+
+        let C = A
+            .hashback_with_color(&self.hasher, g, mod_out, tt[0])
+            .minus_mov(&Dout.cmul(tt[0]));
+        let mut A_ = A.clone();
+        for x in 1..mod_in.size() {
+            A_.plus_eq(&Din);
+            let ct = A_.hash_with(&self.hasher, g) ^ C.plus(&Dout.cmul(tt[x as usize])).as_block();
+            self.write_block(&ct)?;
+        }
+        Ok(C)
+    }
+
+    /// `proj` with a borrowed truth table, so that circuits garbled over and
+    /// over need not copy their tables, see `classic::GarbleSession`.
+    pub(crate) fn proj_table(
+        &mut self,
+        A: &Wire,
+        mod_out: &Modulus,
+        tt: &[u16],
+    ) -> Result<Wire, GarblerError> {
+        let mod_in = A.modulus();
+        if let Some(q) = [mod_in, *mod_out].iter().find(|q| matches!(q, Modulus::ZqBig { .. })) {
+            return Err(GarblerError::FancyError(FancyError::InvalidArg(format!(
+                "Projection of {} is not supported",
+                q
+            ))));
+        }
+        if self.mode == GarbleMode::PrivacyFree {
+            return match (mod_in, mod_out) {
+                (Modulus::Zq { .. }, Modulus::Zq { .. }) => self.proj_privacy_free(A, mod_out, tt),
+                _ => Err(GarblerError::FancyError(FancyError::InvalidArg(format!(
+                    "Privacy-free projection of {} into {} is not supported",
+                    mod_in, mod_out
+                )))),
+            };
+        }
+        let g = tweak(self.current_gate());
+        let Din = self.delta(&mod_in);
+        let Dout = self.delta(mod_out);
+        let (C, gate) = garble_proj(&self.hasher, g, A, &Din, &Dout, mod_out, tt);
+        for block in gate.iter() {
+            self.write_block(block)?;
+        }
+        Ok(C)
+    }
+
+    /// Half-gate multiplication of two wires over the same field.
+    ///
+    /// This is the `Zq` construction with field arithmetic, where the products
+    /// of colors are taken in the field by multiplying deltas with them. Both
+    /// wires share a delta, so no extra ciphertext is needed and the gate costs
+    /// `2 * (q - 1)` ciphertexts for a field of `q` elements.
+    fn mul_field(&mut self, A: &Wire, B: &Wire) -> Result<Wire, GarblerError> {
+        let q = A.modulus();
+        let n = q.order() as usize;
+        let gate_num = self.current_gate();
+        let D = self.delta(&q);
+        let mut gate = vec![Block::default(); 2 * (n - 1)];
+
+        let D_cmul = (0..n).map(|c| D.cmul(c as u16)).collect::<Vec<_>>();
+        let A_cmul = (0..n).map(|c| A.cmul(c as u16)).collect::<Vec<_>>();
+        let r = B.color(); // secret value known only to the garbler (ev knows r+b)
+        let g = tweak2(gate_num as u64, 0);
+
+        // X = H(A+aD) + arD such that a + A.color == 0
+        let alpha_D = D_cmul[A.color() as usize].negate();
+        let X = A
+            .plus(&alpha_D)
+            .hashback_with(&self.hasher, g, &q)
+            .plus_mov(&alpha_D.cmul(r));
+
+        // Y = H(B + bD) + (b + r)A such that b + B.color == 0, i.e. b == -r
+        let Y = B.minus(&D_cmul[r as usize]).hashback_with(&self.hasher, g, &q);
+        let Y_r = Y.minus(&A_cmul[r as usize]);
+
+        for a in 0..n {
+            // garbler's half-gate: outputs X-arD
+            let A_ = A.plus(&D_cmul[a]);
+            if A_.color() != 0 {
+                gate[A_.color() as usize - 1] = A_.hash_with(&self.hasher, g) ^ X.minus(&D_cmul[a].cmul(r)).as_block();
+            }
+        }
+
+        for b in 0..n {
+            // evaluator's half-gate: outputs Y-(b+r)A
+            let B_ = B.plus(&D_cmul[b]);
+            if B_.color() != 0 {
+                gate[n - 1 + B_.color() as usize - 1] = B_.hash_with(&self.hasher, g) ^ Y_r.minus(&A_cmul[b]).as_block();
+            }
+        }
+
+        for block in gate.iter() {
+            self.write_block(block)?;
+        }
+        Ok(X.plus_mov(&Y))
+    }
+
+    /// Three-halves garbling of an AND gate, see `garble::three_halves`.
+    ///
+    /// The rows `X_A`, `X_B` and `X_D` added to the ciphertexts are random
+    /// subject to `N_01 X_A + N_10 X_B = I`, which makes the `P_ij` and `Q_ij`
+    /// of every color pair uniform, whatever the input values. The gate costs
+    /// three half ciphertexts and three control bytes.
+    fn and_three_halves(&mut self, A: &Wire, B: &Wire) -> Result<Wire, GarblerError> {
+        let gate_num = self.current_gate() as u64;
+        let D = self.delta(&A.modulus());
+        let (alpha, beta) = (A.color(), B.color());
+        let (ta, tb, tk) = (tweak2(gate_num, 0), tweak2(gate_num, 1), tweak2(gate_num, 2));
+
+        // labels of color 0
+        let A_ = A.plus(&D.cmul(alpha));
+        let B_ = B.plus(&D.cmul(beta));
+        let AB = A_.plus(&B_);
+        let (a0, ma0) = th::hash_half(A_.hash_with(&self.hasher, ta));
+        let (a1, ma1) = th::hash_half(A_.plus(&D).hash_with(&self.hasher, ta));
+        let (b0, mb0) = th::hash_half(B_.hash_with(&self.hasher, tb));
+        let (b1, mb1) = th::hash_half(B_.plus(&D).hash_with(&self.hasher, tb));
+        let (k0, _) = th::hash_half(AB.hash_with(&self.hasher, tk));
+        let (k1, _) = th::hash_half(AB.plus(&D).hash_with(&self.hasher, tk));
+
+        let r = self.rng.gen_u16();
+        let bits = |k: u16| (r >> (2 * k) & 3) as u8;
+        let (xa1, xa3, xb2, xb3) = (bits(3), bits(4), bits(5), bits(6));
+        let xa = [xa1, th::IDENTITY[1] ^ xa3 ^ xb3, xa3];
+        let xb = [th::IDENTITY[0] ^ xa3 ^ xb3, xb2, xb3];
+        let xd = [bits(0), bits(1), bits(2)];
+
+        let (ha, hb, hd) = (th::split(A_.as_block()), th::split(B_.as_block()), th::split(D.as_block()));
+        let hashes = [a0 ^ a1, b0 ^ b1, k0 ^ k1];
+        let mut gate = Vec::with_capacity(th::GATE_BYTES);
+        for k in 0..3 {
+            let g = hashes[k] ^ th::row(xa[k], ha) ^ th::row(xb[k], hb) ^ th::row(xd[k], hd);
+            gate.extend_from_slice(&g.to_le_bytes());
+        }
+
+        let scaled = |c: u16| if c == 1 { th::IDENTITY } else { [0, 0] };
+        let ya = th::add(th::select(1, 0, [xd[0] ^ xa[0], xd[1] ^ xa[1], xd[2] ^ xa[2]]), scaled(beta));
+        let yb = th::add(th::select(0, 1, [xd[0] ^ xb[0], xd[1] ^ xb[1], xd[2] ^ xb[2]]), scaled(alpha));
+        let (ma, mb) = ([ma0, ma1], [mb0, mb1]);
+        for &(i, j) in [(0, 0), (0, 1), (1, 0)].iter() {
+            let p = th::add(ya, th::select(i, j, xa));
+            let q = th::add(yb, th::select(i, j, xb));
+            gate.push(th::pack(p, q) ^ ma[i as usize] ^ mb[j as usize]);
+        }
+        self.write_halves(&gate)?;
+
+        let mut C = th::combine(a0, b0, k0);
+        for (c, (x, y)) in C.iter_mut().zip(th::apply(ya, ha).iter().zip(th::apply(yb, hb).iter())) {
+            *c ^= x ^ y;
+        }
+        let C = Wire::from_block(th::join(C), &A.modulus());
+        Ok(C.plus_mov(&D.cmul(alpha * beta)))
+    }
+
+    /// Buffer the bytes of a three-halves gate, writing every whole block.
+    pub(crate) fn write_halves(&mut self, bytes: &[u8]) -> Result<(), GarblerError> {
+        self.halves.extend_from_slice(bytes);
+        let n = self.halves.len() / 16 * 16;
+        let whole = self.halves.drain(..n).collect::<Vec<u8>>();
+        for chunk in whole.chunks(16) {
+            let mut b = [0u8; 16];
+            b.copy_from_slice(chunk);
+            self.write_raw(&Block::from(u128::from_le_bytes(b)))?;
+        }
+        Ok(())
+    }
+
+    /// Pad the bytes of buffered three-halves gates to a whole block and write
+    /// it.
+    ///
+    /// The garbler does this before every other write, and the evaluator skips
+    /// the padding before every other read. A protocol which writes to the
+    /// channel itself in between gates must call this first, and
+    /// `Evaluator::align` on the other side.
+    pub fn align(&mut self) -> Result<(), GarblerError> {
+        if !self.halves.is_empty() {
+            let padding = 16 - self.halves.len();
+            self.write_halves(&vec![0; padding])?;
+        }
+        Ok(())
+    }
+
+    /// Write a block after any buffered three-halves gates.
+    pub(crate) fn write_block(&mut self, block: &Block) -> Result<(), GarblerError> {
+        self.align()?;
+        self.write_raw(block)
+    }
+
+    /// Announce the mode before the first block, see `with_mode`.
+    fn write_mode(&mut self) -> Result<(), GarblerError> {
+        if self.announce_mode {
+            self.announce_mode = false;
+            self.write_raw(&Block::from(self.mode.to_byte() as u128))?;
+        }
+        Ok(())
+    }
+
+    /// Write a block to the channel, or to the buffer if buffering is on.
+    fn write_raw(&mut self, block: &Block) -> Result<(), GarblerError> {
+        self.write_mode()?;
+        if self.buffer_size == 0 {
+            self.write_channel(block.as_ref())?;
+        } else {
+            self.buffer.extend_from_slice(block.as_ref());
+            if self.buffer.len() >= self.buffer_size {
+                self.write_buffer()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the buffered ciphertexts to the channel.
+    fn write_buffer(&mut self) -> Result<(), GarblerError> {
+        if !self.buffer.is_empty() {
+            if let Some(hash) = self.transcript.as_mut() {
+                hash.input(&self.buffer);
+            }
+            self.channel.write_bytes(&self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    /// Write bytes to the channel, adding them to the transcript.
+    fn write_channel(&mut self, bytes: &[u8]) -> Result<(), GarblerError> {
+        if let Some(hash) = self.transcript.as_mut() {
+            hash.input(bytes);
+        }
+        self.channel.write_bytes(bytes)?;
+        Ok(())
+    }
+
+    /// Write everything garbled so far to the channel, including buffered
+    /// three-halves gates and ciphertexts, and flush the channel.
+    pub fn flush(&mut self) -> Result<(), GarblerError> {
+        self.align()?;
+        self.write_buffer()?;
+        self.channel.flush()?;
+        Ok(())
+    }
+
     /// The current output index of the garbling computation.
     fn current_output(&mut self) -> usize {
         let current = self.current_output;
@@ -70,6 +498,12 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
         current
     }
 
+    /// Replace the deltas by fresh ones, drawn as they are needed, returning
+    /// the old ones. Wires garbled so far stay valid only under the old deltas.
+    pub(crate) fn refresh_deltas(&mut self) -> HashMap<Modulus, Wire> {
+        std::mem::take(&mut self.deltas)
+    }
+
     /// Get the deltas, consuming the Garbler.
     ///
     /// This is useful for reusing wires in multiple garbled circuit instances.
@@ -79,13 +513,45 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
 
     /// Send a wire over the established channel.
     pub fn send_wire(&mut self, wire: &Wire) -> Result<(), GarblerError> {
-        self.channel.write_block(&wire.as_block())?;
+        self.write_block(&wire.as_block())?;
+        Ok(())
+    }
+
+    /// Send wires over the established channel, in a single write after
+    /// everything garbled so far. Call `flush` to send them.
+    pub fn send_wires(&mut self, wires: &[Wire]) -> Result<(), GarblerError> {
+        self.write_mode()?;
+        self.align()?;
+        self.write_buffer()?;
+        let mut bytes = Vec::with_capacity(16 * wires.len());
+        for wire in wires.iter() {
+            bytes.extend_from_slice(wire.as_block().as_ref());
+        }
+        self.write_channel(&bytes)?;
         Ok(())
     }
 
     /// Encode a wire, producing the zero wire as well as the encoded value.
     pub fn encode_wire(&mut self, val: u16, modulus: &Modulus) -> (Wire, Wire) {
         let zero = Wire::rand(&mut self.rng, modulus);
+        let zero = self.zero_label(zero);
+        let delta = self.delta(modulus);
+        let enc = zero.plus(&delta.cmul(val));
+        (zero, enc)
+    }
+
+    /// Encode `val` with a zero label derived deterministically from `seed`,
+    /// producing the zero wire as well as the encoded value.
+    ///
+    /// The labels are correlated by `self.delta(modulus)` like any other labels
+    /// of this garbler, so they can be used as inputs to the garbled circuit.
+    /// The same `seed` always yields the same zero label, so an external
+    /// protocol can recompute it later. It is only as secret as `seed`: a seed
+    /// known to the evaluator reveals the value of every wire encoded with it,
+    /// and reusing a seed for two values reveals their difference.
+    pub fn encode_with_delta(&mut self, seed: Block, val: u16, modulus: &Modulus) -> (Wire, Wire) {
+        let zero = Wire::rand(&mut AesRng::from_seed(seed), modulus);
+        let zero = self.zero_label(zero);
         let delta = self.delta(modulus);
         let enc = zero.plus(&delta.cmul(val));
         (zero, enc)
@@ -135,40 +601,193 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
         let (gbs, evs) = self.encode_many_wires(&xs, &ms)?;
         Ok((BinaryBundle::new(gbs), BinaryBundle::new(evs)))
     }
+
+    /// Output a batch of wires and flush the channel, so that the evaluator can
+    /// decode them before the rest of the circuit has been garbled.
+    ///
+    /// Pairs with `Evaluator::output_stream`.
+    pub fn output_stream(&mut self, xs: &[Wire]) -> Result<(), GarblerError> {
+        for x in xs.iter() {
+            self.output(x)?;
+        }
+        self.flush()?;
+        Ok(())
+    }
+}
+
+impl<C, RNG, H> HasRole for Garbler<C, RNG, H> {
+    type Role = role::Garbler;
 }
 
-impl<C: AbstractChannel, RNG: RngCore + CryptoRng> FancyReveal for Garbler<C, RNG> {
+impl<C: AbstractChannel, RNG: RngCore + CryptoRng, H: GateHash> FancyReveal
+    for Garbler<C, RNG, H>
+{
     fn reveal(&mut self, x: &Wire) -> Result<u16, GarblerError> {
         // The evaluator needs our cooperation in order to see the output.
         // Hence, we call output() ourselves.
         self.output(x)?;
-        self.channel.flush()?;
+        self.flush()?;
         let val = self.channel.read_u16()?;
         Ok(val)
     }
 }
 
-impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
+impl<C: AbstractChannel, RNG: RngCore + CryptoRng, H: GateHash> FancyBranch
+    for Garbler<C, RNG, H>
+{
+    /// Stack the garbled branches, see `garble::stack`. The evaluator must
+    /// call `branch` with the same branches at the same point.
+    fn branch(
+        &mut self,
+        cond: &Wire,
+        branches: &[SubCircuit],
+        inputs: &[Wire],
+    ) -> Result<Vec<Wire>, GarblerError> {
+        let out_mods = stack::check(self.mode, cond, branches, inputs)?;
+        let n = branches.len();
+        let Dc = self.delta(&cond.modulus());
+        let conds = (0..n).map(|c| cond.plus(&Dc.cmul(c as u16))).collect::<Vec<_>>();
+
+        let seeds = (0..n).map(|_| self.rng.gen::<Block>()).collect::<Vec<_>>();
+        let fakes = (0..n).map(|_| self.rng.gen::<Block>()).collect::<Vec<_>>();
+        let garbled = branches
+            .iter()
+            .zip(seeds.iter())
+            .map(|(b, s)| stack::garble(b, *s, self.mode, &self.hasher))
+            .collect::<Result<Vec<_>, _>>()?;
+        let garbage = branches
+            .iter()
+            .zip(fakes.iter())
+            .map(|(b, s)| stack::garble(b, *s, self.mode, &self.hasher))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // the evaluator taking branch c gets the seeds of the others, and a
+        // garbage seed for c
+        let g = self.current_gate() as u64;
+        let mut table = vec![Block::default(); n * n];
+        for (c, C) in conds.iter().enumerate() {
+            for j in 0..n {
+                let seed = if c == j { fakes[j] } else { seeds[j] };
+                table[C.color() as usize * n + j] = seed ^ C.hash_with(&self.hasher, tweak2(g, j as u64));
+            }
+        }
+        for block in table.iter() {
+            self.write_block(block)?;
+        }
+
+        // the inputs of the taken branch, and idle labels for the others
+        let idle = garbled
+            .iter()
+            .map(|gb| gb.inputs.iter().map(|x| Wire::rand(&mut self.rng, &x.modulus())).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        for (i, gb) in garbled.iter().enumerate() {
+            for (j, x) in inputs.iter().enumerate() {
+                let q = x.modulus();
+                let (Dx, Di) = (self.delta(&q), &gb.deltas[&q]);
+                let g = self.current_gate() as u64;
+                let mut table = vec![Block::default(); n * q.size() as usize];
+                for (c, C) in conds.iter().enumerate() {
+                    let key = C.hash_with(&self.hasher, tweak2(g, 0));
+                    for v in 0..q.size() {
+                        let X = x.plus(&Dx.cmul(v));
+                        let target = if c == i { gb.inputs[j].plus(&Di.cmul(v)) } else { idle[i][j].clone() };
+                        table[(C.color() * q.size() + X.color()) as usize] =
+                            key ^ X.hash_with(&self.hasher, tweak2(g, 1)) ^ target.as_block();
+                    }
+                }
+                for block in table.iter() {
+                    self.write_block(block)?;
+                }
+            }
+        }
+
+        let mut stacked = Vec::new();
+        for gb in garbled.iter() {
+            stack::xor_into(&mut stacked, &gb.material);
+        }
+        for block in stacked.iter() {
+            self.write_block(block)?;
+        }
+
+        // the output labels of the untaken branches, evaluated on the idle
+        // labels and the taken branch's material XORed with its garbage
+        let mut outs = Vec::with_capacity(n);
+        for c in 0..n {
+            let mut rest = garbled[c].material.clone();
+            stack::xor_into(&mut rest, &garbage[c].material);
+            let mut out = Vec::with_capacity(n);
+            for (i, b) in branches.iter().enumerate() {
+                if i == c {
+                    out.push(Vec::new());
+                    continue;
+                }
+                let mut material = rest.clone();
+                stack::xor_into(&mut material, &garbled[i].material);
+                let zs = stack::eval(b, &material, self.mode, &self.hasher, &idle[i])
+                    .map_err(stack::garbler_error)?;
+                out.push(zs);
+            }
+            outs.push(out);
+        }
+
+        // the outputs of the taken branch, plus zero for the others
+        let ys = out_mods.iter().map(|q| Wire::rand(&mut self.rng, q)).collect::<Vec<_>>();
+        for (i, gb) in garbled.iter().enumerate() {
+            for (k, (y, q)) in ys.iter().zip(out_mods.iter()).enumerate() {
+                let (Dy, Di) = (self.delta(q), &gb.deltas[q]);
+                let g = self.current_gate() as u64;
+                let mut table = (0..n * q.size() as usize).map(|_| self.rng.gen::<Block>()).collect::<Vec<_>>();
+                for (c, C) in conds.iter().enumerate() {
+                    let key = C.hash_with(&self.hasher, tweak2(g, 0));
+                    let mut encrypt = |Z: &Wire, Y: &Wire| {
+                        table[(C.color() * q.size() + Z.color()) as usize] =
+                            key ^ Z.hash_with(&self.hasher, tweak2(g, 1)) ^ Y.as_block();
+                    };
+                    if c == i {
+                        for w in 0..q.size() {
+                            encrypt(&gb.outputs[k].plus(&Di.cmul(w)), &y.plus(&Dy.cmul(w)));
+                        }
+                    } else {
+                        encrypt(&outs[c][i][k], &Wire::zero(q));
+                    }
+                }
+                for block in table.iter() {
+                    self.write_block(block)?;
+                }
+            }
+        }
+        Ok(ys)
+    }
+}
+
+impl<C: AbstractChannel, RNG: RngCore + CryptoRng, H: GateHash> Fancy for Garbler<C, RNG, H> {
     type Item = Wire;
     type Error = GarblerError;
 
     fn constant(&mut self, x: u16, q: &Modulus) -> Result<Wire, GarblerError> {
         let zero = Wire::rand(&mut self.rng, q);
-        let wire = zero.plus(&self.delta(q).cmul_eq(x));
+        let zero = self.zero_label(zero);
+        let wire = zero.plus(self.delta(q).cmul_eq(x));
         self.send_wire(&wire)?;
         Ok(zero)
     }
 
     fn add(&mut self, x: &Wire, y: &Wire) -> Result<Wire, GarblerError> {
         if x.modulus() != y.modulus() {
-            return Err(GarblerError::FancyError(FancyError::UnequalModuli));
+            return Err(GarblerError::FancyError(FancyError::UnequalModuli {
+                x: x.modulus(),
+                y: y.modulus(),
+            }));
         }
         Ok(x.plus(y))
     }
 
     fn sub(&mut self, x: &Wire, y: &Wire) -> Result<Wire, GarblerError> {
         if x.modulus() != y.modulus() {
-            return Err(GarblerError::FancyError(FancyError::UnequalModuli));
+            return Err(GarblerError::FancyError(FancyError::UnequalModuli {
+                x: x.modulus(),
+                y: y.modulus(),
+            }));
         }
         Ok(x.minus(y))
     }
@@ -177,12 +796,30 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
         Ok(x.cmul(c))
     }
 
+    fn constant_big(&mut self, x: u64, q: &Modulus) -> Result<Wire, GarblerError> {
+        let zero = Wire::rand(&mut self.rng, q);
+        let zero = self.zero_label(zero);
+        let wire = zero.plus(self.delta(q).cmul_big_eq(x));
+        self.send_wire(&wire)?;
+        Ok(zero)
+    }
+
+    fn cmul_big(&mut self, x: &Wire, c: u64) -> Result<Wire, GarblerError> {
+        Ok(x.cmul_big(c))
+    }
+
     fn mul(&mut self, A: &Wire, B: &Wire) -> Result<Wire, GarblerError> {
         match (A.modulus(), B.modulus()) {
             (Modulus::Zq { q }, Modulus::Zq { q: qb }) => {
                 if q < qb {
                     return self.mul(B, A);
                 }
+                if self.mode == GarbleMode::PrivacyFree {
+                    return self.mul_privacy_free(A, B);
+                }
+                if self.mode == GarbleMode::ThreeHalves && q == 2 {
+                    return self.and_three_halves(A, B);
+                }
 
                 let gate_num = self.current_gate();
 
@@ -211,7 +848,7 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
                             B_.plus_eq(&Db);
                         }
                         let new_color = ((r + b) % q) as u128;
-                        let ct = (u128::from(B_.hash(t)) & 0xFFFF) ^ new_color;
+                        let ct = (u128::from(B_.hash_with(&self.hasher, t)) & 0xFFFF) ^ new_color;
                         minitable[B_.color() as usize] = ct;
                     }
 
@@ -230,14 +867,14 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
                 let alpha = (q - A.color()) % q; // alpha = -A.color
                 let X = A
                     .plus(&D.cmul(alpha))
-                    .hashback(g, &modA)
-                    .plus_mov(&D.cmul(alpha * r % q));
+                    .hashback_with(&self.hasher, g, &modA)
+                    .plus_mov(&D.cmul((alpha as u32 * r as u32 % q as u32) as u16));
 
                 // Y = H(B + bD) + (b + r)A such that b + B.color == 0
                 let beta = (qb - B.color()) % qb;
                 let Y = B
                     .plus(&Db.cmul(beta))
-                    .hashback(g, &modA)
+                    .hashback_with(&self.hasher, g, &modA)
                     .plus_mov(&A.cmul((beta + r) % q));
 
                 let mut precomp = Vec::with_capacity(q as usize);
@@ -259,8 +896,10 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
                     // garbler's half-gate: outputs X-arD
                     // G = H(A+aD) ^ X+a(-r)D = H(A+aD) ^ X-arD
                     if A_.color() != 0 {
+                        // in u32, as the product overflows for moduli above 256
+                        let ar = (a as u32 * r as u32 % q as u32) as u16;
                         gate[A_.color() as usize - 1] =
-                            A_.hash(g) ^ precomp[((q - (a * r % q)) % q) as usize];
+                            A_.hash_with(&self.hasher, g) ^ precomp[((q - ar) % q) as usize];
                     }
                 }
 
@@ -271,7 +910,7 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
                 let mut Y_ = Y.clone();
                 precomp.push(Y_.as_block());
                 for _ in 1..q {
-                    Y_.plus_eq(&A);
+                    Y_.plus_eq(A);
                     precomp.push(Y_.as_block());
                 }
 
@@ -284,18 +923,18 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
                     // G = H(B+bD) + Y-(b+r)A
                     if B_.color() != 0 {
                         gate[q as usize - 1 + B_.color() as usize - 1] =
-                            B_.hash(g) ^ precomp[((q - ((b + r) % q)) % q) as usize];
+                            B_.hash_with(&self.hasher, g) ^ precomp[((q - ((b + r) % q)) % q) as usize];
                     }
                 }
 
                 for block in gate.iter() {
-                    self.channel.write_block(block)?;
+                    self.write_block(block)?;
                 }
                 Ok(X.plus_mov(&Y))
             }
-            // (Modulus::GF4 { p }, Modulus::GF4 { p: pb }) => {
-            //     // TODO
-            // }
+            (q, qb) if q.is_field() && q == qb && self.mode != GarbleMode::PrivacyFree => {
+                self.mul_field(A, B)
+            }
             _ => {
                 Err(GarblerError::FancyError(FancyError::InvalidArg(format!("Multiplication of {:?} and {:?} is not supported", A.modulus(), B.modulus()))))
             }
@@ -304,110 +943,201 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
 
     fn proj(&mut self, A: &Wire, mod_out: &Modulus, tt: Option<Vec<u16>>) -> Result<Wire, GarblerError> {
         let tt = tt.ok_or(GarblerError::TruthTableRequired)?;
+        self.proj_table(A, mod_out, &tt)
+    }
 
-        let mod_in = A.modulus();
-        // let q_in:u16; let q_out:u16;
-        let Din: Wire; let Dout: Wire;
-
-        let mut gate = vec![Block::default(); mod_in.size() as usize - 1];
+    /// Garble the projections on worker threads, then write their ciphertexts
+    /// in order, so that the evaluator sees the same stream as from `proj`.
+    #[cfg(feature = "parallel")]
+    fn proj_many(
+        &mut self,
+        xs: &[Wire],
+        q: &Modulus,
+        tts: &[Vec<u16>],
+    ) -> Result<Vec<Wire>, GarblerError> {
+        use rayon::prelude::*;
 
-        let tao = A.color();
-        let g = tweak(self.current_gate());
-        let C: Wire;
-
-        Din = self.delta(&mod_in);
-        Dout = self.delta(&mod_out);
-
-        // output zero-wire
-        // W_g^0 <- -H(g, W_{a_1}^0 - \tao\Delta_m) - \phi(-\tao)\Delta_n
-        let neg_tao= match mod_in {
-            Modulus::Zq {q: q_i} =>  (q_i - tao) % q_i, // rotate negative -tao over mod q_in
-            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} => tao, // in GF(2^k): -tao = tao
-        };
-        let neg_phi_neg_tao = match mod_out {
-            Modulus::Zq {q: q_o} =>  (q_o - tt[neg_tao as usize]) % q_o,
-            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} => tt[(tao) as usize],
-        };
-        C = A
-            .plus(&Din.cmul(neg_tao))
-            .hashback(g, mod_out)
-            .plus_mov(&Dout.cmul(neg_phi_neg_tao));
-
-        // precompute `let C_ = C.plus(&Dout.cmul(tt[x as usize]))`
-        // TODO: this might compute labels that are not used in the truth table at all!
-        let C_precomputed = match mod_out {
-            Modulus::Zq { q: q_i } => {
-                let mut C_ = C.clone();
-                (0..*q_i).map(|x| {
-                    if x > 0 {
-                        C_.plus_eq(&Dout);
-                    }
-                    C_.as_block()
-                }).collect::<Vec<Block>>()
-            }
-            Modulus::GF4 { .. } | Modulus::GF8 { .. } | Modulus::GFk { .. } => {
-                let mut C_ = C.clone();
-                (0..mod_out.size()).map(|x| {
-                    if x > 0 {
-                        C_ = C.clone();
-                        C_.plus_eq(&Dout.cmul(x));
-                    }
-                    C_.as_block()
-                }).collect::<Vec<Block>>()
+        if tts.len() != xs.len() {
+            return Err(GarblerError::FancyError(FancyError::InvalidArgNum {
+                got: tts.len(),
+                needed: xs.len(),
+            }));
+        }
+        // privacy-free and unsupported projections go through `proj`
+        let supported = self.mode != GarbleMode::PrivacyFree
+            && xs
+                .iter()
+                .map(|x| x.modulus())
+                .chain(std::iter::once(*q))
+                .all(|q| !matches!(q, Modulus::ZqBig { .. }));
+        if !supported {
+            return xs
+                .iter()
+                .zip(tts)
+                .map(|(x, tt)| self.proj(x, q, Some(tt.clone())))
+                .collect();
+        }
+        // number the gates and draw the deltas in the order `proj` would
+        let jobs = xs
+            .iter()
+            .zip(tts)
+            .map(|(x, tt)| {
+                let g = tweak(self.current_gate());
+                let Din = self.delta(&x.modulus());
+                let Dout = self.delta(q);
+                (x, tt, g, Din, Dout)
+            })
+            .collect::<Vec<_>>();
+        let hasher = &self.hasher;
+        let gates = jobs
+            .par_iter()
+            .map(|(x, tt, g, Din, Dout)| garble_proj(hasher, *g, x, Din, Dout, q, tt))
+            .collect::<Vec<_>>();
+        let mut zs = Vec::with_capacity(gates.len());
+        for (z, gate) in gates {
+            for block in gate.iter() {
+                self.write_block(block)?;
             }
-        };
+            zs.push(z);
+        }
+        Ok(zs)
+    }
 
-        let mut A_ = A.clone();
-        match mod_in {
-            Modulus::Zq {q: q_i} => {
-                for x in 0..q_i {
-                    if x > 0 {
-                        A_.plus_eq(&Din); // avoiding expensive cmul for `A_ = A.plus(&Din.cmul(x))`
-                    }
+    fn output(&mut self, X: &Wire) -> Result<Option<u16>, GarblerError> {
+        let modulus = X.modulus();
+        if let Modulus::ZqBig { .. } = modulus {
+            return Err(GarblerError::FancyError(FancyError::InvalidArg(format!(
+                "Outputs of {} need output_big",
+                modulus
+            ))));
+        }
+        let q = modulus.order();
+        let i = self.current_output();
+        let D = self.delta(&modulus);
 
-                    let ix = (tao as usize + x as usize) % q_i as usize;
-                    if ix == 0 {
-                        continue;
-                    }
+        for k in (0..q).map(|k| k as u16) {
+            let block = X.plus(&D.cmul(k)).hash_with(&self.hasher, output_tweak(i, k));
+            self.write_block(&block)?;
+        }
+        Ok(None)
+    }
 
-                    let ct = A_.hash(g) ^ C_precomputed[tt[x as usize] as usize];
-                    gate[ix - 1] = ct;
-                }
-            },
-            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} => {
-                for x in 0..mod_in.size() {
-                    if x > 0 {
-                        A_ = A.clone();
-                        A_.plus_eq(&Din.cmul(x));
-                    }
+    /// Outputs of `ZqBig` wires cannot be decoded by trying all `q` values, so
+    /// the garbler instead sends the color of the zero wire-label, from which
+    /// the evaluator recovers the value since deltas have color `1`. This
+    /// reveals no more than the output, but unlike hashed outputs it does not
+    /// detect an evaluator holding an invalid wire-label.
+    fn output_big(&mut self, X: &Wire) -> Result<Option<u64>, GarblerError> {
+        if let Modulus::ZqBig { .. } = X.modulus() {
+            self.current_output();
+            self.write_block(&Block::from(X.color_big() as u128))?;
+            return Ok(None);
+        }
+        Ok(self.output(X)?.map(u64::from))
+    }
+}
 
-                    let ix = (tao ^ x) as usize;
-                    if ix == 0 {
-                        continue;
-                    }
+/// Garble the standard projection of `A` through `tt` into `mod_out` as gate
+/// `g`, returning the output zero label and the ciphertexts. This only reads
+/// the deltas, so independent projections can be garbled in parallel.
+fn garble_proj<H: GateHash>(
+    hasher: &H,
+    g: Block,
+    A: &Wire,
+    Din: &Wire,
+    Dout: &Wire,
+    mod_out: &Modulus,
+    tt: &[u16],
+) -> (Wire, Vec<Block>) {
+    let mod_in = A.modulus();
+    let mut gate = vec![Block::default(); mod_in.order() as usize - 1];
+
+    let tao = A.color();
+
+    // output zero-wire
+    // W_g^0 <- -H(g, W_{a_1}^0 - \tao\Delta_m) - \phi(-\tao)\Delta_n
+    let neg_tao= match mod_in {
+        Modulus::Zq {q: q_i} =>  (q_i - tao) % q_i, // rotate negative -tao over mod q_in
+        Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} | Modulus::GF16 {..} => tao, // in GF(2^k): -tao = tao
+        Modulus::GFpk { p, k, .. } => util::gfpk_neg(tao, p, k),
+        Modulus::ZqBig { .. } => unreachable!(),
+    };
+    let neg_phi_neg_tao = match mod_out {
+        Modulus::Zq {q: q_o} =>  (q_o - tt[neg_tao as usize]) % q_o,
+        Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} | Modulus::GF16 {..} => tt[neg_tao as usize],
+        Modulus::GFpk { p, k, .. } => util::gfpk_neg(tt[neg_tao as usize], *p, *k),
+        Modulus::ZqBig { .. } => unreachable!(),
+    };
+    let C = A
+        .plus(&Din.cmul(neg_tao))
+        .hashback_with(hasher, g, mod_out)
+        .plus_mov(&Dout.cmul(neg_phi_neg_tao));
 
-                    let ct = A_.hash(g) ^ C_precomputed[tt[x as usize] as usize];
-                    gate[ix - 1] = ct;
+    // precompute `let C_ = C.plus(&Dout.cmul(tt[x as usize]))`, for the
+    // outputs the truth table actually takes
+    let mut needed = vec![false; mod_out.order() as usize];
+    for &y in tt.iter() {
+        needed[y as usize] = true;
+    }
+    let mut C_precomputed = vec![Block::default(); needed.len()];
+    match mod_out {
+        Modulus::Zq { .. } => {
+            // walk up to the largest output by adding `Dout`, avoiding cmuls
+            let max = tt.iter().copied().max().unwrap_or(0);
+            let mut C_ = C.clone();
+            for x in 0..=max {
+                if x > 0 {
+                    C_.plus_eq(Dout);
+                }
+                if needed[x as usize] {
+                    C_precomputed[x as usize] = C_.as_block();
                 }
             }
         }
-
-        for block in gate.iter() {
-            self.channel.write_block(block)?;
+        Modulus::GF4 { .. } | Modulus::GF8 { .. } | Modulus::GFk { .. } | Modulus::GF16 { .. } | Modulus::GFpk { .. } => {
+            for x in (0..needed.len()).filter(|x| needed[*x]) {
+                C_precomputed[x] = C.plus(&Dout.cmul(x as u16)).as_block();
+            }
         }
-        Ok(C)
+        Modulus::ZqBig { .. } => unreachable!(),
     }
 
-    fn output(&mut self, X: &Wire) -> Result<Option<u16>, GarblerError> {
-        let modulus = X.modulus();
-        let q = modulus.size();
-        let i = self.current_output();
-        let D = self.delta(&modulus);
+    let mut A_ = A.clone();
+    match mod_in {
+        Modulus::Zq {q: q_i} => {
+            for x in 0..q_i {
+                if x > 0 {
+                    A_.plus_eq(Din); // avoiding expensive cmul for `A_ = A.plus(&Din.cmul(x))`
+                }
+
+                let ix = (tao as usize + x as usize) % q_i as usize;
+                if ix == 0 {
+                    continue;
+                }
+
+                let ct = A_.hash_with(hasher, g) ^ C_precomputed[tt[x as usize] as usize];
+                gate[ix - 1] = ct;
+            }
+        },
+        Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} | Modulus::GF16 {..} | Modulus::GFpk {..} => {
+            for x in (0..mod_in.order()).map(|x| x as u16) {
+                if x > 0 {
+                    A_ = A.clone();
+                    A_.plus_eq(&Din.cmul(x));
+                }
+
+                // tao + x, which is tao ^ x in GF(2^k)
+                let ix = A_.color() as usize;
+                if ix == 0 {
+                    continue;
+                }
 
-        for k in 0..q {
-            let block = X.plus(&D.cmul(k)).hash(output_tweak(i, k));
-            self.channel.write_block(&block)?;
+                let ct = A_.hash_with(hasher, g) ^ C_precomputed[tt[x as usize] as usize];
+                gate[ix - 1] = ct;
+            }
         }
-        Ok(None)
+        Modulus::ZqBig { .. } => unreachable!(),
     }
+
+    (C, gate)
 }
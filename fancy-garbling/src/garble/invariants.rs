@@ -0,0 +1,280 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Debug-only `Fancy` object which checks the free-XOR invariants of the garbler.
+//!
+//! `InvariantChecker` runs a `Garbler`, an `Evaluator`, and a `Dummy` in lockstep
+//! over an in-memory channel. After every gate it asserts that the label the
+//! evaluator obtained equals `zero + value * delta` for the wire's modulus, where
+//! `zero` is the garbler's zero label and `value` the plaintext result.
+
+use crate::{
+    dummy::{Dummy, DummyVal},
+    errors::{DummyError, FancyError, TwopacError},
+    fancy::{Fancy, HasModulus},
+    garble::{Evaluator, Garbler},
+    wire::{Modulus, Wire},
+};
+use scuttlebutt::{AbstractChannel, AesRng, Channel};
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{Read, Write},
+    rc::Rc,
+};
+
+/// In-memory byte pipe connecting the garbler to the evaluator.
+#[derive(Clone, Debug, Default)]
+struct Loopback(Rc<RefCell<VecDeque<u8>>>);
+
+impl Read for Loopback {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+impl Write for Loopback {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+type LoopbackChannel = Channel<Loopback, Loopback>;
+
+/// Wire of the `InvariantChecker`: the garbler's zero label, the evaluator's
+/// label, and the plaintext value.
+#[derive(Clone, Debug)]
+pub struct CheckedWire {
+    zero: Wire,
+    label: Wire,
+    val: DummyVal,
+}
+
+impl CheckedWire {
+    /// The plaintext value on this wire.
+    pub fn val(&self) -> u16 {
+        self.val.val()
+    }
+}
+
+impl HasModulus for CheckedWire {
+    fn modulus(&self) -> Modulus {
+        self.val.modulus()
+    }
+}
+
+/// `Fancy` object asserting `label = zero + value * delta` after every gate.
+pub struct InvariantChecker {
+    garbler: Garbler<LoopbackChannel, AesRng>,
+    evaluator: Evaluator<LoopbackChannel>,
+    dummy: Dummy,
+    pipe: Loopback,
+    nchecks: usize,
+}
+
+impl InvariantChecker {
+    /// Create a new checker, using `rng` for the garbler's randomness.
+    pub fn new(rng: AesRng) -> Self {
+        let pipe = Loopback::default();
+        let channel = Channel::new(pipe.clone(), pipe.clone());
+        InvariantChecker {
+            garbler: Garbler::new(channel.clone(), rng),
+            evaluator: Evaluator::new(channel),
+            dummy: Dummy::new(),
+            pipe,
+            nchecks: 0,
+        }
+    }
+
+    /// The number of wires checked so far.
+    pub fn nchecks(&self) -> usize {
+        self.nchecks
+    }
+
+    /// Encode an input `x` with modulus `q`.
+    pub fn input(&mut self, x: u16, q: &Modulus) -> Result<CheckedWire, TwopacError> {
+        let (zero, label) = self.garbler.encode_wire(x, q);
+        let val = DummyVal::new(x, *q);
+        self.check(CheckedWire { zero, label, val })
+    }
+
+    /// Encode many inputs.
+    pub fn inputs(&mut self, xs: &[u16], qs: &[Modulus]) -> Result<Vec<CheckedWire>, TwopacError> {
+        if xs.len() != qs.len() {
+            return Err(TwopacError::FancyError(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: qs.len(),
+            }));
+        }
+        xs.iter().zip(qs.iter()).map(|(x, q)| self.input(*x, q)).collect()
+    }
+
+    fn check(&mut self, w: CheckedWire) -> Result<CheckedWire, TwopacError> {
+        let q = w.modulus();
+        let delta = self.garbler.delta(&q);
+        let expected = w.zero.plus(&delta.cmul(w.val()));
+        assert_eq!(
+            w.label,
+            expected,
+            "free-XOR invariant violated for modulus {} with value {}",
+            q,
+            w.val()
+        );
+        assert!(
+            self.pipe.0.borrow().is_empty(),
+            "evaluator did not consume exactly the garbler's ciphertexts"
+        );
+        self.nchecks += 1;
+        Ok(w)
+    }
+}
+
+fn dummy_err(e: DummyError) -> TwopacError {
+    match e {
+        DummyError::FancyError(e) => TwopacError::FancyError(e),
+        e => TwopacError::FancyError(FancyError::InvalidArg(e.to_string())),
+    }
+}
+
+impl Fancy for InvariantChecker {
+    type Item = CheckedWire;
+    type Error = TwopacError;
+
+    fn constant(&mut self, x: u16, q: &Modulus) -> Result<CheckedWire, TwopacError> {
+        let zero = self.garbler.constant(x, q)?;
+        let label = self.evaluator.constant(x, q)?;
+        let val = self.dummy.constant(x, q).map_err(dummy_err)?;
+        self.check(CheckedWire { zero, label, val })
+    }
+
+    fn add(&mut self, x: &CheckedWire, y: &CheckedWire) -> Result<CheckedWire, TwopacError> {
+        let zero = self.garbler.add(&x.zero, &y.zero)?;
+        let label = self.evaluator.add(&x.label, &y.label)?;
+        let val = self.dummy.add(&x.val, &y.val).map_err(dummy_err)?;
+        self.check(CheckedWire { zero, label, val })
+    }
+
+    fn sub(&mut self, x: &CheckedWire, y: &CheckedWire) -> Result<CheckedWire, TwopacError> {
+        let zero = self.garbler.sub(&x.zero, &y.zero)?;
+        let label = self.evaluator.sub(&x.label, &y.label)?;
+        let val = self.dummy.sub(&x.val, &y.val).map_err(dummy_err)?;
+        self.check(CheckedWire { zero, label, val })
+    }
+
+    fn cmul(&mut self, x: &CheckedWire, c: u16) -> Result<CheckedWire, TwopacError> {
+        let zero = self.garbler.cmul(&x.zero, c)?;
+        let label = self.evaluator.cmul(&x.label, c)?;
+        let val = self.dummy.cmul(&x.val, c).map_err(dummy_err)?;
+        self.check(CheckedWire { zero, label, val })
+    }
+
+    fn mul(&mut self, x: &CheckedWire, y: &CheckedWire) -> Result<CheckedWire, TwopacError> {
+        let zero = self.garbler.mul(&x.zero, &y.zero)?;
+        let label = self.evaluator.mul(&x.label, &y.label)?;
+        let val = self.dummy.mul(&x.val, &y.val).map_err(dummy_err)?;
+        self.check(CheckedWire { zero, label, val })
+    }
+
+    fn proj(
+        &mut self,
+        x: &CheckedWire,
+        q: &Modulus,
+        tt: Option<Vec<u16>>,
+    ) -> Result<CheckedWire, TwopacError> {
+        let zero = self.garbler.proj(&x.zero, q, tt.clone())?;
+        let label = self.evaluator.proj(&x.label, q, None)?;
+        let val = self.dummy.proj(&x.val, q, tt).map_err(dummy_err)?;
+        self.check(CheckedWire { zero, label, val })
+    }
+
+    fn output(&mut self, x: &CheckedWire) -> Result<Option<u16>, TwopacError> {
+        self.garbler.output(&x.zero)?;
+        let decoded = self.evaluator.output(&x.label)?;
+        assert_eq!(
+            decoded,
+            Some(x.val()),
+            "output decoding disagrees with plaintext for modulus {}",
+            x.modulus()
+        );
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::RngExt;
+    use rand::{seq::SliceRandom, Rng, SeedableRng};
+
+    // apply `nops` random gates to a pool of wires with modulus `q`
+    fn random_gates(rng: &mut AesRng, q: &Modulus, nops: usize) -> usize {
+        let mut ch = InvariantChecker::new(AesRng::from_seed(rng.gen()));
+        let size = q.size();
+        let inps = (0..4).map(|_| rng.gen_u16() % size).collect::<Vec<_>>();
+        let mut pool = ch.inputs(&inps, &vec![*q; inps.len()]).unwrap();
+        for _ in 0..nops {
+            let x = pool.choose(rng).unwrap().clone();
+            let y = pool.choose(rng).unwrap().clone();
+            let z = match rng.gen_u16() % 6 {
+                0 => ch.add(&x, &y).unwrap(),
+                1 => ch.sub(&x, &y).unwrap(),
+                2 => ch.cmul(&x, rng.gen_u16() % size).unwrap(),
+                3 => ch.constant(rng.gen_u16() % size, q).unwrap(),
+                4 => {
+                    let tt = (0..size).map(|_| rng.gen_u16() % size).collect();
+                    ch.proj(&x, q, Some(tt)).unwrap()
+                }
+                // two Zq wires, or two field elements with `mul_field`
+                _ => ch.mul(&x, &y).unwrap(),
+            };
+            pool.push(z);
+        }
+        for z in pool.iter() {
+            ch.output(z).unwrap();
+        }
+        ch.nchecks()
+    }
+
+    #[test]
+    fn zq() {
+        let mut rng = AesRng::new();
+        for _ in 0..16 {
+            let q = Modulus::Zq { q: rng.gen_modulus() };
+            assert_eq!(random_gates(&mut rng, &q, 64), 68);
+        }
+    }
+
+    #[test]
+    fn gf4() {
+        let mut rng = AesRng::new();
+        for q in Modulus::GF4_MODULI.iter() {
+            assert_eq!(random_gates(&mut rng, q, 64), 68);
+        }
+    }
+
+    #[test]
+    fn gf8() {
+        let mut rng = AesRng::new();
+        for q in Modulus::GF8_MODULI.iter().take(8) {
+            assert_eq!(random_gates(&mut rng, q, 64), 68);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "free-XOR invariant violated")]
+    fn detects_bad_label() {
+        let mut ch = InvariantChecker::new(AesRng::new());
+        let q = Modulus::X4_X_1;
+        let mut x = ch.input(3, &q).unwrap();
+        let y = ch.input(5, &q).unwrap();
+        x.label = x.label.plus(&x.zero);
+        ch.add(&x, &y).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Stacked garbling of conditional branches, following Heath and Kolesnikov,
+//! "Stacked Garbling: Garbled Circuit Proportional to Longest Execution Path"
+//! (CRYPTO 2020), used by `FancyBranch::branch` of the streaming garbler and
+//! evaluator.
+//!
+//! The garbler garbles every branch `i` from its own seed `s_i`, with its own
+//! deltas, and sends the XOR of the garbled materials `M_i`, padded to the
+//! longest. Taking branch `c`, the evaluator learns the seeds `s_j` of the other
+//! branches and a garbage seed `g_c` in place of `s_c`, through a table keyed by
+//! the label of the condition. It regarbles every branch from the seed it got
+//! and XORs the materials out of the stack, leaving `M_c + F_c` where `F_c` is
+//! branch `c` garbled from `g_c`. Not knowing `c`, it evaluates every branch
+//! `i` on the stack with its own regarbled material XORed back in, which is
+//! `M_c` for `i = c` and garbage otherwise:
+//!
+//! 1. A demultiplexer, one table per branch and input, gives the evaluator the
+//!    branch's input labels for the taken branch and a fixed label for the
+//!    others.
+//! 2. The garbler knows everything the evaluator evaluates the untaken
+//!    branches on, so it predicts their garbage output labels.
+//! 3. A multiplexer, one table per branch and output, maps the labels of the
+//!    taken branch to output labels and the predicted garbage to zero, and the
+//!    evaluator adds up what it decrypts.
+//!
+//! Only the material of the longest branch is sent, plus tables quadratic in
+//! the number of branches, in exchange for the garbler garbling every branch
+//! twice and evaluating each `n - 1` times, and the evaluator garbling and
+//! evaluating every branch once. The condition, inputs and outputs must be
+//! `Zq` wires. Privacy-free garbling is not supported, since its evaluator
+//! knows which branch is taken anyway.
+
+use crate::{
+    circuit::SubCircuit,
+    errors::{EvaluatorError, FancyError, GarblerError},
+    fancy::HasModulus,
+    garble::{Evaluator, GarbleMode, Garbler},
+    hash::GateHash,
+    wire::{Modulus, Wire},
+};
+use rand::SeedableRng;
+use scuttlebutt::{AbstractChannel, AesRng, Block, Channel};
+use std::{collections::HashMap, io};
+
+/// A branch garbled on its own.
+pub(crate) struct GarbledBranch {
+    /// The gate tables and constants, as sent by the garbler.
+    pub(crate) material: Vec<Block>,
+    /// The zero labels of the formal inputs.
+    pub(crate) inputs: Vec<Wire>,
+    /// The zero labels of the formal outputs.
+    pub(crate) outputs: Vec<Wire>,
+    /// The deltas of the branch, including those of its inputs and outputs.
+    pub(crate) deltas: HashMap<Modulus, Wire>,
+}
+
+/// Garble `branch` from `seed`.
+pub(crate) fn garble<H: GateHash>(
+    branch: &SubCircuit,
+    seed: Block,
+    mode: GarbleMode,
+    hasher: &H,
+) -> Result<GarbledBranch, GarblerError> {
+    let channel = Channel::new(io::empty(), Vec::new());
+    let mut gb = Garbler::with_mode(channel.clone(), AesRng::from_seed(seed), mode)
+        .without_mode_announcement()
+        .with_hash(hasher);
+    let inputs = branch
+        .input_moduli()
+        .iter()
+        .map(|q| gb.encode_wire(0, q).0)
+        .collect::<Vec<_>>();
+    let outputs = branch.apply(&mut gb, &inputs)?;
+    for z in outputs.iter() {
+        gb.delta(&z.modulus());
+    }
+    gb.flush()?;
+    let deltas = gb.get_deltas();
+    let bytes = channel.writer().take();
+    let material = bytes
+        .chunks(16)
+        .map(|chunk| {
+            let mut b = [0u8; 16];
+            b.copy_from_slice(chunk);
+            Block::from(b)
+        })
+        .collect();
+    Ok(GarbledBranch {
+        material,
+        inputs,
+        outputs,
+        deltas,
+    })
+}
+
+/// Evaluate `branch` on `inputs` from `material`.
+pub(crate) fn eval<H: GateHash>(
+    branch: &SubCircuit,
+    material: &[Block],
+    mode: GarbleMode,
+    hasher: &H,
+    inputs: &[Wire],
+) -> Result<Vec<Wire>, EvaluatorError> {
+    let mut bytes = Vec::with_capacity(16 * material.len());
+    for block in material.iter() {
+        bytes.extend_from_slice(block.as_ref());
+    }
+    let channel = Channel::new(io::Cursor::new(bytes), io::sink());
+    let mut ev = Evaluator::with_mode(channel, mode)
+        .without_mode_announcement()
+        .with_hash(hasher);
+    branch.apply(&mut ev, inputs)
+}
+
+/// XOR `y` into `x`, extending `x` with zeros if it is shorter.
+pub(crate) fn xor_into(x: &mut Vec<Block>, y: &[Block]) {
+    if x.len() < y.len() {
+        x.resize(y.len(), Block::default());
+    }
+    for (a, b) in x.iter_mut().zip(y.iter()) {
+        *a ^= *b;
+    }
+}
+
+/// Check the arguments of `FancyBranch::branch` for stacking, returning the
+/// moduli of the outputs.
+pub(crate) fn check(
+    mode: GarbleMode,
+    cond: &Wire,
+    branches: &[SubCircuit],
+    inputs: &[Wire],
+) -> Result<Vec<Modulus>, FancyError> {
+    if mode == GarbleMode::PrivacyFree {
+        return Err(FancyError::InvalidArg(
+            "Privacy-free stacked branches are not supported".to_string(),
+        ));
+    }
+    let in_mods = inputs.iter().map(|x| x.modulus()).collect::<Vec<_>>();
+    let out_mods = SubCircuit::check_branches(&cond.modulus(), branches, &in_mods)?;
+    let cond_mod = cond.modulus();
+    let mut mods = Some(&cond_mod).into_iter().chain(in_mods.iter()).chain(out_mods.iter());
+    if let Some(q) = mods.find(|q| !matches!(q, Modulus::Zq { .. })) {
+        return Err(FancyError::InvalidArg(format!(
+            "Stacked branches on {} are not supported",
+            q
+        )));
+    }
+    Ok(out_mods)
+}
+
+/// The error of the garbler for an evaluation of garbage which failed.
+pub(crate) fn garbler_error(e: EvaluatorError) -> GarblerError {
+    match e {
+        EvaluatorError::IoError(e) => GarblerError::IoError(e),
+        EvaluatorError::FancyError(e) => GarblerError::FancyError(e),
+        e => GarblerError::CommunicationError(e.to_string()),
+    }
+}
+
+/// The error of the evaluator for a regarbling which failed.
+pub(crate) fn evaluator_error(e: GarblerError) -> EvaluatorError {
+    match e {
+        GarblerError::IoError(e) => EvaluatorError::IoError(e),
+        GarblerError::FancyError(e) => EvaluatorError::FancyError(e),
+        e => EvaluatorError::CommunicationError(e.to_string()),
+    }
+}
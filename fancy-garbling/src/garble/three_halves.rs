@@ -0,0 +1,100 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Arithmetic shared by the garbler and evaluator for three-halves garbling of
+//! binary AND gates, following Rosulek and Roy, "Three Halves Make a Whole?
+//! Beating the Half-Gates Lower Bound for Garbled Circuits" (CRYPTO 2021).
+//!
+//! Labels are split into a left half, whose lowest bit is the color, and a
+//! right half. For input labels `A` and `B` with colors `i` and `j` the
+//! evaluator computes the output label as
+//!
+//! ```text
+//! C = V [H(A), H(B), H(A + B)] + N_ij G + P_ij A + Q_ij B
+//! ```
+//!
+//! where `H` hashes a label to a half, `V` and `N_ij` are public, `G` are the
+//! three half ciphertexts of the gate, and the 2x2 matrices `P_ij`, `Q_ij` act
+//! on halves. The garbler picks `P_ij` and `Q_ij` at random such that they do
+//! not depend on the wire values, and sends them encrypted for each `(i, j)`.
+
+use scuttlebutt::Block;
+
+/// The number of bytes of a garbled AND gate: three half ciphertexts followed
+/// by encrypted control bytes for the color pairs `(0, 0)`, `(0, 1)` and
+/// `(1, 0)`. The control byte for `(1, 1)` is their sum.
+pub(crate) const GATE_BYTES: usize = 3 * 8 + 3;
+
+/// A 2x2 matrix over GF(2) acting on the halves of a label. Each row selects
+/// the left half with bit 0 and the right half with bit 1.
+pub(crate) type Matrix = [u8; 2];
+
+/// The identity on label halves.
+pub(crate) const IDENTITY: Matrix = [1, 2];
+
+/// Split a label into its left and right halves.
+pub(crate) fn split(block: Block) -> [u64; 2] {
+    let x = u128::from(block);
+    [x as u64, (x >> 64) as u64]
+}
+
+/// Join a left and a right half into a label.
+pub(crate) fn join(h: [u64; 2]) -> Block {
+    Block::from(u128::from(h[0]) | (u128::from(h[1]) << 64))
+}
+
+/// Apply one row of a matrix to the halves `h`.
+pub(crate) fn row(r: u8, h: [u64; 2]) -> u64 {
+    let l = if r & 1 == 1 { h[0] } else { 0 };
+    let r = if r & 2 == 2 { h[1] } else { 0 };
+    l ^ r
+}
+
+/// Apply `m` to the halves `h`.
+pub(crate) fn apply(m: Matrix, h: [u64; 2]) -> [u64; 2] {
+    [row(m[0], h), row(m[1], h)]
+}
+
+/// The sum of two matrices.
+pub(crate) fn add(m: Matrix, n: Matrix) -> Matrix {
+    [m[0] ^ n[0], m[1] ^ n[1]]
+}
+
+/// `V [a, b, k]`, combining the half hashes of `A`, `B` and `A + B`.
+pub(crate) fn combine(a: u64, b: u64, k: u64) -> [u64; 2] {
+    [a ^ k, b ^ k]
+}
+
+/// `N_ij x`, the combination of the three rows `x` used by the evaluator with
+/// colors `(i, j)`. Applied to the ciphertexts it cancels the hashes the
+/// evaluator computes in place of those of the color `(0, 0)` labels.
+pub(crate) fn select<T>(i: u16, j: u16, x: [T; 3]) -> [T; 2]
+where
+    T: std::ops::BitXor<Output = T> + Copy + Default,
+{
+    match (i, j) {
+        (0, 0) => [T::default(), T::default()],
+        (1, 0) => [x[0] ^ x[2], x[2]],
+        (0, 1) => [x[2], x[1] ^ x[2]],
+        _ => [x[0], x[1]],
+    }
+}
+
+/// Pack the matrices `P_ij` and `Q_ij` into a control byte.
+pub(crate) fn pack(p: Matrix, q: Matrix) -> u8 {
+    p[0] | p[1] << 2 | q[0] << 4 | q[1] << 6
+}
+
+/// Unpack a control byte into the matrices `P_ij` and `Q_ij`.
+pub(crate) fn unpack(c: u8) -> (Matrix, Matrix) {
+    ([c & 3, c >> 2 & 3], [c >> 4 & 3, c >> 6 & 3])
+}
+
+/// The half hash of a label, and the mask of the control bytes it contributes.
+pub(crate) fn hash_half(h: Block) -> (u64, u8) {
+    let x = u128::from(h);
+    (x as u64, (x >> 64) as u8)
+}
@@ -0,0 +1,80 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Hash functions for garbling gates.
+//!
+//! The garbler and evaluator hash wire labels together with a per-gate tweak
+//! to encrypt and decrypt ciphertexts. By default they use `FixedKeyAes`,
+//! which is fast but whose security relies on modelling AES under a fixed
+//! public key as a random permutation. `AesMmo` rekeys AES per gate but
+//! still needs AES to behave as an ideal cipher; `Sha3` instead relies on
+//! SHA3 as a random oracle. Both are slower. Select one with
+//! `Garbler::with_hash` and `Evaluator::with_hash`; both parties must use the
+//! same one.
+
+use scuttlebutt::{Aes128, Block, AES_HASH};
+use sha3::{Digest, Sha3_256};
+
+/// A tweakable hash function from labels to blocks, used to garble gates.
+///
+/// Hashes are shared between the threads of a parallel garbler, so they must
+/// be `Sync`.
+pub trait GateHash: Sync {
+    /// Hash the label `x` under `tweak`.
+    fn hash(&self, tweak: Block, x: Block) -> Block;
+}
+
+/// The tweakable circular correlation robust hash built from fixed-key AES,
+/// see `scuttlebutt::AesHash::tccr_hash`. This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedKeyAes;
+
+impl GateHash for FixedKeyAes {
+    #[inline]
+    fn hash(&self, tweak: Block, x: Block) -> Block {
+        AES_HASH.tccr_hash(tweak, x)
+    }
+}
+
+/// The Matyas–Meyer–Oseas construction `AES_tweak(x) + x`, keying AES with the
+/// tweak instead of a fixed key. Runs a key schedule for every hash.
+///
+/// The tweak is public, so this is no more than a public-key construction:
+/// its security is proven only when AES is modelled as an ideal cipher.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AesMmo;
+
+impl GateHash for AesMmo {
+    #[inline]
+    fn hash(&self, tweak: Block, x: Block) -> Block {
+        Aes128::new(tweak).encrypt(x) ^ x
+    }
+}
+
+/// SHA3-256 of the tweak followed by the label, truncated to a block.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha3;
+
+impl GateHash for Sha3 {
+    fn hash(&self, tweak: Block, x: Block) -> Block {
+        let mut hasher = Sha3_256::new();
+        hasher.input(tweak.as_ref());
+        hasher.input(x.as_ref());
+        let digest = hasher.result();
+        let mut out = [0u8; 16];
+        out.copy_from_slice(&digest[..16]);
+        Block::from(u128::from_le_bytes(out))
+    }
+}
+
+/// Borrow a hash, so that garblers and evaluators created along the way, e.g.
+/// for stacked branches, share the hash of their parent.
+impl<H: GateHash + ?Sized> GateHash for &H {
+    #[inline]
+    fn hash(&self, tweak: Block, x: Block) -> Block {
+        (**self).hash(tweak, x)
+    }
+}
@@ -5,9 +5,51 @@
 // See LICENSE for licensing information.
 
 //! `Informer` runs a fancy computation and learns information from it.
+//!
+//! It wraps another fancy object, typically a `Dummy`, which does the actual
+//! computation. Wrapping the garbler instead, e.g. `Informer<Garbler<...>>`,
+//! gathers the statistics of the garbling run itself.
+//!
+//! Besides its own counts, an `Informer` totals the cost of the computation
+//! under any `CostModel` registered with `with_cost_model`, such as
+//! `GarbledBytes` or a closure pricing each `Operation`:
+//!
+//! ```
+//! use fancy_garbling::{
+//!     dummy::Dummy,
+//!     informer::{GarbledBytes, Informer, Operation},
+//!     Fancy, FancyInput, GarbleMode, Modulus,
+//! };
+//!
+//! let q = Modulus::Zq { q: 2 };
+//! let mut f = Informer::new(Dummy::new())
+//!     .with_cost_model("bytes", GarbledBytes(GarbleMode::ThreeHalves))
+//!     .with_cost_model("cycles", |op: &Operation| match op {
+//!         Operation::Mul(..) | Operation::Proj(..) => 400.0,
+//!         _ => 10.0,
+//!     });
+//! let xs = f.encode_many(&[1, 1], &[q, q]).unwrap();
+//! let z = f.and(&xs[0], &xs[1]).unwrap();
+//! f.output(&z).unwrap();
+//! assert_eq!(f.stats().cost("bytes"), Some(27.0 + 32.0));
+//! assert_eq!(f.stats().cost("cycles"), Some(430.0));
+//! ```
 
-use crate::{fancy::{Fancy, FancyInput, FancyReveal, HasModulus}, Modulus};
-use std::collections::{HashMap, HashSet};
+use crate::{
+    errors::GarblerError,
+    fancy::{Fancy, FancyInput, FancyReveal, HasModulus},
+    garble::{three_halves, GarbleMode, Garbler},
+    hash::GateHash,
+    twopac::semihonest,
+    Modulus,
+    Wire,
+};
+use rand::{CryptoRng, RngCore};
+use scuttlebutt::AbstractChannel;
+use std::{
+    cmp::max,
+    collections::{HashMap, HashSet},
+};
 
 /// Implements `Fancy`. Used to learn information about a `Fancy` computation in
 /// a lightweight way.
@@ -15,6 +57,7 @@ pub struct Informer<F: Fancy> {
     /// The underlying fancy object.
     pub underlying: F,
     stats: InformerStats,
+    models: Vec<Box<dyn CostModel + Send>>,
 }
 
 /// The statistics revealed by the informer.
@@ -22,15 +65,259 @@ pub struct Informer<F: Fancy> {
 pub struct InformerStats {
     garbler_input_moduli: Vec<Modulus>,
     evaluator_input_moduli: Vec<Modulus>,
-    constants: HashSet<(u16, Modulus)>,
-    outputs: Vec<u16>,
-    nadds: usize,
-    nsubs: usize,
-    ncmuls: usize,
-    nmuls: usize,
-    nprojs: usize,
-    nciphertexts: usize,
+    // the number of evaluator inputs of every `receive_many`
+    evaluator_input_batches: Vec<usize>,
+    constants: HashSet<(u64, Modulus)>,
+    nconstant_labels: usize,
+    outputs: Vec<u32>,
+    gates: Vec<(Modulus, GateCounts)>,
+    // the number of non-free gates at each depth, from depth 1
+    levels: Vec<usize>,
+    output_depth: usize,
+    // the total of every cost model, by name
+    costs: Vec<(String, f64)>,
     moduli: HashMap<Modulus, usize>,
+    // the named wires of the circuit, by gate index
+    names: Vec<(usize, String)>,
+}
+
+/// An operation of a fancy computation, with the moduli of its wires, as
+/// priced by a `CostModel`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// An input of the garbler.
+    GarblerInput(Modulus),
+    /// An input of the evaluator.
+    EvaluatorInput(Modulus),
+    /// A constant.
+    Constant(Modulus),
+    /// An addition.
+    Add(Modulus),
+    /// A subtraction.
+    Sub(Modulus),
+    /// A multiplication by a constant.
+    Cmul(Modulus),
+    /// A multiplication, of the wire of larger order first.
+    Mul(Modulus, Modulus),
+    /// A projection from the first modulus to the second.
+    Proj(Modulus, Modulus),
+    /// An output.
+    Output(Modulus),
+}
+
+/// A price for every operation, totalled by an `Informer`.
+///
+/// It is implemented by closures from `&Operation` to `f64`.
+pub trait CostModel {
+    /// The cost of `op`.
+    fn cost(&self, op: &Operation) -> f64;
+}
+
+impl<M: Fn(&Operation) -> f64> CostModel for M {
+    fn cost(&self, op: &Operation) -> f64 {
+        self(op)
+    }
+}
+
+/// The bytes the streaming `Garbler` writes for every operation in a
+/// `GarbleMode`: one block per constant, the ciphertexts of every gate and the
+/// blocks decoding every output. Inputs are free, as in
+/// `Estimate::garbled_size`.
+///
+/// Three-halves AND gates cost their exact size, without the padding which
+/// the garbler adds before other blocks; an `Estimator` counts that as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GarbledBytes(pub GarbleMode);
+
+impl CostModel for GarbledBytes {
+    fn cost(&self, op: &Operation) -> f64 {
+        let blocks = match *op {
+            Operation::Constant(_) => 1,
+            Operation::Mul(Modulus::Zq { q }, Modulus::Zq { q: qb }) => match self.0 {
+                GarbleMode::PrivacyFree => qb as usize - 1,
+                GarbleMode::ThreeHalves if q == 2 => {
+                    return three_halves::GATE_BYTES as f64;
+                }
+                _ => q as usize + qb as usize - 2 + (q != qb) as usize,
+            },
+            Operation::Mul(q, qb) => {
+                q.order() as usize + qb.order() as usize - 2 + (q != qb) as usize
+            }
+            Operation::Proj(q, _) => q.order() as usize - 1,
+            Operation::Output(Modulus::ZqBig { .. }) => 1,
+            Operation::Output(q) => q.order() as usize,
+            _ => 0,
+        };
+        16.0 * blocks as f64
+    }
+}
+
+/// A wire of an `Informer`: a wire of the underlying computation, with its
+/// depth, the largest number of non-free gates on a path from an input or
+/// constant to it. Multiplications and projections are non-free, as they are
+/// the gates that garbling has to encrypt.
+///
+/// It dereferences to the underlying wire.
+#[derive(Clone, Debug)]
+pub struct InformerVal<W> {
+    wire: W,
+    depth: usize,
+}
+
+impl<W> InformerVal<W> {
+    /// Wrap the underlying `wire` as an input, of depth zero.
+    pub fn new(wire: W) -> Self {
+        InformerVal { wire, depth: 0 }
+    }
+
+    fn at(wire: W, depth: usize) -> Self {
+        InformerVal { wire, depth }
+    }
+
+    /// The number of non-free gates on the longest path to this wire.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The underlying wire.
+    pub fn into_wire(self) -> W {
+        self.wire
+    }
+}
+
+impl<W> std::ops::Deref for InformerVal<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.wire
+    }
+}
+
+impl<W: HasModulus> HasModulus for InformerVal<W> {
+    fn modulus(&self) -> Modulus {
+        self.wire.modulus()
+    }
+}
+
+/// Gate counts on the wires of one modulus, or on all wires.
+///
+/// Gates are counted under the modulus of their input, the larger one for a
+/// multiplication, since that is what their ciphertexts depend on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct GateCounts {
+    /// Number of additions.
+    pub adds: usize,
+    /// Number of subtractions.
+    pub subs: usize,
+    /// Number of scalar multiplications.
+    pub cmuls: usize,
+    /// Number of multiplications.
+    pub muls: usize,
+    /// Number of projections.
+    pub projs: usize,
+    /// Number of ciphertexts of the multiplications.
+    pub mul_ciphertexts: usize,
+    /// Number of ciphertexts of the projections.
+    pub proj_ciphertexts: usize,
+}
+
+impl GateCounts {
+    /// Number of ciphertexts of all gates.
+    pub fn ciphertexts(&self) -> usize {
+        self.mul_ciphertexts + self.proj_ciphertexts
+    }
+}
+
+impl std::ops::AddAssign for GateCounts {
+    fn add_assign(&mut self, other: GateCounts) {
+        self.adds += other.adds;
+        self.subs += other.subs;
+        self.cmuls += other.cmuls;
+        self.muls += other.muls;
+        self.projs += other.projs;
+        self.mul_ciphertexts += other.mul_ciphertexts;
+        self.proj_ciphertexts += other.proj_ciphertexts;
+    }
+}
+
+/// The statistics of an `InformerStats` as plain data, for benchmark scripts
+/// and the like. With the `serde1` feature it serializes, e.g. to JSON with
+/// `to_json`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct InformerSummary {
+    /// Moduli of the garbler inputs.
+    pub garbler_inputs: Vec<Modulus>,
+    /// Moduli of the evaluator inputs.
+    pub evaluator_inputs: Vec<Modulus>,
+    /// Number of distinct constants.
+    pub constants: usize,
+    /// Number of outputs.
+    pub outputs: usize,
+    /// Number of output ciphertexts.
+    pub output_ciphertexts: usize,
+    /// Gate counts on all wires.
+    pub gates: GateCounts,
+    /// Gate counts on the wires of each modulus, in order of first use.
+    pub gates_by_modulus: Vec<(Modulus, GateCounts)>,
+    /// Number of non-free gates at each depth, from depth 1.
+    pub gates_per_level: Vec<usize>,
+    /// Total costs under the cost models of the informer, by name.
+    pub costs: Vec<(String, f64)>,
+}
+
+#[cfg(feature = "serde1")]
+impl InformerSummary {
+    /// The summary as a JSON object.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("an informer summary is valid JSON")
+    }
+}
+
+/// How `twopac::semihonest` transfers the evaluator's inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputTransfer {
+    /// One 1-out-of-2 OT per input bit with ALSZ OT extension, the default.
+    Alsz,
+    /// One 1-out-of-N OT per `GF4` or `GF8` input with the KKRT OPRF, as with
+    /// `Garbler::with_n_ot`, and ALSZ for the others.
+    NOt,
+}
+
+/// The bytes a `twopac::semihonest` run sends in both directions, as estimated
+/// by `InformerStats::semihonest_estimate`.
+///
+/// This leaves out the one-time setup of the OT protocols, whose base OTs do
+/// not depend on the computation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommunicationEstimate {
+    /// Bytes of the block in which the garbler announces its `GarbleMode`.
+    pub mode_bytes: usize,
+    /// Bytes of the wire-labels of the garbler's inputs.
+    pub garbler_input_bytes: usize,
+    /// Bytes of the oblivious transfers of the evaluator's inputs, sent by
+    /// either party.
+    pub ot_bytes: usize,
+    /// Bytes of the wire-labels of constants.
+    pub constant_bytes: usize,
+    /// Bytes of garbled gates, as garbled in `GarbleMode::Standard`.
+    pub gate_bytes: usize,
+    /// Bytes needed to decode the outputs.
+    pub output_bytes: usize,
+}
+
+impl CommunicationEstimate {
+    /// Bytes sent in total.
+    pub fn total_bytes(&self) -> usize {
+        self.mode_bytes
+            + self.garbler_input_bytes
+            + self.ot_bytes
+            + self.constant_bytes
+            + self.gate_bytes
+            + self.output_bytes
+    }
 }
 
 impl InformerStats {
@@ -71,32 +358,138 @@ impl InformerStats {
 
     /// Number of additions in the fancy computation.
     pub fn num_adds(&self) -> usize {
-        self.nadds
+        self.gates().adds
     }
 
     /// Number of subtractions in the fancy computation.
     pub fn num_subs(&self) -> usize {
-        self.nsubs
+        self.gates().subs
     }
 
     /// Number of scalar multiplications in the fancy computation.
     pub fn num_cmuls(&self) -> usize {
-        self.ncmuls
+        self.gates().cmuls
     }
 
     /// Number of multiplications in the fancy computation.
     pub fn num_muls(&self) -> usize {
-        self.nmuls
+        self.gates().muls
     }
 
     /// Number of projections in the fancy computation.
     pub fn num_projs(&self) -> usize {
-        self.nprojs
+        self.gates().projs
     }
 
     /// Number of ciphertexts in the fancy computation.
     pub fn num_ciphertexts(&self) -> usize {
-        self.nciphertexts
+        self.gates().ciphertexts()
+    }
+
+    /// The depth of the computation: the largest number of non-free gates,
+    /// i.e. multiplications and projections, on any path from an input or a
+    /// constant. Gates at the same depth do not depend on each other, so this
+    /// bounds the latency of evaluation.
+    pub fn depth(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The largest depth of an output, which is `depth()` unless some gates
+    /// are not needed by any output.
+    pub fn output_depth(&self) -> usize {
+        self.output_depth
+    }
+
+    /// The number of non-free gates at each depth, from depth 1 to `depth()`.
+    pub fn gates_per_level(&self) -> &[usize] {
+        &self.levels
+    }
+
+    /// The total cost under the cost model registered as `name`, if any.
+    pub fn cost(&self, name: &str) -> Option<f64> {
+        self.costs.iter().find(|(n, _)| n == name).map(|&(_, c)| c)
+    }
+
+    /// The total costs under every cost model, in order of registration.
+    pub fn costs(&self) -> &[(String, f64)] {
+        &self.costs
+    }
+
+    /// Gate counts on all wires.
+    pub fn gates(&self) -> GateCounts {
+        let mut total = GateCounts::default();
+        for (_, counts) in self.gates.iter() {
+            total += *counts;
+        }
+        total
+    }
+
+    /// Gate counts on the wires of each modulus, in order of first use.
+    pub fn gates_by_modulus(&self) -> &[(Modulus, GateCounts)] {
+        &self.gates
+    }
+
+    /// The named wires of the circuit, by gate index, when the statistics come
+    /// from `Circuit::info`.
+    pub fn wire_names(&self) -> &[(usize, String)] {
+        &self.names
+    }
+
+    pub(crate) fn set_wire_names(&mut self, names: Vec<(usize, String)>) {
+        self.names = names;
+    }
+
+    /// The statistics as plain data.
+    pub fn summary(&self) -> InformerSummary {
+        InformerSummary {
+            garbler_inputs: self.garbler_input_moduli.clone(),
+            evaluator_inputs: self.evaluator_input_moduli.clone(),
+            constants: self.num_consts(),
+            outputs: self.num_outputs(),
+            output_ciphertexts: self.num_output_ciphertexts(),
+            gates: self.gates(),
+            gates_by_modulus: self.gates.clone(),
+            gates_per_level: self.levels.clone(),
+            costs: self.costs.clone(),
+        }
+    }
+
+    /// Estimate the bytes sent by running the computation with
+    /// `twopac::semihonest`, the evaluator's inputs being transferred with
+    /// `transfer`, one round of OT per call of `receive_many`.
+    pub fn semihonest_estimate(&self, transfer: InputTransfer) -> CommunicationEstimate {
+        let mut ot_bytes = 0;
+        let mut moduli = self.evaluator_input_moduli.iter();
+        for &n in self.evaluator_input_batches.iter() {
+            let (mut nbits, mut nqueries, mut nlabels) = (0, 0, 0);
+            for q in moduli.by_ref().take(n) {
+                match semihonest::n_ot_size(q) {
+                    Some(size) if transfer == InputTransfer::NOt => {
+                        nqueries += 1;
+                        nlabels += size as usize;
+                    }
+                    _ => nbits += semihonest::input_weights(q).len(),
+                }
+            }
+            // ALSZ: the evaluator sends a 128-bit row per OT, padded to a
+            // multiple of 8 OTs, and the garbler both messages of each
+            if nbits > 0 {
+                ot_bytes += 16 * ((nbits + 7) / 8 * 8) + 32 * nbits;
+            }
+            // KKRT: the evaluator sends two 512-bit rows per query, padded to a
+            // multiple of 16 queries, and the garbler every label masked
+            if nqueries > 0 {
+                ot_bytes += 128 * ((nqueries + 15) / 16 * 16) + 16 * nlabels;
+            }
+        }
+        CommunicationEstimate {
+            mode_bytes: 16,
+            garbler_input_bytes: 16 * self.num_garbler_inputs(),
+            ot_bytes,
+            constant_bytes: 16 * self.nconstant_labels,
+            gate_bytes: 16 * self.num_ciphertexts(),
+            output_bytes: 16 * self.num_output_ciphertexts(),
+        }
     }
 }
 
@@ -116,6 +509,7 @@ impl std::fmt::Display for InformerStats {
     ///   cmuls:                             0
     ///   projections:                       0
     ///   multiplications:                6800
+    ///   depth:                            40
     ///   ciphertexts:                   13600 // comms cost: 1.66 Mb (1700.00 Kb)
     ///   total comms cost:            1.75 Mb // 1700.00 Kb
     /// ```
@@ -135,7 +529,7 @@ impl std::fmt::Display for InformerStats {
         // dependent on the random one. This is for each input bit, so for
         // modulus `q` we need to do `log2(q)` OTs.
         let comm = self.evaluator_input_moduli.iter().fold(0.0, |acc, q| {
-            acc + (q.size() as f64).log2().ceil() * 384.0 / 1000.0
+            acc + (q.order() as f64).log2().ceil() * 384.0 / 1000.0
         });
 
         writeln!(
@@ -170,6 +564,7 @@ impl std::fmt::Display for InformerStats {
         writeln!(f, "  cmuls:              {:16}", self.num_cmuls())?;
         writeln!(f, "  projections:        {:16}", self.num_projs())?;
         writeln!(f, "  multiplications:    {:16}", self.num_muls())?;
+        writeln!(f, "  depth:              {:16}", self.depth())?;
         let cs = self.num_ciphertexts();
         let kb = cs as f64 * 128.0 / 1000.0;
         let mb = kb / 1000.0;
@@ -182,7 +577,16 @@ impl std::fmt::Display for InformerStats {
 
         let mb = total / 1000.0;
         writeln!(f, "  total communication:  {:11.2} Mb", mb)?;
+        for (name, cost) in self.costs.iter() {
+            writeln!(f, "  cost ({}): {:.2}", name, cost)?;
+        }
         writeln!(f, "  wire moduli: {:#?}", self.moduli)?;
+        if !self.names.is_empty() {
+            writeln!(f, "  named wires:")?;
+            for (ix, name) in self.names.iter() {
+                writeln!(f, "    {:16} {}", ix, name)?;
+            }
+        }
         Ok(())
     }
 }
@@ -195,16 +599,32 @@ impl<F: Fancy> Informer<F> {
             stats: InformerStats {
                 garbler_input_moduli: Vec::new(),
                 evaluator_input_moduli: Vec::new(),
+                evaluator_input_batches: Vec::new(),
                 constants: HashSet::new(),
+                nconstant_labels: 0,
                 outputs: Vec::new(),
-                nadds: 0,
-                nsubs: 0,
-                ncmuls: 0,
-                nmuls: 0,
-                nprojs: 0,
-                nciphertexts: 0,
+                gates: Vec::new(),
+                levels: Vec::new(),
+                output_depth: 0,
+                costs: Vec::new(),
                 moduli: HashMap::new(),
+                names: Vec::new(),
             },
+            models: Vec::new(),
+        }
+    }
+
+    /// Total the cost of every operation from now on under `model`, as
+    /// `InformerStats::cost(name)`.
+    pub fn with_cost_model<M: CostModel + Send + 'static>(mut self, name: &str, model: M) -> Self {
+        self.stats.costs.push((name.to_string(), 0.0));
+        self.models.push(Box::new(model));
+        self
+    }
+
+    fn charge(&mut self, op: Operation) {
+        for (model, (_, total)) in self.models.iter().zip(self.stats.costs.iter_mut()) {
+            *total += model.cost(&op);
         }
     }
 
@@ -217,20 +637,46 @@ impl<F: Fancy> Informer<F> {
         let entry = self.stats.moduli.entry(*q).or_insert(0);
         *entry += 1;
     }
+
+    // Count a non-free gate whose inputs have depth at most `depth`, returning
+    // the depth of its output.
+    fn level(&mut self, depth: usize) -> usize {
+        if self.stats.levels.len() == depth {
+            self.stats.levels.push(0);
+        }
+        self.stats.levels[depth] += 1;
+        depth + 1
+    }
+
+    // The gate counts of wires of modulus `q`.
+    fn counts(&mut self, q: &Modulus) -> &mut GateCounts {
+        let i = match self.stats.gates.iter().position(|(p, _)| p == q) {
+            Some(i) => i,
+            None => {
+                self.stats.gates.push((*q, GateCounts::default()));
+                self.stats.gates.len() - 1
+            }
+        };
+        &mut self.stats.gates[i].1
+    }
 }
 
 impl<F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Error>> FancyInput
     for Informer<F>
 {
-    type Item = <F as Fancy>::Item;
+    type Item = InformerVal<<F as Fancy>::Item>;
     type Error = <F as Fancy>::Error;
 
     fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Self::Item>, Self::Error> {
+        let wires = self.underlying.receive_many(moduli)?;
         self.stats
-            .garbler_input_moduli
+            .evaluator_input_moduli
             .extend(moduli.iter().cloned());
-        let values = vec![0; moduli.len()];
-        self.underlying.encode_many(&values, moduli)
+        self.stats.evaluator_input_batches.push(moduli.len());
+        for q in moduli.iter() {
+            self.charge(Operation::EvaluatorInput(*q));
+        }
+        Ok(wires.into_iter().map(InformerVal::new).collect())
     }
 
     fn encode_many(
@@ -238,21 +684,83 @@ impl<F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Erro
         values: &[u16],
         moduli: &[Modulus],
     ) -> Result<Vec<Self::Item>, Self::Error> {
+        let wires = self.underlying.encode_many(values, moduli)?;
         self.stats
             .garbler_input_moduli
             .extend(moduli.iter().cloned());
-        self.underlying.encode_many(values, moduli)
+        for q in moduli.iter() {
+            self.charge(Operation::GarblerInput(*q));
+        }
+        Ok(wires.into_iter().map(InformerVal::new).collect())
+    }
+}
+
+/// The wire-level API of the streaming `Garbler`, counting the inputs it
+/// encodes as garbler inputs.
+impl<C, RNG, H> Informer<Garbler<C, RNG, H>>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + RngCore,
+    H: GateHash,
+{
+    /// Encode a garbler input, see `Garbler::encode_wire`.
+    pub fn encode_wire(&mut self, val: u16, modulus: &Modulus) -> (InformerVal<Wire>, Wire) {
+        let (zero, enc) = self.underlying.encode_wire(val, modulus);
+        self.stats.garbler_input_moduli.push(*modulus);
+        self.charge(Operation::GarblerInput(*modulus));
+        (InformerVal::new(zero), enc)
+    }
+
+    /// Encode garbler inputs, see `Garbler::encode_many_wires`.
+    pub fn encode_many_wires(
+        &mut self,
+        vals: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<(Vec<InformerVal<Wire>>, Vec<Wire>), GarblerError> {
+        let (zeros, encs) = self.underlying.encode_many_wires(vals, moduli)?;
+        self.stats
+            .garbler_input_moduli
+            .extend(moduli.iter().cloned());
+        for q in moduli.iter() {
+            self.charge(Operation::GarblerInput(*q));
+        }
+        Ok((zeros.into_iter().map(InformerVal::new).collect(), encs))
+    }
+
+    /// Send a wire, see `Garbler::send_wire`.
+    pub fn send_wire(&mut self, wire: &Wire) -> Result<(), GarblerError> {
+        self.underlying.send_wire(wire)
+    }
+
+    /// Send wires, see `Garbler::send_wires`.
+    pub fn send_wires(&mut self, wires: &[Wire]) -> Result<(), GarblerError> {
+        self.underlying.send_wires(wires)
+    }
+
+    /// Write everything garbled so far, see `Garbler::flush`.
+    pub fn flush(&mut self) -> Result<(), GarblerError> {
+        self.underlying.flush()
+    }
+
+    /// Output a batch of wires and flush, see `Garbler::output_stream`.
+    pub fn output_stream(&mut self, xs: &[InformerVal<Wire>]) -> Result<(), GarblerError> {
+        for x in xs.iter() {
+            self.output(x)?;
+        }
+        self.flush()
     }
 }
 
 impl<F: Fancy> Fancy for Informer<F> {
-    type Item = F::Item;
+    type Item = InformerVal<F::Item>;
     type Error = F::Error;
 
     fn constant(&mut self, val: u16, q: &Modulus) -> Result<Self::Item, Self::Error> {
-        self.stats.constants.insert((val, *q));
+        self.stats.constants.insert((val as u64, *q));
+        self.stats.nconstant_labels += 1;
+        self.charge(Operation::Constant(*q));
         self.update_moduli(q);
-        self.underlying.constant(val, q)
+        self.underlying.constant(val, q).map(InformerVal::new)
     }
 
     // In general, for the below, we first check to see if the result succeeds before
@@ -260,39 +768,46 @@ impl<F: Fancy> Fancy for Informer<F> {
     // the moduli are equal.
 
     fn add(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
-        let result = self.underlying.add(x, y)?;
-        self.stats.nadds += 1;
+        let result = self.underlying.add(&x.wire, &y.wire)?;
+        self.counts(&x.modulus()).adds += 1;
+        self.charge(Operation::Add(x.modulus()));
         self.update_moduli(&x.modulus());
-        Ok(result)
+        Ok(InformerVal::at(result, max(x.depth, y.depth)))
     }
 
     fn sub(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
-        let result = self.underlying.sub(x, y)?;
-        self.stats.nsubs += 1;
+        let result = self.underlying.sub(&x.wire, &y.wire)?;
+        self.counts(&x.modulus()).subs += 1;
+        self.charge(Operation::Sub(x.modulus()));
         self.update_moduli(&x.modulus());
-        Ok(result)
+        Ok(InformerVal::at(result, max(x.depth, y.depth)))
     }
 
     fn cmul(&mut self, x: &Self::Item, y: u16) -> Result<Self::Item, Self::Error> {
-        let result = self.underlying.cmul(x, y)?;
-        self.stats.ncmuls += 1;
+        let result = self.underlying.cmul(&x.wire, y)?;
+        self.counts(&x.modulus()).cmuls += 1;
+        self.charge(Operation::Cmul(x.modulus()));
         self.update_moduli(&x.modulus());
-        Ok(result)
+        Ok(InformerVal::at(result, x.depth))
     }
 
     fn mul(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
-        if x.modulus().size() < y.modulus().size() {
+        if x.modulus().order() < y.modulus().order() {
             return self.mul(y, x);
         }
-        let result = self.underlying.mul(x, y)?;
-        self.stats.nmuls += 1;
-        self.stats.nciphertexts += x.modulus().size() as usize + y.modulus().size() as usize - 2;
+        let result = self.underlying.mul(&x.wire, &y.wire)?;
+        let mut nciphertexts = x.modulus().order() as usize + y.modulus().order() as usize - 2;
         if x.modulus() != y.modulus() {
             // there is an extra ciphertext to support nonequal inputs
-            self.stats.nciphertexts += 1;
+            nciphertexts += 1;
         }
+        let counts = self.counts(&x.modulus());
+        counts.muls += 1;
+        counts.mul_ciphertexts += nciphertexts;
+        self.charge(Operation::Mul(x.modulus(), y.modulus()));
         self.update_moduli(&x.modulus());
-        Ok(result)
+        let depth = self.level(max(x.depth, y.depth));
+        Ok(InformerVal::at(result, depth))
     }
 
     fn proj(
@@ -301,22 +816,230 @@ impl<F: Fancy> Fancy for Informer<F> {
         q: &Modulus,
         tt: Option<Vec<u16>>,
     ) -> Result<Self::Item, Self::Error> {
-        let result = self.underlying.proj(x, q, tt)?;
-        self.stats.nprojs += 1;
-        self.stats.nciphertexts += x.modulus().size() as usize - 1;
+        let result = self.underlying.proj(&x.wire, q, tt)?;
+        let counts = self.counts(&x.modulus());
+        counts.projs += 1;
+        counts.proj_ciphertexts += x.modulus().order() as usize - 1;
+        self.charge(Operation::Proj(x.modulus(), *q));
         self.update_moduli(q);
-        Ok(result)
+        let depth = self.level(x.depth);
+        Ok(InformerVal::at(result, depth))
     }
 
     fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
-        let result = self.underlying.output(x)?;
-        self.stats.outputs.push(x.modulus().size());
+        let result = self.underlying.output(&x.wire)?;
+        self.stats.outputs.push(x.modulus().order());
+        self.charge(Operation::Output(x.modulus()));
+        self.stats.output_depth = max(self.stats.output_depth, x.depth);
+        Ok(result)
+    }
+
+    fn constant_big(&mut self, val: u64, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        let result = self.underlying.constant_big(val, q)?;
+        self.stats.constants.insert((val, *q));
+        self.stats.nconstant_labels += 1;
+        self.charge(Operation::Constant(*q));
+        self.update_moduli(q);
+        Ok(InformerVal::new(result))
+    }
+
+    fn cmul_big(&mut self, x: &Self::Item, y: u64) -> Result<Self::Item, Self::Error> {
+        let result = self.underlying.cmul_big(&x.wire, y)?;
+        self.counts(&x.modulus()).cmuls += 1;
+        self.charge(Operation::Cmul(x.modulus()));
+        self.update_moduli(&x.modulus());
+        Ok(InformerVal::at(result, x.depth))
+    }
+
+    fn output_big(&mut self, x: &Self::Item) -> Result<Option<u64>, Self::Error> {
+        let result = self.underlying.output_big(&x.wire)?;
+        // outputs of `ZqBig` wires are decoded from a single block
+        let nciphertexts = match x.modulus() {
+            Modulus::ZqBig { .. } => 1,
+            q => q.order(),
+        };
+        self.stats.outputs.push(nciphertexts);
+        self.charge(Operation::Output(x.modulus()));
+        self.stats.output_depth = max(self.stats.output_depth, x.depth);
         Ok(result)
     }
 }
 
 impl<F: Fancy + FancyReveal> FancyReveal for Informer<F> {
     fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
-        self.underlying.reveal(x)
+        self.underlying.reveal(&x.wire)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dummy::{Dummy, DummyVal},
+        garble::Evaluator,
+    };
+    use scuttlebutt::{unix_channel_pair, AesRng};
+
+    #[test]
+    fn gates_by_modulus() {
+        let (q2, g4) = (Modulus::Zq { q: 2 }, Modulus::GF4 { p: 0b10011 });
+        let mut f = Informer::new(Dummy::new());
+        let x = InformerVal::new(DummyVal::new(1, q2));
+        let u = InformerVal::new(DummyVal::new(3, g4));
+        let a = f.and(&x, &x).unwrap();
+        f.xor(&a, &x).unwrap();
+        f.mul(&u, &u).unwrap();
+        f.cmul(&u, 2).unwrap();
+        f.proj(&u, &q2, Some(vec![0; 16])).unwrap();
+        f.output(&a).unwrap();
+
+        let stats = f.stats();
+        let bits = GateCounts {
+            adds: 1,
+            muls: 1,
+            mul_ciphertexts: 2,
+            ..GateCounts::default()
+        };
+        let field = GateCounts {
+            cmuls: 1,
+            muls: 1,
+            projs: 1,
+            mul_ciphertexts: 30,
+            proj_ciphertexts: 15,
+            ..GateCounts::default()
+        };
+        assert_eq!(stats.gates_by_modulus(), &[(q2, bits), (g4, field)]);
+        assert_eq!(stats.num_ciphertexts(), 47);
+        assert_eq!(stats.num_muls(), 2);
+
+        let summary = stats.summary();
+        assert_eq!(summary.gates.ciphertexts(), 47);
+        assert_eq!(summary.outputs, 1);
+        assert_eq!(summary.output_ciphertexts, 2);
+    }
+
+    #[test]
+    fn depth() {
+        let q = Modulus::Zq { q: 2 };
+        let mut f = Informer::new(Dummy::new());
+        let xs = (0..8)
+            .map(|_| InformerVal::new(DummyVal::new(1, q)))
+            .collect::<Vec<_>>();
+        // a chain of ANDs, with free gates in between
+        let mut chain = xs[0].clone();
+        for x in xs[1..].iter() {
+            let y = f.xor(&chain, x).unwrap();
+            chain = f.and(&y, x).unwrap();
+        }
+        assert_eq!(chain.depth(), 7);
+        // a balanced tree of ANDs
+        let mut level = xs.clone();
+        while level.len() > 1 {
+            level = level.chunks(2).map(|p| f.and(&p[0], &p[1]).unwrap()).collect();
+        }
+        assert_eq!(level[0].depth(), 3);
+        let c = f.constant(1, &q).unwrap();
+        let z = f.proj(&c, &q, Some(vec![1, 0])).unwrap();
+        let z = f.xor(&z, &level[0]).unwrap();
+        assert_eq!(z.depth(), 3);
+        f.output(&z).unwrap();
+
+        let stats = f.stats();
+        assert_eq!(stats.depth(), 7);
+        assert_eq!(stats.output_depth(), 3);
+        assert_eq!(stats.gates_per_level(), &[6, 3, 2, 1, 1, 1, 1]);
+        assert_eq!(stats.summary().gates_per_level.iter().sum::<usize>(), 15);
+    }
+
+    // x * y + c, with `y` projected to `q`
+    fn streamed<F: Fancy>(f: &mut F, x: &F::Item, y: &F::Item) -> Result<F::Item, F::Error> {
+        let q = x.modulus();
+        let y = f.proj(y, &q, Some(vec![1, 2, 0]))?;
+        let z = f.mul(x, &y)?;
+        let c = f.constant(3, &q)?;
+        f.add(&z, &c)
+    }
+
+    #[test]
+    fn around_garbler() {
+        let (q, p) = (Modulus::Zq { q: 5 }, Modulus::Zq { q: 3 });
+        let mut d = Informer::new(Dummy::new());
+        let xs = d.encode_many(&[4, 2], &[q, p]).unwrap();
+        let z = streamed(&mut d, &xs[0], &xs[1]).unwrap();
+        d.output(&z).unwrap();
+
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Informer::new(Garbler::new(sender, AesRng::new()));
+            let (zeros, encs) = gb.encode_many_wires(&[4, 2], &[q, p]).unwrap();
+            gb.send_wires(&encs).unwrap();
+            let z = streamed(&mut gb, &zeros[0], &zeros[1]).unwrap();
+            gb.output_stream(&[z]).unwrap();
+            gb.stats().summary()
+        });
+        let mut ev = Evaluator::new(receiver);
+        let xs = [ev.read_wire(&q).unwrap(), ev.read_wire(&p).unwrap()];
+        let z = streamed(&mut ev, &xs[0], &xs[1]).unwrap();
+        // y = 2 projects to 0
+        assert_eq!(ev.output(&z).unwrap(), Some(3));
+        let summary = handle.join().unwrap();
+        assert_eq!(summary, d.stats().summary());
+        assert_eq!(summary.gates.ciphertexts(), 2 + 8);
+    }
+
+    #[test]
+    fn cost_models() {
+        use crate::estimate::estimate;
+
+        fn gates<F>(f: &mut F, field: bool) -> Result<(), <F as Fancy>::Error>
+        where
+            F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Error>,
+        {
+            let (q2, q5, g4) = (Modulus::Zq { q: 2 }, Modulus::Zq { q: 5 }, Modulus::X4_X_1);
+            let xs = f.encode_many(&[1, 3, 1], &[q2, q5, q2])?;
+            let c = f.constant(2, &q5)?;
+            let y = f.mul(&xs[1], &c)?;
+            let y = f.mul(&y, &xs[0])?;
+            let z = f.and(&xs[0], &xs[2])?;
+            let p = f.proj(&z, &q5, Some(vec![4, 1]))?;
+            f.output(&y)?;
+            f.output(&p)?;
+            if field {
+                let u = f.constant(7, &g4)?;
+                let v = f.mul(&u, &u)?;
+                let w = f.proj(&v, &q2, Some(vec![1; 16]))?;
+                f.output(&w)?;
+            }
+            Ok(())
+        }
+
+        for &(mode, field) in &[(GarbleMode::Standard, true), (GarbleMode::PrivacyFree, false)] {
+            let mut f = Informer::new(Dummy::new())
+                .with_cost_model("bytes", GarbledBytes(mode))
+                .with_cost_model("muls", |op: &Operation| match op {
+                    Operation::Mul(..) => 1.0,
+                    _ => 0.0,
+                });
+            gates(&mut f, field).unwrap();
+            let stats = f.stats();
+            let expected = estimate(mode, |f| gates(f, field)).unwrap();
+            assert_eq!(stats.cost("bytes"), Some(expected.garbled_size() as f64));
+            assert_eq!(stats.cost("muls"), Some(stats.num_muls() as f64));
+            assert_eq!(stats.cost("cycles"), None);
+            assert_eq!(stats.summary().costs, stats.costs());
+        }
+        assert!(Informer::new(Dummy::new()).stats().costs().is_empty());
+    }
+
+    #[cfg(feature = "serde1")]
+    #[test]
+    fn summary_json() {
+        let mut f = Informer::new(Dummy::new());
+        let x = InformerVal::new(DummyVal::new(1, Modulus::Zq { q: 3 }));
+        f.proj(&x, &Modulus::Zq { q: 2 }, Some(vec![0, 1, 1])).unwrap();
+        let summary = f.stats().summary();
+        let json = summary.to_json();
+        assert!(json.contains("\"proj_ciphertexts\":2"));
+        assert_eq!(serde_json::from_str::<InformerSummary>(&json).unwrap(), summary);
     }
 }
@@ -5,6 +5,11 @@
 // See LICENSE for licensing information.
 
 //! `fancy-garbling` provides boolean and arithmetic garbling capabilities.
+//!
+//! Without the default `std` feature the crate is `no_std`, needing only
+//! `alloc`, and provides wires, circuits, the `Fancy` traits and gadgets, and
+//! plaintext evaluation with `Dummy`. Garbling, evaluating garbled circuits,
+//! and the protocols, which run over channels, need `std`.
 
 #![deny(clippy::all)]
 #![allow(
@@ -15,6 +20,7 @@
     clippy::needless_range_loop
 )]
 #![allow(non_snake_case)]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(feature = "nightly", feature(test))]
 #![cfg_attr(feature = "nightly", feature(stdsimd))]
 #![cfg_attr(feature = "nightly", feature(external_doc))]
@@ -22,18 +28,36 @@
 #![cfg_attr(feature = "nightly", deny(missing_docs))]
 
 extern crate core;
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
 
 pub mod circuit;
+#[cfg(feature = "std")]
 pub mod classic;
 pub mod depth_informer;
 pub mod dummy;
 pub mod errors;
+#[cfg(feature = "std")]
+pub mod estimate;
 mod fancy;
+#[cfg(feature = "std")]
 mod garble;
+pub mod hash;
+#[cfg(feature = "std")]
 pub mod informer;
+#[cfg(feature = "std")]
+pub mod nparty;
+#[cfg(feature = "std")]
 mod parser;
+#[cfg(feature = "std")]
+pub mod primitives;
+pub mod recorder;
+pub mod role;
+#[cfg(feature = "std")]
 pub mod twopac;
 pub mod util;
 mod wire;
 
-pub use crate::{errors::FancyError, fancy::*, garble::*, wire::*};
+pub use crate::{errors::FancyError, fancy::*, wire::*};
+#[cfg(feature = "std")]
+pub use crate::garble::*;
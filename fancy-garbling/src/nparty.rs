@@ -0,0 +1,1157 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Multi-party garbling in the style of Beaver–Micali–Rogaway.
+//!
+//! Every party holds its own `delta` per modulus and its own share of every
+//! wire label, and every wire carries a mask which is additively shared among
+//! the parties. The evaluator, party `EVALUATOR`, learns the label shares of all
+//! parties for the *masked* value of each wire, so the actual values stay hidden
+//! unless all parties collude. Additions, constant multiplications and
+//! constants are free, as in the two-party garbler.
+//!
+//! The garbled table of a `proj` or `mul` gate has a row per masked input value,
+//! holding every party's label share of the masked output, and is encrypted
+//! under every party's input label shares. Every party picks its mask share and
+//! zero label of each wire as soon as the wire is made, so the tables depend
+//! neither on each other nor on the inputs. The parties therefore only record
+//! gates as they are called, and garble all pending tables at once when an
+//! output is requested: each party encrypts its shares of every row under its
+//! own label shares, and a single joint computation over all gates produces the
+//! shares of the masked tables. In it, the tables of the gate functions are
+//! rotated by each party's mask shares in turn using 1-out-of-N OT, and the
+//! label shares of the masked outputs are computed with another 1-out-of-N OT
+//! per pair of parties. The number of rounds depends on the number of parties
+//! only, not on the size or depth of the circuit. The evaluator then receives
+//! the tables and evaluates them without further interaction.
+//!
+//! This is secure against semi-honest adversaries corrupting all but one party.
+//! A table's size is the number of rows times the number of parties, so this
+//! suits circuits with few nonlinear gates over small moduli such as `GF4` or
+//! small `Zq`. `ZqBig` and `GF16` wires are not supported. Every party learns
+//! every output, at a round per output; outputs requested in the middle of a
+//! computation split its garbling into several such phases.
+
+use crate::{
+    dummy::{Dummy, DummyVal},
+    errors::{FancyError, TwopacError},
+    twopac::semihonest::n_ot_pad,
+    Fancy,
+    FancyInput,
+    HasModulus,
+    Modulus,
+    Wire,
+};
+use ocelot::oprf::{KkrtReceiver, KkrtSender, Receiver as OprfReceiver, Sender as OprfSender};
+use rand::{CryptoRng, Rng};
+use scuttlebutt::{AbstractChannel, Block, AES_HASH};
+use std::collections::HashMap;
+
+/// The party which evaluates the garbled circuit.
+pub const EVALUATOR: usize = 0;
+
+/// A wire shared among the parties.
+#[derive(Clone, Debug)]
+pub struct NWire {
+    zero: Wire,
+    mask: u16,
+    // The index of the wire among all wires of the party.
+    ix: usize,
+}
+
+impl NWire {
+    /// Our share of the label of the zero value masked by `mask`.
+    pub fn zero(&self) -> &Wire {
+        &self.zero
+    }
+
+    /// Our share of the mask of this wire.
+    pub fn mask(&self) -> u16 {
+        self.mask
+    }
+}
+
+impl HasModulus for NWire {
+    fn modulus(&self) -> Modulus {
+        self.zero.modulus()
+    }
+}
+
+// A `proj` or `mul` gate whose table is not garbled yet.
+struct PendingGate {
+    gate: u64,
+    // Our zero label and mask share of every input.
+    inputs: Vec<(Wire, u16)>,
+    // The gate's function on every row of input values.
+    values: Vec<u16>,
+    // Our zero label and mask share of the output.
+    zero: Wire,
+    mask: u16,
+}
+
+impl PendingGate {
+    fn rows(&self) -> Rows {
+        Rows::new(
+            &self
+                .inputs
+                .iter()
+                .map(|(zero, _)| zero.modulus())
+                .collect::<Vec<Modulus>>(),
+        )
+    }
+
+    fn modulus(&self) -> Modulus {
+        self.zero.modulus()
+    }
+}
+
+// How the evaluator computes the label shares of a wire.
+enum Node {
+    Labels(Vec<Wire>),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Cmul(usize, u16),
+    // The output of garbled table `table`, given the indices and our zero
+    // labels of its inputs.
+    Table {
+        gate: u64,
+        table: usize,
+        inputs: Vec<(usize, Wire)>,
+    },
+}
+
+struct Peer<C> {
+    channel: C,
+    sender: KkrtSender,
+    receiver: KkrtReceiver,
+}
+
+/// A party of multi-party garbling.
+pub struct Party<C, RNG> {
+    id: usize,
+    peers: Vec<Option<Peer<C>>>,
+    deltas: HashMap<Modulus, Wire>,
+    rng: RNG,
+    current_gate: u64,
+    nwires: usize,
+    pending: Vec<PendingGate>,
+    // At the evaluator: how to compute the label shares of every wire, the
+    // label shares of the wires evaluated so far, and the garbled tables.
+    nodes: Vec<Node>,
+    labels: Vec<Vec<Wire>>,
+    tables: Vec<Vec<Vec<Wire>>>,
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng> Party<C, RNG> {
+    /// Make party `id`, given a channel to every other party, indexed by party
+    /// with `None` at `id`. Runs the OPRF setup with every other party.
+    pub fn new(id: usize, channels: Vec<Option<C>>, mut rng: RNG) -> Result<Self, TwopacError> {
+        if channels.len() < 2 || id >= channels.len() {
+            return Err(FancyError::InvalidArg(format!(
+                "party {} of {} parties",
+                id,
+                channels.len()
+            ))
+            .into());
+        }
+        if channels
+            .iter()
+            .enumerate()
+            .any(|(p, c)| c.is_some() == (p == id))
+        {
+            return Err(
+                FancyError::InvalidArg("need a channel to every other party".to_string()).into(),
+            );
+        }
+        let n = channels.len();
+        let mut channels = channels;
+        let mut peers = (0..n).map(|_| None).collect::<Vec<Option<Peer<C>>>>();
+        for p in Self::schedule(id, n) {
+            let mut channel = channels[p].take().unwrap();
+            // The lower party of each pair sets up its sender first.
+            let (sender, receiver) = if id < p {
+                let sender = KkrtSender::init(&mut channel, &mut rng)?;
+                (sender, KkrtReceiver::init(&mut channel, &mut rng)?)
+            } else {
+                let receiver = KkrtReceiver::init(&mut channel, &mut rng)?;
+                (KkrtSender::init(&mut channel, &mut rng)?, receiver)
+            };
+            peers[p] = Some(Peer {
+                channel,
+                sender,
+                receiver,
+            });
+        }
+        Ok(Party {
+            id,
+            peers,
+            deltas: HashMap::new(),
+            rng,
+            current_gate: 0,
+            nwires: 0,
+            pending: Vec::new(),
+            nodes: Vec::new(),
+            labels: Vec::new(),
+            tables: Vec::new(),
+        })
+    }
+
+    /// Our party index.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The number of parties.
+    pub fn nparties(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// A view of this party whose `FancyInput::receive_many` receives the
+    /// inputs of `owner`. With more than two parties, `Party::receive_many`
+    /// cannot tell whose inputs to receive.
+    pub fn inputs_from(&mut self, owner: usize) -> InputsFrom<'_, C, RNG> {
+        InputsFrom { party: self, owner }
+    }
+
+    // The other parties in an order in which pairwise steps never deadlock:
+    // every party handles its pairs in lexicographic order.
+    fn schedule(id: usize, n: usize) -> Vec<usize> {
+        let mut others = (0..n).filter(|&p| p != id).collect::<Vec<usize>>();
+        others.sort_by_key(|&p| (p.min(id), p.max(id)));
+        others
+    }
+
+    fn others(&self) -> Vec<usize> {
+        Self::schedule(self.id, self.nparties())
+    }
+
+    fn channel(&mut self, p: usize) -> &mut C {
+        &mut self.peers[p].as_mut().unwrap().channel
+    }
+
+    fn delta(&mut self, q: &Modulus) -> Wire {
+        let rng = &mut self.rng;
+        self.deltas
+            .entry(*q)
+            .or_insert_with(|| Wire::rand_delta(rng, q))
+            .clone()
+    }
+
+    fn rand_value(&mut self, q: &Modulus) -> u16 {
+        self.rng.gen_range(0, q.size())
+    }
+
+    // The masked value of wire `ix` with our zero label `zero`, once evaluated
+    // at the evaluator. Our delta has color one, so the color of our label
+    // share minus our zero label is the masked value.
+    fn masked(&self, ix: usize, zero: &Wire) -> u16 {
+        self.labels[ix][self.id].minus(zero).color()
+    }
+
+    // A new wire, which the evaluator computes by `node`.
+    fn wire(&mut self, zero: Wire, mask: u16, node: Node) -> NWire {
+        if self.id == EVALUATOR {
+            self.nodes.push(node);
+        }
+        let ix = self.nwires;
+        self.nwires += 1;
+        NWire { zero, mask, ix }
+    }
+
+    // Send a 1-out-of-N OT for each entry of `messages`, of which `p` learns the
+    // one it chooses.
+    fn n_ot_send(&mut self, p: usize, messages: &[Vec<Block>]) -> Result<(), TwopacError> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        let rng = &mut self.rng;
+        let peer = self.peers[p].as_mut().unwrap();
+        let seeds = peer.sender.send(&mut peer.channel, messages.len(), rng)?;
+        for (seed, ms) in seeds.into_iter().zip(messages.iter()) {
+            for (j, m) in ms.iter().enumerate() {
+                let pad = n_ot_pad(peer.sender.compute(seed, Block::from(j as u128)));
+                peer.channel.write_block(&(*m ^ pad))?;
+            }
+        }
+        peer.channel.flush()?;
+        Ok(())
+    }
+
+    // Receive the `choices[i]`th of the `sizes[i]` messages of each OT sent by
+    // `p`.
+    fn n_ot_receive(
+        &mut self,
+        p: usize,
+        choices: &[u16],
+        sizes: &[usize],
+    ) -> Result<Vec<Block>, TwopacError> {
+        if choices.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rng = &mut self.rng;
+        let peer = self.peers[p].as_mut().unwrap();
+        let inputs = choices
+            .iter()
+            .map(|c| Block::from(u128::from(*c)))
+            .collect::<Vec<Block>>();
+        let outputs = peer.receiver.receive(&mut peer.channel, &inputs, rng)?;
+        let mut messages = Vec::with_capacity(choices.len());
+        for ((c, n), output) in choices.iter().zip(sizes.iter()).zip(outputs) {
+            let cts = peer.channel.read_blocks(*n)?;
+            messages.push(cts[*c as usize] ^ n_ot_pad(output));
+        }
+        Ok(messages)
+    }
+
+    fn input(
+        &mut self,
+        owner: usize,
+        values: Option<&[u16]>,
+        moduli: &[Modulus],
+    ) -> Result<Vec<NWire>, TwopacError> {
+        for q in moduli.iter() {
+            check_modulus(q)?;
+        }
+        let masks = moduli
+            .iter()
+            .map(|q| self.rand_value(q))
+            .collect::<Vec<u16>>();
+
+        // The owner learns the masks of its inputs and publishes the masked
+        // values.
+        let masked = match values {
+            Some(values) => {
+                let mut masked = values
+                    .iter()
+                    .zip(masks.iter())
+                    .zip(moduli.iter())
+                    .map(|((x, m), q)| add(*x, *m, q))
+                    .collect::<Vec<u16>>();
+                for p in self.others() {
+                    for (c, q) in masked.iter_mut().zip(moduli.iter()) {
+                        let m = self.channel(p).read_u16()?;
+                        *c = add(*c, m, q);
+                    }
+                }
+                for p in self.others() {
+                    for c in masked.iter() {
+                        self.channel(p).write_u16(*c)?;
+                    }
+                    self.channel(p).flush()?;
+                }
+                masked
+            }
+            None => {
+                let channel = self.channel(owner);
+                for m in masks.iter() {
+                    channel.write_u16(*m)?;
+                }
+                channel.flush()?;
+                (0..moduli.len())
+                    .map(|_| channel.read_u16())
+                    .collect::<Result<Vec<u16>, std::io::Error>>()?
+            }
+        };
+
+        let mut wires = Vec::with_capacity(moduli.len());
+        for ((q, mask), c) in moduli.iter().zip(masks).zip(masked.iter()) {
+            let zero = Wire::rand(&mut self.rng, q);
+            let label = zero.plus(&self.delta(q).cmul(*c));
+            wires.push((zero, mask, label));
+        }
+        self.collect_labels(wires)
+    }
+
+    // Send our label shares to the evaluator, which collects all of them.
+    fn collect_labels(&mut self, wires: Vec<(Wire, u16, Wire)>) -> Result<Vec<NWire>, TwopacError> {
+        if self.id != EVALUATOR {
+            let channel = self.channel(EVALUATOR);
+            for (_, _, label) in wires.iter() {
+                channel.write_block(&label.as_block())?;
+            }
+            channel.flush()?;
+            return Ok(wires
+                .into_iter()
+                .map(|(zero, mask, _)| self.wire(zero, mask, Node::Labels(Vec::new())))
+                .collect());
+        }
+        let mut labels = wires
+            .iter()
+            .map(|(_, _, label)| {
+                let mut ls = vec![Wire::zero(&label.modulus()); self.nparties()];
+                ls[self.id] = label.clone();
+                ls
+            })
+            .collect::<Vec<Vec<Wire>>>();
+        for p in self.others() {
+            for ((_, _, label), ls) in wires.iter().zip(labels.iter_mut()) {
+                let block = self.channel(p).read_block()?;
+                ls[p] = Wire::from_block(block, &label.modulus());
+            }
+        }
+        Ok(wires
+            .into_iter()
+            .zip(labels)
+            .map(|((zero, mask, _), labels)| self.wire(zero, mask, Node::Labels(labels)))
+            .collect())
+    }
+
+    // Add a gate computing `f` on the values of `inputs`. Its table is garbled
+    // along with those of all other pending gates by `garble`.
+    fn table_gate<F>(&mut self, inputs: &[&NWire], r: &Modulus, f: F) -> Result<NWire, TwopacError>
+    where
+        F: Fn(&[u16]) -> u16,
+    {
+        check_modulus(r)?;
+        let qs = inputs.iter().map(|x| x.modulus()).collect::<Vec<Modulus>>();
+        for q in qs.iter() {
+            check_modulus(q)?;
+        }
+        let rows = Rows::new(&qs);
+        let gate = self.current_gate;
+        self.current_gate += 1;
+
+        let zero = Wire::rand(&mut self.rng, r);
+        let mask = self.rand_value(r);
+        // The evaluator appends the tables in the order of the pending gates.
+        let node = Node::Table {
+            gate,
+            table: self.tables.len() + self.pending.len(),
+            inputs: inputs.iter().map(|x| (x.ix, x.zero.clone())).collect(),
+        };
+        self.pending.push(PendingGate {
+            gate,
+            inputs: inputs.iter().map(|x| (x.zero.clone(), x.mask)).collect(),
+            values: (0..rows.len()).map(|row| f(&rows.values(row))).collect(),
+            zero: zero.clone(),
+            mask,
+        });
+        Ok(self.wire(zero, mask, node))
+    }
+
+    // Jointly garble the tables of all pending gates and send them to the
+    // evaluator. Every step is batched over all gates, so this takes the same
+    // number of rounds however many gates there are.
+    fn garble(&mut self) -> Result<(), TwopacError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let gates = std::mem::take(&mut self.pending);
+        let rows = gates.iter().map(PendingGate::rows).collect::<Vec<Rows>>();
+        let n = self.nparties();
+
+        // Shares of the table of each gate's function on the masked inputs: the
+        // evaluator starts with the tables rotated by its own masks, and each
+        // other party rotates the shared tables by its masks in turn.
+        let mut shares = gates
+            .iter()
+            .zip(rows.iter())
+            .map(|(g, rows)| {
+                if self.id != EVALUATOR {
+                    return vec![0; rows.len()];
+                }
+                (0..rows.len())
+                    .map(|row| {
+                        let vals = rows
+                            .values(row)
+                            .iter()
+                            .zip(g.inputs.iter())
+                            .map(|(c, (zero, mask))| sub(*c, *mask, &zero.modulus()))
+                            .collect::<Vec<u16>>();
+                        g.values[rows.index(&vals)]
+                    })
+                    .collect()
+            })
+            .collect::<Vec<Vec<u16>>>();
+        let arity = gates.iter().map(|g| g.inputs.len()).max().unwrap_or(0);
+        for k in (0..n).filter(|&k| k != EVALUATOR) {
+            for axis in 0..arity {
+                let ixs = (0..gates.len())
+                    .filter(|&i| axis < gates[i].inputs.len())
+                    .collect::<Vec<usize>>();
+                if self.id == k {
+                    let choices = ixs
+                        .iter()
+                        .map(|&i| {
+                            let (zero, mask) = &gates[i].inputs[axis];
+                            (0..rows[i].len())
+                                .map(|row| sub(rows[i].values(row)[axis], *mask, &zero.modulus()))
+                                .collect::<Vec<u16>>()
+                        })
+                        .collect::<Vec<Vec<u16>>>();
+                    let sizes = ixs
+                        .iter()
+                        .zip(choices.iter())
+                        .flat_map(|(&i, cs)| vec![rows[i].sizes[axis]; cs.len()])
+                        .collect::<Vec<usize>>();
+                    let mut rotated = ixs
+                        .iter()
+                        .zip(choices.iter())
+                        .map(|(&i, cs)| {
+                            cs.iter()
+                                .enumerate()
+                                .map(|(row, c)| shares[i][rows[i].with(row, axis, *c)])
+                                .collect::<Vec<u16>>()
+                        })
+                        .collect::<Vec<Vec<u16>>>();
+                    let choices = choices.concat();
+                    for p in self.others() {
+                        let mut got = self.n_ot_receive(p, &choices, &sizes)?.into_iter();
+                        for (&i, ss) in ixs.iter().zip(rotated.iter_mut()) {
+                            let r = gates[i].modulus();
+                            for (s, b) in ss.iter_mut().zip(&mut got) {
+                                *s = add(*s, u128::from(b) as u16, &r);
+                            }
+                        }
+                    }
+                    for (&i, ss) in ixs.iter().zip(rotated) {
+                        shares[i] = ss;
+                    }
+                } else {
+                    let mut messages = Vec::new();
+                    for &i in ixs.iter() {
+                        let r = gates[i].modulus();
+                        let mut rotated = Vec::with_capacity(rows[i].len());
+                        for row in 0..rows[i].len() {
+                            let s = self.rand_value(&r);
+                            messages.push(
+                                (0..rows[i].sizes[axis])
+                                    .map(|u| {
+                                        let v = shares[i][rows[i].with(row, axis, u as u16)];
+                                        Block::from(u128::from(add(v, s, &r)))
+                                    })
+                                    .collect::<Vec<Block>>(),
+                            );
+                            rotated.push(sub(0, s, &r));
+                        }
+                        shares[i] = rotated;
+                    }
+                    self.n_ot_send(k, &messages)?;
+                }
+            }
+        }
+
+        // Shares of the masked outputs.
+        for (g, ss) in gates.iter().zip(shares.iter_mut()) {
+            for s in ss.iter_mut() {
+                *s = add(*s, g.mask, &g.modulus());
+            }
+        }
+
+        // Shares of every party's output label shares, `zero + masked * delta`.
+        let mut plain = Vec::with_capacity(gates.len());
+        for (g, ss) in gates.iter().zip(shares.iter()) {
+            let r = g.modulus();
+            let delta = self.delta(&r);
+            plain.push(
+                ss.iter()
+                    .map(|s| {
+                        let mut ps = vec![Wire::zero(&r); n];
+                        ps[self.id] = g.zero.plus(&delta.cmul(*s));
+                        ps
+                    })
+                    .collect::<Vec<Vec<Wire>>>(),
+            );
+        }
+        let sizes = gates
+            .iter()
+            .zip(shares.iter())
+            .flat_map(|(g, ss)| vec![g.modulus().size() as usize; ss.len()])
+            .collect::<Vec<usize>>();
+        let shares = shares.concat();
+        for p in self.others() {
+            let order = if self.id < p {
+                [true, false]
+            } else {
+                [false, true]
+            };
+            for &sending in order.iter() {
+                if sending {
+                    let mut messages = Vec::with_capacity(shares.len());
+                    for (g, table) in gates.iter().zip(plain.iter_mut()) {
+                        let r = g.modulus();
+                        let delta = self.delta(&r);
+                        for ps in table.iter_mut() {
+                            let mask = Wire::rand(&mut self.rng, &r);
+                            messages.push(
+                                (0..r.size())
+                                    .map(|u| delta.cmul(u).plus(&mask).as_block())
+                                    .collect::<Vec<Block>>(),
+                            );
+                            ps[self.id].minus_eq(&mask);
+                        }
+                    }
+                    self.n_ot_send(p, &messages)?;
+                } else {
+                    let mut got = self.n_ot_receive(p, &shares, &sizes)?.into_iter();
+                    for (g, table) in gates.iter().zip(plain.iter_mut()) {
+                        for (ps, b) in table.iter_mut().zip(&mut got) {
+                            ps[p].plus_eq(&Wire::from_block(b, &g.modulus()));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Encrypt our shares of each row under our input label shares for it.
+        for ((g, rows), table) in gates.iter().zip(rows.iter()).zip(plain.iter_mut()) {
+            let r = g.modulus();
+            let deltas = g
+                .inputs
+                .iter()
+                .map(|(zero, _)| self.delta(&zero.modulus()))
+                .collect::<Vec<Wire>>();
+            for (row, ps) in table.iter_mut().enumerate() {
+                let labels = rows
+                    .values(row)
+                    .iter()
+                    .zip(g.inputs.iter())
+                    .zip(deltas.iter())
+                    .map(|((c, (zero, _)), delta)| zero.plus(&delta.cmul(*c)))
+                    .collect::<Vec<Wire>>();
+                for (i, p) in ps.iter_mut().enumerate() {
+                    p.plus_eq(&pad(g.gate, row, i, &labels, &r));
+                }
+            }
+        }
+
+        if self.id != EVALUATOR {
+            let channel = self.channel(EVALUATOR);
+            for ps in plain.iter().flatten() {
+                for p in ps.iter() {
+                    channel.write_block(&p.as_block())?;
+                }
+            }
+            channel.flush()?;
+            return Ok(());
+        }
+        for p in self.others() {
+            for ps in plain.iter_mut().flatten() {
+                for c in ps.iter_mut() {
+                    let block = self.channel(p).read_block()?;
+                    c.plus_eq(&Wire::from_block(block, &c.modulus()));
+                }
+            }
+        }
+        self.tables.extend(plain);
+        Ok(())
+    }
+
+    // Compute the label shares of all wires not evaluated yet, at the
+    // evaluator, once their tables are garbled.
+    fn evaluate(&mut self) {
+        let n = self.nparties();
+        for ix in self.labels.len()..self.nodes.len() {
+            let labels = match &self.nodes[ix] {
+                Node::Labels(labels) => labels.clone(),
+                Node::Add(x, y) => self.labels[*x]
+                    .iter()
+                    .zip(self.labels[*y].iter())
+                    .map(|(a, b)| a.plus(b))
+                    .collect(),
+                Node::Sub(x, y) => self.labels[*x]
+                    .iter()
+                    .zip(self.labels[*y].iter())
+                    .map(|(a, b)| a.minus(b))
+                    .collect(),
+                Node::Cmul(x, c) => self.labels[*x].iter().map(|a| a.cmul(*c)).collect(),
+                Node::Table {
+                    gate,
+                    table,
+                    inputs,
+                } => {
+                    let rows = Rows::new(
+                        &inputs
+                            .iter()
+                            .map(|(_, zero)| zero.modulus())
+                            .collect::<Vec<Modulus>>(),
+                    );
+                    let row = rows.index(
+                        &inputs
+                            .iter()
+                            .map(|(x, zero)| self.masked(*x, zero))
+                            .collect::<Vec<u16>>(),
+                    );
+                    let plain = &self.tables[*table][row];
+                    (0..n)
+                        .map(|i| {
+                            let r = plain[i].modulus();
+                            (0..n).fold(plain[i].clone(), |acc, j| {
+                                let ls = inputs
+                                    .iter()
+                                    .map(|(x, _)| self.labels[*x][j].clone())
+                                    .collect::<Vec<Wire>>();
+                                acc.minus(&pad(*gate, row, i, &ls, &r))
+                            })
+                        })
+                        .collect()
+                }
+            };
+            self.labels.push(labels);
+        }
+    }
+
+    // Reveal the value of `x` to every party, garbling and evaluating all
+    // pending gates first.
+    fn decode(&mut self, x: &NWire) -> Result<u16, TwopacError> {
+        self.garble()?;
+        let q = x.modulus();
+        if self.id != EVALUATOR {
+            let channel = self.channel(EVALUATOR);
+            channel.write_u16(x.mask)?;
+            channel.flush()?;
+            return Ok(channel.read_u16()?);
+        }
+        self.evaluate();
+        let mut mask = x.mask;
+        for p in self.others() {
+            mask = add(mask, self.channel(p).read_u16()?, &q);
+        }
+        let val = sub(self.masked(x.ix, &x.zero), mask, &q);
+        for p in self.others() {
+            self.channel(p).write_u16(val)?;
+            self.channel(p).flush()?;
+        }
+        Ok(val)
+    }
+}
+
+/// A `Party` receiving the inputs of one particular party.
+pub struct InputsFrom<'a, C, RNG> {
+    party: &'a mut Party<C, RNG>,
+    owner: usize,
+}
+
+impl<'a, C: AbstractChannel, RNG: CryptoRng + Rng> FancyInput for InputsFrom<'a, C, RNG> {
+    type Item = NWire;
+    type Error = TwopacError;
+
+    fn encode_many(
+        &mut self,
+        values: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<NWire>, TwopacError> {
+        if self.owner != self.party.id {
+            return Err(FancyError::InvalidArg(format!(
+                "cannot encode inputs of party {}",
+                self.owner
+            ))
+            .into());
+        }
+        self.party.encode_many(values, moduli)
+    }
+
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<NWire>, TwopacError> {
+        if self.owner == self.party.id {
+            return Err(FancyError::InvalidArg("cannot receive our own inputs".to_string()).into());
+        }
+        self.party.input(self.owner, None, moduli)
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng> FancyInput for Party<C, RNG> {
+    type Item = NWire;
+    type Error = TwopacError;
+
+    fn encode_many(
+        &mut self,
+        values: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<NWire>, TwopacError> {
+        if values.len() != moduli.len() {
+            return Err(FancyError::InvalidArgNum {
+                got: values.len(),
+                needed: moduli.len(),
+            }
+            .into());
+        }
+        self.input(self.id, Some(values), moduli)
+    }
+
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<NWire>, TwopacError> {
+        if self.nparties() != 2 {
+            return Err(
+                FancyError::InvalidArg("receive through `Party::inputs_from`".to_string()).into(),
+            );
+        }
+        self.input(1 - self.id, None, moduli)
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng> Fancy for Party<C, RNG> {
+    type Item = NWire;
+    type Error = TwopacError;
+
+    fn constant(&mut self, x: u16, q: &Modulus) -> Result<NWire, TwopacError> {
+        check_modulus(q)?;
+        // The label of `x` is zero for every party: the value is public anyway.
+        let zero = self.delta(q).cmul(sub(0, x, q));
+        let labels = vec![Wire::zero(q); self.nparties()];
+        Ok(self.wire(zero, 0, Node::Labels(labels)))
+    }
+
+    fn add(&mut self, x: &NWire, y: &NWire) -> Result<NWire, TwopacError> {
+        let q = equal_moduli(x, y)?;
+        let mask = add(x.mask, y.mask, &q);
+        Ok(self.wire(x.zero.plus(&y.zero), mask, Node::Add(x.ix, y.ix)))
+    }
+
+    fn sub(&mut self, x: &NWire, y: &NWire) -> Result<NWire, TwopacError> {
+        let q = equal_moduli(x, y)?;
+        let mask = sub(x.mask, y.mask, &q);
+        Ok(self.wire(x.zero.minus(&y.zero), mask, Node::Sub(x.ix, y.ix)))
+    }
+
+    fn cmul(&mut self, x: &NWire, c: u16) -> Result<NWire, TwopacError> {
+        let mask = cmul(x.mask, c, &x.modulus());
+        Ok(self.wire(x.zero.cmul(c), mask, Node::Cmul(x.ix, c)))
+    }
+
+    fn mul(&mut self, x: &NWire, y: &NWire) -> Result<NWire, TwopacError> {
+        let q = equal_moduli(x, y)?;
+        self.table_gate(&[x, y], &q, |vs| mul(vs[0], vs[1], &q))
+    }
+
+    fn proj(&mut self, x: &NWire, r: &Modulus, tt: Option<Vec<u16>>) -> Result<NWire, TwopacError> {
+        let tt = tt.ok_or(FancyError::NoTruthTable)?;
+        check_modulus(r)?;
+        if tt.len() < x.modulus().size() as usize || tt.iter().any(|&v| v >= r.size()) {
+            return Err(FancyError::InvalidTruthTable.into());
+        }
+        self.table_gate(&[x], r, |vs| tt[vs[0] as usize])
+    }
+
+    fn output(&mut self, x: &NWire) -> Result<Option<u16>, TwopacError> {
+        self.decode(x).map(Some)
+    }
+
+    fn constant_big(&mut self, x: u64, q: &Modulus) -> Result<NWire, TwopacError> {
+        check_modulus(q)?;
+        self.constant((x % u64::from(q.size())) as u16, q)
+    }
+
+    fn cmul_big(&mut self, x: &NWire, c: u64) -> Result<NWire, TwopacError> {
+        let c = c % u64::from(x.modulus().order());
+        self.cmul(x, c as u16)
+    }
+
+    fn output_big(&mut self, x: &NWire) -> Result<Option<u64>, TwopacError> {
+        self.decode(x).map(|v| Some(u64::from(v)))
+    }
+}
+
+// The rows of a table indexed by one value per input, the first input most
+// significant.
+struct Rows {
+    sizes: Vec<usize>,
+}
+
+impl Rows {
+    fn new(moduli: &[Modulus]) -> Self {
+        Rows {
+            sizes: moduli.iter().map(|q| q.size() as usize).collect(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.sizes.iter().product()
+    }
+
+    fn values(&self, mut row: usize) -> Vec<u16> {
+        let mut vals = vec![0; self.sizes.len()];
+        for (v, size) in vals.iter_mut().zip(self.sizes.iter()).rev() {
+            *v = (row % size) as u16;
+            row /= size;
+        }
+        vals
+    }
+
+    fn index(&self, vals: &[u16]) -> usize {
+        vals.iter()
+            .zip(self.sizes.iter())
+            .fold(0, |acc, (v, size)| acc * size + *v as usize)
+    }
+
+    // The row with the value of input `axis` replaced by `val`.
+    fn with(&self, row: usize, axis: usize, val: u16) -> usize {
+        let mut vals = self.values(row);
+        vals[axis] = val;
+        self.index(&vals)
+    }
+}
+
+// The pad of component `i` of `row` of `gate`, under one label per input.
+fn pad(gate: u64, row: usize, i: usize, labels: &[Wire], r: &Modulus) -> Wire {
+    let tweak = |a: usize| {
+        Block::from(u128::from(gate) << 64 | (a as u128) << 56 | (row as u128) << 16 | i as u128)
+    };
+    let (last, rest) = labels.split_last().unwrap();
+    let h = rest
+        .iter()
+        .enumerate()
+        .fold(Block::default(), |h, (a, label)| {
+            AES_HASH.tccr_hash(tweak(a), label.as_block() ^ h)
+        });
+    // `hashback` makes sure the result is a valid label of modulus `r`.
+    last.hashback(tweak(rest.len()) ^ h, r)
+}
+
+fn equal_moduli(x: &NWire, y: &NWire) -> Result<Modulus, TwopacError> {
+    if x.modulus() != y.modulus() {
+        return Err(FancyError::UnequalModuli {
+            x: x.modulus(),
+            y: y.modulus(),
+        }
+        .into());
+    }
+    Ok(x.modulus())
+}
+
+fn check_modulus(q: &Modulus) -> Result<(), TwopacError> {
+    match q {
+        Modulus::ZqBig { .. } | Modulus::GF16 { .. } => Err(FancyError::InvalidArg(format!(
+            "{} is not supported by multi-party garbling",
+            q
+        ))
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+fn dummy_op<F>(f: F) -> u16
+where
+    F: FnOnce() -> Result<DummyVal, crate::errors::DummyError>,
+{
+    f().expect("operands have equal moduli").val()
+}
+
+fn add(a: u16, b: u16, q: &Modulus) -> u16 {
+    dummy_op(|| Dummy::new().add(&DummyVal::new(a, *q), &DummyVal::new(b, *q)))
+}
+
+fn sub(a: u16, b: u16, q: &Modulus) -> u16 {
+    dummy_op(|| Dummy::new().sub(&DummyVal::new(a, *q), &DummyVal::new(b, *q)))
+}
+
+fn mul(a: u16, b: u16, q: &Modulus) -> u16 {
+    dummy_op(|| Dummy::new().mul(&DummyVal::new(a, *q), &DummyVal::new(b, *q)))
+}
+
+fn cmul(a: u16, c: u16, q: &Modulus) -> u16 {
+    dummy_op(|| Dummy::new().cmul(&DummyVal::new(a, *q), c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util::RngExt, BinaryGadgets};
+    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+    use std::{cell::Cell, rc::Rc};
+
+    // Counts the times a party starts reading after having written, over all
+    // of its channels.
+    struct Rounds<C> {
+        channel: C,
+        wrote: bool,
+        count: Rc<Cell<usize>>,
+    }
+
+    impl<C: AbstractChannel> AbstractChannel for Rounds<C> {
+        fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+            if self.wrote {
+                self.wrote = false;
+                self.count.set(self.count.get() + 1);
+            }
+            self.channel.read_bytes(bytes)
+        }
+
+        fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+            self.wrote = true;
+            self.channel.write_bytes(bytes)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.channel.flush()
+        }
+
+        fn clone(&self) -> Self {
+            Rounds {
+                channel: self.channel.clone(),
+                wrote: self.wrote,
+                count: self.count.clone(),
+            }
+        }
+    }
+
+    type TestParty = Party<Rounds<UnixChannel>, AesRng>;
+
+    // Run `f` as each of `n` parties, returning their results.
+    fn run<F, T>(n: usize, f: F) -> Vec<T>
+    where
+        F: Fn(&mut TestParty) -> T + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        run_counting(n, f).into_iter().map(|(t, _)| t).collect()
+    }
+
+    // Like `run`, also returning the number of rounds of each party.
+    fn run_counting<F, T>(n: usize, f: F) -> Vec<(T, usize)>
+    where
+        F: Fn(&mut TestParty) -> T + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        let mut channels = (0..n)
+            .map(|_| (0..n).map(|_| None).collect::<Vec<Option<UnixChannel>>>())
+            .collect::<Vec<_>>();
+        for i in 0..n {
+            for j in i + 1..n {
+                let (a, b) = unix_channel_pair();
+                channels[i][j] = Some(a);
+                channels[j][i] = Some(b);
+            }
+        }
+        let f = std::sync::Arc::new(f);
+        let handles = channels
+            .into_iter()
+            .enumerate()
+            .map(|(id, channels)| {
+                let f = f.clone();
+                std::thread::spawn(move || {
+                    let count = Rc::new(Cell::new(0));
+                    let channels = channels
+                        .into_iter()
+                        .map(|c| {
+                            c.map(|channel| Rounds {
+                                channel,
+                                wrote: false,
+                                count: count.clone(),
+                            })
+                        })
+                        .collect();
+                    let mut party = Party::new(id, channels, AesRng::new()).unwrap();
+                    (f(&mut party), count.get())
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    }
+
+    // Every party inputs `inputs[id]`, and gets the inputs of all others.
+    fn share_inputs(party: &mut TestParty, inputs: &[u16], q: &Modulus) -> Vec<NWire> {
+        (0..party.nparties())
+            .map(|p| {
+                if p == party.id() {
+                    party.encode(inputs[p], q).unwrap()
+                } else {
+                    party.inputs_from(p).receive(q).unwrap()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut rng = rand::thread_rng();
+        let q = Modulus::Zq { q: 5 };
+        for n in 2..5 {
+            let inputs = (0..n).map(|_| rng.gen_u16() % 5).collect::<Vec<u16>>();
+            let expected = {
+                let s = inputs.iter().sum::<u16>() % 5;
+                let t = s * inputs[n - 1] % 5;
+                (t * t + 3) % 5 % 3
+            };
+            let inputs_ = inputs.clone();
+            let outputs = run(n, move |party| {
+                let xs = share_inputs(party, &inputs_, &q);
+                let s = party.add_many(&xs).unwrap();
+                let t = party.mul(&s, &xs[xs.len() - 1]).unwrap();
+                let u = party.mul(&t, &t).unwrap();
+                let c = party.constant(3, &q).unwrap();
+                let v = party.add(&u, &c).unwrap();
+                let w = party
+                    .proj(&v, &Modulus::Zq { q: 3 }, Some(vec![0, 1, 2, 0, 1]))
+                    .unwrap();
+                party.output(&w).unwrap().unwrap()
+            });
+            assert!(outputs.iter().all(|&o| o == expected), "{:?}", outputs);
+        }
+    }
+
+    #[test]
+    fn gf4() {
+        let mut rng = rand::thread_rng();
+        let q = Modulus::X4_X_1;
+        let inputs = (0..3).map(|_| rng.gen_u16() % 16).collect::<Vec<u16>>();
+        let expected = {
+            let mut d = Dummy::new();
+            let xs = inputs
+                .iter()
+                .map(|x| d.encode(*x, &q).unwrap())
+                .collect::<Vec<_>>();
+            let s = d.add(&xs[0], &xs[1]).unwrap();
+            let s = d.cmul(&s, 7).unwrap();
+            let t = d.mul(&s, &xs[2]).unwrap();
+            d.output(&t).unwrap().unwrap()
+        };
+        let inputs_ = inputs.clone();
+        let outputs = run(3, move |party| {
+            let xs = share_inputs(party, &inputs_, &q);
+            let s = party.add(&xs[0], &xs[1]).unwrap();
+            let s = party.cmul(&s, 7).unwrap();
+            let t = party.mul(&s, &xs[2]).unwrap();
+            party.output(&t).unwrap().unwrap()
+        });
+        assert!(outputs.iter().all(|&o| o == expected), "{:?}", outputs);
+    }
+
+    #[test]
+    fn binary_gadget() {
+        let mut rng = rand::thread_rng();
+        let nbits = 4;
+        let inputs = (0..3).map(|_| rng.gen_u128() % 16).collect::<Vec<u128>>();
+        let expected = inputs.iter().sum::<u128>() % 16;
+        let outputs = run(3, move |party| {
+            let xs = (0..3)
+                .map(|p| {
+                    if p == party.id() {
+                        party.bin_encode(inputs[p], nbits).unwrap()
+                    } else {
+                        party.inputs_from(p).bin_receive(nbits).unwrap()
+                    }
+                })
+                .collect::<Vec<_>>();
+            let s = party.bin_addition_no_carry(&xs[0], &xs[1]).unwrap();
+            let s = party.bin_addition_no_carry(&s, &xs[2]).unwrap();
+            party.bin_output(&s).unwrap().unwrap()
+        });
+        assert!(outputs.iter().all(|&o| o == expected), "{:?}", outputs);
+    }
+
+    #[test]
+    fn constant_rounds() {
+        let q = Modulus::Zq { q: 5 };
+        let rounds = |depth: usize| {
+            let outputs = run_counting(3, move |party| {
+                let xs = share_inputs(party, &[1, 2, 3], &q);
+                let mut t = party.mul(&xs[0], &xs[1]).unwrap();
+                for _ in 0..depth {
+                    t = party.mul(&t, &xs[2]).unwrap();
+                }
+                party.output(&t).unwrap().unwrap()
+            });
+            let expected = (0..depth).fold(2, |t, _| t * 3 % 5);
+            assert!(outputs.iter().all(|(o, _)| *o == expected), "{:?}", outputs);
+            outputs.into_iter().map(|(_, r)| r).collect::<Vec<usize>>()
+        };
+        assert_eq!(rounds(1), rounds(12));
+    }
+
+    #[test]
+    fn needs_all_channels() {
+        let (a, _b) = unix_channel_pair();
+        assert!(Party::new(0, vec![None, Some(a), None], AesRng::new()).is_err());
+    }
+}
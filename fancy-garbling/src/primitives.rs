@@ -0,0 +1,61 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Cryptographic primitives implemented as `Fancy` gadgets.
+//!
+//! Ciphers and permutations are built from the shared layers in `utils`, so
+//! that each primitive only supplies its parameters.
+
+pub mod aes;
+pub mod gift;
+pub mod hmac;
+pub mod interpolation;
+pub mod led;
+pub mod linear_layer;
+pub mod lowmc;
+pub mod mimc;
+pub mod photon;
+pub mod photon_beetle;
+pub mod poseidon;
+pub mod present;
+pub mod prince;
+pub mod sbox_synth;
+pub mod sha256;
+pub mod simon;
+pub mod speck;
+pub mod spn;
+pub mod sponge;
+pub mod utils;
+
+pub use aes::{aes128_encrypt, aes128_encrypt_gf8, AES_MODULUS};
+pub use gift::{
+    gift128_encrypt,
+    gift128_encrypt_nibbles,
+    gift64_encrypt,
+    gift64_encrypt_nibbles,
+    GIFT_SBOX,
+};
+pub use hmac::{hmac, HashGadget};
+pub use interpolation::SboxPolynomial;
+pub use led::{led_cipher, led_encrypt, LED_MODULUS};
+pub use linear_layer::{LinearLayer, LinearOp};
+pub use lowmc::{lowmc_encrypt, LowMcParams, LOWMC_SBOX};
+pub use mimc::{gmimc_permute, mimc_encrypt, GmimcParams, MimcParams};
+pub use photon::{PhotonGadgets, PhotonParams, PhotonState};
+pub use photon_beetle::{
+    photon_beetle_aead_decrypt,
+    photon_beetle_aead_encrypt,
+    PhotonBeetleRate,
+};
+pub use poseidon::{poseidon_hash, poseidon_permute, PoseidonParams};
+pub use present::{present_encrypt, present_encrypt_proj, PRESENT_ROUNDS};
+pub use prince::{prince_decrypt, prince_encrypt, PRINCE_SBOX};
+pub use sbox_synth::{SboxCircuit, SboxGate};
+pub use sha256::{sha256, sha256_compress, Sha256, SHA256_IV};
+pub use simon::{simon_encrypt, SimonParams};
+pub use speck::{speck_encrypt, SpeckParams};
+pub use spn::{spn_encrypt, spn_encrypt_with, MixColumnsStrategy, SpnCipher};
+pub use sponge::{Sponge, SpongePadding};
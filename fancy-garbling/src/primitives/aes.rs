@@ -0,0 +1,516 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The AES-128 block cipher (FIPS-197), with its key schedule.
+//!
+//! Bytes are either `BinaryBundle`s of eight mod-2 wires, least significant
+//! first, or single `GF(2^8)` wires with the AES polynomial. Both orders of
+//! the state are those of FIPS-197: byte `r + 4c` is row `r` of column `c`.
+//!
+//! On bits the S-box is the circuit of Boyar and Peralta, with 34 AND gates,
+//! and everything else is free. On `GF(2^8)` wires the S-box is a projection of
+//! 255 ciphertexts, and everything else is free.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{BinaryBundle, Fancy, HasModulus},
+    Modulus,
+};
+
+/// The field of the `GF(2^8)` variant, with the AES polynomial.
+pub const AES_MODULUS: Modulus = Modulus::GF8 { p: 0b100011011 };
+
+const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// Encrypt the 16 bytes `pt` under the 16 bytes `key` with AES-128, where
+/// each byte is a bundle of 8 mod-2 wires.
+pub fn aes128_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &[BinaryBundle<F::Item>],
+    pt: &[BinaryBundle<F::Item>],
+) -> Result<Vec<BinaryBundle<F::Item>>, F::Error> {
+    for x in key.iter().chain(pt.iter()) {
+        if x.size() != 8 {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: x.size(),
+                needed: 8,
+            }));
+        }
+        check_modulus::<F>(x.wires(), Modulus::Zq { q: 2 })?;
+    }
+    encrypt::<F, Bits>(f, key, pt)
+}
+
+/// Encrypt the 16 bytes `pt` under the 16 bytes `key` with AES-128, where
+/// each byte is a wire of modulus `AES_MODULUS`.
+pub fn aes128_encrypt_gf8<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &[F::Item],
+    pt: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    check_modulus::<F>(key, AES_MODULUS)?;
+    check_modulus::<F>(pt, AES_MODULUS)?;
+    encrypt::<F, Gf8>(f, key, pt)
+}
+
+/// The AES S-box on the bits of a byte, least significant first.
+pub fn aes_sbox_bits<F: Fancy + ?Sized>(
+    f: &mut F,
+    x: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    // the circuit numbers bits from the most significant
+    let u = x.wires().iter().rev().cloned().collect::<Vec<_>>();
+    let mut w = |a: &F::Item, b: &F::Item| f.xor(a, b);
+    let t1 = w(&u[0], &u[3])?;
+    let t2 = w(&u[0], &u[5])?;
+    let t3 = w(&u[0], &u[6])?;
+    let t4 = w(&u[3], &u[5])?;
+    let t5 = w(&u[4], &u[6])?;
+    let t6 = w(&t1, &t5)?;
+    let t7 = w(&u[1], &u[2])?;
+    let t8 = w(&u[7], &t6)?;
+    let t9 = w(&u[7], &t7)?;
+    let t10 = w(&t6, &t7)?;
+    let t11 = w(&u[1], &u[5])?;
+    let t12 = w(&u[2], &u[5])?;
+    let t13 = w(&t3, &t4)?;
+    let t14 = w(&t6, &t11)?;
+    let t15 = w(&t5, &t11)?;
+    let t16 = w(&t5, &t12)?;
+    let t17 = w(&t9, &t16)?;
+    let t18 = w(&u[3], &u[7])?;
+    let t19 = w(&t7, &t18)?;
+    let t20 = w(&t1, &t19)?;
+    let t21 = w(&u[6], &u[7])?;
+    let t22 = w(&t7, &t21)?;
+    let t23 = w(&t2, &t22)?;
+    let t24 = w(&t2, &t10)?;
+    let t25 = w(&t20, &t17)?;
+    let t26 = w(&t3, &t16)?;
+    let t27 = w(&t1, &t12)?;
+
+    let m1 = f.and(&t13, &t6)?;
+    let m2 = f.and(&t23, &t8)?;
+    let m3 = f.xor(&t14, &m1)?;
+    let m4 = f.and(&t19, &u[7])?;
+    let m5 = f.xor(&m4, &m1)?;
+    let m6 = f.and(&t3, &t16)?;
+    let m7 = f.and(&t22, &t9)?;
+    let m8 = f.xor(&t26, &m6)?;
+    let m9 = f.and(&t20, &t17)?;
+    let m10 = f.xor(&m9, &m6)?;
+    let m11 = f.and(&t1, &t15)?;
+    let m12 = f.and(&t4, &t27)?;
+    let m13 = f.xor(&m12, &m11)?;
+    let m14 = f.and(&t2, &t10)?;
+    let m15 = f.xor(&m14, &m11)?;
+    let m16 = f.xor(&m3, &m2)?;
+    let m17 = f.xor(&m5, &t24)?;
+    let m18 = f.xor(&m8, &m7)?;
+    let m19 = f.xor(&m10, &m15)?;
+    let m20 = f.xor(&m16, &m13)?;
+    let m21 = f.xor(&m17, &m15)?;
+    let m22 = f.xor(&m18, &m13)?;
+    let m23 = f.xor(&m19, &t25)?;
+    let m24 = f.xor(&m22, &m23)?;
+    let m25 = f.and(&m22, &m20)?;
+    let m26 = f.xor(&m21, &m25)?;
+    let m27 = f.xor(&m20, &m21)?;
+    let m28 = f.xor(&m23, &m25)?;
+    let m29 = f.and(&m28, &m27)?;
+    let m30 = f.and(&m26, &m24)?;
+    let m31 = f.and(&m20, &m23)?;
+    let m32 = f.and(&m27, &m31)?;
+    let m33 = f.xor(&m27, &m25)?;
+    let m34 = f.and(&m21, &m22)?;
+    let m35 = f.and(&m24, &m34)?;
+    let m36 = f.xor(&m24, &m25)?;
+    let m37 = f.xor(&m21, &m29)?;
+    let m38 = f.xor(&m32, &m33)?;
+    let m39 = f.xor(&m23, &m30)?;
+    let m40 = f.xor(&m35, &m36)?;
+    let m41 = f.xor(&m38, &m40)?;
+    let m42 = f.xor(&m37, &m39)?;
+    let m43 = f.xor(&m37, &m38)?;
+    let m44 = f.xor(&m39, &m40)?;
+    let m45 = f.xor(&m42, &m41)?;
+    let m46 = f.and(&m44, &t6)?;
+    let m47 = f.and(&m40, &t8)?;
+    let m48 = f.and(&m39, &u[7])?;
+    let m49 = f.and(&m43, &t16)?;
+    let m50 = f.and(&m38, &t9)?;
+    let m51 = f.and(&m37, &t17)?;
+    let m52 = f.and(&m42, &t15)?;
+    let m53 = f.and(&m45, &t27)?;
+    let m54 = f.and(&m41, &t10)?;
+    let m55 = f.and(&m44, &t13)?;
+    let m56 = f.and(&m40, &t23)?;
+    let m57 = f.and(&m39, &t19)?;
+    let m58 = f.and(&m43, &t3)?;
+    let m59 = f.and(&m38, &t22)?;
+    let m60 = f.and(&m37, &t20)?;
+    let m61 = f.and(&m42, &t1)?;
+    let m62 = f.and(&m45, &t4)?;
+    let m63 = f.and(&m41, &t2)?;
+
+    let mut w = |a: &F::Item, b: &F::Item| f.xor(a, b);
+    let l0 = w(&m61, &m62)?;
+    let l1 = w(&m50, &m56)?;
+    let l2 = w(&m46, &m48)?;
+    let l3 = w(&m47, &m55)?;
+    let l4 = w(&m54, &m58)?;
+    let l5 = w(&m49, &m61)?;
+    let l6 = w(&m62, &l5)?;
+    let l7 = w(&m46, &l3)?;
+    let l8 = w(&m51, &m59)?;
+    let l9 = w(&m52, &m53)?;
+    let l10 = w(&m53, &l4)?;
+    let l11 = w(&m60, &l2)?;
+    let l12 = w(&m48, &m51)?;
+    let l13 = w(&m50, &l0)?;
+    let l14 = w(&m52, &m61)?;
+    let l15 = w(&m55, &l1)?;
+    let l16 = w(&m56, &l0)?;
+    let l17 = w(&m57, &l1)?;
+    let l18 = w(&m58, &l8)?;
+    let l19 = w(&m63, &l4)?;
+    let l20 = w(&l0, &l1)?;
+    let l21 = w(&l1, &l7)?;
+    let l22 = w(&l3, &l12)?;
+    let l23 = w(&l18, &l2)?;
+    let l24 = w(&l15, &l9)?;
+    let l25 = w(&l6, &l10)?;
+    let l26 = w(&l7, &l9)?;
+    let l27 = w(&l8, &l10)?;
+    let l28 = w(&l11, &l14)?;
+    let l29 = w(&l11, &l17)?;
+    let s0 = w(&l6, &l24)?;
+    let s1 = w(&l16, &l26)?;
+    let s2 = w(&l19, &l28)?;
+    let s3 = w(&l6, &l21)?;
+    let s4 = w(&l20, &l22)?;
+    let s5 = w(&l25, &l29)?;
+    let s6 = w(&l13, &l27)?;
+    let s7 = w(&l6, &l23)?;
+    // s1, s2, s6 and s7 are complemented, which adds 0x63 overall
+    Ok(BinaryBundle::new(vec![
+        f.negate(&s7)?,
+        f.negate(&s6)?,
+        s5,
+        s4,
+        s3,
+        f.negate(&s2)?,
+        f.negate(&s1)?,
+        s0,
+    ]))
+}
+
+// Check that every wire of `xs` has modulus `q`.
+fn check_modulus<F: Fancy + ?Sized>(xs: &[F::Item], q: Modulus) -> Result<(), F::Error> {
+    if let Some(x) = xs.iter().find(|x| x.modulus() != q) {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: x.modulus(),
+            needed: q,
+        }));
+    }
+    Ok(())
+}
+
+// The operations AES needs on one representation of bytes.
+trait Bytes<F: Fancy + ?Sized> {
+    type Byte: Clone;
+    fn sbox(f: &mut F, x: &Self::Byte) -> Result<Self::Byte, F::Error>;
+    fn xor(f: &mut F, x: &Self::Byte, y: &Self::Byte) -> Result<Self::Byte, F::Error>;
+    fn xor_constant(f: &mut F, x: &Self::Byte, c: u8) -> Result<Self::Byte, F::Error>;
+    // multiply by 2 in the field
+    fn xtime(f: &mut F, x: &Self::Byte) -> Result<Self::Byte, F::Error>;
+}
+
+struct Bits;
+
+impl<F: Fancy + ?Sized> Bytes<F> for Bits {
+    type Byte = BinaryBundle<F::Item>;
+
+    fn sbox(f: &mut F, x: &Self::Byte) -> Result<Self::Byte, F::Error> {
+        aes_sbox_bits(f, x)
+    }
+
+    fn xor(f: &mut F, x: &Self::Byte, y: &Self::Byte) -> Result<Self::Byte, F::Error> {
+        x.wires()
+            .iter()
+            .zip(y.wires().iter())
+            .map(|(a, b)| f.xor(a, b))
+            .collect::<Result<Vec<F::Item>, F::Error>>()
+            .map(BinaryBundle::new)
+    }
+
+    fn xor_constant(f: &mut F, x: &Self::Byte, c: u8) -> Result<Self::Byte, F::Error> {
+        x.wires()
+            .iter()
+            .enumerate()
+            .map(|(i, a)| {
+                if (c >> i) & 1 == 1 {
+                    f.negate(a)
+                } else {
+                    Ok(a.clone())
+                }
+            })
+            .collect::<Result<Vec<F::Item>, F::Error>>()
+            .map(BinaryBundle::new)
+    }
+
+    fn xtime(f: &mut F, x: &Self::Byte) -> Result<Self::Byte, F::Error> {
+        // shift left and reduce by x^8 = x^4 + x^3 + x + 1
+        let b = x.wires();
+        Ok(BinaryBundle::new(vec![
+            b[7].clone(),
+            f.xor(&b[0], &b[7])?,
+            b[1].clone(),
+            f.xor(&b[2], &b[7])?,
+            f.xor(&b[3], &b[7])?,
+            b[4].clone(),
+            b[5].clone(),
+            b[6].clone(),
+        ]))
+    }
+}
+
+struct Gf8;
+
+impl<F: Fancy + ?Sized> Bytes<F> for Gf8 {
+    type Byte = F::Item;
+
+    fn sbox(f: &mut F, x: &Self::Byte) -> Result<Self::Byte, F::Error> {
+        utils::aes_sbox(f, x)
+    }
+
+    fn xor(f: &mut F, x: &Self::Byte, y: &Self::Byte) -> Result<Self::Byte, F::Error> {
+        f.add(x, y)
+    }
+
+    fn xor_constant(f: &mut F, x: &Self::Byte, c: u8) -> Result<Self::Byte, F::Error> {
+        utils::add_constant(f, x, c as u16)
+    }
+
+    fn xtime(f: &mut F, x: &Self::Byte) -> Result<Self::Byte, F::Error> {
+        f.cmul(x, 2)
+    }
+}
+
+fn encrypt<F: Fancy + ?Sized, B: Bytes<F>>(
+    f: &mut F,
+    key: &[B::Byte],
+    pt: &[B::Byte],
+) -> Result<Vec<B::Byte>, F::Error> {
+    for xs in [key, pt].iter() {
+        if xs.len() != 16 {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: 16,
+            }));
+        }
+    }
+    let round_keys = expand_key::<F, B>(f, key)?;
+    let add_round_key = |f: &mut F, state: &[B::Byte], k: &[B::Byte]| {
+        state
+            .iter()
+            .zip(k.iter())
+            .map(|(x, k)| B::xor(f, x, k))
+            .collect::<Result<Vec<B::Byte>, F::Error>>()
+    };
+    let mut state = add_round_key(f, pt, &round_keys[0])?;
+    for (round, k) in round_keys.iter().enumerate().skip(1) {
+        state = state
+            .iter()
+            .map(|x| B::sbox(f, x))
+            .collect::<Result<Vec<B::Byte>, F::Error>>()?;
+        state = (0..16)
+            .map(|i| {
+                let (r, c) = (i % 4, i / 4);
+                state[r + 4 * ((c + r) % 4)].clone()
+            })
+            .collect();
+        if round < 10 {
+            state = mix_columns::<F, B>(f, &state)?;
+        }
+        state = add_round_key(f, &state, k)?;
+    }
+    Ok(state)
+}
+
+// Multiply each column with the circulant matrix (2, 3, 1, 1), as
+// b_i = a_i + t + 2 (a_i + a_{i+1}) where t is the sum of the column.
+fn mix_columns<F: Fancy + ?Sized, B: Bytes<F>>(
+    f: &mut F,
+    state: &[B::Byte],
+) -> Result<Vec<B::Byte>, F::Error> {
+    let mut out = Vec::with_capacity(16);
+    for col in state.chunks(4) {
+        let t = B::xor(f, &col[0], &col[1])?;
+        let t = B::xor(f, &t, &col[2])?;
+        let t = B::xor(f, &t, &col[3])?;
+        for i in 0..4 {
+            let u = B::xor(f, &col[i], &col[(i + 1) % 4])?;
+            let u = B::xtime(f, &u)?;
+            let u = B::xor(f, &u, &t)?;
+            out.push(B::xor(f, &u, &col[i])?);
+        }
+    }
+    Ok(out)
+}
+
+// The 11 round keys of 16 bytes each.
+fn expand_key<F: Fancy + ?Sized, B: Bytes<F>>(
+    f: &mut F,
+    key: &[B::Byte],
+) -> Result<Vec<Vec<B::Byte>>, F::Error> {
+    let mut words = key.chunks(4).map(|w| w.to_vec()).collect::<Vec<_>>();
+    for i in 4..44 {
+        let mut t = words[i - 1].clone();
+        if i % 4 == 0 {
+            t.rotate_left(1);
+            t = t
+                .iter()
+                .map(|x| B::sbox(f, x))
+                .collect::<Result<Vec<B::Byte>, F::Error>>()?;
+            t[0] = B::xor_constant(f, &t[0], RCON[i / 4 - 1])?;
+        }
+        let w = words[i - 4]
+            .iter()
+            .zip(t.iter())
+            .map(|(x, y)| B::xor(f, x, y))
+            .collect::<Result<Vec<B::Byte>, F::Error>>()?;
+        words.push(w);
+    }
+    Ok(words.chunks(4).map(|ws| ws.concat()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        util,
+        BinaryGadgets,
+        BundleGadgets,
+        FancyInput,
+    };
+
+    // (key, plaintext, ciphertext) from FIPS-197, appendices B and C.1
+    const KATS: [([u8; 16], [u8; 16], [u8; 16]); 2] = [
+        (
+            [
+                0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09,
+                0xcf, 0x4f, 0x3c,
+            ],
+            [
+                0x32, 0x43, 0xf6, 0xa8, 0x88, 0x5a, 0x30, 0x8d, 0x31, 0x31, 0x98, 0xa2, 0xe0,
+                0x37, 0x07, 0x34,
+            ],
+            [
+                0x39, 0x25, 0x84, 0x1d, 0x02, 0xdc, 0x09, 0xfb, 0xdc, 0x11, 0x85, 0x97, 0x19,
+                0x6a, 0x0b, 0x32,
+            ],
+        ),
+        (
+            [
+                0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
+                0x0d, 0x0e, 0x0f,
+            ],
+            [
+                0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc,
+                0xdd, 0xee, 0xff,
+            ],
+            [
+                0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70,
+                0xb4, 0xc5, 0x5a,
+            ],
+        ),
+    ];
+
+    fn bits(bytes: &[u8]) -> Vec<u16> {
+        bytes
+            .iter()
+            .flat_map(|&x| util::u128_to_bits(x as u128, 8))
+            .collect()
+    }
+
+    #[test]
+    fn sbox_bits() {
+        let mut f = Dummy::new();
+        for x in 0..256 {
+            let x_ = f.bin_encode(x, 8).unwrap();
+            let y = aes_sbox_bits(&mut f, &x_).unwrap();
+            assert_eq!(
+                f.bin_output(&y).unwrap().unwrap(),
+                utils::AES_SBOX[x as usize] as u128
+            );
+        }
+    }
+
+    #[test]
+    fn dummy_kats() {
+        for (key, pt, ct) in KATS.iter() {
+            let mut f = Dummy::new();
+            let enc = |f: &mut Dummy, xs: &[u8]| {
+                xs.iter()
+                    .map(|&x| f.bin_encode(x as u128, 8).unwrap())
+                    .collect::<Vec<_>>()
+            };
+            let (k, p) = (enc(&mut f, key), enc(&mut f, pt));
+            let c = aes128_encrypt(&mut f, &k, &p).unwrap();
+            let c = f.bin_outputs(&c).unwrap().unwrap();
+            assert_eq!(c, ct.iter().map(|&x| x as u128).collect::<Vec<_>>());
+
+            let enc = |xs: &[u8]| {
+                xs.iter()
+                    .map(|&x| DummyVal::new(x as u16, AES_MODULUS))
+                    .collect::<Vec<_>>()
+            };
+            let c = aes128_encrypt_gf8(&mut f, &enc(key), &enc(pt)).unwrap();
+            let c = c.iter().map(|x| x.val() as u8).collect::<Vec<_>>();
+            assert_eq!(&c[..], &ct[..]);
+        }
+        let mut f = Dummy::new();
+        let x = DummyVal::new(0, AES_MODULUS);
+        assert!(aes128_encrypt_gf8(&mut f, &vec![x.clone(); 16], &vec![x; 15]).is_err());
+        let x = DummyVal::new(0, Modulus::X4_X_1);
+        assert!(aes128_encrypt_gf8(&mut f, &vec![x.clone(); 16], &vec![x; 16]).is_err());
+    }
+
+    #[test]
+    fn garbled_kats() {
+        let (key, pt, ct) = KATS[0];
+
+        let mut b = CircuitBuilder::new();
+        let k = (0..16).map(|_| b.bin_garbler_input(8)).collect::<Vec<_>>();
+        let p = (0..16).map(|_| b.bin_evaluator_input(8)).collect::<Vec<_>>();
+        let c = aes128_encrypt(&mut b, &k, &p).unwrap();
+        for x in c.iter() {
+            b.output_bundle(x).unwrap();
+        }
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let K = en.encode_garbler_inputs(&bits(&key));
+        let P = en.encode_evaluator_inputs(&bits(&pt));
+        assert_eq!(gc.eval(&circ, &K, &P).unwrap(), bits(&ct));
+
+        let mut b = CircuitBuilder::new();
+        let k = b.garbler_inputs(&[AES_MODULUS; 16]);
+        let p = b.evaluator_inputs(&[AES_MODULUS; 16]);
+        let c = aes128_encrypt_gf8(&mut b, &k, &p).unwrap();
+        b.outputs(&c).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let to_u16 = |xs: &[u8]| xs.iter().map(|&x| x as u16).collect::<Vec<_>>();
+        let K = en.encode_garbler_inputs(&to_u16(&key));
+        let P = en.encode_evaluator_inputs(&to_u16(&pt));
+        assert_eq!(gc.eval(&circ, &K, &P).unwrap(), to_u16(&ct));
+    }
+}
@@ -0,0 +1,447 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The GIFT-64 and GIFT-128 block ciphers of Banik et al. (CHES 2017), both
+//! with 128-bit keys.
+//!
+//! Blocks and keys are either `BinaryBundle`s, least significant bit first, or
+//! nibbles, least significant first, on wires of any one `GF(2^4)` modulus,
+//! where only addition is used.
+//!
+//! On bits, each S-box is a circuit of AND gates from `utils::sbox_bits`, and
+//! the bit permutation, key schedule and round keys are free. On nibbles, each
+//! S-box is four projections of 15 ciphertexts straight to the bits of its
+//! output, which are permuted and keyed for free, then joined into nibbles
+//! again with four projections of one ciphertext. The key is split into bits
+//! once, at the cost of a further 128 projections of 15 ciphertexts.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{BinaryBundle, Fancy, HasModulus},
+    Modulus,
+};
+
+/// The GIFT S-box.
+pub const GIFT_SBOX: [u16; 16] = [
+    0x1, 0xa, 0x4, 0xc, 0x6, 0xf, 0x3, 0x9, 0x2, 0xd, 0xb, 0x7, 0x5, 0x0, 0x8, 0xe,
+];
+
+/// Encrypt the 64 bits `pt` under the 128 bits `key` with GIFT-64.
+pub fn gift64_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &BinaryBundle<F::Item>,
+    pt: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    check_bits::<F>(key, pt, 64)?;
+    encrypt(f, &Bits, key.wires().to_vec(), pt.wires(), 64).map(BinaryBundle::new)
+}
+
+/// Encrypt the 128 bits `pt` under the 128 bits `key` with GIFT-128.
+pub fn gift128_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &BinaryBundle<F::Item>,
+    pt: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    check_bits::<F>(key, pt, 128)?;
+    encrypt(f, &Bits, key.wires().to_vec(), pt.wires(), 128).map(BinaryBundle::new)
+}
+
+/// Encrypt the 16 nibbles `pt` under the 32 nibbles `key` with GIFT-64.
+pub fn gift64_encrypt_nibbles<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &[F::Item],
+    pt: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    encrypt_nibbles(f, key, pt, 64)
+}
+
+/// Encrypt the 32 nibbles `pt` under the 32 nibbles `key` with GIFT-128.
+pub fn gift128_encrypt_nibbles<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &[F::Item],
+    pt: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    encrypt_nibbles(f, key, pt, 128)
+}
+
+fn check_bits<F: Fancy + ?Sized>(
+    key: &BinaryBundle<F::Item>,
+    pt: &BinaryBundle<F::Item>,
+    n: usize,
+) -> Result<(), F::Error> {
+    for (x, needed) in [(key, 128), (pt, n)].iter() {
+        if x.size() != *needed {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: x.size(),
+                needed: *needed,
+            }));
+        }
+    }
+    check_modulus::<F>(key.wires().iter().chain(pt.wires()), Modulus::Zq { q: 2 })
+}
+
+fn encrypt_nibbles<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &[F::Item],
+    pt: &[F::Item],
+    n: usize,
+) -> Result<Vec<F::Item>, F::Error> {
+    for (xs, needed) in [(key, 32), (pt, n / 4)].iter() {
+        if xs.len() != *needed {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: *needed,
+            }));
+        }
+    }
+    let q = pt[0].modulus();
+    match q {
+        Modulus::GF4 { .. } => check_modulus::<F>(key.iter().chain(pt), q)?,
+        _ => {
+            return Err(F::Error::from(FancyError::InvalidArg(format!(
+                "expected GF(2^4) wires, got modulus {}",
+                q
+            ))))
+        }
+    }
+    let nibbles = Nibbles(q);
+    let mut k = Vec::with_capacity(128);
+    for x in key {
+        k.extend(nibbles.split(f, x, |x| x)?);
+    }
+    encrypt(f, &nibbles, k, pt, n)
+}
+
+fn check_modulus<'a, F: Fancy + ?Sized>(
+    mut xs: impl Iterator<Item = &'a F::Item>,
+    q: Modulus,
+) -> Result<(), F::Error>
+where
+    F::Item: 'a,
+{
+    if let Some(x) = xs.find(|x| x.modulus() != q) {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: x.modulus(),
+            needed: q,
+        }));
+    }
+    Ok(())
+}
+
+// One encoding of the state of GIFT.
+trait Cells<F: Fancy + ?Sized> {
+    // the bits of the state after the S-boxes
+    fn sub_cells(&self, f: &mut F, state: &[F::Item]) -> Result<Vec<F::Item>, F::Error>;
+    // the state holding the given bits
+    fn join(&self, f: &mut F, bits: Vec<F::Item>) -> Result<Vec<F::Item>, F::Error>;
+}
+
+struct Bits;
+
+impl<F: Fancy + ?Sized> Cells<F> for Bits {
+    fn sub_cells(&self, f: &mut F, state: &[F::Item]) -> Result<Vec<F::Item>, F::Error> {
+        let mut bits = Vec::with_capacity(state.len());
+        for nibble in state.chunks(4) {
+            bits.extend(utils::sbox_bits(f, nibble, &GIFT_SBOX)?);
+        }
+        Ok(bits)
+    }
+
+    fn join(&self, _f: &mut F, bits: Vec<F::Item>) -> Result<Vec<F::Item>, F::Error> {
+        Ok(bits)
+    }
+}
+
+struct Nibbles(Modulus);
+
+impl Nibbles {
+    // The bits of `g(x)`, least significant first.
+    fn split<F: Fancy + ?Sized>(
+        &self,
+        f: &mut F,
+        x: &F::Item,
+        g: impl Fn(u16) -> u16,
+    ) -> Result<Vec<F::Item>, F::Error> {
+        (0..4)
+            .map(|i| {
+                let tt = (0..16).map(|a| (g(a) >> i) & 1).collect();
+                f.proj(x, &Modulus::Zq { q: 2 }, Some(tt))
+            })
+            .collect()
+    }
+}
+
+impl<F: Fancy + ?Sized> Cells<F> for Nibbles {
+    fn sub_cells(&self, f: &mut F, state: &[F::Item]) -> Result<Vec<F::Item>, F::Error> {
+        let mut bits = Vec::with_capacity(4 * state.len());
+        for x in state {
+            bits.extend(self.split(f, x, |a| GIFT_SBOX[a as usize])?);
+        }
+        Ok(bits)
+    }
+
+    fn join(&self, f: &mut F, bits: Vec<F::Item>) -> Result<Vec<F::Item>, F::Error> {
+        bits.chunks(4)
+            .map(|bs| {
+                let terms = bs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| f.proj(b, &self.0, Some(vec![0, 1 << i])))
+                    .collect::<Result<Vec<F::Item>, F::Error>>()?;
+                f.add_many(&terms)
+            })
+            .collect()
+    }
+}
+
+// Encrypt the state `pt` of `n` bits under the key bits `k`.
+fn encrypt<F: Fancy + ?Sized, C: Cells<F>>(
+    f: &mut F,
+    cells: &C,
+    mut k: Vec<F::Item>,
+    pt: &[F::Item],
+    n: usize,
+) -> Result<Vec<F::Item>, F::Error> {
+    let rounds = if n == 64 { 28 } else { 40 };
+    let mut state = pt.to_vec();
+    let mut c = 0;
+    for _ in 0..rounds {
+        let bits = cells.sub_cells(f, &state)?;
+        let mut bits = permute_bits(bits);
+        c = ((c << 1) & 0x3f) | (((c >> 5) ^ (c >> 4) ^ 1) & 1);
+        add_round_key(f, &mut bits, &k, c)?;
+        state = cells.join(f, bits)?;
+        update_key(&mut k);
+    }
+    Ok(state)
+}
+
+// Bit `i` moves to `P(i)` of the specification.
+fn permute_bits<W>(bits: Vec<W>) -> Vec<W> {
+    let n = bits.len();
+    let mut out = bits.iter().map(|_| None).collect::<Vec<Option<W>>>();
+    for (i, b) in bits.into_iter().enumerate() {
+        let j = 4 * (i / 16) + n / 4 * ((3 * (i % 16 / 4) + i % 4) % 4) + i % 4;
+        out[j] = Some(b);
+    }
+    out.into_iter().map(Option::unwrap).collect()
+}
+
+// Add the round key from the key bits `k`, and the round constant `c`.
+fn add_round_key<F: Fancy + ?Sized>(
+    f: &mut F,
+    bits: &mut [F::Item],
+    k: &[F::Item],
+    c: u8,
+) -> Result<(), F::Error> {
+    let n = bits.len();
+    // U is key words 1, or 5 and 4, and V is key words 0, or 1 and 0
+    let (u, v, at) = if n == 64 {
+        (&k[16..32], &k[..16], 0)
+    } else {
+        (&k[64..96], &k[..32], 1)
+    };
+    for i in 0..n / 4 {
+        bits[4 * i + at + 1] = f.xor(&bits[4 * i + at + 1], &u[i])?;
+        bits[4 * i + at] = f.xor(&bits[4 * i + at], &v[i])?;
+    }
+    for j in 0..6 {
+        if (c >> j) & 1 == 1 {
+            bits[4 * j + 3] = f.negate(&bits[4 * j + 3])?;
+        }
+    }
+    bits[n - 1] = f.negate(&bits[n - 1])?;
+    Ok(())
+}
+
+// Move the key on by a round: words 7 and 6 become words 1 and 0 rotated right
+// by 2 and 12, and the other words move down by two.
+fn update_key<W>(k: &mut [W]) {
+    k[..16].rotate_left(12);
+    k[16..32].rotate_left(2);
+    k.rotate_left(32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        util,
+        BinaryGadgets,
+        BundleGadgets,
+        FancyInput,
+    };
+    use rand::{thread_rng, Rng};
+
+    // (block bits, key, plaintext, ciphertext) from the designers' reference
+    // code
+    const KATS: [(usize, u128, u128, u128); 6] = [
+        (64, 0, 0, 0xf62bc3ef34f775ac),
+        (
+            64,
+            0xfedcba9876543210fedcba9876543210,
+            0xfedcba9876543210,
+            0xc1b71f66160ff587,
+        ),
+        (
+            64,
+            0xbd91731eb6bc2713a1f9f6ffc75044e7,
+            0xc450c7727a9b8a7d,
+            0xe3272885fa94ba8b,
+        ),
+        (128, 0, 0, 0xcd0bd738388ad3f668b15a36ceb6ff92),
+        (
+            128,
+            0xfedcba9876543210fedcba9876543210,
+            0xfedcba9876543210fedcba9876543210,
+            0x8422241a6dbf5a9346af468409ee0152,
+        ),
+        (
+            128,
+            0xd0f5c59a7700d3e799028fa9f90ad837,
+            0xe39c141fa57dba43f08a85b6a91f86c1,
+            0x13ede67cbdcc3dbf400a62d6977265ea,
+        ),
+    ];
+
+    // GIFT in the clear, straight from the specification.
+    fn gift(n: usize, key: u128, pt: u128) -> u128 {
+        let word = |k: u128, i: usize| (k >> (16 * i)) as u16 as u128;
+        let mut k = key;
+        let mut state = pt;
+        let mut c = 0;
+        for _ in 0..if n == 64 { 28 } else { 40 } {
+            let mut s = 0;
+            for i in 0..n / 4 {
+                s |= (GIFT_SBOX[((state >> (4 * i)) & 0xf) as usize] as u128) << (4 * i);
+            }
+            state = 0;
+            for i in 0..n {
+                let j = 4 * (i / 16) + n / 4 * ((3 * (i % 16 / 4) + i % 4) % 4) + i % 4;
+                state |= ((s >> i) & 1) << j;
+            }
+            let (u, v, at) = if n == 64 {
+                (word(k, 1), word(k, 0), 0)
+            } else {
+                (word(k, 5) << 16 | word(k, 4), word(k, 1) << 16 | word(k, 0), 1)
+            };
+            for i in 0..n / 4 {
+                state ^= ((u >> i) & 1) << (4 * i + at + 1);
+                state ^= ((v >> i) & 1) << (4 * i + at);
+            }
+            c = ((c << 1) & 0x3f) | (((c >> 5) ^ (c >> 4) ^ 1) & 1);
+            for j in 0..6 {
+                state ^= ((c >> j) & 1) << (4 * j + 3);
+            }
+            state ^= 1 << (n - 1);
+            let rotr = |w: u128, r: usize| ((w >> r) | (w << (16 - r))) & 0xffff;
+            k = (k >> 32) | rotr(word(k, 1), 2) << 112 | rotr(word(k, 0), 12) << 96;
+        }
+        state
+    }
+
+    fn nibbles(x: u128, n: usize) -> Vec<u16> {
+        (0..n / 4).map(|i| (x >> (4 * i)) as u16 & 0xf).collect()
+    }
+
+    fn encode(xs: &[u16], q: &Modulus) -> Vec<DummyVal> {
+        xs.iter().map(|&x| DummyVal::new(x, *q)).collect()
+    }
+
+    #[test]
+    fn reference_kats() {
+        for &(n, key, pt, ct) in KATS.iter() {
+            assert_eq!(gift(n, key, pt), ct, "GIFT-{} on {:x}", n, pt);
+        }
+    }
+
+    #[test]
+    fn dummy() {
+        let mut rng = thread_rng();
+        let mut cases = KATS.to_vec();
+        for &n in &[64, 128] {
+            for _ in 0..4 {
+                let key = rng.gen::<u128>();
+                let pt = rng.gen::<u128>() >> (128 - n);
+                cases.push((n, key, pt, gift(n, key, pt)));
+            }
+        }
+        let q = Modulus::X4_X3_X2_X_1;
+        for &(n, key, pt, ct) in cases.iter() {
+            let mut f = Dummy::new();
+            let k = f.bin_encode(key, 128).unwrap();
+            let p = f.bin_encode(pt, n).unwrap();
+            let c = if n == 64 {
+                gift64_encrypt(&mut f, &k, &p).unwrap()
+            } else {
+                gift128_encrypt(&mut f, &k, &p).unwrap()
+            };
+            assert_eq!(f.bin_output(&c).unwrap().unwrap(), ct);
+
+            let k = encode(&nibbles(key, 128), &q);
+            let p = encode(&nibbles(pt, n), &q);
+            let c = if n == 64 {
+                gift64_encrypt_nibbles(&mut f, &k, &p).unwrap()
+            } else {
+                gift128_encrypt_nibbles(&mut f, &k, &p).unwrap()
+            };
+            let c = c.iter().map(|x| x.val()).collect::<Vec<_>>();
+            assert_eq!(c, nibbles(ct, n));
+        }
+        let mut f = Dummy::new();
+        let k = f.bin_encode(0, 128).unwrap();
+        let p = f.bin_encode(0, 128).unwrap();
+        assert!(gift64_encrypt(&mut f, &k, &p).is_err());
+        let k = encode(&nibbles(0, 128), &q);
+        let p = encode(&nibbles(0, 64), &Modulus::Zq { q: 16 });
+        assert!(gift64_encrypt_nibbles(&mut f, &k, &p).is_err());
+    }
+
+    #[test]
+    fn garbled() {
+        let mut rng = thread_rng();
+        let q = Modulus::X4_X_1;
+        for &n in &[64, 128] {
+            let key = rng.gen::<u128>();
+            let pt = rng.gen::<u128>() >> (128 - n);
+            let ct = gift(n, key, pt);
+
+            let mut b = CircuitBuilder::new();
+            let k = b.bin_garbler_input(128);
+            let p = b.bin_evaluator_input(n);
+            let c = if n == 64 {
+                gift64_encrypt(&mut b, &k, &p).unwrap()
+            } else {
+                gift128_encrypt(&mut b, &k, &p).unwrap()
+            };
+            b.output_bundle(&c).unwrap();
+            let circ = b.finish();
+            let (en, gc) = garble(&circ).unwrap();
+            let K = en.encode_garbler_inputs(&util::u128_to_bits(key, 128));
+            let P = en.encode_evaluator_inputs(&util::u128_to_bits(pt, n));
+            assert_eq!(gc.eval(&circ, &K, &P).unwrap(), util::u128_to_bits(ct, n));
+
+            let mut b = CircuitBuilder::new();
+            let k = b.garbler_inputs(&vec![q; 32]);
+            let p = b.evaluator_inputs(&vec![q; n / 4]);
+            let c = if n == 64 {
+                gift64_encrypt_nibbles(&mut b, &k, &p).unwrap()
+            } else {
+                gift128_encrypt_nibbles(&mut b, &k, &p).unwrap()
+            };
+            b.outputs(&c).unwrap();
+            let circ = b.finish();
+            let (en, gc) = garble(&circ).unwrap();
+            let K = en.encode_garbler_inputs(&nibbles(key, 128));
+            let P = en.encode_evaluator_inputs(&nibbles(pt, n));
+            assert_eq!(gc.eval(&circ, &K, &P).unwrap(), nibbles(ct, n));
+        }
+    }
+}
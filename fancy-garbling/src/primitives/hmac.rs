@@ -0,0 +1,180 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! HMAC of RFC 2104, generic over hash gadgets on bytes.
+//!
+//! Keys, messages, and tags are bytes, as 8-bit `BinaryBundle`s. The key and
+//! message lengths are public. XORing the pads into the key is free, so HMAC
+//! costs two hashes, three if the key is longer than a block.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{BinaryBundle, BinaryGadgets},
+};
+
+/// A hash function on bytes implemented as a gadget, as needed by `hmac`.
+pub trait HashGadget<F: BinaryGadgets> {
+    /// The number of bytes in a block.
+    const BLOCK_BYTES: usize;
+
+    /// Hash the bytes `msg`, returning the digest as bytes.
+    fn hash(
+        f: &mut F,
+        msg: &[BinaryBundle<F::Item>],
+    ) -> Result<Vec<BinaryBundle<F::Item>>, F::Error>;
+}
+
+/// Compute the HMAC of `msg` under `key` with the hash `H`.
+pub fn hmac<F: BinaryGadgets, H: HashGadget<F>>(
+    f: &mut F,
+    key: &[BinaryBundle<F::Item>],
+    msg: &[BinaryBundle<F::Item>],
+) -> Result<Vec<BinaryBundle<F::Item>>, F::Error> {
+    if let Some(b) = key.iter().find(|b| b.size() != 8) {
+        return Err(F::Error::from(FancyError::InvalidArgNum {
+            got: b.size(),
+            needed: 8,
+        }));
+    }
+    let key = if key.len() > H::BLOCK_BYTES {
+        H::hash(f, key)?
+    } else {
+        key.to_vec()
+    };
+    let inner = keyed_block(f, &key, 0x36, H::BLOCK_BYTES)?;
+    let inner = H::hash(f, &[inner, msg.to_vec()].concat())?;
+    let outer = keyed_block(f, &key, 0x5c, H::BLOCK_BYTES)?;
+    H::hash(f, &[outer, inner].concat())
+}
+
+// The key, padded with zeros to a block, XORed with `pad` in every byte.
+fn keyed_block<F: BinaryGadgets>(
+    f: &mut F,
+    key: &[BinaryBundle<F::Item>],
+    pad: u128,
+    block_bytes: usize,
+) -> Result<Vec<BinaryBundle<F::Item>>, F::Error> {
+    let mut block = key
+        .iter()
+        .map(|b| utils::xor_constant_bits(f, b.wires(), pad).map(BinaryBundle::new))
+        .collect::<Result<Vec<_>, _>>()?;
+    for _ in key.len()..block_bytes {
+        block.push(f.bin_constant_bundle(pad, 8)?);
+    }
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        primitives::sha256::Sha256,
+        util,
+        BundleGadgets,
+        FancyInput,
+    };
+    use rand::{thread_rng, Rng};
+    use sha2::Digest;
+
+    // (key, message, HMAC-SHA-256) from RFC 4231, cases 1, 2, and 6
+    fn kats() -> Vec<(Vec<u8>, Vec<u8>, &'static str)> {
+        vec![
+            (
+                vec![0x0b; 20],
+                b"Hi There".to_vec(),
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe".to_vec(),
+                b"what do ya want for nothing?".to_vec(),
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                vec![0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+        ]
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn hmac_sha256(key: &[u8], msg: &[u8]) -> Vec<u8> {
+        let mut key = if key.len() > 64 {
+            sha2::Sha256::digest(key).to_vec()
+        } else {
+            key.to_vec()
+        };
+        key.resize(64, 0);
+        let ipad = key.iter().map(|k| k ^ 0x36).chain(msg.iter().cloned());
+        let inner = sha2::Sha256::digest(&ipad.collect::<Vec<_>>());
+        let opad = key.iter().map(|k| k ^ 0x5c).chain(inner);
+        sha2::Sha256::digest(&opad.collect::<Vec<_>>()).to_vec()
+    }
+
+    fn dummy_hmac(key: &[u8], msg: &[u8]) -> Vec<u8> {
+        let mut f = Dummy::new();
+        let bytes = |xs: &[u8]| xs.iter().map(|&x| x as u128).collect::<Vec<_>>();
+        let k = f.bin_encode_many(&bytes(key), 8).unwrap();
+        let m = f.bin_encode_many(&bytes(msg), 8).unwrap();
+        let t = hmac::<_, Sha256>(&mut f, &k, &m).unwrap();
+        f.bin_outputs(&t).unwrap().unwrap().iter().map(|&x| x as u8).collect()
+    }
+
+    #[test]
+    fn dummy_kats() {
+        for (key, msg, tag) in kats() {
+            assert_eq!(hex(&hmac_sha256(&key, &msg)), tag);
+            assert_eq!(hex(&dummy_hmac(&key, &msg)), tag);
+        }
+        let mut rng = thread_rng();
+        for &(klen, mlen) in &[(0, 0), (64, 63), (65, 64), (32, 130)] {
+            let key = (0..klen).map(|_| rng.gen()).collect::<Vec<u8>>();
+            let msg = (0..mlen).map(|_| rng.gen()).collect::<Vec<u8>>();
+            assert_eq!(dummy_hmac(&key, &msg), hmac_sha256(&key, &msg));
+        }
+        let mut f = Dummy::new();
+        let k = f.bin_encode_many(&[0, 0], 7).unwrap();
+        assert!(hmac::<_, Sha256>(&mut f, &k, &[]).is_err());
+    }
+
+    #[test]
+    fn garbled_kats() {
+        let (key, msg, tag) = kats().swap_remove(1);
+        let mut b = CircuitBuilder::new();
+        let k = (0..key.len())
+            .map(|_| b.bin_garbler_input(8))
+            .collect::<Vec<_>>();
+        let m = (0..msg.len())
+            .map(|_| b.bin_evaluator_input(8))
+            .collect::<Vec<_>>();
+        let t = hmac::<_, Sha256>(&mut b, &k, &m).unwrap();
+        for x in t.iter() {
+            b.output_bundle(x).unwrap();
+        }
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let bits = |xs: &[u8]| {
+            xs.iter()
+                .flat_map(|&x| util::u128_to_bits(x as u128, 8))
+                .collect::<Vec<_>>()
+        };
+        let K = en.encode_garbler_inputs(&bits(&key));
+        let M = en.encode_evaluator_inputs(&bits(&msg));
+        let out = gc.eval(&circ, &K, &M).unwrap();
+        let out = out
+            .chunks(8)
+            .map(|bs| util::u128_from_bits(bs) as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(hex(&out), tag);
+    }
+}
@@ -0,0 +1,263 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! S-box lookups on `GF(2^k)` wires by evaluating the interpolating
+//! polynomial of the table, without projections.
+//!
+//! Every function on `GF(q)` is a polynomial of degree below `q`, whose
+//! coefficients `SboxPolynomial::interpolate` computes from the table. The
+//! gadget evaluates it with the Paterson-Stockmeyer method: the powers `x^i`
+//! for `i <= m` are computed with field `mul`, the polynomial is split into
+//! blocks of `m` coefficients, each a free combination of `cmul` and `add`
+//! gates, and the blocks are joined by Horner's rule in `x^m`. A polynomial of
+//! degree `n` thus takes about `2 * sqrt(n)` multiplications, 30 for the AES
+//! S-box.
+//!
+//! With the half-gate garbler, a field multiplication costs `2 * (q - 1)`
+//! ciphertexts and a projection `q - 1`, so this only pays off against
+//! `utils::sbox` where projections are unavailable or dearer than
+//! multiplications; `num_muls` gives the count to compare.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{Fancy, HasModulus},
+    Modulus,
+};
+
+/// The polynomial over `GF(2^k)` interpolating an S-box table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SboxPolynomial {
+    modulus: Modulus,
+    coefficients: Vec<u16>,
+}
+
+impl SboxPolynomial {
+    /// Interpolate `table`, which maps every element of the field `modulus`
+    /// of `2^k` elements, for `k <= 8`, to another.
+    pub fn interpolate(table: &[u16], modulus: &Modulus) -> Result<SboxPolynomial, FancyError> {
+        match modulus {
+            Modulus::GF4 { .. } | Modulus::GF8 { .. } => {}
+            Modulus::GFk { k, .. } if *k <= 8 => {}
+            _ => {
+                return Err(FancyError::InvalidArg(format!(
+                    "interpolation needs a binary field of at most 2^8 elements, got {}",
+                    modulus
+                )))
+            }
+        }
+        let n = modulus.size() as usize;
+        if table.len() != n || table.iter().any(|&y| y as usize >= n) {
+            return Err(FancyError::InvalidTruthTable);
+        }
+
+        // the indicator of `a` is 1 + (x + a)^(n-1), and in characteristic 2
+        // (x + a)^(n-1) is the sum of x^i a^(n-1-i), so x^i has the
+        // coefficient sum of table[a] a^(n-1-i) for i >= 1, with 0^0 = 1
+        let mut coefficients = vec![0; n];
+        coefficients[0] = table[0];
+        for (a, &y) in table.iter().enumerate() {
+            if y == 0 {
+                continue;
+            }
+            let mut p = y;
+            for i in (1..n).rev() {
+                coefficients[i] ^= p;
+                p = utils::modulus_mul(p, a as u16, modulus);
+            }
+        }
+        Ok(SboxPolynomial {
+            modulus: *modulus,
+            coefficients,
+        })
+    }
+
+    /// The field of the coefficients and of the wires.
+    pub fn modulus(&self) -> Modulus {
+        self.modulus
+    }
+
+    /// The coefficients, of the constant term first.
+    pub fn coefficients(&self) -> &[u16] {
+        &self.coefficients
+    }
+
+    /// The degree, or zero for the zero polynomial.
+    pub fn degree(&self) -> usize {
+        self.coefficients.iter().rposition(|&c| c != 0).unwrap_or(0)
+    }
+
+    /// The number of field multiplications of `eval`.
+    pub fn num_muls(&self) -> usize {
+        muls(self.degree(), self.block_size())
+    }
+
+    /// Evaluate the polynomial on the plaintext `x`.
+    pub fn eval_plain(&self, x: u16) -> u16 {
+        self.coefficients.iter().rev().fold(0, |acc, &c| {
+            utils::modulus_add(utils::modulus_mul(acc, x, &self.modulus), c, &self.modulus)
+        })
+    }
+
+    /// Evaluate the polynomial on the wire `x`.
+    pub fn eval<F: Fancy + ?Sized>(&self, f: &mut F, x: &F::Item) -> Result<F::Item, F::Error> {
+        if x.modulus() != self.modulus {
+            return Err(F::Error::from(FancyError::InvalidArgMod {
+                got: x.modulus(),
+                needed: self.modulus,
+            }));
+        }
+        let n = self.degree();
+        let m = self.block_size();
+        let nblocks = n / m + 1;
+        let top = if nblocks == 1 { n } else { m };
+
+        // powers[i] = x^(i + 1)
+        let mut powers = vec![x.clone()];
+        for i in 2..=top {
+            let h = i / 2;
+            let p = f.mul(&powers[h - 1], &powers[i - h - 1])?;
+            powers.push(p);
+        }
+
+        let mut acc: Option<F::Item> = None;
+        for j in (0..nblocks).rev() {
+            let end = (j * m + m).min(n + 1);
+            let block = self.block(f, &powers, j * m, end)?;
+            acc = Some(match acc {
+                None => block,
+                Some(a) => {
+                    let a = f.mul(&a, &powers[m - 1])?;
+                    f.add(&a, &block)?
+                }
+            });
+        }
+        Ok(acc.unwrap())
+    }
+
+    // The number of coefficients per block that minimizes the multiplications.
+    fn block_size(&self) -> usize {
+        let n = self.degree();
+        (1..=n + 1).min_by_key(|&m| (muls(n, m), m)).unwrap()
+    }
+
+    // Sum the terms of degree `start` to `end - 1`, divided by x^start, which
+    // takes the powers up to x^(end - start - 1).
+    fn block<F: Fancy + ?Sized>(
+        &self,
+        f: &mut F,
+        powers: &[F::Item],
+        start: usize,
+        end: usize,
+    ) -> Result<F::Item, F::Error> {
+        let mut terms = Vec::new();
+        for (i, &c) in self.coefficients[start..end].iter().enumerate().skip(1) {
+            match c {
+                0 => {}
+                1 => terms.push(powers[i - 1].clone()),
+                c => terms.push(f.cmul(&powers[i - 1], c)?),
+            }
+        }
+        let c = self.coefficients[start];
+        match terms.len() {
+            0 => f.constant(c, &self.modulus),
+            1 => utils::add_constant(f, &terms[0], c),
+            _ => {
+                let sum = f.add_many(&terms)?;
+                utils::add_constant(f, &sum, c)
+            }
+        }
+    }
+}
+
+// The multiplications for degree `n` and blocks of `m` coefficients: the
+// powers up to x^n for a single block, otherwise up to x^m and one per block
+// after the first.
+fn muls(n: usize, m: usize) -> usize {
+    let nblocks = n / m + 1;
+    if nblocks == 1 {
+        n.saturating_sub(1)
+    } else {
+        (m - 1) + (nblocks - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        informer::{Informer, InformerVal},
+        primitives::utils::{AES_SBOX, PRESENT_SBOX},
+        util::RngExt,
+    };
+    use rand::thread_rng;
+
+    #[test]
+    fn interpolates_tables() {
+        let mut rng = thread_rng();
+        let moduli = [
+            Modulus::GFk { k: 2, p: 0b111 },
+            Modulus::GFk { k: 3, p: 0b1011 },
+            Modulus::X4_X_1,
+            Modulus::GF8 { p: 0b100011011 },
+        ];
+        for q in moduli.iter() {
+            let n = q.size();
+            let table = (0..n).map(|_| rng.gen_u16() % n).collect::<Vec<_>>();
+            let poly = SboxPolynomial::interpolate(&table, q).unwrap();
+            for x in 0..n {
+                assert_eq!(poly.eval_plain(x), table[x as usize]);
+            }
+        }
+
+        let poly = SboxPolynomial::interpolate(&[0, 1, 2, 3], &moduli[0]).unwrap();
+        assert_eq!(poly.degree(), 1);
+        assert_eq!(poly.num_muls(), 0);
+        let poly = SboxPolynomial::interpolate(&[5; 16], &moduli[2]).unwrap();
+        assert_eq!(poly.degree(), 0);
+    }
+
+    #[test]
+    fn dummy_matches_table() {
+        let q = Modulus::GF8 { p: 0b100011011 };
+        let poly = SboxPolynomial::interpolate(&AES_SBOX, &q).unwrap();
+        assert_eq!(poly.degree(), 254);
+        assert_eq!(poly.num_muls(), 30);
+        for x in 0..256 {
+            let mut f = Informer::new(Dummy::new());
+            let x = InformerVal::new(DummyVal::new(x, q));
+            let y = poly.eval(&mut f, &x).unwrap();
+            assert_eq!(y.val(), AES_SBOX[x.val() as usize]);
+            assert_eq!(f.stats().num_muls(), poly.num_muls());
+            assert_eq!(f.stats().num_projs(), 0);
+        }
+
+        let mut f = Dummy::new();
+        assert!(poly.eval(&mut f, &DummyVal::new(0, Modulus::X4_X_1)).is_err());
+        assert!(SboxPolynomial::interpolate(&PRESENT_SBOX, &q).is_err());
+        assert!(SboxPolynomial::interpolate(&AES_SBOX, &Modulus::Zq { q: 256 }).is_err());
+        assert!(SboxPolynomial::interpolate(&[0, 1, 2, 4], &Modulus::GFk { k: 2, p: 7 }).is_err());
+    }
+
+    #[test]
+    fn garbled_matches_table() {
+        let q = Modulus::X4_X_1;
+        let poly = SboxPolynomial::interpolate(&PRESENT_SBOX, &q).unwrap();
+        let mut b = CircuitBuilder::new();
+        let x = b.evaluator_input(&q);
+        let y = poly.eval(&mut b, &x).unwrap();
+        b.output(&y).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        for x in 0..16 {
+            let X = en.encode_evaluator_inputs(&[x]);
+            assert_eq!(gc.eval(&circ, &[], &X).unwrap(), vec![PRESENT_SBOX[x as usize]]);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The LED block cipher of Guo, Peyrin, Poschmann and Robshaw (CHES 2011),
+//! with 64- and 128-bit keys.
+//!
+//! Blocks and keys are nibbles on `GF(2^4)` wires of modulus `LED_MODULUS`,
+//! the first, most significant, nibble first; the 16 nibbles of a block are
+//! its 4-by-4 state row-major. LED is an `SpnCipher` with the rounds of
+//! PHOTON and the LED round constants, so only `SubCells` costs ciphertexts,
+//! one projection per cell, and the key is added every four rounds.
+
+use super::{
+    spn::{spn_encrypt, SpnCipher},
+    utils,
+};
+use crate::{errors::FancyError, fancy::Fancy, Modulus};
+
+/// The field of the LED state.
+pub const LED_MODULUS: Modulus = Modulus::X4_X_1;
+
+// The last row of the serial matrix of MixColumnsSerial.
+const Z: [u16; 4] = [4, 1, 2, 2];
+
+/// The LED cipher for keys of `key_nibbles` nibbles, 16 or 32, as an
+/// `SpnCipher` keyed every four rounds, with its constants fused into the
+/// S-box tables.
+pub fn led_cipher(key_nibbles: usize) -> SpnCipher {
+    let ks = 4 * key_nibbles as u16;
+    let rounds = if key_nibbles == 16 { 32 } else { 48 };
+    let mut rc = 0;
+    let round_constants = (0..rounds)
+        .map(|_| {
+            rc = ((rc << 1) & 0x3f) | (((rc >> 5) ^ (rc >> 4) ^ 1) & 1);
+            // the first column gets the key size, the second the round constant
+            let mut cs = vec![0; 16];
+            for i in 0..4 {
+                cs[4 * i] = i as u16 ^ (ks >> (4 * (1 - i / 2)) & 0xf);
+                cs[4 * i + 1] = if i % 2 == 0 { rc >> 3 } else { rc & 7 };
+            }
+            cs
+        })
+        .collect();
+    SpnCipher {
+        d: 4,
+        modulus: LED_MODULUS,
+        sbox: utils::PRESENT_SBOX.to_vec(),
+        shift_rows: vec![0, 1, 2, 3],
+        mds_row: Z.to_vec(),
+        round_constants,
+        key_period: Some(4),
+        fuse_constants: true,
+    }
+}
+
+/// Encrypt the 16 nibbles `pt` under the 16 or 32 nibbles `key` with LED-64 or
+/// LED-128.
+pub fn led_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &[F::Item],
+    pt: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    if key.len() != 16 && key.len() != 32 {
+        return Err(F::Error::from(FancyError::InvalidArg(format!(
+            "LED keys have 16 or 32 nibbles, not {}",
+            key.len()
+        ))));
+    }
+    // LED-128 alternates the two halves of its key
+    let round_keys = key.chunks(16).map(|k| k.to_vec()).collect::<Vec<_>>();
+    spn_encrypt(f, &led_cipher(key.len()), &round_keys, pt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+    };
+
+    // (key, plaintext, ciphertext) from the LED specification
+    const KATS: [(&str, &str, &str); 4] = [
+        ("0000000000000000", "0000000000000000", "39c2401003a0c798"),
+        ("0123456789abcdef", "0123456789abcdef", "a003551e3893fc58"),
+        (
+            "00000000000000000000000000000000",
+            "0000000000000000",
+            "3decb2a0850cdba1",
+        ),
+        (
+            "0123456789abcdef0123456789abcdef",
+            "0123456789abcdef",
+            "d6b824587f014fc2",
+        ),
+    ];
+
+    fn nibbles(s: &str) -> Vec<u16> {
+        s.chars().map(|c| c.to_digit(16).unwrap() as u16).collect()
+    }
+
+    #[test]
+    fn dummy_kats() {
+        let mut f = Dummy::new();
+        let enc = |xs: &[u16]| {
+            xs.iter()
+                .map(|&x| DummyVal::new(x, LED_MODULUS))
+                .collect::<Vec<_>>()
+        };
+        for (key, pt, ct) in KATS.iter() {
+            let c = led_encrypt(&mut f, &enc(&nibbles(key)), &enc(&nibbles(pt))).unwrap();
+            let c = c.iter().map(|x| x.val()).collect::<Vec<_>>();
+            assert_eq!(c, nibbles(ct), "LED on key {}", key);
+        }
+        let x = DummyVal::new(0, LED_MODULUS);
+        assert!(led_encrypt(&mut f, &vec![x.clone(); 20], &vec![x.clone(); 16]).is_err());
+        assert!(led_encrypt(&mut f, &vec![x.clone(); 16], &vec![x; 15]).is_err());
+        let x = DummyVal::new(0, Modulus::X4_X3_X2_X_1);
+        assert!(led_encrypt(&mut f, &vec![x.clone(); 16], &vec![x; 16]).is_err());
+    }
+
+    #[test]
+    fn garbled_kats() {
+        for (key, pt, ct) in KATS[1..].iter().step_by(2) {
+            let (key, pt) = (nibbles(key), nibbles(pt));
+            let mut b = CircuitBuilder::new();
+            let k = b.garbler_inputs(&vec![LED_MODULUS; key.len()]);
+            let p = b.evaluator_inputs(&[LED_MODULUS; 16]);
+            let c = led_encrypt(&mut b, &k, &p).unwrap();
+            b.outputs(&c).unwrap();
+            let circ = b.finish();
+            let (en, gc) = garble(&circ).unwrap();
+            let K = en.encode_garbler_inputs(&key);
+            let P = en.encode_evaluator_inputs(&pt);
+            assert_eq!(gc.eval(&circ, &K, &P).unwrap(), nibbles(ct));
+        }
+    }
+}
@@ -0,0 +1,452 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Linear layers given by a full public matrix over `GF(2^k)` or `Zq`.
+//!
+//! `LinearLayer::new` compiles the matrix into a straight-line program of
+//! `cmul` and `add` gates. Every product of an input with a constant is
+//! computed once, then sums shared between rows are factored out with Paar's
+//! greedy heuristic: as long as some pair of terms appears in two rows or
+//! more, the most frequent pair is added once and replaces the pair in every
+//! row containing it. On the bits of `GF(2^k)` cells, from
+//! `LinearLayer::binary`, this is the usual XOR-count heuristic; the AES
+//! `MixColumns` matrix takes 108 XORs instead of 152.
+//!
+//! Both gates are free, so a linear layer costs no ciphertexts either way.
+
+use crate::{
+    errors::FancyError,
+    fancy::Fancy,
+    primitives::utils,
+    Modulus,
+};
+use std::{cmp::Reverse, collections::HashMap};
+
+/// An operation of a `LinearLayer`. Operations read earlier wires: the inputs
+/// come first, followed by the output of every operation in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinearOp {
+    /// The product of input `j` with a constant other than zero or one.
+    Cmul(usize, u16),
+    /// The sum of two wires.
+    Add(usize, usize),
+}
+
+/// A public matrix, multiplied with vectors of wires by a program of `cmul`
+/// and `add` gates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinearLayer {
+    matrix: Vec<Vec<u16>>,
+    modulus: Modulus,
+    ops: Vec<LinearOp>,
+    outputs: Vec<Option<usize>>,
+}
+
+impl LinearLayer {
+    /// Compile `matrix`, given row by row, over `modulus`, which is also the
+    /// modulus of the wires it is applied to.
+    pub fn new(matrix: Vec<Vec<u16>>, modulus: Modulus) -> Result<LinearLayer, FancyError> {
+        let ncols = match matrix.first() {
+            Some(row) if !row.is_empty() => row.len(),
+            _ => return Err(FancyError::InvalidArgNum { got: 0, needed: 1 }),
+        };
+        if let Some(row) = matrix.iter().find(|row| row.len() != ncols) {
+            return Err(FancyError::InvalidArgNum {
+                got: row.len(),
+                needed: ncols,
+            });
+        }
+        if let Some(&c) = matrix.concat().iter().find(|&&c| c as u32 >= modulus.order()) {
+            return Err(FancyError::InvalidArg(format!(
+                "matrix entry {} is not an element of {}",
+                c, modulus
+            )));
+        }
+        let (ops, outputs) = compile(&matrix);
+        Ok(LinearLayer {
+            matrix,
+            modulus,
+            ops,
+            outputs,
+        })
+    }
+
+    /// Compile `matrix` over the binary field `modulus` as a matrix over
+    /// `GF(2)` acting on the bits of each cell, least significant first, as
+    /// given by `utils::binary_matrix`.
+    pub fn binary(matrix: &[Vec<u16>], modulus: &Modulus) -> Result<LinearLayer, FancyError> {
+        match modulus {
+            Modulus::GF4 { .. }
+            | Modulus::GF8 { .. }
+            | Modulus::GFk { .. }
+            | Modulus::GF16 { .. } => {
+                LinearLayer::new(utils::binary_matrix(matrix, modulus), Modulus::Zq { q: 2 })
+            }
+            _ => Err(FancyError::InvalidArg(format!(
+                "{} is not a binary field",
+                modulus
+            ))),
+        }
+    }
+
+    /// Compile `Serial(z)^d` over `modulus`, as in `utils::serial_matrix_power`.
+    pub fn serial(z: &[u16], modulus: &Modulus) -> Result<LinearLayer, FancyError> {
+        if z.is_empty() {
+            return Err(FancyError::InvalidArgNum { got: 0, needed: 1 });
+        }
+        LinearLayer::new(utils::serial_matrix_power(z, modulus), *modulus)
+    }
+
+    /// The matrix, row by row.
+    pub fn matrix(&self) -> &[Vec<u16>] {
+        &self.matrix
+    }
+
+    /// The modulus of the matrix and of the wires.
+    pub fn modulus(&self) -> Modulus {
+        self.modulus
+    }
+
+    /// The number of inputs.
+    pub fn ninputs(&self) -> usize {
+        self.matrix[0].len()
+    }
+
+    /// The number of outputs.
+    pub fn noutputs(&self) -> usize {
+        self.matrix.len()
+    }
+
+    /// The operations, in order.
+    pub fn ops(&self) -> &[LinearOp] {
+        &self.ops
+    }
+
+    /// The wire holding every output, or `None` for a zero row.
+    pub fn outputs(&self) -> &[Option<usize>] {
+        &self.outputs
+    }
+
+    /// The number of `add` gates.
+    pub fn num_adds(&self) -> usize {
+        self.ops
+            .iter()
+            .filter(|op| matches!(op, LinearOp::Add(..)))
+            .count()
+    }
+
+    /// The number of `cmul` gates.
+    pub fn num_cmuls(&self) -> usize {
+        self.ops.len() - self.num_adds()
+    }
+
+    /// Multiply the matrix with the plaintext vector `xs`.
+    pub fn eval_plain(&self, xs: &[u16]) -> Vec<u16> {
+        assert_eq!(xs.len(), self.ninputs());
+        let mut wires = xs.to_vec();
+        for op in self.ops.iter() {
+            let z = match *op {
+                LinearOp::Cmul(j, c) => utils::modulus_mul(wires[j], c, &self.modulus),
+                LinearOp::Add(a, b) => utils::modulus_add(wires[a], wires[b], &self.modulus),
+            };
+            wires.push(z);
+        }
+        self.outputs
+            .iter()
+            .map(|w| w.map_or(0, |w| wires[w]))
+            .collect()
+    }
+
+    /// Multiply the matrix with the vector of wires `xs`.
+    pub fn apply<F: Fancy + ?Sized>(
+        &self,
+        f: &mut F,
+        xs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error> {
+        if xs.len() != self.ninputs() {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: self.ninputs(),
+            }));
+        }
+        let mut wires = xs.to_vec();
+        for op in self.ops.iter() {
+            let z = match *op {
+                LinearOp::Cmul(j, c) => f.cmul(&wires[j], c)?,
+                LinearOp::Add(a, b) => f.add(&wires[a], &wires[b])?,
+            };
+            wires.push(z);
+        }
+        self.outputs
+            .iter()
+            .map(|w| match w {
+                Some(w) => Ok(wires[*w].clone()),
+                None => f.constant(0, &self.modulus),
+            })
+            .collect()
+    }
+
+    /// Multiply every column of the row-major `state`, of `w` wires per cell,
+    /// with the matrix, which must be square and act on the `w` wires of every
+    /// cell of a column, from the top.
+    pub fn apply_columns<F: Fancy + ?Sized>(
+        &self,
+        f: &mut F,
+        state: &[F::Item],
+        w: usize,
+    ) -> Result<Vec<F::Item>, F::Error> {
+        let n = self.ninputs();
+        if self.noutputs() != n || w == 0 || n % w != 0 {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: self.noutputs(),
+                needed: n,
+            }));
+        }
+        if state.len() % n != 0 {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: state.len(),
+                needed: n * (state.len() / n + 1),
+            }));
+        }
+        let ncols = state.len() / n;
+        let mut out = state.to_vec();
+        for j in 0..ncols {
+            let col = (0..n / w)
+                .flat_map(|i| state[(i * ncols + j) * w..][..w].iter().cloned())
+                .collect::<Vec<_>>();
+            for (t, x) in self.apply(f, &col)?.into_iter().enumerate() {
+                out[((t / w) * ncols + j) * w + t % w] = x;
+            }
+        }
+        Ok(out)
+    }
+}
+
+// Compile `matrix` into operations and the wire of every output.
+fn compile(matrix: &[Vec<u16>]) -> (Vec<LinearOp>, Vec<Option<usize>>) {
+    let ninputs = matrix[0].len();
+    let mut ops = Vec::new();
+
+    // each row as the set of terms it sums, sharing the products between rows
+    let mut products = HashMap::new();
+    let mut rows = Vec::with_capacity(matrix.len());
+    for row in matrix.iter() {
+        let mut terms = Vec::new();
+        for (j, &c) in row.iter().enumerate() {
+            match c {
+                0 => {}
+                1 => terms.push(j),
+                c => terms.push(*products.entry((j, c)).or_insert_with(|| {
+                    ops.push(LinearOp::Cmul(j, c));
+                    ninputs + ops.len() - 1
+                })),
+            }
+        }
+        rows.push(terms);
+    }
+
+    // Paar's heuristic: add the pair of terms shared by the most rows, the
+    // first such pair on ties, until no pair is shared
+    loop {
+        let mut counts = HashMap::new();
+        for row in rows.iter() {
+            for (s, &a) in row.iter().enumerate() {
+                for &b in row[s + 1..].iter() {
+                    *counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+                }
+            }
+        }
+        let best = counts
+            .into_iter()
+            .filter(|&(_, n)| n >= 2)
+            .max_by_key(|&(pair, n)| (n, Reverse(pair)));
+        let (a, b) = match best {
+            Some((pair, _)) => pair,
+            None => break,
+        };
+        ops.push(LinearOp::Add(a, b));
+        let sum = ninputs + ops.len() - 1;
+        for row in rows.iter_mut() {
+            if row.contains(&a) && row.contains(&b) {
+                row.retain(|&t| t != a && t != b);
+                row.push(sum);
+            }
+        }
+    }
+
+    let outputs = rows
+        .into_iter()
+        .map(|row| {
+            let (&first, rest) = row.split_first()?;
+            Some(rest.iter().fold(first, |acc, &t| {
+                ops.push(LinearOp::Add(acc, t));
+                ninputs + ops.len() - 1
+            }))
+        })
+        .collect();
+    (ops, outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        informer::{Informer, InformerVal},
+        util::RngExt,
+    };
+    use rand::{thread_rng, Rng};
+
+    fn plain(matrix: &[Vec<u16>], xs: &[u16], modulus: &Modulus) -> Vec<u16> {
+        matrix
+            .iter()
+            .map(|row| {
+                row.iter().zip(xs).fold(0, |acc, (&c, &x)| {
+                    utils::modulus_add(acc, utils::modulus_mul(c, x, modulus), modulus)
+                })
+            })
+            .collect()
+    }
+
+    // Direct multiplication as in `utils::matrix_vec_mul`.
+    fn naive_adds(matrix: &[Vec<u16>]) -> usize {
+        matrix
+            .iter()
+            .map(|row| row.iter().filter(|&&c| c != 0).count().saturating_sub(1))
+            .sum()
+    }
+
+    #[test]
+    fn random_matrices() {
+        let mut rng = thread_rng();
+        let moduli = [
+            Modulus::GF4 { p: 0b10011 },
+            Modulus::GF8 { p: 0b100011011 },
+            Modulus::Zq { q: 2 },
+            Modulus::Zq { q: 97 },
+        ];
+        for modulus in moduli.iter() {
+            for _ in 0..8 {
+                let (nrows, ncols) = (rng.gen_range(1, 8), rng.gen_range(1, 8));
+                let matrix = (0..nrows)
+                    .map(|_| {
+                        (0..ncols)
+                            .map(|_| match rng.gen_range(0, 3) {
+                                0 => 0,
+                                1 => 1,
+                                _ => rng.gen_u16() % modulus.size(),
+                            })
+                            .collect()
+                    })
+                    .collect::<Vec<Vec<u16>>>();
+                let layer = LinearLayer::new(matrix.clone(), *modulus).unwrap();
+                assert!(layer.num_adds() <= naive_adds(&matrix));
+                let xs = (0..ncols)
+                    .map(|_| rng.gen_u16() % modulus.size())
+                    .collect::<Vec<_>>();
+                let expected = plain(&matrix, &xs, modulus);
+                assert_eq!(layer.eval_plain(&xs), expected);
+
+                let mut f = Informer::new(Dummy::new());
+                let ws = xs
+                    .iter()
+                    .map(|&x| InformerVal::new(DummyVal::new(x, *modulus)))
+                    .collect::<Vec<_>>();
+                let ys = layer.apply(&mut f, &ws).unwrap();
+                assert_eq!(ys.iter().map(|y| y.val()).collect::<Vec<_>>(), expected);
+                assert_eq!(f.stats().num_adds(), layer.num_adds());
+                assert_eq!(f.stats().num_cmuls(), layer.num_cmuls());
+            }
+        }
+    }
+
+    #[test]
+    fn aes_mix_columns_xor_count() {
+        let q = Modulus::GF8 { p: 0b100011011 };
+        let matrix = (0..4)
+            .map(|i| (0..4).map(|j| [2, 3, 1, 1][(j + 4 - i) % 4]).collect())
+            .collect::<Vec<Vec<u16>>>();
+        let layer = LinearLayer::binary(&matrix, &q).unwrap();
+        assert_eq!(naive_adds(layer.matrix()), 152);
+        assert_eq!(layer.num_adds(), 108);
+        assert_eq!(layer.num_cmuls(), 0);
+
+        let mut rng = thread_rng();
+        let xs = (0..4).map(|_| rng.gen_u16() % 256).collect::<Vec<_>>();
+        let bits = xs
+            .iter()
+            .flat_map(|&x| (0..8).map(move |i| (x >> i) & 1))
+            .collect::<Vec<_>>();
+        let ys = layer
+            .eval_plain(&bits)
+            .chunks(8)
+            .map(|bs| bs.iter().rev().fold(0, |acc, b| acc << 1 | b))
+            .collect::<Vec<_>>();
+        assert_eq!(ys, plain(&matrix, &xs, &q));
+    }
+
+    #[test]
+    fn columns() {
+        let mut rng = thread_rng();
+        let q = Modulus::GF4 { p: 0b10011 };
+        let z = [1, 2, 1, 4];
+        let layer = LinearLayer::serial(&z, &q).unwrap();
+        let state = (0..16).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let mut f = Dummy::new();
+        let ws = state
+            .iter()
+            .map(|&x| DummyVal::new(x, q))
+            .collect::<Vec<_>>();
+        let out = layer.apply_columns(&mut f, &ws, 1).unwrap();
+        for j in 0..4 {
+            let col = (0..4).map(|i| state[i * 4 + j]).collect::<Vec<_>>();
+            let expected = layer.eval_plain(&col);
+            for i in 0..4 {
+                assert_eq!(out[i * 4 + j].val(), expected[i]);
+            }
+        }
+        assert!(layer.apply_columns(&mut f, &ws[..15], 1).is_err());
+        assert!(layer.apply_columns(&mut f, &ws, 3).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_matrices() {
+        let q = Modulus::GF4 { p: 0b10011 };
+        assert!(LinearLayer::new(vec![], q).is_err());
+        assert!(LinearLayer::new(vec![vec![]], q).is_err());
+        assert!(LinearLayer::new(vec![vec![1, 2], vec![3]], q).is_err());
+        assert!(LinearLayer::new(vec![vec![1, 16]], q).is_err());
+        assert!(LinearLayer::binary(&[vec![1]], &Modulus::Zq { q: 5 }).is_err());
+
+        let layer = LinearLayer::new(vec![vec![0, 0], vec![1, 3]], q).unwrap();
+        assert_eq!(layer.outputs()[0], None);
+        let mut f = Dummy::new();
+        assert!(layer.apply(&mut f, &[DummyVal::new(1, q)]).is_err());
+    }
+
+    #[test]
+    fn garbled() {
+        let mut rng = thread_rng();
+        let q = Modulus::GF8 { p: 0b100011011 };
+        let matrix = (0..4)
+            .map(|i| (0..4).map(|j| [2, 3, 1, 1][(j + 4 - i) % 4]).collect())
+            .collect::<Vec<Vec<u16>>>();
+        let layer = LinearLayer::new(matrix, q).unwrap();
+
+        let mut b = CircuitBuilder::new();
+        let xs = b.evaluator_inputs(&[q; 4]);
+        let ys = layer.apply(&mut b, &xs).unwrap();
+        b.outputs(&ys).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+
+        let inputs = (0..4).map(|_| rng.gen_u16() % 256).collect::<Vec<_>>();
+        let X = en.encode_evaluator_inputs(&inputs);
+        assert_eq!(gc.eval(&circ, &[], &X).unwrap(), layer.eval_plain(&inputs));
+    }
+}
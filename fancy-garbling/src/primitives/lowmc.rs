@@ -0,0 +1,478 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The LowMC block ciphers of Albrecht et al. (EUROCRYPT 2015), designed for
+//! few AND gates.
+//!
+//! Blocks and keys are `BinaryBundle`s, least significant bit first. Each round
+//! applies `m` 3-bit S-boxes to the lowest `3m` bits of the state, then a
+//! linear layer, a round constant and a round key. Only the S-boxes cost AND
+//! gates, three each, while the linear layer and the round keys are free.
+//!
+//! The matrices and constants of an instance are random, and are given by a
+//! `LowMcParams`, read from the parameter files of the reference
+//! implementation or generated afresh.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{BinaryBundle, Fancy, HasModulus},
+    Modulus,
+};
+use rand::Rng;
+use std::fmt;
+
+/// The LowMC S-box on 3 bits.
+pub const LOWMC_SBOX: [u16; 8] = [0x0, 0x1, 0x3, 0x6, 0x7, 0x4, 0x5, 0x2];
+
+// A matrix over GF(2) as its rows. Entry `j` of row `i` is the coefficient of
+// input bit `j` in output bit `i`.
+type Matrix = Vec<Vec<bool>>;
+
+/// The matrices and constants of a LowMC instance.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LowMcParams {
+    sboxes: usize,
+    linear: Vec<Matrix>,
+    constants: Vec<Vec<bool>>,
+    key_matrices: Vec<Matrix>,
+}
+
+impl LowMcParams {
+    /// Create an instance with `sboxes` S-boxes per round from its linear
+    /// layers, round constants, and round key matrices, of which there is one
+    /// more than there are rounds.
+    ///
+    /// The block size is the number of rows of the matrices, and the key size
+    /// the number of columns of the key matrices.
+    pub fn new(
+        sboxes: usize,
+        linear: Vec<Vec<Vec<bool>>>,
+        constants: Vec<Vec<bool>>,
+        key_matrices: Vec<Vec<Vec<bool>>>,
+    ) -> Result<Self, FancyError> {
+        let rounds = linear.len();
+        if constants.len() != rounds || key_matrices.len() != rounds + 1 {
+            return Err(FancyError::InvalidArg(format!(
+                "{} linear layers need as many round constants and one more round key \
+                 matrix, got {} and {}",
+                rounds,
+                constants.len(),
+                key_matrices.len()
+            )));
+        }
+        let n = key_matrices[0].len();
+        let k = key_matrices[0].first().map_or(0, Vec::len);
+        if n == 0 || k == 0 || 3 * sboxes > n {
+            return Err(FancyError::InvalidArg(format!(
+                "cannot fit {} S-boxes in blocks of {} bits with keys of {} bits",
+                sboxes, n, k
+            )));
+        }
+        let shapes = linear
+            .iter()
+            .map(|l| (l, n))
+            .chain(key_matrices.iter().map(|l| (l, k)));
+        for (m, cols) in shapes {
+            if m.len() != n || m.iter().any(|row| row.len() != cols) {
+                return Err(FancyError::InvalidArg(format!(
+                    "expected a {} by {} matrix",
+                    n, cols
+                )));
+            }
+        }
+        if constants.iter().any(|c| c.len() != n) {
+            return Err(FancyError::InvalidArg(format!(
+                "expected round constants of {} bits",
+                n
+            )));
+        }
+        Ok(LowMcParams {
+            sboxes,
+            linear,
+            constants,
+            key_matrices,
+        })
+    }
+
+    /// Generate an instance with random invertible linear layers and round key
+    /// matrices of full rank.
+    pub fn random<R: Rng>(
+        rng: &mut R,
+        block_size: usize,
+        key_size: usize,
+        sboxes: usize,
+        rounds: usize,
+    ) -> Result<Self, FancyError> {
+        let mut matrix = |rows: usize, cols: usize| loop {
+            let m = (0..rows)
+                .map(|_| (0..cols).map(|_| rng.gen()).collect())
+                .collect::<Matrix>();
+            if rank(&m) == rows.min(cols) {
+                return m;
+            }
+        };
+        let linear = (0..rounds)
+            .map(|_| matrix(block_size, block_size))
+            .collect();
+        let key_matrices = (0..=rounds)
+            .map(|_| matrix(block_size, key_size))
+            .collect();
+        let constants = (0..rounds)
+            .map(|_| (0..block_size).map(|_| rng.gen()).collect())
+            .collect();
+        LowMcParams::new(sboxes, linear, constants, key_matrices)
+    }
+
+    /// Read an instance with `sboxes` S-boxes per round from the contents of a
+    /// parameter file as written by the reference implementation's
+    /// `generate_matrices.py`, or by the `Display` implementation.
+    ///
+    /// The file has a header giving the block size, key size and number of
+    /// rounds, then sections `Linear layer r:`, `Round constant r:` and
+    /// `Round key matrix r:`, each followed by rows of the form `[0, 1, ...]`.
+    pub fn parse(s: &str, sboxes: usize) -> Result<Self, FancyError> {
+        let err = |msg: String| FancyError::InvalidArg(format!("LowMC parameters: {}", msg));
+        let mut header = [None; 3];
+        let mut linear = Vec::new();
+        let mut constants = Vec::new();
+        let mut key_matrices = Vec::new();
+        let mut current: Option<&mut Matrix> = None;
+        for line in s.lines().map(str::trim) {
+            if line.starts_with('[') {
+                let row = line
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .split(',')
+                    .map(|b| match b.trim() {
+                        "0" => Ok(false),
+                        "1" => Ok(true),
+                        b => Err(err(format!("invalid bit {:?}", b))),
+                    })
+                    .collect::<Result<Vec<bool>, FancyError>>()?;
+                match current {
+                    Some(ref mut m) => m.push(row),
+                    None => return Err(err("row outside of a section".to_string())),
+                }
+                continue;
+            }
+            let fields = ["Block size:", "Key size:", "Rounds:"];
+            if let Some(i) = fields.iter().position(|f| line.starts_with(f)) {
+                let v = line[fields[i].len()..].trim();
+                header[i] = Some(v.parse::<usize>().map_err(|_| err(format!("bad {}", line)))?);
+            }
+            current = if line.starts_with("Linear layer ") && line.ends_with(':') {
+                linear.push(Vec::new());
+                linear.last_mut()
+            } else if line.starts_with("Round constant ") && line.ends_with(':') {
+                constants.push(Vec::new());
+                constants.last_mut()
+            } else if line.starts_with("Round key matrix ") && line.ends_with(':') {
+                key_matrices.push(Vec::new());
+                key_matrices.last_mut()
+            } else {
+                None
+            };
+        }
+        let constants = constants
+            .into_iter()
+            .map(|mut c| match c.len() {
+                1 => Ok(c.pop().unwrap()),
+                _ => Err(err("round constants must be a single row".to_string())),
+            })
+            .collect::<Result<Vec<Vec<bool>>, FancyError>>()?;
+        let params = LowMcParams::new(sboxes, linear, constants, key_matrices)?;
+        let sizes = [params.block_size(), params.key_size(), params.rounds()];
+        if header.iter().zip(sizes.iter()).any(|(h, s)| *h != Some(*s)) {
+            return Err(err(format!(
+                "header {:?} does not match the matrices, of sizes {:?}",
+                header, sizes
+            )));
+        }
+        Ok(params)
+    }
+
+    /// The number of bits in a block.
+    pub fn block_size(&self) -> usize {
+        self.key_matrices[0].len()
+    }
+
+    /// The number of bits in a key.
+    pub fn key_size(&self) -> usize {
+        self.key_matrices[0][0].len()
+    }
+
+    /// The number of S-boxes in each round.
+    pub fn sboxes(&self) -> usize {
+        self.sboxes
+    }
+
+    /// The number of rounds.
+    pub fn rounds(&self) -> usize {
+        self.linear.len()
+    }
+}
+
+impl fmt::Display for LowMcParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let row = |f: &mut fmt::Formatter, bits: &[bool]| {
+            let bits = bits
+                .iter()
+                .map(|&b| if b { "1" } else { "0" })
+                .collect::<Vec<_>>();
+            writeln!(f, "[{}]", bits.join(", "))
+        };
+        writeln!(f, "LowMC matrices and constants")?;
+        writeln!(f, "============================")?;
+        writeln!(f, "Block size: {}", self.block_size())?;
+        writeln!(f, "Key size: {}", self.key_size())?;
+        writeln!(f, "Rounds: {}", self.rounds())?;
+        for (r, m) in self.linear.iter().enumerate() {
+            writeln!(f, "\nLinear layer {}:", r + 1)?;
+            for bits in m {
+                row(f, bits)?;
+            }
+        }
+        for (r, c) in self.constants.iter().enumerate() {
+            writeln!(f, "\nRound constant {}:", r + 1)?;
+            row(f, c)?;
+        }
+        for (r, m) in self.key_matrices.iter().enumerate() {
+            writeln!(f, "\nRound key matrix {}:", r)?;
+            for bits in m {
+                row(f, bits)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The rank of `m` over GF(2).
+fn rank(m: &Matrix) -> usize {
+    let mut m = m.clone();
+    let cols = m.first().map_or(0, Vec::len);
+    let mut rank = 0;
+    for c in 0..cols {
+        if let Some(p) = (rank..m.len()).find(|&r| m[r][c]) {
+            m.swap(rank, p);
+            for r in 0..m.len() {
+                if r != rank && m[r][c] {
+                    let pivot = m[rank].clone();
+                    for (x, y) in m[r].iter_mut().zip(pivot) {
+                        *x ^= y;
+                    }
+                }
+            }
+            rank += 1;
+        }
+    }
+    rank
+}
+
+/// Encrypt `pt` under `key` with the LowMC instance `params`.
+pub fn lowmc_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    params: &LowMcParams,
+    key: &BinaryBundle<F::Item>,
+    pt: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    for (x, needed) in [(key, params.key_size()), (pt, params.block_size())].iter() {
+        if x.size() != *needed {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: x.size(),
+                needed: *needed,
+            }));
+        }
+    }
+    if let Some(x) = key
+        .wires()
+        .iter()
+        .chain(pt.wires())
+        .find(|x| x.modulus() != Modulus::Zq { q: 2 })
+    {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: x.modulus(),
+            needed: Modulus::Zq { q: 2 },
+        }));
+    }
+
+    let k = key.wires();
+    let round_key = |f: &mut F, r: usize| matrix_mul(f, &params.key_matrices[r], k);
+    let k0 = round_key(f, 0)?;
+    let mut state = xor(f, pt.wires(), &k0)?;
+    for r in 0..params.rounds() {
+        for i in 0..params.sboxes {
+            let y = utils::sbox_bits(f, &state[3 * i..3 * i + 3], &LOWMC_SBOX)?;
+            state.splice(3 * i..3 * i + 3, y);
+        }
+        state = matrix_mul(f, &params.linear[r], &state)?;
+        state = bits_xor_constant(f, &state, &params.constants[r])?;
+        let kr = round_key(f, r + 1)?;
+        state = xor(f, &state, &kr)?;
+    }
+    Ok(BinaryBundle::new(state))
+}
+
+fn xor<F: Fancy + ?Sized>(
+    f: &mut F,
+    xs: &[F::Item],
+    ys: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    xs.iter().zip(ys).map(|(x, y)| f.xor(x, y)).collect()
+}
+
+fn bits_xor_constant<F: Fancy + ?Sized>(
+    f: &mut F,
+    xs: &[F::Item],
+    c: &[bool],
+) -> Result<Vec<F::Item>, F::Error> {
+    xs.iter()
+        .zip(c)
+        .map(|(x, &c)| if c { f.negate(x) } else { Ok(x.clone()) })
+        .collect()
+}
+
+// The product of `m` with the bits `xs`, with XOR gates only.
+fn matrix_mul<F: Fancy + ?Sized>(
+    f: &mut F,
+    m: &Matrix,
+    xs: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    m.iter()
+        .map(|row| {
+            let terms = row
+                .iter()
+                .zip(xs)
+                .filter(|(&b, _)| b)
+                .map(|(_, x)| x.clone())
+                .collect::<Vec<_>>();
+            match terms.len() {
+                0 => f.constant(0, &Modulus::Zq { q: 2 }),
+                1 => Ok(terms[0].clone()),
+                _ => f.add_many(&terms),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util,
+        BinaryGadgets,
+        BundleGadgets,
+        FancyInput,
+    };
+    use rand::thread_rng;
+
+    // LowMC in the clear, on bits.
+    fn lowmc(params: &LowMcParams, key: &[bool], pt: &[bool]) -> Vec<bool> {
+        let mul = |m: &Matrix, xs: &[bool]| {
+            m.iter()
+                .map(|row| row.iter().zip(xs).fold(false, |acc, (&a, &b)| acc ^ (a & b)))
+                .collect::<Vec<bool>>()
+        };
+        let xor = |xs: &[bool], ys: &[bool]| {
+            xs.iter().zip(ys).map(|(x, y)| x ^ y).collect::<Vec<bool>>()
+        };
+        let mut s = xor(pt, &mul(&params.key_matrices[0], key));
+        for r in 0..params.rounds() {
+            for i in 0..params.sboxes() {
+                let x = (0..3).fold(0, |acc, j| acc | (s[3 * i + j] as u16) << j);
+                let y = LOWMC_SBOX[x as usize];
+                for j in 0..3 {
+                    s[3 * i + j] = (y >> j) & 1 == 1;
+                }
+            }
+            s = mul(&params.linear[r], &s);
+            s = xor(&s, &params.constants[r]);
+            s = xor(&s, &mul(&params.key_matrices[r + 1], key));
+        }
+        s
+    }
+
+    fn to_u128(bits: &[bool]) -> u128 {
+        bits.iter().rev().fold(0, |acc, &b| acc << 1 | b as u128)
+    }
+
+    fn from_u128(x: u128, n: usize) -> Vec<bool> {
+        (0..n).map(|i| (x >> i) & 1 == 1).collect()
+    }
+
+    #[test]
+    fn sbox() {
+        // (a, b, c) -> (a + bc, a + b + ac, a + b + c + ab), with a the top bit
+        for x in 0..8u16 {
+            let (a, b, c) = (x >> 2, (x >> 1) & 1, x & 1);
+            let y = (a ^ b & c) << 2 | (a ^ b ^ a & c) << 1 | (a ^ b ^ c ^ a & b);
+            assert_eq!(LOWMC_SBOX[x as usize], y);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let mut rng = thread_rng();
+        let params = LowMcParams::random(&mut rng, 24, 20, 5, 3).unwrap();
+        assert_eq!(
+            (params.block_size(), params.key_size(), params.rounds()),
+            (24, 20, 3)
+        );
+        let s = params.to_string();
+        assert_eq!(LowMcParams::parse(&s, 5).unwrap(), params);
+        assert!(LowMcParams::parse(&s, 9).is_err());
+        assert!(LowMcParams::parse(&s.replace("Rounds: 3", "Rounds: 4"), 5).is_err());
+        assert!(LowMcParams::parse(&s.replacen("[0", "[2", 1), 5).is_err());
+        let truncated = &s[..s.rfind("\n[").unwrap()];
+        assert!(LowMcParams::parse(truncated, 5).is_err());
+    }
+
+    #[test]
+    fn dummy() {
+        let mut rng = thread_rng();
+        for &(n, k, m, r) in &[(24, 24, 8, 6), (64, 80, 10, 12), (128, 128, 1, 4)] {
+            let params = LowMcParams::random(&mut rng, n, k, m, r).unwrap();
+            for _ in 0..4 {
+                let key = from_u128(rng.gen(), k);
+                let pt = from_u128(rng.gen(), n);
+                let mut f = Dummy::new();
+                let k_ = f.bin_encode(to_u128(&key), k).unwrap();
+                let p = f.bin_encode(to_u128(&pt), n).unwrap();
+                let c = lowmc_encrypt(&mut f, &params, &k_, &p).unwrap();
+                let ct = to_u128(&lowmc(&params, &key, &pt));
+                assert_eq!(f.bin_output(&c).unwrap().unwrap(), ct);
+            }
+        }
+        let params = LowMcParams::random(&mut rng, 24, 24, 8, 1).unwrap();
+        let mut f = Dummy::new();
+        let k = f.bin_encode(0, 24).unwrap();
+        let p = f.bin_encode(0, 23).unwrap();
+        assert!(lowmc_encrypt(&mut f, &params, &k, &p).is_err());
+        assert!(LowMcParams::random(&mut rng, 24, 24, 9, 1).is_err());
+    }
+
+    #[test]
+    fn garbled() {
+        let mut rng = thread_rng();
+        let params = LowMcParams::random(&mut rng, 64, 64, 10, 8).unwrap();
+        let mut b = CircuitBuilder::new();
+        let k = b.bin_garbler_input(64);
+        let p = b.bin_evaluator_input(64);
+        let c = lowmc_encrypt(&mut b, &params, &k, &p).unwrap();
+        b.output_bundle(&c).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let key = from_u128(rng.gen(), 64);
+        let pt = from_u128(rng.gen(), 64);
+        let K = en.encode_garbler_inputs(&util::u128_to_bits(to_u128(&key), 64));
+        let P = en.encode_evaluator_inputs(&util::u128_to_bits(to_u128(&pt), 64));
+        let ct = to_u128(&lowmc(&params, &key, &pt));
+        assert_eq!(gc.eval(&circ, &K, &P).unwrap(), util::u128_to_bits(ct, 64));
+    }
+}
@@ -0,0 +1,293 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The MiMC-p/p block cipher of Albrecht et al. (ASIACRYPT 2016), and the
+//! GMiMC-erf Feistel permutation of Albrecht et al. (ESORICS 2019), over a
+//! prime field.
+//!
+//! Elements are wires of modulus `Zq` for a prime `q` with `gcd(3, q - 1) = 1`,
+//! so that cubing is a permutation. Each cube costs two `mul` gates, of
+//! `2 (q - 1)` ciphertexts each when garbled, and everything else is free.
+//!
+//! The round constants are drawn from an `AesRng` seeded with a public
+//! `Block`, so that both parties derive the same instance from the seed.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{Fancy, HasModulus},
+    Modulus,
+};
+use rand::{Rng, SeedableRng};
+use scuttlebutt::{AesRng, Block};
+
+/// The parameters of MiMC-p/p over `Zq`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MimcParams {
+    q: u16,
+    constants: Vec<u16>,
+}
+
+impl MimcParams {
+    /// MiMC-p/p over `Zq`, with `ceil(log_3 q)` rounds whose constants, apart
+    /// from the first, which is zero, are drawn using `seed`.
+    pub fn new(q: u16, seed: Block) -> Result<Self, FancyError> {
+        check_prime(q)?;
+        let mut rounds = 0;
+        let mut x = 1u32;
+        while x < q as u32 {
+            x *= 3;
+            rounds += 1;
+        }
+        let mut constants = vec![0];
+        constants.extend(round_constants(q, rounds - 1, seed));
+        Ok(MimcParams { q, constants })
+    }
+
+    /// The modulus of the elements.
+    pub fn modulus(&self) -> Modulus {
+        Modulus::Zq { q: self.q }
+    }
+
+    /// The round constants, one per round.
+    pub fn constants(&self) -> &[u16] {
+        &self.constants
+    }
+}
+
+/// The parameters of the GMiMC-erf permutation over `Zq`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GmimcParams {
+    q: u16,
+    branches: usize,
+    constants: Vec<u16>,
+}
+
+impl GmimcParams {
+    /// GMiMC-erf over `Zq` with `branches` branches and `rounds` rounds, whose
+    /// constants are drawn using `seed`.
+    pub fn new(q: u16, branches: usize, rounds: usize, seed: Block) -> Result<Self, FancyError> {
+        check_prime(q)?;
+        if branches < 2 {
+            return Err(FancyError::InvalidArgNum {
+                got: branches,
+                needed: 2,
+            });
+        }
+        Ok(GmimcParams {
+            q,
+            branches,
+            constants: round_constants(q, rounds, seed),
+        })
+    }
+
+    /// The modulus of the elements.
+    pub fn modulus(&self) -> Modulus {
+        Modulus::Zq { q: self.q }
+    }
+
+    /// The number of branches, that is of elements in the state.
+    pub fn branches(&self) -> usize {
+        self.branches
+    }
+
+    /// The round constants, one per round.
+    pub fn constants(&self) -> &[u16] {
+        &self.constants
+    }
+}
+
+// Check that `q` is a prime for which cubing is a permutation.
+fn check_prime(q: u16) -> Result<(), FancyError> {
+    let n = q as u32;
+    let prime = n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| n % d != 0);
+    if !prime || (q - 1) % 3 == 0 {
+        return Err(FancyError::InvalidArg(format!(
+            "MiMC needs a prime q with gcd(3, q - 1) = 1, got {}",
+            q
+        )));
+    }
+    Ok(())
+}
+
+fn round_constants(q: u16, n: usize, seed: Block) -> Vec<u16> {
+    let mut rng = AesRng::from_seed(seed);
+    (0..n).map(|_| rng.gen_range(0, q)).collect()
+}
+
+/// Encrypt `x` under `key` with MiMC-p/p: each round maps `x` to
+/// `(x + key + c)^3`, and the key is added once more at the end.
+pub fn mimc_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    params: &MimcParams,
+    key: &F::Item,
+    x: &F::Item,
+) -> Result<F::Item, F::Error> {
+    check_modulus::<F>(&[key.clone(), x.clone()], params.modulus())?;
+    let mut x = x.clone();
+    for &c in params.constants.iter() {
+        let y = f.add(&x, key)?;
+        let y = utils::add_constant(f, &y, c)?;
+        x = cube(f, &y)?;
+    }
+    f.add(&x, key)
+}
+
+/// Apply the GMiMC-erf permutation to `state`: each round cubes the first
+/// element plus the round constant, adds the result to every other element,
+/// and rotates the state left by one.
+pub fn gmimc_permute<F: Fancy + ?Sized>(
+    f: &mut F,
+    params: &GmimcParams,
+    state: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    if state.len() != params.branches {
+        return Err(F::Error::from(FancyError::InvalidArgNum {
+            got: state.len(),
+            needed: params.branches,
+        }));
+    }
+    check_modulus::<F>(state, params.modulus())?;
+    let mut state = state.to_vec();
+    for &c in params.constants.iter() {
+        let y = utils::add_constant(f, &state[0], c)?;
+        let s = cube(f, &y)?;
+        for x in state[1..].iter_mut() {
+            *x = f.add(x, &s)?;
+        }
+        state.rotate_left(1);
+    }
+    Ok(state)
+}
+
+fn cube<F: Fancy + ?Sized>(f: &mut F, x: &F::Item) -> Result<F::Item, F::Error> {
+    let x2 = f.mul(x, x)?;
+    f.mul(&x2, x)
+}
+
+fn check_modulus<F: Fancy + ?Sized>(xs: &[F::Item], q: Modulus) -> Result<(), F::Error> {
+    if let Some(x) = xs.iter().find(|x| x.modulus() != q) {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: x.modulus(),
+            needed: q,
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+    };
+    use rand::thread_rng;
+
+    fn cube(x: u64, q: u64) -> u64 {
+        x * x % q * x % q
+    }
+
+    fn mimc(params: &MimcParams, key: u64, x: u64) -> u64 {
+        let q = params.q as u64;
+        let x = params
+            .constants
+            .iter()
+            .fold(x, |x, &c| cube((x + key + c as u64) % q, q));
+        (x + key) % q
+    }
+
+    fn gmimc(params: &GmimcParams, state: &[u64]) -> Vec<u64> {
+        let q = params.q as u64;
+        let mut state = state.to_vec();
+        for &c in params.constants.iter() {
+            let s = cube((state[0] + c as u64) % q, q);
+            for x in state[1..].iter_mut() {
+                *x = (*x + s) % q;
+            }
+            state.rotate_left(1);
+        }
+        state
+    }
+
+    #[test]
+    fn params() {
+        let seed = Block::from(7u128);
+        let p = MimcParams::new(65519, seed).unwrap();
+        assert_eq!(p.constants().len(), 11);
+        assert_eq!(p.constants()[0], 0);
+        assert_eq!(p, MimcParams::new(65519, seed).unwrap());
+        assert_ne!(p, MimcParams::new(65519, Block::from(8u128)).unwrap());
+        assert_eq!(MimcParams::new(11, seed).unwrap().constants().len(), 3);
+        // not prime, and a prime with 3 | q - 1
+        assert!(MimcParams::new(65517, seed).is_err());
+        assert!(MimcParams::new(65521, seed).is_err());
+        assert!(GmimcParams::new(65519, 1, 10, seed).is_err());
+    }
+
+    #[test]
+    fn dummy() {
+        let mut rng = thread_rng();
+        for &q in &[5, 101, 65519] {
+            let params = MimcParams::new(q, rng.gen::<Block>()).unwrap();
+            let gparams = GmimcParams::new(q, 4, 20, rng.gen::<Block>()).unwrap();
+            let m = params.modulus();
+            for _ in 0..16 {
+                let (key, x) = (rng.gen_range(0, q), rng.gen_range(0, q));
+                let mut f = Dummy::new();
+                let (key_, x_) = (DummyVal::new(key, m), DummyVal::new(x, m));
+                let z = mimc_encrypt(&mut f, &params, &key_, &x_).unwrap();
+                assert_eq!(z.val() as u64, mimc(&params, key as u64, x as u64));
+
+                let state = (0..4).map(|_| rng.gen_range(0, q)).collect::<Vec<u16>>();
+                let xs = state.iter().map(|&x| DummyVal::new(x, m)).collect::<Vec<_>>();
+                let zs = gmimc_permute(&mut f, &gparams, &xs).unwrap();
+                let state = state.iter().map(|&x| x as u64).collect::<Vec<_>>();
+                assert_eq!(
+                    zs.iter().map(|z| z.val() as u64).collect::<Vec<_>>(),
+                    gmimc(&gparams, &state)
+                );
+            }
+        }
+        let params = MimcParams::new(101, Block::default()).unwrap();
+        let mut f = Dummy::new();
+        let x = DummyVal::new(0, Modulus::Zq { q: 103 });
+        assert!(mimc_encrypt(&mut f, &params, &x, &x).is_err());
+    }
+
+    #[test]
+    fn garbled() {
+        let mut rng = thread_rng();
+        let q = 257;
+        let params = MimcParams::new(q, rng.gen::<Block>()).unwrap();
+        let gparams = GmimcParams::new(q, 3, 12, rng.gen::<Block>()).unwrap();
+        let m = params.modulus();
+
+        let mut b = CircuitBuilder::new();
+        let key = b.garbler_input(&m);
+        let x = b.evaluator_input(&m);
+        let z = mimc_encrypt(&mut b, &params, &key, &x).unwrap();
+        let xs = b.evaluator_inputs(&[m; 3]);
+        let zs = gmimc_permute(&mut b, &gparams, &xs).unwrap();
+        b.output(&z).unwrap();
+        b.outputs(&zs).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+
+        let key = rng.gen_range(0, q);
+        let inputs = (0..4).map(|_| rng.gen_range(0, q)).collect::<Vec<u16>>();
+        let K = en.encode_garbler_inputs(&[key]);
+        let X = en.encode_evaluator_inputs(&inputs);
+        let out = gc.eval(&circ, &K, &X).unwrap();
+        let state = inputs[1..].iter().map(|&x| x as u64).collect::<Vec<_>>();
+        assert_eq!(out[0] as u64, mimc(&params, key as u64, inputs[0] as u64));
+        assert_eq!(
+            out[1..].iter().map(|&x| x as u64).collect::<Vec<_>>(),
+            gmimc(&gparams, &state)
+        );
+    }
+}
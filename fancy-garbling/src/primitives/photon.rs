@@ -0,0 +1,687 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The PHOTON family of permutations (Guo, Peyrin, Poschmann, CRYPTO 2011).
+//!
+//! A state is a `d`-by-`d` matrix of field cells stored row-major. Each of the
+//! 12 rounds applies `AddConstants`, `SubCells`, `ShiftRows`, and
+//! `MixColumnsSerial`; only `SubCells` costs ciphertexts, one projection per cell.
+//! `PhotonParams::rounds` and `first_round` select reduced-round variants.
+//!
+//! The hash functions PHOTON-`n`/`r`/`r'` run the permutations in a `Sponge`
+//! absorbing `r` bits and squeezing `r'` bits at a time. Messages are cells of
+//! the permutation's field, with the bits of the message most significant
+//! first, so a byte is two `GF(2^4)` cells, high nibble first, or one `GF(2^8)`
+//! cell.
+
+use super::{
+    spn::{spn_encrypt, spn_encrypt_with, MixColumnsStrategy, SpnCipher},
+    sponge::{Sponge, SpongePadding},
+    utils,
+};
+use crate::{
+    errors::FancyError,
+    fancy::{Fancy, HasModulus, StateMatrix},
+    Modulus,
+};
+
+/// A PHOTON state, the `d`-by-`d` matrix of cells that the permutations act on.
+pub type PhotonState<W> = StateMatrix<W>;
+
+/// Round constant number `r`, shared by all PHOTON permutations. The 12 of
+/// the specification come from a 4-bit LFSR, which continues them with period
+/// 15 for longer variants.
+fn round_constant(r: usize) -> u16 {
+    (0..r % 15).fold(1, |rc, _| ((rc << 1) & 0xf) | (((rc >> 3) ^ (rc >> 2) ^ 1) & 1))
+}
+
+/// Parameters of a PHOTON permutation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhotonParams {
+    /// The dimension of the state.
+    pub d: usize,
+    /// The field of the state cells.
+    pub modulus: Modulus,
+    /// The S-box applied to every cell.
+    pub sbox: &'static [u16],
+    /// The last row of the `Serial` matrix, whose `d`-th power is the
+    /// `MixColumnsSerial` matrix.
+    pub z: &'static [u16],
+    /// The internal constants, added to the first column each round.
+    pub ic: &'static [u16],
+    /// The number of rounds, 12 in the specification.
+    pub rounds: usize,
+    /// The number of the round constant of the first round, 0 in the
+    /// specification. Reduced-round variants starting in the middle of the
+    /// permutation set both.
+    pub first_round: usize,
+}
+
+impl PhotonParams {
+    /// The permutation of PHOTON-80/20/16.
+    pub const P100: PhotonParams = PhotonParams {
+        d: 5,
+        modulus: Modulus::X4_X_1,
+        sbox: &utils::PRESENT_SBOX,
+        z: &[1, 2, 9, 9, 2],
+        ic: &[0, 1, 3, 6, 4],
+        rounds: 12,
+        first_round: 0,
+    };
+
+    /// The permutation of PHOTON-128/16/16.
+    pub const P144: PhotonParams = PhotonParams {
+        d: 6,
+        modulus: Modulus::X4_X_1,
+        sbox: &utils::PRESENT_SBOX,
+        z: &[1, 2, 8, 5, 8, 2],
+        ic: &[0, 1, 3, 7, 6, 4],
+        rounds: 12,
+        first_round: 0,
+    };
+
+    /// The permutation of PHOTON-160/36/36.
+    pub const P196: PhotonParams = PhotonParams {
+        d: 7,
+        modulus: Modulus::X4_X_1,
+        sbox: &utils::PRESENT_SBOX,
+        z: &[1, 4, 6, 1, 1, 6, 4],
+        ic: &[0, 1, 2, 5, 3, 6, 4],
+        rounds: 12,
+        first_round: 0,
+    };
+
+    /// The permutation of PHOTON-224/32/32.
+    pub const P256: PhotonParams = PhotonParams {
+        d: 8,
+        modulus: Modulus::X4_X_1,
+        sbox: &utils::PRESENT_SBOX,
+        z: &[2, 4, 2, 11, 2, 8, 5, 6],
+        ic: &[0, 1, 3, 7, 15, 14, 12, 8],
+        rounds: 12,
+        first_round: 0,
+    };
+
+    /// The permutation of PHOTON-256/32/32, over `GF(2^8)` with the AES
+    /// polynomial.
+    pub const P288: PhotonParams = PhotonParams {
+        d: 6,
+        modulus: Modulus::GF8 { p: 0b100011011 },
+        sbox: &utils::AES_SBOX,
+        z: &[2, 3, 1, 2, 1, 4],
+        ic: &[0, 1, 3, 7, 6, 4],
+        rounds: 12,
+        first_round: 0,
+    };
+
+    /// The number of cells in the state.
+    pub fn ncells(&self) -> usize {
+        self.d * self.d
+    }
+
+    /// The number of bits in a cell.
+    pub fn cell_bits(&self) -> usize {
+        self.modulus.bit_length()
+    }
+
+    /// The permutation as an unkeyed `SpnCipher`, whose round `r` adds round
+    /// constant `first_round + r` xored with `ic[i]` to the first cell of row
+    /// `i`. The constants are fused into the S-box tables.
+    pub fn spn(&self) -> SpnCipher {
+        let d = self.d;
+        let round_constants = (self.first_round..self.first_round + self.rounds)
+            .map(|r| {
+                let rc = round_constant(r);
+                let mut cs = vec![0; d * d];
+                for (i, &ic) in self.ic.iter().enumerate() {
+                    cs[i * d] = rc ^ ic;
+                }
+                cs
+            })
+            .collect();
+        SpnCipher {
+            d,
+            modulus: self.modulus,
+            sbox: self.sbox.to_vec(),
+            shift_rows: (0..d).collect(),
+            mds_row: self.z.to_vec(),
+            round_constants,
+            key_period: None,
+            fuse_constants: true,
+        }
+    }
+}
+
+/// Fancy DSL providing the PHOTON permutations.
+pub trait PhotonGadgets: Fancy {
+    /// Apply the PHOTON permutation described by `params` to the row-major
+    /// `state`, whose cells must all have modulus `params.modulus`.
+    fn photon_custom(
+        &mut self,
+        state: &[Self::Item],
+        params: &PhotonParams,
+    ) -> Result<Vec<Self::Item>, Self::Error> {
+        spn_encrypt(self, &params.spn(), &[], state)
+    }
+
+    /// `photon_custom` on cells encoded according to `strategy`, e.g. as bits
+    /// with `MixColumnsStrategy::Binary`. `MixColumnsStrategy::cheapest` of
+    /// `params.spn()` tells which encoding garbles with fewer ciphertexts.
+    fn photon_custom_with(
+        &mut self,
+        state: &[Self::Item],
+        params: &PhotonParams,
+        strategy: MixColumnsStrategy,
+    ) -> Result<Vec<Self::Item>, Self::Error> {
+        spn_encrypt_with(self, &params.spn(), strategy, &[], state)
+    }
+
+    /// Apply the PHOTON permutation described by `params` to `state`, which
+    /// must be `d`-by-`d`.
+    fn photon_state(
+        &mut self,
+        state: &PhotonState<Self::Item>,
+        params: &PhotonParams,
+    ) -> Result<PhotonState<Self::Item>, Self::Error> {
+        let d = params.d;
+        if state.nrows() != d || state.ncols() != d {
+            return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                "PHOTON needs a {}-by-{} state, got {}-by-{}",
+                d,
+                d,
+                state.nrows(),
+                state.ncols()
+            ))));
+        }
+        let cells = self.photon_custom(state.cells(), params)?;
+        PhotonState::from_rows(d, d, cells).map_err(Self::Error::from)
+    }
+
+    /// Output the cells of `state` row by row, returning the plaintext state
+    /// if this `Fancy` learns outputs.
+    fn output_photon(
+        &mut self,
+        state: &PhotonState<Self::Item>,
+    ) -> Result<Option<PhotonState<u16>>, Self::Error> {
+        match self.outputs(state.cells())? {
+            Some(zs) => PhotonState::from_rows(state.nrows(), state.ncols(), zs)
+                .map(Some)
+                .map_err(Self::Error::from),
+            None => Ok(None),
+        }
+    }
+
+    /// PHOTON-100 on 25 `GF(2^4)` cells.
+    fn photon_100(&mut self, state: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error> {
+        self.photon_custom(state, &PhotonParams::P100)
+    }
+
+    /// PHOTON-144 on 36 `GF(2^4)` cells.
+    fn photon_144(&mut self, state: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error> {
+        self.photon_custom(state, &PhotonParams::P144)
+    }
+
+    /// PHOTON-196 on 49 `GF(2^4)` cells.
+    fn photon_196(&mut self, state: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error> {
+        self.photon_custom(state, &PhotonParams::P196)
+    }
+
+    /// PHOTON-256 on 64 `GF(2^4)` cells.
+    fn photon_256(&mut self, state: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error> {
+        self.photon_custom(state, &PhotonParams::P256)
+    }
+
+    /// PHOTON-288 on 36 `GF(2^8)` cells.
+    fn photon_288(&mut self, state: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error> {
+        self.photon_custom(state, &PhotonParams::P288)
+    }
+
+    /// Hash `msg` with PHOTON-`n`/`r`/`r_prime` over the permutation of
+    /// `params`, returning `n` bits of digest as cells. The sizes are in bits
+    /// and must be whole cells.
+    ///
+    /// The state starts as zeros followed by the bytes `n / 4`, `r`, and
+    /// `r_prime`, and the message is padded with a one bit and zeros.
+    fn photon_hash_custom(
+        &mut self,
+        msg: &[Self::Item],
+        params: &PhotonParams,
+        n: usize,
+        r: usize,
+        r_prime: usize,
+    ) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: Sized,
+    {
+        let k = params.cell_bits();
+        if [n, r, r_prime].iter().any(|&x| x == 0 || x % k != 0) || r >= k * params.ncells() {
+            return Err(Self::Error::from(FancyError::InvalidArg(format!(
+                "PHOTON-{}/{}/{} needs sizes in whole cells of {} bits, with room for a capacity",
+                n, r, r_prime, k
+            ))));
+        }
+        if let Some(x) = msg.iter().find(|x| x.modulus() != params.modulus) {
+            return Err(Self::Error::from(FancyError::InvalidArgMod {
+                got: x.modulus(),
+                needed: params.modulus,
+            }));
+        }
+        let iv = ((n / 4) << 16 | r << 8 | r_prime) as u32;
+        let ncells = params.ncells();
+        let mut state = Vec::with_capacity(ncells);
+        for i in 0..ncells {
+            // the last 24 bits hold the IV, most significant first
+            let shift = (ncells - 1 - i) * k;
+            let c = if shift < 32 { (iv >> shift) & ((1 << k) - 1) } else { 0 };
+            state.push(self.constant(c as u16, &params.modulus)?);
+        }
+        let permute = |f: &mut Self, xs: &[Self::Item]| f.photon_custom(xs, params);
+        let mut sponge = Sponge::new(state, 0..r / k, permute)
+            .map_err(Self::Error::from)?
+            .with_padding(SpongePadding::OneZeros(1 << (k - 1)))
+            .with_squeeze_rate(r_prime / k);
+        sponge.absorb(self, msg)?;
+        sponge.squeeze(self, n / k)
+    }
+
+    /// PHOTON-80/20/16 on `GF(2^4)` cells.
+    fn photon_hash_80(&mut self, msg: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.photon_hash_custom(msg, &PhotonParams::P100, 80, 20, 16)
+    }
+
+    /// PHOTON-128/16/16 on `GF(2^4)` cells.
+    fn photon_hash_128(&mut self, msg: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.photon_hash_custom(msg, &PhotonParams::P144, 128, 16, 16)
+    }
+
+    /// PHOTON-160/36/36 on `GF(2^4)` cells.
+    fn photon_hash_160(&mut self, msg: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.photon_hash_custom(msg, &PhotonParams::P196, 160, 36, 36)
+    }
+
+    /// PHOTON-224/32/32 on `GF(2^4)` cells.
+    fn photon_hash_224(&mut self, msg: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.photon_hash_custom(msg, &PhotonParams::P256, 224, 32, 32)
+    }
+
+    /// PHOTON-256/32/32 on `GF(2^8)` cells.
+    fn photon_hash_256(&mut self, msg: &[Self::Item]) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: Sized,
+    {
+        self.photon_hash_custom(msg, &PhotonParams::P288, 256, 32, 32)
+    }
+}
+
+impl<F: Fancy> PhotonGadgets for F {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        util::{field_tables, RngExt},
+        FancyInput,
+    };
+    use rand::thread_rng;
+
+    const ALL: [PhotonParams; 5] = [
+        PhotonParams::P100,
+        PhotonParams::P144,
+        PhotonParams::P196,
+        PhotonParams::P256,
+        PhotonParams::P288,
+    ];
+
+    // the round constants from the specification
+    const RC: [u16; 12] = [1, 3, 7, 14, 13, 11, 6, 12, 9, 2, 5, 10];
+
+    // Straight-line plaintext PHOTON, written independently of the gadget layers.
+    fn photon_plain(state: &[u16], params: &PhotonParams) -> Vec<u16> {
+        let d = params.d;
+        let k = params.modulus.bit_length() as u8;
+        let tables = field_tables(params.modulus.value(), k).unwrap();
+        let mut x = (0..d)
+            .map(|i| state[i * d..(i + 1) * d].to_vec())
+            .collect::<Vec<_>>();
+        for &rc in RC.iter() {
+            for i in 0..d {
+                x[i][0] ^= rc ^ params.ic[i];
+            }
+            for row in x.iter_mut() {
+                for c in row.iter_mut() {
+                    *c = params.sbox[*c as usize];
+                }
+            }
+            for (i, row) in x.iter_mut().enumerate() {
+                row.rotate_left(i);
+            }
+            for j in 0..d {
+                for _ in 0..d {
+                    let last = (0..d).fold(0, |acc, i| acc ^ tables.mul(params.z[i], x[i][j]));
+                    for i in 0..d - 1 {
+                        x[i][j] = x[i + 1][j];
+                    }
+                    x[d - 1][j] = last;
+                }
+            }
+        }
+        x.concat()
+    }
+
+    // determinant of a square matrix over the field, by Gaussian elimination
+    fn det(mut m: Vec<Vec<u16>>, params: &PhotonParams) -> u16 {
+        let k = params.modulus.bit_length() as u8;
+        let tables = field_tables(params.modulus.value(), k).unwrap();
+        let n = m.len();
+        let mut d = 1;
+        for c in 0..n {
+            let p = match (c..n).find(|&r| m[r][c] != 0) {
+                Some(p) => p,
+                None => return 0,
+            };
+            m.swap(c, p);
+            d = tables.mul(d, m[c][c]);
+            let inv = tables.inv(m[c][c]).unwrap();
+            for r in c + 1..n {
+                let f = tables.mul(m[r][c], inv);
+                for j in c..n {
+                    m[r][j] ^= tables.mul(f, m[c][j]);
+                }
+            }
+        }
+        d
+    }
+
+    #[test]
+    fn mix_columns_is_mds() {
+        for params in ALL.iter() {
+            let m = utils::serial_matrix_power(params.z, &params.modulus);
+            assert_eq!(&m[0][..], params.z);
+            let n = params.d;
+            // every square submatrix must be nonsingular
+            for size in 1..=n {
+                let subsets = (0..1u32 << n)
+                    .filter(|s| s.count_ones() as usize == size)
+                    .collect::<Vec<_>>();
+                for rows in subsets.iter() {
+                    for cols in subsets.iter() {
+                        let sub = (0..n)
+                            .filter(|i| rows >> i & 1 == 1)
+                            .map(|i| {
+                                (0..n)
+                                    .filter(|j| cols >> j & 1 == 1)
+                                    .map(|j| m[i][j])
+                                    .collect()
+                            })
+                            .collect();
+                        assert_ne!(det(sub, params), 0, "d = {}", params.d);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dummy_matches_reference() {
+        let mut rng = thread_rng();
+        for params in ALL.iter() {
+            for _ in 0..4 {
+                let size = params.modulus.size();
+                let inp = (0..params.ncells())
+                    .map(|_| rng.gen_u16() % size)
+                    .collect::<Vec<_>>();
+                let mut f = Dummy::new();
+                let xs = inp
+                    .iter()
+                    .map(|&x| DummyVal::new(x, params.modulus))
+                    .collect::<Vec<_>>();
+                let zs = f.photon_custom(&xs, params).unwrap();
+                let got = zs.iter().map(|z| z.val()).collect::<Vec<_>>();
+                assert_eq!(got, photon_plain(&inp, params), "d = {}", params.d);
+            }
+        }
+    }
+
+    #[test]
+    fn round_constants() {
+        for (r, &rc) in RC.iter().enumerate() {
+            assert_eq!(round_constant(r), rc);
+        }
+        assert_eq!(round_constant(12), 4);
+        assert_eq!(round_constant(27), round_constant(12));
+    }
+
+    #[test]
+    fn reduced_rounds_compose() {
+        let mut rng = thread_rng();
+        let params = PhotonParams::P100;
+        let inp = (0..25).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let mut f = Dummy::new();
+        let xs = inp
+            .iter()
+            .map(|&x| DummyVal::new(x, params.modulus))
+            .collect::<Vec<_>>();
+        let head = PhotonParams {
+            rounds: 5,
+            ..params
+        };
+        let tail = PhotonParams {
+            rounds: 7,
+            first_round: 5,
+            ..params
+        };
+        let mut run = |xs: &[DummyVal], params: &PhotonParams| {
+            let zs = f.photon_custom(xs, params).unwrap();
+            let vals = zs.iter().map(|z| z.val()).collect::<Vec<_>>();
+            (zs, vals)
+        };
+        let (ys, _) = run(&xs, &head);
+        let (_, got) = run(&ys, &tail);
+        assert_eq!(got, photon_plain(&inp, &params));
+        let (_, tail_only) = run(&xs, &tail);
+
+        assert_eq!(run(&xs, &PhotonParams { rounds: 0, ..params }).1, inp);
+        // 24 rounds are two different halves, the second with the continued
+        // constants
+        let second = PhotonParams {
+            first_round: 12,
+            ..params
+        };
+        let (_, double) = run(&xs, &PhotonParams { rounds: 24, ..params });
+        let (ys, _) = run(&xs, &params);
+        assert_eq!(run(&ys, &second).1, double);
+        assert_ne!(run(&ys, &params).1, double);
+
+        let mut b = CircuitBuilder::new();
+        let x = b.evaluator_inputs(&[params.modulus; 25]);
+        let z = b.photon_custom(&x, &tail).unwrap();
+        b.outputs(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let X = en.encode_evaluator_inputs(&inp);
+        assert_eq!(gc.eval(&circ, &[], &X).unwrap(), tail_only);
+    }
+
+    #[test]
+    fn binary_strategy_matches_reference() {
+        let mut rng = thread_rng();
+        let q2 = Modulus::Zq { q: 2 };
+        for params in [PhotonParams::P100, PhotonParams::P288].iter() {
+            let k = params.cell_bits();
+            let inp = (0..params.ncells())
+                .map(|_| rng.gen_u16() % params.modulus.size())
+                .collect::<Vec<_>>();
+            let mut f = Dummy::new();
+            let xs = inp
+                .iter()
+                .flat_map(|&x| (0..k).map(move |i| DummyVal::new((x >> i) & 1, q2)))
+                .collect::<Vec<_>>();
+            let zs = f
+                .photon_custom_with(&xs, params, MixColumnsStrategy::Binary)
+                .unwrap();
+            let got = zs
+                .chunks(k)
+                .map(|bs| bs.iter().rev().fold(0, |acc, b| acc << 1 | b.val()))
+                .collect::<Vec<_>>();
+            assert_eq!(got, photon_plain(&inp, params), "d = {}", params.d);
+            let spn = params.spn();
+            assert_eq!(MixColumnsStrategy::cheapest(&spn), MixColumnsStrategy::Field);
+        }
+    }
+
+    #[test]
+    fn garbled_matches_reference() {
+        let mut rng = thread_rng();
+        for params in [PhotonParams::P100, PhotonParams::P288].iter() {
+            let mut b = CircuitBuilder::new();
+            let xs = b.evaluator_inputs(&vec![params.modulus; params.ncells()]);
+            let zs = b.photon_custom(&xs, params).unwrap();
+            b.outputs(&zs).unwrap();
+            let c = b.finish();
+
+            let size = params.modulus.size();
+            let inp = (0..params.ncells())
+                .map(|_| rng.gen_u16() % size)
+                .collect::<Vec<_>>();
+            let (en, gc) = garble(&c).unwrap();
+            let xs = en.encode_evaluator_inputs(&inp);
+            let got = gc.eval(&c, &[], &xs).unwrap();
+            assert_eq!(got, photon_plain(&inp, params), "d = {}", params.d);
+        }
+    }
+
+    // (params, n, r, r') of the PHOTON hash functions
+    const HASHES: [(PhotonParams, usize, usize, usize); 5] = [
+        (PhotonParams::P100, 80, 20, 16),
+        (PhotonParams::P144, 128, 16, 16),
+        (PhotonParams::P196, 160, 36, 36),
+        (PhotonParams::P256, 224, 32, 32),
+        (PhotonParams::P288, 256, 32, 32),
+    ];
+
+    // The hash mode over `photon_plain`, bit by bit as the paper writes it.
+    fn hash_plain(msg: &[u16], params: &PhotonParams, n: usize, r: usize, r_: usize) -> Vec<u16> {
+        let k = params.cell_bits();
+        let t = k * params.ncells();
+        let to_bits = |cells: &[u16]| {
+            cells
+                .iter()
+                .flat_map(|&c| (0..k).rev().map(move |i| (c >> i) & 1))
+                .collect::<Vec<_>>()
+        };
+        let to_cells = |bits: &[u16]| {
+            bits.chunks(k)
+                .map(|b| b.iter().fold(0, |acc, &x| acc << 1 | x))
+                .collect::<Vec<_>>()
+        };
+        let mut state = vec![0; t - 24];
+        for &byte in &[n / 4, r, r_] {
+            state.extend((0..8).rev().map(|i| ((byte >> i) & 1) as u16));
+        }
+        let mut m = to_bits(msg);
+        m.push(1);
+        while m.len() % r != 0 {
+            m.push(0);
+        }
+        for block in m.chunks(r) {
+            for (s, x) in state.iter_mut().zip(block) {
+                *s ^= x;
+            }
+            state = to_bits(&photon_plain(&to_cells(&state), params));
+        }
+        let mut z = state[..r_].to_vec();
+        while z.len() < n {
+            state = to_bits(&photon_plain(&to_cells(&state), params));
+            z.extend_from_slice(&state[..r_]);
+        }
+        to_cells(&z[..n])
+    }
+
+    #[test]
+    fn hash_dummy_matches_reference() {
+        let mut rng = thread_rng();
+        for (params, n, r, r_) in HASHES.iter() {
+            let size = params.modulus.size();
+            for &len in &[0, 1, r / params.cell_bits(), 13] {
+                let msg = (0..len).map(|_| rng.gen_u16() % size).collect::<Vec<_>>();
+                let mut f = Dummy::new();
+                let xs = msg
+                    .iter()
+                    .map(|&x| DummyVal::new(x, params.modulus))
+                    .collect::<Vec<_>>();
+                let zs = match n {
+                    80 => f.photon_hash_80(&xs),
+                    128 => f.photon_hash_128(&xs),
+                    160 => f.photon_hash_160(&xs),
+                    224 => f.photon_hash_224(&xs),
+                    _ => f.photon_hash_256(&xs),
+                }
+                .unwrap();
+                let got = zs.iter().map(|z| z.val()).collect::<Vec<_>>();
+                assert_eq!(got, hash_plain(&msg, params, *n, *r, *r_), "n = {}", n);
+            }
+        }
+        let mut f = Dummy::new();
+        let xs = vec![DummyVal::new(0, Modulus::Zq { q: 16 }); 4];
+        assert!(f.photon_hash_80(&xs).is_err());
+        assert!(f.photon_hash_custom(&[], &PhotonParams::P100, 80, 18, 16).is_err());
+    }
+
+    #[test]
+    fn hash_garbled_matches_reference() {
+        let mut rng = thread_rng();
+        let params = PhotonParams::P100;
+        let mut b = CircuitBuilder::new();
+        let xs = b.evaluator_inputs(&[params.modulus; 12]);
+        let zs = b.photon_hash_80(&xs).unwrap();
+        b.outputs(&zs).unwrap();
+        let c = b.finish();
+
+        let msg = (0..12).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let (en, gc) = garble(&c).unwrap();
+        let xs = en.encode_evaluator_inputs(&msg);
+        let got = gc.eval(&c, &[], &xs).unwrap();
+        assert_eq!(got, hash_plain(&msg, &params, 80, 20, 16));
+    }
+
+    #[test]
+    fn state_matrix() {
+        let mut rng = thread_rng();
+        let params = PhotonParams::P144;
+        let inp = (0..36).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let values = PhotonState::from_rows(6, 6, inp.clone()).unwrap();
+        let mut f = Dummy::new();
+        let xs = f.encode_matrix(&values, &params.modulus).unwrap();
+        let zs = f.photon_state(&xs, &params).unwrap();
+        let got = f.output_photon(&zs).unwrap().unwrap();
+        assert_eq!(got.into_cells(), photon_plain(&inp, &params));
+        let xs = f.encode_matrix(&PhotonState::from_rows(4, 9, inp).unwrap(), &params.modulus);
+        assert!(f.photon_state(&xs.unwrap(), &params).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_modulus() {
+        let mut f = Dummy::new();
+        let xs = vec![DummyVal::new(0, Modulus::Zq { q: 16 }); 25];
+        assert!(f.photon_100(&xs).is_err());
+        assert!(f.photon_100(&xs[..24]).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The PHOTON-Beetle-AEAD authenticated encryption of Bao et al. (NIST
+//! lightweight cryptography finalist), on top of `PhotonGadgets::photon_256`.
+//!
+//! Byte strings are `GF(2^4)` cells of modulus `PhotonParams::P256.modulus`,
+//! two per byte with the low nibble first, which is how the designers' code
+//! loads bytes into the row-major state. Keys, nonces, and tags are 16 bytes.
+//! Lengths are public, so the domain separation constants are too.
+//!
+//! Besides the permutations, each cell of encrypted or decrypted data costs the
+//! one projection of the `rho` shuffle, and checking a tag costs one projection
+//! per cell.
+
+use super::{
+    photon::{PhotonGadgets, PhotonParams},
+    utils,
+};
+use crate::{
+    errors::FancyError,
+    fancy::{Fancy, GfGadgets, HasModulus},
+};
+
+/// The inverse of `X` modulo `X^4 + X + 1`, which shifts a polynomial with no
+/// constant term right by one.
+const X_INV: u16 = 0b1001;
+
+/// The rate of a PHOTON-Beetle-AEAD instance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhotonBeetleRate {
+    /// PHOTON-Beetle-AEAD[32], absorbing 4 bytes per permutation.
+    R32,
+    /// PHOTON-Beetle-AEAD[128], absorbing 16 bytes per permutation.
+    R128,
+}
+
+impl PhotonBeetleRate {
+    /// The number of cells absorbed per permutation.
+    pub fn ncells(self) -> usize {
+        match self {
+            PhotonBeetleRate::R32 => 8,
+            PhotonBeetleRate::R128 => 32,
+        }
+    }
+}
+
+/// Encrypt `msg` with associated data `ad` under `key` and `nonce`, returning
+/// the ciphertext, which is as long as `msg`, and the 16-byte tag.
+pub fn photon_beetle_aead_encrypt<F: Fancy>(
+    f: &mut F,
+    rate: PhotonBeetleRate,
+    key: &[F::Item],
+    nonce: &[F::Item],
+    ad: &[F::Item],
+    msg: &[F::Item],
+) -> Result<(Vec<F::Item>, Vec<F::Item>), F::Error> {
+    check_cells::<F>(key, nonce, &[ad, msg])?;
+    let mut state = [nonce, key].concat();
+    let ct = beetle(f, rate, &mut state, ad, msg, false)?;
+    let tag = f.photon_256(&state)?;
+    Ok((ct, tag[..32].to_vec()))
+}
+
+/// Decrypt `ct` with associated data `ad` under `key` and `nonce`, returning
+/// the plaintext and a mod-2 wire which is 1 if `tag` is valid. The plaintext
+/// is computed either way, so it should only be revealed under that wire.
+pub fn photon_beetle_aead_decrypt<F: Fancy>(
+    f: &mut F,
+    rate: PhotonBeetleRate,
+    key: &[F::Item],
+    nonce: &[F::Item],
+    ad: &[F::Item],
+    ct: &[F::Item],
+    tag: &[F::Item],
+) -> Result<(Vec<F::Item>, F::Item), F::Error> {
+    check_cells::<F>(key, nonce, &[ad, ct, tag])?;
+    if tag.len() != 32 {
+        return Err(F::Error::from(FancyError::InvalidArgNum {
+            got: tag.len(),
+            needed: 32,
+        }));
+    }
+    let mut state = [nonce, key].concat();
+    let pt = beetle(f, rate, &mut state, ad, ct, true)?;
+    let state = f.photon_256(&state)?;
+    let eqs = state
+        .iter()
+        .zip(tag)
+        .map(|(x, y)| f.gf_eq(x, y))
+        .collect::<Result<Vec<_>, _>>()?;
+    let valid = f.and_many(&eqs)?;
+    Ok((pt, valid))
+}
+
+// Absorb `ad`, then encrypt or decrypt `data`, leaving `state` ready for the
+// final permutation that gives the tag.
+fn beetle<F: Fancy>(
+    f: &mut F,
+    rate: PhotonBeetleRate,
+    state: &mut Vec<F::Item>,
+    ad: &[F::Item],
+    data: &[F::Item],
+    decrypt: bool,
+) -> Result<Vec<F::Item>, F::Error> {
+    let r = rate.ncells();
+    if ad.is_empty() && data.is_empty() {
+        domain(f, state, 1)?;
+        return Ok(Vec::new());
+    }
+    if !ad.is_empty() {
+        for block in ad.chunks(r) {
+            *state = f.photon_256(state)?;
+            for (s, x) in state.iter_mut().zip(block) {
+                *s = f.add(s, x)?;
+            }
+            pad(f, state, block.len(), r)?;
+        }
+        let c = select(!data.is_empty(), ad.len() % r == 0, [1, 2, 3, 4]);
+        domain(f, state, c)?;
+    }
+    let mut out = Vec::with_capacity(data.len());
+    if !data.is_empty() {
+        for block in data.chunks(r) {
+            *state = f.photon_256(state)?;
+            let shuffled = shuffle(f, &state[..r], block.len())?;
+            let ys = shuffled
+                .iter()
+                .zip(block)
+                .map(|(s, x)| f.add(s, x))
+                .collect::<Result<Vec<_>, _>>()?;
+            // the state always absorbs the plaintext
+            let pt = if decrypt { &ys[..] } else { block };
+            for (s, x) in state.iter_mut().zip(pt) {
+                *s = f.add(s, x)?;
+            }
+            pad(f, state, block.len(), r)?;
+            out.extend(ys);
+        }
+        let c = select(!ad.is_empty(), data.len() % r == 0, [1, 2, 5, 6]);
+        domain(f, state, c)?;
+    }
+    Ok(out)
+}
+
+// The first `n` cells of `S2 || (S1 >>> 1)` for the rate part `S1 || S2`,
+// where `S1` rotates right by one bit as a little-endian string. Each cell of
+// `S1` contributes its low bit, extracted by a projection, to the cell below,
+// and the rest of it, shifted right for free, to its own cell.
+fn shuffle<F: Fancy>(f: &mut F, rate: &[F::Item], n: usize) -> Result<Vec<F::Item>, F::Error> {
+    let h = rate.len() / 2;
+    let (s1, s2) = rate.split_at(h);
+    let mut out = s2[..n.min(h)].to_vec();
+    if n > h {
+        let q = s1[0].modulus();
+        let tt = (0..q.size()).map(|a| a & 1).collect::<Vec<_>>();
+        let lows = s1
+            .iter()
+            .map(|x| f.proj(x, &q, Some(tt.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+        for j in 0..n - h {
+            let hi = f.add(&s1[j], &lows[j])?;
+            let hi = f.cmul(&hi, X_INV)?;
+            let lo = f.cmul(&lows[(j + 1) % h], 8)?;
+            out.push(f.add(&hi, &lo)?);
+        }
+    }
+    Ok(out)
+}
+
+// Pad a partial block of `len` cells with a one bit.
+fn pad<F: Fancy>(f: &mut F, state: &mut [F::Item], len: usize, r: usize) -> Result<(), F::Error> {
+    if len < r {
+        state[len] = utils::add_constant(f, &state[len], 1)?;
+    }
+    Ok(())
+}
+
+// Add the domain separation constant `c` to the top three bits of the state.
+fn domain<F: Fancy>(f: &mut F, state: &mut [F::Item], c: u16) -> Result<(), F::Error> {
+    let last = state.len() - 1;
+    state[last] = utils::add_constant(f, &state[last], c << 1)?;
+    Ok(())
+}
+
+fn select(c1: bool, c2: bool, options: [u16; 4]) -> u16 {
+    options[2 * !c1 as usize + !c2 as usize]
+}
+
+// Check that `key` and `nonce` are 16 bytes, that the strings in `data` are
+// whole bytes, and that every cell has the PHOTON-256 modulus.
+fn check_cells<F: Fancy>(
+    key: &[F::Item],
+    nonce: &[F::Item],
+    data: &[&[F::Item]],
+) -> Result<(), F::Error> {
+    for xs in [key, nonce].iter() {
+        if xs.len() != 32 {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: 32,
+            }));
+        }
+    }
+    if let Some(xs) = data.iter().find(|xs| xs.len() % 2 != 0) {
+        return Err(F::Error::from(FancyError::InvalidArg(format!(
+            "PHOTON-Beetle needs whole bytes, got {} cells",
+            xs.len()
+        ))));
+    }
+    let q = PhotonParams::P256.modulus;
+    let mut cells = key
+        .iter()
+        .chain(nonce)
+        .chain(data.iter().flat_map(|xs| xs.iter()));
+    if let Some(x) = cells.find(|x| x.modulus() != q) {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: x.modulus(),
+            needed: q,
+        }));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        Modulus,
+    };
+    use rand::{thread_rng, Rng};
+
+    fn cells(bytes: &[u8]) -> Vec<u16> {
+        bytes
+            .iter()
+            .flat_map(|&b| vec![b as u16 & 0xf, b as u16 >> 4])
+            .collect()
+    }
+
+    fn bytes(cells: &[u16]) -> Vec<u8> {
+        cells.chunks(2).map(|c| (c[0] | c[1] << 4) as u8).collect()
+    }
+
+    fn dummy_cells(xs: &[u8]) -> Vec<DummyVal> {
+        let q = PhotonParams::P256.modulus;
+        cells(xs).into_iter().map(|x| DummyVal::new(x, q)).collect()
+    }
+
+    fn vals(xs: &[DummyVal]) -> Vec<u16> {
+        xs.iter().map(|x| x.val()).collect()
+    }
+
+    // A byte-level transcription of the designers' reference implementation,
+    // sharing only the permutation, which `photon` checks on its own.
+    struct Reference {
+        rate: usize,
+        state: [u8; 32],
+    }
+
+    impl Reference {
+        fn permute(&mut self) {
+            let mut f = Dummy::new();
+            let xs = dummy_cells(&self.state);
+            let zs = f.photon_256(&xs).unwrap();
+            self.state.copy_from_slice(&bytes(&vals(&zs)));
+        }
+
+        fn xor_const(&mut self, c: u8) {
+            self.state[31] ^= c << 5;
+        }
+
+        fn hash(&mut self, data: &[u8], c: u8) {
+            for block in data.chunks(self.rate) {
+                self.permute();
+                for (s, x) in self.state.iter_mut().zip(block) {
+                    *s ^= x;
+                }
+                if block.len() < self.rate {
+                    self.state[block.len()] ^= 1;
+                }
+            }
+            self.xor_const(c);
+        }
+
+        fn enc_or_dec(&mut self, data: &[u8], c: u8, decrypt: bool) -> Vec<u8> {
+            let h = self.rate / 2;
+            let mut out = Vec::new();
+            for block in data.chunks(self.rate) {
+                self.permute();
+                let mut rot = vec![0u8; h];
+                for i in 0..h {
+                    rot[i] = (self.state[i] >> 1) | ((self.state[(i + 1) % h] & 1) << 7);
+                }
+                let ys = block
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| x ^ if i < h { self.state[h + i] } else { rot[i - h] })
+                    .collect::<Vec<_>>();
+                let pt = if decrypt { &ys[..] } else { block };
+                for (s, x) in self.state.iter_mut().zip(pt) {
+                    *s ^= x;
+                }
+                if block.len() < self.rate {
+                    self.state[block.len()] ^= 1;
+                }
+                out.extend(ys);
+            }
+            self.xor_const(c);
+            out
+        }
+
+        fn run(
+            rate: usize,
+            key: &[u8],
+            nonce: &[u8],
+            ad: &[u8],
+            data: &[u8],
+            decrypt: bool,
+        ) -> (Vec<u8>, Vec<u8>) {
+            let mut r = Reference {
+                rate,
+                state: [0; 32],
+            };
+            r.state[..16].copy_from_slice(nonce);
+            r.state[16..].copy_from_slice(key);
+            let mut out = Vec::new();
+            if ad.is_empty() && data.is_empty() {
+                r.xor_const(1);
+            } else {
+                let sel = |c1: bool, c2: bool, o: [u8; 4]| o[2 * !c1 as usize + !c2 as usize];
+                let c0 = sel(!data.is_empty(), ad.len() % rate == 0, [1, 2, 3, 4]);
+                let c1 = sel(!ad.is_empty(), data.len() % rate == 0, [1, 2, 5, 6]);
+                if !ad.is_empty() {
+                    r.hash(ad, c0);
+                }
+                if !data.is_empty() {
+                    out = r.enc_or_dec(data, c1, decrypt);
+                }
+            }
+            r.permute();
+            (out, r.state[..16].to_vec())
+        }
+    }
+
+    fn random_bytes(n: usize) -> Vec<u8> {
+        let mut rng = thread_rng();
+        (0..n).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn dummy_matches_reference() {
+        let mut rng = thread_rng();
+        for &rate in &[PhotonBeetleRate::R32, PhotonBeetleRate::R128] {
+            let rb = rate.ncells() / 2;
+            // lengths around the block boundaries, including the empty cases
+            let lens = [0, 1, rb - 1, rb, rb + 1, 2 * rb, 2 * rb + 3];
+            for &alen in lens.iter() {
+                for &mlen in lens.iter() {
+                    let (key, nonce) = (random_bytes(16), random_bytes(16));
+                    let (ad, msg) = (random_bytes(alen), random_bytes(mlen));
+                    let (ct, tag) = Reference::run(rb, &key, &nonce, &ad, &msg, false);
+
+                    let mut f = Dummy::new();
+                    let (k, n) = (dummy_cells(&key), dummy_cells(&nonce));
+                    let (a, m) = (dummy_cells(&ad), dummy_cells(&msg));
+                    let (c, t) =
+                        photon_beetle_aead_encrypt(&mut f, rate, &k, &n, &a, &m).unwrap();
+                    assert_eq!(bytes(&vals(&c)), ct);
+                    assert_eq!(bytes(&vals(&t)), tag);
+
+                    let (p, ok) =
+                        photon_beetle_aead_decrypt(&mut f, rate, &k, &n, &a, &c, &t).unwrap();
+                    assert_eq!(bytes(&vals(&p)), msg);
+                    assert_eq!(ok.val(), 1);
+
+                    let mut bad = tag.clone();
+                    bad[rng.gen_range(0, 16)] ^= 1 << rng.gen_range(0, 8);
+                    let t = dummy_cells(&bad);
+                    let (_, ok) =
+                        photon_beetle_aead_decrypt(&mut f, rate, &k, &n, &a, &c, &t).unwrap();
+                    assert_eq!(ok.val(), 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_inputs() {
+        let mut f = Dummy::new();
+        let rate = PhotonBeetleRate::R128;
+        let k = dummy_cells(&[0; 16]);
+        let m = dummy_cells(&[0; 3]);
+        assert!(photon_beetle_aead_encrypt(&mut f, rate, &k[..30], &k, &[], &m).is_err());
+        assert!(photon_beetle_aead_encrypt(&mut f, rate, &k, &k, &m[..3], &m).is_err());
+        let x = vec![DummyVal::new(0, Modulus::Zq { q: 16 }); 2];
+        assert!(photon_beetle_aead_encrypt(&mut f, rate, &k, &k, &x, &m).is_err());
+        assert!(photon_beetle_aead_decrypt(&mut f, rate, &k, &k, &[], &m, &m).is_err());
+    }
+
+    #[test]
+    fn garbled_matches_reference() {
+        let rate = PhotonBeetleRate::R32;
+        let q = PhotonParams::P256.modulus;
+        let (key, nonce) = (random_bytes(16), random_bytes(16));
+        let (ad, msg) = (random_bytes(3), random_bytes(6));
+        let (ct, tag) = Reference::run(4, &key, &nonce, &ad, &msg, false);
+
+        let mut b = CircuitBuilder::new();
+        let k = b.garbler_inputs(&[q; 32]);
+        let n = b.evaluator_inputs(&[q; 32]);
+        let a = b.evaluator_inputs(&[q; 6]);
+        let m = b.evaluator_inputs(&[q; 12]);
+        let (c, t) = photon_beetle_aead_encrypt(&mut b, rate, &k, &n, &a, &m).unwrap();
+        let (p, ok) = photon_beetle_aead_decrypt(&mut b, rate, &k, &n, &a, &c, &t).unwrap();
+        b.outputs(&c).unwrap();
+        b.outputs(&t).unwrap();
+        b.outputs(&p).unwrap();
+        b.output(&ok).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+
+        let K = en.encode_garbler_inputs(&cells(&key));
+        let xs = [cells(&nonce), cells(&ad), cells(&msg)].concat();
+        let X = en.encode_evaluator_inputs(&xs);
+        let out = gc.eval(&circ, &K, &X).unwrap();
+        assert_eq!(bytes(&out[..12]), ct);
+        assert_eq!(bytes(&out[12..44]), tag);
+        assert_eq!(bytes(&out[44..56]), msg);
+        assert_eq!(out[56], 1);
+    }
+}
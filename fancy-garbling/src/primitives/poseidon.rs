@@ -0,0 +1,364 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The Poseidon permutation and sponge hash of Grassi et al. (USENIX Security
+//! 2021), over a prime field.
+//!
+//! Elements are wires of modulus `Zq` for a prime `q`. Each round adds its
+//! constants, applies the S-box `x^alpha` to every element in a full round or
+//! to the first in a partial round, and multiplies by the MDS matrix, which is
+//! compiled once into a `LinearLayer`. The rounds are split as `R_F / 2` full,
+//! `R_P` partial, then `R_F / 2` full.
+//!
+//! Only the S-boxes cost anything: each is a single projection of `q - 1`
+//! ciphertexts, from `ZqGadgets::zq_pow_const`.
+
+use super::{
+    sponge::{Sponge, SpongePadding},
+    utils,
+    LinearLayer,
+};
+use crate::{
+    errors::FancyError,
+    fancy::{Fancy, HasModulus, ZqGadgets},
+    util,
+    Modulus,
+};
+use rand::{Rng, SeedableRng};
+use scuttlebutt::{AesRng, Block};
+
+/// The parameters of a Poseidon permutation over `Zq`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoseidonParams {
+    q: u16,
+    alpha: u32,
+    full_rounds: usize,
+    partial_rounds: usize,
+    mds: LinearLayer,
+    constants: Vec<Vec<u16>>,
+}
+
+impl PoseidonParams {
+    /// Poseidon over `Zq` with S-box `x^alpha`, `full_rounds` full and
+    /// `partial_rounds` partial rounds, the `t`-by-`t` matrix `mds`, and one
+    /// vector of `t` constants per round.
+    ///
+    /// Fails unless `q` is prime, `alpha > 1` is coprime to `q - 1`, so that
+    /// the S-box is a permutation, `full_rounds` is even, and the dimensions
+    /// agree. That `mds` is MDS is up to the caller.
+    pub fn new(
+        q: u16,
+        alpha: u32,
+        full_rounds: usize,
+        partial_rounds: usize,
+        mds: Vec<Vec<u16>>,
+        constants: Vec<Vec<u16>>,
+    ) -> Result<Self, FancyError> {
+        if !util::is_prime(q) || alpha < 2 || gcd(alpha, q as u32 - 1) != 1 {
+            return Err(FancyError::InvalidArg(format!(
+                "Poseidon needs a prime q and alpha > 1 coprime to q - 1, got q = {}, alpha = {}",
+                q, alpha
+            )));
+        }
+        if full_rounds % 2 != 0 {
+            return Err(FancyError::InvalidArg(format!(
+                "Poseidon needs an even number of full rounds, got {}",
+                full_rounds
+            )));
+        }
+        let t = mds.len();
+        if t < 2 {
+            return Err(FancyError::InvalidArgNum { got: t, needed: 2 });
+        }
+        if constants.len() != full_rounds + partial_rounds {
+            return Err(FancyError::InvalidArgNum {
+                got: constants.len(),
+                needed: full_rounds + partial_rounds,
+            });
+        }
+        if let Some(row) = mds.iter().chain(constants.iter()).find(|r| r.len() != t) {
+            return Err(FancyError::InvalidArgNum {
+                got: row.len(),
+                needed: t,
+            });
+        }
+        if mds.iter().chain(constants.iter()).flatten().any(|&c| c >= q) {
+            return Err(FancyError::InvalidArg(format!(
+                "Poseidon constants must be reduced modulo {}",
+                q
+            )));
+        }
+        Ok(PoseidonParams {
+            q,
+            alpha,
+            full_rounds,
+            partial_rounds,
+            mds: LinearLayer::new(mds, Modulus::Zq { q })?,
+            constants,
+        })
+    }
+
+    /// Poseidon over `Zq` with width `t`, whose round constants are drawn from
+    /// an `AesRng` seeded with `seed`, so that both parties derive the same
+    /// instance, and whose matrix is the Cauchy matrix `1 / (i + t + j)`.
+    pub fn generate(
+        q: u16,
+        t: usize,
+        alpha: u32,
+        full_rounds: usize,
+        partial_rounds: usize,
+        seed: Block,
+    ) -> Result<Self, FancyError> {
+        if 2 * t > q as usize {
+            return Err(FancyError::InvalidArg(format!(
+                "a Cauchy matrix of width {} needs q >= {}, got {}",
+                t,
+                2 * t,
+                q
+            )));
+        }
+        let mds = (0..t)
+            .map(|i| {
+                (0..t)
+                    .map(|j| util::pow_mod((i + t + j) as u16, q as u32 - 2, q))
+                    .collect()
+            })
+            .collect();
+        let mut rng = AesRng::from_seed(seed);
+        let constants = (0..full_rounds + partial_rounds)
+            .map(|_| (0..t).map(|_| rng.gen_range(0, q)).collect())
+            .collect();
+        PoseidonParams::new(q, alpha, full_rounds, partial_rounds, mds, constants)
+    }
+
+    /// The modulus of the elements.
+    pub fn modulus(&self) -> Modulus {
+        Modulus::Zq { q: self.q }
+    }
+
+    /// The width `t`, that is the number of elements in the state.
+    pub fn width(&self) -> usize {
+        self.mds.noutputs()
+    }
+
+    /// The exponent of the S-box.
+    pub fn alpha(&self) -> u32 {
+        self.alpha
+    }
+
+    /// The number of full rounds.
+    pub fn full_rounds(&self) -> usize {
+        self.full_rounds
+    }
+
+    /// The number of partial rounds.
+    pub fn partial_rounds(&self) -> usize {
+        self.partial_rounds
+    }
+
+    /// The MDS matrix.
+    pub fn mds(&self) -> &[Vec<u16>] {
+        self.mds.matrix()
+    }
+
+    /// The round constants, one vector per round.
+    pub fn constants(&self) -> &[Vec<u16>] {
+        &self.constants
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Apply the Poseidon permutation of `params` to `state`.
+pub fn poseidon_permute<F: Fancy>(
+    f: &mut F,
+    params: &PoseidonParams,
+    state: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    let t = params.width();
+    if state.len() != t {
+        return Err(F::Error::from(FancyError::InvalidArgNum {
+            got: state.len(),
+            needed: t,
+        }));
+    }
+    let q = params.modulus();
+    if let Some(x) = state.iter().find(|x| x.modulus() != q) {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: x.modulus(),
+            needed: q,
+        }));
+    }
+
+    let half = params.full_rounds / 2;
+    let mut state = state.to_vec();
+    for (r, cs) in params.constants.iter().enumerate() {
+        for (x, &c) in state.iter_mut().zip(cs) {
+            *x = utils::add_constant(f, x, c)?;
+        }
+        let full = r < half || r >= half + params.partial_rounds;
+        let n = if full { t } else { 1 };
+        for x in state[..n].iter_mut() {
+            *x = f.zq_pow_const(x, params.alpha)?;
+        }
+        state = params.mds.apply(f, &state)?;
+    }
+    Ok(state)
+}
+
+/// Hash `xs` with the Poseidon sponge of `params`, with a capacity of one
+/// element and a rate of `t - 1`.
+///
+/// The capacity element starts as the number of inputs, which separates
+/// inputs of different lengths, so a partial last block is just filled with
+/// zeros. The first rate element is the digest.
+pub fn poseidon_hash<F: Fancy>(
+    f: &mut F,
+    params: &PoseidonParams,
+    xs: &[F::Item],
+) -> Result<F::Item, F::Error> {
+    let (q, t) = (params.modulus(), params.width());
+    let len = (xs.len() % params.q as usize) as u16;
+    let mut state = vec![f.constant(len, &q)?];
+    for _ in 1..t {
+        state.push(f.constant(0, &q)?);
+    }
+    let permute = |f: &mut F, xs: &[F::Item]| poseidon_permute(f, params, xs);
+    let mut sponge = Sponge::new(state, 1..t, permute)
+        .map_err(F::Error::from)?
+        .with_padding(SpongePadding::Zeros);
+    sponge.absorb(f, xs)?;
+    Ok(sponge.squeeze(f, 1)?.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+    };
+    use rand::thread_rng;
+
+    fn permute(params: &PoseidonParams, state: &[u64]) -> Vec<u64> {
+        let q = params.q as u64;
+        let half = params.full_rounds / 2;
+        let mut x = state.to_vec();
+        for (r, cs) in params.constants.iter().enumerate() {
+            for (x, &c) in x.iter_mut().zip(cs) {
+                *x = (*x + c as u64) % q;
+            }
+            let n = if r < half || r >= half + params.partial_rounds {
+                x.len()
+            } else {
+                1
+            };
+            for x in x[..n].iter_mut() {
+                *x = util::pow_mod(*x as u16, params.alpha, params.q) as u64;
+            }
+            x = params
+                .mds()
+                .iter()
+                .map(|row| row.iter().zip(&x).map(|(&m, &x)| m as u64 * x % q).sum::<u64>() % q)
+                .collect();
+        }
+        x
+    }
+
+    fn hash(params: &PoseidonParams, xs: &[u64]) -> u64 {
+        let q = params.q as u64;
+        let mut state = vec![0; params.width()];
+        state[0] = xs.len() as u64 % q;
+        if xs.is_empty() {
+            state = permute(params, &state);
+        }
+        for block in xs.chunks(params.width() - 1) {
+            for (s, x) in state[1..].iter_mut().zip(block) {
+                *s = (*s + x) % q;
+            }
+            state = permute(params, &state);
+        }
+        state[1]
+    }
+
+    #[test]
+    fn params() {
+        let seed = Block::from(3u128);
+        let p = PoseidonParams::generate(65519, 3, 3, 8, 57, seed).unwrap();
+        assert_eq!(p.width(), 3);
+        assert_eq!(p.constants().len(), 65);
+        assert_eq!(p, PoseidonParams::generate(65519, 3, 3, 8, 57, seed).unwrap());
+        // the Cauchy matrix times its first column of inverses
+        for (i, row) in p.mds().iter().enumerate() {
+            assert_eq!(row[0] as u64 * (i as u64 + 3) % 65519, 1);
+        }
+        // not prime, alpha sharing a factor with q - 1, odd full rounds
+        assert!(PoseidonParams::generate(65517, 3, 3, 8, 57, seed).is_err());
+        assert!(PoseidonParams::generate(65519, 3, 2, 8, 57, seed).is_err());
+        assert!(PoseidonParams::generate(65519, 3, 3, 7, 57, seed).is_err());
+        let mds = vec![vec![1, 2], vec![3, 4]];
+        assert!(PoseidonParams::new(101, 3, 2, 0, mds.clone(), vec![vec![0, 0]; 2]).is_ok());
+        assert!(PoseidonParams::new(101, 3, 2, 0, mds.clone(), vec![vec![0, 0]; 3]).is_err());
+        assert!(PoseidonParams::new(101, 3, 2, 0, mds, vec![vec![0, 101]; 2]).is_err());
+    }
+
+    #[test]
+    fn dummy() {
+        let mut rng = thread_rng();
+        for &(q, t, alpha) in &[(101, 3, 3), (65519, 3, 3), (65519, 5, 5)] {
+            let params = PoseidonParams::generate(q, t, alpha, 8, 22, rng.gen::<Block>()).unwrap();
+            let m = params.modulus();
+            for len in 0..2 * t {
+                let xs = (0..len).map(|_| rng.gen_range(0, q)).collect::<Vec<u16>>();
+                let mut f = Dummy::new();
+                let ws = xs.iter().map(|&x| DummyVal::new(x, m)).collect::<Vec<_>>();
+                let xs = xs.iter().map(|&x| x as u64).collect::<Vec<_>>();
+                let z = poseidon_hash(&mut f, &params, &ws).unwrap();
+                assert_eq!(z.val() as u64, hash(&params, &xs));
+                if len == t {
+                    let zs = poseidon_permute(&mut f, &params, &ws).unwrap();
+                    let zs = zs.iter().map(|z| z.val() as u64).collect::<Vec<_>>();
+                    assert_eq!(zs, permute(&params, &xs));
+                }
+            }
+        }
+        let params = PoseidonParams::generate(101, 3, 3, 8, 22, Block::default()).unwrap();
+        let mut f = Dummy::new();
+        let xs = vec![DummyVal::new(0, Modulus::Zq { q: 103 }); 3];
+        assert!(poseidon_permute(&mut f, &params, &xs).is_err());
+        assert!(poseidon_permute(&mut f, &params, &xs[..2]).is_err());
+    }
+
+    #[test]
+    fn garbled() {
+        let mut rng = thread_rng();
+        let q = 101;
+        let params = PoseidonParams::generate(q, 3, 3, 8, 22, rng.gen::<Block>()).unwrap();
+        let m = params.modulus();
+
+        let mut b = CircuitBuilder::new();
+        let xs = b.garbler_inputs(&[m; 2]);
+        let ys = b.evaluator_inputs(&[m; 3]);
+        let z = poseidon_hash(&mut b, &params, &[xs, ys].concat()).unwrap();
+        b.output(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+
+        let inputs = (0..5).map(|_| rng.gen_range(0, q)).collect::<Vec<u16>>();
+        let X = en.encode_garbler_inputs(&inputs[..2]);
+        let Y = en.encode_evaluator_inputs(&inputs[2..]);
+        let out = gc.eval(&circ, &X, &Y).unwrap();
+        let inputs = inputs.iter().map(|&x| x as u64).collect::<Vec<_>>();
+        assert_eq!(out[0] as u64, hash(&params, &inputs));
+    }
+}
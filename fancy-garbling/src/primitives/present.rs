@@ -0,0 +1,256 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The PRESENT block cipher of Bogdanov et al. (CHES 2007), with its 80- and
+//! 128-bit key schedules.
+//!
+//! Blocks and keys are `BinaryBundle`s, least significant bit first, so bit `i`
+//! of a bundle is bit `i` of the integers in the specification. The permutation
+//! layer and the key rotations are then free wiring, and the cost is all in the
+//! S-boxes: 16 per round for the state and one or two for the key.
+//!
+//! The S-boxes are either circuits of AND gates, from `utils::sbox_bits`, or
+//! projections, from `utils::sbox_bits_proj`.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{BinaryBundle, Fancy, HasModulus},
+    Modulus,
+};
+
+/// The number of rounds of PRESENT.
+pub const PRESENT_ROUNDS: usize = 31;
+
+/// Encrypt the 64 bits `pt` under the 80 or 128 bits `key` with PRESENT, using
+/// AND gates for the S-boxes.
+pub fn present_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &BinaryBundle<F::Item>,
+    pt: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    encrypt(f, key, pt, utils::sbox_bits::<F>)
+}
+
+/// Encrypt the 64 bits `pt` under the 80 or 128 bits `key` with PRESENT, using
+/// projections for the S-boxes.
+pub fn present_encrypt_proj<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &BinaryBundle<F::Item>,
+    pt: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    encrypt(f, key, pt, utils::sbox_bits_proj::<F>)
+}
+
+// An S-box on bits, least significant first.
+type Sbox<F> = fn(
+    &mut F,
+    &[<F as Fancy>::Item],
+    &[u16],
+) -> Result<Vec<<F as Fancy>::Item>, <F as Fancy>::Error>;
+
+fn encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &BinaryBundle<F::Item>,
+    pt: &BinaryBundle<F::Item>,
+    sbox: Sbox<F>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    if pt.size() != 64 {
+        return Err(F::Error::from(FancyError::InvalidArgNum {
+            got: pt.size(),
+            needed: 64,
+        }));
+    }
+    if key.size() != 80 && key.size() != 128 {
+        return Err(F::Error::from(FancyError::InvalidArg(format!(
+            "PRESENT keys have 80 or 128 bits, not {}",
+            key.size()
+        ))));
+    }
+    if let Some(x) = key
+        .wires()
+        .iter()
+        .chain(pt.wires())
+        .find(|x| x.modulus() != Modulus::Zq { q: 2 })
+    {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: x.modulus(),
+            needed: Modulus::Zq { q: 2 },
+        }));
+    }
+
+    let n = key.size();
+    let mut k = key.wires().to_vec();
+    let mut state = pt.wires().to_vec();
+    for round in 1..=PRESENT_ROUNDS {
+        state = add_round_key(f, &state, &k[n - 64..])?;
+        let mut s = Vec::with_capacity(64);
+        for nibble in state.chunks(4) {
+            s.extend(sbox(f, nibble, &utils::PRESENT_SBOX)?);
+        }
+        // bit i moves to 16i mod 63, and bit 63 stays put
+        for (i, x) in s.into_iter().enumerate() {
+            state[if i == 63 { 63 } else { 16 * i % 63 }] = x;
+        }
+        update_key(f, &mut k, round, sbox)?;
+    }
+    add_round_key(f, &state, &k[n - 64..]).map(BinaryBundle::new)
+}
+
+fn add_round_key<F: Fancy + ?Sized>(
+    f: &mut F,
+    state: &[F::Item],
+    k: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    state.iter().zip(k).map(|(x, k)| f.xor(x, k)).collect()
+}
+
+// Move the key register on from round `round`: rotate it left by 61, pass
+// the top nibble (two for 128-bit keys) through the S-box, and add the round
+// counter to five bits in the middle.
+fn update_key<F: Fancy + ?Sized>(
+    f: &mut F,
+    k: &mut Vec<F::Item>,
+    round: usize,
+    sbox: Sbox<F>,
+) -> Result<(), F::Error> {
+    let n = k.len();
+    k.rotate_right(61);
+    let (sboxes, counter) = if n == 80 { (1, 15) } else { (2, 62) };
+    for i in 0..sboxes {
+        let at = n - 4 * (i + 1);
+        let y = sbox(f, &k[at..at + 4], &utils::PRESENT_SBOX)?;
+        k.splice(at..at + 4, y);
+    }
+    for i in 0..5 {
+        if (round >> i) & 1 == 1 {
+            k[counter + i] = f.negate(&k[counter + i])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util,
+        BinaryGadgets,
+        BundleGadgets,
+        FancyInput,
+    };
+    use rand::{thread_rng, Rng};
+
+    // (key bits, key, plaintext, ciphertext); the 80-bit ones are from the
+    // appendix of the PRESENT paper, and the 128-bit ones those computed by
+    // `present` below, which shares all but the key schedule with PRESENT-80
+    const KATS: [(usize, u128, u64, u64); 8] = [
+        (80, 0, 0, 0x5579c1387b228445),
+        (80, (1 << 80) - 1, 0, 0xe72c46c0f5945049),
+        (80, 0, !0, 0xa112ffc72f68417b),
+        (80, (1 << 80) - 1, !0, 0x3333dcd3213210d2),
+        (128, 0, 0, 0x96db702a2e6900af),
+        (128, !0, 0, 0x13238c710272a5d8),
+        (128, 0, !0, 0x3c6019e5e5edd563),
+        (128, !0, !0, 0x628d9fbd4218e5b4),
+    ];
+
+    // PRESENT in the clear, straight from the specification.
+    fn present(n: usize, key: u128, pt: u64) -> u64 {
+        let sbox = |x: u128| utils::PRESENT_SBOX[x as usize] as u128;
+        let mask = if n == 128 { !0 } else { (1 << n) - 1 };
+        let mut k = key;
+        let mut state = pt;
+        for round in 1..=31u128 {
+            state ^= (k >> (n - 64)) as u64;
+            let mut s = 0;
+            for i in 0..16 {
+                s |= (sbox((state >> (4 * i)) as u128 & 0xf) as u64) << (4 * i);
+            }
+            state = 0;
+            for i in 0..64 {
+                let j = if i == 63 { 63 } else { 16 * i % 63 };
+                state |= ((s >> i) & 1) << j;
+            }
+            k = ((k << 61) | (k >> (n - 61))) & mask;
+            k = (k & !(0xf << (n - 4))) | (sbox(k >> (n - 4)) << (n - 4));
+            if n == 80 {
+                k ^= round << 15;
+            } else {
+                k = (k & !(0xf << (n - 8))) | (sbox((k >> (n - 8)) & 0xf) << (n - 8));
+                k ^= round << 62;
+            }
+        }
+        state ^ (k >> (n - 64)) as u64
+    }
+
+    #[test]
+    fn reference_kats() {
+        for &(n, key, pt, ct) in KATS.iter() {
+            assert_eq!(present(n, key, pt), ct, "PRESENT-{} on {:x}", n, key);
+        }
+    }
+
+    #[test]
+    fn dummy() {
+        let mut rng = thread_rng();
+        let mut cases = KATS.to_vec();
+        for &n in &[80, 128] {
+            for _ in 0..8 {
+                let key = rng.gen::<u128>() >> (128 - n);
+                let pt = rng.gen::<u64>();
+                cases.push((n, key, pt, present(n, key, pt)));
+            }
+        }
+        for &(n, key, pt, ct) in cases.iter() {
+            let mut f = Dummy::new();
+            let k = f.bin_encode(key, n).unwrap();
+            let p = f.bin_encode(pt as u128, 64).unwrap();
+            let c = present_encrypt(&mut f, &k, &p).unwrap();
+            assert_eq!(f.bin_output(&c).unwrap().unwrap(), ct as u128);
+            let c = present_encrypt_proj(&mut f, &k, &p).unwrap();
+            assert_eq!(f.bin_output(&c).unwrap().unwrap(), ct as u128);
+        }
+        let mut f = Dummy::new();
+        let k = f.bin_encode(0, 64).unwrap();
+        let p = f.bin_encode(0, 64).unwrap();
+        assert!(present_encrypt(&mut f, &k, &p).is_err());
+        let k = f.bin_encode(0, 80).unwrap();
+        let p = f.bin_encode(0, 63).unwrap();
+        assert!(present_encrypt_proj(&mut f, &k, &p).is_err());
+    }
+
+    #[test]
+    fn garbled() {
+        let mut rng = thread_rng();
+        for &n in &[80, 128] {
+            for &proj in &[false, true] {
+                let mut b = CircuitBuilder::new();
+                let k = b.bin_garbler_input(n);
+                let p = b.bin_evaluator_input(64);
+                let c = if proj {
+                    present_encrypt_proj(&mut b, &k, &p).unwrap()
+                } else {
+                    present_encrypt(&mut b, &k, &p).unwrap()
+                };
+                b.output_bundle(&c).unwrap();
+                let circ = b.finish();
+                let (en, gc) = garble(&circ).unwrap();
+                let key = rng.gen::<u128>() >> (128 - n);
+                let pt = rng.gen::<u64>();
+                let K = en.encode_garbler_inputs(&util::u128_to_bits(key, n));
+                let P = en.encode_evaluator_inputs(&util::u128_to_bits(pt as u128, 64));
+                assert_eq!(
+                    gc.eval(&circ, &K, &P).unwrap(),
+                    util::u128_to_bits(present(n, key, pt) as u128, 64)
+                );
+            }
+        }
+    }
+}
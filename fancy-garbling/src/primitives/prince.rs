@@ -0,0 +1,275 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The PRINCE block cipher of Borghoff et al. (ASIACRYPT 2012).
+//!
+//! Blocks and keys are `BinaryBundle`s, least significant bit first, and the
+//! 128-bit key is `k0 || k1`, with `k0` in the top 64 bits. The linear layers
+//! and the keys are free, so the cost is all in the 16 S-boxes of each of the
+//! 12 S-box layers, half of them inverse S-boxes, each a circuit of AND gates
+//! from `utils::sbox_bits`.
+//!
+//! Decryption is encryption with `k0` and `k0'` swapped and `k1` replaced by
+//! `k1 ^ alpha`, which here means running the round constants backwards.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{BinaryBundle, Fancy, HasModulus},
+    Modulus,
+};
+
+/// The PRINCE S-box.
+pub const PRINCE_SBOX: [u16; 16] = [
+    0xb, 0xf, 0x3, 0x2, 0xa, 0xc, 0x9, 0x1, 0x6, 0x7, 0x8, 0x0, 0xe, 0x5, 0xd, 0x4,
+];
+
+// The round constants, for which RC[i] ^ RC[11 - i] is alpha.
+const RC: [u64; 12] = [
+    0x0000000000000000,
+    0x13198a2e03707344,
+    0xa4093822299f31d0,
+    0x082efa98ec4e6c89,
+    0x452821e638d01377,
+    0xbe5466cf34e90c6c,
+    0x7ef84f78fd955cb1,
+    0x85840851f1ac43aa,
+    0xc882d32f25323c54,
+    0x64a51195e0e3610d,
+    0xd3b5a399ca0c2399,
+    0xc0ac29b7c97c50dd,
+];
+
+/// Encrypt the 64 bits `pt` under the 128 bits `key` with PRINCE.
+pub fn prince_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &BinaryBundle<F::Item>,
+    pt: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    prince(f, key, pt, false)
+}
+
+/// Decrypt the 64 bits `ct` under the 128 bits `key` with PRINCE.
+pub fn prince_decrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &BinaryBundle<F::Item>,
+    ct: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    prince(f, key, ct, true)
+}
+
+// Everything below works on bits most significant first, as the
+// specification does: bit `p` here is bit `63 - p` of a block.
+fn prince<F: Fancy + ?Sized>(
+    f: &mut F,
+    key: &BinaryBundle<F::Item>,
+    x: &BinaryBundle<F::Item>,
+    decrypt: bool,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    for (bs, needed) in [(key, 128), (x, 64)].iter() {
+        if bs.size() != *needed {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: bs.size(),
+                needed: *needed,
+            }));
+        }
+    }
+    if let Some(w) = key
+        .wires()
+        .iter()
+        .chain(x.wires())
+        .find(|w| w.modulus() != Modulus::Zq { q: 2 })
+    {
+        return Err(F::Error::from(FancyError::InvalidArgMod {
+            got: w.modulus(),
+            needed: Modulus::Zq { q: 2 },
+        }));
+    }
+
+    let k = key.wires().iter().rev().cloned().collect::<Vec<_>>();
+    let (k0, k1) = k.split_at(64);
+    // k0' = (k0 >>> 1) ^ (k0 >> 63)
+    let mut k0_ = Vec::with_capacity(64);
+    k0_.push(k0[63].clone());
+    k0_.extend_from_slice(&k0[..62]);
+    k0_.push(f.xor(&k0[62], &k0[0])?);
+    let (pre, post) = if decrypt { (&k0_[..], k0) } else { (k0, &k0_[..]) };
+    let mut rc = RC;
+    if decrypt {
+        rc.reverse();
+    }
+
+    let s = x.wires().iter().rev().cloned().collect::<Vec<_>>();
+    let s = add_key(f, &s, pre, 0)?;
+    let mut s = add_key(f, &s, k1, rc[0])?;
+    for &c in rc[1..6].iter() {
+        s = sbox_layer(f, &s, &PRINCE_SBOX)?;
+        s = shift_rows(&m_prime(f, &s)?, false);
+        s = add_key(f, &s, k1, c)?;
+    }
+    let inv = utils::inverse_sbox(&PRINCE_SBOX).map_err(F::Error::from)?;
+    s = sbox_layer(f, &s, &PRINCE_SBOX)?;
+    s = m_prime(f, &s)?;
+    s = sbox_layer(f, &s, &inv)?;
+    for &c in rc[6..11].iter() {
+        s = add_key(f, &s, k1, c)?;
+        s = m_prime(f, &shift_rows(&s, true))?;
+        s = sbox_layer(f, &s, &inv)?;
+    }
+    let s = add_key(f, &s, k1, rc[11])?;
+    let s = add_key(f, &s, post, 0)?;
+    Ok(BinaryBundle::new(s.into_iter().rev().collect()))
+}
+
+// Add the key bits `k` and the constant `c`.
+fn add_key<F: Fancy + ?Sized>(
+    f: &mut F,
+    s: &[F::Item],
+    k: &[F::Item],
+    c: u64,
+) -> Result<Vec<F::Item>, F::Error> {
+    s.iter()
+        .zip(k)
+        .enumerate()
+        .map(|(p, (x, k))| {
+            let z = f.xor(x, k)?;
+            if (c >> (63 - p)) & 1 == 1 {
+                f.negate(&z)
+            } else {
+                Ok(z)
+            }
+        })
+        .collect()
+}
+
+fn sbox_layer<F: Fancy + ?Sized>(
+    f: &mut F,
+    s: &[F::Item],
+    table: &[u16],
+) -> Result<Vec<F::Item>, F::Error> {
+    let mut out = Vec::with_capacity(64);
+    for nibble in s.chunks(4) {
+        let xs = nibble.iter().rev().cloned().collect::<Vec<_>>();
+        out.extend(utils::sbox_bits(f, &xs, table)?.into_iter().rev());
+    }
+    Ok(out)
+}
+
+// The involution M', which is block diagonal with blocks M0, M1, M1, M0 of 16
+// bits. Block `h` adds bit `b` of its four nibbles into bit `b` of nibble `r`,
+// except for nibble `(b - r - 1) mod 4` in M1 and `(b - r) mod 4` in M0.
+fn m_prime<F: Fancy + ?Sized>(f: &mut F, s: &[F::Item]) -> Result<Vec<F::Item>, F::Error> {
+    let mut out = Vec::with_capacity(64);
+    for h in 0..4 {
+        let hat = if h == 0 || h == 3 { 0 } else { 1 };
+        for r in 0..4 {
+            for b in 0..4 {
+                let mut terms = (0..4)
+                    .filter(|c| (r + c + hat) % 4 != b)
+                    .map(|c| &s[16 * h + 4 * c + b]);
+                let first = terms.next().unwrap().clone();
+                out.push(terms.try_fold(first, |acc, x| f.xor(&acc, x))?);
+            }
+        }
+    }
+    Ok(out)
+}
+
+// The AES-like ShiftRows on the nibbles of a column-major state, or its
+// inverse.
+fn shift_rows<W: Clone>(s: &[W], inverse: bool) -> Vec<W> {
+    let mut out = s.to_vec();
+    for i in 0..16 {
+        let j = (i + 20 * (i % 4)) % 16;
+        let (to, from) = if inverse { (j, i) } else { (i, j) };
+        out[4 * to..4 * to + 4].clone_from_slice(&s[4 * from..4 * from + 4]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util,
+        BinaryGadgets,
+        BundleGadgets,
+        FancyInput,
+    };
+    use rand::{thread_rng, Rng};
+
+    // (k0, k1, plaintext, ciphertext) from the appendix of the PRINCE paper
+    const KATS: [(u64, u64, u64, u64); 5] = [
+        (0, 0, 0, 0x818665aa0d02dfda),
+        (0, 0, !0, 0x604ae6ca03c20ada),
+        (!0, 0, 0, 0x9fb51935fc3df524),
+        (0, !0, 0, 0x78a54cbe737bb7ef),
+        (0, 0xfedcba9876543210, 0x0123456789abcdef, 0xae25ad3ca8fa9ccf),
+    ];
+
+    #[test]
+    fn constants() {
+        for i in 0..12 {
+            assert_eq!(RC[i] ^ RC[11 - i], RC[11]);
+        }
+    }
+
+    #[test]
+    fn dummy_kats() {
+        let mut rng = thread_rng();
+        for &(k0, k1, pt, ct) in KATS.iter() {
+            let mut f = Dummy::new();
+            let k = f.bin_encode((k0 as u128) << 64 | k1 as u128, 128).unwrap();
+            let p = f.bin_encode(pt as u128, 64).unwrap();
+            let c = prince_encrypt(&mut f, &k, &p).unwrap();
+            assert_eq!(f.bin_output(&c).unwrap().unwrap(), ct as u128);
+            let p = prince_decrypt(&mut f, &k, &c).unwrap();
+            assert_eq!(f.bin_output(&p).unwrap().unwrap(), pt as u128);
+        }
+        for _ in 0..16 {
+            let mut f = Dummy::new();
+            let k = f.bin_encode(rng.gen::<u128>(), 128).unwrap();
+            let pt = rng.gen::<u64>() as u128;
+            let p = f.bin_encode(pt, 64).unwrap();
+            let c = prince_encrypt(&mut f, &k, &p).unwrap();
+            let p = prince_decrypt(&mut f, &k, &c).unwrap();
+            assert_eq!(f.bin_output(&p).unwrap().unwrap(), pt);
+        }
+        let mut f = Dummy::new();
+        let k = f.bin_encode(0, 64).unwrap();
+        let p = f.bin_encode(0, 64).unwrap();
+        assert!(prince_encrypt(&mut f, &k, &p).is_err());
+    }
+
+    #[test]
+    fn garbled_kats() {
+        let (k0, k1, pt, ct) = KATS[4];
+        let key = (k0 as u128) << 64 | k1 as u128;
+        for &decrypt in &[false, true] {
+            let mut b = CircuitBuilder::new();
+            let k = b.bin_garbler_input(128);
+            let x = b.bin_evaluator_input(64);
+            let z = if decrypt {
+                prince_decrypt(&mut b, &k, &x).unwrap()
+            } else {
+                prince_encrypt(&mut b, &k, &x).unwrap()
+            };
+            b.output_bundle(&z).unwrap();
+            let circ = b.finish();
+            let (en, gc) = garble(&circ).unwrap();
+            let (x, z) = if decrypt { (ct, pt) } else { (pt, ct) };
+            let K = en.encode_garbler_inputs(&util::u128_to_bits(key, 128));
+            let X = en.encode_evaluator_inputs(&util::u128_to_bits(x as u128, 64));
+            assert_eq!(
+                gc.eval(&circ, &K, &X).unwrap(),
+                util::u128_to_bits(z as u128, 64)
+            );
+        }
+    }
+}
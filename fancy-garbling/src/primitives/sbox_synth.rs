@@ -0,0 +1,446 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Synthesis of AND/XOR circuits for S-boxes given as truth tables.
+//!
+//! `SboxCircuit::synthesize` compiles any table of `n`-bit inputs and `m`-bit
+//! outputs, for `n <= 8`, into a straight-line program on bits, least
+//! significant first. It builds several candidates and keeps the one with the
+//! fewest AND gates, then the fewest XOR gates:
+//!
+//! - the algebraic normal form of every output bit, sharing monomials between
+//!   bits, as `utils::sbox_bits` does, and
+//! - binary decision diagrams for several variable orders, splitting a
+//!   function into `f0 ^ x & (f0 ^ f1)` for the cofactors `f0` and `f1` of its
+//!   last variable `x`, sharing equal subfunctions between output bits, and
+//!   building affine subfunctions from XORs alone.
+//!
+//! XOR and NOT gates are free with free-XOR garbling and every AND gate costs
+//! two ciphertexts, so the circuit costs `2 * num_ands()` ciphertexts.
+
+use crate::{
+    errors::FancyError,
+    fancy::{Fancy, HasModulus},
+    Modulus,
+};
+use std::collections::HashMap;
+
+/// A gate of an `SboxCircuit`. Gates read earlier wires: the inputs come
+/// first, followed by the output of every gate in order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SboxGate {
+    /// The constant bit.
+    Const(bool),
+    /// The XOR of two wires.
+    Xor(usize, usize),
+    /// The AND of two wires.
+    And(usize, usize),
+    /// The negation of a wire.
+    Not(usize),
+}
+
+/// An S-box as a circuit of AND, XOR, and NOT gates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SboxCircuit {
+    ninputs: usize,
+    gates: Vec<SboxGate>,
+    outputs: Vec<usize>,
+}
+
+impl SboxCircuit {
+    /// Synthesize a circuit mapping the bits of every `x` to the `noutputs`
+    /// bits of `table[x]`. The length of `table` must be `2^n` for some
+    /// `1 <= n <= 8`.
+    pub fn synthesize(table: &[u16], noutputs: usize) -> Result<SboxCircuit, FancyError> {
+        let n = table.len().trailing_zeros() as usize;
+        if !table.len().is_power_of_two()
+            || n == 0
+            || n > 8
+            || noutputs == 0
+            || noutputs > 16
+            || table.iter().any(|&y| noutputs < 16 && y >> noutputs != 0)
+        {
+            return Err(FancyError::InvalidTruthTable);
+        }
+        let bits = (0..noutputs)
+            .map(|j| table.iter().map(|y| (y >> j) & 1 == 1).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let mut candidates = vec![Builder::new(n, (0..n).collect()).anf(&bits)];
+        for r in 0..n {
+            let order = (0..n).map(|i| (i + r) % n).collect::<Vec<_>>();
+            let reversed = order.iter().rev().cloned().collect();
+            candidates.push(Builder::new(n, order).bdd(&bits));
+            candidates.push(Builder::new(n, reversed).bdd(&bits));
+        }
+        Ok(candidates
+            .into_iter()
+            .min_by_key(|c| (c.num_ands(), c.num_xors()))
+            .unwrap())
+    }
+
+    /// The number of input bits.
+    pub fn ninputs(&self) -> usize {
+        self.ninputs
+    }
+
+    /// The number of output bits.
+    pub fn noutputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// The gates, in order.
+    pub fn gates(&self) -> &[SboxGate] {
+        &self.gates
+    }
+
+    /// The wires holding the output bits, least significant first.
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    /// The number of AND gates.
+    pub fn num_ands(&self) -> usize {
+        self.gates
+            .iter()
+            .filter(|g| matches!(g, SboxGate::And(..)))
+            .count()
+    }
+
+    /// The number of XOR and NOT gates.
+    pub fn num_xors(&self) -> usize {
+        self.gates
+            .iter()
+            .filter(|g| matches!(g, SboxGate::Xor(..) | SboxGate::Not(_)))
+            .count()
+    }
+
+    /// Evaluate the circuit on the plaintext `x`.
+    pub fn eval_plain(&self, x: u16) -> u16 {
+        let mut wires = (0..self.ninputs)
+            .map(|i| (x >> i) & 1 == 1)
+            .collect::<Vec<_>>();
+        for gate in self.gates.iter() {
+            let z = match *gate {
+                SboxGate::Const(b) => b,
+                SboxGate::Xor(a, b) => wires[a] ^ wires[b],
+                SboxGate::And(a, b) => wires[a] & wires[b],
+                SboxGate::Not(a) => !wires[a],
+            };
+            wires.push(z);
+        }
+        self.outputs
+            .iter()
+            .rev()
+            .fold(0, |acc, &w| acc << 1 | wires[w] as u16)
+    }
+
+    /// Evaluate the circuit on the mod-2 wires `xs`, least significant first.
+    pub fn eval<F: Fancy + ?Sized>(
+        &self,
+        f: &mut F,
+        xs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error> {
+        if xs.len() != self.ninputs {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: self.ninputs,
+            }));
+        }
+        if let Some(x) = xs.iter().find(|x| x.modulus() != Modulus::Zq { q: 2 }) {
+            return Err(F::Error::from(FancyError::InvalidArgMod {
+                got: x.modulus(),
+                needed: Modulus::Zq { q: 2 },
+            }));
+        }
+        let mut wires = xs.to_vec();
+        for gate in self.gates.iter() {
+            let z = match *gate {
+                SboxGate::Const(b) => f.constant(b as u16, &Modulus::Zq { q: 2 })?,
+                SboxGate::Xor(a, b) => f.xor(&wires[a], &wires[b])?,
+                SboxGate::And(a, b) => f.and(&wires[a], &wires[b])?,
+                SboxGate::Not(a) => f.negate(&wires[a])?,
+            };
+            wires.push(z);
+        }
+        Ok(self.outputs.iter().map(|&w| wires[w].clone()).collect())
+    }
+}
+
+// Builds one candidate circuit. Wires are `Option<usize>`, with `None` for
+// the constant zero.
+struct Builder {
+    ninputs: usize,
+    // the input read by variable `i` of the truth tables
+    order: Vec<usize>,
+    gates: Vec<SboxGate>,
+    // the wire of every gate, to reuse it
+    known: HashMap<SboxGate, usize>,
+    // the wire of every truth table over the first variables built so far
+    functions: HashMap<Vec<bool>, Option<usize>>,
+}
+
+impl Builder {
+    fn new(ninputs: usize, order: Vec<usize>) -> Self {
+        Builder {
+            ninputs,
+            order,
+            gates: Vec::new(),
+            known: HashMap::new(),
+            functions: HashMap::new(),
+        }
+    }
+
+    fn gate(&mut self, gate: SboxGate) -> usize {
+        let gate = match gate {
+            SboxGate::Xor(a, b) => SboxGate::Xor(a.min(b), a.max(b)),
+            SboxGate::And(a, b) => SboxGate::And(a.min(b), a.max(b)),
+            g => g,
+        };
+        if let Some(&w) = self.known.get(&gate) {
+            return w;
+        }
+        self.gates.push(gate);
+        let w = self.ninputs + self.gates.len() - 1;
+        self.known.insert(gate, w);
+        w
+    }
+
+    fn xor(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        match (a, b) {
+            (None, x) | (x, None) => x,
+            (Some(a), Some(b)) => Some(self.gate(SboxGate::Xor(a, b))),
+        }
+    }
+
+    fn not(&mut self, a: Option<usize>) -> Option<usize> {
+        Some(match a {
+            None => self.gate(SboxGate::Const(true)),
+            Some(a) => self.gate(SboxGate::Not(a)),
+        })
+    }
+
+    fn finish(mut self, outputs: Vec<Option<usize>>) -> SboxCircuit {
+        let outputs = outputs
+            .into_iter()
+            .map(|w| match w {
+                Some(w) => w,
+                None => self.gate(SboxGate::Const(false)),
+            })
+            .collect();
+        SboxCircuit {
+            ninputs: self.ninputs,
+            gates: self.gates,
+            outputs,
+        }
+    }
+
+    // XOR the monomials of the algebraic normal form of every output bit.
+    fn anf(mut self, bits: &[Vec<bool>]) -> SboxCircuit {
+        let mut monomials: HashMap<usize, Option<usize>> = HashMap::new();
+        let outputs = bits
+            .iter()
+            .map(|tt| {
+                let coeffs = anf(tt);
+                let mut z = None;
+                for u in (1..tt.len()).filter(|&u| coeffs[u]) {
+                    let m = self.monomial(u, &mut monomials);
+                    z = self.xor(z, m);
+                }
+                if coeffs[0] {
+                    z = self.not(z);
+                }
+                z
+            })
+            .collect();
+        self.finish(outputs)
+    }
+
+    // The product of the variables in the nonempty set `u`.
+    fn monomial(
+        &mut self,
+        u: usize,
+        monomials: &mut HashMap<usize, Option<usize>>,
+    ) -> Option<usize> {
+        if let Some(&m) = monomials.get(&u) {
+            return m;
+        }
+        let low = u.trailing_zeros() as usize;
+        let x = self.order[low];
+        let m = if u == 1 << low {
+            Some(x)
+        } else {
+            let rest = self.monomial(u & (u - 1), monomials).unwrap();
+            Some(self.gate(SboxGate::And(rest, x)))
+        };
+        monomials.insert(u, m);
+        m
+    }
+
+    // Decompose every output bit into a shared decision diagram.
+    fn bdd(mut self, bits: &[Vec<bool>]) -> SboxCircuit {
+        // permute the tables so that variable `i` reads input `order[i]`
+        let n = self.ninputs;
+        let bits = bits
+            .iter()
+            .map(|tt| {
+                (0..tt.len())
+                    .map(|y| {
+                        let x = (0..n).fold(0, |x, i| x | ((y >> i) & 1) << self.order[i]);
+                        tt[x]
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let outputs = bits.iter().map(|tt| self.function(tt)).collect();
+        self.finish(outputs)
+    }
+
+    // The wire computing the truth table `tt` over the first variables.
+    fn function(&mut self, tt: &[bool]) -> Option<usize> {
+        if let Some(&w) = self.functions.get(tt) {
+            return w;
+        }
+        let coeffs = anf(tt);
+        let w = if (1..tt.len()).all(|u| !coeffs[u] || u.is_power_of_two()) {
+            // affine, so only XORs
+            let mut z = None;
+            for i in (0..tt.len().trailing_zeros() as usize).filter(|&i| coeffs[1 << i]) {
+                z = self.xor(z, Some(self.order[i]));
+            }
+            if coeffs[0] {
+                self.not(z)
+            } else {
+                z
+            }
+        } else {
+            let half = tt.len() / 2;
+            let (f0, f1) = tt.split_at(half);
+            let g = f0.iter().zip(f1).map(|(a, b)| a ^ b).collect::<Vec<_>>();
+            let x = self.order[half.trailing_zeros() as usize];
+            let z0 = self.function(f0);
+            let t = if g.iter().all(|&b| b) {
+                Some(x)
+            } else {
+                let zg = self.function(&g);
+                zg.map(|zg| self.gate(SboxGate::And(x, zg)))
+            };
+            self.xor(z0, t)
+        };
+        self.functions.insert(tt.to_vec(), w);
+        w
+    }
+}
+
+// The coefficients of the algebraic normal form of the truth table `tt`.
+fn anf(tt: &[bool]) -> Vec<bool> {
+    let mut coeffs = tt.to_vec();
+    let mut step = 1;
+    while step < coeffs.len() {
+        for u in 0..coeffs.len() {
+            if u & step != 0 {
+                coeffs[u] ^= coeffs[u ^ step];
+            }
+        }
+        step <<= 1;
+    }
+    coeffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        primitives::utils::{AES_SBOX, PRESENT_SBOX},
+        util,
+    };
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn random_tables() {
+        let mut rng = thread_rng();
+        for &(n, m) in &[(1, 1), (2, 3), (3, 5), (4, 4), (5, 3), (6, 6), (7, 1), (8, 2)] {
+            for _ in 0..4 {
+                let table = (0..1 << n)
+                    .map(|_| rng.gen_range(0, 1u32 << m) as u16)
+                    .collect::<Vec<_>>();
+                let c = SboxCircuit::synthesize(&table, m).unwrap();
+                assert_eq!((c.ninputs(), c.noutputs()), (n, m));
+                for (x, &y) in table.iter().enumerate() {
+                    assert_eq!(c.eval_plain(x as u16), y, "table {:?}", table);
+                }
+            }
+        }
+        // constant and affine outputs need no AND gates
+        let table = (0..16)
+            .map(|x| 0b1000 | (x & 1) << 1 | ((x >> 1) ^ (x >> 3)) & 1)
+            .collect::<Vec<_>>();
+        let c = SboxCircuit::synthesize(&table, 4).unwrap();
+        assert_eq!(c.num_ands(), 0);
+        for (x, &y) in table.iter().enumerate() {
+            assert_eq!(c.eval_plain(x as u16), y);
+        }
+    }
+
+    #[test]
+    fn no_worse_than_anf() {
+        for table in [&PRESENT_SBOX[..], &AES_SBOX[..]].iter() {
+            let n = table.len().trailing_zeros() as usize;
+            let bits = (0..n)
+                .map(|j| table.iter().map(|y| (y >> j) & 1 == 1).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let anf = Builder::new(n, (0..n).collect()).anf(&bits);
+            let c = SboxCircuit::synthesize(table, n).unwrap();
+            assert!(c.num_ands() <= anf.num_ands());
+            for (x, &y) in table.iter().enumerate() {
+                assert_eq!(c.eval_plain(x as u16), y);
+            }
+        }
+        assert!(SboxCircuit::synthesize(&PRESENT_SBOX, 4).unwrap().num_ands() <= 11);
+    }
+
+    #[test]
+    fn rejects_bad_tables() {
+        assert!(SboxCircuit::synthesize(&[0, 1, 2], 2).is_err());
+        assert!(SboxCircuit::synthesize(&[0], 1).is_err());
+        assert!(SboxCircuit::synthesize(&[0, 4], 2).is_err());
+        assert!(SboxCircuit::synthesize(&[0, 1], 0).is_err());
+        assert!(SboxCircuit::synthesize(&vec![0; 512], 1).is_err());
+    }
+
+    #[test]
+    fn dummy_and_garbled() {
+        let q2 = Modulus::Zq { q: 2 };
+        let c = SboxCircuit::synthesize(&PRESENT_SBOX, 4).unwrap();
+        let mut f = Dummy::new();
+        for x in 0..16u16 {
+            let xs = (0..4)
+                .map(|i| DummyVal::new((x >> i) & 1, q2))
+                .collect::<Vec<_>>();
+            let ys = c.eval(&mut f, &xs).unwrap();
+            let y = ys.iter().rev().fold(0, |acc, y| acc << 1 | y.val());
+            assert_eq!(y, PRESENT_SBOX[x as usize]);
+        }
+        assert!(c.eval(&mut f, &[DummyVal::new(0, q2)]).is_err());
+        assert!(c.eval(&mut f, &vec![DummyVal::new(0, Modulus::Zq { q: 3 }); 4]).is_err());
+
+        let c = SboxCircuit::synthesize(&AES_SBOX, 8).unwrap();
+        let mut b = CircuitBuilder::new();
+        let xs = b.evaluator_inputs(&[q2; 8]);
+        let ys = c.eval(&mut b, &xs).unwrap();
+        b.outputs(&ys).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        for x in 0..256 {
+            let X = en.encode_evaluator_inputs(&util::u128_to_bits(x, 8));
+            let y = util::u128_from_bits(&gc.eval(&circ, &[], &X).unwrap());
+            assert_eq!(y as u16, AES_SBOX[x as usize]);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The SHA-256 hash function of FIPS 180-4.
+//!
+//! Words are 32-bit `BinaryBundle`s, least significant bit first, and messages
+//! are bytes, as 8-bit `BinaryBundle`s, read into words big-endian as the
+//! standard does. The message length is public, so padding is free.
+//!
+//! Rotations, shifts, and the sigma functions are free. Each round of the
+//! compression function costs 32 AND gates for each of `Ch` and `Maj` and
+//! seven additions of 31 AND gates, and each scheduled word three more
+//! additions.
+
+use super::{hmac::HashGadget, simon::rotate};
+use crate::{
+    errors::FancyError,
+    fancy::{BinaryBundle, BinaryGadgets, HasModulus},
+    Modulus,
+};
+
+/// The initial hash value.
+pub const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// The round constants, the fractional parts of the cube roots of the first 64
+// primes.
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// SHA-256 as a `HashGadget`, whose digest is the 32 bytes of `sha256`, each
+/// word big-endian.
+#[derive(Clone, Copy, Debug)]
+pub struct Sha256;
+
+impl<F: BinaryGadgets> HashGadget<F> for Sha256 {
+    const BLOCK_BYTES: usize = 64;
+
+    fn hash(
+        f: &mut F,
+        msg: &[BinaryBundle<F::Item>],
+    ) -> Result<Vec<BinaryBundle<F::Item>>, F::Error> {
+        let words = sha256(f, msg)?;
+        Ok(words
+            .iter()
+            .flat_map(|w| w.wires().chunks(8).rev().map(|b| BinaryBundle::new(b.to_vec())))
+            .collect())
+    }
+}
+
+/// Hash the bytes `msg` with SHA-256, returning the digest as eight words.
+pub fn sha256<F: BinaryGadgets>(
+    f: &mut F,
+    msg: &[BinaryBundle<F::Item>],
+) -> Result<Vec<BinaryBundle<F::Item>>, F::Error> {
+    check_bits::<F>(msg, 8)?;
+    // 0x80, zeros up to 56 bytes mod 64, then the length in bits
+    let mut padded = msg.to_vec();
+    let nzeros = (119 - msg.len() % 64) % 64;
+    padded.push(f.bin_constant_bundle(0x80, 8)?);
+    for _ in 0..nzeros {
+        padded.push(f.bin_constant_bundle(0, 8)?);
+    }
+    let bits = (msg.len() as u64).wrapping_mul(8);
+    for i in (0..8).rev() {
+        padded.push(f.bin_constant_bundle(((bits >> (8 * i)) & 0xff) as u128, 8)?);
+    }
+
+    let mut state = SHA256_IV
+        .iter()
+        .map(|&h| f.bin_constant_bundle(h as u128, 32))
+        .collect::<Result<Vec<_>, _>>()?;
+    for block in padded.chunks(64) {
+        let words = block
+            .chunks(4)
+            .map(|bytes| {
+                let bits = bytes.iter().rev().flat_map(|b| b.wires().to_vec());
+                BinaryBundle::new(bits.collect())
+            })
+            .collect::<Vec<_>>();
+        state = sha256_compress(f, &state, &words)?;
+    }
+    Ok(state)
+}
+
+/// The SHA-256 compression function, updating the eight words of `state` with
+/// the sixteen words of `block`.
+pub fn sha256_compress<F: BinaryGadgets>(
+    f: &mut F,
+    state: &[BinaryBundle<F::Item>],
+    block: &[BinaryBundle<F::Item>],
+) -> Result<Vec<BinaryBundle<F::Item>>, F::Error> {
+    for (words, needed) in [(state, 8), (block, 16)].iter() {
+        if words.len() != *needed {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: words.len(),
+                needed: *needed,
+            }));
+        }
+    }
+    check_bits::<F>(state, 32)?;
+    check_bits::<F>(block, 32)?;
+
+    let mut w = block.to_vec();
+    for t in 16..64 {
+        let s0 = sigma(f, &w[t - 15], 7, 18, 3)?;
+        let s1 = sigma(f, &w[t - 2], 17, 19, 10)?;
+        let x = f.bin_addition_no_carry(&s1, &w[t - 7])?;
+        let y = f.bin_addition_no_carry(&s0, &w[t - 16])?;
+        w.push(f.bin_addition_no_carry(&x, &y)?);
+    }
+
+    let mut v = state.to_vec();
+    for t in 0..64 {
+        let (a, b, c) = (&v[0], &v[1], &v[2]);
+        let (e, g, h) = (&v[4], &v[6], &v[7]);
+        let s1 = big_sigma(f, e, 6, 11, 25)?;
+        // Ch(e, f, g) = g ^ (e & (f ^ g))
+        let ch = f.bin_xor(&v[5], g)?;
+        let ch = f.bin_and(e, &ch)?;
+        let ch = f.bin_xor(&ch, g)?;
+        let k = f.bin_constant_bundle(K[t] as u128, 32)?;
+        let t1 = f.bin_addition_no_carry(h, &s1)?;
+        let t1 = f.bin_addition_no_carry(&t1, &ch)?;
+        let kw = f.bin_addition_no_carry(&k, &w[t])?;
+        let t1 = f.bin_addition_no_carry(&t1, &kw)?;
+        let s0 = big_sigma(f, a, 2, 13, 22)?;
+        // Maj(a, b, c) = b ^ ((a ^ b) & (b ^ c))
+        let x = f.bin_xor(a, b)?;
+        let y = f.bin_xor(b, c)?;
+        let maj = f.bin_and(&x, &y)?;
+        let maj = f.bin_xor(&maj, b)?;
+        let t2 = f.bin_addition_no_carry(&s0, &maj)?;
+        let e = f.bin_addition_no_carry(&v[3], &t1)?;
+        let a = f.bin_addition_no_carry(&t1, &t2)?;
+        v.pop();
+        v.insert(0, a);
+        v[4] = e;
+    }
+    state
+        .iter()
+        .zip(v.iter())
+        .map(|(h, x)| f.bin_addition_no_carry(h, x))
+        .collect()
+}
+
+// x >>> r0 ^ x >>> r1 ^ x >>> r2
+fn big_sigma<F: BinaryGadgets>(
+    f: &mut F,
+    x: &BinaryBundle<F::Item>,
+    r0: usize,
+    r1: usize,
+    r2: usize,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    let y = f.bin_xor(&rotate(x, 32 - r0), &rotate(x, 32 - r1))?;
+    f.bin_xor(&y, &rotate(x, 32 - r2))
+}
+
+// x >>> r0 ^ x >>> r1 ^ x >> s, where the shift only reaches the low bits.
+fn sigma<F: BinaryGadgets>(
+    f: &mut F,
+    x: &BinaryBundle<F::Item>,
+    r0: usize,
+    r1: usize,
+    s: usize,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    let y = f.bin_xor(&rotate(x, 32 - r0), &rotate(x, 32 - r1))?;
+    let mut bits = y.wires().to_vec();
+    for (i, b) in bits[..32 - s].iter_mut().enumerate() {
+        *b = f.xor(b, &x.wires()[i + s])?;
+    }
+    Ok(BinaryBundle::new(bits))
+}
+
+fn check_bits<F: BinaryGadgets>(words: &[BinaryBundle<F::Item>], n: usize) -> Result<(), F::Error> {
+    for w in words {
+        if w.size() != n {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: w.size(),
+                needed: n,
+            }));
+        }
+        if let Some(x) = w.wires().iter().find(|x| x.modulus() != Modulus::Zq { q: 2 }) {
+            return Err(F::Error::from(FancyError::InvalidArgMod {
+                got: x.modulus(),
+                needed: Modulus::Zq { q: 2 },
+            }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util,
+        BundleGadgets,
+        FancyInput,
+    };
+    use rand::{thread_rng, Rng};
+    use sha2::{Digest, Sha256};
+
+    // (message, digest) from the FIPS 180 examples
+    const KATS: [(&[u8], [u32; 8]); 3] = [
+        (
+            b"abc",
+            [
+                0xba7816bf, 0x8f01cfea, 0x414140de, 0x5dae2223, 0xb00361a3, 0x96177a9c, 0xb410ff61,
+                0xf20015ad,
+            ],
+        ),
+        (
+            b"",
+            [
+                0xe3b0c442, 0x98fc1c14, 0x9afbf4c8, 0x996fb924, 0x27ae41e4, 0x649b934c, 0xa495991b,
+                0x7852b855,
+            ],
+        ),
+        (
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            [
+                0x248d6a61, 0xd20638b8, 0xe5c02693, 0x0c3e6039, 0xa33ce459, 0x64ff2167, 0xf6ecedd4,
+                0x19db06c1,
+            ],
+        ),
+    ];
+
+    fn digest_words(msg: &[u8]) -> Vec<u128> {
+        Sha256::digest(msg)
+            .chunks(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as u128)
+            .collect()
+    }
+
+    fn dummy_sha256(msg: &[u8]) -> Vec<u128> {
+        let mut f = Dummy::new();
+        let bytes = msg.iter().map(|&b| b as u128).collect::<Vec<_>>();
+        let m = f.bin_encode_many(&bytes, 8).unwrap();
+        let h = sha256(&mut f, &m).unwrap();
+        f.bin_outputs(&h).unwrap().unwrap()
+    }
+
+    #[test]
+    fn constants() {
+        let primes = (2u32..).filter(|&p| util::is_prime(p as u16));
+        for (p, &k) in primes.zip(K.iter()) {
+            let r = (p as f64).cbrt();
+            assert_eq!(((r - r.floor()) * (1u64 << 32) as f64) as u32, k);
+        }
+        let primes = (2u32..).filter(|&p| util::is_prime(p as u16));
+        for (p, &h) in primes.zip(SHA256_IV.iter()) {
+            let r = (p as f64).sqrt();
+            assert_eq!(((r - r.floor()) * (1u64 << 32) as f64) as u32, h);
+        }
+    }
+
+    #[test]
+    fn dummy_kats() {
+        for (msg, digest) in KATS.iter() {
+            let want = digest.iter().map(|&w| w as u128).collect::<Vec<_>>();
+            assert_eq!(dummy_sha256(msg), want);
+        }
+        let mut rng = thread_rng();
+        // every padding case, up to three blocks
+        for len in (50..70).chain(110..130).chain(rng.gen_range(0, 192)..192).step_by(3) {
+            let msg = (0..len).map(|_| rng.gen()).collect::<Vec<u8>>();
+            assert_eq!(dummy_sha256(&msg), digest_words(&msg), "length {}", len);
+        }
+        let mut f = Dummy::new();
+        let m = f.bin_encode_many(&[0, 0], 7).unwrap();
+        assert!(sha256(&mut f, &m).is_err());
+        let s = f.bin_encode_many(&[0; 8], 32).unwrap();
+        assert!(sha256_compress(&mut f, &s, &s).is_err());
+    }
+
+    #[test]
+    fn garbled_kats() {
+        let (msg, digest) = KATS[0];
+        let mut b = CircuitBuilder::new();
+        let m = (0..msg.len())
+            .map(|_| b.bin_evaluator_input(8))
+            .collect::<Vec<_>>();
+        let h = sha256(&mut b, &m).unwrap();
+        for x in h.iter() {
+            b.output_bundle(x).unwrap();
+        }
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let bits = msg
+            .iter()
+            .flat_map(|&x| util::u128_to_bits(x as u128, 8))
+            .collect::<Vec<_>>();
+        let M = en.encode_evaluator_inputs(&bits);
+        let want = digest
+            .iter()
+            .flat_map(|&x| util::u128_to_bits(x as u128, 32))
+            .collect::<Vec<_>>();
+        assert_eq!(gc.eval(&circ, &[], &M).unwrap(), want);
+    }
+}
@@ -0,0 +1,286 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The SIMON family of block ciphers of Beaulieu et al. (NSA, 2013).
+//!
+//! Words are `BinaryBundle`s, least significant bit first. Blocks are two
+//! words `[x, y]` and keys `m` words `[k_{m-1}, ..., k_0]`, in the order the
+//! specification writes them. Rotations and the key schedule are free, and each
+//! round costs one AND gate per bit of a word.
+
+use super::utils;
+use crate::{
+    errors::FancyError,
+    fancy::{BinaryBundle, BinaryGadgets, HasModulus},
+    Modulus,
+};
+
+// The constant sequences z_0 to z_4, bit i being the i-th of the sequence.
+const Z: [u64; 5] = [
+    0x19c3522fb386a45f,
+    0x16864fb8ad0c9f71,
+    0x3369f885192c0ef5,
+    0x3c2ce51207a635db,
+    0x3dc94c3a046d678b,
+];
+
+/// Parameters of a SIMON block cipher.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimonParams {
+    /// The number of bits in a word, half the block size.
+    pub n: usize,
+    /// The number of words in the key.
+    pub m: usize,
+    /// The number of rounds.
+    pub rounds: usize,
+    /// The index of the constant sequence of the key schedule.
+    pub z: usize,
+}
+
+impl SimonParams {
+    /// SIMON32/64.
+    pub const SIMON32_64: SimonParams = SimonParams {
+        n: 16,
+        m: 4,
+        rounds: 32,
+        z: 0,
+    };
+
+    /// SIMON48/72.
+    pub const SIMON48_72: SimonParams = SimonParams {
+        n: 24,
+        m: 3,
+        rounds: 36,
+        z: 0,
+    };
+
+    /// SIMON48/96.
+    pub const SIMON48_96: SimonParams = SimonParams {
+        n: 24,
+        m: 4,
+        rounds: 36,
+        z: 1,
+    };
+
+    /// SIMON64/96.
+    pub const SIMON64_96: SimonParams = SimonParams {
+        n: 32,
+        m: 3,
+        rounds: 42,
+        z: 2,
+    };
+
+    /// SIMON64/128.
+    pub const SIMON64_128: SimonParams = SimonParams {
+        n: 32,
+        m: 4,
+        rounds: 44,
+        z: 3,
+    };
+
+    /// SIMON96/96.
+    pub const SIMON96_96: SimonParams = SimonParams {
+        n: 48,
+        m: 2,
+        rounds: 52,
+        z: 2,
+    };
+
+    /// SIMON96/144.
+    pub const SIMON96_144: SimonParams = SimonParams {
+        n: 48,
+        m: 3,
+        rounds: 54,
+        z: 3,
+    };
+
+    /// SIMON128/128.
+    pub const SIMON128_128: SimonParams = SimonParams {
+        n: 64,
+        m: 2,
+        rounds: 68,
+        z: 2,
+    };
+
+    /// SIMON128/192.
+    pub const SIMON128_192: SimonParams = SimonParams {
+        n: 64,
+        m: 3,
+        rounds: 69,
+        z: 3,
+    };
+
+    /// SIMON128/256.
+    pub const SIMON128_256: SimonParams = SimonParams {
+        n: 64,
+        m: 4,
+        rounds: 72,
+        z: 4,
+    };
+}
+
+/// Encrypt the block `pt` under `key` with the SIMON cipher of `params`.
+pub fn simon_encrypt<F: BinaryGadgets>(
+    f: &mut F,
+    params: &SimonParams,
+    key: &[BinaryBundle<F::Item>],
+    pt: &[BinaryBundle<F::Item>],
+) -> Result<Vec<BinaryBundle<F::Item>>, F::Error> {
+    let (n, m) = (params.n, params.m);
+    check_words::<F>(key, pt, n, m)?;
+
+    let mut k = key.iter().rev().cloned().collect::<Vec<_>>();
+    let (mut x, mut y) = (pt[0].clone(), pt[1].clone());
+    for i in 0..params.rounds {
+        if i >= m {
+            // k_i from k_{i-m} to k_{i-1}
+            let mut t = rotate(&k[i - 1], n - 3);
+            if m == 4 {
+                t = f.bin_xor(&t, &k[i - 3])?;
+            }
+            let t = f.bin_xor(&t, &rotate(&t, n - 1))?;
+            let t = f.bin_xor(&k[i - m], &t)?;
+            let z = (Z[params.z] >> ((i - m) % 62)) & 1;
+            let c = !3 ^ z as u128;
+            k.push(BinaryBundle::new(utils::xor_constant_bits(f, t.wires(), c)?));
+        }
+        let a = f.bin_and(&rotate(&x, 1), &rotate(&x, 8))?;
+        let b = f.bin_xor(&a, &rotate(&x, 2))?;
+        let b = f.bin_xor(&b, &y)?;
+        y = x;
+        x = f.bin_xor(&b, &k[i])?;
+    }
+    Ok(vec![x, y])
+}
+
+// Rotate the word `x` left by `r`.
+pub(super) fn rotate<W: Clone + HasModulus>(x: &BinaryBundle<W>, r: usize) -> BinaryBundle<W> {
+    BinaryBundle::new(utils::rotate_left(x.wires(), r))
+}
+
+// Check that `key` and `block` are `m` and 2 words of `n` bits.
+pub(super) fn check_words<F: BinaryGadgets>(
+    key: &[BinaryBundle<F::Item>],
+    block: &[BinaryBundle<F::Item>],
+    n: usize,
+    m: usize,
+) -> Result<(), F::Error> {
+    for (words, needed) in [(key, m), (block, 2)].iter() {
+        if words.len() != *needed {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: words.len(),
+                needed: *needed,
+            }));
+        }
+    }
+    for w in key.iter().chain(block) {
+        if w.size() != n {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: w.size(),
+                needed: n,
+            }));
+        }
+        if let Some(x) = w.wires().iter().find(|x| x.modulus() != Modulus::Zq { q: 2 }) {
+            return Err(F::Error::from(FancyError::InvalidArgMod {
+                got: x.modulus(),
+                needed: Modulus::Zq { q: 2 },
+            }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util,
+        BundleGadgets,
+        FancyInput,
+    };
+
+    // (params, key, plaintext, ciphertext) from the SIMON and SPECK paper
+    const KATS: [(SimonParams, &[u128], [u128; 2], [u128; 2]); 5] = [
+        (
+            SimonParams::SIMON32_64,
+            &[0x1918, 0x1110, 0x0908, 0x0100],
+            [0x6565, 0x6877],
+            [0xc69b, 0xe9bb],
+        ),
+        (
+            SimonParams::SIMON48_72,
+            &[0x121110, 0x0a0908, 0x020100],
+            [0x612067, 0x6e696c],
+            [0xdae5ac, 0x292cac],
+        ),
+        (
+            SimonParams::SIMON64_128,
+            &[0x1b1a1918, 0x13121110, 0x0b0a0908, 0x03020100],
+            [0x656b696c, 0x20646e75],
+            [0x44c8fc20, 0xb9dfa07a],
+        ),
+        (
+            SimonParams::SIMON96_96,
+            &[0x0d0c0b0a0908, 0x050403020100],
+            [0x2072616c6c69, 0x702065687420],
+            [0x602807a462b4, 0x69063d8ff082],
+        ),
+        (
+            SimonParams::SIMON128_128,
+            &[0x0f0e0d0c0b0a0908, 0x0706050403020100],
+            [0x6373656420737265, 0x6c6c657661727420],
+            [0x49681b1e1e54fe3f, 0x65aa832af84e0bbc],
+        ),
+    ];
+
+    #[test]
+    fn dummy_kats() {
+        for (params, key, pt, ct) in KATS.iter() {
+            let mut f = Dummy::new();
+            let k = f.bin_encode_many(key, params.n).unwrap();
+            let p = f.bin_encode_many(pt, params.n).unwrap();
+            let c = simon_encrypt(&mut f, params, &k, &p).unwrap();
+            assert_eq!(&f.bin_outputs(&c).unwrap().unwrap()[..], &ct[..]);
+        }
+        let (params, key, pt, _) = KATS[0];
+        let mut f = Dummy::new();
+        let k = f.bin_encode_many(&key[1..], params.n).unwrap();
+        let p = f.bin_encode_many(&pt, params.n).unwrap();
+        assert!(simon_encrypt(&mut f, &params, &k, &p).is_err());
+        let k = f.bin_encode_many(key, params.n).unwrap();
+        let p = f.bin_encode_many(&pt, 24).unwrap();
+        assert!(simon_encrypt(&mut f, &params, &k, &p).is_err());
+    }
+
+    #[test]
+    fn garbled_kats() {
+        let (params, key, pt, ct) = KATS[2];
+        let mut b = CircuitBuilder::new();
+        let k = (0..params.m)
+            .map(|_| b.bin_garbler_input(params.n))
+            .collect::<Vec<_>>();
+        let p = (0..2)
+            .map(|_| b.bin_evaluator_input(params.n))
+            .collect::<Vec<_>>();
+        let c = simon_encrypt(&mut b, &params, &k, &p).unwrap();
+        for x in c.iter() {
+            b.output_bundle(x).unwrap();
+        }
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let bits = |xs: &[u128]| {
+            xs.iter()
+                .flat_map(|&x| util::u128_to_bits(x, params.n))
+                .collect::<Vec<_>>()
+        };
+        let K = en.encode_garbler_inputs(&bits(key));
+        let P = en.encode_evaluator_inputs(&bits(&pt));
+        assert_eq!(gc.eval(&circ, &K, &P).unwrap(), bits(&ct));
+    }
+}
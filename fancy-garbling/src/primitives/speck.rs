@@ -0,0 +1,256 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The SPECK family of block ciphers of Beaulieu et al. (NSA, 2013).
+//!
+//! Words are `BinaryBundle`s, least significant bit first. Blocks are two
+//! words `[x, y]` and keys `m` words `[l_{m-2}, ..., l_0, k_0]`, in the order
+//! the specification writes them. Rotations are free, and each round costs an
+//! addition of words for the state and another for the key schedule, each of
+//! `n - 1` AND gates for words of `n` bits.
+
+use super::{
+    simon::{check_words, rotate},
+    utils,
+};
+use crate::fancy::{BinaryBundle, BinaryGadgets};
+
+/// Parameters of a SPECK block cipher.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpeckParams {
+    /// The number of bits in a word, half the block size.
+    pub n: usize,
+    /// The number of words in the key.
+    pub m: usize,
+    /// The number of rounds.
+    pub rounds: usize,
+    /// The right rotation of `x` in each round.
+    pub alpha: usize,
+    /// The left rotation of `y` in each round.
+    pub beta: usize,
+}
+
+impl SpeckParams {
+    /// SPECK32/64.
+    pub const SPECK32_64: SpeckParams = SpeckParams {
+        n: 16,
+        m: 4,
+        rounds: 22,
+        alpha: 7,
+        beta: 2,
+    };
+
+    /// SPECK48/72.
+    pub const SPECK48_72: SpeckParams = SpeckParams {
+        n: 24,
+        m: 3,
+        rounds: 22,
+        alpha: 8,
+        beta: 3,
+    };
+
+    /// SPECK48/96.
+    pub const SPECK48_96: SpeckParams = SpeckParams {
+        n: 24,
+        m: 4,
+        rounds: 23,
+        alpha: 8,
+        beta: 3,
+    };
+
+    /// SPECK64/96.
+    pub const SPECK64_96: SpeckParams = SpeckParams {
+        n: 32,
+        m: 3,
+        rounds: 26,
+        alpha: 8,
+        beta: 3,
+    };
+
+    /// SPECK64/128.
+    pub const SPECK64_128: SpeckParams = SpeckParams {
+        n: 32,
+        m: 4,
+        rounds: 27,
+        alpha: 8,
+        beta: 3,
+    };
+
+    /// SPECK96/96.
+    pub const SPECK96_96: SpeckParams = SpeckParams {
+        n: 48,
+        m: 2,
+        rounds: 28,
+        alpha: 8,
+        beta: 3,
+    };
+
+    /// SPECK96/144.
+    pub const SPECK96_144: SpeckParams = SpeckParams {
+        n: 48,
+        m: 3,
+        rounds: 29,
+        alpha: 8,
+        beta: 3,
+    };
+
+    /// SPECK128/128.
+    pub const SPECK128_128: SpeckParams = SpeckParams {
+        n: 64,
+        m: 2,
+        rounds: 32,
+        alpha: 8,
+        beta: 3,
+    };
+
+    /// SPECK128/192.
+    pub const SPECK128_192: SpeckParams = SpeckParams {
+        n: 64,
+        m: 3,
+        rounds: 33,
+        alpha: 8,
+        beta: 3,
+    };
+
+    /// SPECK128/256.
+    pub const SPECK128_256: SpeckParams = SpeckParams {
+        n: 64,
+        m: 4,
+        rounds: 34,
+        alpha: 8,
+        beta: 3,
+    };
+}
+
+/// Encrypt the block `pt` under `key` with the SPECK cipher of `params`.
+pub fn speck_encrypt<F: BinaryGadgets>(
+    f: &mut F,
+    params: &SpeckParams,
+    key: &[BinaryBundle<F::Item>],
+    pt: &[BinaryBundle<F::Item>],
+) -> Result<Vec<BinaryBundle<F::Item>>, F::Error> {
+    let (n, m) = (params.n, params.m);
+    check_words::<F>(key, pt, n, m)?;
+
+    let mut k = key[m - 1].clone();
+    let mut l = key[..m - 1].iter().rev().cloned().collect::<Vec<_>>();
+    let (mut x, mut y) = (pt[0].clone(), pt[1].clone());
+    for i in 0..params.rounds {
+        let s = add(f, params, &x, &y)?;
+        x = f.bin_xor(&s, &k)?;
+        y = rotate(&y, params.beta);
+        y = f.bin_xor(&y, &x)?;
+        if i + 1 < params.rounds {
+            // the key schedule is the round function with the counter as key
+            let l_ = add(f, params, &l[i], &k)?;
+            let l_ = BinaryBundle::new(utils::xor_constant_bits(f, l_.wires(), i as u128)?);
+            k = rotate(&k, params.beta);
+            k = f.bin_xor(&k, &l_)?;
+            l.push(l_);
+        }
+    }
+    Ok(vec![x, y])
+}
+
+// x rotated right by alpha, plus y.
+fn add<F: BinaryGadgets>(
+    f: &mut F,
+    params: &SpeckParams,
+    x: &BinaryBundle<F::Item>,
+    y: &BinaryBundle<F::Item>,
+) -> Result<BinaryBundle<F::Item>, F::Error> {
+    let x = rotate(x, params.n - params.alpha);
+    f.bin_addition_no_carry(&x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::Dummy,
+        util,
+        BundleGadgets,
+        FancyInput,
+    };
+
+    // (params, key, plaintext, ciphertext) from the SIMON and SPECK paper
+    const KATS: [(SpeckParams, &[u128], [u128; 2], [u128; 2]); 5] = [
+        (
+            SpeckParams::SPECK32_64,
+            &[0x1918, 0x1110, 0x0908, 0x0100],
+            [0x6574, 0x694c],
+            [0xa868, 0x42f2],
+        ),
+        (
+            SpeckParams::SPECK48_72,
+            &[0x121110, 0x0a0908, 0x020100],
+            [0x20796c, 0x6c6172],
+            [0xc049a5, 0x385adc],
+        ),
+        (
+            SpeckParams::SPECK64_128,
+            &[0x1b1a1918, 0x13121110, 0x0b0a0908, 0x03020100],
+            [0x3b726574, 0x7475432d],
+            [0x8c6fa548, 0x454e028b],
+        ),
+        (
+            SpeckParams::SPECK96_96,
+            &[0x0d0c0b0a0908, 0x050403020100],
+            [0x65776f68202c, 0x656761737520],
+            [0x9e4d09ab7178, 0x62bdde8f79aa],
+        ),
+        (
+            SpeckParams::SPECK128_128,
+            &[0x0f0e0d0c0b0a0908, 0x0706050403020100],
+            [0x6c61766975716520, 0x7469206564616d20],
+            [0xa65d985179783265, 0x7860fedf5c570d18],
+        ),
+    ];
+
+    #[test]
+    fn dummy_kats() {
+        for (params, key, pt, ct) in KATS.iter() {
+            let mut f = Dummy::new();
+            let k = f.bin_encode_many(key, params.n).unwrap();
+            let p = f.bin_encode_many(pt, params.n).unwrap();
+            let c = speck_encrypt(&mut f, params, &k, &p).unwrap();
+            assert_eq!(&f.bin_outputs(&c).unwrap().unwrap()[..], &ct[..]);
+        }
+        let (params, key, pt, _) = KATS[0];
+        let mut f = Dummy::new();
+        let k = f.bin_encode_many(key, params.n).unwrap();
+        let p = f.bin_encode_many(&pt[..1], params.n).unwrap();
+        assert!(speck_encrypt(&mut f, &params, &k, &p).is_err());
+    }
+
+    #[test]
+    fn garbled_kats() {
+        let (params, key, pt, ct) = KATS[2];
+        let mut b = CircuitBuilder::new();
+        let k = (0..params.m)
+            .map(|_| b.bin_garbler_input(params.n))
+            .collect::<Vec<_>>();
+        let p = (0..2)
+            .map(|_| b.bin_evaluator_input(params.n))
+            .collect::<Vec<_>>();
+        let c = speck_encrypt(&mut b, &params, &k, &p).unwrap();
+        for x in c.iter() {
+            b.output_bundle(x).unwrap();
+        }
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let bits = |xs: &[u128]| {
+            xs.iter()
+                .flat_map(|&x| util::u128_to_bits(x, params.n))
+                .collect::<Vec<_>>()
+        };
+        let K = en.encode_garbler_inputs(&bits(key));
+        let P = en.encode_evaluator_inputs(&bits(&pt));
+        assert_eq!(gc.eval(&circ, &K, &P).unwrap(), bits(&ct));
+    }
+}
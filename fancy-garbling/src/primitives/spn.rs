@@ -0,0 +1,513 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Substitution-permutation networks described as data.
+//!
+//! An `SpnCipher` lists everything about an AES-like cipher on a square state
+//! of field cells, and `spn_encrypt` runs any of them. PHOTON and LED are
+//! parameter sets for it, see `PhotonParams::spn` and `led_cipher`.
+//!
+//! The state is row-major. Only `SubCells` costs ciphertexts: one projection
+//! per cell per round with field wires, AND gates with the binary encoding of
+//! `MixColumnsStrategy::Binary`. Constants, keys, `ShiftRows`, and `MixColumns`
+//! are free either way, and `MixColumnsStrategy::cheapest` picks the encoding.
+//! With `fuse_constants`, round constants are folded into the S-box tables,
+//! which the garbler precomputes once per distinct constant.
+//!
+//! The projections of a round are independent and are made with a single
+//! `Fancy::proj_many`, which the streaming `Garbler` computes on worker threads
+//! with the `parallel` feature.
+
+use super::{utils, LinearLayer};
+use crate::{
+    dummy::{Dummy, DummyVal},
+    errors::FancyError,
+    fancy::{Fancy, HasModulus},
+    informer::{Informer, InformerVal},
+    Modulus,
+};
+use std::collections::HashMap;
+
+/// An SPN cipher or permutation on a `d`-by-`d` state.
+///
+/// Each round adds its constants, applies the S-box to every cell, rotates
+/// row `i` left by `shift_rows[i]`, and multiplies every column by
+/// `Serial(mds_row)^d`. With a `key_period`, a round key is added before the
+/// first round, then before every `key_period`-th round, and once more after
+/// the last round.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpnCipher {
+    /// The dimension of the state.
+    pub d: usize,
+    /// The field of the state cells.
+    pub modulus: Modulus,
+    /// The S-box applied to every cell.
+    pub sbox: Vec<u16>,
+    /// The left rotation of each row.
+    pub shift_rows: Vec<usize>,
+    /// The last row of the `Serial` matrix, whose `d`-th power is applied to
+    /// the columns, as in `utils::mix_columns_mds`.
+    pub mds_row: Vec<u16>,
+    /// The constants of each round, a row-major matrix of `d * d` cells per
+    /// round. There are as many rounds as matrices.
+    pub round_constants: Vec<Vec<u16>>,
+    /// How many rounds apart round keys are added, if at all.
+    pub key_period: Option<usize>,
+    /// Whether to fold each round constant into the S-box table of its cell,
+    /// which saves a constant and an addition per nonzero constant. The output
+    /// is the same either way.
+    pub fuse_constants: bool,
+}
+
+/// How `spn_encrypt_with` encodes the cells of the state, which decides how
+/// `MixColumns` and `SubCells` are computed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MixColumnsStrategy {
+    /// One field wire per cell. `MixColumns` uses free `cmul` and `add` gates,
+    /// as compiled by `LinearLayer::serial`, and `SubCells` one projection per
+    /// cell.
+    Field,
+    /// `k` mod-2 wires per cell of `GF(2^k)`, least significant first, for
+    /// `k <= 8`. `MixColumns` is a free XOR network, as in
+    /// `utils::mix_columns_mds_bits`, and `SubCells` uses `utils::sbox_bits`.
+    Binary,
+}
+
+impl MixColumnsStrategy {
+    /// The number of wires encoding a cell of `modulus`.
+    pub fn wires_per_cell(&self, modulus: &Modulus) -> usize {
+        match self {
+            MixColumnsStrategy::Field => 1,
+            MixColumnsStrategy::Binary => modulus.bit_length(),
+        }
+    }
+
+    /// The modulus of the wires encoding a cell of `modulus`.
+    pub fn wire_modulus(&self, modulus: &Modulus) -> Modulus {
+        match self {
+            MixColumnsStrategy::Field => *modulus,
+            MixColumnsStrategy::Binary => Modulus::Zq { q: 2 },
+        }
+    }
+
+    /// The strategy that garbles `cipher` with the fewest ciphertexts, then
+    /// the fewest free gates, as counted by an `Informer`. Ties go to `Field`.
+    pub fn cheapest(cipher: &SpnCipher) -> MixColumnsStrategy {
+        let cost = |strategy: MixColumnsStrategy| {
+            let mut f = Informer::new(Dummy::new());
+            let w = strategy.wires_per_cell(&cipher.modulus);
+            let x = DummyVal::new(0, strategy.wire_modulus(&cipher.modulus));
+            let x = InformerVal::new(x);
+            let state = vec![x; cipher.d * cipher.d * w];
+            let keys = match cipher.key_period {
+                Some(_) => vec![state.clone()],
+                None => vec![],
+            };
+            spn_encrypt_with(&mut f, cipher, strategy, &keys, &state).ok()?;
+            let stats = f.stats();
+            let gates = stats.num_adds() + stats.num_subs() + stats.num_cmuls();
+            Some((stats.num_ciphertexts(), gates))
+        };
+        [MixColumnsStrategy::Field, MixColumnsStrategy::Binary]
+            .iter()
+            .filter_map(|&s| cost(s).map(|c| (c, s)))
+            .min_by_key(|&(c, _)| c)
+            .map_or(MixColumnsStrategy::Field, |(_, s)| s)
+    }
+}
+
+impl SpnCipher {
+    /// The number of rounds.
+    pub fn rounds(&self) -> usize {
+        self.round_constants.len()
+    }
+
+    /// The number of round keys added by `spn_encrypt`.
+    pub fn key_additions(&self) -> usize {
+        match self.key_period {
+            Some(p) => (self.rounds() + p - 1) / p + 1,
+            None => 0,
+        }
+    }
+
+    // The S-box preceded by adding `c`.
+    fn fused_sbox(&self, c: u16) -> Vec<u16> {
+        (0..self.sbox.len())
+            .map(|x| self.sbox[utils::modulus_add(x as u16, c, &self.modulus) as usize])
+            .collect()
+    }
+
+    // Check the description itself, then the inputs of `spn_encrypt_with`.
+    fn check<F: Fancy + ?Sized>(
+        &self,
+        strategy: MixColumnsStrategy,
+        round_keys: &[Vec<F::Item>],
+        state: &[F::Item],
+    ) -> Result<(), F::Error> {
+        let d = self.d;
+        let invalid = |msg: String| Err(F::Error::from(FancyError::InvalidArg(msg)));
+        if self.shift_rows.len() != d || self.mds_row.len() != d {
+            return invalid(format!(
+                "an SPN of dimension {} needs {} shifts and MDS entries",
+                d, d
+            ));
+        }
+        if self.sbox.len() != self.modulus.order() as usize {
+            return invalid(format!("the S-box must have {} entries", self.modulus.order()));
+        }
+        if self.round_constants.iter().any(|cs| cs.len() != d * d) {
+            return invalid(format!("round constants must have {} cells", d * d));
+        }
+        if self.key_period == Some(0) {
+            return invalid("the key period must be positive".to_string());
+        }
+        if self.key_period.is_some() == round_keys.is_empty() {
+            return invalid(format!(
+                "got {} round keys for key period {:?}",
+                round_keys.len(),
+                self.key_period
+            ));
+        }
+        if strategy == MixColumnsStrategy::Binary {
+            match self.modulus {
+                Modulus::GF4 { .. } | Modulus::GF8 { .. } | Modulus::GFk { k: 1..=8, .. } => {}
+                q => return invalid(format!("cells of {} have no binary encoding", q)),
+            }
+        }
+        let n = d * d * strategy.wires_per_cell(&self.modulus);
+        let q = strategy.wire_modulus(&self.modulus);
+        for xs in round_keys.iter().map(|k| &k[..]).chain(std::iter::once(state)) {
+            if xs.len() != n {
+                return Err(F::Error::from(FancyError::InvalidArgNum {
+                    got: xs.len(),
+                    needed: n,
+                }));
+            }
+            if let Some(x) = xs.iter().find(|x| x.modulus() != q) {
+                return Err(F::Error::from(FancyError::InvalidArgMod {
+                    got: x.modulus(),
+                    needed: q,
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run `cipher` on the row-major `state` of field wires. The `k`-th key
+/// addition adds `round_keys[k % round_keys.len()]`, so a single key is added
+/// every time and two keys alternate; an unkeyed `cipher` takes no round keys.
+pub fn spn_encrypt<F: Fancy + ?Sized>(
+    f: &mut F,
+    cipher: &SpnCipher,
+    round_keys: &[Vec<F::Item>],
+    state: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    spn_encrypt_with(f, cipher, MixColumnsStrategy::Field, round_keys, state)
+}
+
+/// `spn_encrypt` with the cells of `state` and `round_keys` encoded according
+/// to `strategy`.
+pub fn spn_encrypt_with<F: Fancy + ?Sized>(
+    f: &mut F,
+    cipher: &SpnCipher,
+    strategy: MixColumnsStrategy,
+    round_keys: &[Vec<F::Item>],
+    state: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    cipher.check::<F>(strategy, round_keys, state)?;
+    let (d, w) = (cipher.d, strategy.wires_per_cell(&cipher.modulus));
+    let mds = match strategy {
+        MixColumnsStrategy::Field => LinearLayer::serial(&cipher.mds_row, &cipher.modulus)?,
+        MixColumnsStrategy::Binary => {
+            let m = utils::serial_matrix_power(&cipher.mds_row, &cipher.modulus);
+            LinearLayer::binary(&m, &cipher.modulus)?
+        }
+    };
+    let mut fused = HashMap::new();
+    let mut keys = round_keys.iter().cycle();
+    let mut state = state.to_vec();
+    for (r, cs) in cipher.round_constants.iter().enumerate() {
+        if let Some(p) = cipher.key_period {
+            if r % p == 0 {
+                state = add_key(f, &state, keys.next().unwrap())?;
+            }
+        }
+        // the cells are independent, so SubCells is a batch that the backend
+        // may garble in parallel
+        let mut xs = Vec::with_capacity(state.len());
+        let mut tables = Vec::with_capacity(d * d);
+        for (x, &c) in state.chunks(w).zip(cs) {
+            if cipher.fuse_constants && c != 0 {
+                xs.extend(x.iter().cloned());
+                tables.push(fused.entry(c).or_insert_with(|| cipher.fused_sbox(c)).clone());
+            } else {
+                xs.extend(add_constant(f, strategy, x, c)?);
+                tables.push(cipher.sbox.clone());
+            }
+        }
+        let cells = match strategy {
+            MixColumnsStrategy::Field => f.proj_many(&xs, &cipher.modulus, &tables)?,
+            MixColumnsStrategy::Binary => {
+                let mut cells = Vec::with_capacity(xs.len());
+                for (x, table) in xs.chunks(w).zip(&tables) {
+                    cells.extend(utils::sbox_bits(f, x, table)?);
+                }
+                cells
+            }
+        };
+        state = (0..d * d)
+            .flat_map(|k| {
+                let (i, j) = (k / d, k % d);
+                cells[(i * d + (j + cipher.shift_rows[i]) % d) * w..][..w].to_vec()
+            })
+            .collect();
+        state = mds.apply_columns(f, &state, w)?;
+    }
+    if cipher.key_period.is_some() {
+        state = add_key(f, &state, keys.next().unwrap())?;
+    }
+    Ok(state)
+}
+
+fn add_key<F: Fancy + ?Sized>(
+    f: &mut F,
+    state: &[F::Item],
+    k: &[F::Item],
+) -> Result<Vec<F::Item>, F::Error> {
+    state.iter().zip(k).map(|(x, k)| f.add(x, k)).collect()
+}
+
+// Add the constant `c` to the cell `x`.
+fn add_constant<F: Fancy + ?Sized>(
+    f: &mut F,
+    strategy: MixColumnsStrategy,
+    x: &[F::Item],
+    c: u16,
+) -> Result<Vec<F::Item>, F::Error> {
+    match strategy {
+        MixColumnsStrategy::Field => Ok(vec![utils::add_constant(f, &x[0], c)?]),
+        MixColumnsStrategy::Binary => x
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                if (c >> i) & 1 == 1 {
+                    f.negate(b)
+                } else {
+                    Ok(b.clone())
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::CircuitBuilder, classic::garble, util::RngExt};
+    use rand::{thread_rng, Rng};
+
+    // A random cipher on a 3-by-3 state of GF(2^4) cells, keyed every other
+    // round.
+    fn toy(rounds: usize) -> SpnCipher {
+        let mut rng = thread_rng();
+        let mut sbox = (0..16).collect::<Vec<u16>>();
+        for i in (1..16).rev() {
+            sbox.swap(i, rng.gen_range(0, i + 1));
+        }
+        SpnCipher {
+            d: 3,
+            modulus: Modulus::X4_X_1,
+            sbox,
+            shift_rows: vec![0, 2, 1],
+            mds_row: vec![1, 2, 5],
+            round_constants: (0..rounds)
+                .map(|_| (0..9).map(|_| rng.gen_u16() % 16).collect())
+                .collect(),
+            key_period: Some(2),
+            fuse_constants: false,
+        }
+    }
+
+    // Straight-line plaintext evaluation, written from the description above.
+    fn plain(cipher: &SpnCipher, keys: &[Vec<u16>], pt: &[u16]) -> Vec<u16> {
+        let d = cipher.d;
+        let mul = |a, b| utils::modulus_mul(a, b, &cipher.modulus);
+        let mut x = pt.to_vec();
+        let mut k = 0;
+        for (r, cs) in cipher.round_constants.iter().enumerate() {
+            if r % cipher.key_period.unwrap() == 0 {
+                x = x.iter().zip(&keys[k % keys.len()]).map(|(a, b)| a ^ b).collect();
+                k += 1;
+            }
+            x = x.iter().zip(cs).map(|(&a, &c)| cipher.sbox[(a ^ c) as usize]).collect();
+            x = (0..d * d)
+                .map(|t| x[(t / d) * d + (t % d + cipher.shift_rows[t / d]) % d])
+                .collect();
+            for j in 0..d {
+                for _ in 0..d {
+                    let last = (0..d).fold(0, |acc, i| acc ^ mul(cipher.mds_row[i], x[i * d + j]));
+                    for i in 0..d - 1 {
+                        x[i * d + j] = x[(i + 1) * d + j];
+                    }
+                    x[(d - 1) * d + j] = last;
+                }
+            }
+        }
+        x.iter().zip(&keys[k % keys.len()]).map(|(a, b)| a ^ b).collect()
+    }
+
+    #[test]
+    fn dummy_matches_plain() {
+        let mut rng = thread_rng();
+        for &rounds in &[1, 4, 7] {
+            let cipher = toy(rounds);
+            assert_eq!(cipher.key_additions(), (rounds + 1) / 2 + 1);
+            for nkeys in 1..3 {
+                let keys = (0..nkeys)
+                    .map(|_| (0..9).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                let pt = (0..9).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+                let mut f = Dummy::new();
+                let enc = |xs: &[u16]| {
+                    xs.iter()
+                        .map(|&x| DummyVal::new(x, cipher.modulus))
+                        .collect::<Vec<_>>()
+                };
+                let ks = keys.iter().map(|k| enc(k)).collect::<Vec<_>>();
+                let ct = spn_encrypt(&mut f, &cipher, &ks, &enc(&pt)).unwrap();
+                let ct = ct.iter().map(|x| x.val()).collect::<Vec<_>>();
+                assert_eq!(ct, plain(&cipher, &keys, &pt));
+            }
+        }
+    }
+
+    #[test]
+    fn binary_matches_field() {
+        let mut rng = thread_rng();
+        let cipher = toy(3);
+        let q2 = Modulus::Zq { q: 2 };
+        let bits = |xs: &[u16]| {
+            xs.iter()
+                .flat_map(|&x| (0..4).map(move |i| DummyVal::new((x >> i) & 1, q2)))
+                .collect::<Vec<_>>()
+        };
+        let key = (0..9).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let pt = (0..9).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let mut f = Dummy::new();
+        let ct = spn_encrypt_with(
+            &mut f,
+            &cipher,
+            MixColumnsStrategy::Binary,
+            &[bits(&key)],
+            &bits(&pt),
+        )
+        .unwrap();
+        let ct = ct
+            .chunks(4)
+            .map(|bs| bs.iter().rev().fold(0, |acc, b| acc << 1 | b.val()))
+            .collect::<Vec<_>>();
+        assert_eq!(ct, plain(&cipher, &[key], &pt));
+
+        let x = vec![DummyVal::new(0, Modulus::X4_X_1); 9];
+        let strategy = MixColumnsStrategy::Binary;
+        assert!(spn_encrypt_with(&mut f, &cipher, strategy, std::slice::from_ref(&x), &x).is_err());
+        let mut bad = cipher.clone();
+        bad.modulus = Modulus::Zq { q: 17 };
+        bad.sbox = (0..17).collect();
+        let x = bits(&[0; 9]);
+        assert!(spn_encrypt_with(&mut f, &bad, strategy, std::slice::from_ref(&x), &x).is_err());
+    }
+
+    #[test]
+    fn fused_constants() {
+        let mut rng = thread_rng();
+        let cipher = toy(4);
+        let fused = SpnCipher {
+            fuse_constants: true,
+            ..cipher.clone()
+        };
+        let key = (0..9).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let pt = (0..9).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let enc = |xs: &[u16]| {
+            xs.iter()
+                .map(|&x| InformerVal::new(DummyVal::new(x, cipher.modulus)))
+                .collect::<Vec<_>>()
+        };
+        let mut adds = Vec::new();
+        for c in [&cipher, &fused].iter() {
+            let mut f = Informer::new(Dummy::new());
+            let ct = spn_encrypt(&mut f, c, &[enc(&key)], &enc(&pt)).unwrap();
+            let ct = ct.iter().map(|x| x.val()).collect::<Vec<_>>();
+            assert_eq!(ct, plain(&cipher, std::slice::from_ref(&key), &pt));
+            adds.push(f.stats().num_adds());
+        }
+        let nonzero = cipher.round_constants.concat().iter().filter(|&&c| c != 0).count();
+        assert_eq!(adds[0] - adds[1], nonzero);
+    }
+
+    #[test]
+    fn cheapest_strategy() {
+        // the PRESENT S-box needs more AND gates than its projection costs,
+        // while every 2-bit S-box is affine and so free on bits
+        let mut present = toy(2);
+        present.sbox = utils::PRESENT_SBOX.to_vec();
+        assert_eq!(MixColumnsStrategy::cheapest(&present), MixColumnsStrategy::Field);
+        let tiny = SpnCipher {
+            d: 2,
+            modulus: Modulus::GFk { k: 2, p: 0b111 },
+            sbox: vec![0, 1, 3, 2],
+            shift_rows: vec![0, 1],
+            mds_row: vec![1, 2],
+            round_constants: vec![vec![1, 0, 2, 3]; 4],
+            key_period: None,
+            fuse_constants: false,
+        };
+        assert_eq!(MixColumnsStrategy::cheapest(&tiny), MixColumnsStrategy::Binary);
+    }
+
+    #[test]
+    fn rejects_bad_descriptions() {
+        let mut f = Dummy::new();
+        let x = vec![DummyVal::new(0, Modulus::X4_X_1); 9];
+        let keys = vec![x.clone()];
+        let good = toy(2);
+        assert!(spn_encrypt(&mut f, &good, &keys, &x).is_ok());
+        assert!(spn_encrypt(&mut f, &good, &[], &x).is_err());
+        assert!(spn_encrypt(&mut f, &good, &keys, &x[..8]).is_err());
+        let mut bad = good.clone();
+        bad.sbox.pop();
+        assert!(spn_encrypt(&mut f, &bad, &keys, &x).is_err());
+        let mut bad = good.clone();
+        bad.shift_rows.push(3);
+        assert!(spn_encrypt(&mut f, &bad, &keys, &x).is_err());
+        let mut bad = good.clone();
+        bad.key_period = None;
+        assert!(spn_encrypt(&mut f, &bad, &keys, &x).is_err());
+        let y = vec![DummyVal::new(0, Modulus::X4_X3_X2_X_1); 9];
+        assert!(spn_encrypt(&mut f, &good, &keys, &y).is_err());
+    }
+
+    #[test]
+    fn garbled_matches_plain() {
+        let mut rng = thread_rng();
+        let cipher = toy(5);
+        let q = cipher.modulus;
+        let mut b = CircuitBuilder::new();
+        let k = b.garbler_inputs(&[q; 9]);
+        let x = b.evaluator_inputs(&[q; 9]);
+        let z = spn_encrypt(&mut b, &cipher, &[k], &x).unwrap();
+        b.outputs(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+
+        let key = (0..9).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let pt = (0..9).map(|_| rng.gen_u16() % 16).collect::<Vec<_>>();
+        let K = en.encode_garbler_inputs(&key);
+        let X = en.encode_evaluator_inputs(&pt);
+        assert_eq!(gc.eval(&circ, &K, &X).unwrap(), plain(&cipher, &[key], &pt));
+    }
+}
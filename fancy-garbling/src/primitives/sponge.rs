@@ -0,0 +1,331 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! The sponge construction of Bertoni et al., over any permutation gadget.
+//!
+//! A `Sponge` holds a state of cells, a range of them forming the rate, and a
+//! permutation, given as a closure over the `Fancy` object so that any gadget
+//! in `primitives` can drive it. Absorbing adds blocks into the rate, which is
+//! free, and squeezing reads cells off it, so the only cost is that of the
+//! permutations.
+//!
+//! Squeezing a fixed number of cells gives a hash, and squeezing repeatedly an
+//! extendable-output function.
+
+use super::utils;
+use crate::{errors::FancyError, fancy::Fancy};
+use std::ops::Range;
+
+/// How a `Sponge` pads the last block of its input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpongePadding {
+    /// No padding: the input must be a whole number of blocks.
+    None,
+    /// Fill a partial last block with zeros, absorbing at least one block.
+    /// Only safe when the input length is fixed or encoded elsewhere, for
+    /// instance in the initial capacity.
+    Zeros,
+    /// Append a cell with the given value, then zeros up to the end of the
+    /// block, so that a whole last block gets a block of padding. With `1`
+    /// for bits least significant first this is the usual `10*` padding.
+    OneZeros(u16),
+}
+
+/// A sponge over the permutation `P`, which maps a state to a new state.
+pub struct Sponge<F: Fancy, P> {
+    state: Vec<F::Item>,
+    rate: Range<usize>,
+    squeeze_rate: usize,
+    padding: SpongePadding,
+    permute: P,
+    pending: Vec<F::Item>,
+    // the number of cells squeezed from the current state, once squeezing
+    squeezed: Option<usize>,
+    absorbed_any: bool,
+}
+
+impl<F, P> Sponge<F, P>
+where
+    F: Fancy,
+    P: FnMut(&mut F, &[F::Item]) -> Result<Vec<F::Item>, F::Error>,
+{
+    /// Create a sponge from the initial `state`, absorbing into and squeezing
+    /// from the cells `rate`, with `10*` padding.
+    pub fn new(state: Vec<F::Item>, rate: Range<usize>, permute: P) -> Result<Self, FancyError> {
+        if rate.start >= rate.end || rate.end > state.len() {
+            return Err(FancyError::InvalidArg(format!(
+                "a sponge rate must be a nonempty range within the {} cells of the state, got {:?}",
+                state.len(),
+                rate
+            )));
+        }
+        Ok(Sponge {
+            squeeze_rate: rate.len(),
+            state,
+            rate,
+            padding: SpongePadding::OneZeros(1),
+            permute,
+            pending: Vec::new(),
+            squeezed: None,
+            absorbed_any: false,
+        })
+    }
+
+    /// Use `padding` for the last block.
+    pub fn with_padding(mut self, padding: SpongePadding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Squeeze only the first `n` cells of the rate between permutations, as
+    /// PHOTON does. This is clamped to the rate.
+    pub fn with_squeeze_rate(mut self, n: usize) -> Self {
+        self.squeeze_rate = n.max(1).min(self.rate.len());
+        self
+    }
+
+    /// The current state.
+    pub fn state(&self) -> &[F::Item] {
+        &self.state
+    }
+
+    /// Absorb the cells `xs`, permuting after every whole block.
+    pub fn absorb(&mut self, f: &mut F, xs: &[F::Item]) -> Result<(), F::Error> {
+        if self.squeezed.is_some() {
+            return Err(F::Error::from(FancyError::InvalidArg(
+                "cannot absorb into a sponge after squeezing".to_string(),
+            )));
+        }
+        self.pending.extend_from_slice(xs);
+        let r = self.rate.len();
+        while self.pending.len() >= r {
+            let block = self.pending.drain(..r).collect::<Vec<_>>();
+            self.absorb_block(f, &block, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Squeeze `n` cells, padding the input and permuting as needed. Once
+    /// squeezing starts nothing more can be absorbed.
+    pub fn squeeze(&mut self, f: &mut F, n: usize) -> Result<Vec<F::Item>, F::Error> {
+        if self.squeezed.is_none() {
+            self.finish_absorbing(f)?;
+            self.squeezed = Some(0);
+        }
+        let mut out = Vec::with_capacity(n);
+        while out.len() < n {
+            let mut i = self.squeezed.unwrap();
+            if i == self.squeeze_rate {
+                self.state = (self.permute)(f, &self.state)?;
+                i = 0;
+            }
+            let k = (self.squeeze_rate - i).min(n - out.len());
+            let start = self.rate.start + i;
+            out.extend_from_slice(&self.state[start..start + k]);
+            self.squeezed = Some(i + k);
+        }
+        Ok(out)
+    }
+
+    fn finish_absorbing(&mut self, f: &mut F) -> Result<(), F::Error> {
+        let pending = std::mem::take(&mut self.pending);
+        let r = self.rate.len();
+        match self.padding {
+            SpongePadding::None => {
+                if !pending.is_empty() {
+                    return Err(F::Error::from(FancyError::InvalidArg(format!(
+                        "unpadded sponge input must be whole blocks of {} cells",
+                        r
+                    ))));
+                }
+            }
+            SpongePadding::Zeros => {
+                if !pending.is_empty() || !self.absorbed_any {
+                    self.absorb_block(f, &pending, &[])?;
+                }
+            }
+            SpongePadding::OneZeros(one) => {
+                let mut pad = vec![0; r - pending.len()];
+                pad[0] = one;
+                self.absorb_block(f, &pending, &pad)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Add `xs`, followed by the constants `pad`, into the rate and permute.
+    fn absorb_block(&mut self, f: &mut F, xs: &[F::Item], pad: &[u16]) -> Result<(), F::Error> {
+        let rate = &mut self.state[self.rate.clone()];
+        for (s, x) in rate.iter_mut().zip(xs) {
+            *s = f.add(s, x)?;
+        }
+        for (s, &c) in rate[xs.len()..].iter_mut().zip(pad) {
+            *s = utils::add_constant(f, s, c)?;
+        }
+        self.state = (self.permute)(f, &self.state)?;
+        self.absorbed_any = true;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder,
+        classic::garble,
+        dummy::{Dummy, DummyVal},
+        errors::DummyError,
+        primitives::photon::{PhotonGadgets, PhotonParams},
+        Modulus,
+    };
+    use rand::{thread_rng, Rng};
+
+    const Q: u16 = 17;
+
+    // A toy permutation of Z_17^4: rotate, then add the neighbours and a
+    // constant, which is invertible.
+    fn toy(xs: &[u16]) -> Vec<u16> {
+        let n = xs.len();
+        (0..n)
+            .map(|i| (3 * xs[(i + 1) % n] + 2 * xs[i] + i as u16 + 1) % Q)
+            .collect()
+    }
+
+    fn toy_gadget(f: &mut Dummy, xs: &[DummyVal]) -> Result<Vec<DummyVal>, DummyError> {
+        let n = xs.len();
+        (0..n)
+            .map(|i| {
+                let a = f.cmul(&xs[(i + 1) % n], 3)?;
+                let b = f.cmul(&xs[i], 2)?;
+                let c = f.add(&a, &b)?;
+                utils::add_constant(f, &c, i as u16 + 1)
+            })
+            .collect()
+    }
+
+    // The plain sponge on `toy`, with the rate `1..3`.
+    fn plain(xs: &[u16], padding: SpongePadding, squeeze_rate: usize, n: usize) -> Vec<u16> {
+        let mut xs = xs.to_vec();
+        match padding {
+            SpongePadding::None => {}
+            SpongePadding::Zeros => {
+                while xs.is_empty() || xs.len() % 2 != 0 {
+                    xs.push(0);
+                }
+            }
+            SpongePadding::OneZeros(one) => {
+                xs.push(one);
+                if xs.len() % 2 != 0 {
+                    xs.push(0);
+                }
+            }
+        }
+        let mut s = vec![5, 0, 0, 0];
+        for block in xs.chunks(2) {
+            s[1] = (s[1] + block[0]) % Q;
+            s[2] = (s[2] + block[1]) % Q;
+            s = toy(&s);
+        }
+        let mut out = Vec::new();
+        loop {
+            for &x in s[1..1 + squeeze_rate].iter() {
+                out.push(x);
+                if out.len() == n {
+                    return out;
+                }
+            }
+            s = toy(&s);
+        }
+    }
+
+    fn dummy(xs: &[u16], padding: SpongePadding, squeeze_rate: usize, n: usize) -> Vec<u16> {
+        let m = Modulus::Zq { q: Q };
+        let mut f = Dummy::new();
+        let state = [5, 0, 0, 0].iter().map(|&x| DummyVal::new(x, m)).collect();
+        let mut sponge = Sponge::new(state, 1..3, toy_gadget)
+            .unwrap()
+            .with_padding(padding)
+            .with_squeeze_rate(squeeze_rate);
+        let xs = xs.iter().map(|&x| DummyVal::new(x, m)).collect::<Vec<_>>();
+        // absorbing in pieces is the same as all at once
+        let (a, b) = xs.split_at(xs.len() / 3);
+        sponge.absorb(&mut f, a).unwrap();
+        sponge.absorb(&mut f, b).unwrap();
+        let mut out = sponge.squeeze(&mut f, n / 2).unwrap();
+        out.extend(sponge.squeeze(&mut f, n - n / 2).unwrap());
+        out.iter().map(|x| x.val()).collect()
+    }
+
+    #[test]
+    fn dummy_matches_plain() {
+        let mut rng = thread_rng();
+        for len in 0..7 {
+            let xs = (0..len).map(|_| rng.gen_range(0, Q)).collect::<Vec<_>>();
+            for &padding in &[SpongePadding::Zeros, SpongePadding::OneZeros(1)] {
+                for &(sr, n) in &[(2, 1), (2, 5), (1, 4)] {
+                    assert_eq!(dummy(&xs, padding, sr, n), plain(&xs, padding, sr, n));
+                }
+            }
+            if len % 2 == 0 {
+                let padding = SpongePadding::None;
+                assert_eq!(dummy(&xs, padding, 2, 3), plain(&xs, padding, 2, 3));
+            }
+        }
+    }
+
+    #[test]
+    fn misuse() {
+        let m = Modulus::Zq { q: Q };
+        let mut f = Dummy::new();
+        let state = vec![DummyVal::new(0, m); 4];
+        assert!(Sponge::new(state.clone(), 2..2, toy_gadget).is_err());
+        assert!(Sponge::new(state.clone(), 3..5, toy_gadget).is_err());
+        let mut sponge = Sponge::new(state.clone(), 0..2, toy_gadget)
+            .unwrap()
+            .with_padding(SpongePadding::None);
+        sponge.absorb(&mut f, &state[..1]).unwrap();
+        assert!(sponge.squeeze(&mut f, 1).is_err());
+        let mut sponge = Sponge::new(state.clone(), 0..2, toy_gadget).unwrap();
+        sponge.squeeze(&mut f, 1).unwrap();
+        assert!(sponge.absorb(&mut f, &state[..1]).is_err());
+    }
+
+    #[test]
+    fn garbled_photon() {
+        // the same sponge over PHOTON-100, garbled and with Dummy
+        let params = PhotonParams::P100;
+        let q = params.modulus;
+        let mut rng = thread_rng();
+        let inputs = (0..7).map(|_| rng.gen_range(0, 16)).collect::<Vec<u16>>();
+
+        let mut f = Dummy::new();
+        let state = vec![DummyVal::new(0, q); 25];
+        let permute = |f: &mut Dummy, xs: &[DummyVal]| f.photon_100(xs);
+        let mut sponge = Sponge::new(state, 0..5, permute)
+            .unwrap()
+            .with_squeeze_rate(4);
+        let xs = inputs.iter().map(|&x| DummyVal::new(x, q)).collect::<Vec<_>>();
+        sponge.absorb(&mut f, &xs).unwrap();
+        let want = sponge.squeeze(&mut f, 6).unwrap();
+        let want = want.iter().map(|x| x.val()).collect::<Vec<_>>();
+
+        let mut b = CircuitBuilder::new();
+        let xs = b.evaluator_inputs(&[q; 7]);
+        let state = (0..25).map(|_| b.constant(0, &q)).collect::<Result<_, _>>().unwrap();
+        let permute = |b: &mut CircuitBuilder, xs: &[_]| b.photon_100(xs);
+        let mut sponge = Sponge::new(state, 0..5, permute)
+            .unwrap()
+            .with_squeeze_rate(4);
+        sponge.absorb(&mut b, &xs).unwrap();
+        let zs = sponge.squeeze(&mut b, 6).unwrap();
+        b.outputs(&zs).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble(&circ).unwrap();
+        let X = en.encode_evaluator_inputs(&inputs);
+        assert_eq!(gc.eval(&circ, &[], &X).unwrap(), want);
+    }
+}
@@ -0,0 +1,352 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! `Recorder` logs a fancy computation, to turn it into a `Circuit`.
+//!
+//! Gadgets are usually written against `Fancy` and run by the streaming
+//! `Garbler` and `Evaluator` directly. Run against a `Recorder` instead, every
+//! call is logged as a `Record`, and `replay` builds the same computation in a
+//! `CircuitBuilder`, giving a static `Circuit` which can be optimized, saved
+//! and garbled offline.
+//!
+//! Inputs are recorded as garbler inputs when encoded and as evaluator inputs
+//! when received, in order, and outputs in the order they are output. The
+//! values of encoded inputs are not recorded.
+
+use crate::{
+    circuit::{Circuit, CircuitBuilder, CircuitRef},
+    errors::{CircuitBuilderError, FancyError},
+    fancy::{Fancy, FancyInput, HasModulus},
+    Modulus,
+};
+use alloc::vec::Vec;
+
+/// A call logged by a `Recorder`. Values are referred to by the index of the
+/// record which produced them.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub enum Record {
+    /// A garbler input.
+    GarblerInput(Modulus),
+    /// An evaluator input.
+    EvaluatorInput(Modulus),
+    /// A constant.
+    Constant(u64, Modulus),
+    /// An addition.
+    Add(usize, usize),
+    /// A subtraction.
+    Sub(usize, usize),
+    /// A multiplication by a constant.
+    Cmul(usize, u64),
+    /// A multiplication.
+    Mul(usize, usize),
+    /// A projection to a modulus by a truth table.
+    Proj(usize, Modulus, Vec<u16>),
+    /// An output.
+    Output(usize),
+}
+
+/// A value of a `Recorder`: the index of the record which produced it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecorderVal {
+    ix: usize,
+    modulus: Modulus,
+}
+
+impl RecorderVal {
+    /// The index of the record which produced this value.
+    pub fn index(&self) -> usize {
+        self.ix
+    }
+}
+
+impl HasModulus for RecorderVal {
+    fn modulus(&self) -> Modulus {
+        self.modulus
+    }
+}
+
+/// Implements `Fancy` and `FancyInput` by logging every call, see the module
+/// documentation.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    log: Vec<Record>,
+}
+
+impl Recorder {
+    /// Make a new `Recorder` with an empty log.
+    pub fn new() -> Recorder {
+        Recorder { log: Vec::new() }
+    }
+
+    /// The calls recorded so far.
+    pub fn log(&self) -> &[Record] {
+        &self.log
+    }
+
+    /// Build the recorded computation in `b`, with new inputs for the
+    /// recorded inputs, returning the wires of the recorded outputs, which
+    /// are also made outputs of `b`.
+    pub fn replay(&self, b: &mut CircuitBuilder) -> Result<Vec<CircuitRef>, CircuitBuilderError> {
+        let mut refs: Vec<Option<CircuitRef>> = Vec::with_capacity(self.log.len());
+        let mut outputs = Vec::new();
+        for record in self.log.iter() {
+            // records only refer to earlier ones, which produced values
+            let r = |i: &usize| refs[*i].expect("a record refers to an output");
+            let result = match record {
+                Record::GarblerInput(q) => b.garbler_input(q),
+                Record::EvaluatorInput(q) => b.evaluator_input(q),
+                Record::Constant(c, q) => b.constant_big(*c, q)?,
+                Record::Add(x, y) => b.add(&r(x), &r(y))?,
+                Record::Sub(x, y) => b.sub(&r(x), &r(y))?,
+                Record::Cmul(x, c) => b.cmul_big(&r(x), *c)?,
+                Record::Mul(x, y) => b.mul(&r(x), &r(y))?,
+                Record::Proj(x, q, tt) => b.proj(&r(x), q, Some(tt.clone()))?,
+                Record::Output(x) => {
+                    let x = r(x);
+                    b.output(&x)?;
+                    outputs.push(x);
+                    refs.push(None);
+                    continue;
+                }
+            };
+            refs.push(Some(result));
+        }
+        Ok(outputs)
+    }
+
+    /// The recorded computation as a circuit.
+    pub fn to_circuit(&self) -> Result<Circuit, CircuitBuilderError> {
+        let mut b = CircuitBuilder::new();
+        self.replay(&mut b)?;
+        Ok(b.finish())
+    }
+
+    fn record(&mut self, record: Record, modulus: &Modulus) -> RecorderVal {
+        self.log.push(record);
+        RecorderVal {
+            ix: self.log.len() - 1,
+            modulus: *modulus,
+        }
+    }
+}
+
+impl FancyInput for Recorder {
+    type Item = RecorderVal;
+    type Error = FancyError;
+
+    fn encode_many(
+        &mut self,
+        values: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<RecorderVal>, FancyError> {
+        if values.len() != moduli.len() {
+            return Err(FancyError::InvalidArgNum {
+                got: values.len(),
+                needed: moduli.len(),
+            });
+        }
+        Ok(moduli
+            .iter()
+            .map(|q| self.record(Record::GarblerInput(*q), q))
+            .collect())
+    }
+
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<RecorderVal>, FancyError> {
+        Ok(moduli
+            .iter()
+            .map(|q| self.record(Record::EvaluatorInput(*q), q))
+            .collect())
+    }
+}
+
+impl Fancy for Recorder {
+    type Item = RecorderVal;
+    type Error = FancyError;
+
+    fn constant(&mut self, val: u16, q: &Modulus) -> Result<RecorderVal, FancyError> {
+        self.constant_big(val as u64, q)
+    }
+
+    fn constant_big(&mut self, val: u64, q: &Modulus) -> Result<RecorderVal, FancyError> {
+        Ok(self.record(Record::Constant(val, *q), q))
+    }
+
+    fn add(&mut self, x: &RecorderVal, y: &RecorderVal) -> Result<RecorderVal, FancyError> {
+        if x.modulus != y.modulus {
+            return Err(FancyError::UnequalModuli {
+                x: x.modulus,
+                y: y.modulus,
+            });
+        }
+        Ok(self.record(Record::Add(x.ix, y.ix), &x.modulus))
+    }
+
+    fn sub(&mut self, x: &RecorderVal, y: &RecorderVal) -> Result<RecorderVal, FancyError> {
+        if x.modulus != y.modulus {
+            return Err(FancyError::UnequalModuli {
+                x: x.modulus,
+                y: y.modulus,
+            });
+        }
+        Ok(self.record(Record::Sub(x.ix, y.ix), &x.modulus))
+    }
+
+    fn cmul(&mut self, x: &RecorderVal, c: u16) -> Result<RecorderVal, FancyError> {
+        self.cmul_big(x, c as u64)
+    }
+
+    fn cmul_big(&mut self, x: &RecorderVal, c: u64) -> Result<RecorderVal, FancyError> {
+        Ok(self.record(Record::Cmul(x.ix, c), &x.modulus))
+    }
+
+    fn mul(&mut self, x: &RecorderVal, y: &RecorderVal) -> Result<RecorderVal, FancyError> {
+        // the same moduli as a `CircuitBuilder` accepts
+        let q = match (x.modulus, y.modulus) {
+            (Modulus::Zq { q }, Modulus::Zq { q: qb }) => Modulus::Zq { q: q.max(qb) },
+            (q, qb) if q.is_field() && q == qb => q,
+            (q, qb) if q.is_field() => return Err(FancyError::UnequalModuli { x: q, y: qb }),
+            (q, qb) => {
+                return Err(FancyError::InvalidArg(format!(
+                    "Multiplication of {} and {} is not supported",
+                    q, qb
+                )))
+            }
+        };
+        Ok(self.record(Record::Mul(x.ix, y.ix), &q))
+    }
+
+    fn proj(
+        &mut self,
+        x: &RecorderVal,
+        q: &Modulus,
+        tt: Option<Vec<u16>>,
+    ) -> Result<RecorderVal, FancyError> {
+        let tt = tt.ok_or(FancyError::NoTruthTable)?;
+        if tt.len() < x.modulus.order() as usize || tt.iter().any(|&y| y as u32 >= q.order()) {
+            return Err(FancyError::InvalidTruthTable);
+        }
+        Ok(self.record(Record::Proj(x.ix, *q, tt), q))
+    }
+
+    fn output(&mut self, x: &RecorderVal) -> Result<Option<u16>, FancyError> {
+        self.log.push(Record::Output(x.ix));
+        Ok(None)
+    }
+
+    fn output_big(&mut self, x: &RecorderVal) -> Result<Option<u64>, FancyError> {
+        self.output(x)?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classic::garble,
+        dummy::Dummy,
+        fancy::{BinaryBundle, BinaryGadgets},
+        util::RngExt,
+    };
+    use rand::thread_rng;
+
+    // (x + y) * 3 in binary, the low bit of x + y projected to Zq(3), and
+    // z * w + 5 in GF(2^4)
+    fn gadget<F>(f: &mut F, xs: &[u16], z: u16) -> Result<Option<Vec<u16>>, <F as Fancy>::Error>
+    where
+        F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Error>,
+    {
+        let g = Modulus::X4_X_1;
+        let x = BinaryBundle::new(f.encode_many(xs, &[Modulus::Zq { q: 2 }; 8])?);
+        let y = f.bin_receive(8)?;
+        let s = f.bin_addition_no_carry(&x, &y)?;
+        let mut outputs = f.bin_cmul(&s, 3, 8)?.wires().to_vec();
+        outputs.push(f.proj(&s.wires()[0], &Modulus::Zq { q: 3 }, Some(vec![1, 2]))?);
+        let z = f.encode(z, &g)?;
+        let w = f.receive(&g)?;
+        let u = f.mul(&z, &w)?;
+        let c = f.constant(5, &g)?;
+        outputs.push(f.add(&u, &c)?);
+        f.outputs(&outputs)
+    }
+
+    #[test]
+    fn circuit_matches_dummy() {
+        let mut rng = thread_rng();
+        let mut r = Recorder::new();
+        assert_eq!(gadget(&mut r, &[0; 8], 0).unwrap(), None);
+        let circ = r.to_circuit().unwrap();
+        assert_eq!(circ.num_garbler_inputs(), 9);
+        assert_eq!(circ.num_evaluator_inputs(), 9);
+        assert_eq!(circ.noutputs(), 10);
+        let (en, gc) = garble(&circ).unwrap();
+        for _ in 0..16 {
+            let mut gb = (0..8).map(|_| rng.gen_u16() % 2).collect::<Vec<_>>();
+            let mut ev = (0..8).map(|_| rng.gen_u16() % 2).collect::<Vec<_>>();
+            gb.push(rng.gen_u16() % 16);
+            ev.push(rng.gen_u16() % 16);
+            let mut d = Dummy::with_inputs(&[], &ev);
+            let expected = gadget(&mut d, &gb[..8], gb[8]).unwrap().unwrap();
+            assert_eq!(circ.eval_plain(&gb, &ev).unwrap(), expected);
+            let X = en.encode_garbler_inputs(&gb);
+            let Y = en.encode_evaluator_inputs(&ev);
+            assert_eq!(gc.eval(&circ, &X, &Y).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn replays_into_builder() {
+        let q = Modulus::Zq { q: 5 };
+        let mut r = Recorder::new();
+        let x = r.encode(2, &q).unwrap();
+        let y = r.receive(&q).unwrap();
+        let a = r.add(&x, &y).unwrap();
+        let b = r.add(&x, &y).unwrap();
+        let z = r.mul(&a, &b).unwrap();
+        r.output(&z).unwrap();
+        assert_eq!(z.index(), 4);
+        assert_eq!(
+            r.log(),
+            &[
+                Record::GarblerInput(q),
+                Record::EvaluatorInput(q),
+                Record::Add(0, 1),
+                Record::Add(0, 1),
+                Record::Mul(2, 3),
+                Record::Output(4),
+            ]
+        );
+
+        // the builder may merge the two additions, and replaying again makes
+        // a second copy on new inputs
+        let mut b = CircuitBuilder::with_cse();
+        assert_eq!(r.replay(&mut b).unwrap().len(), 1);
+        r.replay(&mut b).unwrap();
+        let circ = b.finish();
+        assert_eq!(circ.gates.len(), 2 * (r.to_circuit().unwrap().gates.len() - 1));
+        assert_eq!(circ.num_garbler_inputs(), 2);
+        assert_eq!(circ.noutputs(), 2);
+        assert_eq!(circ.eval_plain(&[2, 1], &[3, 3]).unwrap(), vec![0, 1]);
+    }
+
+    #[test]
+    fn rejects_bad_gates() {
+        let mut r = Recorder::new();
+        let x = r.receive(&Modulus::Zq { q: 3 }).unwrap();
+        let y = r.receive(&Modulus::Zq { q: 5 }).unwrap();
+        let g = r.receive(&Modulus::X4_X_1).unwrap();
+        assert!(r.add(&x, &y).is_err());
+        assert!(r.sub(&x, &y).is_err());
+        assert!(r.mul(&x, &g).is_err());
+        assert!(r.proj(&x, &Modulus::Zq { q: 2 }, Some(vec![0, 1])).is_err());
+        assert!(r.proj(&x, &Modulus::Zq { q: 2 }, Some(vec![0, 1, 2])).is_err());
+        assert!(r.proj(&x, &Modulus::Zq { q: 2 }, None).is_err());
+        assert!(r.encode_many(&[0], &[]).is_err());
+        assert_eq!(r.log().len(), 3);
+        assert_eq!(r.mul(&x, &y).unwrap().modulus(), Modulus::Zq { q: 5 });
+    }
+}
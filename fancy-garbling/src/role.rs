@@ -0,0 +1,128 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Protocol roles, available both as a runtime value and as marker types.
+//!
+//! Code which must only ever run on one side of the protocol can require
+//! `F: HasRole<Role = role::Garbler>`, so that calling it with an evaluator is a
+//! type error. `RoleInput` does so for inputs: only the owner of an input can
+//! encode it, and only the other party can receive it. Code shared by both
+//! sides can instead `match` on `HasRole::party`.
+
+use crate::{fancy::FancyInput, wire::Modulus};
+use alloc::vec::Vec;
+
+/// The party an object plays in a two-party garbling protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Party {
+    /// The party generating the garbled circuit.
+    Garbler,
+    /// The party evaluating the garbled circuit.
+    Evaluator,
+}
+
+impl Party {
+    /// The other party of the protocol.
+    pub fn other(self) -> Party {
+        match self {
+            Party::Garbler => Party::Evaluator,
+            Party::Evaluator => Party::Garbler,
+        }
+    }
+}
+
+/// A type-level protocol role.
+pub trait Role {
+    /// The party corresponding to this role.
+    const PARTY: Party;
+}
+
+/// Marker type for the garbler role.
+#[derive(Clone, Copy, Debug)]
+pub struct Garbler;
+
+/// Marker type for the evaluator role.
+#[derive(Clone, Copy, Debug)]
+pub struct Evaluator;
+
+impl Role for Garbler {
+    const PARTY: Party = Party::Garbler;
+}
+
+impl Role for Evaluator {
+    const PARTY: Party = Party::Evaluator;
+}
+
+/// An object which acts as a fixed party of the protocol.
+pub trait HasRole {
+    /// The role of this object.
+    type Role: Role;
+
+    /// The party this object acts as.
+    fn party(&self) -> Party {
+        <Self::Role as Role>::PARTY
+    }
+}
+
+/// Inputs of a two-party protocol, restricted to the side which may handle
+/// them. Calling `encode_evaluator_inputs` on a garbler, say, does not compile,
+/// rather than deadlocking with both parties waiting on each other:
+///
+/// ```compile_fail
+/// use fancy_garbling::{role::RoleInput, twopac::semihonest::Garbler, Modulus};
+/// use ocelot::ot::ChouOrlandiSender;
+/// use scuttlebutt::{AesRng, UnixChannel};
+///
+/// fn wrong_side(gb: &mut Garbler<UnixChannel, AesRng, ChouOrlandiSender>) {
+///     gb.encode_evaluator_inputs(&[1], &[Modulus::Zq { q: 2 }]).unwrap();
+/// }
+/// ```
+pub trait RoleInput: FancyInput + HasRole {
+    /// Encode the garbler's own inputs.
+    fn encode_garbler_inputs(
+        &mut self,
+        values: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: HasRole<Role = Garbler>,
+    {
+        self.encode_many(values, moduli)
+    }
+
+    /// Receive the inputs the garbler encoded with `encode_garbler_inputs`.
+    fn receive_garbler_inputs(&mut self, moduli: &[Modulus]) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: HasRole<Role = Evaluator>,
+    {
+        self.receive_many(moduli)
+    }
+
+    /// Encode the evaluator's own inputs.
+    fn encode_evaluator_inputs(
+        &mut self,
+        values: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: HasRole<Role = Evaluator>,
+    {
+        self.encode_many(values, moduli)
+    }
+
+    /// Receive the inputs the evaluator encoded with `encode_evaluator_inputs`.
+    fn receive_evaluator_inputs(
+        &mut self,
+        moduli: &[Modulus],
+    ) -> Result<Vec<Self::Item>, Self::Error>
+    where
+        Self: HasRole<Role = Garbler>,
+    {
+        self.receive_many(moduli)
+    }
+}
+
+impl<F: FancyInput + HasRole> RoleInput for F {}
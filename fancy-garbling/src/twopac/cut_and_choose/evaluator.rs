@@ -0,0 +1,340 @@
+// -*- mode: rust; -*-
+//
+// This file is part of twopac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use super::InstanceWires;
+use crate::{
+    errors::{EvaluatorError, GarblerError, TwopacError},
+    role::{self, HasRole},
+    twopac::semihonest::{input_bits, input_weights},
+    Evaluator as Ev,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+    Garbler as Gb,
+    Modulus,
+    Wire,
+};
+use ocelot::ot::Receiver as OtReceiver;
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, Channel, Malicious, SemiHonest};
+use std::{cell::RefCell, rc::Rc};
+
+// Check instances are regarbled into a buffer and compared with the channel.
+type LocalChannel = Channel<std::io::Empty, Vec<u8>>;
+
+enum Instance<C> {
+    Eval {
+        evaluator: Ev<C>,
+        pad: AesRng,
+    },
+    Check {
+        garbler: Gb<LocalChannel, AesRng>,
+        written: Rc<RefCell<Vec<u8>>>,
+    },
+}
+
+/// Cut-and-choose evaluator. See the module documentation for which deviations
+/// of the garbler it detects.
+pub struct Evaluator<C, RNG, OT> {
+    instances: Vec<Instance<C>>,
+    input_shares: usize,
+    channel: C,
+    ot: OT,
+    rng: RNG,
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + Malicious>
+    Evaluator<C, RNG, OT>
+{
+    /// Make a new `Evaluator` with `DEFAULT_INSTANCES` instances and
+    /// `DEFAULT_INPUT_SHARES` shares per evaluator input.
+    pub fn new(channel: C, rng: RNG) -> Result<Self, TwopacError> {
+        Self::with_instances(
+            channel,
+            rng,
+            super::DEFAULT_INSTANCES,
+            super::DEFAULT_INPUT_SHARES,
+        )
+    }
+
+    /// Make a new `Evaluator` for `instances` instances, half of which are
+    /// checked, splitting each input into `input_shares` shares. Both parties
+    /// must use the same parameters.
+    pub fn with_instances(
+        mut channel: C,
+        mut rng: RNG,
+        instances: usize,
+        input_shares: usize,
+    ) -> Result<Self, TwopacError> {
+        super::check_parameters(instances, input_shares)?;
+        let mut ot = OT::init(&mut channel, &mut rng)?;
+        let mut choices = vec![false; instances];
+        for i in rand::seq::index::sample(&mut rng, instances, instances / 2).iter() {
+            choices[i] = true;
+        }
+        let keys = ot.receive(&mut channel, &choices, &mut rng)?;
+
+        let instances = choices
+            .into_iter()
+            .zip(keys)
+            .map(|(eval, key)| {
+                if eval {
+                    Instance::Eval {
+                        evaluator: Ev::new(channel.clone()),
+                        pad: AesRng::from_seed(key),
+                    }
+                } else {
+                    let local = Channel::new(std::io::empty(), Vec::new());
+                    let written = local.clone().writer();
+                    Instance::Check {
+                        garbler: Gb::new(local, AesRng::from_seed(key)),
+                        written,
+                    }
+                }
+            })
+            .collect();
+        Ok(Evaluator {
+            instances,
+            input_shares,
+            channel,
+            ot,
+            rng,
+        })
+    }
+
+    /// Get a reference to the internal channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+}
+
+// Read the bytes a check instance should have sent from the channel, and
+// compare them with what regarbling it produced.
+fn compare<C: AbstractChannel>(
+    channel: &mut C,
+    written: &RefCell<Vec<u8>>,
+    instance: usize,
+) -> Result<(), TwopacError> {
+    let expected = std::mem::take(&mut *written.borrow_mut());
+    let mut got = vec![0u8; expected.len()];
+    channel.read_bytes(&mut got)?;
+    if got != expected {
+        return Err(TwopacError::CheatingDetected(format!(
+            "check instance {} does not match its seed",
+            instance
+        )));
+    }
+    Ok(())
+}
+
+// Run the same operation on every instance, evaluating or checking it.
+macro_rules! each {
+    ($self:ident, |$f:ident, $i:ident| $body:expr) => {
+        $self.each(|$f, $i| $body, |$f, $i| $body)
+    };
+}
+
+impl<C: AbstractChannel, RNG, OT> Evaluator<C, RNG, OT> {
+    fn each<FE, FG>(&mut self, mut fe: FE, mut fg: FG) -> Result<InstanceWires, TwopacError>
+    where
+        FE: FnMut(&mut Ev<C>, usize) -> Result<Wire, EvaluatorError>,
+        FG: FnMut(&mut Gb<LocalChannel, AesRng>, usize) -> Result<Wire, GarblerError>,
+    {
+        let mut wires = Vec::with_capacity(self.instances.len());
+        for (i, instance) in self.instances.iter_mut().enumerate() {
+            let w = match instance {
+                Instance::Eval { evaluator, .. } => fe(evaluator, i)?,
+                Instance::Check { garbler, written } => {
+                    let w = fg(garbler, i)?;
+                    compare(&mut self.channel, written, i)?;
+                    w
+                }
+            };
+            wires.push(w);
+        }
+        Ok(InstanceWires(wires))
+    }
+
+    // Decode `x` in every evaluated instance, returning the majority value.
+    fn decode<T, FE, FG>(
+        &mut self,
+        x: &InstanceWires,
+        mut fe: FE,
+        mut fg: FG,
+    ) -> Result<T, TwopacError>
+    where
+        T: Copy + Eq,
+        FE: FnMut(&mut Ev<C>, &Wire) -> Result<Option<T>, EvaluatorError>,
+        FG: FnMut(&mut Gb<LocalChannel, AesRng>, &Wire) -> Result<Option<T>, GarblerError>,
+    {
+        let mut votes: Vec<(T, usize)> = Vec::new();
+        let mut nevaluated = 0;
+        for (i, (instance, w)) in self.instances.iter_mut().zip(x.0.iter()).enumerate() {
+            match instance {
+                Instance::Eval { evaluator, .. } => {
+                    nevaluated += 1;
+                    match fe(evaluator, w) {
+                        Ok(Some(v)) => match votes.iter_mut().find(|(u, _)| *u == v) {
+                            Some((_, n)) => *n += 1,
+                            None => votes.push((v, 1)),
+                        },
+                        // a corrupted instance may not decode at all
+                        Ok(None) | Err(EvaluatorError::DecodingFailed) => (),
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Instance::Check { garbler, written } => {
+                    fg(garbler, w)?;
+                    compare(&mut self.channel, written, i)?;
+                }
+            }
+        }
+        votes
+            .into_iter()
+            .find(|(_, n)| 2 * n > nevaluated)
+            .map(|(v, _)| v)
+            .ok_or_else(|| {
+                TwopacError::CheatingDetected(
+                    "the evaluated instances have no majority output".to_string(),
+                )
+            })
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + Malicious> FancyInput
+    for Evaluator<C, RNG, OT>
+{
+    type Item = InstanceWires;
+    type Error = TwopacError;
+
+    /// Receive garbler input wires.
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<InstanceWires>, TwopacError> {
+        let mut wires = vec![Vec::with_capacity(self.instances.len()); moduli.len()];
+        for instance in self.instances.iter_mut() {
+            for (q, ws) in moduli.iter().zip(wires.iter_mut()) {
+                let block = self.channel.read_block()?;
+                let w = match instance {
+                    Instance::Eval { pad, .. } => Wire::from_block(block ^ pad.gen::<Block>(), q),
+                    Instance::Check { garbler, .. } => garbler.encode_wire(0, q).0,
+                };
+                ws.push(w);
+            }
+        }
+        Ok(wires.into_iter().map(InstanceWires).collect())
+    }
+
+    /// Perform OT on shares of the inputs and obtain wires for them.
+    fn encode_many(
+        &mut self,
+        inputs: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<InstanceWires>, TwopacError> {
+        let mut bits = Vec::new();
+        for (x, q) in inputs.iter().zip(moduli.iter()) {
+            for share in super::share(&mut self.rng, *x, q, self.input_shares) {
+                bits.extend(input_bits(share, q));
+            }
+        }
+        let keys = self.ot.receive(&mut self.channel, &bits, &mut self.rng)?;
+
+        let shares = self.input_shares;
+        let mut wires = vec![Vec::with_capacity(self.instances.len()); moduli.len()];
+        for (i, instance) in self.instances.iter_mut().enumerate() {
+            let mut keys = keys.iter().zip(bits.iter());
+            for (q, ws) in moduli.iter().zip(wires.iter_mut()) {
+                let weights = input_weights(q);
+                let mut wire = Wire::zero(q);
+                for (c, (key, bit)) in weights
+                    .iter()
+                    .cycle()
+                    .zip(keys.by_ref().take(shares * weights.len()))
+                {
+                    let zero = self.channel.read_block()?;
+                    let one = self.channel.read_block()?;
+                    // Input labels of check instances are not verified, since
+                    // whether that succeeds could depend on our input.
+                    let label = match instance {
+                        Instance::Eval { .. } => {
+                            let ct = if *bit { one } else { zero };
+                            Wire::from_block(ct ^ super::input_pad(i, *key), q)
+                        }
+                        Instance::Check { garbler, .. } => garbler.encode_wire(0, q).0,
+                    };
+                    wire = wire.plus(&label.cmul_big(*c));
+                }
+                ws.push(wire);
+            }
+        }
+        Ok(wires.into_iter().map(InstanceWires).collect())
+    }
+}
+
+impl<C: AbstractChannel, RNG, OT> Fancy for Evaluator<C, RNG, OT> {
+    type Item = InstanceWires;
+    type Error = TwopacError;
+
+    fn constant(&mut self, x: u16, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, _i| f.constant(x, q))
+    }
+
+    fn add(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.add(&x.0[i], &y.0[i]))
+    }
+
+    fn sub(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.sub(&x.0[i], &y.0[i]))
+    }
+
+    fn cmul(&mut self, x: &Self::Item, c: u16) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.cmul(&x.0[i], c))
+    }
+
+    fn mul(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.mul(&x.0[i], &y.0[i]))
+    }
+
+    fn proj(
+        &mut self,
+        x: &Self::Item,
+        q: &Modulus,
+        tt: Option<Vec<u16>>,
+    ) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.proj(&x.0[i], q, tt.clone()))
+    }
+
+    fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
+        self.decode(x, |ev, w| ev.output(w), |gb, w| gb.output(w))
+            .map(Some)
+    }
+
+    fn constant_big(&mut self, x: u64, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, _i| f.constant_big(x, q))
+    }
+
+    fn cmul_big(&mut self, x: &Self::Item, c: u64) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.cmul_big(&x.0[i], c))
+    }
+
+    fn output_big(&mut self, x: &Self::Item) -> Result<Option<u64>, Self::Error> {
+        self.decode(x, |ev, w| ev.output_big(w), |gb, w| gb.output_big(w))
+            .map(Some)
+    }
+}
+
+impl<C: AbstractChannel, RNG, OT> FancyReveal for Evaluator<C, RNG, OT> {
+    fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
+        let val = self.output(x)?.expect("Evaluator always outputs Some(u16)");
+        self.channel.write_u16(val)?;
+        self.channel.flush()?;
+        Ok(val)
+    }
+}
+
+impl<C, RNG, OT> HasRole for Evaluator<C, RNG, OT> {
+    type Role = role::Evaluator;
+}
+
+impl<C, RNG, OT> SemiHonest for Evaluator<C, RNG, OT> {}
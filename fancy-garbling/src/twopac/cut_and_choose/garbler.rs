@@ -0,0 +1,241 @@
+// -*- mode: rust; -*-
+//
+// This file is part of twopac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use super::InstanceWires;
+use crate::{
+    errors::{GarblerError, TwopacError},
+    role::{self, HasRole},
+    twopac::semihonest::input_weights,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+    Garbler as Gb,
+    Modulus,
+    Wire,
+};
+use ocelot::ot::Sender as OtSender;
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, Malicious, SemiHonest};
+
+/// Cut-and-choose garbler. See the module documentation for which deviations
+/// of either party it protects against.
+pub struct Garbler<C, RNG, OT> {
+    instances: Vec<Gb<C, AesRng>>,
+    pads: Vec<AesRng>,
+    input_shares: usize,
+    channel: C,
+    ot: OT,
+    rng: RNG,
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtSender<Msg = Block> + Malicious>
+    Garbler<C, RNG, OT>
+{
+    /// Make a new `Garbler` with `DEFAULT_INSTANCES` instances and
+    /// `DEFAULT_INPUT_SHARES` shares per evaluator input.
+    pub fn new(channel: C, rng: RNG) -> Result<Self, TwopacError> {
+        Self::with_instances(
+            channel,
+            rng,
+            super::DEFAULT_INSTANCES,
+            super::DEFAULT_INPUT_SHARES,
+        )
+    }
+
+    /// Make a new `Garbler` garbling `instances` instances, splitting each
+    /// evaluator input into `input_shares` shares. Both parties must use the
+    /// same parameters.
+    pub fn with_instances(
+        mut channel: C,
+        mut rng: RNG,
+        instances: usize,
+        input_shares: usize,
+    ) -> Result<Self, TwopacError> {
+        super::check_parameters(instances, input_shares)?;
+        let mut ot = OT::init(&mut channel, &mut rng)?;
+        // The evaluator learns the seed of check instances, and the pad of
+        // the garbler's input labels of evaluated instances.
+        let keys = (0..instances)
+            .map(|_| (rng.gen::<Block>(), rng.gen::<Block>()))
+            .collect::<Vec<(Block, Block)>>();
+        ot.send(&mut channel, &keys, &mut rng)?;
+        channel.flush()?;
+
+        let pads = keys.iter().map(|(_, pad)| AesRng::from_seed(*pad)).collect();
+        let instances = keys
+            .into_iter()
+            .map(|(seed, _)| Gb::new(channel.clone(), AesRng::from_seed(seed)))
+            .collect();
+        Ok(Garbler {
+            instances,
+            pads,
+            input_shares,
+            channel,
+            ot,
+            rng,
+        })
+    }
+
+    /// Get a reference to the internal channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+}
+
+impl<C: AbstractChannel, RNG, OT> Garbler<C, RNG, OT> {
+    // Run `f` on every instance.
+    fn each<F>(&mut self, mut f: F) -> Result<InstanceWires, TwopacError>
+    where
+        F: FnMut(&mut Gb<C, AesRng>, usize) -> Result<Wire, GarblerError>,
+    {
+        self.instances
+            .iter_mut()
+            .enumerate()
+            .map(|(i, gb)| f(gb, i).map_err(TwopacError::from))
+            .collect::<Result<Vec<Wire>, TwopacError>>()
+            .map(InstanceWires)
+    }
+
+    // Output `x` in every instance.
+    fn output_all(&mut self, x: &InstanceWires) -> Result<(), TwopacError> {
+        for (gb, w) in self.instances.iter_mut().zip(x.0.iter()) {
+            gb.output(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtSender<Msg = Block> + Malicious> FancyInput
+    for Garbler<C, RNG, OT>
+{
+    type Item = InstanceWires;
+    type Error = TwopacError;
+
+    fn encode_many(
+        &mut self,
+        vals: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<InstanceWires>, TwopacError> {
+        let mut wires = vec![Vec::with_capacity(self.instances.len()); vals.len()];
+        for (gb, pad) in self.instances.iter_mut().zip(self.pads.iter_mut()) {
+            for ((x, q), ws) in vals.iter().zip(moduli.iter()).zip(wires.iter_mut()) {
+                let (zero, enc) = gb.encode_wire(*x, q);
+                let mask = pad.gen::<Block>();
+                self.channel.write_block(&(enc.as_block() ^ mask))?;
+                ws.push(zero);
+            }
+        }
+        self.channel.flush()?;
+        Ok(wires.into_iter().map(InstanceWires).collect())
+    }
+
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<InstanceWires>, TwopacError> {
+        let shares = self.input_shares;
+        let nbits = moduli
+            .iter()
+            .map(|q| shares * input_weights(q).len())
+            .sum();
+        let keys = (0..nbits)
+            .map(|_| (self.rng.gen::<Block>(), self.rng.gen::<Block>()))
+            .collect::<Vec<(Block, Block)>>();
+        self.ot.send(&mut self.channel, &keys, &mut self.rng)?;
+
+        let mut wires = vec![Vec::with_capacity(self.instances.len()); moduli.len()];
+        for (i, gb) in self.instances.iter_mut().enumerate() {
+            let mut keys = keys.iter();
+            for (q, ws) in moduli.iter().zip(wires.iter_mut()) {
+                let weights = input_weights(q);
+                let mut wire = Wire::zero(q);
+                // The wire is the sum of the shares, each weighted by bits.
+                for (c, (key0, key1)) in weights
+                    .iter()
+                    .cycle()
+                    .zip(keys.by_ref().take(shares * weights.len()))
+                {
+                    // The check instances regarble this with `encode_wire`, so
+                    // the delta must not be drawn before it.
+                    let (zero, _) = gb.encode_wire(0, q);
+                    let one = zero.plus(&gb.delta(q));
+                    self.channel
+                        .write_block(&(zero.as_block() ^ super::input_pad(i, *key0)))?;
+                    self.channel
+                        .write_block(&(one.as_block() ^ super::input_pad(i, *key1)))?;
+                    wire = wire.plus(&zero.cmul_big(*c));
+                }
+                ws.push(wire);
+            }
+        }
+        self.channel.flush()?;
+        Ok(wires.into_iter().map(InstanceWires).collect())
+    }
+}
+
+impl<C: AbstractChannel, RNG, OT> Fancy for Garbler<C, RNG, OT> {
+    type Item = InstanceWires;
+    type Error = TwopacError;
+
+    fn constant(&mut self, x: u16, modulus: &Modulus) -> Result<Self::Item, Self::Error> {
+        self.each(|gb, _| gb.constant(x, modulus))
+    }
+
+    fn add(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.each(|gb, i| gb.add(&x.0[i], &y.0[i]))
+    }
+
+    fn sub(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.each(|gb, i| gb.sub(&x.0[i], &y.0[i]))
+    }
+
+    fn cmul(&mut self, x: &Self::Item, c: u16) -> Result<Self::Item, Self::Error> {
+        self.each(|gb, i| gb.cmul(&x.0[i], c))
+    }
+
+    fn mul(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.each(|gb, i| gb.mul(&x.0[i], &y.0[i]))
+    }
+
+    fn proj(
+        &mut self,
+        x: &Self::Item,
+        modulus: &Modulus,
+        tt: Option<Vec<u16>>,
+    ) -> Result<Self::Item, Self::Error> {
+        self.each(|gb, i| gb.proj(&x.0[i], modulus, tt.clone()))
+    }
+
+    fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
+        self.output_all(x)?;
+        Ok(None)
+    }
+
+    fn constant_big(&mut self, x: u64, modulus: &Modulus) -> Result<Self::Item, Self::Error> {
+        self.each(|gb, _| gb.constant_big(x, modulus))
+    }
+
+    fn cmul_big(&mut self, x: &Self::Item, c: u64) -> Result<Self::Item, Self::Error> {
+        self.each(|gb, i| gb.cmul_big(&x.0[i], c))
+    }
+
+    fn output_big(&mut self, x: &Self::Item) -> Result<Option<u64>, Self::Error> {
+        self.output_all(x)?;
+        Ok(None)
+    }
+}
+
+impl<C: AbstractChannel, RNG, OT> FancyReveal for Garbler<C, RNG, OT> {
+    fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
+        self.output_all(x)?;
+        self.channel.flush()?;
+        let val = self.channel.read_u16()?;
+        Ok(val)
+    }
+}
+
+impl<C, RNG, OT> HasRole for Garbler<C, RNG, OT> {
+    type Role = role::Garbler;
+}
+
+impl<C, RNG, OT> SemiHonest for Garbler<C, RNG, OT> {}
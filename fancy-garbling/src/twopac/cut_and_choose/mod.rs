@@ -0,0 +1,259 @@
+// -*- mode: rust; -*-
+//
+// This file is part of twopac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of two-party computation by cut-and-choose, which catches a
+//! garbler garbling the wrong circuit.
+//!
+//! This is not authenticated garbling and does not give malicious security.
+//! The garbler garbles the computation `instances` times, each from its own
+//! seed, over the same channel. Using OT, the evaluator secretly learns the
+//! seeds of a random half of the instances, the *check* instances, which it
+//! regarbles locally and compares byte for byte against what the garbler sent.
+//! For the other half, the *evaluated* instances, it learns a key instead which
+//! unmasks the garbler's input labels, and it evaluates them as usual. Outputs
+//! are decided by majority over the evaluated instances, so a garbler which
+//! corrupts enough instances to change the output is caught unless none of
+//! them is checked.
+//!
+//! The evaluator's inputs are split into `input_shares` random additive shares
+//! before OT, so that a garbler which corrupts some of the OT messages learns
+//! at most bits of random shares when the evaluator aborts; this leakage
+//! decreases exponentially in the number of shares.
+//!
+//! Since this is not malicious security, `Garbler` and `Evaluator` only
+//! implement `SemiHonest`. Against a malicious garbler it covers:
+//!
+//! * garbled gates and outputs which differ from the agreed computation, and
+//! * corrupted OT messages for the evaluator's inputs, up to the leakage
+//!   above.
+//!
+//! It does not cover a garbler encoding different inputs in different
+//! evaluated instances: their consistency is not checked, and the majority
+//! output, which the garbler learns through `reveal`, may then leak
+//! information about the evaluator's input. Nor are the input labels sent for
+//! check instances compared against the regarbled ones, so corrupted input
+//! labels are never caught, and the outcome of the evaluation can tell the
+//! garbler which of them the evaluator chose, up to the sharing above.
+//! Against a malicious evaluator the garbler's inputs stay hidden, since every
+//! instance gives away either its seed or the key of its input labels but not
+//! both, but `reveal` returns whatever value the evaluator sends back,
+//! unauthenticated. Only `GarbleMode::Standard` is supported.
+//!
+//! `Garbler` and `Evaluator` implement `Fancy` and `FancyInput` with
+//! `InstanceWires` items, so any gadget runs unchanged on top of them.
+
+mod evaluator;
+mod garbler;
+
+pub use evaluator::Evaluator;
+pub use garbler::Garbler;
+
+use crate::{
+    dummy::{Dummy, DummyVal},
+    errors::{FancyError, TwopacError},
+    Fancy,
+    HasModulus,
+    Modulus,
+    Wire,
+};
+use rand::Rng;
+use scuttlebutt::{Block, AES_HASH};
+
+/// The default number of garbled instances.
+pub const DEFAULT_INSTANCES: usize = 40;
+
+/// The default number of shares each evaluator input is split into.
+pub const DEFAULT_INPUT_SHARES: usize = 4;
+
+/// A wire in every garbled instance.
+#[derive(Clone, Debug)]
+pub struct InstanceWires(pub(crate) Vec<Wire>);
+
+impl InstanceWires {
+    /// The wire in each instance. The evaluator's wires in check instances are
+    /// the garbler's zero labels.
+    pub fn wires(&self) -> &[Wire] {
+        &self.0
+    }
+}
+
+impl HasModulus for InstanceWires {
+    fn modulus(&self) -> Modulus {
+        self.0[0].modulus()
+    }
+}
+
+pub(crate) fn check_parameters(instances: usize, input_shares: usize) -> Result<(), TwopacError> {
+    if instances < 2 {
+        return Err(TwopacError::FancyError(FancyError::InvalidArg(
+            "cut-and-choose needs at least two instances".to_string(),
+        )));
+    }
+    if input_shares < 1 {
+        return Err(TwopacError::FancyError(FancyError::InvalidArg(
+            "evaluator inputs need at least one share".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+// Masks the label of an evaluator input bit in `instance` under the OT `key`.
+pub(crate) fn input_pad(instance: usize, key: Block) -> Block {
+    AES_HASH.tccr_hash(Block::from(instance as u128), key)
+}
+
+// Split the evaluator input `x` into `n` random additive shares.
+pub(crate) fn share<R: Rng>(rng: &mut R, x: u16, q: &Modulus, n: usize) -> Vec<u64> {
+    let mut dummy = Dummy::new();
+    let mut last = DummyVal::new_big(u64::from(x), *q);
+    let mut shares = Vec::with_capacity(n);
+    for _ in 1..n {
+        let r = match *q {
+            Modulus::ZqBig { q } => rng.gen::<u64>() % q,
+            _ => u64::from(rng.gen::<u32>() % q.order()),
+        };
+        last = dummy
+            .sub(&last, &DummyVal::new_big(r, *q))
+            .expect("shares have the same modulus");
+        shares.push(r);
+    }
+    shares.push(last.val_big());
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dummy::Dummy, role::RoleInput, util::RngExt, CrtBundle, CrtGadgets, Fancy, FancyInput,
+    };
+    use itertools::Itertools;
+    use ocelot::ot::{ChouOrlandiReceiver, ChouOrlandiSender};
+    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+
+    type Gb = Garbler<UnixChannel, AesRng, ChouOrlandiSender>;
+    type Ev = Evaluator<UnixChannel, AesRng, ChouOrlandiReceiver>;
+
+    const INSTANCES: usize = 6;
+    const SHARES: usize = 2;
+
+    fn addition<F: Fancy>(f: &mut F, a: &F::Item, b: &F::Item) -> Result<Option<u16>, F::Error> {
+        let c = f.add(a, b)?;
+        let d = f.mul(&c, &c)?;
+        f.output(&d)
+    }
+
+    #[test]
+    fn test_addition_circuit() {
+        let q = Modulus::Zq { q: 5 };
+        for a in 0..5 {
+            for b in 0..5 {
+                let (sender, receiver) = unix_channel_pair();
+                let handle = std::thread::spawn(move || {
+                    let mut gb =
+                        Gb::with_instances(sender, AesRng::new(), INSTANCES, SHARES).unwrap();
+                    let xs = gb.encode_garbler_inputs(&[a], &[q]).unwrap();
+                    let ys = gb.receive_evaluator_inputs(&[q]).unwrap();
+                    addition(&mut gb, &xs[0], &ys[0]).unwrap();
+                });
+                let mut ev =
+                    Ev::with_instances(receiver, AesRng::new(), INSTANCES, SHARES).unwrap();
+                let xs = ev.receive_garbler_inputs(&[q]).unwrap();
+                let ys = ev.encode_evaluator_inputs(&[b], &[q]).unwrap();
+                let output = addition(&mut ev, &xs[0], &ys[0]).unwrap().unwrap();
+                assert_eq!((a + b) * (a + b) % 5, output);
+                handle.join().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_addition_circuit_GF4() {
+        for a in 0..16 {
+            let b = 15 - a;
+            let (sender, receiver) = unix_channel_pair();
+            let handle = std::thread::spawn(move || {
+                let mut gb = Gb::with_instances(sender, AesRng::new(), INSTANCES, SHARES).unwrap();
+                let x = gb.encode(a, &Modulus::X4_X_1).unwrap();
+                let ys = gb.receive_many(&[Modulus::X4_X_1]).unwrap();
+                let z = gb.add(&x, &ys[0]).unwrap();
+                gb.output(&z).unwrap();
+            });
+            let mut ev = Ev::with_instances(receiver, AesRng::new(), INSTANCES, SHARES).unwrap();
+            let x = ev.receive(&Modulus::X4_X_1).unwrap();
+            let ys = ev.encode_many(&[b], &[Modulus::X4_X_1]).unwrap();
+            let z = ev.add(&x, &ys[0]).unwrap();
+            assert_eq!(ev.output(&z).unwrap(), Some(a ^ b));
+            handle.join().unwrap();
+        }
+    }
+
+    fn relu<F: Fancy>(b: &mut F, xs: &[CrtBundle<F::Item>]) -> Option<Vec<u128>> {
+        let mut outputs = Vec::new();
+        for x in xs.iter() {
+            let z = b.crt_relu(x, "100%", None).unwrap();
+            outputs.push(b.crt_output(&z).unwrap());
+        }
+        outputs.into_iter().collect()
+    }
+
+    #[test]
+    fn test_relu() {
+        let mut rng = rand::thread_rng();
+        let n = 3;
+        let ps = crate::util::primes_with_width(8);
+        let q = crate::util::product(&ps);
+        let input = (0..n).map(|_| rng.gen_u128() % q).collect::<Vec<u128>>();
+
+        let mut dummy = Dummy::new();
+        let dummy_input = input
+            .iter()
+            .map(|x| dummy.crt_encode(*x, q).unwrap())
+            .collect_vec();
+        let target = relu(&mut dummy, &dummy_input).unwrap();
+
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Gb::with_instances(sender, AesRng::new(), INSTANCES, SHARES).unwrap();
+            let xs = gb.crt_receive_many(n, q).unwrap();
+            relu(&mut gb, &xs);
+        });
+        let mut ev = Ev::with_instances(receiver, AesRng::new(), INSTANCES, SHARES).unwrap();
+        let xs = ev.crt_encode_many(&input, q).unwrap();
+        let result = relu(&mut ev, &xs).unwrap();
+        assert_eq!(target, result);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_cheating_garbler() {
+        let q = Modulus::Zq { q: 3 };
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Gb::with_instances(sender, AesRng::new(), INSTANCES, SHARES).unwrap();
+            let x = gb.encode(1, &q).unwrap();
+            // garble a different table than the one agreed on
+            let y = gb.proj(&x, &q, Some(vec![2, 1, 0])).unwrap();
+            gb.output(&y).unwrap();
+        });
+        let mut ev = Ev::with_instances(receiver, AesRng::new(), INSTANCES, SHARES).unwrap();
+        let x = ev.receive(&q).unwrap();
+        let result = ev
+            .proj(&x, &q, Some(vec![0, 1, 2]))
+            .and_then(|y| ev.output(&y));
+        match result {
+            Err(TwopacError::CheatingDetected(_)) => (),
+            r => panic!("cheating was not detected: {:?}", r),
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let (sender, _receiver) = unix_channel_pair();
+        assert!(Gb::with_instances(sender, AesRng::new(), 1, SHARES).is_err());
+    }
+}
@@ -0,0 +1,639 @@
+// -*- mode: rust; -*-
+//
+// This file is part of twopac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of two-party computation by dual execution.
+//!
+//! Both parties garble the computation and evaluate the other's garbled
+//! circuit at the same time, over one channel. `output` only records the
+//! output labels; `Party::check_outputs` then compares a single hash over the
+//! labels of all recorded outputs in both circuits, which agree only if both
+//! executions produced the same values, and returns the values. Each party
+//! commits to its hash before either opens, so that neither can choose its
+//! hash after seeing the other's. A malicious party can therefore not change
+//! the outputs of the honest party, and learns at most one bit more than the
+//! outputs per check: whether it succeeded. Checking once, at the end of the
+//! computation, leaks a single bit in total; `reveal` has to check right away,
+//! and so leaks a bit each.
+//!
+//! `Garbler` and `Evaluator` name the two parties as elsewhere in `twopac`, so
+//! gadgets which dispatch on `HasRole` run unchanged; the `Garbler` sends first
+//! in each step. Both parties learn every output.
+//!
+//! There is no input consistency check. Each party's inputs are encoded
+//! independently in the two executions, and a malicious party may use
+//! different inputs in its own circuit and in its OT for the other's. The
+//! outcome of the check may then depend on the honest party's input beyond
+//! the bit above.
+
+use crate::{
+    errors::{EvaluatorError, GarblerError, TwopacError},
+    role::{self, HasRole, Party as RoleParty, Role},
+    twopac::semihonest::{input_bits, input_weights},
+    Evaluator as Ev,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+    Garbler as Gb,
+    HasModulus,
+    Modulus,
+    Wire,
+};
+use ocelot::ot::{Receiver as OtReceiver, Sender as OtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{
+    commitment::{Commitment, ShaCommitment},
+    AbstractChannel,
+    Block,
+    Malicious,
+    SemiHonest,
+};
+use sha3::{Digest, Sha3_256};
+use std::marker::PhantomData;
+
+/// A wire in both executions: our zero label in the circuit we garble, and the
+/// label we evaluated in the other party's circuit.
+#[derive(Clone, Debug)]
+pub struct DualWire {
+    mine: Wire,
+    theirs: Wire,
+}
+
+impl DualWire {
+    /// The zero label of this wire in our garbled circuit.
+    pub fn mine(&self) -> &Wire {
+        &self.mine
+    }
+
+    /// The label of this wire evaluated in the other party's garbled circuit.
+    pub fn theirs(&self) -> &Wire {
+        &self.theirs
+    }
+}
+
+impl HasModulus for DualWire {
+    fn modulus(&self) -> Modulus {
+        self.mine.modulus()
+    }
+}
+
+/// A party of dual execution, acting as `R` towards `HasRole`.
+pub struct Party<C, RNG, OTS, OTR, R> {
+    garbler: Gb<C, RNG>,
+    evaluator: Ev<C>,
+    channel: C,
+    ot_sender: OTS,
+    ot_receiver: OTR,
+    rng: RNG,
+    // The values of the outputs not checked yet, and their labels in both
+    // circuits, the circuit of the party which sends first first.
+    outputs: Vec<u64>,
+    output_labels: Vec<u8>,
+    role: PhantomData<R>,
+}
+
+/// The dual execution party which sends first.
+pub type Garbler<C, RNG, OTS, OTR> = Party<C, RNG, OTS, OTR, role::Garbler>;
+
+/// The dual execution party which receives first.
+pub type Evaluator<C, RNG, OTS, OTR> = Party<C, RNG, OTS, OTR, role::Evaluator>;
+
+impl<
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+        OTS: OtSender<Msg = Block> + Malicious,
+        OTR: OtReceiver<Msg = Block> + Malicious,
+        R: Role,
+    > Party<C, RNG, OTS, OTR, R>
+{
+    /// Make a new dual execution party.
+    pub fn new(mut channel: C, mut rng: RNG) -> Result<Self, TwopacError> {
+        // Our OT sender pairs with the other party's OT receiver.
+        let (ot_sender, ot_receiver) = if R::PARTY == RoleParty::Garbler {
+            let ot_sender = OTS::init(&mut channel, &mut rng)?;
+            (ot_sender, OTR::init(&mut channel, &mut rng)?)
+        } else {
+            let ot_receiver = OTR::init(&mut channel, &mut rng)?;
+            (OTS::init(&mut channel, &mut rng)?, ot_receiver)
+        };
+        let garbler = Gb::new(channel.clone(), RNG::from_seed(rng.gen()));
+        let evaluator = Ev::new(channel.clone());
+        Ok(Party {
+            garbler,
+            evaluator,
+            channel,
+            ot_sender,
+            ot_receiver,
+            rng,
+            outputs: Vec::new(),
+            output_labels: Vec::new(),
+            role: PhantomData,
+        })
+    }
+
+    /// Get a reference to the internal channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OTS, OTR, R: Role> Party<C, RNG, OTS, OTR, R> {
+    fn sends_first(&self) -> bool {
+        R::PARTY == RoleParty::Garbler
+    }
+
+    // Garble and evaluate one step. The party which sends first garbles before
+    // evaluating, so that both never wait on each other.
+    fn both<FG, FE>(&mut self, fg: FG, fe: FE) -> Result<DualWire, TwopacError>
+    where
+        FG: FnOnce(&mut Gb<C, RNG>) -> Result<Wire, GarblerError>,
+        FE: FnOnce(&mut Ev<C>) -> Result<Wire, EvaluatorError>,
+    {
+        if self.sends_first() {
+            let mine = fg(&mut self.garbler)?;
+            self.channel.flush()?;
+            let theirs = fe(&mut self.evaluator)?;
+            Ok(DualWire { mine, theirs })
+        } else {
+            let theirs = fe(&mut self.evaluator)?;
+            let mine = fg(&mut self.garbler)?;
+            self.channel.flush()?;
+            Ok(DualWire { mine, theirs })
+        }
+    }
+
+    /// Check that both executions agree on every output recorded since the
+    /// last check, and return their values in order.
+    ///
+    /// All outputs are compared at once, so a cheating party learns a single
+    /// bit from this however many there are. If the check fails, this returns
+    /// `TwopacError::CheatingDetected` and none of the values.
+    pub fn check_outputs(&mut self) -> Result<Vec<u64>, TwopacError> {
+        let outputs = std::mem::take(&mut self.outputs);
+        let labels = std::mem::take(&mut self.output_labels);
+        if outputs.is_empty() {
+            return Ok(outputs);
+        }
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(&Sha3_256::digest(&labels));
+
+        // Commit to our hash, then open it once both commitments are in.
+        let seed = self.rng.gen::<[u8; 32]>();
+        let commitment = self.exchange(&commit_digest(R::PARTY, &digest, seed))?;
+        let mut opening = digest.to_vec();
+        opening.extend_from_slice(&seed);
+        let opening = self.exchange(&opening)?;
+        let mut other = [0u8; 32];
+        let mut seed = [0u8; 32];
+        other.copy_from_slice(&opening[..32]);
+        seed.copy_from_slice(&opening[32..]);
+        let party = match R::PARTY {
+            RoleParty::Garbler => RoleParty::Evaluator,
+            RoleParty::Evaluator => RoleParty::Garbler,
+        };
+        if commit_digest(party, &other, seed)[..] != commitment[..] {
+            return Err(TwopacError::CheatingDetected(
+                "the other party's output hash does not match its commitment".to_string(),
+            ));
+        }
+        if digest != other {
+            return Err(TwopacError::CheatingDetected(
+                "the outputs of the two executions differ".to_string(),
+            ));
+        }
+        Ok(outputs)
+    }
+
+    // Decode `x` in the other party's circuit, and record its labels in both
+    // circuits for the next `check_outputs`.
+    fn decode(&mut self, x: &DualWire, big: bool) -> Result<(), TwopacError> {
+        if self.sends_first() {
+            self.garble_output(x, big)?;
+        }
+        let val = if big {
+            self.evaluator.output_big(&x.theirs)?
+        } else {
+            self.evaluator.output(&x.theirs)?.map(u64::from)
+        }
+        .expect("Evaluator always outputs Some");
+        if !self.sends_first() {
+            self.garble_output(x, big)?;
+        }
+
+        // Our label for `val` in our circuit, and the evaluated label in theirs,
+        // ordered by the circuit of the party which sends first.
+        let delta = self.garbler.delta(&x.modulus());
+        let ours = x.mine.plus(&delta.cmul_big(val)).as_block();
+        let theirs = x.theirs.as_block();
+        let (first, second) = if self.sends_first() {
+            (ours, theirs)
+        } else {
+            (theirs, ours)
+        };
+        self.output_labels.extend_from_slice(first.as_ref());
+        self.output_labels.extend_from_slice(second.as_ref());
+        self.outputs.push(val);
+        Ok(())
+    }
+
+    // Send `msg` and receive the other party's message of the same length, the
+    // party which sends first writing before reading.
+    fn exchange(&mut self, msg: &[u8]) -> Result<Vec<u8>, TwopacError> {
+        let mut other = vec![0u8; msg.len()];
+        if self.sends_first() {
+            self.channel.write_bytes(msg)?;
+            self.channel.flush()?;
+            self.channel.read_bytes(&mut other)?;
+        } else {
+            self.channel.read_bytes(&mut other)?;
+            self.channel.write_bytes(msg)?;
+            self.channel.flush()?;
+        }
+        Ok(other)
+    }
+
+    fn garble_output(&mut self, x: &DualWire, big: bool) -> Result<(), TwopacError> {
+        if big {
+            self.garbler.output_big(&x.mine)?;
+        } else {
+            self.garbler.output(&x.mine)?;
+        }
+        self.channel.flush()?;
+        Ok(())
+    }
+}
+
+// The commitment of `party` to its output hash `digest`. Committing to the
+// party too keeps the other party from replaying our commitment as its own.
+fn commit_digest(party: RoleParty, digest: &[u8; 32], seed: [u8; 32]) -> [u8; 32] {
+    let mut commit = ShaCommitment::new(seed);
+    commit.input(&[(party == RoleParty::Evaluator) as u8]);
+    commit.input(digest);
+    commit.finish()
+}
+
+impl<
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        OTS: OtSender<Msg = Block> + Malicious,
+        OTR: OtReceiver<Msg = Block> + Malicious,
+        R: Role,
+    > FancyInput for Party<C, RNG, OTS, OTR, R>
+{
+    type Item = DualWire;
+    type Error = TwopacError;
+
+    /// Encode our inputs directly in our circuit, and obtain their labels in
+    /// the other party's circuit by OT.
+    fn encode_many(&mut self, vals: &[u16], moduli: &[Modulus]) -> Result<Vec<DualWire>, TwopacError> {
+        let mut mine = Vec::with_capacity(vals.len());
+        let mut bits = Vec::new();
+        for (x, q) in vals.iter().zip(moduli.iter()) {
+            let (zero, enc) = self.garbler.encode_wire(*x, q);
+            self.garbler.send_wire(&enc)?;
+            mine.push(zero);
+            bits.extend(input_bits(u64::from(*x), q));
+        }
+        self.channel.flush()?;
+
+        let labels = self
+            .ot_receiver
+            .receive(&mut self.channel, &bits, &mut self.rng)?;
+        let mut labels = labels.into_iter();
+        Ok(mine
+            .into_iter()
+            .zip(moduli.iter())
+            .map(|(mine, q)| {
+                let theirs = input_weights(q)
+                    .into_iter()
+                    .zip(labels.by_ref())
+                    .fold(Wire::zero(q), |acc, (c, label)| {
+                        acc.plus(&Wire::from_block(label, q).cmul_big(c))
+                    });
+                DualWire { mine, theirs }
+            })
+            .collect())
+    }
+
+    /// Read the other party's input labels in its circuit, and send them the
+    /// labels of their inputs in ours by OT.
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<DualWire>, TwopacError> {
+        let theirs = moduli
+            .iter()
+            .map(|q| self.evaluator.read_wire(q))
+            .collect::<Result<Vec<Wire>, EvaluatorError>>()?;
+
+        let mut mine = Vec::with_capacity(moduli.len());
+        let mut inputs = Vec::new();
+        for q in moduli.iter() {
+            let delta = self.garbler.delta(q);
+            let mut wire = Wire::zero(q);
+            for c in input_weights(q) {
+                let zero = Wire::rand(&mut self.rng, q);
+                let one = zero.plus(&delta);
+                wire = wire.plus(&zero.cmul_big(c));
+                inputs.push((zero.as_block(), one.as_block()));
+            }
+            mine.push(wire);
+        }
+        self.ot_sender
+            .send(&mut self.channel, &inputs, &mut self.rng)?;
+        Ok(mine
+            .into_iter()
+            .zip(theirs)
+            .map(|(mine, theirs)| DualWire { mine, theirs })
+            .collect())
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OTS, OTR, R: Role> Fancy
+    for Party<C, RNG, OTS, OTR, R>
+{
+    type Item = DualWire;
+    type Error = TwopacError;
+
+    fn constant(&mut self, x: u16, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        self.both(|gb| gb.constant(x, q), |ev| ev.constant(x, q))
+    }
+
+    fn add(&mut self, x: &DualWire, y: &DualWire) -> Result<Self::Item, Self::Error> {
+        self.both(
+            |gb| gb.add(&x.mine, &y.mine),
+            |ev| ev.add(&x.theirs, &y.theirs),
+        )
+    }
+
+    fn sub(&mut self, x: &DualWire, y: &DualWire) -> Result<Self::Item, Self::Error> {
+        self.both(
+            |gb| gb.sub(&x.mine, &y.mine),
+            |ev| ev.sub(&x.theirs, &y.theirs),
+        )
+    }
+
+    fn cmul(&mut self, x: &DualWire, c: u16) -> Result<Self::Item, Self::Error> {
+        self.both(|gb| gb.cmul(&x.mine, c), |ev| ev.cmul(&x.theirs, c))
+    }
+
+    fn mul(&mut self, x: &DualWire, y: &DualWire) -> Result<Self::Item, Self::Error> {
+        self.both(
+            |gb| gb.mul(&x.mine, &y.mine),
+            |ev| ev.mul(&x.theirs, &y.theirs),
+        )
+    }
+
+    fn proj(
+        &mut self,
+        x: &DualWire,
+        q: &Modulus,
+        tt: Option<Vec<u16>>,
+    ) -> Result<Self::Item, Self::Error> {
+        let tt_ = tt.clone();
+        self.both(|gb| gb.proj(&x.mine, q, tt), |ev| ev.proj(&x.theirs, q, tt_))
+    }
+
+    /// Record `x` for the next `check_outputs`, which returns its value.
+    fn output(&mut self, x: &DualWire) -> Result<Option<u16>, Self::Error> {
+        self.decode(x, false).map(|_| None)
+    }
+
+    fn constant_big(&mut self, x: u64, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        self.both(|gb| gb.constant_big(x, q), |ev| ev.constant_big(x, q))
+    }
+
+    fn cmul_big(&mut self, x: &DualWire, c: u64) -> Result<Self::Item, Self::Error> {
+        self.both(|gb| gb.cmul_big(&x.mine, c), |ev| ev.cmul_big(&x.theirs, c))
+    }
+
+    /// Record `x` for the next `check_outputs`, which returns its value.
+    fn output_big(&mut self, x: &DualWire) -> Result<Option<u64>, Self::Error> {
+        self.decode(x, true).map(|_| None)
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OTS, OTR, R: Role> FancyReveal
+    for Party<C, RNG, OTS, OTR, R>
+{
+    /// Output `x` and check all recorded outputs right away, since the value
+    /// is needed now.
+    fn reveal(&mut self, x: &DualWire) -> Result<u16, Self::Error> {
+        self.decode(x, false)?;
+        let outputs = self.check_outputs()?;
+        Ok(outputs[outputs.len() - 1] as u16)
+    }
+}
+
+impl<C, RNG, OTS, OTR, R: Role> HasRole for Party<C, RNG, OTS, OTR, R> {
+    type Role = R;
+}
+
+impl<C, RNG, OTS, OTR, R> SemiHonest for Party<C, RNG, OTS, OTR, R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dummy::Dummy, util::RngExt, CrtBundle, CrtGadgets};
+    use itertools::Itertools;
+    use ocelot::ot::{ChouOrlandiReceiver, ChouOrlandiSender};
+    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+
+    type Alice = Garbler<UnixChannel, AesRng, ChouOrlandiSender, ChouOrlandiReceiver>;
+    type Bob = Evaluator<UnixChannel, AesRng, ChouOrlandiSender, ChouOrlandiReceiver>;
+
+    // shared by both parties: the garbler's input `a` comes first
+    fn role_mul<F>(f: &mut F, input: u16, q: &Modulus) -> Result<(), TwopacError>
+    where
+        F: Fancy<Item = DualWire, Error = TwopacError>
+            + FancyInput<Item = DualWire, Error = TwopacError>
+            + HasRole,
+    {
+        let (a, b) = match f.party() {
+            RoleParty::Garbler => {
+                let a = f.encode(input, q)?;
+                (a, f.receive(q)?)
+            }
+            RoleParty::Evaluator => {
+                let a = f.receive(q)?;
+                (a, f.encode(input, q)?)
+            }
+        };
+        let c = f.add(&a, &b)?;
+        let d = f.mul(&c, &b)?;
+        assert_eq!(f.output(&c)?, None);
+        assert_eq!(f.output(&d)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dual_execution() {
+        let q = Modulus::Zq { q: 7 };
+        for a in 0..7 {
+            let b = 6 - a / 2;
+            let (sender, receiver) = unix_channel_pair();
+            let handle = std::thread::spawn(move || {
+                let mut alice = Alice::new(sender, AesRng::new()).unwrap();
+                role_mul(&mut alice, a, &q).unwrap();
+                alice.check_outputs().unwrap()
+            });
+            let mut bob = Bob::new(receiver, AesRng::new()).unwrap();
+            role_mul(&mut bob, b, &q).unwrap();
+            let outputs = bob.check_outputs().unwrap();
+            let c = u64::from((a + b) % 7);
+            assert_eq!(outputs, [c, c * u64::from(b) % 7]);
+            assert_eq!(handle.join().unwrap(), outputs);
+        }
+    }
+
+    #[test]
+    fn test_dual_execution_GF4() {
+        for a in 0..16 {
+            let b = 15 - a;
+            let (sender, receiver) = unix_channel_pair();
+            let handle = std::thread::spawn(move || {
+                let mut alice = Alice::new(sender, AesRng::new()).unwrap();
+                role_mul(&mut alice, a, &Modulus::X4_X_1).unwrap();
+                alice.check_outputs().unwrap()
+            });
+            let mut bob = Bob::new(receiver, AesRng::new()).unwrap();
+            role_mul(&mut bob, b, &Modulus::X4_X_1).unwrap();
+            let outputs = bob.check_outputs().unwrap();
+            let mut dummy = Dummy::new();
+            let x = dummy.encode(a, &Modulus::X4_X_1).unwrap();
+            let y = dummy.encode(b, &Modulus::X4_X_1).unwrap();
+            let z = dummy.add(&x, &y).unwrap();
+            let w = dummy.mul(&z, &y).unwrap();
+            let expected = [
+                u64::from(dummy.output(&z).unwrap().unwrap()),
+                u64::from(dummy.output(&w).unwrap().unwrap()),
+            ];
+            assert_eq!(outputs, expected);
+            assert_eq!(handle.join().unwrap(), outputs);
+        }
+    }
+
+    // Output the relu of every `xs`, and check all of them at once.
+    fn relu<R: Role>(
+        b: &mut Party<UnixChannel, AesRng, ChouOrlandiSender, ChouOrlandiReceiver, R>,
+        xs: &[CrtBundle<DualWire>],
+    ) -> Vec<u128> {
+        for x in xs.iter() {
+            let z = b.crt_relu(x, "100%", None).unwrap();
+            assert_eq!(b.crt_output(&z).unwrap(), None);
+        }
+        let q = xs[0].composite_modulus();
+        b.check_outputs()
+            .unwrap()
+            .chunks(xs[0].moduli().len())
+            .map(|rs| crate::util::crt_inv_factor(&rs.iter().map(|r| *r as u16).collect_vec(), q))
+            .collect()
+    }
+
+    #[test]
+    fn test_relu() {
+        let mut rng = rand::thread_rng();
+        let n = 4;
+        let ps = crate::util::primes_with_width(10);
+        let q = crate::util::product(&ps);
+        let input = (0..n).map(|_| rng.gen_u128() % q).collect::<Vec<u128>>();
+
+        let mut dummy = Dummy::new();
+        let target = input
+            .iter()
+            .map(|x| {
+                let x = dummy.crt_encode(*x, q).unwrap();
+                let z = dummy.crt_relu(&x, "100%", None).unwrap();
+                dummy.crt_output(&z).unwrap().unwrap()
+            })
+            .collect_vec();
+
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut alice = Alice::new(sender, AesRng::new()).unwrap();
+            let xs = alice.crt_receive_many(n, q).unwrap();
+            relu(&mut alice, &xs)
+        });
+        let mut bob = Bob::new(receiver, AesRng::new()).unwrap();
+        let xs = bob.crt_encode_many(&input, q).unwrap();
+        let result = relu(&mut bob, &xs);
+        assert_eq!(target, result);
+        assert_eq!(handle.join().unwrap(), result);
+    }
+
+    #[test]
+    fn test_reveal() {
+        let q = Modulus::Zq { q: 5 };
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut alice = Alice::new(sender, AesRng::new()).unwrap();
+            let x = alice.encode(2, &q).unwrap();
+            let y = alice.receive(&q).unwrap();
+            let z = alice.mul(&x, &y).unwrap();
+            alice.reveal(&z).unwrap()
+        });
+        let mut bob = Bob::new(receiver, AesRng::new()).unwrap();
+        let x = bob.receive(&q).unwrap();
+        let y = bob.encode(4, &q).unwrap();
+        let z = bob.mul(&x, &y).unwrap();
+        assert_eq!(bob.reveal(&z).unwrap(), 3);
+        assert_eq!(handle.join().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_cheating_is_detected() {
+        let q = Modulus::Zq { q: 3 };
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut alice = Alice::new(sender, AesRng::new()).unwrap();
+            let x = alice.encode(0, &q).unwrap();
+            // garble a different table than the one agreed on
+            let y = alice.proj(&x, &q, Some(vec![2, 1, 0])).unwrap();
+            alice.output(&x).unwrap();
+            alice.output(&y).unwrap();
+            alice.check_outputs()
+        });
+        let mut bob = Bob::new(receiver, AesRng::new()).unwrap();
+        let x = bob.receive(&q).unwrap();
+        let y = bob.proj(&x, &q, Some(vec![0, 1, 2])).unwrap();
+        bob.output(&x).unwrap();
+        bob.output(&y).unwrap();
+        match bob.check_outputs() {
+            Err(TwopacError::CheatingDetected(_)) => (),
+            r => panic!("cheating was not detected: {:?}", r),
+        }
+        match handle.join().unwrap() {
+            Err(TwopacError::CheatingDetected(_)) => (),
+            r => panic!("cheating was not detected: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_substituted_digest_is_detected() {
+        let q = Modulus::Zq { q: 3 };
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut alice = Alice::new(sender, AesRng::new()).unwrap();
+            let x = alice.encode(0, &q).unwrap();
+            let y = alice.proj(&x, &q, Some(vec![0, 1, 2])).unwrap();
+            alice.output(&y).unwrap();
+            alice.check_outputs()
+        });
+        // Bob garbles another table, and answers the output check with
+        // Alice's own commitment and opening instead of his.
+        let mut bob = Bob::new(receiver, AesRng::new()).unwrap();
+        let x = bob.receive(&q).unwrap();
+        let y = bob.proj(&x, &q, Some(vec![2, 1, 0])).unwrap();
+        bob.evaluator.output(&y.theirs).unwrap();
+        bob.garble_output(&y, false).unwrap();
+        for n in [32, 64].iter() {
+            let mut msg = vec![0u8; *n];
+            bob.channel.read_bytes(&mut msg).unwrap();
+            bob.channel.write_bytes(&msg).unwrap();
+            bob.channel.flush().unwrap();
+        }
+        match handle.join().unwrap() {
+            Err(TwopacError::CheatingDetected(_)) => (),
+            r => panic!("cheating was not detected: {:?}", r),
+        }
+    }
+}
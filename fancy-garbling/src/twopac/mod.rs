@@ -6,4 +6,8 @@
 
 //! Implementations of two-party secure computation.
 
+pub mod cut_and_choose;
+pub mod dualex;
+pub mod pvc;
 pub mod semihonest;
+pub mod session;
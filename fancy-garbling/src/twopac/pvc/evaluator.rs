@@ -0,0 +1,442 @@
+// -*- mode: rust; -*-
+//
+// This file is part of twopac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use super::{
+    Certificate,
+    HashingChannel,
+    OtView,
+    RecordingChannel,
+    Replay,
+    Sent,
+    Transcript,
+    VerifyingKey,
+};
+use crate::{
+    errors::{EvaluatorError, TwopacError},
+    role::{self, HasRole},
+    twopac::{
+        cut_and_choose::{check_parameters, input_pad, share, InstanceWires},
+        semihonest::{input_bits, input_weights},
+    },
+    Evaluator as Ev,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+    Modulus,
+    Wire,
+};
+use ocelot::ot::Receiver as OtReceiver;
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, Malicious, SemiHonest};
+
+enum Instance<C> {
+    Eval {
+        evaluator: Box<Ev<HashingChannel<C>>>,
+        transcript: HashingChannel<C>,
+        pad: Box<AesRng>,
+    },
+    Check {
+        replay: Box<Replay>,
+        seed: Block,
+        received: Sent,
+    },
+}
+
+/// PVC evaluator, which obtains a `Certificate` when it catches the garbler
+/// cheating.
+pub struct Evaluator<C, RNG, OT> {
+    instances: Vec<Instance<C>>,
+    commitments: Vec<[u8; 32]>,
+    ot_view: OtView,
+    // The number of transcripts checked so far.
+    round: u64,
+    input_shares: usize,
+    key: VerifyingKey,
+    // Proof of the first check instance found not to match its seed.
+    certificate: Option<Certificate>,
+    channel: C,
+    ot: OT,
+    rng: RNG,
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + Malicious>
+    Evaluator<C, RNG, OT>
+{
+    /// Make a new `Evaluator` with `DEFAULT_INSTANCES` instances and
+    /// `DEFAULT_INPUT_SHARES` shares per input, for a garbler with key `key`.
+    pub fn new(channel: C, rng: RNG, key: VerifyingKey) -> Result<Self, TwopacError> {
+        Self::with_instances(
+            channel,
+            rng,
+            key,
+            super::DEFAULT_INSTANCES,
+            super::DEFAULT_INPUT_SHARES,
+        )
+    }
+
+    /// Make a new `Evaluator` for `instances` instances, all but one of which
+    /// are checked, splitting each input into `input_shares` shares. Both
+    /// parties must use the same parameters.
+    pub fn with_instances(
+        mut channel: C,
+        mut rng: RNG,
+        key: VerifyingKey,
+        instances: usize,
+        input_shares: usize,
+    ) -> Result<Self, TwopacError> {
+        check_parameters(instances, input_shares)?;
+        // The OT runs on its own randomness, so that anyone can run our side
+        // of it again from a certificate.
+        let ot_seed = rng.gen::<Block>();
+        let mut ot_rng = AesRng::from_seed(ot_seed);
+        let mut recorder = RecordingChannel::new(channel.clone());
+        let mut ot = OT::init(&mut recorder, &mut ot_rng)?;
+        let mut commitments = vec![[0u8; 32]; instances];
+        for c in commitments.iter_mut() {
+            channel.read_bytes(c)?;
+        }
+        let evaluated = rng.gen_range(0, instances);
+        let choices = (0..instances)
+            .map(|i| i == evaluated)
+            .collect::<Vec<bool>>();
+        let keys = ot.receive(&mut recorder, &choices, &mut ot_rng)?;
+        let (garbler, evaluator) = recorder.take();
+        let ot_view = OtView {
+            seed: ot_seed,
+            evaluated,
+            garbler,
+            evaluator,
+        };
+
+        let instances = keys
+            .into_iter()
+            .enumerate()
+            .map(|(i, key)| {
+                if i == evaluated {
+                    let transcript = HashingChannel::new(channel.clone());
+                    Instance::Eval {
+                        evaluator: Box::new(Ev::new(transcript.clone())),
+                        transcript,
+                        pad: Box::new(AesRng::from_seed(key)),
+                    }
+                } else {
+                    Instance::Check {
+                        replay: Box::new(Replay::new(key, input_shares)),
+                        seed: key,
+                        received: Sent::new(),
+                    }
+                }
+            })
+            .collect();
+        let mut evaluator = Evaluator {
+            instances,
+            commitments,
+            ot_view,
+            round: 0,
+            input_shares,
+            key,
+            certificate: None,
+            channel,
+            ot,
+            rng,
+        };
+        evaluator.check_signature()?;
+        Ok(evaluator)
+    }
+
+    /// Get a reference to the internal channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+}
+
+// Read the bytes a check instance sent from the channel, as many as
+// regarbling it produced.
+fn receive_check<C: AbstractChannel>(
+    channel: &mut C,
+    replay: &mut Replay,
+    received: &mut Sent,
+) -> Result<(), TwopacError> {
+    let mut got = vec![0u8; replay.take_written().len()];
+    channel.read_bytes(&mut got)?;
+    received.input(&got);
+    Ok(())
+}
+
+// Run the same operation on every instance, evaluating or checking it.
+macro_rules! each {
+    ($self:ident, |$f:ident, $i:ident| $body:expr) => {
+        $self.each(|$f, $i| $body, |$f, $i| $body)
+    };
+}
+
+impl<C: AbstractChannel, RNG, OT> Evaluator<C, RNG, OT> {
+    /// End the session, checking the garbler's signature on the transcript.
+    ///
+    /// Returns a `Certificate` if a check instance did not match its seed, and
+    /// `None` if all of them did, in which case the outputs can be trusted
+    /// with the deterrence factor of the protocol.
+    pub fn finish(&mut self) -> Result<Option<Certificate>, TwopacError> {
+        self.check_signature()?;
+        Ok(self.certificate.take())
+    }
+
+    // Check the garbler's signature on what each instance sent so far, see
+    // `Garbler::sign`, and compare what it signed for the check instances with
+    // their seeds.
+    fn check_signature(&mut self) -> Result<(), TwopacError> {
+        for instance in self.instances.iter_mut() {
+            if let Instance::Check {
+                replay, received, ..
+            } = instance
+            {
+                replay.flush()?;
+                receive_check(&mut self.channel, replay, received)?;
+            }
+        }
+        let mut sent = Vec::with_capacity(self.instances.len());
+        for _ in 0..self.instances.len() {
+            let mut bytes = [0u8; 40];
+            self.channel.read_bytes(&mut bytes)?;
+            sent.push((super::read_u64(&bytes), super::read_hash(&bytes[8..])));
+        }
+        let mut bytes = [0u8; 64];
+        self.channel.read_bytes(&mut bytes)?;
+        let signature = super::signature_from_bytes(&bytes).ok_or_else(|| {
+            TwopacError::CheatingDetected("the transcript signature is malformed".to_string())
+        })?;
+        let transcript = Transcript {
+            input_shares: self.input_shares,
+            round: self.round,
+            ot: self.ot_view.hash(),
+            commitments: self.commitments.clone(),
+            sent,
+        };
+        self.round += 1;
+        if !self.key.verify(&transcript.to_bytes(), &signature) {
+            return Err(TwopacError::CheatingDetected(
+                "the transcript signature is invalid".to_string(),
+            ));
+        }
+
+        // The garbler is held to what it signed, even if it sent us otherwise.
+        let mut forged = false;
+        for (i, (instance, signed)) in self
+            .instances
+            .iter()
+            .zip(transcript.sent.iter())
+            .enumerate()
+        {
+            match instance {
+                Instance::Eval { transcript, .. } => forged |= transcript.digest() != *signed,
+                Instance::Check {
+                    replay,
+                    seed,
+                    received,
+                } => {
+                    if super::commit(*seed) != self.commitments[i]
+                        || replay.sent.digest() != *signed
+                    {
+                        if self.certificate.is_none() {
+                            self.certificate = Some(Certificate {
+                                transcript: transcript.clone(),
+                                signature,
+                                instance: i,
+                                ot: self.ot_view.clone(),
+                            });
+                        }
+                    } else {
+                        forged |= received.digest() != *signed;
+                    }
+                }
+            }
+        }
+        if forged && self.certificate.is_none() {
+            return Err(TwopacError::CheatingDetected(
+                "the garbler sent other bytes than it signed".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn each<FE, FG>(&mut self, mut fe: FE, mut fg: FG) -> Result<InstanceWires, TwopacError>
+    where
+        FE: FnMut(&mut Ev<HashingChannel<C>>, usize) -> Result<Wire, EvaluatorError>,
+        FG: FnMut(&mut Replay, usize) -> Result<Wire, TwopacError>,
+    {
+        let mut wires = Vec::with_capacity(self.instances.len());
+        for (i, instance) in self.instances.iter_mut().enumerate() {
+            let w = match instance {
+                Instance::Eval { evaluator, .. } => fe(evaluator, i)?,
+                Instance::Check {
+                    replay, received, ..
+                } => {
+                    let w = fg(replay, i)?;
+                    receive_check(&mut self.channel, replay, received)?;
+                    w
+                }
+            };
+            wires.push(w);
+        }
+        Ok(InstanceWires(wires))
+    }
+
+    // Decode `x` in the evaluated instance.
+    fn decode<T, FE, FG>(&mut self, x: &InstanceWires, mut fe: FE, mut fg: FG) -> Result<T, TwopacError>
+    where
+        FE: FnMut(&mut Ev<HashingChannel<C>>, &Wire) -> Result<Option<T>, EvaluatorError>,
+        FG: FnMut(&mut Replay, &Wire) -> Result<Option<T>, TwopacError>,
+    {
+        let mut val = None;
+        for (instance, w) in self.instances.iter_mut().zip(x.0.iter()) {
+            match instance {
+                Instance::Eval { evaluator, .. } => val = fe(evaluator, w)?,
+                Instance::Check {
+                    replay, received, ..
+                } => {
+                    fg(replay, w)?;
+                    receive_check(&mut self.channel, replay, received)?;
+                }
+            }
+        }
+        Ok(val.expect("Evaluator always outputs Some"))
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + Malicious> FancyInput
+    for Evaluator<C, RNG, OT>
+{
+    type Item = InstanceWires;
+    type Error = TwopacError;
+
+    /// Receive garbler input wires.
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<InstanceWires>, TwopacError> {
+        let mut wires = vec![Vec::with_capacity(self.instances.len()); moduli.len()];
+        for instance in self.instances.iter_mut() {
+            for (q, ws) in moduli.iter().zip(wires.iter_mut()) {
+                let block = self.channel.read_block()?;
+                let w = match instance {
+                    Instance::Eval { pad, .. } => Wire::from_block(block ^ pad.gen::<Block>(), q),
+                    Instance::Check { replay, .. } => replay.garbler.encode_wire(0, q).0,
+                };
+                ws.push(w);
+            }
+        }
+        Ok(wires.into_iter().map(InstanceWires).collect())
+    }
+
+    /// Perform OT on shares of the inputs and obtain wires for them.
+    fn encode_many(
+        &mut self,
+        inputs: &[u16],
+        moduli: &[Modulus],
+    ) -> Result<Vec<InstanceWires>, TwopacError> {
+        let mut bits = Vec::new();
+        for (x, q) in inputs.iter().zip(moduli.iter()) {
+            for share in share(&mut self.rng, *x, q, self.input_shares) {
+                bits.extend(input_bits(share, q));
+            }
+        }
+        let keys = self.ot.receive(&mut self.channel, &bits, &mut self.rng)?;
+
+        let shares = self.input_shares;
+        let mut wires = vec![Vec::with_capacity(self.instances.len()); moduli.len()];
+        for (i, instance) in self.instances.iter_mut().enumerate() {
+            let mut keys = keys.iter().zip(bits.iter());
+            for (q, ws) in moduli.iter().zip(wires.iter_mut()) {
+                let weights = input_weights(q);
+                let mut wire = Wire::zero(q);
+                for (c, (key, bit)) in weights
+                    .iter()
+                    .cycle()
+                    .zip(keys.by_ref().take(shares * weights.len()))
+                {
+                    let zero = self.channel.read_block()?;
+                    let one = self.channel.read_block()?;
+                    let label = match instance {
+                        Instance::Eval { .. } => {
+                            let ct = if *bit { one } else { zero };
+                            Wire::from_block(ct ^ input_pad(i, *key), q)
+                        }
+                        Instance::Check { replay, .. } => replay.garbler.encode_wire(0, q).0,
+                    };
+                    wire = wire.plus(&label.cmul_big(*c));
+                }
+                ws.push(wire);
+            }
+        }
+        Ok(wires.into_iter().map(InstanceWires).collect())
+    }
+}
+
+impl<C: AbstractChannel, RNG, OT> Fancy for Evaluator<C, RNG, OT> {
+    type Item = InstanceWires;
+    type Error = TwopacError;
+
+    fn constant(&mut self, x: u16, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, _i| f.constant(x, q))
+    }
+
+    fn add(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.add(&x.0[i], &y.0[i]))
+    }
+
+    fn sub(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.sub(&x.0[i], &y.0[i]))
+    }
+
+    fn cmul(&mut self, x: &Self::Item, c: u16) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.cmul(&x.0[i], c))
+    }
+
+    fn mul(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.mul(&x.0[i], &y.0[i]))
+    }
+
+    fn proj(
+        &mut self,
+        x: &Self::Item,
+        q: &Modulus,
+        tt: Option<Vec<u16>>,
+    ) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.proj(&x.0[i], q, tt.clone()))
+    }
+
+    fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
+        self.decode(x, |ev, w| ev.output(w), |r, w| r.output(w))
+            .map(Some)
+    }
+
+    fn constant_big(&mut self, x: u64, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, _i| f.constant_big(x, q))
+    }
+
+    fn cmul_big(&mut self, x: &Self::Item, c: u64) -> Result<Self::Item, Self::Error> {
+        each!(self, |f, i| f.cmul_big(&x.0[i], c))
+    }
+
+    fn output_big(&mut self, x: &Self::Item) -> Result<Option<u64>, Self::Error> {
+        self.decode(x, |ev, w| ev.output_big(w), |r, w| r.output_big(w))
+            .map(Some)
+    }
+}
+
+impl<C: AbstractChannel, RNG, OT> FancyReveal for Evaluator<C, RNG, OT> {
+    fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
+        let val = self.output(x)?.expect("Evaluator always outputs Some(u16)");
+        // The garbler must sign before it can learn anything about our choice.
+        self.check_signature()?;
+        self.channel.write_u16(val)?;
+        self.channel.flush()?;
+        Ok(val)
+    }
+}
+
+impl<C, RNG, OT> HasRole for Evaluator<C, RNG, OT> {
+    type Role = role::Evaluator;
+}
+
+impl<C, RNG, OT> SemiHonest for Evaluator<C, RNG, OT> {}
@@ -0,0 +1,789 @@
+// -*- mode: rust; -*-
+//
+// This file is part of twopac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of two-party computation with publicly verifiable covert
+//! (PVC) security.
+//!
+//! As in `cut_and_choose`, the garbler garbles the computation `instances` times,
+//! each from its own seed, and the evaluator learns the seeds of the instances
+//! it checks by OT. Here however only one instance is evaluated and all others
+//! are checked, so few instances suffice: a cheating garbler is caught with
+//! probability `1 - 1 / instances`, the deterrence factor.
+//!
+//! Since the evaluator's choice is hidden by the OT, the garbler cannot know
+//! whether it would be caught, and must be held to everything it sent before
+//! the evaluator sends back anything depending on that choice. It commits to
+//! the seeds up front, and signs the commitments and the transcript of the OT
+//! sending the seeds right after the OT, as in the signed OT of Asharov and
+//! Orlandi, "Calling Out Cheaters: Covert Security with Public Verifiability"
+//! (ASIACRYPT 2012). Before every `reveal` and in `Garbler::finish`, it signs
+//! them again together with the length and hash of what each instance sent so
+//! far.
+//!
+//! If a check instance does not match the seed it got by OT, the evaluator
+//! keeps the first signed transcript showing it, and `Evaluator::finish`
+//! returns it as a `Certificate`, along with the randomness and messages of
+//! the evaluator in the OT. Anyone with the garbler's `VerifyingKey`, the OT
+//! and the circuit can verify the certificate by running the evaluator's side
+//! of the OT again, which gives the seed of the instance, and regarbling it,
+//! so a caught garbler cannot deny having cheated.
+//!
+//! Limitations:
+//!
+//! * Rather than using the dedicated signed OT of Asharov and Orlandi, the
+//!   garbler signs the transcript of an OT from `ocelot`, so its receiver must
+//!   be deterministic given its randomness and the messages of the sender.
+//! * A garbler which does not sign, or sends other bytes than it signs, makes
+//!   the evaluator abort without a certificate. It does so before learning
+//!   anything about the evaluator's choice.
+//! * As in `cut_and_choose`, input labels of check instances are not verified.
+//! * Outputs must not be acted upon before `Evaluator::finish` succeeds.
+//! * Signatures are Ed25519 signatures from `ed25519-dalek`, verified strictly.
+
+mod evaluator;
+mod garbler;
+
+pub use evaluator::Evaluator;
+pub use garbler::Garbler;
+
+use crate::{
+    errors::TwopacError,
+    twopac::semihonest::input_weights,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+    Garbler as Gb,
+    Modulus,
+    Wire,
+};
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer};
+use ocelot::ot::Receiver as OtReceiver;
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, Channel};
+use sha2::{Digest, Sha256};
+use std::{cell::RefCell, convert::TryFrom, rc::Rc};
+
+/// The default number of garbled instances.
+pub const DEFAULT_INSTANCES: usize = 4;
+
+/// The default number of shares each evaluator input is split into.
+pub const DEFAULT_INPUT_SHARES: usize = 2;
+
+/// The garbler's key for signing transcripts.
+#[derive(Debug)]
+pub struct SigningKey(Keypair);
+
+impl SigningKey {
+    /// Generate a random signing key.
+    pub fn generate<R: CryptoRng + Rng>(rng: &mut R) -> Self {
+        SigningKey(Keypair::generate(rng))
+    }
+
+    /// The key verifying our signatures.
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(self.0.public)
+    }
+
+    fn sign(&self, msg: &[u8]) -> Signature {
+        self.0.sign(msg)
+    }
+}
+
+impl Clone for SigningKey {
+    fn clone(&self) -> Self {
+        SigningKey(Keypair::from_bytes(&self.0.to_bytes()).expect("a valid keypair"))
+    }
+}
+
+/// The public key of a garbler, verifying its transcript signatures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyingKey(PublicKey);
+
+impl VerifyingKey {
+    fn verify(&self, msg: &[u8], sig: &Signature) -> bool {
+        self.0.verify_strict(msg, sig).is_ok()
+    }
+}
+
+fn signature_from_bytes(bytes: &[u8]) -> Option<Signature> {
+    Signature::try_from(bytes).ok()
+}
+
+// What the garbler signs: the commitments to the seeds, the hash of the OT
+// sending them, and the length and hash of what each instance sent up to the
+// `round`-th signature.
+#[derive(Clone, Debug)]
+struct Transcript {
+    input_shares: usize,
+    round: u64,
+    ot: [u8; 32],
+    commitments: Vec<[u8; 32]>,
+    sent: Vec<(u64, [u8; 32])>,
+}
+
+const TRANSCRIPT_LABEL: &[u8] = b"fancy-garbling pvc transcript";
+
+impl Transcript {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = TRANSCRIPT_LABEL.to_vec();
+        bytes.extend_from_slice(&(self.commitments.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.input_shares as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.round.to_le_bytes());
+        bytes.extend_from_slice(&self.ot);
+        for c in self.commitments.iter() {
+            bytes.extend_from_slice(c);
+        }
+        for (len, hash) in self.sent.iter() {
+            bytes.extend_from_slice(&len.to_le_bytes());
+            bytes.extend_from_slice(hash);
+        }
+        bytes
+    }
+
+    // Parse a transcript from the front of `bytes`, returning it and its length.
+    fn from_bytes(bytes: &[u8]) -> Option<(Self, usize)> {
+        let mut pos = TRANSCRIPT_LABEL.len();
+        if bytes.len() < pos + 56 || &bytes[..pos] != TRANSCRIPT_LABEL {
+            return None;
+        }
+        let instances = read_u64(&bytes[pos..]) as usize;
+        let input_shares = read_u64(&bytes[pos + 8..]) as usize;
+        let round = read_u64(&bytes[pos + 16..]);
+        let ot = read_hash(&bytes[pos + 24..]);
+        pos += 56;
+        if (bytes.len() - pos) / 72 < instances {
+            return None;
+        }
+        let commitments = bytes[pos..pos + 32 * instances]
+            .chunks(32)
+            .map(read_hash)
+            .collect();
+        pos += 32 * instances;
+        let sent = bytes[pos..pos + 40 * instances]
+            .chunks(40)
+            .map(|c| (read_u64(c), read_hash(&c[8..])))
+            .collect();
+        Some((
+            Transcript {
+                input_shares,
+                round,
+                ot,
+                commitments,
+                sent,
+            },
+            pos + 40 * instances,
+        ))
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(b)
+}
+
+fn read_hash(bytes: &[u8]) -> [u8; 32] {
+    let mut h = [0u8; 32];
+    h.copy_from_slice(&bytes[..32]);
+    h
+}
+
+// The commitment to the seed of an instance.
+fn commit(seed: Block) -> [u8; 32] {
+    digest(Sha256::new().chain(seed.as_ref()))
+}
+
+fn digest(hash: Sha256) -> [u8; 32] {
+    read_hash(&hash.result())
+}
+
+// The hash of the OT sending the seeds, from the messages of both sides.
+fn ot_hash(garbler: &[u8], evaluator: &[u8]) -> [u8; 32] {
+    digest(
+        Sha256::new()
+            .chain((garbler.len() as u64).to_le_bytes())
+            .chain(garbler)
+            .chain(evaluator),
+    )
+}
+
+// The evaluator's side of the OT sending the seeds: the seed of its
+// randomness, the instance it chose to evaluate, and the messages of both
+// sides.
+#[derive(Clone, Debug)]
+struct OtView {
+    seed: Block,
+    evaluated: usize,
+    garbler: Vec<u8>,
+    evaluator: Vec<u8>,
+}
+
+impl OtView {
+    fn hash(&self) -> [u8; 32] {
+        ot_hash(&self.garbler, &self.evaluator)
+    }
+
+    // Run the evaluator's side of the OT again, returning what it received,
+    // or `None` if it does not send the same messages.
+    fn replay<OT: OtReceiver<Msg = Block>>(&self, instances: usize) -> Option<Vec<Block>> {
+        let channel = Channel::new(std::io::Cursor::new(self.garbler.clone()), Vec::new());
+        let mut rng = AesRng::from_seed(self.seed);
+        let choices = (0..instances)
+            .map(|i| i == self.evaluated)
+            .collect::<Vec<bool>>();
+        let mut ot = OT::init(&mut channel.clone(), &mut rng).ok()?;
+        let keys = ot.receive(&mut channel.clone(), &choices, &mut rng).ok()?;
+        let read = channel.clone().reader().borrow().position();
+        if read != self.garbler.len() as u64 || *channel.writer().borrow() != self.evaluator {
+            return None;
+        }
+        Some(keys)
+    }
+}
+
+/// Proof that a garbler cheated in a check instance.
+#[derive(Clone, Debug)]
+pub struct Certificate {
+    transcript: Transcript,
+    signature: Signature,
+    instance: usize,
+    ot: OtView,
+}
+
+impl Certificate {
+    /// The instance which does not match its seed.
+    pub fn instance(&self) -> usize {
+        self.instance
+    }
+
+    /// Check that the garbler with key `key` signed a transcript in which
+    /// `instance` does not match the seed it sent by OT, when garbling the
+    /// computation `circuit` with the oblivious transfer `OT`.
+    ///
+    /// `circuit` must run the evaluator's side of the computation on the given
+    /// `Replay`, which regarbles the instance. The values of inputs are
+    /// ignored, and revealed values are zero.
+    pub fn verify<OT, F>(&self, key: &VerifyingKey, circuit: F) -> bool
+    where
+        OT: OtReceiver<Msg = Block>,
+        F: FnOnce(&mut Replay) -> Result<(), TwopacError>,
+    {
+        let transcript = &self.transcript;
+        let instances = transcript.commitments.len();
+        if !key.verify(&transcript.to_bytes(), &self.signature)
+            || self.instance >= instances
+            || self.instance == self.ot.evaluated
+            || transcript.ot != self.ot.hash()
+        {
+            return false;
+        }
+        let seed = match self.ot.replay::<OT>(instances) {
+            Some(keys) if keys.len() == instances => keys[self.instance],
+            _ => return false,
+        };
+        if commit(seed) != transcript.commitments[self.instance] {
+            return true;
+        }
+        let mut replay = Replay::new(seed, transcript.input_shares);
+        if circuit(&mut replay).and_then(|_| replay.checkpoint()).is_err() {
+            return false;
+        }
+        match replay.checkpoints.get(transcript.round as usize) {
+            Some(sent) => *sent != transcript.sent[self.instance],
+            None => false,
+        }
+    }
+
+    /// Serialize the certificate, to hand it to a third party.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.transcript.to_bytes();
+        bytes.extend_from_slice(&self.signature.to_bytes());
+        bytes.extend_from_slice(&(self.instance as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.ot.evaluated as u64).to_le_bytes());
+        bytes.extend_from_slice(self.ot.seed.as_ref());
+        for msgs in [&self.ot.garbler, &self.ot.evaluator].iter() {
+            bytes.extend_from_slice(&(msgs.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(msgs);
+        }
+        bytes
+    }
+
+    /// Deserialize a certificate produced by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (transcript, mut pos) = Transcript::from_bytes(bytes)?;
+        if bytes.len() < pos + 64 + 8 + 8 + 16 {
+            return None;
+        }
+        let signature = signature_from_bytes(&bytes[pos..pos + 64])?;
+        let instance = read_u64(&bytes[pos + 64..]) as usize;
+        let evaluated = read_u64(&bytes[pos + 72..]) as usize;
+        let mut seed = [0u8; 16];
+        seed.copy_from_slice(&bytes[pos + 80..pos + 96]);
+        pos += 96;
+        let mut msgs = Vec::with_capacity(2);
+        for _ in 0..2 {
+            if bytes.len() - pos < 8 {
+                return None;
+            }
+            let len = read_u64(&bytes[pos..]) as usize;
+            pos += 8;
+            if bytes.len() - pos < len {
+                return None;
+            }
+            msgs.push(bytes[pos..pos + len].to_vec());
+            pos += len;
+        }
+        if pos != bytes.len() {
+            return None;
+        }
+        let evaluator = msgs.pop()?;
+        let garbler = msgs.pop()?;
+        Some(Certificate {
+            transcript,
+            signature,
+            instance,
+            ot: OtView {
+                seed: Block::from(seed),
+                evaluated,
+                garbler,
+                evaluator,
+            },
+        })
+    }
+}
+
+// The length and running hash of what an instance sent.
+#[derive(Clone)]
+struct Sent {
+    len: u64,
+    hash: Sha256,
+}
+
+impl Sent {
+    fn new() -> Self {
+        Sent {
+            len: 0,
+            hash: Sha256::new(),
+        }
+    }
+
+    fn input(&mut self, bytes: &[u8]) {
+        self.len += bytes.len() as u64;
+        self.hash.input(bytes);
+    }
+
+    fn digest(&self) -> (u64, [u8; 32]) {
+        (self.len, digest(self.hash.clone()))
+    }
+}
+
+// Check instances are regarbled into a buffer and compared with the channel.
+type LocalChannel = Channel<std::io::Empty, Vec<u8>>;
+
+/// Regarbles one instance from its seed, as the evaluator does for check
+/// instances, for verifying a `Certificate`.
+///
+/// It implements `Fancy`, `FancyInput` and `FancyReveal` from the evaluator's
+/// point of view: `receive` stands for the garbler's inputs and `encode` for
+/// ours.
+pub struct Replay {
+    garbler: Gb<LocalChannel, AesRng>,
+    written: Rc<RefCell<Vec<u8>>>,
+    sent: Sent,
+    // What the instance sent at each signature of the garbler.
+    checkpoints: Vec<(u64, [u8; 32])>,
+    input_shares: usize,
+}
+
+impl Replay {
+    fn new(seed: Block, input_shares: usize) -> Self {
+        let local = Channel::new(std::io::empty(), Vec::new());
+        let written = local.clone().writer();
+        let sent = Sent::new();
+        Replay {
+            garbler: Gb::new(local, AesRng::from_seed(seed)),
+            written,
+            checkpoints: vec![sent.digest()],
+            sent,
+            input_shares,
+        }
+    }
+
+    // Take what the instance sent since the last call, and hash it.
+    fn take_written(&mut self) -> Vec<u8> {
+        let bytes = std::mem::take(&mut *self.written.borrow_mut());
+        self.sent.input(&bytes);
+        bytes
+    }
+
+    fn flush(&mut self) -> Result<(), TwopacError> {
+        self.garbler.flush().map_err(TwopacError::from)
+    }
+
+    // Record what the instance sent when the garbler signs its transcript.
+    fn checkpoint(&mut self) -> Result<(), TwopacError> {
+        self.flush()?;
+        self.take_written();
+        self.checkpoints.push(self.sent.digest());
+        Ok(())
+    }
+}
+
+impl FancyInput for Replay {
+    type Item = Wire;
+    type Error = TwopacError;
+
+    fn encode_many(&mut self, _values: &[u16], moduli: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
+        Ok(moduli
+            .iter()
+            .map(|q| {
+                let weights = input_weights(q);
+                weights
+                    .iter()
+                    .cycle()
+                    .take(self.input_shares * weights.len())
+                    .fold(Wire::zero(q), |wire, c| {
+                        let (zero, _) = self.garbler.encode_wire(0, q);
+                        wire.plus(&zero.cmul_big(*c))
+                    })
+            })
+            .collect())
+    }
+
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
+        Ok(moduli
+            .iter()
+            .map(|q| self.garbler.encode_wire(0, q).0)
+            .collect())
+    }
+}
+
+impl Fancy for Replay {
+    type Item = Wire;
+    type Error = TwopacError;
+
+    fn constant(&mut self, x: u16, q: &Modulus) -> Result<Wire, TwopacError> {
+        Ok(self.garbler.constant(x, q)?)
+    }
+
+    fn add(&mut self, x: &Wire, y: &Wire) -> Result<Wire, TwopacError> {
+        Ok(self.garbler.add(x, y)?)
+    }
+
+    fn sub(&mut self, x: &Wire, y: &Wire) -> Result<Wire, TwopacError> {
+        Ok(self.garbler.sub(x, y)?)
+    }
+
+    fn cmul(&mut self, x: &Wire, c: u16) -> Result<Wire, TwopacError> {
+        Ok(self.garbler.cmul(x, c)?)
+    }
+
+    fn mul(&mut self, x: &Wire, y: &Wire) -> Result<Wire, TwopacError> {
+        Ok(self.garbler.mul(x, y)?)
+    }
+
+    fn proj(&mut self, x: &Wire, q: &Modulus, tt: Option<Vec<u16>>) -> Result<Wire, TwopacError> {
+        Ok(self.garbler.proj(x, q, tt)?)
+    }
+
+    fn output(&mut self, x: &Wire) -> Result<Option<u16>, TwopacError> {
+        Ok(self.garbler.output(x)?)
+    }
+
+    fn constant_big(&mut self, x: u64, q: &Modulus) -> Result<Wire, TwopacError> {
+        Ok(self.garbler.constant_big(x, q)?)
+    }
+
+    fn cmul_big(&mut self, x: &Wire, c: u64) -> Result<Wire, TwopacError> {
+        Ok(self.garbler.cmul_big(x, c)?)
+    }
+
+    fn output_big(&mut self, x: &Wire) -> Result<Option<u64>, TwopacError> {
+        Ok(self.garbler.output_big(x)?)
+    }
+}
+
+impl FancyReveal for Replay {
+    fn reveal(&mut self, x: &Wire) -> Result<u16, TwopacError> {
+        self.garbler.output(x)?;
+        self.checkpoint()?;
+        Ok(0)
+    }
+}
+
+// A channel which hashes everything read from or written to it.
+struct HashingChannel<C> {
+    channel: C,
+    sent: Rc<RefCell<Sent>>,
+}
+
+impl<C: AbstractChannel> HashingChannel<C> {
+    fn new(channel: C) -> Self {
+        HashingChannel {
+            channel,
+            sent: Rc::new(RefCell::new(Sent::new())),
+        }
+    }
+
+    fn digest(&self) -> (u64, [u8; 32]) {
+        self.sent.borrow().digest()
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for HashingChannel<C> {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        self.channel.read_bytes(bytes)?;
+        self.sent.borrow_mut().input(bytes);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.sent.borrow_mut().input(bytes);
+        self.channel.write_bytes(bytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        HashingChannel {
+            channel: self.channel.clone(),
+            sent: self.sent.clone(),
+        }
+    }
+}
+
+// A channel which records everything read from or written to it, for the OT
+// sending the seeds.
+struct RecordingChannel<C> {
+    channel: C,
+    read: Rc<RefCell<Vec<u8>>>,
+    written: Rc<RefCell<Vec<u8>>>,
+}
+
+impl<C: AbstractChannel> RecordingChannel<C> {
+    fn new(channel: C) -> Self {
+        RecordingChannel {
+            channel,
+            read: Rc::new(RefCell::new(Vec::new())),
+            written: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    // Take what was read and written so far.
+    fn take(&self) -> (Vec<u8>, Vec<u8>) {
+        (
+            std::mem::take(&mut *self.read.borrow_mut()),
+            std::mem::take(&mut *self.written.borrow_mut()),
+        )
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for RecordingChannel<C> {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        self.channel.read_bytes(bytes)?;
+        self.read.borrow_mut().extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.written.borrow_mut().extend_from_slice(bytes);
+        self.channel.write_bytes(bytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        RecordingChannel {
+            channel: self.channel.clone(),
+            read: self.read.clone(),
+            written: self.written.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dummy::Dummy, util::RngExt, CrtBundle, CrtGadgets};
+    use itertools::Itertools;
+    use ocelot::ot::{ChouOrlandiReceiver, ChouOrlandiSender};
+    use scuttlebutt::{unix_channel_pair, UnixChannel};
+
+    type Gb = Garbler<UnixChannel, AesRng, ChouOrlandiSender>;
+    type Ev = Evaluator<UnixChannel, AesRng, ChouOrlandiReceiver>;
+
+    fn relu<F: Fancy>(b: &mut F, xs: &[CrtBundle<F::Item>]) -> Option<Vec<u128>> {
+        let mut outputs = Vec::new();
+        for x in xs.iter() {
+            let z = b.crt_relu(x, "100%", None).unwrap();
+            outputs.push(b.crt_output(&z).unwrap());
+        }
+        outputs.into_iter().collect()
+    }
+
+    #[test]
+    fn test_relu() {
+        let mut rng = rand::thread_rng();
+        let n = 3;
+        let ps = crate::util::primes_with_width(8);
+        let q = crate::util::product(&ps);
+        let input = (0..n).map(|_| rng.gen_u128() % q).collect::<Vec<u128>>();
+
+        let mut dummy = Dummy::new();
+        let dummy_input = input
+            .iter()
+            .map(|x| dummy.crt_encode(*x, q).unwrap())
+            .collect_vec();
+        let target = relu(&mut dummy, &dummy_input).unwrap();
+
+        let key = SigningKey::generate(&mut rng);
+        let vk = key.verifying_key();
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Gb::new(sender, AesRng::new(), key).unwrap();
+            let xs = gb.crt_receive_many(n, q).unwrap();
+            relu(&mut gb, &xs);
+            gb.finish().unwrap();
+        });
+        let mut ev = Ev::new(receiver, AesRng::new(), vk).unwrap();
+        let xs = ev.crt_encode_many(&input, q).unwrap();
+        let result = relu(&mut ev, &xs).unwrap();
+        assert!(ev.finish().unwrap().is_none());
+        assert_eq!(target, result);
+        handle.join().unwrap();
+    }
+
+    fn circuit<F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Error>>(
+        f: &mut F,
+        tt: Vec<u16>,
+    ) -> Result<(), <F as Fancy>::Error> {
+        let q = Modulus::Zq { q: 3 };
+        let x = f.receive(&q)?;
+        let y = f.encode(2, &q)?;
+        let z = f.mul(&x, &y)?;
+        let z = f.proj(&z, &q, Some(tt))?;
+        f.output(&z)?;
+        Ok(())
+    }
+
+    // A certificate from a garbler which garbles the wrong table in `circuit`.
+    fn cheat() -> (Certificate, VerifyingKey) {
+        let key = SigningKey::generate(&mut rand::thread_rng());
+        let vk = key.verifying_key();
+        let q = Modulus::Zq { q: 3 };
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Gb::new(sender, AesRng::new(), key).unwrap();
+            let x = gb.encode(1, &q).unwrap();
+            let y = gb.receive(&q).unwrap();
+            let z = gb.mul(&x, &y).unwrap();
+            // garble a different table than the one agreed on
+            let z = gb.proj(&z, &q, Some(vec![2, 1, 0])).unwrap();
+            gb.output(&z).unwrap();
+            gb.finish().unwrap();
+        });
+        let mut ev = Ev::new(receiver, AesRng::new(), vk).unwrap();
+        circuit(&mut ev, vec![0, 1, 2]).unwrap();
+        let certificate = ev.finish().unwrap().expect("cheating was not detected");
+        handle.join().unwrap();
+        (certificate, vk)
+    }
+
+    #[test]
+    fn test_certificate() {
+        let mut rng = rand::thread_rng();
+        let (certificate, vk) = cheat();
+        let certificate = Certificate::from_bytes(&certificate.to_bytes()).unwrap();
+        let honest = |r: &mut Replay| circuit(r, vec![0, 1, 2]);
+        assert!(certificate.verify::<ChouOrlandiReceiver, _>(&vk, honest));
+        // the garbler did garble this table
+        assert!(!certificate.verify::<ChouOrlandiReceiver, _>(&vk, |r| circuit(r, vec![2, 1, 0])));
+        let other = SigningKey::generate(&mut rng).verifying_key();
+        assert!(!certificate.verify::<ChouOrlandiReceiver, _>(&other, honest));
+        // the seed comes from the evaluator's side of the OT, which must match
+        // the messages the garbler signed
+        let mut forged = certificate.clone();
+        forged.ot.seed = rng.gen::<Block>();
+        assert!(!forged.verify::<ChouOrlandiReceiver, _>(&vk, honest));
+        let mut forged = certificate.clone();
+        forged.instance = forged.ot.evaluated;
+        assert!(!forged.verify::<ChouOrlandiReceiver, _>(&vk, honest));
+    }
+
+    #[test]
+    fn test_tampered_transcript() {
+        let (certificate, vk) = cheat();
+        let honest = |r: &mut Replay| circuit(r, vec![0, 1, 2]);
+        let mut forged = certificate.clone();
+        forged.transcript.round += 1;
+        assert!(!forged.verify::<ChouOrlandiReceiver, _>(&vk, honest));
+        let mut forged = certificate.clone();
+        forged.transcript.sent[certificate.instance].1[0] ^= 1;
+        assert!(!forged.verify::<ChouOrlandiReceiver, _>(&vk, honest));
+
+        // flipping any bit of the signed transcript or of the signature
+        let bytes = certificate.to_bytes();
+        for i in 0..certificate.transcript.to_bytes().len() + 64 {
+            for bit in 0..8 {
+                let mut bytes = bytes.clone();
+                bytes[i] ^= 1 << bit;
+                if let Some(forged) = Certificate::from_bytes(&bytes) {
+                    assert!(!forged.verify::<ChouOrlandiReceiver, _>(&vk, honest));
+                }
+            }
+        }
+    }
+
+    fn revealing<F>(f: &mut F, tt: Vec<u16>) -> Result<(), <F as Fancy>::Error>
+    where
+        F: FancyReveal + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Error>,
+    {
+        let q = Modulus::Zq { q: 3 };
+        let x = f.receive(&q)?;
+        let y = f.encode(2, &q)?;
+        let z = f.proj(&x, &q, Some(tt))?;
+        f.reveal(&z)?;
+        let z = f.mul(&z, &y)?;
+        f.output(&z)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_certificate_before_reveal() {
+        let mut rng = rand::thread_rng();
+        let key = SigningKey::generate(&mut rng);
+        let vk = key.verifying_key();
+        let q = Modulus::Zq { q: 3 };
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Gb::new(sender, AesRng::new(), key).unwrap();
+            let x = gb.encode(1, &q).unwrap();
+            let y = gb.receive(&q).unwrap();
+            // garble a different table than the one agreed on
+            let z = gb.proj(&x, &q, Some(vec![2, 1, 0])).unwrap();
+            gb.reveal(&z).unwrap();
+            let z = gb.mul(&z, &y).unwrap();
+            gb.output(&z).unwrap();
+            gb.finish().unwrap();
+        });
+        let mut ev = Ev::new(receiver, AesRng::new(), vk).unwrap();
+        revealing(&mut ev, vec![0, 1, 2]).unwrap();
+        let certificate = ev.finish().unwrap().expect("cheating was not detected");
+        handle.join().unwrap();
+
+        // the transcript was signed before the evaluator revealed anything
+        assert_eq!(certificate.transcript.round, 1);
+        let verify = |tt: Vec<u16>| {
+            certificate.verify::<ChouOrlandiReceiver, _>(&vk, |r| revealing(r, tt))
+        };
+        assert!(verify(vec![0, 1, 2]));
+        assert!(!verify(vec![2, 1, 0]));
+    }
+}
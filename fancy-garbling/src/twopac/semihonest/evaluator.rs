@@ -4,34 +4,80 @@
 // Copyright © 2019 Galois, Inc.
 // See LICENSE for licensing information.
 
-use crate::{errors::TwopacError, Evaluator as Ev, Fancy, FancyInput, FancyReveal, Wire, Modulus};
-use ocelot::ot::Receiver as OtReceiver;
+use crate::{
+    circuit::SubCircuit,
+    errors::{FancyError, TwopacError},
+    role::{self, HasRole},
+    Evaluator as Ev,
+    Fancy,
+    FancyBranch,
+    FancyInput,
+    FancyReveal,
+    HasModulus,
+    Wire,
+    Modulus,
+};
+use ocelot::{
+    oprf::{KkrtReceiver, Receiver as OprfReceiver},
+    ot::{AlszReceiver, Receiver as OtReceiver},
+};
 use rand::{CryptoRng, Rng};
-use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+use scuttlebutt::{AbstractChannel, Block, Block512, SemiHonest};
+use std::collections::VecDeque;
 
 /// Semi-honest evaluator.
-pub struct Evaluator<C, RNG, OT> {
+///
+/// Our inputs are obtained with `OT`, by default ALSZ OT extension, whose base
+/// OTs run once in `new`.
+pub struct Evaluator<C, RNG, OT = AlszReceiver> {
     evaluator: Ev<C>,
     channel: C,
     ot: OT,
     rng: RNG,
+    use_n_ot: bool,
+    n_ot: Option<KkrtReceiver>,
+    committed_outputs: usize,
+    output_commitments: VecDeque<(usize, Vec<[u8; 32]>)>,
 }
 
-impl<C, RNG, OT> Evaluator<C, RNG, OT> {}
-
 impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + SemiHonest>
     Evaluator<C, RNG, OT>
 {
     /// Make a new `Evaluator`.
     pub fn new(mut channel: C, mut rng: RNG) -> Result<Self, TwopacError> {
         let ot = OT::init(&mut channel, &mut rng)?;
+        Ok(Self::with_ot(channel, rng, ot))
+    }
+
+    /// Make a new `Evaluator` reusing an initialized OT receiver, such as the
+    /// one returned by `into_ot` at the end of a previous session on `channel`.
+    /// This skips the base OTs of OT extension.
+    pub fn with_ot(channel: C, rng: RNG, ot: OT) -> Self {
         let evaluator = Ev::new(channel.clone());
-        Ok(Self {
+        Self {
             evaluator,
             channel,
             ot,
             rng,
-        })
+            use_n_ot: false,
+            n_ot: None,
+            committed_outputs: 0,
+            output_commitments: VecDeque::new(),
+        }
+    }
+
+    /// Obtain our inputs in `GF4` and `GF8` with one 1-out-of-N OT each,
+    /// instead of one OT per bit. The garbler must do the same, see
+    /// `Garbler::with_n_ot`.
+    pub fn with_n_ot(mut self) -> Self {
+        self.use_n_ot = true;
+        self
+    }
+
+    /// Consume the `Evaluator`, returning its OT receiver for use in another
+    /// session with the same garbler.
+    pub fn into_ot(self) -> OT {
+        self.ot
     }
 
     /// Get a reference to the internal channel.
@@ -39,6 +85,75 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + Sem
         &mut self.channel
     }
 
+    /// Receive the garbler's commitments to the decoding of the outputs `xs`,
+    /// see `Garbler::commit_outputs`.
+    pub fn receive_output_commitments(&mut self, xs: &[Wire]) -> Result<(), TwopacError> {
+        self.evaluator.align();
+        for x in xs.iter() {
+            let q = x.modulus();
+            if let Modulus::ZqBig { .. } = q {
+                return Err(TwopacError::FancyError(FancyError::InvalidArg(format!(
+                    "cannot commit to outputs of {}",
+                    q
+                ))));
+            }
+            let mut commitment = vec![[0u8; 32]; q.order() as usize];
+            for c in commitment.iter_mut() {
+                self.channel.read_bytes(c)?;
+            }
+            self.output_commitments
+                .push_back((self.committed_outputs, commitment));
+            self.committed_outputs += 1;
+        }
+        Ok(())
+    }
+
+    /// Output `x`, checking that the garbler decodes it as it committed to
+    /// before any output was decoded.
+    ///
+    /// Outputs are checked against the commitments in the order they were
+    /// received. Our label of `x` must match the commitment to the value it
+    /// decodes to, and no other, otherwise the garbler has changed the
+    /// decoding since committing and this returns
+    /// `TwopacError::CheatingDetected`.
+    ///
+    /// This does not verify the output. The commitments bind the garbler only
+    /// to what it chose to commit to, and nothing ties them to the circuit: a
+    /// malicious garbler can commit to a permuted decoding from the start, or
+    /// garble a different circuit, and go undetected. What this rules out is a
+    /// garbler changing the decoding of an output after committing, e.g.
+    /// depending on outputs revealed to it in the meantime.
+    pub fn committed_output(&mut self, x: &Wire) -> Result<u16, TwopacError> {
+        let (i, commitment) = self.output_commitments.pop_front().ok_or_else(|| {
+            TwopacError::FancyError(FancyError::InvalidArg(
+                "no output commitment left".to_string(),
+            ))
+        })?;
+        if commitment.len() != x.modulus().order() as usize {
+            return Err(TwopacError::FancyError(FancyError::InvalidArg(format!(
+                "output {} was committed with another modulus",
+                i
+            ))));
+        }
+        let val = self
+            .evaluator
+            .output(x)?
+            .expect("Evaluator always outputs Some(u16)");
+        let matches = commitment
+            .iter()
+            .enumerate()
+            .filter(|(k, c)| super::commit_output(i, *k as u16, x) == **c)
+            .map(|(k, _)| k as u16)
+            .collect::<Vec<u16>>();
+        if matches != [val] {
+            return Err(TwopacError::CheatingDetected(format!(
+                "output {} decodes to {}, but its commitment opens to {:?}",
+                i, val, matches
+            )));
+        }
+        Ok(val)
+    }
+
     fn run_ot(&mut self, inputs: &[bool]) -> Result<Vec<Block>, TwopacError> {
         self.ot
             .receive(&mut self.channel, &inputs, &mut self.rng)
@@ -58,41 +173,117 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + Sem
         Ok(w)
     }
 
-    /// Receive garbler input wires.
+    /// Receive garbler input wires, sent together in one message.
     fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
-        moduli.iter().map(|modulus| self.receive(modulus)).collect()
+        let ws = self.evaluator.read_wires(moduli)?;
+        Ok(ws)
     }
 
     /// Perform OT and obtain wires for the evaluator's inputs.
     fn encode_many(&mut self, inputs: &[u16], moduli: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
         let mut lens = Vec::new();
         let mut bs = Vec::new();
+        let mut n_ot_inputs = Vec::new();
         for (x, q) in inputs.iter().zip(moduli.iter()) {
-            let len = q.bit_length();
-            for b in (0..len).map(|i| x & (1 << i) != 0) {
-                bs.push(b);
+            if self.use_n_ot && super::n_ot_size(q).is_some() {
+                n_ot_inputs.push((*x, *q));
+                lens.push(None);
+                continue;
             }
-            lens.push(len);
+            let bits = super::input_bits(u64::from(*x), q);
+            lens.push(Some(bits.len()));
+            bs.extend(bits);
         }
-        let wires = self.run_ot(&bs)?;
+        self.evaluator.align();
+        // Send our OPRF queries and OT extension matrix before waiting for the
+        // garbler, so that the whole call takes one round trip.
+        let outputs = self.n_ot_query(&n_ot_inputs)?;
+        let wires = if bs.is_empty() {
+            Vec::new()
+        } else {
+            self.run_ot(&bs)?
+        };
+        let mut n_ot_wires = self.receive_n_ot(&n_ot_inputs, outputs)?.into_iter();
         let mut start = 0;
         Ok(lens
             .into_iter()
             .zip(moduli.iter())
-            .map(|(len, q)| {
-                let range = start..start + len;
-                let chunk = &wires[range];
-                start += len;
-                combine(chunk, q)
+            .map(|(len, q)| match len {
+                Some(len) => {
+                    let range = start..start + len;
+                    let chunk = &wires[range];
+                    start += len;
+                    combine(chunk, q)
+                }
+                None => n_ot_wires.next().unwrap(),
             })
             .collect::<Vec<Wire>>())
     }
 }
 
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> Evaluator<C, RNG, OT> {
+    // Query the OPRF of 1-out-of-N OT on the value of each input.
+    fn n_ot_query(&mut self, inputs: &[(u16, Modulus)]) -> Result<Vec<Block512>, TwopacError> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.n_ot.is_none() {
+            self.n_ot = Some(KkrtReceiver::init(&mut self.channel, &mut self.rng)?);
+        }
+        let values = inputs
+            .iter()
+            .map(|(x, _)| Block::from(u128::from(*x)))
+            .collect::<Vec<Block>>();
+        let outputs = self.n_ot.as_mut().unwrap().receive(
+            &mut self.channel,
+            &values,
+            &mut self.rng,
+        )?;
+        Ok(outputs)
+    }
+
+    // Decrypt the label of each input's value among those sent by
+    // `Garbler::send_n_ot`, using the OPRF `outputs` of the values.
+    fn receive_n_ot(
+        &mut self,
+        inputs: &[(u16, Modulus)],
+        outputs: Vec<Block512>,
+    ) -> Result<Vec<Wire>, TwopacError> {
+        let mut wires = Vec::with_capacity(inputs.len());
+        for ((x, q), output) in inputs.iter().zip(outputs) {
+            let cts = self.channel.read_blocks(q.size() as usize)?;
+            let label = cts[*x as usize] ^ super::n_ot_pad(output);
+            wires.push(Wire::from_block(label, q));
+        }
+        Ok(wires)
+    }
+
+    /// Evaluate the next circuit of a `Session`, translating the wires `xs`
+    /// carried over from the previous one, see `Garbler::translate`.
+    pub(crate) fn translate(&mut self, xs: &[Wire], run: usize) -> Result<Vec<Wire>, TwopacError> {
+        self.evaluator.align();
+        let mut wires = Vec::with_capacity(xs.len());
+        for (i, x) in xs.iter().enumerate() {
+            let q = x.modulus();
+            if let Modulus::ZqBig { .. } = q {
+                return Err(TwopacError::FancyError(FancyError::InvalidArg(format!(
+                    "cannot carry over a wire of {}",
+                    q
+                ))));
+            }
+            let table = self.channel.read_blocks(q.order() as usize)?;
+            let ct = table[x.color() as usize] ^ x.hash(super::translation_tweak(run, i));
+            wires.push(Wire::from_block(ct, &q));
+        }
+        Ok(wires)
+    }
+}
+
 fn combine(wires: &[Block], q: &Modulus) -> Wire {
-    wires.iter().enumerate().fold(Wire::zero(q), |acc, (i, w)| {
+    let weights = super::input_weights(q);
+    wires.iter().zip(weights).fold(Wire::zero(q), |acc, (w, c)| {
         let w = Wire::from_block(*w, q);
-        acc.plus(&w.cmul(1 << i))
+        acc.plus(&w.cmul_big(c))
     })
 }
 
@@ -127,6 +318,18 @@ impl<C: AbstractChannel, RNG, OT> Fancy for Evaluator<C, RNG, OT> {
     fn output(&mut self, x: &Wire) -> Result<Option<u16>, Self::Error> {
         self.evaluator.output(&x).map_err(Self::Error::from)
     }
+
+    fn constant_big(&mut self, x: u64, q: &Modulus) -> Result<Self::Item, Self::Error> {
+        self.evaluator.constant_big(x, q).map_err(Self::Error::from)
+    }
+
+    fn cmul_big(&mut self, x: &Wire, c: u64) -> Result<Self::Item, Self::Error> {
+        self.evaluator.cmul_big(x, c).map_err(Self::Error::from)
+    }
+
+    fn output_big(&mut self, x: &Wire) -> Result<Option<u64>, Self::Error> {
+        self.evaluator.output_big(x).map_err(Self::Error::from)
+    }
 }
 
 impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyReveal for Evaluator<C, RNG, OT> {
@@ -135,4 +338,19 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyReveal for Evaluator<C,
     }
 }
 
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyBranch for Evaluator<C, RNG, OT> {
+    fn branch(
+        &mut self,
+        cond: &Wire,
+        branches: &[SubCircuit],
+        inputs: &[Wire],
+    ) -> Result<Vec<Wire>, Self::Error> {
+        self.evaluator.branch(cond, branches, inputs).map_err(Self::Error::from)
+    }
+}
+
+impl<C, RNG, OT> HasRole for Evaluator<C, RNG, OT> {
+    type Role = role::Evaluator;
+}
+
 impl<C: AbstractChannel, RNG, OT> SemiHonest for Evaluator<C, RNG, OT> {}
@@ -4,17 +4,38 @@
 // Copyright © 2019 Galois, Inc.
 // See LICENSE for licensing information.
 
-use crate::{errors::TwopacError, Fancy, FancyInput, FancyReveal, Garbler as Gb, Wire, Modulus};
-use ocelot::ot::Sender as OtSender;
+use crate::{
+    circuit::SubCircuit,
+    errors::{FancyError, TwopacError},
+    role::{self, HasRole},
+    Fancy,
+    FancyBranch,
+    FancyInput,
+    FancyReveal,
+    Garbler as Gb,
+    HasModulus,
+    Wire,
+    Modulus,
+};
+use ocelot::{
+    oprf::{KkrtSender, Sender as OprfSender},
+    ot::{AlszSender, Sender as OtSender},
+};
 use rand::{CryptoRng, Rng, SeedableRng};
-use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+use scuttlebutt::{AbstractChannel, Block, Block512, SemiHonest};
 
 /// Semi-honest garbler.
-pub struct Garbler<C, RNG, OT> {
+///
+/// The evaluator's inputs are transferred with `OT`, by default ALSZ OT
+/// extension, whose base OTs run once in `new`.
+pub struct Garbler<C, RNG, OT = AlszSender> {
     garbler: Gb<C, RNG>,
     channel: C,
     ot: OT,
     rng: RNG,
+    use_n_ot: bool,
+    n_ot: Option<KkrtSender>,
+    committed_outputs: usize,
 }
 
 impl<C, OT, RNG> std::ops::Deref for Garbler<C, RNG, OT> {
@@ -39,14 +60,40 @@ impl<
     /// Make a new `Garbler`.
     pub fn new(mut channel: C, mut rng: RNG) -> Result<Self, TwopacError> {
         let ot = OT::init(&mut channel, &mut rng)?;
+        Ok(Self::with_ot(channel, rng, ot))
+    }
 
+    /// Make a new `Garbler` reusing an initialized OT sender, such as the one
+    /// returned by `into_ot` at the end of a previous session on `channel`.
+    /// This skips the base OTs of OT extension.
+    pub fn with_ot(channel: C, mut rng: RNG, ot: OT) -> Self {
         let garbler = Gb::new(channel.clone(), RNG::from_seed(rng.gen()));
-        Ok(Garbler {
+        Garbler {
             garbler,
             channel,
             ot,
             rng,
-        })
+            use_n_ot: false,
+            n_ot: None,
+            committed_outputs: 0,
+        }
+    }
+
+    /// Transfer evaluator inputs in `GF4` and `GF8` with one 1-out-of-N OT
+    /// each, instead of one OT per bit. The evaluator must do the same.
+    ///
+    /// This needs a single round of OT extension no matter how many bits the
+    /// inputs have, but sends a ciphertext for each of the `N` field elements.
+    /// The OPRF it is built from is set up on first use.
+    pub fn with_n_ot(mut self) -> Self {
+        self.use_n_ot = true;
+        self
+    }
+
+    /// Consume the `Garbler`, returning its OT sender for use in another
+    /// session with the same evaluator.
+    pub fn into_ot(self) -> OT {
+        self.ot
     }
 
     /// Get a reference to the internal channel.
@@ -54,19 +101,47 @@ impl<
         &mut self.channel
     }
 
+    /// Commit to the decoding of the outputs `xs` before any of them is
+    /// output, by sending a SHA3-256 hash of the label of each of their values.
+    ///
+    /// The evaluator receives these with
+    /// `Evaluator::receive_output_commitments`, and then checks that every
+    /// output decodes consistently with them using
+    /// `Evaluator::committed_output`, while we `output` each of `xs` in the same
+    /// order. See there for what
+    /// this does and does not guarantee.
+    pub fn commit_outputs(&mut self, xs: &[Wire]) -> Result<(), TwopacError> {
+        self.garbler.flush()?;
+        for x in xs.iter() {
+            let q = x.modulus();
+            if let Modulus::ZqBig { .. } = q {
+                return Err(TwopacError::FancyError(FancyError::InvalidArg(format!(
+                    "cannot commit to outputs of {}",
+                    q
+                ))));
+            }
+            let delta = self.garbler.delta(&q);
+            let i = self.committed_outputs;
+            // GF16 has 2^16 elements, so count in u32
+            for k in 0..q.order() {
+                let label = x.plus(&delta.cmul(k as u16));
+                self.channel
+                    .write_bytes(&super::commit_output(i, k as u16, &label))?;
+            }
+            self.committed_outputs += 1;
+        }
+        self.channel.flush()?;
+        Ok(())
+    }
+
     fn _evaluator_input(&mut self, delta: &Wire, modulus: &Modulus) -> (Wire, Vec<(Block, Block)>) {
-        let len = match modulus {
-            Modulus::Zq { q: qq } => f32::from(*qq).log(2.0).ceil() as u16,
-            Modulus::GF4 { .. } => 4,
-            Modulus::GF8 { .. } => 8,
-            Modulus::GFk { k, .. } => (*k).into(), 
-        };
         let mut wire = Wire::zero(modulus);
-        let inputs = (0..len)
-            .map(|i| {
+        let inputs = super::input_weights(modulus)
+            .into_iter()
+            .map(|c| {
                 let zero = Wire::rand(&mut self.rng, modulus);
                 let one = zero.plus(&delta);
-                wire = wire.plus(&zero.cmul(1 << i));   // see 7.1 in paper for binary representation labels
+                wire = wire.plus(&zero.cmul_big(c));   // see 7.1 in paper for binary representation labels
                 (zero.as_block(), one.as_block())
             })
             .collect::<Vec<(Block, Block)>>();
@@ -84,41 +159,125 @@ impl<
     type Error = TwopacError;
 
     fn encode(&mut self, val: u16, modulus: &Modulus) -> Result<Wire, TwopacError> {
-        let (mine, theirs) = self.garbler.encode_wire(val, modulus);
-        self.garbler.send_wire(&theirs)?;
-        self.channel.flush()?;
-        Ok(mine)
+        let mut ws = self.encode_many(&[val], &[*modulus])?;
+        Ok(ws.remove(0))
     }
 
+    /// Encode our inputs, sending all their labels to the evaluator in one
+    /// message.
     fn encode_many(&mut self, vals: &[u16], moduli: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
-        let ws = vals
-            .iter()
-            .zip(moduli.iter())
-            .map(|(x, q)| {
-                let (mine, theirs) = self.garbler.encode_wire(*x, q);
-                self.garbler.send_wire(&theirs)?;
-                Ok(mine)
-            })
-            .collect();
-        self.channel.flush()?;
-        ws
+        let (mine, theirs) = self.garbler.encode_many_wires(vals, moduli)?;
+        self.garbler.send_wires(&theirs)?;
+        self.garbler.flush()?;
+        Ok(mine)
     }
 
+    /// Transfer the evaluator's inputs with a single round of OT for the whole
+    /// call, whether they use 1-out-of-2 OT, 1-out-of-N OT or both.
     fn receive_many(&mut self, ms: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
-        let n = ms.len();
-        let lens = ms.iter().map(|q| q.bit_length());
-        let mut wires = Vec::with_capacity(n);
-        let mut inputs = Vec::with_capacity(lens.sum());
+        let mut wires = Vec::with_capacity(ms.len());
+        let mut inputs = Vec::new();
+        let mut n_ot_moduli = Vec::new();
 
         for q in ms.iter() {
+            if self.use_n_ot && super::n_ot_size(q).is_some() {
+                n_ot_moduli.push(*q);
+                wires.push(None);
+                continue;
+            }
             let delta = self.garbler.delta(q);
             let (wire, input) = self._evaluator_input(&delta, q);
-            wires.push(wire);
-            for i in input.into_iter() {
-                inputs.push(i);
+            wires.push(Some(wire));
+            inputs.extend(input);
+        }
+        self.garbler.flush()?;
+        // The evaluator sends its OPRF queries and OT extension matrix before
+        // reading anything, and we answer both at once.
+        let seeds = self.n_ot_seeds(n_ot_moduli.len())?;
+        if !inputs.is_empty() {
+            self.ot.send(&mut self.channel, &inputs, &mut self.rng)?;
+        }
+        let mut n_ot_wires = self.send_n_ot(&n_ot_moduli, seeds)?.into_iter();
+        self.channel.flush()?;
+        Ok(wires
+            .into_iter()
+            .map(|w| w.or_else(|| n_ot_wires.next()).unwrap())
+            .collect())
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> Garbler<C, RNG, OT> {
+    // Answer `n` OPRF queries of the evaluator for 1-out-of-N OT.
+    fn n_ot_seeds(&mut self, n: usize) -> Result<Vec<Block512>, TwopacError> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        if self.n_ot.is_none() {
+            self.n_ot = Some(KkrtSender::init(&mut self.channel, &mut self.rng)?);
+        }
+        let oprf = self.n_ot.as_mut().unwrap();
+        Ok(oprf.send(&mut self.channel, n, &mut self.rng)?)
+    }
+
+    // Send the labels of all values of each input in `moduli`, each encrypted
+    // under the OPRF of its value with `seeds`, so that the evaluator can
+    // decrypt just one.
+    fn send_n_ot(
+        &mut self,
+        moduli: &[Modulus],
+        seeds: Vec<Block512>,
+    ) -> Result<Vec<Wire>, TwopacError> {
+        let mut wires = Vec::with_capacity(moduli.len());
+        for (seed, q) in seeds.into_iter().zip(moduli.iter()) {
+            let oprf = self.n_ot.as_ref().unwrap();
+            let delta = self.garbler.delta(q);
+            let zero = Wire::rand(&mut self.rng, q);
+            for j in 0..q.size() {
+                let label = zero.plus(&delta.cmul(j));
+                let pad = super::n_ot_pad(oprf.compute(seed, Block::from(u128::from(j))));
+                self.channel.write_block(&(label.as_block() ^ pad))?;
+            }
+            wires.push(zero);
+        }
+        Ok(wires)
+    }
+
+    /// Garble the next circuit of a `Session` under fresh deltas, carrying the
+    /// wires `xs` over from the previous one.
+    ///
+    /// For each wire we send a table translating the label of each value under
+    /// the old delta to its label under the new one, ordered by the colors of
+    /// the old labels.
+    pub(crate) fn translate(&mut self, xs: &[Wire], run: usize) -> Result<Vec<Wire>, TwopacError> {
+        self.garbler.flush()?;
+        let old = self.garbler.refresh_deltas();
+        let mut wires = Vec::with_capacity(xs.len());
+        for (i, x) in xs.iter().enumerate() {
+            let q = x.modulus();
+            let old_delta = match (q, old.get(&q)) {
+                (Modulus::ZqBig { .. }, _) | (_, None) => {
+                    return Err(TwopacError::FancyError(FancyError::InvalidArg(format!(
+                        "cannot carry over a wire of {}",
+                        q
+                    ))))
+                }
+                (_, Some(delta)) => delta,
+            };
+            let delta = self.garbler.delta(&q);
+            let zero = Wire::rand(&mut self.rng, &q);
+            let tweak = super::translation_tweak(run, i);
+            let mut table = vec![Block::default(); q.order() as usize];
+            for k in 0..q.order() {
+                let from = x.plus(&old_delta.cmul(k as u16));
+                let to = zero.plus(&delta.cmul(k as u16));
+                table[from.color() as usize] = from.hash(tweak) ^ to.as_block();
+            }
+            for ct in table.iter() {
+                self.channel.write_block(ct)?;
             }
+            wires.push(zero);
         }
-        self.ot.send(&mut self.channel, &inputs, &mut self.rng)?;
+        self.channel.flush()?;
         Ok(wires)
     }
 }
@@ -154,6 +313,18 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> Fancy for Garbler<C, RNG, OT>
     fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
         self.garbler.output(x).map_err(Self::Error::from)
     }
+
+    fn constant_big(&mut self, x: u64, modulus: &Modulus) -> Result<Self::Item, Self::Error> {
+        self.garbler.constant_big(x, modulus).map_err(Self::Error::from)
+    }
+
+    fn cmul_big(&mut self, x: &Wire, c: u64) -> Result<Self::Item, Self::Error> {
+        self.garbler.cmul_big(x, c).map_err(Self::Error::from)
+    }
+
+    fn output_big(&mut self, x: &Self::Item) -> Result<Option<u64>, Self::Error> {
+        self.garbler.output_big(x).map_err(Self::Error::from)
+    }
 }
 
 impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyReveal for Garbler<C, RNG, OT> {
@@ -162,4 +333,19 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyReveal for Garbler<C, RN
     }
 }
 
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyBranch for Garbler<C, RNG, OT> {
+    fn branch(
+        &mut self,
+        cond: &Wire,
+        branches: &[SubCircuit],
+        inputs: &[Wire],
+    ) -> Result<Vec<Wire>, Self::Error> {
+        self.garbler.branch(cond, branches, inputs).map_err(Self::Error::from)
+    }
+}
+
+impl<C, RNG, OT> HasRole for Garbler<C, RNG, OT> {
+    type Role = role::Garbler;
+}
+
 impl<C, RNG, OT> SemiHonest for Garbler<C, RNG, OT> {}
@@ -12,21 +12,110 @@ mod garbler;
 pub use evaluator::Evaluator;
 pub use garbler::Garbler;
 
+use crate::{Modulus, Wire};
+use scuttlebutt::{Block, Block512};
+use sha3::{Digest, Sha3_256};
+
+// The evaluator's inputs are sent one bit at a time through OT, and their wire
+// is the sum of the bit wires weighted by these constants. For GF(p^k), whose
+// base-p digits add without carries, the bits of each digit are weighted
+// separately. The weights are `u64` so that they also cover `ZqBig` wires.
+pub(crate) fn input_weights(modulus: &Modulus) -> Vec<u64> {
+    match *modulus {
+        Modulus::GFpk { p, k, .. } => {
+            let nbits = 16 - (p - 1).leading_zeros() as usize;
+            (0..k as u32)
+                .flat_map(|j| (0..nbits).map(move |i| ((1 << i) * p.pow(j)) as u64))
+                .collect()
+        }
+        _ => (0..modulus.bit_length()).map(|i| 1 << i).collect(),
+    }
+}
+
+// The bits of `x` matching `input_weights(modulus)`.
+pub(super) fn input_bits(x: u64, modulus: &Modulus) -> Vec<bool> {
+    match *modulus {
+        Modulus::GFpk { p, k, .. } => {
+            let nbits = 16 - (p - 1).leading_zeros() as usize;
+            (0..k as u32)
+                .flat_map(|j| {
+                    let d = x / u64::from(p).pow(j) % u64::from(p);
+                    (0..nbits).map(move |i| d & (1 << i) != 0)
+                })
+                .collect()
+        }
+        _ => (0..modulus.bit_length()).map(|i| x >> i & 1 != 0).collect(),
+    }
+}
+
+// With 1-out-of-N OT, inputs in these moduli are sent as one of their `N`
+// values rather than bit by bit.
+pub(crate) fn n_ot_size(modulus: &Modulus) -> Option<u16> {
+    match *modulus {
+        Modulus::GF4 { .. } | Modulus::GF8 { .. } => Some(modulus.size()),
+        _ => None,
+    }
+}
+
+// The KKRT OPRF outputs are correlated across inputs, so they are hashed
+// before masking labels with them.
+pub(crate) fn n_ot_pad(output: Block512) -> Block {
+    let digest = Sha3_256::digest(output.as_ref());
+    let mut pad = [0u8; 16];
+    pad.copy_from_slice(&digest[..16]);
+    Block::from(u128::from_le_bytes(pad))
+}
+
+// The commitment to `label` as the label of value `k` of the `i`th committed
+// output: SHA3-256 of `i`, `k` and the label. Unlike the gate hash, whose key
+// is public and which the garbler could invert, this is collision resistant,
+// so no commitment opens to two labels.
+fn commit_output(i: usize, k: u16, label: &Wire) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.input((i as u64).to_le_bytes());
+    hasher.input(k.to_le_bytes());
+    hasher.input(label.as_block().as_ref());
+    let mut commitment = [0u8; 32];
+    commitment.copy_from_slice(&hasher.result());
+    commitment
+}
+
+// The tweak of the translation table of the `i`th wire carried over into run
+// `run` of a `Session`. The top bits keep these apart from other tweaks.
+fn translation_tweak(run: usize, i: usize) -> Block {
+    Block::from(3 << 126 | (run as u128) << 64 | i as u128)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
         circuit::Circuit,
         dummy::Dummy,
+        errors::TwopacError,
+        primitives::photon::{PhotonGadgets, PhotonParams},
+        role::{HasRole, Party},
         util::RngExt,
+        BinaryBundle,
+        BinaryGadgets,
         CrtBundle,
         CrtGadgets,
         Fancy,
         FancyInput, Modulus,
+        StateMatrix,
     };
     use itertools::Itertools;
-    use ocelot::ot::{ChouOrlandiReceiver, ChouOrlandiSender};
-    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+    use ocelot::ot::{
+        AlszReceiver,
+        AlszSender,
+        ChouOrlandiReceiver,
+        ChouOrlandiSender,
+        KosReceiver,
+        KosSender,
+        Receiver as OtReceiver,
+        Sender as OtSender,
+    };
+    use scuttlebutt::{unix_channel_pair, AbstractChannel, AesRng, Block, SemiHonest, UnixChannel};
 
     fn addition<F: Fancy>(f: &mut F, a: &F::Item, b: &F::Item) -> Result<Option<u16>, F::Error> {
         let c = f.add(&a, &b)?;
@@ -85,6 +174,289 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_addition_circuit_GFpk() {
+        let q = Modulus::GFpk { p: 3, k: 2, poly: 5 };
+        for a in 0..9 {
+            for b in 0..9 {
+                let (sender, receiver) = unix_channel_pair();
+                std::thread::spawn(move || {
+                    let rng = AesRng::new();
+                    let mut gb =
+                        Garbler::<UnixChannel, AesRng, ChouOrlandiSender>::new(sender, rng)
+                            .unwrap();
+                    let x = gb.encode(a, &q).unwrap();
+                    let ys = gb.receive_many(&[q]).unwrap();
+                    addition(&mut gb, &x, &ys[0]).unwrap();
+                });
+                let rng = AesRng::new();
+                let mut ev =
+                    Evaluator::<UnixChannel, AesRng, ChouOrlandiReceiver>::new(receiver, rng)
+                        .unwrap();
+                let x = ev.receive(&q).unwrap();
+                let ys = ev.encode_many(&[b], &[q]).unwrap();
+                let output = addition(&mut ev, &x, &ys[0]).unwrap().unwrap();
+                assert_eq!(crate::util::gfpk_add(a, b, 3, 2), output);
+            }
+        }
+    }
+
+    #[test]
+    fn test_n_ot_inputs() {
+        let mut rng = rand::thread_rng();
+        let gf8 = Modulus::GF8 { p: 0x11b };
+        let moduli = [gf8, Modulus::Zq { q: 5 }, Modulus::X4_X_1, gf8];
+        for _ in 0..4 {
+            let a = rng.gen_u16() % 256;
+            let bs = moduli
+                .iter()
+                .map(|q| rng.gen_u16() % q.size())
+                .collect::<Vec<u16>>();
+            let (sender, receiver) = unix_channel_pair();
+            let handle = std::thread::spawn(move || {
+                let mut gb = Garbler::<UnixChannel, AesRng>::new(sender, AesRng::new())
+                    .unwrap()
+                    .with_n_ot();
+                let x = gb.encode(a, &gf8).unwrap();
+                let ys = gb.receive_many(&moduli).unwrap();
+                for y in ys.iter() {
+                    gb.output(y).unwrap();
+                }
+                let z = gb.add(&x, &ys[3]).unwrap();
+                gb.output(&z).unwrap();
+            });
+            let mut ev = Evaluator::<UnixChannel, AesRng>::new(receiver, AesRng::new())
+                .unwrap()
+                .with_n_ot();
+            let x = ev.receive(&gf8).unwrap();
+            let ys = ev.encode_many(&bs, &moduli).unwrap();
+            for (y, b) in ys.iter().zip(bs.iter()) {
+                assert_eq!(ev.output(y).unwrap(), Some(*b));
+            }
+            let z = ev.add(&x, &ys[3]).unwrap();
+            assert_eq!(ev.output(&z).unwrap(), Some(a ^ bs[3]));
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_batched_inputs() {
+        let mut rng = rand::thread_rng();
+        let moduli = (0..200)
+            .map(|i| match i % 3 {
+                0 => Modulus::X4_X_1,
+                1 => Modulus::Zq { q: 2 },
+                _ => Modulus::Zq { q: 7 },
+            })
+            .collect::<Vec<Modulus>>();
+        let as_ = moduli
+            .iter()
+            .map(|q| rng.gen_u16() % q.size())
+            .collect::<Vec<u16>>();
+        let bs = moduli
+            .iter()
+            .map(|q| rng.gen_u16() % q.size())
+            .collect::<Vec<u16>>();
+        let (sender, receiver) = unix_channel_pair();
+        let gb_moduli = moduli.clone();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Garbler::<UnixChannel, AesRng>::new(sender, AesRng::new())
+                .unwrap()
+                .with_n_ot();
+            let xs = gb.encode_many(&as_, &gb_moduli).unwrap();
+            let ys = gb.receive_many(&gb_moduli).unwrap();
+            for (x, y) in xs.iter().zip(ys.iter()) {
+                let z = gb.mul(x, y).unwrap();
+                gb.output(x).unwrap();
+                gb.output(y).unwrap();
+                gb.output(&z).unwrap();
+            }
+        });
+        let mut ev = Evaluator::<UnixChannel, AesRng>::new(receiver, AesRng::new())
+            .unwrap()
+            .with_n_ot();
+        let xs = ev.receive_many(&moduli).unwrap();
+        let ys = ev.encode_many(&bs, &moduli).unwrap();
+        let mut dummy = crate::dummy::Dummy::new();
+        for ((x, y), q) in xs.iter().zip(ys.iter()).zip(moduli.iter()) {
+            let z = ev.mul(x, y).unwrap();
+            let a = dummy.encode(ev.output(x).unwrap().unwrap(), q).unwrap();
+            let b = dummy.encode(ev.output(y).unwrap().unwrap(), q).unwrap();
+            let c = dummy.mul(&a, &b).unwrap();
+            assert_eq!(ev.output(&z).unwrap(), dummy.output(&c).unwrap());
+        }
+        handle.join().unwrap();
+    }
+
+    // the gates of `test_informer_estimate`, on garbler inputs `xs` and
+    // evaluator inputs `ys`
+    fn estimated_gates<F: Fancy>(
+        f: &mut F,
+        xs: &[F::Item],
+        ys: &[F::Item],
+    ) -> Result<(), F::Error> {
+        let q2 = Modulus::Zq { q: 2 };
+        let z = f.mul(&xs[0], &ys[0])?;
+        let c = f.constant(1, &q2)?;
+        let z = f.add(&z, &c)?;
+        let p = f.proj(&ys[1], &q2, Some(vec![0, 1, 1, 0, 1]))?;
+        let m = f.mul(&xs[1], &ys[2])?;
+        f.output(&z)?;
+        f.output(&p)?;
+        f.output(&m)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_informer_estimate() {
+        use crate::informer::{InputTransfer, Informer};
+        use scuttlebutt::{SyncChannel, TrackChannel};
+        use std::{
+            io::{BufReader, BufWriter},
+            os::unix::net::UnixStream,
+        };
+
+        let (q2, gf4, gf8) = (Modulus::Zq { q: 2 }, Modulus::X4_X_1, Modulus::GF8 { p: 0x11b });
+        let gb_moduli = vec![q2, gf4];
+        let ev_moduli = vec![q2, Modulus::Zq { q: 5 }, gf4, gf8, q2];
+        for &transfer in &[InputTransfer::Alsz, InputTransfer::NOt] {
+            let mut informer = Informer::new(Dummy::with_inputs(&[], &[0; 5]));
+            let xs = informer.encode_many(&[0, 0], &gb_moduli).unwrap();
+            let mut ys = informer.receive_many(&ev_moduli[..4]).unwrap();
+            ys.extend(informer.receive_many(&ev_moduli[4..]).unwrap());
+            estimated_gates(&mut informer, &xs, &ys).unwrap();
+            let estimate = informer.stats().semihonest_estimate(transfer);
+
+            // the garbler counts the bytes in both directions, so only its
+            // channel is tracked
+            let n_ot = transfer == InputTransfer::NOt;
+            let (tx, rx) = UnixStream::pair().unwrap();
+            let (gbm, evm) = (gb_moduli.clone(), ev_moduli.clone());
+            let handle = std::thread::spawn(move || {
+                let reader = BufReader::new(rx.try_clone().unwrap());
+                let channel = SyncChannel::new(reader, BufWriter::new(rx));
+                let mut ev = Evaluator::<UnixChannel, AesRng>::new(channel, AesRng::new()).unwrap();
+                if n_ot {
+                    ev = ev.with_n_ot();
+                    ev.encode_many(&[0], &[gf4]).unwrap();
+                }
+                let xs = ev.receive_many(&gbm).unwrap();
+                let mut ys = ev.encode_many(&[1, 4, 7, 200], &evm[..4]).unwrap();
+                ys.extend(ev.encode_many(&[0], &evm[4..]).unwrap());
+                estimated_gates(&mut ev, &xs, &ys).unwrap();
+            });
+            let reader = BufReader::new(tx.try_clone().unwrap());
+            let channel = TrackChannel::new(reader, BufWriter::new(tx));
+            let mut gb = Garbler::<_, AesRng>::new(channel, AesRng::new()).unwrap();
+            if n_ot {
+                // set up the OPRF before counting
+                gb = gb.with_n_ot();
+                gb.receive_many(&[gf4]).unwrap();
+            }
+            gb.get_channel().clear();
+            let xs = gb.encode_many(&[1, 3], &gb_moduli).unwrap();
+            let mut ys = gb.receive_many(&ev_moduli[..4]).unwrap();
+            ys.extend(gb.receive_many(&ev_moduli[4..]).unwrap());
+            estimated_gates(&mut gb, &xs, &ys).unwrap();
+            handle.join().unwrap();
+            let bits = (gb.get_channel().total_kilobits() * 1000.0).round() as usize;
+            assert_eq!(bits, 8 * estimate.total_bytes());
+        }
+    }
+
+    // shared by both parties: the garbler's input `a` comes first
+    fn role_addition<F>(f: &mut F, input: u16) -> Result<Option<u16>, <F as Fancy>::Error>
+    where
+        F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Error> + HasRole,
+    {
+        let q = Modulus::Zq { q: 7 };
+        let (a, b) = match f.party() {
+            Party::Garbler => {
+                let a = f.encode(input, &q)?;
+                (a, f.receive(&q)?)
+            }
+            Party::Evaluator => {
+                let a = f.receive(&q)?;
+                (a, f.encode(input, &q)?)
+            }
+        };
+        addition(f, &a, &b)
+    }
+
+    #[test]
+    fn test_role_dispatch() {
+        for a in 0..7 {
+            let b = 6 - a / 2;
+            let (sender, receiver) = unix_channel_pair();
+            let handle = std::thread::spawn(move || {
+                let rng = AesRng::new();
+                let mut gb =
+                    Garbler::<UnixChannel, AesRng, ChouOrlandiSender>::new(sender, rng).unwrap();
+                assert_eq!(gb.party(), Party::Garbler);
+                role_addition(&mut gb, a).unwrap();
+            });
+            let rng = AesRng::new();
+            let mut ev =
+                Evaluator::<UnixChannel, AesRng, ChouOrlandiReceiver>::new(receiver, rng).unwrap();
+            assert_eq!(ev.party(), Party::Evaluator);
+            let output = role_addition(&mut ev, b).unwrap().unwrap();
+            assert_eq!((a + b) % 7, output);
+            handle.join().unwrap();
+        }
+    }
+
+    fn bin_sum<F: Fancy>(f: &mut F, xs: &[BinaryBundle<F::Item>]) -> Option<u128> {
+        let z = xs[1..].iter().fold(xs[0].clone(), |acc, x| {
+            f.bin_addition_no_carry(&acc, x).unwrap()
+        });
+        f.bin_output(&z).unwrap()
+    }
+
+    // Many evaluator input bits over OT extension, in two sessions sharing the
+    // base OTs.
+    fn ot_extension<OTS, OTR>()
+    where
+        OTS: OtSender<Msg = Block> + SemiHonest + Send + 'static,
+        OTR: OtReceiver<Msg = Block> + SemiHonest,
+    {
+        let mut rng = rand::thread_rng();
+        let nbits = 64;
+        let inputs = (0..2)
+            .map(|_| (0..8).map(|_| rng.gen_u128() % (1 << 60)).collect_vec())
+            .collect_vec();
+        let targets = inputs.iter().map(|xs| xs.iter().sum::<u128>()).collect_vec();
+
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut ot = OTS::init(&mut sender.clone(), &mut AesRng::new()).unwrap();
+            for _ in 0..2 {
+                let mut gb = Garbler::<UnixChannel, AesRng, OTS>::with_ot(
+                    sender.clone(),
+                    AesRng::new(),
+                    ot,
+                );
+                let xs = gb.bin_receive_many(8, nbits).unwrap();
+                bin_sum(&mut gb, &xs);
+                ot = gb.into_ot();
+            }
+        });
+        let mut ot = OTR::init(&mut receiver.clone(), &mut AesRng::new()).unwrap();
+        for (xs, target) in inputs.iter().zip(targets.iter()) {
+            let mut ev =
+                Evaluator::<UnixChannel, AesRng, OTR>::with_ot(receiver.clone(), AesRng::new(), ot);
+            let xs = ev.bin_encode_many(xs, nbits).unwrap();
+            assert_eq!(bin_sum(&mut ev, &xs), Some(*target));
+            ot = ev.into_ot();
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_ot_extension() {
+        ot_extension::<AlszSender, AlszReceiver>();
+        ot_extension::<KosSender, KosReceiver>();
+    }
+
     fn relu<F: Fancy>(b: &mut F, xs: &[CrtBundle<F::Item>]) -> Option<Vec<u128>> {
         let mut outputs = Vec::new();
         for x in xs.iter() {
@@ -131,6 +503,103 @@ mod tests {
         assert_eq!(target, result);
     }
 
+    #[test]
+    fn test_committed_output() {
+        let q = Modulus::Zq { q: 5 };
+        for cheat in [false, true].iter().cloned() {
+            let (sender, receiver) = unix_channel_pair();
+            let handle = std::thread::spawn(move || {
+                let mut gb = Garbler::<UnixChannel, AesRng>::new(sender, AesRng::new()).unwrap();
+                let x = gb.encode(3, &q).unwrap();
+                let ys = gb.receive_many(&[q]).unwrap();
+                let z = gb.mul(&x, &ys[0]).unwrap();
+                gb.commit_outputs(&[x.clone(), z.clone()]).unwrap();
+                gb.output(&x).unwrap();
+                if cheat {
+                    // decode `z` as one less than its value
+                    let delta = gb.delta(&q);
+                    gb.output(&z.plus(&delta)).unwrap();
+                } else {
+                    gb.output(&z).unwrap();
+                }
+            });
+            let mut ev = Evaluator::<UnixChannel, AesRng>::new(receiver, AesRng::new()).unwrap();
+            let x = ev.receive(&q).unwrap();
+            let ys = ev.encode_many(&[4], &[q]).unwrap();
+            let z = ev.mul(&x, &ys[0]).unwrap();
+            ev.receive_output_commitments(&[x.clone(), z.clone()]).unwrap();
+            assert_eq!(ev.committed_output(&x).unwrap(), 3);
+            match ev.committed_output(&z) {
+                Err(TwopacError::CheatingDetected(_)) => assert!(cheat),
+                r => assert_eq!(r.unwrap(), 2),
+            }
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_committed_output_GF16() {
+        // 2^16 commitments, one per element
+        let q = Modulus::X16_X5_X3_X2_1;
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Garbler::<UnixChannel, AesRng>::new(sender, AesRng::new()).unwrap();
+            let x = gb.encode(40000, &q).unwrap();
+            let ys = gb.receive_many(&[q]).unwrap();
+            let z = gb.add(&x, &ys[0]).unwrap();
+            gb.commit_outputs(std::slice::from_ref(&z)).unwrap();
+            gb.output(&z).unwrap();
+        });
+        let mut ev = Evaluator::<UnixChannel, AesRng>::new(receiver, AesRng::new()).unwrap();
+        let x = ev.receive(&q).unwrap();
+        let ys = ev.encode_many(&[12345], &[q]).unwrap();
+        let z = ev.add(&x, &ys[0]).unwrap();
+        ev.receive_output_commitments(std::slice::from_ref(&z)).unwrap();
+        assert_eq!(ev.committed_output(&z).unwrap(), 40000 ^ 12345);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_photon_matrix() {
+        let params = PhotonParams::P100;
+        let q = params.modulus;
+        let key = StateMatrix::from_rows(5, 5, (0..25).map(|i| i % 16).collect()).unwrap();
+        let msg = StateMatrix::from_columns(5, 5, (0..25).map(|i| 15 - i % 16).collect()).unwrap();
+        let expected = {
+            let mut f = Dummy::new();
+            let k = f.encode_matrix(&key, &q).unwrap();
+            let m = f.encode_matrix(&msg, &q).unwrap();
+            let x = k.cells().iter().zip(m.cells()).map(|(a, b)| f.add(a, b).unwrap());
+            let x = StateMatrix::from_rows(5, 5, x.collect()).unwrap();
+            let z = f.photon_state(&x, &params).unwrap();
+            f.output_photon(&z).unwrap().unwrap()
+        };
+
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let rng = AesRng::new();
+            let mut gb =
+                Garbler::<UnixChannel, AesRng, ChouOrlandiSender>::new(sender, rng).unwrap();
+            let k = gb.encode_matrix(&key, &q).unwrap();
+            let m = gb.receive_matrix(5, 5, &q).unwrap();
+            let x = k.cells().iter().zip(m.cells()).map(|(a, b)| gb.add(a, b).unwrap());
+            let x = StateMatrix::from_rows(5, 5, x.collect()).unwrap();
+            let z = gb.photon_state(&x, &params).unwrap();
+            assert!(gb.output_photon(&z).unwrap().is_none());
+        });
+        let rng = AesRng::new();
+        let mut ev =
+            Evaluator::<UnixChannel, AesRng, ChouOrlandiReceiver>::new(receiver, rng).unwrap();
+        let k = ev.receive_matrix(5, 5, &q).unwrap();
+        let m = ev.encode_matrix(&msg, &q).unwrap();
+        let x = k.cells().iter().zip(m.cells()).map(|(a, b)| ev.add(a, b).unwrap());
+        let x = StateMatrix::from_rows(5, 5, x.collect()).unwrap();
+        let z = ev.photon_state(&x, &params).unwrap();
+        let z = ev.output_photon(&z).unwrap().unwrap();
+        assert_eq!(z, expected);
+        handle.join().unwrap();
+    }
+
     #[test]
     fn test_aes() {
         let circ = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
@@ -0,0 +1,206 @@
+// -*- mode: rust; -*-
+//
+// This file is part of twopac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Reactive two-party computation, running a sequence of circuits whose
+//! outputs feed the next circuit without being decoded.
+//!
+//! A `Session` owns a semi-honest `Garbler` or `Evaluator`, and with it the
+//! channel and the garbler's deltas. Each call to `Session::run` garbles one
+//! circuit under fresh deltas, so that circuits stay independent. The wires the
+//! previous circuit chose to retain are carried into it by translation tables,
+//! one per wire with a ciphertext per value, which re-encode their labels under
+//! the new deltas. Nothing about the retained values is revealed. `ZqBig`
+//! wires cannot be retained.
+
+use crate::{
+    errors::TwopacError,
+    twopac::semihonest::{Evaluator, Garbler},
+    Wire,
+};
+use ocelot::ot::{Receiver as OtReceiver, Sender as OtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+
+/// A party which can carry wires over from one circuit to the next.
+pub trait Reactive {
+    /// Start run `run` of a session, translating the wires `xs` retained from
+    /// the previous run. Both parties must retain the same wires.
+    fn translate(&mut self, xs: &[Wire], run: usize) -> Result<Vec<Wire>, TwopacError>;
+}
+
+impl<
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+        OT: OtSender<Msg = Block> + SemiHonest,
+    > Reactive for Garbler<C, RNG, OT>
+{
+    fn translate(&mut self, xs: &[Wire], run: usize) -> Result<Vec<Wire>, TwopacError> {
+        Garbler::translate(self, xs, run)
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + SemiHonest> Reactive
+    for Evaluator<C, RNG, OT>
+{
+    fn translate(&mut self, xs: &[Wire], run: usize) -> Result<Vec<Wire>, TwopacError> {
+        Evaluator::translate(self, xs, run)
+    }
+}
+
+/// A sequence of circuits run by one party, see the module documentation.
+pub struct Session<P> {
+    party: P,
+    retained: Vec<Wire>,
+    run: usize,
+}
+
+impl<P: Reactive> Session<P> {
+    /// Start a session for `party`.
+    pub fn new(party: P) -> Self {
+        Session {
+            party,
+            retained: Vec::new(),
+            run: 0,
+        }
+    }
+
+    /// Run the next circuit.
+    ///
+    /// `circuit` is given the party and the wires retained by the previous
+    /// circuit, and returns its result together with the wires to retain for
+    /// the next one. Both parties must run the same circuits in the same
+    /// order.
+    pub fn run<T, F>(&mut self, circuit: F) -> Result<T, TwopacError>
+    where
+        F: FnOnce(&mut P, &[Wire]) -> Result<(T, Vec<Wire>), TwopacError>,
+    {
+        let retained = std::mem::take(&mut self.retained);
+        let state = if self.run == 0 {
+            retained
+        } else {
+            self.party.translate(&retained, self.run)?
+        };
+        let (result, retained) = circuit(&mut self.party, &state)?;
+        self.retained = retained;
+        self.run += 1;
+        Ok(result)
+    }
+
+    /// The number of circuits run so far.
+    pub fn runs(&self) -> usize {
+        self.run
+    }
+
+    /// The wires retained by the last circuit.
+    pub fn retained(&self) -> &[Wire] {
+        &self.retained
+    }
+
+    /// Get a reference to the party.
+    pub fn party(&mut self) -> &mut P {
+        &mut self.party
+    }
+
+    /// End the session, returning the party.
+    pub fn into_party(self) -> P {
+        self.party
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Fancy, FancyInput, Modulus};
+    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+
+    // Each run adds our next input to the retained value and squares it, and
+    // the last run outputs it.
+    fn step<F: Fancy<Item = Wire, Error = TwopacError>>(
+        f: &mut F,
+        state: &[Wire],
+        input: Wire,
+        last: bool,
+    ) -> Result<(Option<u16>, Vec<Wire>), TwopacError> {
+        let x = match state.first() {
+            Some(s) => f.add(s, &input)?,
+            None => input,
+        };
+        let y = f.mul(&x, &x)?;
+        if last {
+            Ok((f.output(&y)?, Vec::new()))
+        } else {
+            Ok((None, vec![y]))
+        }
+    }
+
+    fn reactive(q: Modulus, inputs: Vec<u16>) -> u16 {
+        let (sender, receiver) = unix_channel_pair();
+        let n = inputs.len();
+        let handle = std::thread::spawn(move || {
+            let gb = Garbler::<UnixChannel, AesRng>::new(sender, AesRng::new()).unwrap();
+            let mut session = Session::new(gb);
+            for i in 0..n {
+                session
+                    .run(|gb, state| {
+                        let x = gb.receive(&q)?;
+                        step(gb, state, x, i == n - 1)
+                    })
+                    .unwrap();
+            }
+        });
+        let ev = Evaluator::<UnixChannel, AesRng>::new(receiver, AesRng::new()).unwrap();
+        let mut session = Session::new(ev);
+        let mut output = None;
+        for (i, v) in inputs.into_iter().enumerate() {
+            output = session
+                .run(|ev, state| {
+                    let x = ev.encode(v, &q)?;
+                    step(ev, state, x, i == n - 1)
+                })
+                .unwrap();
+        }
+        assert_eq!(session.runs(), n);
+        handle.join().unwrap();
+        output.unwrap()
+    }
+
+    #[test]
+    fn test_reactive_zq() {
+        let q = 7;
+        let inputs = vec![3, 5, 2, 6];
+        let expected = inputs
+            .iter()
+            .fold(0, |acc, x| (acc + x) * (acc + x) % q);
+        assert_eq!(reactive(Modulus::Zq { q }, inputs), expected);
+    }
+
+    #[test]
+    fn test_reactive_gf4() {
+        let q = Modulus::X4_X_1;
+        let inputs = vec![9, 4, 13];
+        let mut dummy = crate::dummy::Dummy::new();
+        let expected = inputs.iter().fold(0, |acc, x| {
+            let s = dummy.encode(acc ^ x, &q).unwrap();
+            let y = dummy.mul(&s, &s).unwrap();
+            dummy.output(&y).unwrap().unwrap()
+        });
+        assert_eq!(reactive(q, inputs), expected);
+    }
+
+    #[test]
+    fn test_reactive_gf16() {
+        // translation tables of 2^16 ciphertexts
+        let q = Modulus::X16_X5_X3_X2_1;
+        let inputs = vec![40000, 12345];
+        let mut dummy = crate::dummy::Dummy::new();
+        let expected = inputs.iter().fold(0, |acc, x| {
+            let s = dummy.encode(acc ^ x, &q).unwrap();
+            let y = dummy.mul(&s, &s).unwrap();
+            dummy.output(&y).unwrap().unwrap()
+        });
+        assert_eq!(reactive(q, inputs), expected);
+    }
+}
@@ -8,13 +8,24 @@
 //!
 //! Note: all number representations in this library are little-endian.
 
-use std::convert::TryInto;
-use crate::{Wire, Modulus};
+use alloc::{boxed::Box, vec::Vec};
+use core::convert::TryInto;
 #[cfg(feature = "nightly")]
 use core::arch::x86_64::*;
+#[cfg(not(feature = "std"))]
+use core::{
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
 use itertools::Itertools;
 use scuttlebutt::Block;
-use std::collections::HashMap;
+#[cfg(feature = "std")]
+use crate::{Modulus, Wire};
+#[cfg(feature = "std")]
+use std::{
+    collections::HashMap,
+    sync::{OnceLock, RwLock},
+};
 
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -89,7 +100,7 @@ pub fn base_q_add_eq(xs: &mut [u16], ys: &[u16], q: u16) {
 
 /// Convert `x` into base `q`, building a vector of length `n`.
 fn as_base_q(x: u128, q: u16, n: usize) -> Vec<u16> {
-    let ms = std::iter::repeat(q).take(n).collect_vec();
+    let ms = core::iter::repeat(q).take(n).collect_vec();
     as_mixed_radix(x, &ms)
 }
 
@@ -156,6 +167,34 @@ pub fn from_base_q(ds: &[u16], q: u16) -> u128 {
     x
 }
 
+/// Determine how many `mod q` digits fit into a `u128`, for a modulus `q` of up
+/// to 32 bits.
+pub fn digits_per_u128_big(q: u64) -> usize {
+    debug_assert!(q > 1 && q < 1 << 32);
+    128 / (64 - (q - 1).leading_zeros() as usize)
+}
+
+/// Convert `x` into base `q`, for a modulus `q` of up to 32 bits, dropping the
+/// digits beyond `digits_per_u128_big(q)`.
+pub fn as_base_q_big(x: u128, q: u64) -> Vec<u32> {
+    let mut x = x;
+    (0..digits_per_u128_big(q))
+        .map(|_| {
+            let d = x % q as u128;
+            x /= q as u128;
+            d as u32
+        })
+        .collect()
+}
+
+/// Convert little-endian base `q` digits into `u128`, for a modulus `q` of up
+/// to 32 bits.
+pub fn from_base_q_big(ds: &[u32], q: u64) -> u128 {
+    ds.iter()
+        .rev()
+        .fold(0u128, |x, &d| x * q as u128 + d as u128)
+}
+
 /// Convert a vector of elements in GF(2^k) to a vector of u8s.
 /// Every element in GF(2^k) is represented by its binary representation 
 /// and is then fit into an array of u8 integers.
@@ -186,7 +225,7 @@ pub fn from_poly_p_array(elts: &Vec<u16>, p: u16, k: u8) -> [u8; 16] {
 /// This function fits the array into the u128 as tightly as possible.
 /// It is meant for elements in GF(2^k) where k is different from 4 or 8.
 pub fn from_poly_p_u128(elts: &Vec<u16>, p: u16, k: u8) -> u128 {
-    debug_assert!(p < 2_u16.pow((k+1).into()), "field polynomial has a degree that is too high");
+    debug_assert!((p as u32) < 1 << (k + 1), "field polynomial has a degree that is too high");
     
     let mut x = 0u128;
     for i in (0..elts.len()).rev() {
@@ -196,21 +235,461 @@ pub fn from_poly_p_u128(elts: &Vec<u16>, p: u16, k: u8) -> u128 {
     x
 }
 
+// Masks selecting, after each step of `gf4_bitslice`, the bits gathered so far.
+const GF4_SLICE_MASKS: [u128; 6] = [
+    0x1111_1111_1111_1111_1111_1111_1111_1111,
+    0x0303_0303_0303_0303_0303_0303_0303_0303,
+    0x000f_000f_000f_000f_000f_000f_000f_000f,
+    0x0000_00ff_0000_00ff_0000_00ff_0000_00ff,
+    0x0000_0000_0000_ffff_0000_0000_0000_ffff,
+    0x0000_0000_0000_0000_0000_0000_ffff_ffff,
+];
+
+/// Split 32 packed GF(2^4) elements, element `i` in bits `4i..4i+4` of `x`,
+/// into four bit-planes: bit `i` of plane `j` is bit `j` of element `i`.
+pub fn gf4_bitslice(x: u128) -> [u32; 4] {
+    let mut planes = [0u32; 4];
+    for (j, plane) in planes.iter_mut().enumerate() {
+        let mut y = (x >> j) & GF4_SLICE_MASKS[0];
+        for (s, mask) in GF4_SLICE_MASKS[1..].iter().enumerate() {
+            y = (y | y >> (3 << s)) & mask;
+        }
+        *plane = y as u32;
+    }
+    planes
+}
+
+/// Inverse of `gf4_bitslice`.
+pub fn gf4_unbitslice(planes: &[u32; 4]) -> u128 {
+    let mut x = 0;
+    for (j, &plane) in planes.iter().enumerate() {
+        let mut y = plane as u128;
+        for (s, mask) in GF4_SLICE_MASKS[..5].iter().enumerate().rev() {
+            y = (y | y << (3 << s)) & mask;
+        }
+        x |= y << j;
+    }
+    x
+}
+
+/// Multiply every element of the bit-sliced GF(2^4) `planes` by `c` modulo `p`.
+///
+/// Multiplication by `c` is linear over GF(2), so output plane `r` is the XOR
+/// of the input planes `j` for which bit `r` of `c * X^j` is set.
+///
+/// `c` may be secret, e.g. a label color, so this is constant-time in it as
+/// well: the columns are computed with `field_mul_ct` and select the planes by
+/// masks.
+pub fn gf4_bitsliced_cmul_eq(planes: &mut [u32; 4], c: u16, p: u16) {
+    let xs = *planes;
+    *planes = [0; 4];
+    for (j, &x) in xs.iter().enumerate() {
+        let col = u32::from(field_mul_ct(c, 1 << j, p, 4));
+        for (r, plane) in planes.iter_mut().enumerate() {
+            *plane ^= 0u32.wrapping_sub(col >> r & 1) & x;
+        }
+    }
+}
+
 /// Field multiplication (a*b) in GF(2^k) using the Russian peasant multiplication algorithm.
 /// https://en.wikipedia.org/wiki/Ancient_Egyptian_multiplication#Russian_peasant_multiplication
-pub fn field_mul(a: u16, b: u16, p: u16, k: u8) -> u8 {
-    let mut x = a;
-    let mut ret = 0;
+///
+/// Uses the cached log/antilog tables of `field_tables` when `p` is irreducible.
+/// The lookups depend on `a` and `b`, so use `field_mul_ct` for secret operands.
+pub fn field_mul(a: u16, b: u16, p: u16, k: u8) -> u16 {
+    match field_tables(p, k) {
+        Some(tables) => tables.mul(a, b),
+        None => field_mul_ct(a, b, p, k),
+    }
+}
+
+/// Constant-time field multiplication (a*b) in GF(2^k): the Russian peasant
+/// algorithm without any data-dependent branches or memory accesses.
+///
+/// For `k = 16` the leading term of `p` does not fit in a `u16`, so `p` holds
+/// only its lower 16 coefficients.
+pub fn field_mul_ct(a: u16, b: u16, p: u16, k: u8) -> u16 {
+    let mut x = a as u32;
+    let mut ret = 0u32;
+    let p = p as u32;
+    let mask = (1u32 << k) - 1;
 
     for i in 0..k {
-        if ((b >> i) & 1) != 0 { ret ^= x };
+        let bit = 0u32.wrapping_sub((b as u32 >> i) & 1);
+        ret ^= x & bit;
+        let carry = 0u32.wrapping_sub((x >> (k - 1)) & 1);
+        x = ((x << 1) ^ (p & carry)) & mask;
+    }
+    ret as u16
+}
+
+// Whether `p` has degree `k`, keeping in mind that for `k = 16` the leading
+// term is implicit.
+fn field_poly_degree_is(p: u16, k: u8) -> bool {
+    match k {
+        1..=15 => (p as u32) >> k == 1,
+        16 => true,
+        _ => false,
+    }
+}
+
+// Compute `x^e` in GF(2^k) modulo `p`.
+fn field_pow(mut x: u16, mut e: u32, p: u16, k: u8) -> u16 {
+    let mut acc = 1;
+    while e > 0 {
+        if e & 1 == 1 {
+            acc = field_mul_ct(acc, x, p, k);
+        }
+        x = field_mul_ct(x, x, p, k);
+        e >>= 1;
+    }
+    acc
+}
+
+// Degree of a nonzero binary polynomial.
+const fn poly_degree(a: u32) -> u32 {
+    31 - a.leading_zeros()
+}
+
+// Remainder of the binary polynomial `a` divided by the nonzero `b`.
+const fn poly_rem(mut a: u32, b: u32) -> u32 {
+    let db = poly_degree(b);
+    while a != 0 && poly_degree(a) >= db {
+        a ^= b << (poly_degree(a) - db);
+    }
+    a
+}
+
+// Square `x` modulo the degree-`k` binary polynomial `p`, given with its
+// leading term.
+const fn poly_sqr_mod(x: u32, p: u32, k: u32) -> u32 {
+    let mut acc = 0;
+    let mut y = x;
+    let mut i = 0;
+    while i < k {
+        if (x >> i) & 1 == 1 {
+            acc ^= y;
+        }
+        y <<= 1;
+        if (y >> k) & 1 == 1 {
+            y ^= p;
+        }
+        i += 1;
+    }
+    acc
+}
+
+// `x^(2^m)` modulo the degree-`k` binary polynomial `p`.
+const fn poly_frobenius(p: u32, k: u32, m: u32) -> u32 {
+    let mut x = poly_rem(0b10, p);
+    let mut i = 0;
+    while i < m {
+        x = poly_sqr_mod(x, p, k);
+        i += 1;
+    }
+    x
+}
+
+/// Whether `p` is an irreducible polynomial of degree `k` over GF(2), using
+/// Rabin's test.
+///
+/// As in `field_mul_ct`, for `k = 16` the leading term is implicit and `p`
+/// holds only the lower 16 coefficients.
+pub const fn is_irreducible(p: u16, k: u8) -> bool {
+    let k = k as u32;
+    let p = match k {
+        1..=15 if (p as u32) >> k == 1 => p as u32,
+        16 => p as u32 | 1 << 16,
+        _ => return false,
+    };
+    // x^(2^k) = x modulo p iff every irreducible factor of p has a degree
+    // dividing k
+    if poly_frobenius(p, k, k) != poly_rem(0b10, p) {
+        return false;
+    }
+    // and p has no factor of degree dividing k/r for any r > 1 dividing k (the
+    // primes r would suffice, but the others are cheap at these sizes)
+    let mut r = 2;
+    while r <= k {
+        if k % r == 0 {
+            let mut a = poly_frobenius(p, k, k / r) ^ poly_rem(0b10, p);
+            let mut b = p;
+            while a != 0 {
+                let t = poly_rem(b, a);
+                b = a;
+                a = t;
+            }
+            if b != 1 {
+                return false;
+            }
+        }
+        r += 1;
+    }
+    true
+}
+
+/// Log and antilog tables of GF(2^k) for a fixed irreducible polynomial `p`.
+#[derive(Debug)]
+pub struct FieldTables {
+    k: u8,
+    // exp[i] = g^i for a generator g, doubled in length so that sums of two
+    // logarithms never need reducing
+    exp: Vec<u16>,
+    log: Vec<u16>,
+}
+
+impl FieldTables {
+    /// Build the tables, returning `None` if `p` is not an irreducible polynomial
+    /// of degree `k`.
+    pub fn new(p: u16, k: u8) -> Option<Self> {
+        if !field_poly_degree_is(p, k) {
+            return None;
+        }
+        let size = 1u32 << k;
+        let order = (size - 1) as usize;
+        // search for a generator of the multiplicative group; an element of order
+        // 2^k - 1 exists iff p is irreducible, and g has that order iff g^order = 1
+        // and g^(order / r) != 1 for every prime r dividing order
+        let order_ = order as u32;
+        let factors = (2..=order_).filter(|&r| order_ % r == 0 && is_prime(r as u16));
+        let factors = factors.collect::<Vec<_>>();
+        let g = (1..size).map(|g| g as u16).find(|&g| {
+            field_pow(g, order_, p, k) == 1
+                && factors.iter().all(|&r| field_pow(g, order_ / r, p, k) != 1)
+        })?;
+        let mut exp = vec![0; 2 * order];
+        let mut log = vec![0; size as usize];
+        let mut x = 1;
+        for i in 0..order {
+            exp[i] = x;
+            exp[i + order] = x;
+            log[x as usize] = i as u16;
+            x = field_mul_ct(x, g, p, k);
+        }
+        Some(FieldTables { k, exp, log })
+    }
+
+    /// Multiply `a` and `b`. Only the low `k` bits of each operand are used.
+    #[inline]
+    pub fn mul(&self, a: u16, b: u16) -> u16 {
+        let mask = (self.log.len() - 1) as u16;
+        let (a, b) = (a & mask, b & mask);
+        if a == 0 || b == 0 {
+            0
+        } else {
+            self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+        }
+    }
+
+    /// The multiplicative inverse of `a`, or `None` if `a` is zero.
+    pub fn inv(&self, a: u16) -> Option<u16> {
+        let a = a & (self.log.len() - 1) as u16;
+        if a == 0 {
+            None
+        } else {
+            let order = self.exp.len() / 2;
+            Some(self.exp[(order - self.log[a as usize] as usize) % order])
+        }
+    }
+
+    /// The degree `k` of the field.
+    pub fn k(&self) -> u8 {
+        self.k
+    }
+}
+
+/// The roots in GF(2^8) modulo `p8` of the degree-4 polynomial `p4`.
+///
+/// When `p4` is irreducible there are four of them, conjugate under squaring,
+/// and each one is the image of `x` under a distinct embedding of GF(2^4)
+/// modulo `p4` into GF(2^8) modulo `p8`.
+pub fn gf4_embedding_roots(p4: u8, p8: u16) -> Vec<u16> {
+    (0..256)
+        .filter(|&y| {
+            // Horner's rule, from the leading coefficient down
+            let v = (0..5).rev().fold(0, |acc, i| {
+                field_mul(acc, y, p8, 8) ^ ((p4 as u16 >> i) & 1)
+            });
+            v == 0
+        })
+        .collect()
+}
+
+/// The embedding of GF(2^4) modulo `p4` into GF(2^8) modulo `p8` sending `x`
+/// to `root`, as a table of the images of the 16 elements.
+///
+/// Only a homomorphism when `root` is one of `gf4_embedding_roots(p4, p8)`.
+pub fn gf4_embedding(p8: u16, root: u16) -> Vec<u16> {
+    let powers = (0..4)
+        .scan(1, |acc, _| {
+            let y = *acc;
+            *acc = field_mul(*acc, root, p8, 8);
+            Some(y)
+        })
+        .collect::<Vec<u16>>();
+    (0..16u16)
+        .map(|a| (0..4).filter(|i| (a >> i) & 1 == 1).fold(0, |acc, i| acc ^ powers[i]))
+        .collect()
+}
+
+/// Multiply every element of `xs` by the constant `c` in GF(2^k) modulo `p`.
+///
+/// The elements are wire-label digits, so this is constant-time in them: the
+/// product is the XOR of `c * X^i` over the bits `i` of an element, selected by
+/// masks, without table lookups. Only `c` may be public.
+pub fn field_cmul_eq(xs: &mut [u16], c: u16, p: u16, k: u8) {
+    let mut basis = [0u16; 16];
+    let mut b = field_mul_ct(c, 1, p, k);
+    for y in basis.iter_mut().take(k as usize) {
+        *y = b;
+        b = field_mul_ct(b, 2, p, k);
+    }
+    xs.iter_mut().for_each(|x| {
+        *x = (0..k as usize).fold(0, |acc, i| acc ^ (basis[i] & 0u16.wrapping_sub(*x >> i & 1)))
+    });
+}
+
+/// Get the log/antilog tables of GF(2^k) modulo `p`, generating them on first use.
+///
+/// Returns `None` if `p` is not an irreducible polynomial of degree `k`.
+#[cfg(feature = "std")]
+pub fn field_tables(p: u16, k: u8) -> Option<&'static FieldTables> {
+    // a degree-k polynomial lies in [2^k, 2^(k+1)), so for k <= 8 it indexes its
+    // own slot and lookups are two atomic loads
+    static SMALL: OnceLock<Vec<OnceLock<Option<FieldTables>>>> = OnceLock::new();
+    static LARGE: OnceLock<RwLock<HashMap<(u16, u8), &'static Option<FieldTables>>>> =
+        OnceLock::new();
+
+    if !field_poly_degree_is(p, k) {
+        return None;
+    }
+    if k <= 8 {
+        let small = SMALL.get_or_init(|| (0..512).map(|_| OnceLock::new()).collect());
+        return small[p as usize].get_or_init(|| FieldTables::new(p, k)).as_ref();
+    }
+    let large = LARGE.get_or_init(|| RwLock::new(HashMap::new()));
+    if let Some(tables) = large.read().unwrap().get(&(p, k)) {
+        return tables.as_ref();
+    }
+    let mut large = large.write().unwrap();
+    let tables = large
+        .entry((p, k))
+        .or_insert_with(|| Box::leak(Box::new(FieldTables::new(p, k))));
+    tables.as_ref()
+}
+
+/// Get the log/antilog tables of GF(2^k) modulo `p`, generating them on first use.
+///
+/// Returns `None` if `p` is not an irreducible polynomial of degree `k`.
+#[cfg(not(feature = "std"))]
+pub fn field_tables(p: u16, k: u8) -> Option<&'static FieldTables> {
+    // without locks, generated tables are kept in a list which is only ever
+    // pushed to, so entries once read stay valid; racing threads may both
+    // generate the tables of a field, and the loser's copy is leaked
+    struct Entry {
+        p: u16,
+        k: u8,
+        tables: Option<FieldTables>,
+        next: *mut Entry,
+    }
+    static HEAD: AtomicPtr<Entry> = AtomicPtr::new(ptr::null_mut());
+
+    if !field_poly_degree_is(p, k) {
+        return None;
+    }
+    let find = |mut e: *mut Entry| {
+        while let Some(entry) = unsafe { e.as_ref() } {
+            if entry.p == p && entry.k == k {
+                return Some(entry.tables.as_ref());
+            }
+            e = entry.next;
+        }
+        None
+    };
+    let mut head = HEAD.load(Ordering::Acquire);
+    if let Some(tables) = find(head) {
+        return tables;
+    }
+    let entry = Box::leak(Box::new(Entry {
+        p,
+        k,
+        tables: FieldTables::new(p, k),
+        next: head,
+    }));
+    loop {
+        match HEAD.compare_exchange(head, entry, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => return entry.tables.as_ref(),
+            Err(new) => {
+                // look through the entries pushed since, then retry
+                let mut e = new;
+                while e != head {
+                    let other = unsafe { &*e };
+                    if other.p == p && other.k == k {
+                        return other.tables.as_ref();
+                    }
+                    e = other.next;
+                }
+                head = new;
+                entry.next = head;
+            }
+        }
+    }
+}
+
+/// Add `a` and `b` in GF(p^k). Elements are the base-`p` encodings of their
+/// coefficients, which add digit-wise without carries.
+pub fn gfpk_add(a: u16, b: u16, p: u16, k: u8) -> u16 {
+    let (mut a, mut b) = (a as u32, b as u32);
+    let (p, mut pi, mut z) = (p as u32, 1, 0);
+    for _ in 0..k {
+        z += (a % p + b % p) % p * pi;
+        a /= p;
+        b /= p;
+        pi *= p;
+    }
+    z as u16
+}
+
+/// Negate `a` in GF(p^k).
+pub fn gfpk_neg(a: u16, p: u16, k: u8) -> u16 {
+    let mut a = a as u32;
+    let (p, mut pi, mut z) = (p as u32, 1, 0);
+    for _ in 0..k {
+        z += (p - a % p) % p * pi;
+        a /= p;
+        pi *= p;
+    }
+    z as u16
+}
 
-        if ((x >> (k - 1)) & 1) != 0 {
-            x <<= 1;
-            x ^= p;
-        } else { x <<= 1 }
+/// Multiply `a` and `b` in GF(p^k) modulo the monic polynomial of degree `k`
+/// whose lower `k` coefficients are base-`p` encoded in `poly`.
+pub fn gfpk_mul(a: u16, b: u16, p: u16, k: u8, poly: u16) -> u16 {
+    let k = k as usize;
+    let xs = as_base_q(a.into(), p, k);
+    let ys = as_base_q(b.into(), p, k);
+    let cs = as_base_q(poly.into(), p, k);
+    let p = p as u32;
+    let mut zs = vec![0u32; 2 * k - 1];
+    for (i, &x) in xs.iter().enumerate() {
+        for (j, &y) in ys.iter().enumerate() {
+            zs[i + j] = (zs[i + j] + x as u32 * y as u32) % p;
+        }
+    }
+    // X^k = -(c_{k-1} X^{k-1} + ... + c_0), reducing from the top
+    for d in (k..2 * k - 1).rev() {
+        let z = zs[d];
+        for (i, &c) in cs.iter().enumerate() {
+            zs[d - k + i] = (zs[d - k + i] + (p - z * c as u32 % p)) % p;
+        }
     }
-    return ret as u8;
+    zs.truncate(k);
+    zs.iter().rev().fold(0, |acc, &z| acc * p + z) as u16
+}
+
+/// Multiply every element of `xs` by the constant `c` in GF(p^k) modulo `poly`.
+pub fn gfpk_cmul_eq(xs: &mut [u16], c: u16, p: u16, k: u8, poly: u16) {
+    xs.iter_mut().for_each(|x| *x = gfpk_mul(*x, c, p, k, poly));
 }
 
 
@@ -430,7 +909,29 @@ pub fn is_power_of_2(x: u16) -> bool {
     (x & (x - 1)) == 0
 }
 
+/// Returns `true` if `x` is prime.
+pub fn is_prime(x: u16) -> bool {
+    x >= 2 && (2..).take_while(|d| d * d <= x as u32).all(|d| x as u32 % d != 0)
+}
+
+/// Compute `x^e mod q` by square-and-multiply.
+pub fn pow_mod(x: u16, e: u32, q: u16) -> u16 {
+    let q = q as u64;
+    let mut base = x as u64 % q;
+    let mut acc = 1 % q;
+    let mut e = e;
+    while e > 0 {
+        if e & 1 == 1 {
+            acc = acc * base % q;
+        }
+        base = base * base % q;
+        e >>= 1;
+    }
+    acc as u16
+}
+
 // Generate deltas for GC
+#[cfg(feature = "std")]
 pub fn generate_deltas(moduli: &[Modulus]) -> HashMap<Modulus, Wire> {
     let mut deltas = HashMap::new();
     let mut rng = rand::thread_rng();
@@ -590,6 +1091,97 @@ mod tests {
         assert_eq!(array.len(), 16);
         assert!(array.iter().all(|el| *el == *el as u8));
     }
+
+    #[test]
+    fn GF4_bitslicing() {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let x = rng.gen_u128();
+            let planes = gf4_bitslice(x);
+            for i in 0..32 {
+                let elt = (x >> (4 * i)) as u32 & 15;
+                let bits = (0..4).map(|j| (planes[j] >> i & 1) << j).sum::<u32>();
+                assert_eq!(bits, elt);
+            }
+            assert_eq!(gf4_unbitslice(&planes), x);
+
+            let c = rng.gen_u16() % 16;
+            let mut ys = planes;
+            gf4_bitsliced_cmul_eq(&mut ys, c, 0b10011);
+            let should_be = as_base_q_u128(x, 16)
+                .iter()
+                .map(|&e| field_mul_ct(e, c, 0b10011, 4))
+                .collect::<Vec<_>>();
+            assert_eq!(as_base_q_u128(gf4_unbitslice(&ys), 16), should_be);
+        }
+    }
+
+    #[test]
+    fn field_cmul_eq_matches_peasant() {
+        let mut rng = thread_rng();
+        for &(p, k) in [(0b111, 2), (0b1011, 3), (0b10011, 4), (0b100011011, 8), (0b101101, 16)]
+            .iter()
+        {
+            let mask = ((1u32 << k) - 1) as u16;
+            for _ in 0..100 {
+                let c = rng.gen_u16() & mask;
+                let xs = (0..32).map(|_| rng.gen_u16() & mask).collect::<Vec<_>>();
+                let mut ys = xs.clone();
+                field_cmul_eq(&mut ys, c, p, k);
+                for (x, y) in xs.iter().zip(ys.iter()) {
+                    assert_eq!(*y, field_mul_ct(*x, c, p, k));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn field_tables_agree_with_peasant() {
+        for m in Modulus::GF4_MODULI.iter().chain(Modulus::GF8_MODULI.iter()) {
+            let (p, k) = (m.value(), m.bit_length() as u8);
+            let tables = match field_tables(p, k) {
+                Some(tables) => tables,
+                // x^4 + x^2 + 1 = (x^2 + x + 1)^2 is not actually irreducible
+                None if *m == Modulus::X4_X2_1 => continue,
+                None => panic!("no field tables for {}", m),
+            };
+            for a in 0..m.size() {
+                for b in 0..m.size() {
+                    assert_eq!(tables.mul(a, b), field_mul_ct(a, b, p, k));
+                }
+                if a != 0 {
+                    assert_eq!(tables.mul(a, tables.inv(a).unwrap()), 1);
+                }
+            }
+            assert_eq!(tables.inv(0), None);
+        }
+        // AES: 0x57 * 0x83 = 0xc1
+        assert_eq!(field_mul(0x57, 0x83, 0b100011011, 8), 0xc1);
+    }
+
+    #[test]
+    fn field_tables_reject_reducible() {
+        // x^4 + x^2 + x = x (x^3 + x + 1) is reducible
+        assert!(field_tables(0b10110, 4).is_none());
+        // degree does not match k
+        assert!(field_tables(0b10011, 8).is_none());
+        // multiplication still works through the fallback path
+        assert_eq!(field_mul(3, 3, 0b10110, 4), 5);
+    }
+
+    #[test]
+    fn gfpk_is_field() {
+        // every nonzero element has an inverse modulo an irreducible polynomial
+        for &(p, k, poly) in [(3u16, 2u8, 5), (3, 3, 7), (5, 2, 2), (7, 2, 1)].iter() {
+            let q = p.pow(k as u32);
+            for a in 1..q {
+                assert!((1..q).any(|b| gfpk_mul(a, b, p, k, poly) == 1));
+                assert_eq!(gfpk_add(a, gfpk_neg(a, p, k), p, k), 0);
+            }
+        }
+        // but not modulo x^2 + 2 = (x + 1)(x + 2) over GF(3)
+        assert_eq!(gfpk_mul(3 + 1, 3 + 2, 3, 2, 2), 0);
+    }
 }
 
 #[cfg(all(feature = "nightly", test))]
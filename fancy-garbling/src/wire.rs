@@ -6,11 +6,18 @@
 
 //! Low-level operations on wire-labels, the basic building block of garbled circuits.
 
-use crate::{fancy::HasModulus, util};
+use crate::{
+    errors::{FancyError, WireDecodingError},
+    fancy::HasModulus,
+    util,
+};
+use alloc::vec::Vec;
 use rand::{CryptoRng, Rng, RngCore};
-use scuttlebutt::{Block, AES_HASH};
+use crate::hash::{FixedKeyAes, GateHash};
+use scuttlebutt::Block;
 
 mod npaths_tab;
+mod simd;
 
 /// The core wire-label type.
 #[derive(Debug, Clone, PartialEq)]
@@ -47,17 +54,26 @@ pub enum Wire {
         /// A list of `mod-q` digits.
         ds: Vec<u16>,
     },
-    /// An element in the field GF(2^k) is represented by the coefficients of the polynomial
-    /// EXAMPLE: x^3 + x + 1 in GF(2^4)
-    ///   ===>   Elt: u16 = (0000...0 1 0 1 1) = 11
-    /// 
-    /// 
-    /// Representation of a wire in GF(2^4)
+    /// Representation of a `mod-q` wire for a modulus `q` too large for a
+    /// `u16`, which only supports linear operations.
+    ///
+    /// Like `ModN`, it is the modulus `q` alongside a list of `mod-q` digits.
+    ModBig {
+        /// The modulus of this wire-label, below `2^32`.
+        q: u64,
+        /// A list of `mod-q` digits.
+        ds: Vec<u32>,
+    },
+    /// Representation of a wire in GF(2^4).
+    ///
+    /// We represent a GF(2^4) wire by 32 elements, bit-sliced like `Mod3`: bit
+    /// `i` of `planes[j]` is the coefficient of `X^j` in element `i`. Addition
+    /// is then four XORs, and multiplication by a constant a few more.
     GF4 {
         /// Irreducible polynomial.
         p: u8,
-        /// A list of GF(2^4) elements.
-        elts: Vec<u16>,
+        /// The four bit-planes of the GF(2^4) elements.
+        planes: [u32; 4],
     },
     /// Representation of a wire in GF(2^8)
     GF8 {
@@ -66,7 +82,11 @@ pub enum Wire {
         /// A list of GF(2^4) elements
         elts: Vec<u16>,
     },
-    /// Representation of a wire in GF(2^k)
+    /// Representation of a wire in GF(2^k).
+    ///
+    /// An element in the field GF(2^k) is represented by the coefficients of
+    /// the polynomial, so `x^3 + x + 1` in GF(2^4) is the `u16`
+    /// `(0000...0 1 0 1 1) = 11`.
     GFk {
         /// k
         k: u8,
@@ -75,16 +95,45 @@ pub enum Wire {
         /// A list of GF(2^4) elements.
         elts: Vec<u16>,
     },
+    /// Representation of a wire in GF(2^16)
+    GF16 {
+        /// Irreducible polynomial, without its leading `X^16` term.
+        p: u16,
+        /// A list of GF(2^16) elements.
+        elts: Vec<u16>,
+    },
+    /// Representation of a wire in GF(p^k) for an odd prime `p`. An element is
+    /// the base-`p` encoding of its coefficients, so `x^2 + 2` in GF(3^3) is
+    /// `1 * 9 + 0 * 3 + 2 = 11`.
+    GFpk {
+        /// The characteristic.
+        p: u16,
+        /// The degree of the extension.
+        k: u8,
+        /// Irreducible monic polynomial of degree `k`, without its leading term
+        /// and base-`p` encoded like the elements.
+        poly: u16,
+        /// A list of GF(p^k) elements.
+        elts: Vec<u16>,
+    },
 }
 
 /// Modulus type, either an integer modulus for Zq, or an irreducible polynomial representation for GF(2^k)
-#[derive(Copy, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Copy, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 pub enum Modulus {
     /// Integer modulus for Zq.
     Zq {
         q: u16,
     },
+    /// Integer modulus for Zq with `q` too large for a `u16`, but below `2^32`.
+    ///
+    /// Wires of such moduli support constants, addition, subtraction, constant
+    /// multiplication, and outputs, but neither multiplication nor projection,
+    /// whose garbled tables would have `q` rows.
+    ZqBig {
+        q: u64,
+    },
     /// Irreducible polynomial for GF(2^4).
     GF4 {
         p: u8,
@@ -98,20 +147,36 @@ pub enum Modulus {
         k: u8, 
         p: u16,
     },
+    /// Irreducible polynomial for GF(2^16). The leading `X^16` term does not fit
+    /// in a `u16` and is left implicit.
+    GF16 {
+        p: u16,
+    },
+    /// Irreducible monic polynomial of degree `k` over GF(p), for GF(p^k). Its
+    /// leading term is implicit and the rest is base-`p` encoded, see
+    /// `Wire::GFpk`. `p^k` must fit in a `u16`.
+    GFpk {
+        p: u16,
+        k: u8,
+        poly: u16,
+    },
 }
 
-impl std::fmt::Display for Modulus {
-    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+impl core::fmt::Display for Modulus {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
         match *self {
             Modulus::Zq { q } => write!(fmt, "Zq(q={})", q),
+            Modulus::ZqBig { q } => write!(fmt, "ZqBig(q={})", q),
             Modulus::GF4 { p } => write!(fmt, "GF4(p={})", p),
             Modulus::GF8 { p } => write!(fmt, "GF8(p={})",p),
             Modulus::GFk { k, p } => write!(fmt, "GF2^{}(p={})",k,p),
+            Modulus::GF16 { p } => write!(fmt, "GF16(p={})", p as u32 | 1 << 16),
+            Modulus::GFpk { p, k, poly } => write!(fmt, "GF{}^{}(poly={})", p, k, poly),
         }
     }
 }
 
-impl std::default::Default for Wire {
+impl core::default::Default for Wire {
     fn default() -> Self {
         Wire::Mod2 {
             val: Block::default(),
@@ -127,46 +192,108 @@ impl Modulus {
     /// Irreducible polynomial X^4 + X^3 + X^2 + X + 1 in GF(2^4)
     pub const X4_X3_X2_X_1: Modulus = Modulus::GF4 {p: 0b11111};
 
+    /// Irreducible polynomial X^16 + X^5 + X^3 + X^2 + 1 in GF(2^16)
+    pub const X16_X5_X3_X2_1: Modulus = Modulus::GF16 {p: 0b101101};
+
     /// All moduli of GF(2^4)
     pub const GF4_MODULI: [Modulus; 3] = [Self::X4_X_1, Self::X4_X2_1, Self::X4_X3_X2_X_1];
 
-    /// All moduli of GF(2^8)
-    pub const GF8_MODULI: [Modulus; 30] = [
-        Modulus::GF8 { p: 0b100011011 }, Modulus::GF8 { p: 0b100011101 }, Modulus::GF8 { p: 0b100101011 },
-        Modulus::GF8 { p: 0b100101101 }, Modulus::GF8 { p: 0b100111001 }, Modulus::GF8 { p: 0b100111111 },
-        Modulus::GF8 { p: 0b101001101 }, Modulus::GF8 { p: 0b101011111 }, Modulus::GF8 { p: 0b101100011 },
-        Modulus::GF8 { p: 0b101100101 }, Modulus::GF8 { p: 0b101101001 }, Modulus::GF8 { p: 0b101110001 },
-        Modulus::GF8 { p: 0b101110111 }, Modulus::GF8 { p: 0b101111011 }, Modulus::GF8 { p: 0b110000111 },
-        Modulus::GF8 { p: 0b110001011 }, Modulus::GF8 { p: 0b110001101 }, Modulus::GF8 { p: 0b110011111 },
-        Modulus::GF8 { p: 0b110100011 }, Modulus::GF8 { p: 0b110101001 }, Modulus::GF8 { p: 0b110110001 },
-        Modulus::GF8 { p: 0b110111101 }, Modulus::GF8 { p: 0b111000011 }, Modulus::GF8 { p: 0b111001111 },
-        Modulus::GF8 { p: 0b111010111 }, Modulus::GF8 { p: 0b111011101 }, Modulus::GF8 { p: 0b111100111 },
-        Modulus::GF8 { p: 0b111110011 }, Modulus::GF8 { p: 0b111110101 }, Modulus::GF8 { p: 0b111111001 },
-    ];
+    /// All moduli of GF(2^8), in increasing order.
+    pub const GF8_MODULI: [Modulus; 30] = Self::gf8_moduli();
+
+    const fn gf8_moduli() -> [Modulus; 30] {
+        let mut moduli = [Modulus::GF8 { p: 0 }; 30];
+        let mut i = 0;
+        let mut p = 1 << 8;
+        while p < 1 << 9 {
+            if util::is_irreducible(p, 8) {
+                moduli[i] = Modulus::GF8 { p };
+                i += 1;
+            }
+            p += 1;
+        }
+        moduli
+    }
+
+    /// A modulus for GF(2^k), checking that `p` is an irreducible polynomial of
+    /// degree `k` for 2 <= k <= 16.
+    ///
+    /// Returns the dedicated variant when `k` is 4, 8 or 16. As for `GF16`, the
+    /// leading term of `p` is implicit when `k = 16`.
+    pub fn try_new_gfk(p: u16, k: u8) -> Result<Modulus, FancyError> {
+        if !(2..=16).contains(&k) {
+            return Err(FancyError::InvalidArg(format!("GF(2^{}) is not supported", k)));
+        }
+        if !util::is_irreducible(p, k) {
+            return Err(FancyError::InvalidArg(format!(
+                "{:#b} is not an irreducible polynomial of degree {}",
+                p, k
+            )));
+        }
+        Ok(match k {
+            4 => Modulus::GF4 { p: p as u8 },
+            8 => Modulus::GF8 { p },
+            16 => Modulus::GF16 { p },
+            _ => Modulus::GFk { k, p },
+        })
+    }
+
+    /// All irreducible polynomials of degree `k` for 2 <= k <= 16, in increasing
+    /// order and in the representation taken by `try_new_gfk`.
+    pub fn irreducible_polys(k: u8) -> Vec<u16> {
+        match k {
+            2..=15 => (1u32 << k..1 << (k + 1))
+                .map(|p| p as u16)
+                .filter(|&p| util::is_irreducible(p, k))
+                .collect(),
+            16 => (0..=u16::MAX).filter(|&p| util::is_irreducible(p, k)).collect(),
+            _ => Vec::new(),
+        }
+    }
 
     pub fn value(&self) -> u16 {
         match self {
             Modulus::Zq { q } => *q,
+            Modulus::ZqBig { .. } => panic!("[Modulus::value] ZqBig does not fit in a u16"),
             Modulus::GF4 { p } => *p as u16 ,
             Modulus::GF8 { p } => *p,
             Modulus::GFk { k: _, p } => *p,
+            Modulus::GF16 { p } => *p,
+            Modulus::GFpk { poly, .. } => *poly,
         }
     }
 
+    /// The number of elements, as a `u16`.
+    ///
+    /// Panics for `GF16` and `ZqBig`, whose elements do not fit; use `order`
+    /// instead.
     pub fn size(&self) -> u16 {
         match self {
             Modulus::Zq { q } => *q,
+            Modulus::ZqBig { .. } => panic!("[Modulus::size] ZqBig does not fit in a u16, use order"),
             Modulus::GF4 { .. } => 16 ,
             Modulus::GF8 { .. } => 256,
             Modulus::GFk { k, .. } => 2_u16.pow(*k as u32),
+            Modulus::GF16 { .. } => panic!("[Modulus::size] GF(2^16) has 2^16 elements, use order"),
+            Modulus::GFpk { p, k, .. } => p.pow(*k as u32),
+        }
+    }
+
+    /// The number of elements.
+    pub fn order(&self) -> u32 {
+        match self {
+            Modulus::GF16 { .. } => 1 << 16,
+            Modulus::ZqBig { q } => *q as u32,
+            _ => self.size() as u32,
         }
     }
 
-    /// Returns true if the modulus is GF(2^n) for some n
+    /// Returns true if the modulus is GF(2^n) or GF(p^n) for some n
     pub fn is_field(&self) -> bool {
         match self {
-            Modulus::Zq {..} => false,
-            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} => true
+            Modulus::Zq {..} | Modulus::ZqBig {..} => false,
+            Modulus::GF4 {..} | Modulus::GF8 {..} | Modulus::GFk {..} | Modulus::GF16 {..} => true,
+            Modulus::GFpk {..} => true,
         }
     }
 
@@ -174,9 +301,12 @@ impl Modulus {
     pub fn bit_length(&self) -> usize {
         match self {
             Modulus::Zq{ q } => f32::from(*q).log(2.0).ceil() as usize,
+            Modulus::ZqBig { q } => 64 - (q - 1).leading_zeros() as usize,
             Modulus::GF4 { .. } => 4,
             Modulus::GF8 { .. } => 8,
             Modulus::GFk { k, .. } => (*k).into(),
+            Modulus::GF16 { .. } => 16,
+            Modulus::GFpk { .. } => 32 - (self.order() - 1).leading_zeros() as usize,
         }
     }
 }
@@ -186,9 +316,12 @@ impl HasModulus for Wire {
             Wire::Mod2 { .. } => Modulus::Zq { q: 2 },
             Wire::Mod3 { .. } => Modulus::Zq { q: 3 },
             Wire::ModN { q, .. } => Modulus::Zq { q: *q },
+            Wire::ModBig { q, .. } => Modulus::ZqBig { q: *q },
             Wire::GF4 { p, .. } => Modulus::GF4 { p: *p },
             Wire::GF8 { p, .. } =>  Modulus::GF8 { p: *p },
             Wire::GFk { k, p, .. } => Modulus::GFk { k: *k, p: *p },
+            Wire::GF16 { p, .. } => Modulus::GF16 { p: *p },
+            Wire::GFpk { p, k, poly, .. } => Modulus::GFpk { p: *p, k: *k, poly: *poly },
         }
     }
 }
@@ -204,9 +337,12 @@ impl Wire {
                 .map(|i| (((lsb >> i) as u16) & 1) & ((((msb >> i) as u16) & 1) << 1))
                 .collect(),
             Wire::ModN { ds, .. } => ds.clone(),
-            Wire::GF4 { elts, .. } => elts.clone(),
+            Wire::ModBig { .. } => panic!("[Wire::digits] ModBig digits do not fit in a u16"),
+            Wire::GF4 { planes, .. } => util::as_base_q_u128(util::gf4_unbitslice(planes), 16),
             Wire::GF8 { elts, .. } => elts.clone(),
             Wire::GFk { elts, ..} => elts.clone(),
+            Wire::GF16 { elts, .. } => elts.clone(),
+            Wire::GFpk { elts, .. } => elts.clone(),
         }
     }
 
@@ -284,9 +420,23 @@ impl Wire {
     pub fn from_block(inp: Block, modulus: &Modulus) -> Self {
         match *modulus {
             Modulus::Zq { q } => Wire::from_block_mod(inp, q),
-            Modulus::GF4 { p } => Wire::from_block_GFk(inp, p.into(), 4),
+            Modulus::ZqBig { q } => Wire::ModBig {
+                q,
+                ds: util::as_base_q_big(u128::from(inp), q),
+            },
+            Modulus::GF4 { p } => Wire::GF4 {
+                p,
+                planes: util::gf4_bitslice(u128::from(inp)),
+            },
             Modulus::GF8 { p } => Wire::from_block_GFk(inp, p, 8),
             Modulus::GFk { k, p } => Wire::from_block_GFk(inp, p, k),
+            Modulus::GF16 { p } => Wire::from_block_GFk(inp, p, 16),
+            Modulus::GFpk { p, k, poly } => Wire::GFpk {
+                p,
+                k,
+                poly,
+                elts: util::as_base_q_u128(u128::from(inp), modulus.size()),
+            },
         }
     }
 
@@ -322,18 +472,21 @@ impl Wire {
     }
 
     fn from_block_GFk(inp: Block, p: u16, k: u8) -> Self {
+        if k == 4 {
+            return Wire::from_block(inp, &Modulus::GF4 { p: p as u8 });
+        }
         let mut inp = u128::from(inp);
         let mut elts: Vec<u16> = Vec::new();
         let length = 128 / k;
-        let mask = ((1 << k)-1) as u16;
+        let mask = ((1u32 << k) - 1) as u16;
         for _ in 0..length {
             elts.push((inp & mask as u128) as u16);
             inp >>= k;
         }
 
         match k {
-            4 => Wire::GF4 { p: p as u8, elts },
             8 => Wire::GF8 { p, elts },
+            16 => Wire::GF16 { p, elts },
             _ => Wire::GFk { k, p, elts },
             }
         }
@@ -345,9 +498,12 @@ impl Wire {
             Wire::Mod2 { val } => *val,
             Wire::Mod3 { lsb, msb } => Block::from(((*msb as u128) << 64) | (*lsb as u128)),
             Wire::ModN { q, ref ds } => Block::from(util::from_base_q(ds, *q)),
-            Wire::GF4 { p, elts } => Block::from(util::from_poly_p_array(elts, *p as u16, 4)),
+            Wire::ModBig { q, ref ds } => Block::from(util::from_base_q_big(ds, *q)),
+            Wire::GF4 { planes, .. } => Block::from(util::gf4_unbitslice(planes)),
             Wire::GF8 { p, elts } => Block::from(util::from_poly_p_array(elts, *p as u16, 8)),
             Wire::GFk { k, p, elts } => Block::from(util::from_poly_p_u128(elts, *p as u16, *k)),
+            Wire::GF16 { elts, .. } => Block::from(util::from_poly_p_u128(elts, 0, 16)),
+            Wire::GFpk { p, k, elts, .. } => Block::from(util::from_base_q(elts, p.pow(*k as u32))),
         }
     }
 
@@ -367,9 +523,13 @@ impl Wire {
                 q,
                 ds: vec![0; util::digits_per_u128(q)],
             },
-            Modulus::GF4 { p } => Wire::GF4 { 
+            Modulus::ZqBig { q } => Wire::ModBig {
+                q,
+                ds: vec![0; util::digits_per_u128_big(q)],
+            },
+            Modulus::GF4 { p } => Wire::GF4 {
                 p,
-                elts: vec![0; 32],
+                planes: [0; 4],
             },
             Modulus::GF8 { p } => Wire::GF8 {
                 p, 
@@ -380,6 +540,16 @@ impl Wire {
                 p, 
                 elts: vec![0; (128/k).into()],
             },
+            Modulus::GF16 { p } => Wire::GF16 {
+                p,
+                elts: vec![0; 8],
+            },
+            Modulus::GFpk { p, k, poly } => Wire::GFpk {
+                p,
+                k,
+                poly,
+                elts: vec![0; util::digits_per_u128(modulus.size())],
+            },
         }
     }
 
@@ -399,14 +569,25 @@ impl Wire {
                 *msb &= 0xFFFF_FFFF_FFFF_FFFE;
             }
             Wire::ModN { ref mut ds, .. } => ds[0] = 1,
-            Wire::GF4 { ref mut elts, .. } => elts[0] = 1,
+            Wire::ModBig { ref mut ds, .. } => ds[0] = 1,
+            Wire::GF4 { ref mut planes, .. } => {
+                // The color is the first element, so set its lowest bit and
+                // clear the rest.
+                planes[0] |= 1;
+                planes[1..].iter_mut().for_each(|x| *x &= !1);
+            }
             Wire::GF8 { ref mut elts, .. } => elts[0] = 1,
             Wire::GFk { ref mut elts, .. } => elts[0] = 1,
+            Wire::GF16 { ref mut elts, .. } => elts[0] = 1,
+            Wire::GFpk { ref mut elts, .. } => elts[0] = 1,
         }
         w
     }
 
     /// Get the color digit of the wire.
+    ///
+    /// Panics for `ModBig` wires, whose color may not fit; use `color_big`
+    /// instead.
     pub fn color(&self) -> u16 {
         match self {
             Wire::Mod2 { val } => val.lsb() as u16,
@@ -420,11 +601,12 @@ impl Wire {
                 debug_assert!(color < *q);
                 color
             }
-            Wire::GF4 { ref elts, .. } => {
-                let color = elts[0];
-                debug_assert!(color < 16);
-                color
-            }
+            Wire::ModBig { .. } => panic!("[Wire::color] ModBig colors do not fit in a u16, use color_big"),
+            Wire::GF4 { ref planes, .. } => planes
+                .iter()
+                .enumerate()
+                .map(|(j, x)| ((x & 1) as u16) << j)
+                .sum(),
             Wire::GF8 { ref elts, .. } => {
                 let color = elts[0];
                 debug_assert!(color < 256);
@@ -435,6 +617,16 @@ impl Wire {
                 debug_assert!(color < 2_u16.pow(*k as u32));
                 color
             }
+            Wire::GF16 { ref elts, .. } => elts[0],
+            Wire::GFpk { ref elts, .. } => elts[0],
+        }
+    }
+
+    /// Get the color digit of the wire, for wires of any modulus.
+    pub fn color_big(&self) -> u64 {
+        match self {
+            Wire::ModBig { ref ds, .. } => ds[0] as u64,
+            _ => self.color() as u64,
         }
     }
 
@@ -483,15 +675,28 @@ impl Wire {
                 });
             }
             (
-                Wire::GF4 { p: ref xpoly, elts: ref mut xs },
-                Wire::GF4 { p: ref ypoly, elts: ref ys},
+                Wire::ModBig {
+                    q: ref xmod,
+                    ds: ref mut xs,
+                },
+                Wire::ModBig {
+                    q: ref ymod,
+                    ds: ref ys,
+                },
+            ) => {
+                debug_assert_eq!(xmod, ymod);
+                debug_assert_eq!(xs.len(), ys.len());
+                xs.iter_mut()
+                    .zip(ys.iter())
+                    .for_each(|(x, &y)| *x = ((*x as u64 + y as u64) % *xmod) as u32);
+            }
+            (
+                Wire::GF4 { p: ref xpoly, planes: ref mut xs },
+                Wire::GF4 { p: ref ypoly, planes: ref ys },
             ) => {
                 // Because we work in F(2^k), this is just a bitwise addition in F2.
                 debug_assert_eq!(xpoly, ypoly);
-                debug_assert_eq!(xs.len(), ys.len());
-                xs.iter_mut().zip(ys.iter()).for_each(|(x,&y)| {
-                    *x ^= y;
-                });
+                xs.iter_mut().zip(ys.iter()).for_each(|(x, &y)| *x ^= y);
             },
             (
                 Wire::GF8 { p: ref xpoly, elts: ref mut xs },
@@ -501,12 +706,24 @@ impl Wire {
                 Wire::GFk { p: ref xpoly, elts: ref mut xs, .. },
                 Wire::GFk { p: ref ypoly, elts: ref ys, .. },
             )
+            | (
+                Wire::GF16 { p: ref xpoly, elts: ref mut xs },
+                Wire::GF16 { p: ref ypoly, elts: ref ys },
+            )
             => {
                 // Because we work in F(2^k), this is just a bitwise addition in F2. 
                 debug_assert_eq!(xpoly, ypoly);
-                debug_assert_eq!(xs.len(), ys.len());                
-                xs.iter_mut().zip(ys.iter()).for_each(|(x,&y)| {
-                    *x ^= y;
+                simd::xor_eq(xs, ys);
+            }
+            (
+                Wire::GFpk { p, k, poly: ref xpoly, elts: ref mut xs },
+                Wire::GFpk { poly: ref ypoly, elts: ref ys, .. },
+            ) => {
+                // Coefficients add mod p.
+                debug_assert_eq!(xpoly, ypoly);
+                debug_assert_eq!(xs.len(), ys.len());
+                xs.iter_mut().zip(ys.iter()).for_each(|(x, &y)| {
+                    *x = util::gfpk_add(*x, y, *p, *k);
                 });
             }
             _ => panic!("[Wire::plus_eq] unequal moduli!"),
@@ -542,7 +759,7 @@ impl Wire {
                 2 => {
                     // Multiplication by two is the same as negation in `mod-3`,
                     // which just involves swapping `lsb` and `msb`.
-                    std::mem::swap(lsb, msb);
+                    core::mem::swap(lsb, msb);
                 }
                 c => {
                     self.cmul_eq(c % 3);
@@ -552,22 +769,15 @@ impl Wire {
                 ds.iter_mut()
                     .for_each(|d| *d = (*d as u32 * c as u32 % *q as u32) as u16);
             },
-            // Uses the field_mul function to multiply to elements(polynomials)
-            Wire::GF4 { p, elts } => {
-                elts.iter_mut().for_each(|d| {
-                    *d = util::field_mul(*d, c, (*p).into(), 4) as u16;
-                });
-            }
-            Wire::GF8 { p, elts } => {
-                elts.iter_mut().for_each(|d| {
-                    *d = util::field_mul(*d, c, *p, 8) as u16;
-                });
-            }
-            Wire::GFk {k, p, elts} => {
-                elts.iter_mut().for_each(|d| {
-                    *d = util::field_mul(*d, c, *p, *k) as u16;
-                });
+            Wire::ModBig { .. } => {
+                self.cmul_big_eq(c as u64);
             }
+            // Multiplies the elements (polynomials) in constant time, since they are secret
+            Wire::GF4 { p, planes } => util::gf4_bitsliced_cmul_eq(planes, c, (*p).into()),
+            Wire::GF8 { p, elts } => simd::cmul_eq(elts, c, *p, 8),
+            Wire::GFk { k, p, elts } => util::field_cmul_eq(elts, c, *p, *k),
+            Wire::GF16 { p, elts } => util::field_cmul_eq(elts, c, *p, 16),
+            Wire::GFpk { p, k, poly, elts } => util::gfpk_cmul_eq(elts, c, *p, *k, *poly),
         }
         self
     }
@@ -578,6 +788,32 @@ impl Wire {
         self
     }
 
+    /// Multiply each digit by a constant `c mod q` which may not fit in a `u16`,
+    /// returning a new wire.
+    pub fn cmul_big(&self, c: u64) -> Self {
+        let mut w = self.clone();
+        w.cmul_big_eq(c);
+        w
+    }
+
+    /// Multiply each digit by a constant `c mod q` which may not fit in a `u16`.
+    ///
+    /// For moduli other than `ZqBig`, `c` is first reduced modulo the order.
+    pub fn cmul_big_eq(&mut self, c: u64) -> &mut Wire {
+        match self {
+            Wire::ModBig { q, ds } => {
+                let c = c % *q;
+                ds.iter_mut()
+                    .for_each(|d| *d = (*d as u64 * c % *q) as u32);
+            }
+            _ => {
+                let c = c % self.modulus().order() as u64;
+                self.cmul_eq(c as u16);
+            }
+        }
+        self
+    }
+
     /// Negate all the digits `mod q`, returning a new wire.
     pub fn negate(&self) -> Self {
         self.clone().negate_mov()
@@ -591,7 +827,7 @@ impl Wire {
             }
             Wire::Mod3 { lsb, msb } => {
                 // Negation just involves swapping `lsb` and `msb`.
-                std::mem::swap(lsb, msb);
+                core::mem::swap(lsb, msb);
             }
             Wire::ModN { q, ds } => {
                 ds.iter_mut().for_each(|d| {
@@ -602,9 +838,15 @@ impl Wire {
                     }
                 });
             }
-            Wire::GF4 { .. } | Wire::GF8 { .. } | Wire::GFk { .. } => {
+            Wire::ModBig { q, ds } => {
+                ds.iter_mut().for_each(|d| *d = ((*q - *d as u64) % *q) as u32);
+            }
+            Wire::GF4 { .. } | Wire::GF8 { .. } | Wire::GFk { .. } | Wire::GF16 { .. } => {
                 // Do nothing. Additive inverse is a no-op for coefficients with mod 2.
             }
+            Wire::GFpk { p, k, elts, .. } => {
+                elts.iter_mut().for_each(|x| *x = util::gfpk_neg(*x, *p, *k));
+            }
         }
         self
     }
@@ -655,13 +897,17 @@ impl Wire {
                     Wire::ModN { q, ds }
                 }
             },
+            Modulus::ZqBig { q } => {
+                let ds = (0..util::digits_per_u128_big(q))
+                    .map(|_| (rng.gen::<u64>() % q) as u32)
+                    .collect();
+                Wire::ModBig { q, ds }
+            },
             // Generate random number and and it with a mask to make it 
             // a random element in the field.
-            Modulus::GF4 { p } => {
-                let elts = (0..32)
-                    .map(|_| (rng.gen::<u8>()&(15)) as u16)
-                    .collect();
-                Wire::GF4 { p, elts }
+            Modulus::GF4 { p } => Wire::GF4 {
+                p,
+                planes: [rng.gen(), rng.gen(), rng.gen(), rng.gen()],
             },
             Modulus::GF8 { p } => {
                 let elts = (0..16)
@@ -675,6 +921,17 @@ impl Wire {
                     .collect();
                 Wire::GFk { k, p, elts }
             },
+            Modulus::GF16 { p } => {
+                let elts = (0..8).map(|_| rng.gen::<u16>()).collect();
+                Wire::GF16 { p, elts }
+            },
+            Modulus::GFpk { p, k, poly } => {
+                let q = modulus.size();
+                let elts = (0..util::digits_per_u128(q))
+                    .map(|_| rng.gen::<u16>() % q)
+                    .collect();
+                Wire::GFpk { p, k, poly, elts }
+            },
         }
     }
 
@@ -683,14 +940,31 @@ impl Wire {
     /// Uses fixed-key AES.
     #[inline(never)]
     pub fn hash(&self, tweak: Block) -> Block {
-        AES_HASH.tccr_hash(tweak, self.as_block())
+        self.hash_with(&FixedKeyAes, tweak)
+    }
+
+    /// Compute the hash of this wire using `hasher`.
+    #[inline]
+    pub fn hash_with<H: GateHash + ?Sized>(&self, hasher: &H, tweak: Block) -> Block {
+        hasher.hash(tweak, self.as_block())
     }
 
     /// Compute the hash of this wire, converting the result back to a wire.
     ///
     /// Uses fixed-key AES.
     pub fn hashback(&self, tweak: Block, modulus: &Modulus) -> Wire {
-        let block = self.hash(tweak);
+        self.hashback_with(&FixedKeyAes, tweak, modulus)
+    }
+
+    /// Compute the hash of this wire using `hasher`, converting the result back
+    /// to a wire.
+    pub fn hashback_with<H: GateHash + ?Sized>(
+        &self,
+        hasher: &H,
+        tweak: Block,
+        modulus: &Modulus,
+    ) -> Wire {
+        let block = self.hash_with(hasher, tweak);
         if let Modulus::Zq {q: 3} = modulus {
             // We have to convert `block` into a valid `Mod3` encoding. We do
             // this by computing the `Mod3` digits using `_unrank`, and then map
@@ -707,6 +981,203 @@ impl Wire {
         }
         Self::from_block(block, modulus)
     }
+
+    /// Like `hashback`, but shifted so that the result has color `color`.
+    ///
+    /// Privacy-free garbling keeps every zero label at color `0`, so it needs
+    /// hashes of a chosen color. Only defined for `Zq` moduli, whose labels
+    /// with a single leading `1` digit have color `1`.
+    #[cfg(feature = "std")]
+    pub(crate) fn hashback_with_color<H: GateHash + ?Sized>(
+        &self,
+        hasher: &H,
+        tweak: Block,
+        modulus: &Modulus,
+        color: u16,
+    ) -> Wire {
+        let h = self.hashback_with(hasher, tweak, modulus);
+        let q = u32::from(modulus.size());
+        let shift = (u32::from(color) + q - u32::from(h.color())) % q;
+        let unit = Wire::from_block(Block::from(1u128), modulus);
+        h.plus_mov(&unit.cmul(shift as u16))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// serialization
+
+/// Version of the binary encoding produced by `Wire::to_bytes`.
+const WIRE_ENCODING_VERSION: u8 = 1;
+
+impl Modulus {
+    // Tag and little-endian parameters of the modulus, as used by
+    // `Wire::to_bytes` and `Circuit::save`.
+    pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+        match *self {
+            Modulus::Zq { q } => {
+                out.push(0);
+                out.extend_from_slice(&q.to_le_bytes());
+            }
+            Modulus::GF4 { p } => out.extend_from_slice(&[1, p]),
+            Modulus::GF8 { p } => {
+                out.push(2);
+                out.extend_from_slice(&p.to_le_bytes());
+            }
+            Modulus::GFk { k, p } => {
+                out.extend_from_slice(&[3, k]);
+                out.extend_from_slice(&p.to_le_bytes());
+            }
+            Modulus::GF16 { p } => {
+                out.push(4);
+                out.extend_from_slice(&p.to_le_bytes());
+            }
+            Modulus::GFpk { p, k, poly } => {
+                out.push(5);
+                out.extend_from_slice(&p.to_le_bytes());
+                out.push(k);
+                out.extend_from_slice(&poly.to_le_bytes());
+            }
+            Modulus::ZqBig { q } => {
+                out.push(6);
+                out.extend_from_slice(&q.to_le_bytes());
+            }
+        }
+    }
+
+    // Inverse of `write_bytes`, returning the modulus and the remaining bytes.
+    pub(crate) fn read_bytes(bytes: &[u8]) -> Result<(Modulus, &[u8]), WireDecodingError> {
+        let (&tag, rest) = bytes.split_first().ok_or(WireDecodingError::InvalidLength)?;
+        let mut rest = rest;
+        let mut take = |n: usize| -> Result<&[u8], WireDecodingError> {
+            if rest.len() < n {
+                return Err(WireDecodingError::InvalidLength);
+            }
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            Ok(head)
+        };
+        let u16_at = |b: &[u8]| u16::from_le_bytes([b[0], b[1]]);
+        let modulus = match tag {
+            0 => Modulus::Zq { q: u16_at(take(2)?) },
+            1 => Modulus::GF4 { p: take(1)?[0] },
+            2 => Modulus::GF8 { p: u16_at(take(2)?) },
+            3 => {
+                let k = take(1)?[0];
+                Modulus::GFk { k, p: u16_at(take(2)?) }
+            }
+            4 => Modulus::GF16 { p: u16_at(take(2)?) },
+            5 => {
+                let p = u16_at(take(2)?);
+                let k = take(1)?[0];
+                Modulus::GFpk { p, k, poly: u16_at(take(2)?) }
+            }
+            6 => {
+                let mut q = [0; 8];
+                q.copy_from_slice(take(8)?);
+                Modulus::ZqBig { q: u64::from_le_bytes(q) }
+            }
+            t => return Err(WireDecodingError::UnknownModulus(t)),
+        };
+        let valid = match modulus {
+            Modulus::Zq { q } => q >= 2,
+            Modulus::ZqBig { q } => (2..1 << 32).contains(&q),
+            Modulus::GFk { k, .. } => (1..16).contains(&k),
+            Modulus::GFpk { p, k, .. } => {
+                p >= 2 && k >= 1 && (p as u32).checked_pow(k as u32).is_some_and(|n| n <= 1 << 16)
+            }
+            _ => true,
+        };
+        if !valid {
+            return Err(WireDecodingError::InvalidModulus);
+        }
+        Ok((modulus, rest))
+    }
+}
+
+impl Wire {
+    /// Serialize the wire into a compact binary encoding.
+    ///
+    /// The encoding is a version byte, a modulus tag with its parameters (at
+    /// most 9 bytes), and the 16 bytes of `as_block` in little-endian order.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![WIRE_ENCODING_VERSION];
+        self.write_bytes(&mut out);
+        out
+    }
+
+    /// Deserialize a wire produced by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Wire, WireDecodingError> {
+        let rest = Self::check_version(bytes)?;
+        let (w, rest) = Self::read_bytes(rest)?;
+        if !rest.is_empty() {
+            return Err(WireDecodingError::InvalidLength);
+        }
+        Ok(w)
+    }
+
+    /// Serialize many wires into a single buffer.
+    ///
+    /// The version byte is written once, followed by the number of wires as a
+    /// little-endian `u64` and the encoding of each wire without its version.
+    pub fn vec_to_bytes(wires: &[Wire]) -> Vec<u8> {
+        let mut out = vec![WIRE_ENCODING_VERSION];
+        out.extend_from_slice(&(wires.len() as u64).to_le_bytes());
+        for w in wires.iter() {
+            w.write_bytes(&mut out);
+        }
+        out
+    }
+
+    /// Deserialize many wires produced by `vec_to_bytes`.
+    pub fn vec_from_bytes(bytes: &[u8]) -> Result<Vec<Wire>, WireDecodingError> {
+        let rest = Self::check_version(bytes)?;
+        if rest.len() < 8 {
+            return Err(WireDecodingError::InvalidLength);
+        }
+        let (n, mut rest) = rest.split_at(8);
+        let mut len = [0; 8];
+        len.copy_from_slice(n);
+        let n = u64::from_le_bytes(len) as usize;
+        // each wire takes at least 18 bytes, which bounds the allocation
+        if n > rest.len() / 18 {
+            return Err(WireDecodingError::InvalidLength);
+        }
+        let mut wires = Vec::with_capacity(n);
+        for _ in 0..n {
+            let (w, tail) = Self::read_bytes(rest)?;
+            wires.push(w);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(WireDecodingError::InvalidLength);
+        }
+        Ok(wires)
+    }
+
+    fn check_version(bytes: &[u8]) -> Result<&[u8], WireDecodingError> {
+        match bytes.split_first() {
+            Some((&WIRE_ENCODING_VERSION, rest)) => Ok(rest),
+            Some((&v, _)) => Err(WireDecodingError::UnsupportedVersion(v)),
+            None => Err(WireDecodingError::InvalidLength),
+        }
+    }
+
+    pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+        self.modulus().write_bytes(out);
+        out.extend_from_slice(&u128::from(self.as_block()).to_le_bytes());
+    }
+
+    pub(crate) fn read_bytes(bytes: &[u8]) -> Result<(Wire, &[u8]), WireDecodingError> {
+        let (modulus, rest) = Modulus::read_bytes(bytes)?;
+        if rest.len() < 16 {
+            return Err(WireDecodingError::InvalidLength);
+        }
+        let (block, rest) = rest.split_at(16);
+        let mut b = [0; 16];
+        b.copy_from_slice(block);
+        let block = Block::from(u128::from_le_bytes(b));
+        Ok((Wire::from_block(block, &modulus), rest))
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -752,6 +1223,74 @@ mod tests {
         }
     }
 
+    #[test]
+    fn packing_ZqBig() {
+        let rng = &mut thread_rng();
+        for &q in &[65537, 1 << 20, 4294967291] {
+            for _ in 0..1000 {
+                let w = Wire::rand(rng, &Modulus::ZqBig { q });
+                assert_eq!(w, Wire::from_block(w.as_block(), &Modulus::ZqBig { q }));
+            }
+        }
+    }
+
+    #[test]
+    fn serialization() {
+        let rng = &mut thread_rng();
+        let moduli = [
+            Modulus::Zq { q: 2 },
+            Modulus::Zq { q: 3 },
+            Modulus::Zq { q: 113 },
+            Modulus::ZqBig { q: 4294967291 },
+            Modulus::X4_X_1,
+            Modulus::GF8 { p: 283 },
+            Modulus::GFk { k: 5, p: 0b100101 },
+            Modulus::X16_X5_X3_X2_1,
+            GFPK_MODULI[0],
+        ];
+        let ws = moduli.iter().map(|q| Wire::rand(rng, q)).collect_vec();
+        for w in ws.iter() {
+            let bytes = w.to_bytes();
+            assert!(bytes.len() <= 26);
+            assert_eq!(Wire::from_bytes(&bytes), Ok(w.clone()));
+            assert_eq!(
+                Wire::from_bytes(&bytes[..bytes.len() - 1]),
+                Err(WireDecodingError::InvalidLength)
+            );
+        }
+        let bytes = Wire::vec_to_bytes(&ws);
+        assert_eq!(Wire::vec_from_bytes(&bytes), Ok(ws));
+        assert_eq!(Wire::vec_from_bytes(&[2]), Err(WireDecodingError::UnsupportedVersion(2)));
+        assert_eq!(
+            Wire::from_bytes(&[1, 0, 1, 0]),
+            Err(WireDecodingError::InvalidModulus)
+        );
+    }
+
+    #[test]
+    fn irreducible_polys() {
+        // the number of irreducible binary polynomials of each degree
+        let counts = [1, 2, 3, 6, 9, 18, 30, 56, 99, 186, 335, 630, 1161, 2182, 4080];
+        for (k, &n) in (2..=16).zip(counts.iter()) {
+            let polys = Modulus::irreducible_polys(k);
+            assert_eq!(polys.len(), n, "k={}", k);
+            for &p in polys.iter() {
+                assert!(Modulus::try_new_gfk(p, k).is_ok());
+            }
+        }
+        assert_eq!(
+            Modulus::irreducible_polys(8),
+            Modulus::GF8_MODULI.iter().map(|m| m.value()).collect_vec()
+        );
+        assert!(Modulus::irreducible_polys(16).contains(&Modulus::X16_X5_X3_X2_1.value()));
+        assert_eq!(Modulus::try_new_gfk(0b10011, 4).unwrap(), Modulus::X4_X_1);
+        // x^4 + x^2 + 1 = (x^2 + x + 1)^2
+        assert!(Modulus::try_new_gfk(0b10101, 4).is_err());
+        // not of degree 5
+        assert!(Modulus::try_new_gfk(0b10011, 5).is_err());
+        assert!(Modulus::try_new_gfk(0b11, 1).is_err());
+    }
+
     #[test]
     fn packing_GF4() {
         let ref mut rng = thread_rng();
@@ -795,6 +1334,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn packing_GF16() {
+        let rng = &mut thread_rng();
+        let p = Modulus::X16_X5_X3_X2_1;
+        for _ in 0..1000 {
+            let w = Wire::rand(rng, &p);
+            assert_eq!(w, Wire::from_block(w.as_block(), &p));
+        }
+    }
+
     #[test]
     fn base_conversion_lookup_method() {
         let ref mut rng = thread_rng();
@@ -840,7 +1389,7 @@ mod tests {
             let y = x.hashback(Block::from(1u128), &p);
             assert_ne!(x, y);
             match y {
-                Wire::GF4 { elts, .. } => assert!(!elts.iter().all(|&y| y == 0)),
+                Wire::GF4 { planes, .. } => assert!(!planes.iter().all(|&y| y == 0)),
                 _ => panic!(),
             }
         }
@@ -879,6 +1428,118 @@ mod tests {
         }
     }
 
+    #[test]
+    fn hash_GF16() {
+        let mut rng = thread_rng();
+        let p = Modulus::X16_X5_X3_X2_1;
+        for _ in 0..100 {
+            let x = Wire::rand(&mut rng, &p);
+            let y = x.hashback(Block::from(1u128), &p);
+            assert_ne!(x, y);
+            match y {
+                Wire::GF16 { elts, .. } => assert!(!elts.iter().all(|&y| y == 0)),
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn cmul_GF16() {
+        let mut rng = thread_rng();
+        let p = Modulus::X16_X5_X3_X2_1;
+        let tables = util::field_tables(p.value(), 16).unwrap();
+        for _ in 0..100 {
+            let x = Wire::rand(&mut rng, &p);
+            let c = rng.gen_u16();
+            let should_be = match &x {
+                Wire::GF16 { elts, .. } => elts.iter().map(|&e| tables.mul(e, c)).collect_vec(),
+                _ => panic!(),
+            };
+            match x.cmul(c) {
+                Wire::GF16 { elts, .. } => assert_eq!(elts, should_be),
+                _ => panic!(),
+            }
+        }
+        // x^15 * x = x^5 + x^3 + x^2 + 1
+        let x = Wire::GF16 { p: 0b101101, elts: vec![1 << 15] };
+        assert_eq!(x.cmul(2), Wire::GF16 { p: 0b101101, elts: vec![0b101101] });
+    }
+
+    // irreducible polynomials over GF(3), GF(5) and GF(7)
+    const GFPK_MODULI: [Modulus; 7] = [
+        Modulus::GFpk { p: 3, k: 2, poly: 1 },
+        Modulus::GFpk { p: 3, k: 3, poly: 7 },
+        Modulus::GFpk { p: 3, k: 5, poly: 14 },
+        Modulus::GFpk { p: 3, k: 10, poly: 19 },
+        Modulus::GFpk { p: 5, k: 2, poly: 2 },
+        Modulus::GFpk { p: 5, k: 3, poly: 6 },
+        Modulus::GFpk { p: 7, k: 2, poly: 1 },
+    ];
+
+    #[test]
+    fn packing_GFpk() {
+        let rng = &mut thread_rng();
+        for p in &GFPK_MODULI {
+            for _ in 0..1000 {
+                let w = Wire::rand(rng, p);
+                assert_eq!(w, Wire::from_block(w.as_block(), p));
+            }
+        }
+    }
+
+    #[test]
+    fn hash_GFpk() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let p = GFPK_MODULI.choose(&mut rng).unwrap();
+            let x = Wire::rand(&mut rng, p);
+            let y = x.hashback(Block::from(1u128), p);
+            assert_ne!(x, y);
+            match y {
+                Wire::GFpk { elts, .. } => {
+                    assert!(!elts.iter().all(|&y| y == 0));
+                    assert!(elts.iter().all(|&y| y < p.size()));
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn cmul_GFpk() {
+        let mut rng = thread_rng();
+        for _ in 0..100 {
+            let p = GFPK_MODULI.choose(&mut rng).unwrap();
+            let x = Wire::rand(&mut rng, p);
+            let a = rng.gen_u16() % p.size();
+            let b = rng.gen_u16() % p.size();
+            let (ab, a_b) = match *p {
+                Modulus::GFpk { p, k, poly } => {
+                    (util::gfpk_mul(a, b, p, k, poly), util::gfpk_add(a, b, p, k))
+                }
+                _ => unreachable!(),
+            };
+            assert_eq!(x.cmul(a).cmul(b), x.cmul(ab));
+            assert_eq!(x.cmul(a).plus(&x.cmul(b)), x.cmul(a_b));
+            assert_eq!(x.cmul(1), x);
+            assert_eq!(x.cmul(0), Wire::zero(p));
+        }
+        // x^2 * x = x^3 = x + 2 modulo x^3 + 2x + 1
+        let x = Wire::GFpk { p: 3, k: 3, poly: 7, elts: vec![9] };
+        assert_eq!(x.cmul(3), Wire::GFpk { p: 3, k: 3, poly: 7, elts: vec![5] });
+    }
+
+    #[test]
+    fn negation_GFpk() {
+        let rng = &mut thread_rng();
+        for p in &GFPK_MODULI {
+            let x = Wire::rand(rng, p);
+            assert_eq!(x.plus(&x.negate()), Wire::zero(p));
+            assert_eq!(x.minus(&x), Wire::zero(p));
+            assert_eq!(x.negate().negate(), x);
+        }
+    }
+
     #[test]
     fn negation() {
         let ref mut rng = thread_rng();
@@ -1046,6 +1707,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn arithmetic_ZqBig() {
+        let mut rng = thread_rng();
+        for &q in &[65537, 4294967291] {
+            let m = Modulus::ZqBig { q };
+            for _ in 0..1000 {
+                let x = Wire::rand(&mut rng, &m);
+                let y = Wire::rand(&mut rng, &m);
+                assert_eq!(x.cmul_big(0), Wire::zero(&m));
+                assert_eq!(x.cmul_big(q), Wire::zero(&m));
+                assert_eq!(x.plus(&x).plus(&x), x.cmul_big(3));
+                assert_eq!(x.cmul_big(q - 1), x.negate());
+                assert_eq!(x.plus(&x.negate()), Wire::zero(&m));
+                assert_eq!(x.minus(&y), x.plus(&y.negate()));
+                assert_eq!(
+                    x.plus(&y).color_big(),
+                    (x.color_big() + y.color_big()) % q
+                );
+            }
+        }
+    }
+
     #[test]
     fn basic_arithmeticGF() {
         let mut rng = thread_rng();
@@ -1090,8 +1773,9 @@ mod tests {
             let line = line.expect("Unable to read line");
             let p: Vec<&str> = line.split(' ').collect();
             let (x, y, z) = p.into_iter().map(|s| s.parse::<u16>().unwrap()).collect_tuple().unwrap();
-            let w = Wire::GF4 { p: (X4_X_1), elts: vec![x]};
-            assert_eq!(w.cmul(y), Wire::GF4 { p: (X4_X_1), elts: vec![z] }); 
+            let q = Modulus::GF4 { p: X4_X_1 };
+            let w = Wire::from_block(Block::from(x as u128), &q);
+            assert_eq!(w.cmul(y), Wire::from_block(Block::from(z as u128), &q));
         }
     }
 
@@ -1108,8 +1792,9 @@ mod tests {
             let line = line.expect("Unable to read line");
             let p: Vec<&str> = line.split(' ').collect();
             let (x, y, z) = p.into_iter().map(|s| s.parse::<u16>().unwrap()).collect_tuple().unwrap();
-            let w = Wire::GF4 { p: (X4_X3_1), elts: vec![x]};
-            assert_eq!(w.cmul(y), Wire::GF4 { p: (X4_X3_1), elts: vec![z] }); 
+            let q = Modulus::GF4 { p: X4_X3_1 };
+            let w = Wire::from_block(Block::from(x as u128), &q);
+            assert_eq!(w.cmul(y), Wire::from_block(Block::from(z as u128), &q));
         }
     }
 
@@ -1126,8 +1811,9 @@ mod tests {
             let line = line.expect("Unable to read line");
             let p: Vec<&str> = line.split(' ').collect();
             let (x, y, z) = p.into_iter().map(|s| s.parse::<u16>().unwrap()).collect_tuple().unwrap();
-            let w = Wire::GF4 { p: (X4_X_1), elts: vec![x]};
-            assert_eq!(w.cmul(y), Wire::GF4 { p: (X4_X_1), elts: vec![z] }); 
+            let q = Modulus::GF4 { p: X4_X_1 };
+            let w = Wire::from_block(Block::from(x as u128), &q);
+            assert_eq!(w.cmul(y), Wire::from_block(Block::from(z as u128), &q));
         }
     }
 
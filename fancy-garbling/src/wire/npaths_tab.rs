@@ -7,6 +7,7 @@
 #![allow(clippy::unreadable_literal)]
 
 use crate::util;
+use alloc::vec::Vec;
 
 pub fn lookup(q: u16) -> Vec<u128> {
     match q {
@@ -0,0 +1,188 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Element-wise arithmetic on the `u16` elements of GF(2^k) wire-labels.
+//!
+//! With the `simd` feature, AVX2 (on x86-64) or NEON (on AArch64) handles as
+//! much of each slice as fills whole vectors, and a scalar loop the rest.
+//! Without it, or on other targets, everything is scalar.
+
+use crate::util;
+
+/// Add `ys` into `xs` element-wise, i.e. XOR them.
+pub fn xor_eq(xs: &mut [u16], ys: &[u16]) {
+    debug_assert_eq!(xs.len(), ys.len());
+    let done = vector::xor_eq(xs, ys);
+    xs[done..]
+        .iter_mut()
+        .zip(ys[done..].iter())
+        .for_each(|(x, &y)| *x ^= y);
+}
+
+/// Multiply every element of `xs` by the constant `c` in GF(2^k) modulo `p`,
+/// for `k <= 8`.
+pub fn cmul_eq(xs: &mut [u16], c: u16, p: u16, k: u8) {
+    debug_assert!(k <= 8);
+    if !vector::ENABLED {
+        return util::field_cmul_eq(xs, c, p, k);
+    }
+    let (lo, hi) = nibble_tables(c, p, k);
+    let done = vector::cmul_eq(xs, &lo, &hi);
+    // the shuffles are constant-time, but scalar lookups would not be
+    util::field_cmul_eq(&mut xs[done..], c, p, k);
+}
+
+// Multiplication by `c` is linear over GF(2), so `c * x` is the XOR of `c` times
+// the low and the high nibble of `x`. Returns the tables of both products.
+fn nibble_tables(c: u16, p: u16, k: u8) -> ([u8; 16], [u8; 16]) {
+    // basis[i] = c * X^i
+    let mut basis = [0u16; 8];
+    let mut x = util::field_mul_ct(c, 1, p, k);
+    for b in basis.iter_mut().take(k as usize) {
+        *b = x;
+        x = util::field_mul_ct(x, 2, p, k);
+    }
+    let (mut lo, mut hi) = ([0u8; 16], [0u8; 16]);
+    for i in 1..16usize {
+        let t = i.trailing_zeros() as usize;
+        lo[i] = lo[i & (i - 1)] ^ basis[t] as u8;
+        hi[i] = hi[i & (i - 1)] ^ basis[t + 4] as u8;
+    }
+    (lo, hi)
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+mod vector {
+    use core::arch::x86_64::*;
+
+    pub const ENABLED: bool = true;
+
+    pub fn xor_eq(xs: &mut [u16], ys: &[u16]) -> usize {
+        let n = xs.len() / 16 * 16;
+        for (x, y) in xs[..n].chunks_exact_mut(16).zip(ys.chunks_exact(16)) {
+            // Safety: both chunks hold exactly 256 bits and AVX2 is enabled.
+            unsafe {
+                let a = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+                let b = _mm256_loadu_si256(y.as_ptr() as *const __m256i);
+                _mm256_storeu_si256(x.as_mut_ptr() as *mut __m256i, _mm256_xor_si256(a, b));
+            }
+        }
+        n
+    }
+
+    pub fn cmul_eq(xs: &mut [u16], lo: &[u8; 16], hi: &[u8; 16]) -> usize {
+        let n = xs.len() / 16 * 16;
+        // Safety: the tables hold 128 bits, every chunk 256 bits, and AVX2 is
+        // enabled. Elements are below 2^8, so the high byte of every lane only
+        // ever looks up the zero entry of the tables.
+        unsafe {
+            let lo = _mm256_broadcastsi128_si256(_mm_loadu_si128(lo.as_ptr() as *const __m128i));
+            let hi = _mm256_broadcastsi128_si256(_mm_loadu_si128(hi.as_ptr() as *const __m128i));
+            let mask = _mm256_set1_epi8(0x0f);
+            for x in xs[..n].chunks_exact_mut(16) {
+                let v = _mm256_loadu_si256(x.as_ptr() as *const __m256i);
+                let l = _mm256_and_si256(v, mask);
+                let h = _mm256_and_si256(_mm256_srli_epi16(v, 4), mask);
+                let z = _mm256_xor_si256(_mm256_shuffle_epi8(lo, l), _mm256_shuffle_epi8(hi, h));
+                _mm256_storeu_si256(x.as_mut_ptr() as *mut __m256i, z);
+            }
+        }
+        n
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "aarch64", target_feature = "neon"))]
+mod vector {
+    use core::arch::aarch64::*;
+
+    pub const ENABLED: bool = true;
+
+    pub fn xor_eq(xs: &mut [u16], ys: &[u16]) -> usize {
+        let n = xs.len() / 8 * 8;
+        for (x, y) in xs[..n].chunks_exact_mut(8).zip(ys.chunks_exact(8)) {
+            // Safety: both chunks hold exactly 128 bits and NEON is enabled.
+            unsafe {
+                let z = veorq_u16(vld1q_u16(x.as_ptr()), vld1q_u16(y.as_ptr()));
+                vst1q_u16(x.as_mut_ptr(), z);
+            }
+        }
+        n
+    }
+
+    pub fn cmul_eq(xs: &mut [u16], lo: &[u8; 16], hi: &[u8; 16]) -> usize {
+        let n = xs.len() / 8 * 8;
+        // Safety: the tables and every chunk hold 128 bits, and NEON is enabled.
+        // Elements are below 2^8, so the high byte of every lane only ever looks
+        // up the zero entry of the tables.
+        unsafe {
+            let lo = vld1q_u8(lo.as_ptr());
+            let hi = vld1q_u8(hi.as_ptr());
+            let mask = vdupq_n_u8(0x0f);
+            for x in xs[..n].chunks_exact_mut(8) {
+                let v = vld1q_u16(x.as_ptr());
+                let l = vandq_u8(vreinterpretq_u8_u16(v), mask);
+                let h = vandq_u8(vreinterpretq_u8_u16(vshrq_n_u16::<4>(v)), mask);
+                let z = veorq_u8(vqtbl1q_u8(lo, l), vqtbl1q_u8(hi, h));
+                vst1q_u16(x.as_mut_ptr(), vreinterpretq_u16_u8(z));
+            }
+        }
+        n
+    }
+}
+
+#[cfg(not(any(
+    all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"),
+    all(feature = "simd", target_arch = "aarch64", target_feature = "neon")
+)))]
+mod vector {
+    pub const ENABLED: bool = false;
+
+    pub fn xor_eq(_: &mut [u16], _: &[u16]) -> usize {
+        0
+    }
+
+    pub fn cmul_eq(_: &mut [u16], _: &[u8; 16], _: &[u8; 16]) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util::RngExt, Modulus};
+    use rand::thread_rng;
+
+    #[test]
+    fn xor_matches_scalar() {
+        let mut rng = thread_rng();
+        // lengths that do and do not fill whole vectors
+        for &n in [8, 16, 32, 37].iter() {
+            let mut xs = (0..n).map(|_| rng.gen_u16()).collect::<Vec<_>>();
+            let ys = (0..n).map(|_| rng.gen_u16()).collect::<Vec<_>>();
+            let should_be = xs.iter().zip(ys.iter()).map(|(x, y)| x ^ y).collect::<Vec<_>>();
+            xor_eq(&mut xs, &ys);
+            assert_eq!(xs, should_be);
+        }
+    }
+
+    #[test]
+    fn cmul_matches_scalar() {
+        let mut rng = thread_rng();
+        let moduli = Modulus::GF4_MODULI.iter().chain(Modulus::GF8_MODULI.iter());
+        for q in moduli {
+            let (p, k) = (q.value(), q.bit_length() as u8);
+            for &n in [16, 32, 37].iter() {
+                let c = rng.gen_u16() % q.size();
+                let xs = (0..n).map(|_| rng.gen_u16() % q.size()).collect::<Vec<_>>();
+                let mut ys = xs.clone();
+                cmul_eq(&mut ys, c, p, k);
+                for (x, y) in xs.iter().zip(ys.iter()) {
+                    assert_eq!(*y, util::field_mul_ct(*x, c, p, k), "p = {}", p);
+                }
+            }
+        }
+    }
+}
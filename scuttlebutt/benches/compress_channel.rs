@@ -0,0 +1,52 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::RngCore;
+use scuttlebutt::{AbstractChannel, Channel, CompressChannel};
+use std::{
+    io::Sink,
+    time::Duration,
+};
+
+// A channel which discards everything written to it, paired with a peer which
+// always agrees to compress.
+fn sink(compress: bool) -> CompressChannel<Channel<&'static [u8], Sink>> {
+    let peer: &'static [u8] = &[1];
+    CompressChannel::negotiate(Channel::new(peer, std::io::sink()), compress).unwrap()
+}
+
+fn bench_compress_channel(c: &mut Criterion) {
+    let mut random = vec![0u8; 1 << 20];
+    rand::thread_rng().fill_bytes(&mut random);
+    let redundant = (0..1 << 20).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+    for &(name, compress) in [("raw", false), ("lz4", true)].iter() {
+        let data = random.clone();
+        c.bench_function(&format!("CompressChannel::write ({}, 1MiB random)", name), move |b| {
+            let mut channel = sink(compress);
+            b.iter(|| {
+                channel.write_bytes(&data).unwrap();
+                channel.flush().unwrap();
+            });
+        });
+        let data = redundant.clone();
+        c.bench_function(&format!("CompressChannel::write ({}, 1MiB redundant)", name), move |b| {
+            let mut channel = sink(compress);
+            b.iter(|| {
+                channel.write_bytes(&data).unwrap();
+                channel.flush().unwrap();
+            });
+        });
+    }
+}
+
+criterion_group! {
+    name = compress_channel;
+    config = Criterion::default().warm_up_time(Duration::from_millis(100));
+    targets = bench_compress_channel
+}
+criterion_main!(compress_channel);
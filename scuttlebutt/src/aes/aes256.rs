@@ -96,7 +96,7 @@ fn expand(key: &[u8; 32]) -> [__m128i; 15] {
         let mut enc_keys: [__m128i; 15] = mem::MaybeUninit::uninit().assume_init();
 
         #[allow(clippy::useless_transmute)] // XXX remove if possible!
-        let kp = std::mem::transmute(key);
+        let kp = core::mem::transmute(key);
         // let kp = key.as_ptr() as *const __m128i;
         let k1 = _mm_loadu_si128(kp);
         let k2 = _mm_loadu_si128(kp.offset(1));
@@ -156,7 +156,7 @@ mod tests {
         let k1: u128 = 0x81777D85F0AE732BBE71CA1510EB3D60;
         let k2: u128 = 0xF4DF1409A310982DD708613B072C351F;
         let key = [k1, k2];
-        let key = unsafe { std::mem::transmute(key) };
+        let key = unsafe { core::mem::transmute(key) };
         let cipher = Aes256::new(&key);
         let pt = Block::from(0x2A179373117E3DE9969F402EE2BEC16B);
         let ct = cipher.encrypt(pt);
@@ -4,13 +4,23 @@
 // Copyright © 2019 Galois, Inc.
 // See LICENSE for licensing information.
 
+#[cfg(feature = "compression")]
+mod compress_channel;
 mod hash_channel;
+mod pipelined_channel;
 mod sync_channel;
 mod track_channel;
 #[cfg(unix)]
 mod unix_channel;
 
+#[cfg(feature = "compression")]
+pub use compress_channel::{CompressChannel, DEFAULT_CHUNK_SIZE};
 pub use hash_channel::HashChannel;
+pub use pipelined_channel::{
+    PipelinedChannel,
+    DEFAULT_PIPELINE_CHUNK_SIZE,
+    DEFAULT_PIPELINE_DEPTH,
+};
 pub use sync_channel::SyncChannel;
 pub use track_channel::TrackChannel;
 
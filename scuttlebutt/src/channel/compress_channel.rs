@@ -0,0 +1,311 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use crate::AbstractChannel;
+use std::{
+    io::{Error, ErrorKind, Result},
+    sync::{Arc, Mutex},
+};
+
+/// The default number of bytes buffered before a frame is sent.
+pub const DEFAULT_CHUNK_SIZE: usize = 1 << 16;
+
+// frame kinds
+const RAW: u8 = 0;
+const LZ4: u8 = 1;
+
+// kind, uncompressed length, payload length, CRC32 of the uncompressed data
+const HEADER_SIZE: usize = 1 + 4 + 4 + 4;
+
+/// A channel which transparently compresses the data sent over an underlying
+/// channel with LZ4.
+///
+/// Writes are buffered and sent in frames of at most `chunk_size` bytes, either
+/// when the buffer fills up or on `flush`. Every frame carries a CRC32 of its
+/// uncompressed contents, and frames which LZ4 does not shrink are sent as is.
+/// Whether compression is used at all is agreed on by both parties in
+/// `CompressChannel::negotiate`; if either declines, the channel passes data
+/// through untouched.
+pub struct CompressChannel<C> {
+    writer: Arc<Mutex<FrameWriter<C>>>,
+    reader: Arc<Mutex<FrameReader<C>>>,
+    enabled: bool,
+}
+
+struct FrameWriter<C> {
+    channel: C,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+    nbytes_in: usize,
+    nbytes_out: usize,
+}
+
+struct FrameReader<C> {
+    channel: C,
+    chunk_size: usize,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl<C: AbstractChannel> CompressChannel<C> {
+    /// Wrap `channel`, enabling compression if `compress` is set and the other
+    /// party, calling this concurrently, also asks for it.
+    pub fn negotiate(mut channel: C, compress: bool) -> Result<Self> {
+        channel.write_bool(compress)?;
+        channel.flush()?;
+        let peer = channel.read_bool()?;
+        let enabled = compress && peer;
+        let writer = FrameWriter {
+            channel: channel.clone(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            buffer: Vec::new(),
+            nbytes_in: 0,
+            nbytes_out: 0,
+        };
+        let reader = FrameReader {
+            channel,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            buffer: Vec::new(),
+            pos: 0,
+        };
+        Ok(Self {
+            writer: Arc::new(Mutex::new(writer)),
+            reader: Arc::new(Mutex::new(reader)),
+            enabled,
+        })
+    }
+
+    /// Whether compression was agreed on.
+    pub fn is_compressed(&self) -> bool {
+        self.enabled
+    }
+
+    /// Set the number of bytes buffered before a frame is sent. Larger chunks
+    /// compress better but delay data until the next `flush`.
+    ///
+    /// Frames received from the other party are rejected if they are larger
+    /// than this, so both parties must use the same chunk size.
+    pub fn set_chunk_size(&mut self, chunk_size: usize) {
+        assert!(chunk_size > 0 && chunk_size <= u32::MAX as usize);
+        self.writer.lock().unwrap().chunk_size = chunk_size;
+        self.reader.lock().unwrap().chunk_size = chunk_size;
+    }
+
+    /// The number of bytes written to this channel.
+    pub fn bytes_written(&self) -> usize {
+        self.writer.lock().unwrap().nbytes_in
+    }
+
+    /// The number of bytes this channel wrote to the underlying channel,
+    /// including frame headers.
+    pub fn bytes_sent(&self) -> usize {
+        self.writer.lock().unwrap().nbytes_out
+    }
+}
+
+impl<C: AbstractChannel> FrameWriter<C> {
+    fn send_frame(&mut self, len: usize) -> Result<()> {
+        let data = &self.buffer[..len];
+        let compressed = lz4_flex::block::compress(data);
+        let (kind, payload) = if compressed.len() < data.len() {
+            (LZ4, &compressed[..])
+        } else {
+            (RAW, data)
+        };
+        let mut header = [0u8; HEADER_SIZE];
+        header[0] = kind;
+        header[1..5].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        header[5..9].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+        header[9..13].copy_from_slice(&crc32fast::hash(data).to_le_bytes());
+        self.channel.write_bytes(&header)?;
+        self.channel.write_bytes(payload)?;
+        self.nbytes_out += HEADER_SIZE + payload.len();
+        self.buffer.drain(..len);
+        Ok(())
+    }
+}
+
+impl<C: AbstractChannel> FrameReader<C> {
+    fn recv_frame(&mut self) -> Result<()> {
+        let mut header = [0u8; HEADER_SIZE];
+        self.channel.read_bytes(&mut header)?;
+        let word = |i: usize| {
+            let mut w = [0u8; 4];
+            w.copy_from_slice(&header[i..i + 4]);
+            u32::from_le_bytes(w)
+        };
+        let (len, payload_len, crc) = (word(1) as usize, word(5) as usize, word(9));
+        // check the lengths before allocating anything the peer asks for
+        if len > self.chunk_size
+            || payload_len > lz4_flex::block::get_maximum_output_size(self.chunk_size)
+        {
+            return Err(Error::new(ErrorKind::InvalidData, "frame too large"));
+        }
+        let payload = self.channel.read_vec(payload_len)?;
+        let data = match header[0] {
+            RAW => payload,
+            LZ4 => lz4_flex::block::decompress(&payload, len)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?,
+            kind => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unknown frame kind {}", kind),
+                ))
+            }
+        };
+        if data.len() != len || crc32fast::hash(&data) != crc {
+            return Err(Error::new(ErrorKind::InvalidData, "frame checksum mismatch"));
+        }
+        self.buffer = data;
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for CompressChannel<C> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut w = self.writer.lock().unwrap();
+        w.nbytes_in += bytes.len();
+        if !self.enabled {
+            w.nbytes_out += bytes.len();
+            return w.channel.write_bytes(bytes);
+        }
+        w.buffer.extend_from_slice(bytes);
+        while w.buffer.len() >= w.chunk_size {
+            let n = w.chunk_size;
+            w.send_frame(n)?;
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        let mut r = self.reader.lock().unwrap();
+        if !self.enabled {
+            return r.channel.read_bytes(bytes);
+        }
+        let mut filled = 0;
+        while filled < bytes.len() {
+            if r.pos == r.buffer.len() {
+                r.recv_frame()?;
+            }
+            let n = (bytes.len() - filled).min(r.buffer.len() - r.pos);
+            bytes[filled..filled + n].copy_from_slice(&r.buffer[r.pos..r.pos + n]);
+            r.pos += n;
+            filled += n;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let mut w = self.writer.lock().unwrap();
+        if !w.buffer.is_empty() {
+            let n = w.buffer.len();
+            w.send_frame(n)?;
+        }
+        w.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            reader: self.reader.clone(),
+            enabled: self.enabled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{unix_channel_pair, UnixChannel};
+    use rand::{Rng, RngCore};
+
+    fn pair(a: bool, b: bool) -> (CompressChannel<UnixChannel>, CompressChannel<UnixChannel>) {
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || CompressChannel::negotiate(sender, a).unwrap());
+        let receiver = CompressChannel::negotiate(receiver, b).unwrap();
+        (handle.join().unwrap(), receiver)
+    }
+
+    fn roundtrip(a: bool, b: bool, data: Vec<u8>) -> CompressChannel<UnixChannel> {
+        let (mut sender, mut receiver) = pair(a, b);
+        assert_eq!(sender.is_compressed(), a && b);
+        sender.set_chunk_size(1000);
+        let expected = data.clone();
+        let handle = std::thread::spawn(move || {
+            // write in uneven pieces to cross frame boundaries
+            for piece in data.chunks(333) {
+                sender.write_bytes(piece).unwrap();
+            }
+            sender.flush().unwrap();
+            sender
+        });
+        let mut got = vec![0u8; expected.len()];
+        for piece in got.chunks_mut(777) {
+            receiver.read_bytes(piece).unwrap();
+        }
+        assert_eq!(got, expected);
+        handle.join().unwrap()
+    }
+
+    #[test]
+    fn compresses_redundant_data() {
+        let data = (0..10_000).map(|i| (i % 7) as u8).collect::<Vec<_>>();
+        let sender = roundtrip(true, true, data);
+        assert_eq!(sender.bytes_written(), 10_000);
+        assert!(sender.bytes_sent() < 2_000);
+    }
+
+    #[test]
+    fn falls_back_on_random_data() {
+        let mut data = vec![0u8; 10_000];
+        rand::thread_rng().fill_bytes(&mut data);
+        let sender = roundtrip(true, true, data);
+        assert_eq!(sender.bytes_sent(), 10_000 + 10 * HEADER_SIZE);
+    }
+
+    #[test]
+    fn passthrough_unless_both_agree() {
+        let data = vec![0u8; 5_000];
+        assert_eq!(roundtrip(true, false, data.clone()).bytes_sent(), 5_000);
+        assert_eq!(roundtrip(false, true, data).bytes_sent(), 5_000);
+    }
+
+    #[test]
+    fn detects_corruption() {
+        let (sender, mut receiver) = pair(true, true);
+        let mut raw = sender.writer.lock().unwrap().channel.clone();
+        let data = rand::thread_rng().gen::<[u8; 16]>();
+        let mut header = [0u8; HEADER_SIZE];
+        header[0] = RAW;
+        header[1..5].copy_from_slice(&16u32.to_le_bytes());
+        header[5..9].copy_from_slice(&16u32.to_le_bytes());
+        header[9..13].copy_from_slice(&(crc32fast::hash(&data) ^ 1).to_le_bytes());
+        raw.write_bytes(&header).unwrap();
+        raw.write_bytes(&data).unwrap();
+        raw.flush().unwrap();
+        let mut buf = [0u8; 16];
+        let err = receiver.read_bytes(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        for (len, payload_len) in [(u32::MAX, 16), (16, u32::MAX)] {
+            let (sender, mut receiver) = pair(true, true);
+            let mut raw = sender.writer.lock().unwrap().channel.clone();
+            let mut header = [0u8; HEADER_SIZE];
+            header[0] = LZ4;
+            header[1..5].copy_from_slice(&len.to_le_bytes());
+            header[5..9].copy_from_slice(&payload_len.to_le_bytes());
+            raw.write_bytes(&header).unwrap();
+            raw.flush().unwrap();
+            let mut buf = [0u8; 16];
+            let err = receiver.read_bytes(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use crate::AbstractChannel;
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+/// The default number of bytes collected before they are handed to the writer
+/// thread.
+pub const DEFAULT_PIPELINE_CHUNK_SIZE: usize = 1 << 16;
+
+/// The default number of chunks which may wait for the writer thread before
+/// writes block.
+pub const DEFAULT_PIPELINE_DEPTH: usize = 4;
+
+enum Message {
+    Write(Vec<u8>),
+    Flush(mpsc::Sender<Result<()>>),
+}
+
+/// A channel which writes from a background thread, so that computing the
+/// data to send overlaps with sending it.
+///
+/// Writes are collected into chunks of `chunk_size` bytes, and full chunks are
+/// handed to a thread owning the writer. Once `depth` chunks are waiting,
+/// writing blocks until the thread catches up. `flush` hands over the partial
+/// chunk and waits until everything has been written and flushed. An error of
+/// the writer thread is returned by the next write or flush.
+pub struct PipelinedChannel<R> {
+    reader: Arc<Mutex<R>>,
+    writer: Arc<Mutex<PipelinedWriter>>,
+}
+
+struct PipelinedWriter {
+    chunk_size: usize,
+    buffer: Vec<u8>,
+    sender: Option<mpsc::SyncSender<Message>>,
+    handle: Option<JoinHandle<Result<()>>>,
+}
+
+impl<R: Read> PipelinedChannel<R> {
+    /// Make a new channel from a `reader` and a `writer` which is moved to the
+    /// writer thread, with the default chunk size and depth.
+    pub fn new<W: Write + Send + 'static>(reader: R, writer: W) -> Self {
+        Self::with_capacity(
+            reader,
+            writer,
+            DEFAULT_PIPELINE_CHUNK_SIZE,
+            DEFAULT_PIPELINE_DEPTH,
+        )
+    }
+
+    /// Make a new channel collecting writes into chunks of `chunk_size` bytes,
+    /// of which at most `depth` wait for the writer thread.
+    pub fn with_capacity<W: Write + Send + 'static>(
+        reader: R,
+        mut writer: W,
+        chunk_size: usize,
+        depth: usize,
+    ) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Message>(depth);
+        let handle = thread::spawn(move || {
+            for message in receiver.iter() {
+                match message {
+                    Message::Write(bytes) => writer.write_all(&bytes)?,
+                    Message::Flush(ack) => {
+                        let _ = ack.send(writer.flush());
+                    }
+                }
+            }
+            writer.flush()
+        });
+        let writer = PipelinedWriter {
+            chunk_size: chunk_size.max(1),
+            buffer: Vec::with_capacity(chunk_size),
+            sender: Some(sender),
+            handle: Some(handle),
+        };
+        Self {
+            reader: Arc::new(Mutex::new(reader)),
+            writer: Arc::new(Mutex::new(writer)),
+        }
+    }
+}
+
+impl PipelinedWriter {
+    /// Hand `message` to the writer thread, returning its error if it stopped.
+    fn send(&mut self, message: Message) -> Result<()> {
+        let sent = match self.sender {
+            Some(ref sender) => sender.send(message).is_ok(),
+            None => false,
+        };
+        if sent {
+            Ok(())
+        } else {
+            Err(self.stop())
+        }
+    }
+
+    /// Stop the writer thread, returning why it stopped.
+    fn stop(&mut self) -> Error {
+        self.sender = None;
+        match self.handle.take().map(|handle| handle.join()) {
+            Some(Ok(Err(e))) => e,
+            _ => Error::new(ErrorKind::BrokenPipe, "pipelined writer thread stopped"),
+        }
+    }
+
+    fn write(&mut self, mut bytes: &[u8]) -> Result<()> {
+        while !bytes.is_empty() {
+            let n = (self.chunk_size - self.buffer.len()).min(bytes.len());
+            self.buffer.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if self.buffer.len() == self.chunk_size {
+                let chunk =
+                    std::mem::replace(&mut self.buffer, Vec::with_capacity(self.chunk_size));
+                self.send(Message::Write(chunk))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            let chunk = std::mem::take(&mut self.buffer);
+            self.send(Message::Write(chunk))?;
+        }
+        let (ack, done) = mpsc::channel();
+        self.send(Message::Flush(ack))?;
+        match done.recv() {
+            Ok(result) => result,
+            Err(_) => Err(self.stop()),
+        }
+    }
+}
+
+impl Drop for PipelinedWriter {
+    fn drop(&mut self) {
+        if !self.buffer.is_empty() {
+            let chunk = std::mem::take(&mut self.buffer);
+            let _ = self.send(Message::Write(chunk));
+        }
+        self.sender = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl<R: Read> AbstractChannel for PipelinedChannel<R> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer.lock().unwrap().write(bytes)
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        self.reader.lock().unwrap().read_exact(bytes)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            reader: self.reader.clone(),
+            writer: self.writer.clone(),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn roundtrip() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let mut data = vec![0u8; 100_000];
+        rand::thread_rng().fill_bytes(&mut data);
+        let expected = data.clone();
+        let handle = std::thread::spawn(move || {
+            let mut sender =
+                PipelinedChannel::with_capacity(a.try_clone().unwrap(), a, 1000, 2);
+            for piece in data.chunks(333) {
+                sender.write_bytes(piece).unwrap();
+            }
+            sender.flush().unwrap();
+            // the reader still works after writing
+            assert!(sender.read_bool().unwrap());
+        });
+        let mut receiver = crate::SymChannel::new(b);
+        let mut got = vec![0u8; expected.len()];
+        receiver.read_bytes(&mut got).unwrap();
+        assert_eq!(got, expected);
+        receiver.write_bool(true).unwrap();
+        receiver.flush().unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn writer_errors_are_returned() {
+        struct Failing;
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> Result<usize> {
+                Err(Error::new(ErrorKind::Other, "disk on fire"))
+            }
+            fn flush(&mut self) -> Result<()> {
+                Ok(())
+            }
+        }
+        let mut channel = PipelinedChannel::with_capacity(std::io::empty(), Failing, 16, 1);
+        channel.write_bytes(&[0; 16]).unwrap();
+        let e = channel.flush().unwrap_err();
+        assert_eq!(e.to_string(), "disk on fire");
+    }
+}
@@ -5,6 +5,7 @@
 // See LICENSE for licensing information.
 
 #![allow(clippy::many_single_char_names)]
+#![cfg_attr(not(feature = "std"), no_std)]
 #![cfg_attr(feature = "nightly", feature(stdsimd))]
 #![cfg_attr(feature = "nightly", feature(test))]
 #![cfg_attr(feature = "nightly", feature(external_doc))]
@@ -12,17 +13,24 @@
 #![cfg_attr(feature = "nightly", deny(missing_docs))]
 
 //!
+//! Without the default `std` feature, only blocks, AES, and the AES-based hash
+//! and random number generator are available, for `no_std` targets.
 
 mod aes;
 mod block;
 mod block512;
+#[cfg(feature = "std")]
 pub mod bloomfilter;
 /// Module for encapsulating communication channels for `swanky`.
+#[cfg(feature = "std")]
 pub mod channel;
+#[cfg(feature = "std")]
 pub mod cointoss;
+#[cfg(feature = "std")]
 pub mod commitment;
 mod hash_aes;
 mod rand_aes;
+#[cfg(feature = "std")]
 pub mod utils;
 
 pub use crate::{
@@ -32,12 +40,25 @@ pub use crate::{
     },
     block::Block,
     block512::Block512,
-    channel::{AbstractChannel, Channel, HashChannel, SyncChannel, TrackChannel, SymChannel},
     hash_aes::{AesHash, AES_HASH},
     rand_aes::AesRng,
 };
 
-#[cfg(unix)]
+#[cfg(feature = "std")]
+pub use crate::channel::{
+    AbstractChannel,
+    Channel,
+    HashChannel,
+    PipelinedChannel,
+    SymChannel,
+    SyncChannel,
+    TrackChannel,
+};
+
+#[cfg(all(feature = "std", feature = "compression"))]
+pub use crate::channel::CompressChannel;
+
+#[cfg(all(feature = "std", unix))]
 pub use crate::channel::{
     track_unix_channel_pair,
     unix_channel_pair,
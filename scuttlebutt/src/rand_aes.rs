@@ -53,7 +53,9 @@ impl CryptoRng for AesRng {}
 
 impl AesRng {
     /// Create a new random number generator using a random seed from
-    /// `rand::random`.
+    /// `rand::random`. Without `std` there is no source of randomness, and
+    /// generators must be seeded with `from_seed`.
+    #[cfg(feature = "std")]
     #[inline]
     pub fn new() -> Self {
         let seed = rand::random::<Block>();
@@ -68,6 +70,7 @@ impl AesRng {
     }
 }
 
+#[cfg(feature = "std")]
 impl Default for AesRng {
     #[inline]
     fn default() -> Self {
@@ -82,8 +85,8 @@ pub struct AesRngCore {
     state: u128,
 }
 
-impl std::fmt::Debug for AesRngCore {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Debug for AesRngCore {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "AesRngCore {{}}")
     }
 }
@@ -99,7 +102,7 @@ impl BlockRngCore for AesRngCore {
     fn generate(&mut self, results: &mut Self::Results) {
         // We can't just cast this because the alignment of [u32; 32] may not
         // match that of [Block; 8].
-        let mut ms: [Block; 8] = unsafe { std::mem::transmute(*results) };
+        let mut ms: [Block; 8] = unsafe { core::mem::transmute(*results) };
         ms[0] = Block::from(self.state);
         self.state += 1;
         ms[1] = Block::from(self.state);